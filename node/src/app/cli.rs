@@ -4,24 +4,43 @@
 
 pub mod arglang;
 
-use std::{env, fs, path::PathBuf, str::FromStr};
+use std::{
+    env, fs,
+    path::{Path, PathBuf},
+    str::FromStr,
+    time::Duration,
+};
 
 use anyhow::{self, bail, Context};
 use rand::SeedableRng;
 use rand_chacha::ChaCha20Rng;
 use regex::Regex;
+use serde::de::DeserializeOwned;
 use structopt::StructOpt;
+use tokio::signal::unix::{self, SignalKind};
 use toml::{value::Table, Value};
-use tracing::{info, trace};
+use tracing::{info, trace, warn};
 
 use crate::config;
 use casper_node::{
     logging,
-    reactor::{initializer, joiner, validator, Runner},
-    utils::WithDir,
+    reactor::{initializer, joiner, validator, Reactor, Runner},
+    toggle_message_tracing,
+    types::ShutdownReason,
+    utils::{RngState, WithDir},
 };
 use prometheus::Registry;
 
+/// Maximum time to spend processing already queued events on shutdown.
+const SHUTDOWN_DRAIN_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Prefix of the environment variables overriding individual configuration file entries.
+///
+/// A variable named `CASPER_NODE__<SECTION>__<KEY>` overrides the entry `<KEY>` in section
+/// `<SECTION>`, with both names converted to lowercase.  Values use the same syntax as
+/// command-line overrides.
+const CONFIG_ENV_PREFIX: &str = "CASPER_NODE__";
+
 // Note: The docstring on `Cli` is the help shown when calling the binary with `--help`.
 #[derive(Debug, StructOpt)]
 /// Casper blockchain node.
@@ -44,6 +63,16 @@ pub enum Cli {
         /// Overrides and extensions for configuration file entries in the form
         /// <SECTION>.<KEY>=<VALUE>.  For example, '-C=node.chainspec_config_path=chainspec.toml'
         config_ext: Vec<ConfigExt>,
+
+        #[cfg(any(test, feature = "test-support"))]
+        #[structopt(long, env = "NODE_RNG_STATE")]
+        /// Path to a file holding the state of the node's random number generator.  If the file
+        /// exists, the generator is restored from it, so that randomness continues
+        /// deterministically across restarts.  The state is saved on startup and on shutdown.
+        ///
+        /// Anyone able to read the file can predict the node's keys and nonces, so this is only
+        /// available in test builds.
+        rng_state: Option<PathBuf>,
     },
 }
 
@@ -79,6 +108,56 @@ impl ConfigExt {
     }
 }
 
+/// Applies the configuration overrides given by those of `vars` whose names start with
+/// `CONFIG_ENV_PREFIX` to a TOML table, in the order of their names.
+///
+/// Returns an error naming the offending variable if its name or value is malformed, or if its
+/// value turns a configuration which deserializes into a `C` into one which doesn't.
+fn apply_env_overrides<C, I>(toml_value: &mut Value, vars: I) -> anyhow::Result<()>
+where
+    C: DeserializeOwned,
+    I: IntoIterator<Item = (String, String)>,
+{
+    let mut overrides: Vec<_> = vars
+        .into_iter()
+        .filter(|(name, _)| name.starts_with(CONFIG_ENV_PREFIX))
+        .collect();
+    overrides.sort();
+
+    let mut is_valid = toml_value.clone().try_into::<C>().is_ok();
+    for (name, value) in overrides {
+        let mut path = name[CONFIG_ENV_PREFIX.len()..].splitn(2, "__");
+        let (section, key) = match (path.next(), path.next()) {
+            (Some(section), Some(key)) if !section.is_empty() && !key.is_empty() => {
+                (section.to_lowercase(), key.to_lowercase())
+            }
+            _ => bail!(
+                "environment variable {} is not of the form {}<SECTION>__<KEY>",
+                name,
+                CONFIG_ENV_PREFIX
+            ),
+        };
+        let config_ext = ConfigExt {
+            section,
+            key,
+            value,
+        };
+        config_ext
+            .update_toml_table(toml_value)
+            .with_context(|| format!("invalid value in environment variable {}", name))?;
+
+        // Only blame this variable if the configuration was valid before applying it.
+        match toml_value.clone().try_into::<C>() {
+            Err(error) if is_valid => {
+                return Err(anyhow::Error::new(error)
+                    .context(format!("invalid value in environment variable {}", name)))
+            }
+            result => is_valid = result.is_ok(),
+        }
+    }
+    Ok(())
+}
+
 impl FromStr for ConfigExt {
     type Err = anyhow::Error;
 
@@ -109,10 +188,27 @@ impl FromStr for ConfigExt {
 }
 
 impl Cli {
+    /// Returns the path given by `--rng-state`, if any.
+    #[cfg(any(test, feature = "test-support"))]
+    fn rng_state(&self) -> Option<PathBuf> {
+        match self {
+            Cli::Validator { rng_state, .. } => rng_state.clone(),
+        }
+    }
+
+    /// Returns `None`, as `--rng-state` is only available in test builds.
+    #[cfg(not(any(test, feature = "test-support")))]
+    fn rng_state(&self) -> Option<PathBuf> {
+        None
+    }
+
     /// Executes selected CLI command.
     pub async fn run(self) -> anyhow::Result<()> {
+        let rng_state = self.rng_state();
         match self {
-            Cli::Validator { config, config_ext } => {
+            Cli::Validator {
+                config, config_ext, ..
+            } => {
                 // Determine the parent directory of the configuration file, if any.
                 // Otherwise, we default to `/`.
                 let root = config
@@ -129,13 +225,17 @@ impl Cli {
                 // defaulted config instance if one is not provided.
                 let mut config_table: Value = toml::from_str(&config_raw)?;
 
-                // If any command line overrides to the config values are passed, apply them.
+                // Environment variables override the config file, and are in turn overridden by
+                // any command line overrides.
+                apply_env_overrides::<validator::Config, _>(&mut config_table, env::vars())?;
                 for item in config_ext {
                     item.update_toml_table(&mut config_table)?;
                 }
 
                 // Create validator config, including any overridden values.
                 let validator_config: validator::Config = config_table.try_into()?;
+                // Report all configuration problems at once, before any component is created.
+                validator_config.validate(&root)?;
                 logging::init_with_config(&validator_config.logging)?;
                 info!(version = %env!("CARGO_PKG_VERSION"), "node starting up");
                 trace!("{}", config::to_string(&validator_config)?);
@@ -144,10 +244,14 @@ impl Cli {
                 // eliminate any chance of runtime failures, regardless of how small (these
                 // exist with `OsRng`). Additionally, we want to limit the number of syscalls for
                 // performance reasons.
-                let mut rng = ChaCha20Rng::from_entropy();
+                let mut rng = load_rng_state(rng_state.as_deref())?;
+                // Persist the state right away, so that a crash replays randomness from here.
+                save_rng_state(&mut rng, rng_state.as_deref())?;
 
                 // The metrics are shared across all reactors.
                 let registry = Registry::new();
+                let stall_threshold =
+                    Duration::from_millis(validator_config.node.dispatch_stall_threshold_millis);
 
                 let mut initializer_runner = Runner::<initializer::Reactor, _>::with_metrics(
                     WithDir::new(root.clone(), validator_config),
@@ -155,7 +259,17 @@ impl Cli {
                     &registry,
                 )
                 .await?;
-                initializer_runner.run(&mut rng).await;
+                initializer_runner.set_stall_threshold(stall_threshold);
+                let shutdown = tokio::signal::ctrl_c();
+                if let Some(reason) = initializer_runner.run_until(&mut rng, shutdown).await {
+                    shut_down(&mut initializer_runner, &mut rng, reason.clone()).await;
+                    if let ShutdownReason::OperatorSignal = reason {
+                        save_rng_state(&mut rng, rng_state.as_deref())?;
+                        return Ok(());
+                    }
+                    bail!("failed to initialize: {}", reason);
+                }
+                initializer_runner.stop();
 
                 info!("finished initialization");
 
@@ -170,7 +284,17 @@ impl Cli {
                     &registry,
                 )
                 .await?;
-                joiner_runner.run(&mut rng).await;
+                joiner_runner.set_stall_threshold(stall_threshold);
+                let shutdown = tokio::signal::ctrl_c();
+                if let Some(reason) = joiner_runner.run_until(&mut rng, shutdown).await {
+                    shut_down(&mut joiner_runner, &mut rng, reason.clone()).await;
+                    if let ShutdownReason::OperatorSignal = reason {
+                        save_rng_state(&mut rng, rng_state.as_deref())?;
+                        return Ok(());
+                    }
+                    bail!("failed to join: {}", reason);
+                }
+                joiner_runner.stop();
 
                 info!("finished joining");
 
@@ -179,10 +303,154 @@ impl Cli {
                 let mut validator_runner =
                     Runner::<validator::Reactor<_>, _>::with_metrics(config, &mut rng, &registry)
                         .await?;
-                validator_runner.run(&mut rng).await;
+                validator_runner.set_stall_threshold(stall_threshold);
+                tokio::spawn(toggle_message_tracing_on_signal());
+                let shutdown = tokio::signal::ctrl_c();
+                let reason = validator_runner.run_until(&mut rng, shutdown).await;
+                match &reason {
+                    Some(reason) => {
+                        shut_down(&mut validator_runner, &mut rng, reason.clone()).await
+                    }
+                    None => validator_runner.stop(),
+                }
+                save_rng_state(&mut rng, rng_state.as_deref())?;
+                match reason {
+                    None | Some(ShutdownReason::OperatorSignal) => (),
+                    Some(reason) => bail!("shut down: {}", reason),
+                }
             }
         }
 
         Ok(())
     }
 }
+
+/// Shuts `runner` down for `reason`, warning if not all queued events could be processed.
+async fn shut_down<R>(
+    runner: &mut Runner<R, ChaCha20Rng>,
+    rng: &mut ChaCha20Rng,
+    reason: ShutdownReason,
+) where
+    R: Reactor<ChaCha20Rng>,
+    R::Error: From<prometheus::Error>,
+{
+    if !runner.shutdown(rng, reason, SHUTDOWN_DRAIN_TIMEOUT).await {
+        warn!("some events were dropped on shutdown");
+    }
+}
+
+/// Toggles consensus message tracing whenever the node receives `SIGUSR1`.
+async fn toggle_message_tracing_on_signal() {
+    let mut signals = match unix::signal(SignalKind::user_defined1()) {
+        Ok(signals) => signals,
+        Err(error) => {
+            warn!(%error, "could not listen for SIGUSR1 to toggle consensus message tracing");
+            return;
+        }
+    };
+    while signals.recv().await.is_some() {
+        let enabled = toggle_message_tracing();
+        info!(enabled, "toggled consensus message tracing");
+    }
+}
+
+/// Restores the random number generator from `path`, if given and existing, or seeds a new one
+/// from the operating system's entropy source.
+fn load_rng_state(path: Option<&Path>) -> anyhow::Result<ChaCha20Rng> {
+    let state = match path {
+        Some(path) => RngState::load(path)
+            .context("could not load rng state")
+            .with_context(|| path.display().to_string())?,
+        None => None,
+    };
+    Ok(state
+        .map(|state| state.restore())
+        .unwrap_or_else(ChaCha20Rng::from_entropy))
+}
+
+/// Snapshots `rng` and saves its state to `path`, if given.
+fn save_rng_state(rng: &mut ChaCha20Rng, path: Option<&Path>) -> anyhow::Result<()> {
+    if let Some(path) = path {
+        RngState::snapshot(rng)
+            .save(path)
+            .context("could not save rng state")
+            .with_context(|| path.display().to_string())?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use rand::RngCore;
+
+    use super::*;
+
+    fn local_config_table() -> Value {
+        let config_path = format!(
+            "{}/../resources/local/config.toml",
+            env!("CARGO_MANIFEST_DIR")
+        );
+        toml::from_str(&fs::read_to_string(config_path).unwrap()).unwrap()
+    }
+
+    fn var(name: &str, value: &str) -> (String, String) {
+        (name.to_string(), value.to_string())
+    }
+
+    #[test]
+    fn rng_state_flag_should_restore_generator_across_restarts() {
+        let temp_dir = tempfile::tempdir().expect("should get tempdir");
+        let path = temp_dir.path().join("rng_state.json");
+        let cli = Cli::from_iter_safe(&[
+            "casper-node",
+            "validator",
+            "config.toml",
+            "--rng-state",
+            path.to_str().unwrap(),
+        ])
+        .expect("should parse");
+        let rng_state = cli.rng_state();
+        assert_eq!(rng_state.as_deref(), Some(path.as_path()));
+
+        // First start: No state saved yet, so a fresh generator is created and persisted.
+        let mut rng = load_rng_state(rng_state.as_deref()).unwrap();
+        save_rng_state(&mut rng, rng_state.as_deref()).unwrap();
+        let _ = rng.next_u64();
+
+        // Shutdown and restart: The restarted node continues with the same sequence.
+        save_rng_state(&mut rng, rng_state.as_deref()).unwrap();
+        let expected: Vec<u64> = (0..10).map(|_| rng.next_u64()).collect();
+        let mut restored = load_rng_state(rng_state.as_deref()).unwrap();
+        let actual: Vec<u64> = (0..10).map(|_| restored.next_u64()).collect();
+        assert_eq!(actual, expected);
+    }
+
+    #[test]
+    fn env_var_should_override_file_value() {
+        let mut config_table = local_config_table();
+        let vars = vec![
+            var("CASPER_NODE__CONSENSUS__MAX_HELD_MESSAGES_PER_SENDER", "5"),
+            var("UNRELATED", "ignored"),
+        ];
+        apply_env_overrides::<validator::Config, _>(&mut config_table, vars).unwrap();
+
+        let config: validator::Config = config_table.try_into().unwrap();
+        assert_eq!(config.consensus.max_held_messages_per_sender, 5);
+    }
+
+    #[test]
+    fn malformed_env_var_should_be_reported() {
+        let name = "CASPER_NODE__CONSENSUS__MAX_HELD_MESSAGES_PER_SENDER";
+        let mut config_table = local_config_table();
+        let error =
+            apply_env_overrides::<validator::Config, _>(&mut config_table, vec![var(name, "many")])
+                .unwrap_err();
+        assert!(format!("{:#}", error).contains(name), "{:#}", error);
+
+        let name = "CASPER_NODE__CONSENSUS";
+        let error =
+            apply_env_overrides::<validator::Config, _>(&mut config_table, vec![var(name, "1")])
+                .unwrap_err();
+        assert!(format!("{:#}", error).contains(name), "{:#}", error);
+    }
+}
@@ -14,6 +14,8 @@ use std::{
 use backtrace::Backtrace;
 use structopt::StructOpt;
 
+use casper_node::crypto::audit;
+
 use cli::Cli;
 
 /// Aborting panic hook.
@@ -44,5 +46,9 @@ pub async fn main() -> anyhow::Result<()> {
 
     // Parse CLI args and run selected subcommand.
     let opts = Cli::from_args();
-    opts.run().await
+    let result = opts.run().await;
+
+    // Dropping the audit sink waits for the entries still being written.
+    audit::set_audit_sink(None);
+    result
 }
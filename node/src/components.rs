@@ -5,19 +5,23 @@
 pub(crate) mod api_server;
 pub(crate) mod block_executor;
 pub(crate) mod block_validator;
+pub(crate) mod chain_follower;
 pub(crate) mod chainspec_loader;
 pub(crate) mod consensus;
 pub mod contract_runtime;
 pub(crate) mod deploy_acceptor;
 pub(crate) mod deploy_buffer;
 pub(crate) mod fetcher;
+pub(crate) mod finality_signature_collector;
 pub(crate) mod gossiper;
 pub(crate) mod linear_chain;
 pub(crate) mod linear_chain_sync;
+pub(crate) mod load_shedder;
 // The  `in_memory_network` is public for use in doctests.
 #[cfg(test)]
 pub mod in_memory_network;
 pub(crate) mod metrics;
+pub(crate) mod proposal_builder;
 pub(crate) mod small_network;
 pub(crate) mod storage;
 
@@ -63,3 +67,22 @@ pub trait Component<REv, R: Rng + CryptoRng + ?Sized> {
         event: Self::Event,
     ) -> Effects<Self::Event>;
 }
+
+/// Startup and teardown hooks of a component.
+///
+/// Constructing or dropping a component doesn't capture work which has to happen once the node is
+/// actually running or stops running, like flushing buffered data to disk.  Components needing it
+/// implement this trait, and their reactor calls the hooks from `Reactor::start`, `Reactor::stop`
+/// and `Reactor::restart`.  All hooks do nothing by default.
+pub trait ComponentLifecycle {
+    /// Called once the reactor has been created, before it dispatches any event.
+    fn on_start(&mut self) {}
+
+    /// Called once the reactor has finished running, or has been shut down and its event queue
+    /// drained.
+    fn on_stop(&mut self) {}
+
+    /// Called when the reactor is restarted in place, to discard state which is only meaningful
+    /// while running, like in-flight requests.
+    fn on_restart(&mut self) {}
+}
@@ -9,14 +9,31 @@
 //!
 //! For the list of supported RPCs, see
 //! https://github.com/CasperLabs/ceps/blob/master/text/0009-client-api.md#rpcs
+//!
+//! All queries are JSON-RPC methods served on the single RPC path; there are no REST routes.  E.g.
+//! the pending deploys are listed by the "info_get_mempool" RPC rather than by `GET /mempool`, and
+//! the lifecycle state of a deploy is reported by the "info_get_deploy_status" RPC rather than by
+//! `GET /deploys/{hash}/status`.
+//!
+//! The API server also tracks the lifecycle of recently seen deploys, as reported by the
+//! "info_get_deploy_status" RPC, and pushes notifications of deploys being included and finalized
+//! to clients subscribed to its event stream.
+//!
+//! The status reports whether consensus has stalled, as announced by consensus.
+//!
+//! Until the node has enough peers, chain queries are answered with "503 Service Unavailable"; see
+//! the `readiness` module.  Once the node is shutting down, all RPCs are answered that way.
 
 mod config;
+mod deploy_tracker;
 mod event;
+mod event_stream;
+mod readiness;
 pub mod rpcs;
 
-use std::{convert::Infallible, fmt::Debug, net::SocketAddr};
+use std::{convert::Infallible, fmt::Debug, net::SocketAddr, time::Duration};
 
-use futures::{future, join};
+use futures::future;
 use hyper::Server;
 use lazy_static::lazy_static;
 use rand::{CryptoRng, Rng};
@@ -31,21 +48,28 @@ use casper_types::{Key, URef};
 
 use super::Component;
 use crate::{
-    components::storage::Storage,
-    crypto::hash::Digest,
+    components::{
+        deploy_acceptor,
+        storage::{Storage, Value},
+    },
+    crypto::{hash::Digest, merkle::MerkleProof},
     effect::{
         announcements::ApiServerAnnouncement,
         requests::{
-            ApiRequest, ContractRuntimeRequest, LinearChainRequest, MetricsRequest,
-            NetworkInfoRequest, StorageRequest,
+            ApiRequest, ConsensusRequest, ContractRuntimeRequest, DeployBufferRequest,
+            LinearChainRequest, MetricsRequest, NetworkInfoRequest, StorageRequest,
         },
         EffectBuilder, EffectExt, Effects, Responder,
     },
     small_network::NodeId,
-    types::StatusFeed,
+    types::{BlockHeader, DeployHash, StatusFeed, Timestamp},
 };
 pub use config::Config;
+use deploy_tracker::DeployTracker;
+pub use deploy_tracker::{DeployState, DeployStatus};
 pub(crate) use event::Event;
+use event_stream::EventStream;
+use readiness::{Readiness, ReadyFlag};
 use rpcs::{RpcWithOptionalParamsExt, RpcWithParamsExt, RpcWithoutParamsExt};
 
 // TODO - confirm if we want to use the protocol version for this.
@@ -53,6 +77,9 @@ lazy_static! {
     static ref CLIENT_API_VERSION: Version = Version::new(1, 0, 0);
 }
 
+/// The time to wait for all components to contribute to a status query.
+const STATUS_TIMEOUT: Duration = Duration::from_secs(5);
+
 /// A helper trait whose bounds represent the requirements for a reactor event that `run_server` can
 /// work with.
 trait ReactorEventT:
@@ -77,10 +104,27 @@ impl<REv> ReactorEventT for REv where
 }
 
 #[derive(Debug)]
-pub(crate) struct ApiServer {}
+pub(crate) struct ApiServer {
+    /// The lifecycle states of recently seen deploys.
+    deploy_tracker: DeployTracker,
+    /// The clients subscribed to deploy notifications.
+    event_stream: EventStream,
+    /// The conditions under which chain queries are served.
+    readiness: Readiness,
+    /// Whether deploys submitted by clients are rejected to shed load.
+    is_rejecting_submissions: bool,
+    /// If consensus has stalled, when the last block was finalized before.
+    consensus_stalled_since: Option<Timestamp>,
+}
 
 impl ApiServer {
-    pub(crate) fn new<REv>(config: Config, effect_builder: EffectBuilder<REv>) -> Self
+    /// Creates a new API server for a node which is already synced if `is_synced`, i.e. holds a
+    /// block of the linear chain.
+    pub(crate) fn new<REv>(
+        config: Config,
+        effect_builder: EffectBuilder<REv>,
+        is_synced: bool,
+    ) -> Self
     where
         REv: From<Event>
             + From<ApiRequest<NodeId>>
@@ -89,33 +133,82 @@ impl ApiServer {
             + From<ContractRuntimeRequest>
             + Send,
     {
-        tokio::spawn(run_server(config, effect_builder));
-        ApiServer {}
+        let deploy_tracker = DeployTracker::new(config.deploy_status_max_age);
+        let event_stream = EventStream::new(
+            config.max_event_stream_subscribers,
+            config.event_stream_buffer_length,
+        );
+        let readiness = Readiness::new(&config, is_synced);
+        tokio::spawn(run_event_stream_server(
+            SocketAddr::from((config.bind_interface, config.event_stream_port)),
+            event_stream.clone(),
+        ));
+        tokio::spawn(run_server(config, effect_builder, readiness.ready_flag()));
+        ApiServer {
+            deploy_tracker,
+            event_stream,
+            readiness,
+            is_rejecting_submissions: false,
+            consensus_stalled_since: None,
+        }
+    }
+
+    /// Sets whether deploys submitted by clients are rejected to shed load.
+    pub(crate) fn set_rejecting_submissions(&mut self, is_rejecting_submissions: bool) {
+        if is_rejecting_submissions != self.is_rejecting_submissions {
+            info!(
+                is_rejecting_submissions,
+                "changed acceptance of deploys from clients"
+            );
+        }
+        self.is_rejecting_submissions = is_rejecting_submissions;
     }
 }
 
 /// Run the HTTP server.
-async fn run_server<REv: ReactorEventT>(config: Config, effect_builder: EffectBuilder<REv>) {
+async fn run_server<REv: ReactorEventT>(
+    config: Config,
+    effect_builder: EffectBuilder<REv>,
+    ready: ReadyFlag,
+) {
+    let shutting_down = rpcs::unavailable_while_shutting_down(effect_builder);
     let put_deploy = rpcs::account::PutDeploy::create_filter(effect_builder);
-    let get_block = rpcs::chain::GetBlock::create_filter(effect_builder);
-    let get_global_state_hash = rpcs::chain::GetGlobalStateHash::create_filter(effect_builder);
-    let get_item = rpcs::state::GetItem::create_filter(effect_builder);
-    let get_balance = rpcs::state::GetBalance::create_filter(effect_builder);
-    let get_deploy = rpcs::info::GetDeploy::create_filter(effect_builder);
+    let get_block = rpcs::chain::GetBlock::create_filter_when_ready(effect_builder, &ready);
+    let get_block_range =
+        rpcs::chain::GetBlockRange::create_filter_when_ready(effect_builder, &ready);
+    let get_global_state_hash =
+        rpcs::chain::GetGlobalStateHash::create_filter_when_ready(effect_builder, &ready);
+    let get_item = rpcs::state::GetItem::create_filter_when_ready(effect_builder, &ready);
+    let get_balance = rpcs::state::GetBalance::create_filter_when_ready(effect_builder, &ready);
+    let get_deploy = rpcs::info::GetDeploy::create_filter_when_ready(effect_builder, &ready);
+    let get_deploy_inclusion_proof =
+        rpcs::info::GetDeployInclusionProof::create_filter_when_ready(effect_builder, &ready);
     let get_peers = rpcs::info::GetPeers::create_filter(effect_builder);
     let get_status = rpcs::info::GetStatus::create_filter(effect_builder);
     let get_metrics = rpcs::info::GetMetrics::create_filter(effect_builder);
+    let get_mempool = rpcs::info::GetMempool::create_filter(effect_builder);
+    let get_peer_latencies = rpcs::info::GetPeerLatencies::create_filter(effect_builder);
+    let get_deploy_status = rpcs::info::GetDeployStatus::create_filter(effect_builder);
+    let get_validator_participation =
+        rpcs::info::GetValidatorParticipation::create_filter(effect_builder);
 
     let service = warp_json_rpc::service(
-        put_deploy
+        shutting_down
+            .or(put_deploy)
             .or(get_block)
+            .or(get_block_range)
             .or(get_global_state_hash)
             .or(get_item)
             .or(get_balance)
             .or(get_deploy)
+            .or(get_deploy_inclusion_proof)
             .or(get_peers)
             .or(get_status)
-            .or(get_metrics),
+            .or(get_metrics)
+            .or(get_mempool)
+            .or(get_peer_latencies)
+            .or(get_deploy_status)
+            .or(get_validator_participation),
     );
 
     let mut server_addr = SocketAddr::from((config.bind_interface, config.bind_port));
@@ -148,6 +241,31 @@ async fn run_server<REv: ReactorEventT>(config: Config, effect_builder: EffectBu
     }
 }
 
+/// Run the HTTP server pushing deploy notifications to subscribed clients.
+async fn run_event_stream_server(mut server_addr: SocketAddr, event_stream: EventStream) {
+    let filter = event_stream::create_filter(event_stream);
+
+    // As for the JSON-RPC server, fall back to a random port if the chosen one is unavailable.
+    loop {
+        match warp::serve(filter.clone()).try_bind_ephemeral(server_addr) {
+            Ok((address, server)) => {
+                info!(%address, "started event stream server");
+                server.await;
+                return;
+            }
+            Err(error) => {
+                if server_addr.port() == 0 {
+                    warn!(%error, "failed to start event stream server");
+                    return;
+                } else {
+                    server_addr.set_port(0);
+                    debug!(%error, "failed to start event stream server. retrying on random port");
+                }
+            }
+        }
+    }
+}
+
 impl ApiServer {
     fn handle_query<REv: ReactorEventT>(
         &mut self,
@@ -181,6 +299,45 @@ impl ApiServer {
                 main_responder: responder,
             })
     }
+
+    fn handle_get_deploy_inclusion_proof<REv: ReactorEventT>(
+        &mut self,
+        effect_builder: EffectBuilder<REv>,
+        hash: DeployHash,
+        responder: Responder<Option<(BlockHeader, MerkleProof)>>,
+    ) -> Effects<Event> {
+        async move {
+            let maybe_proof = deploy_inclusion_proof(effect_builder, hash).await;
+            responder.respond(maybe_proof).await
+        }
+        .ignore()
+    }
+}
+
+/// Returns the header of a stored block containing the given deploy and the proof of its
+/// inclusion.
+///
+/// The blocks containing the deploy are found via the deploy's metadata.
+async fn deploy_inclusion_proof<REv: ReactorEventT>(
+    effect_builder: EffectBuilder<REv>,
+    hash: DeployHash,
+) -> Option<(BlockHeader, MerkleProof)> {
+    let (_deploy, metadata) = effect_builder
+        .get_deploy_and_metadata_from_storage::<Storage>(hash)
+        .await?;
+    for block_hash in metadata.execution_results.keys() {
+        let block = match effect_builder
+            .get_canonical_block_from_storage::<Storage>(*block_hash)
+            .await
+        {
+            Some(block) => block,
+            None => continue,
+        };
+        if let Some(proof) = block.deploy_inclusion_proof(&hash) {
+            return Some((block.take_header(), proof));
+        }
+    }
+    None
 }
 
 impl<REv, R> Component<REv, R> for ApiServer
@@ -189,8 +346,10 @@ where
         + From<NetworkInfoRequest<NodeId>>
         + From<LinearChainRequest<NodeId>>
         + From<ContractRuntimeRequest>
+        + From<DeployBufferRequest>
         + From<MetricsRequest>
         + From<StorageRequest<Storage>>
+        + From<ConsensusRequest>
         + From<Event>
         + From<ApiRequest<NodeId>>
         + Send,
@@ -206,15 +365,22 @@ where
     ) -> Effects<Self::Event> {
         match event {
             Event::ApiRequest(ApiRequest::SubmitDeploy { deploy, responder }) => {
-                let mut effects = effect_builder.announce_deploy_received(deploy).ignore();
-                effects.extend(responder.respond(()).ignore());
-                effects
+                if self.is_rejecting_submissions {
+                    info!("rejected deploy {} to shed load", deploy.id());
+                    return responder
+                        .respond(Err(deploy_acceptor::Error::Overloaded))
+                        .ignore();
+                }
+                // The deploy acceptor tells the client whether it accepted the deploy.
+                effect_builder
+                    .announce_deploy_received(deploy, Some(responder))
+                    .ignore()
             }
             Event::ApiRequest(ApiRequest::GetBlock {
                 maybe_hash: Some(hash),
                 responder,
             }) => effect_builder
-                .get_block_from_storage(hash)
+                .get_canonical_block_from_storage(hash)
                 .event(move |result| Event::GetBlockResult {
                     maybe_hash: Some(hash),
                     result: Box::new(result),
@@ -248,28 +414,107 @@ where
                     result: Box::new(result),
                     main_responder: responder,
                 }),
+            Event::ApiRequest(ApiRequest::GetDeployInclusionProof { hash, responder }) => {
+                self.handle_get_deploy_inclusion_proof(effect_builder, hash, responder)
+            }
             Event::ApiRequest(ApiRequest::GetPeers { responder }) => effect_builder
                 .network_peers()
                 .event(move |peers| Event::GetPeersResult {
                     peers,
                     main_responder: responder,
                 }),
-            Event::ApiRequest(ApiRequest::GetStatus { responder }) => async move {
-                let (last_finalized_block, peers) = join!(
-                    effect_builder.get_last_finalized_block(),
-                    effect_builder.network_peers()
-                );
-                let status_feed = StatusFeed::new(last_finalized_block, peers);
-                debug!("GetStatus --status_feed: {:?}", status_feed);
-                responder.respond(status_feed).await;
+            Event::ApiRequest(ApiRequest::GetStatus { responder }) => {
+                let is_ready = self.readiness.is_ready();
+                let consensus_stalled_since = self.consensus_stalled_since;
+                async move {
+                    let (last_finalized_block, peers) = effect_builder
+                        .join(
+                            STATUS_TIMEOUT,
+                            effect_builder.get_last_finalized_block(),
+                            effect_builder.network_peers(),
+                        )
+                        .await;
+                    if last_finalized_block.is_none() || peers.is_none() {
+                        warn!("status incomplete, as some components did not respond in time");
+                    }
+                    let last_finalized_block = last_finalized_block.flatten();
+                    let peers = peers.unwrap_or_default();
+                    let status_feed = StatusFeed::new(
+                        last_finalized_block,
+                        peers,
+                        is_ready,
+                        consensus_stalled_since,
+                    );
+                    debug!("GetStatus --status_feed: {:?}", status_feed);
+                    responder.respond(status_feed).await;
+                }
+                .ignore()
             }
-            .ignore(),
             Event::ApiRequest(ApiRequest::GetMetrics { responder }) => effect_builder
                 .get_metrics()
                 .event(move |text| Event::GetMetricsResult {
                     text,
                     main_responder: responder,
                 }),
+            Event::ApiRequest(ApiRequest::GetMempool { responder }) => async move {
+                let pending_deploys = effect_builder.get_pending_deploys().await;
+                responder.respond(pending_deploys).await
+            }
+            .ignore(),
+            Event::ApiRequest(ApiRequest::GetPeerLatencies { responder }) => async move {
+                let latencies = effect_builder.network_peer_latencies().await;
+                responder.respond(latencies).await
+            }
+            .ignore(),
+            Event::ApiRequest(ApiRequest::GetDeployStatus { hash, responder }) => responder
+                .respond(self.deploy_tracker.status(&hash).cloned())
+                .ignore(),
+            Event::ApiRequest(ApiRequest::GetValidatorParticipation { responder }) => async move {
+                let report = effect_builder.get_validator_participation().await;
+                responder.respond(report).await
+            }
+            .ignore(),
+            Event::DeployAccepted(deploy_hash) => {
+                self.deploy_tracker.accepted(deploy_hash, Timestamp::now());
+                Effects::new()
+            }
+            Event::DeployGossiped(deploy_hash) => {
+                self.deploy_tracker.gossiped(deploy_hash, Timestamp::now());
+                Effects::new()
+            }
+            Event::BlockAdded {
+                block_hash,
+                deploy_hashes,
+            } => {
+                self.event_stream
+                    .notify(&deploy_hashes, DeployState::Included, &block_hash);
+                self.deploy_tracker
+                    .included(block_hash, deploy_hashes, Timestamp::now());
+                self.readiness.block_added();
+                Effects::new()
+            }
+            Event::BlockFinalized(block_hash) => {
+                let deploy_hashes = self.deploy_tracker.finalized(&block_hash, Timestamp::now());
+                self.event_stream
+                    .notify(&deploy_hashes, DeployState::Finalized, &block_hash);
+                Effects::new()
+            }
+            Event::PeerConnected(peer) => {
+                self.readiness.peer_connected(peer);
+                Effects::new()
+            }
+            Event::PeerDisconnected(peer) => {
+                self.readiness.peer_disconnected(&peer);
+                Effects::new()
+            }
+            Event::ConsensusStalled { last_progress } => {
+                self.consensus_stalled_since = Some(last_progress);
+                Effects::new()
+            }
+            Event::ConsensusResumed => {
+                self.consensus_stalled_since = None;
+                Effects::new()
+            }
             Event::GetBlockResult {
                 maybe_hash: _,
                 result,
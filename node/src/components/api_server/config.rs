@@ -1,7 +1,17 @@
-use std::net::{IpAddr, Ipv4Addr};
+use std::{
+    net::{IpAddr, Ipv4Addr},
+    time::Duration,
+};
 
 use serde::{Deserialize, Serialize};
 
+/// Default time for which the lifecycle state of a deploy is tracked.
+const DEFAULT_DEPLOY_STATUS_MAX_AGE: Duration = Duration::from_secs(24 * 60 * 60);
+/// Default maximum number of clients subscribed to the event stream at a time.
+const DEFAULT_MAX_EVENT_STREAM_SUBSCRIBERS: usize = 100;
+/// Default number of notifications buffered for each event stream subscriber.
+const DEFAULT_EVENT_STREAM_BUFFER_LENGTH: usize = 100;
+
 /// API server configuration.
 #[derive(Debug, Deserialize, Serialize)]
 // Disallow unknown fields to ensure config files and command-line overrides contain valid keys.
@@ -12,6 +22,28 @@ pub struct Config {
 
     /// Port to bind to. Use 0 for a random port.
     pub bind_port: u16,
+
+    /// Port to bind the event stream server to. Use 0 for a random port.
+    pub event_stream_port: u16,
+
+    /// Maximum number of clients subscribed to the event stream at a time.
+    pub max_event_stream_subscribers: usize,
+
+    /// Number of notifications buffered for each event stream subscriber.  A subscriber falling
+    /// further behind is disconnected.
+    pub event_stream_buffer_length: usize,
+
+    /// Time in milliseconds for which the lifecycle state of a deploy is tracked.
+    #[serde(with = "crate::utils::milliseconds")]
+    pub deploy_status_max_age: Duration,
+
+    /// Minimum number of connected peers before chain queries are served.
+    #[serde(default)]
+    pub min_peers: usize,
+
+    /// Whether chain queries are only served once a finalized block is known.
+    #[serde(default)]
+    pub require_synced: bool,
 }
 
 impl Config {
@@ -20,6 +52,12 @@ impl Config {
         Config {
             bind_interface: Ipv4Addr::LOCALHOST.into(),
             bind_port: 0,
+            event_stream_port: 0,
+            max_event_stream_subscribers: DEFAULT_MAX_EVENT_STREAM_SUBSCRIBERS,
+            event_stream_buffer_length: DEFAULT_EVENT_STREAM_BUFFER_LENGTH,
+            deploy_status_max_age: DEFAULT_DEPLOY_STATUS_MAX_AGE,
+            min_peers: 0,
+            require_synced: false,
         }
     }
 }
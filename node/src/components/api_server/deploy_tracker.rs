@@ -0,0 +1,260 @@
+//! Tracking of the lifecycle of deploys.
+//!
+//! A deploy is accepted, gossiped to enough peers, included in a block of the linear chain, and
+//! finally that block is signed by enough validators to be final.  The tracker records when each
+//! of these states was entered, so that clients can follow a deploy through a single RPC.  The
+//! states are only ever advanced, so announcements arriving out of order can't move a deploy back.
+//!
+//! Deploys are forgotten once they have been tracked for longer than the configured maximum age.
+
+use std::time::Duration;
+
+use linked_hash_map::LinkedHashMap;
+use serde::{Deserialize, Serialize};
+
+use crate::types::{BlockHash, DeployHash, Timestamp};
+
+/// The states of a deploy's lifecycle, in the order they are entered.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum DeployState {
+    /// The deploy has been accepted by this node.
+    Accepted,
+    /// Gossiping the deploy has finished.
+    Gossiped,
+    /// The deploy has been included in a block of the linear chain.
+    Included,
+    /// The block including the deploy has been signed by enough validators to be final.
+    Finalized,
+}
+
+/// The current lifecycle state of a deploy, and when each state was entered.
+///
+/// States which were skipped, e.g. because the deploy was first seen in a block, have no time.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct DeployStatus {
+    /// The current state.
+    pub state: DeployState,
+    /// When the deploy was accepted.
+    pub accepted: Option<Timestamp>,
+    /// When gossiping the deploy finished.
+    pub gossiped: Option<Timestamp>,
+    /// When the deploy was included in a block.
+    pub included: Option<Timestamp>,
+    /// When the block including the deploy became final.
+    pub finalized: Option<Timestamp>,
+    /// The hash of the block including the deploy.
+    pub block_hash: Option<BlockHash>,
+}
+
+impl DeployStatus {
+    fn new(state: DeployState) -> Self {
+        DeployStatus {
+            state,
+            accepted: None,
+            gossiped: None,
+            included: None,
+            finalized: None,
+            block_hash: None,
+        }
+    }
+}
+
+/// The lifecycle states of recently seen deploys.
+#[derive(Debug)]
+pub(super) struct DeployTracker {
+    /// The status of each deploy with the time it was first tracked, the earliest first.
+    statuses: LinkedHashMap<DeployHash, (Timestamp, DeployStatus)>,
+    /// The deploys of each block which is not final yet, with the time the block was added, the
+    /// earliest first.
+    pending_blocks: LinkedHashMap<BlockHash, (Timestamp, Vec<DeployHash>)>,
+    /// The time after which deploys and blocks are forgotten.
+    max_age: Duration,
+}
+
+impl DeployTracker {
+    /// Creates a tracker forgetting deploys after `max_age`.
+    pub(super) fn new(max_age: Duration) -> Self {
+        DeployTracker {
+            statuses: LinkedHashMap::new(),
+            pending_blocks: LinkedHashMap::new(),
+            max_age,
+        }
+    }
+
+    /// Records that `deploy_hash` was accepted.
+    pub(super) fn accepted(&mut self, deploy_hash: DeployHash, now: Timestamp) {
+        self.prune(now);
+        let _ = self.advance(deploy_hash, DeployState::Accepted, now);
+    }
+
+    /// Records that gossiping `deploy_hash` finished.
+    pub(super) fn gossiped(&mut self, deploy_hash: DeployHash, now: Timestamp) {
+        self.prune(now);
+        let _ = self.advance(deploy_hash, DeployState::Gossiped, now);
+    }
+
+    /// Records that the block `block_hash`, including `deploy_hashes`, was added to the linear
+    /// chain.
+    pub(super) fn included(
+        &mut self,
+        block_hash: BlockHash,
+        deploy_hashes: Vec<DeployHash>,
+        now: Timestamp,
+    ) {
+        self.prune(now);
+        for deploy_hash in &deploy_hashes {
+            let status = self.advance(*deploy_hash, DeployState::Included, now);
+            status.block_hash.get_or_insert(block_hash);
+        }
+        let _ = self.pending_blocks.insert(block_hash, (now, deploy_hashes));
+    }
+
+    /// Records that the block `block_hash` became final, and returns the deploys it includes.
+    pub(super) fn finalized(&mut self, block_hash: &BlockHash, now: Timestamp) -> Vec<DeployHash> {
+        self.prune(now);
+        let deploy_hashes = self
+            .pending_blocks
+            .remove(block_hash)
+            .map(|(_, deploy_hashes)| deploy_hashes)
+            .unwrap_or_default();
+        for deploy_hash in &deploy_hashes {
+            let _ = self.advance(*deploy_hash, DeployState::Finalized, now);
+        }
+        deploy_hashes
+    }
+
+    /// Returns the status of `deploy_hash`, or `None` if it isn't tracked.
+    pub(super) fn status(&self, deploy_hash: &DeployHash) -> Option<&DeployStatus> {
+        self.statuses.get(deploy_hash).map(|(_, status)| status)
+    }
+
+    /// Advances `deploy_hash` to `state` unless it is in a later state already, and records `now`
+    /// as the time `state` was entered unless it was entered before.
+    fn advance(
+        &mut self,
+        deploy_hash: DeployHash,
+        state: DeployState,
+        now: Timestamp,
+    ) -> &mut DeployStatus {
+        let (_, status) = self
+            .statuses
+            .entry(deploy_hash)
+            .or_insert_with(|| (now, DeployStatus::new(state)));
+        status.state = status.state.max(state);
+        let entered = match state {
+            DeployState::Accepted => &mut status.accepted,
+            DeployState::Gossiped => &mut status.gossiped,
+            DeployState::Included => &mut status.included,
+            DeployState::Finalized => &mut status.finalized,
+        };
+        entered.get_or_insert(now);
+        status
+    }
+
+    /// Forgets the deploys and blocks which have been tracked for longer than the maximum age.
+    fn prune(&mut self, now: Timestamp) {
+        let max_age = self.max_age.as_millis() as u64;
+        let is_expired = |since: Timestamp| now.saturating_sub(since).millis() > max_age;
+        while let Some((_, (since, _))) = self.statuses.front() {
+            if !is_expired(*since) {
+                break;
+            }
+            let _ = self.statuses.pop_front();
+        }
+        while let Some((_, (since, _))) = self.pending_blocks.front() {
+            if !is_expired(*since) {
+                break;
+            }
+            let _ = self.pending_blocks.pop_front();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{crypto::hash::Digest, testing::TestRng};
+
+    #[test]
+    fn should_transition_through_lifecycle_states() {
+        let mut rng = TestRng::new();
+        let deploy_hash = DeployHash::new(Digest::random(&mut rng));
+        let other_deploy_hash = DeployHash::new(Digest::random(&mut rng));
+        let block_hash = BlockHash::new(Digest::random(&mut rng));
+        let mut tracker = DeployTracker::new(Duration::from_secs(60));
+        assert_eq!(tracker.status(&deploy_hash), None);
+
+        tracker.accepted(deploy_hash, Timestamp::from(1_000));
+        assert_eq!(
+            tracker.status(&deploy_hash).map(|status| status.state),
+            Some(DeployState::Accepted)
+        );
+
+        tracker.gossiped(deploy_hash, Timestamp::from(2_000));
+        assert_eq!(
+            tracker.status(&deploy_hash).map(|status| status.state),
+            Some(DeployState::Gossiped)
+        );
+
+        // The other deploy is first seen in the block, so it has no earlier times.
+        tracker.included(
+            block_hash,
+            vec![deploy_hash, other_deploy_hash],
+            Timestamp::from(3_000),
+        );
+        assert_eq!(
+            tracker.status(&deploy_hash).map(|status| status.state),
+            Some(DeployState::Included)
+        );
+        let other_status = tracker.status(&other_deploy_hash).unwrap();
+        assert_eq!(other_status.state, DeployState::Included);
+        assert_eq!(other_status.accepted, None);
+        assert_eq!(other_status.block_hash, Some(block_hash));
+
+        // A late announcement doesn't move the deploy back.
+        tracker.gossiped(other_deploy_hash, Timestamp::from(3_500));
+        assert_eq!(
+            tracker
+                .status(&other_deploy_hash)
+                .map(|status| status.state),
+            Some(DeployState::Included)
+        );
+
+        let finalized = tracker.finalized(&block_hash, Timestamp::from(4_000));
+        assert_eq!(finalized, vec![deploy_hash, other_deploy_hash]);
+        let expected = DeployStatus {
+            state: DeployState::Finalized,
+            accepted: Some(Timestamp::from(1_000)),
+            gossiped: Some(Timestamp::from(2_000)),
+            included: Some(Timestamp::from(3_000)),
+            finalized: Some(Timestamp::from(4_000)),
+            block_hash: Some(block_hash),
+        };
+        assert_eq!(tracker.status(&deploy_hash), Some(&expected));
+        assert_eq!(
+            tracker
+                .status(&other_deploy_hash)
+                .map(|status| status.state),
+            Some(DeployState::Finalized)
+        );
+    }
+
+    #[test]
+    fn should_forget_old_deploys() {
+        let mut rng = TestRng::new();
+        let old_deploy_hash = DeployHash::new(Digest::random(&mut rng));
+        let new_deploy_hash = DeployHash::new(Digest::random(&mut rng));
+        let mut tracker = DeployTracker::new(Duration::from_secs(10));
+
+        tracker.accepted(old_deploy_hash, Timestamp::from(1_000));
+        tracker.accepted(new_deploy_hash, Timestamp::from(8_000));
+        // Still within the maximum age of the old deploy, so both are kept.
+        tracker.gossiped(new_deploy_hash, Timestamp::from(11_000));
+        assert!(tracker.status(&old_deploy_hash).is_some());
+
+        tracker.gossiped(new_deploy_hash, Timestamp::from(12_000));
+        assert_eq!(tracker.status(&old_deploy_hash), None);
+        assert!(tracker.status(&new_deploy_hash).is_some());
+    }
+}
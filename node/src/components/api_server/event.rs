@@ -11,7 +11,7 @@ use casper_execution_engine::core::engine_state::{self, BalanceResult, QueryResu
 use crate::{
     components::{small_network::NodeId, storage::DeployMetadata},
     effect::{requests::ApiRequest, Responder},
-    types::{Block, BlockHash, Deploy, DeployHash},
+    types::{Block, BlockHash, Deploy, DeployHash, Timestamp},
 };
 
 #[derive(Debug, From)]
@@ -44,6 +44,25 @@ pub enum Event {
         result: Result<BalanceResult, engine_state::Error>,
         main_responder: Responder<Result<BalanceResult, engine_state::Error>>,
     },
+    /// A deploy has been accepted.
+    DeployAccepted(DeployHash),
+    /// Gossiping a deploy has finished.
+    DeployGossiped(DeployHash),
+    /// A block has been added to the linear chain.
+    BlockAdded {
+        block_hash: BlockHash,
+        deploy_hashes: Vec<DeployHash>,
+    },
+    /// A block has been signed by enough validators to be final.
+    BlockFinalized(BlockHash),
+    /// A peer has connected.
+    PeerConnected(NodeId),
+    /// A peer has disconnected.
+    PeerDisconnected(NodeId),
+    /// Consensus has stalled, i.e. no block has been finalized since `last_progress`.
+    ConsensusStalled { last_progress: Timestamp },
+    /// A block has been finalized after consensus had stalled.
+    ConsensusResumed,
 }
 
 impl Display for Event {
@@ -74,6 +93,16 @@ impl Display for Event {
                 Some(txt) => write!(formatter, "get metrics ({} bytes)", txt.len()),
                 None => write!(formatter, "get metrics (failed)"),
             },
+            Event::DeployAccepted(deploy_hash) => write!(formatter, "accepted {}", deploy_hash),
+            Event::DeployGossiped(deploy_hash) => write!(formatter, "gossiped {}", deploy_hash),
+            Event::BlockAdded { block_hash, .. } => write!(formatter, "added {}", block_hash),
+            Event::BlockFinalized(block_hash) => write!(formatter, "finalized {}", block_hash),
+            Event::PeerConnected(peer) => write!(formatter, "peer {} connected", peer),
+            Event::PeerDisconnected(peer) => write!(formatter, "peer {} disconnected", peer),
+            Event::ConsensusStalled { last_progress } => {
+                write!(formatter, "consensus stalled since {}", last_progress)
+            }
+            Event::ConsensusResumed => write!(formatter, "consensus resumed"),
         }
     }
 }
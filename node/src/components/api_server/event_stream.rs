@@ -0,0 +1,345 @@
+//! Push notifications of deploy lifecycle events to subscribed clients.
+//!
+//! Rather than polling "info_get_deploy_status", clients can hold open a server-sent events
+//! connection to `GET /events` and are pushed a notification whenever a deploy is included in a
+//! block or that block becomes final.  The subscription can be restricted to particular deploys
+//! via `?deploys=<hash>,<hash>`, with the hashes hex-encoded.
+//!
+//! A subscription is forgotten once its client disconnects.  The number of subscribers is limited,
+//! further subscription requests are answered with "503 Service Unavailable".  Only a limited
+//! number of notifications is buffered for each subscriber, and a client falling further behind is
+//! disconnected, rather than have notifications pile up in memory.
+
+use std::{
+    collections::HashSet,
+    convert::Infallible,
+    mem,
+    sync::{Arc, Mutex},
+};
+
+use futures::{
+    channel::mpsc::{self, Receiver, Sender},
+    StreamExt,
+};
+use http::StatusCode;
+use serde::{Deserialize, Serialize};
+use tracing::info;
+use warp::{
+    reject::{self, Reject},
+    Filter, Rejection, Reply,
+};
+
+use super::DeployState;
+use crate::{
+    crypto::hash::Digest,
+    types::{BlockHash, DeployHash},
+};
+
+/// The URL path.
+pub const EVENTS_API_PATH: &str = "events";
+
+/// A notification that a deploy entered a new lifecycle state.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub(super) struct DeployNotification {
+    /// The hex-encoded deploy hash.
+    deploy_hash: String,
+    /// The state the deploy entered.
+    state: DeployState,
+    /// The hex-encoded hash of the block including the deploy.
+    block_hash: String,
+}
+
+impl DeployNotification {
+    fn new(deploy_hash: &DeployHash, state: DeployState, block_hash: &BlockHash) -> Self {
+        DeployNotification {
+            deploy_hash: hex::encode(deploy_hash.inner()),
+            state,
+            block_hash: hex::encode(block_hash.inner()),
+        }
+    }
+}
+
+/// A client subscribed to deploy notifications.
+#[derive(Debug)]
+struct Subscriber {
+    /// The deploys the client is interested in, or `None` if it wants to be notified of all.
+    deploy_hashes: Option<HashSet<DeployHash>>,
+    sender: Sender<DeployNotification>,
+}
+
+impl Subscriber {
+    fn is_interested_in(&self, deploy_hash: &DeployHash) -> bool {
+        self.deploy_hashes
+            .as_ref()
+            .map_or(true, |deploy_hashes| deploy_hashes.contains(deploy_hash))
+    }
+
+    /// Sends the notifications the subscriber is interested in, and returns whether it is still
+    /// subscribed, i.e. neither disconnected nor lagging behind.
+    fn notify(
+        &mut self,
+        deploy_hashes: &[DeployHash],
+        state: DeployState,
+        block_hash: &BlockHash,
+    ) -> bool {
+        for deploy_hash in deploy_hashes {
+            if !self.is_interested_in(deploy_hash) {
+                continue;
+            }
+            let notification = DeployNotification::new(deploy_hash, state, block_hash);
+            if let Err(error) = self.sender.try_send(notification) {
+                if error.is_full() {
+                    info!("disconnecting event stream subscriber lagging behind");
+                }
+                return false;
+            }
+        }
+        true
+    }
+}
+
+/// The clients subscribed to deploy notifications, shared between the API server component and
+/// the HTTP server.
+#[derive(Clone, Debug)]
+pub(super) struct EventStream {
+    subscribers: Arc<Mutex<Vec<Subscriber>>>,
+    /// The maximum number of subscribers at a time.
+    max_subscribers: usize,
+    /// The number of notifications buffered for each subscriber.
+    buffer_length: usize,
+}
+
+impl EventStream {
+    /// Creates an event stream for at most `max_subscribers` subscribers at a time, each of which
+    /// is disconnected once more than `buffer_length` notifications are waiting to be sent to it.
+    pub(super) fn new(max_subscribers: usize, buffer_length: usize) -> Self {
+        EventStream {
+            subscribers: Arc::new(Mutex::new(Vec::new())),
+            max_subscribers,
+            buffer_length,
+        }
+    }
+
+    /// Subscribes to notifications about `deploy_hashes`, or about all deploys if `None`.
+    ///
+    /// The subscription ends when the returned receiver is dropped, or once it lags behind.
+    /// Returns `None` if there are too many subscribers already.
+    pub(super) fn subscribe(
+        &self,
+        deploy_hashes: Option<HashSet<DeployHash>>,
+    ) -> Option<Receiver<DeployNotification>> {
+        let mut subscribers = self.lock();
+        subscribers.retain(|subscriber| !subscriber.sender.is_closed());
+        if subscribers.len() >= self.max_subscribers {
+            return None;
+        }
+        // The channel holds one more notification than its buffer, so the buffer is one shorter.
+        let (sender, receiver) = mpsc::channel(self.buffer_length.saturating_sub(1));
+        subscribers.push(Subscriber {
+            deploy_hashes,
+            sender,
+        });
+        Some(receiver)
+    }
+
+    /// Notifies the interested subscribers that each of `deploy_hashes` entered `state` as part of
+    /// the block `block_hash`.
+    pub(super) fn notify(
+        &self,
+        deploy_hashes: &[DeployHash],
+        state: DeployState,
+        block_hash: &BlockHash,
+    ) {
+        let mut subscribers = self.lock();
+        // Dropping a subscriber's sender ends its stream, disconnecting the client.
+        *subscribers = mem::take(&mut *subscribers)
+            .into_iter()
+            .filter_map(|mut subscriber| {
+                if subscriber.notify(deploy_hashes, state, block_hash) {
+                    Some(subscriber)
+                } else {
+                    None
+                }
+            })
+            .collect();
+    }
+
+    fn lock(&self) -> std::sync::MutexGuard<Vec<Subscriber>> {
+        self.subscribers
+            .lock()
+            .expect("event stream subscribers lock poisoned")
+    }
+}
+
+/// The query of a subscription request.
+#[derive(Debug, Deserialize)]
+struct SubscriptionQuery {
+    /// Comma-separated hex-encoded hashes of the deploys to subscribe to.
+    deploys: Option<String>,
+}
+
+/// The subscription request contained an invalid deploy hash.
+#[derive(Debug)]
+struct InvalidDeployHash(String);
+
+impl Reject for InvalidDeployHash {}
+
+fn parse_deploy_hashes(deploys: &str) -> Result<HashSet<DeployHash>, InvalidDeployHash> {
+    deploys
+        .split(',')
+        .map(|hex_hash| {
+            Digest::from_hex(hex_hash)
+                .map(DeployHash::new)
+                .map_err(|error| InvalidDeployHash(error.to_string()))
+        })
+        .collect()
+}
+
+/// Creates the warp filter serving deploy notifications as server-sent events.
+pub(super) fn create_filter(
+    event_stream: EventStream,
+) -> impl Filter<Extract = (impl Reply,), Error = Rejection> + Clone {
+    warp::path(EVENTS_API_PATH)
+        .and(warp::path::end())
+        .and(warp::get())
+        .and(warp::query::<SubscriptionQuery>())
+        .and_then(move |query: SubscriptionQuery| {
+            let event_stream = event_stream.clone();
+            async move {
+                let deploy_hashes = query
+                    .deploys
+                    .as_deref()
+                    .map(parse_deploy_hashes)
+                    .transpose()
+                    .map_err(reject::custom)?;
+                let reply: Box<dyn Reply> = match event_stream.subscribe(deploy_hashes) {
+                    Some(receiver) => {
+                        let notifications = receiver
+                            .map(|notification| Ok::<_, Infallible>(warp::sse::json(notification)));
+                        Box::new(warp::sse::reply(
+                            warp::sse::keep_alive().stream(notifications),
+                        ))
+                    }
+                    None => Box::new(warp::reply::with_status(
+                        "too many event stream subscribers",
+                        StatusCode::SERVICE_UNAVAILABLE,
+                    )),
+                };
+                Ok::<_, Rejection>(reply)
+            }
+        })
+}
+
+#[cfg(test)]
+mod tests {
+    use tokio::task;
+
+    use super::*;
+    use crate::testing::TestRng;
+
+    #[test]
+    fn should_only_notify_subscribed_deploy() {
+        let mut rng = TestRng::new();
+        let deploy_hash = DeployHash::new(Digest::random(&mut rng));
+        let other_deploy_hash = DeployHash::new(Digest::random(&mut rng));
+        let block_hash = BlockHash::new(Digest::random(&mut rng));
+        let event_stream = EventStream::new(10, 10);
+
+        let mut receiver = event_stream
+            .subscribe(Some(vec![deploy_hash].into_iter().collect()))
+            .unwrap();
+        let mut all_receiver = event_stream.subscribe(None).unwrap();
+        event_stream.notify(
+            &[other_deploy_hash, deploy_hash],
+            DeployState::Included,
+            &block_hash,
+        );
+
+        let expected = DeployNotification::new(&deploy_hash, DeployState::Included, &block_hash);
+        assert_eq!(receiver.try_next().unwrap(), Some(expected));
+        assert!(receiver.try_next().is_err());
+        // A subscriber without a filter is notified of both deploys.
+        assert!(all_receiver.try_next().unwrap().is_some());
+        assert!(all_receiver.try_next().unwrap().is_some());
+
+        // Subscriptions of disconnected clients are forgotten.
+        drop(receiver);
+        drop(all_receiver);
+        event_stream.notify(&[deploy_hash], DeployState::Finalized, &block_hash);
+        assert!(event_stream.lock().is_empty());
+    }
+
+    #[test]
+    fn should_limit_subscribers_and_disconnect_laggards() {
+        let mut rng = TestRng::new();
+        let deploy_hash = DeployHash::new(Digest::random(&mut rng));
+        let block_hash = BlockHash::new(Digest::random(&mut rng));
+        let event_stream = EventStream::new(2, 2);
+
+        let mut receiver = event_stream.subscribe(None).unwrap();
+        let lagging_receiver = event_stream.subscribe(None).unwrap();
+        assert!(event_stream.subscribe(None).is_none());
+
+        // Both subscribers have room for two notifications.
+        event_stream.notify(&[deploy_hash], DeployState::Included, &block_hash);
+        event_stream.notify(&[deploy_hash], DeployState::Finalized, &block_hash);
+        assert_eq!(event_stream.lock().len(), 2);
+
+        // Only the subscriber keeping up stays subscribed.
+        assert!(receiver.try_next().unwrap().is_some());
+        event_stream.notify(&[deploy_hash], DeployState::Finalized, &block_hash);
+        assert_eq!(event_stream.lock().len(), 1);
+        drop(lagging_receiver);
+
+        // Which frees a slot for another subscriber.
+        assert!(event_stream.subscribe(None).is_some());
+    }
+
+    #[tokio::test]
+    async fn should_serve_subscriptions_over_http() {
+        let mut rng = TestRng::new();
+        let deploy_hash = DeployHash::new(Digest::random(&mut rng));
+        let other_deploy_hash = DeployHash::new(Digest::random(&mut rng));
+        let block_hash = BlockHash::new(Digest::random(&mut rng));
+        let event_stream = EventStream::new(1, 1);
+        let filter = create_filter(event_stream.clone());
+        let path = format!(
+            "/{}?deploys={}",
+            EVENTS_API_PATH,
+            hex::encode(deploy_hash.inner())
+        );
+
+        let subscription = {
+            let filter = filter.clone();
+            let path = path.clone();
+            tokio::spawn(async move { warp::test::request().path(&path).reply(&filter).await })
+        };
+        while event_stream.lock().is_empty() {
+            task::yield_now().await;
+        }
+
+        // The only slot is taken.
+        let response = warp::test::request().path(&path).reply(&filter).await;
+        assert_eq!(response.status(), StatusCode::SERVICE_UNAVAILABLE);
+
+        // The subscriber is only sent the notification about its deploy, and disconnected once it
+        // lags behind, which ends the response.
+        event_stream.notify(
+            &[other_deploy_hash, deploy_hash],
+            DeployState::Included,
+            &block_hash,
+        );
+        event_stream.notify(&[deploy_hash], DeployState::Finalized, &block_hash);
+        let response = subscription.await.unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        let body = String::from_utf8(response.body().to_vec()).unwrap();
+        let expected = serde_json::to_string(&DeployNotification::new(
+            &deploy_hash,
+            DeployState::Included,
+            &block_hash,
+        ))
+        .unwrap();
+        assert_eq!(body.matches("data:").count(), 1, "{}", body);
+        assert!(body.contains(&expected), "{}", body);
+    }
+}
@@ -0,0 +1,114 @@
+//! Readiness of the node to serve chain queries.
+//!
+//! A node without peers, or one which doesn't know a finalized block yet, can't give meaningful
+//! chain data.  Until the configured minimum number of peers is connected and, if required, the
+//! node is synced, RPCs querying the chain are answered with "503 Service Unavailable".  Status
+//! RPCs are still served, and report that the node is not ready.
+//!
+//! The API server keeps track of the connected peers and of whether a block has been added to the
+//! linear chain from the announcements it is passed, and caches the resulting readiness in a flag
+//! shared with the HTTP server, so that checking it doesn't involve the reactor.
+
+use std::{
+    collections::HashSet,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc,
+    },
+};
+
+use tracing::info;
+
+use super::Config;
+use crate::small_network::NodeId;
+
+/// A flag shared with the HTTP server, set while the node is ready to serve chain queries.
+#[derive(Clone, Debug, Default)]
+pub(super) struct ReadyFlag(Arc<AtomicBool>);
+
+impl ReadyFlag {
+    /// Returns whether the node is ready to serve chain queries.
+    pub(super) fn is_set(&self) -> bool {
+        self.0.load(Ordering::SeqCst)
+    }
+
+    fn set(&self, is_ready: bool) {
+        self.0.store(is_ready, Ordering::SeqCst)
+    }
+}
+
+/// The conditions under which the node is ready to serve chain queries, and whether they are met.
+#[derive(Debug)]
+pub(super) struct Readiness {
+    /// The minimum number of connected peers.
+    min_peers: usize,
+    /// Whether the node must know a finalized block.
+    require_synced: bool,
+    /// The connected peers.
+    peers: HashSet<NodeId>,
+    /// Whether a block has been added to the linear chain.
+    is_synced: bool,
+    /// The flag caching whether the conditions are met.
+    ready_flag: ReadyFlag,
+}
+
+impl Readiness {
+    /// Creates the readiness conditions given in `config`, for a node without peers which is
+    /// already synced if `is_synced`.
+    pub(super) fn new(config: &Config, is_synced: bool) -> Self {
+        let readiness = Readiness {
+            min_peers: config.min_peers,
+            require_synced: config.require_synced,
+            peers: HashSet::new(),
+            is_synced,
+            ready_flag: ReadyFlag::default(),
+        };
+        readiness.ready_flag.set(readiness.is_met());
+        readiness
+    }
+
+    /// Returns the flag caching whether the node is ready.
+    pub(super) fn ready_flag(&self) -> ReadyFlag {
+        self.ready_flag.clone()
+    }
+
+    /// Returns whether the node is ready to serve chain queries.
+    pub(super) fn is_ready(&self) -> bool {
+        self.ready_flag.is_set()
+    }
+
+    /// Records that `peer` has connected.
+    pub(super) fn peer_connected(&mut self, peer: NodeId) {
+        self.peers.insert(peer);
+        self.update();
+    }
+
+    /// Records that `peer` has disconnected.
+    pub(super) fn peer_disconnected(&mut self, peer: &NodeId) {
+        self.peers.remove(peer);
+        self.update();
+    }
+
+    /// Records that a block has been added to the linear chain.
+    pub(super) fn block_added(&mut self) {
+        self.is_synced = true;
+        self.update();
+    }
+
+    fn is_met(&self) -> bool {
+        self.peers.len() >= self.min_peers && (self.is_synced || !self.require_synced)
+    }
+
+    fn update(&self) {
+        let is_ready = self.is_met();
+        if is_ready != self.ready_flag.is_set() {
+            info!(
+                is_ready,
+                peer_count = self.peers.len(),
+                is_synced = self.is_synced,
+                "changed readiness to serve chain queries"
+            );
+            self.ready_flag.set(is_ready);
+        }
+    }
+}
@@ -10,7 +10,7 @@ pub mod state;
 use std::str;
 
 use futures::{future::BoxFuture, TryFutureExt};
-use http::Response;
+use http::{Response, StatusCode};
 use hyper::Body;
 use serde::{Deserialize, Serialize};
 use warp::{
@@ -20,7 +20,7 @@ use warp::{
 };
 use warp_json_rpc::{filters, Builder};
 
-use super::{ApiRequest, ReactorEventT};
+use super::{readiness::ReadyFlag, ApiRequest, ReactorEventT};
 use crate::effect::EffectBuilder;
 
 /// The URL path.
@@ -43,6 +43,12 @@ enum ErrorCode {
     ParseGetBalanceURef = 32009,
     GetBalanceFailed = 32010,
     GetBalanceFailedToExecute = 32011,
+    DeployBufferFull = 32012,
+    NotReady = 32013,
+    ParticipationNotAvailable = 32014,
+    DeployRejected = 32015,
+    BlockRangeOffsetTooLarge = 32016,
+    ShuttingDown = 32017,
 }
 
 #[derive(Debug)]
@@ -56,6 +62,63 @@ impl From<anyhow::Error> for Error {
     }
 }
 
+/// Creates a filter answering all JSON-RPC requests with "503 Service Unavailable" once the node is
+/// shutting down, so that no new requests enter the draining event queue.
+///
+/// Until then, requests are rejected, so that they fall through to the RPCs' own filters.
+pub(super) fn unavailable_while_shutting_down<REv: ReactorEventT>(
+    effect_builder: EffectBuilder<REv>,
+) -> BoxedFilter<(Response<Body>,)> {
+    warp::path(RPC_API_PATH)
+        .and(filters::json_rpc())
+        .and_then(move |response_builder: Builder| async move {
+            if !effect_builder.is_shutting_down() {
+                return Err(reject::not_found());
+            }
+            let mut response = response_builder
+                .error(warp_json_rpc::Error::custom(
+                    ErrorCode::ShuttingDown as i64,
+                    "node is shutting down",
+                ))
+                .map_err(|error| reject::custom(Error::from(error)))?;
+            *response.status_mut() = StatusCode::SERVICE_UNAVAILABLE;
+            Ok(response)
+        })
+        .boxed()
+}
+
+/// Creates a filter answering requests for the JSON-RPC `method` with "503 Service Unavailable"
+/// while the node is not ready to serve chain queries.
+///
+/// Once the `ready` flag is set, requests are rejected, so that they fall through to the RPC's own
+/// filter.
+fn unavailable_until_ready(
+    ready: &ReadyFlag,
+    method: &'static str,
+) -> BoxedFilter<(Response<Body>,)> {
+    let ready = ready.clone();
+    warp::path(RPC_API_PATH)
+        .and(filters::json_rpc())
+        .and(filters::method(method))
+        .and_then(move |response_builder: Builder| {
+            let is_ready = ready.is_set();
+            async move {
+                if is_ready {
+                    return Err(reject::not_found());
+                }
+                let mut response = response_builder
+                    .error(warp_json_rpc::Error::custom(
+                        ErrorCode::NotReady as i64,
+                        "node is not ready to serve chain queries",
+                    ))
+                    .map_err(|error| reject::custom(Error::from(error)))?;
+                *response.status_mut() = StatusCode::SERVICE_UNAVAILABLE;
+                Ok(response)
+            }
+        })
+        .boxed()
+}
+
 /// A JSON-RPC requiring the "params" field to be present.
 pub trait RpcWithParams {
     /// The JSON-RPC "method" name.
@@ -93,6 +156,18 @@ pub(super) trait RpcWithParamsExt: RpcWithParams {
         response_builder: Builder,
         params: Self::RequestParams,
     ) -> BoxFuture<'static, Result<Response<Body>, Error>>;
+
+    /// Creates the warp filter for this particular RPC, answering with "503 Service Unavailable"
+    /// while the node is not ready to serve chain queries.
+    fn create_filter_when_ready<REv: ReactorEventT>(
+        effect_builder: EffectBuilder<REv>,
+        ready: &ReadyFlag,
+    ) -> BoxedFilter<(Response<Body>,)> {
+        unavailable_until_ready(ready, Self::METHOD)
+            .or(Self::create_filter(effect_builder))
+            .unify()
+            .boxed()
+    }
 }
 
 /// A JSON-RPC requiring the "params" field to be absent.
@@ -124,6 +199,18 @@ pub(super) trait RpcWithoutParamsExt: RpcWithoutParams {
         effect_builder: EffectBuilder<REv>,
         response_builder: Builder,
     ) -> BoxFuture<'static, Result<Response<Body>, Error>>;
+
+    /// Creates the warp filter for this particular RPC, answering with "503 Service Unavailable"
+    /// while the node is not ready to serve chain queries.
+    fn create_filter_when_ready<REv: ReactorEventT>(
+        effect_builder: EffectBuilder<REv>,
+        ready: &ReadyFlag,
+    ) -> BoxedFilter<(Response<Body>,)> {
+        unavailable_until_ready(ready, Self::METHOD)
+            .or(Self::create_filter(effect_builder))
+            .unify()
+            .boxed()
+    }
 }
 
 /// A JSON-RPC with the "params" field optional.
@@ -170,4 +257,136 @@ pub(super) trait RpcWithOptionalParamsExt: RpcWithOptionalParams {
         response_builder: Builder,
         maybe_params: Option<Self::OptionalRequestParams>,
     ) -> BoxFuture<'static, Result<Response<Body>, Error>>;
+
+    /// Creates the warp filter for this particular RPC, answering with "503 Service Unavailable"
+    /// while the node is not ready to serve chain queries.
+    fn create_filter_when_ready<REv: ReactorEventT>(
+        effect_builder: EffectBuilder<REv>,
+        ready: &ReadyFlag,
+    ) -> BoxedFilter<(Response<Body>,)> {
+        unavailable_until_ready(ready, Self::METHOD)
+            .or(Self::create_filter(effect_builder))
+            .unify()
+            .boxed()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+
+    use derive_more::From;
+    use futures::future;
+    use http::Request;
+    use hyper::service::Service;
+    use rand::Rng;
+    use serde_json::{json, Value};
+
+    use super::*;
+    use crate::{
+        components::{
+            api_server::{readiness::Readiness, Config, Event},
+            small_network::NodeId,
+            storage::Storage,
+        },
+        effect::requests::{ContractRuntimeRequest, LinearChainRequest, StorageRequest},
+        reactor::{EventQueueHandle, QueueKind, Scheduler},
+        testing::TestRng,
+        types::{StatusFeed, Timestamp},
+        utils,
+    };
+
+    #[derive(Debug, From)]
+    enum ReactorEvent {
+        #[from]
+        ApiServer(Event),
+        #[from]
+        ApiRequest(ApiRequest<NodeId>),
+        #[from]
+        Storage(StorageRequest<Storage>),
+        #[from]
+        LinearChain(LinearChainRequest<NodeId>),
+        #[from]
+        ContractRuntime(ContractRuntimeRequest),
+    }
+
+    fn rpc_request(method: &str) -> Request<Body> {
+        let body = json!({ "jsonrpc": "2.0", "id": 1, "method": method });
+        Request::post(format!("/{}", RPC_API_PATH))
+            .header("content-type", "application/json")
+            .body(Body::from(body.to_string()))
+            .unwrap()
+    }
+
+    #[tokio::test]
+    async fn should_refuse_chain_queries_below_peer_threshold() {
+        let mut rng = TestRng::new();
+        let scheduler = utils::leak(Scheduler::<ReactorEvent>::new(QueueKind::weights()));
+        let effect_builder = EffectBuilder::new(EventQueueHandle::new(scheduler));
+        let mut readiness = Readiness::new(
+            &Config {
+                min_peers: 2,
+                ..Config::default()
+            },
+            false,
+        );
+        let ready = readiness.ready_flag();
+        readiness.peer_connected(rng.gen());
+
+        // Answer the requests the RPCs make in place of the reactor.
+        let status_ready = ready.clone();
+        tokio::spawn(async move {
+            loop {
+                match scheduler.pop().await.0 {
+                    ReactorEvent::ApiRequest(ApiRequest::GetStatus { responder }) => {
+                        let stalled_since = Some(Timestamp::from(1_000));
+                        let status = StatusFeed::new(
+                            None,
+                            HashMap::new(),
+                            status_ready.is_set(),
+                            stalled_since,
+                        );
+                        responder.respond(status).await
+                    }
+                    ReactorEvent::ApiRequest(ApiRequest::GetBlock { responder, .. }) => {
+                        responder.respond(None).await
+                    }
+                    other => panic!("unexpected event {:?}", other),
+                }
+            }
+        });
+
+        let mut service = warp_json_rpc::service(
+            chain::GetBlock::create_filter_when_ready(effect_builder, &ready)
+                .or(info::GetStatus::create_filter(effect_builder))
+                .unify(),
+        );
+        future::poll_fn(|cx| service.poll_ready(cx)).await.unwrap();
+
+        let response = service.call(rpc_request("chain_get_block")).await.unwrap();
+        assert_eq!(response.status(), StatusCode::SERVICE_UNAVAILABLE);
+
+        // The status is still reported, with the node not ready.
+        let response = service.call(rpc_request("info_get_status")).await.unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        let body = hyper::body::to_bytes(response.into_body()).await.unwrap();
+        let status: Value = serde_json::from_slice(&body).unwrap();
+        assert_eq!(status["result"]["is_ready"], Value::Bool(false));
+        assert!(!status["result"]["consensus_stalled_since"].is_null());
+
+        // Readiness is lost again once a peer disconnects.
+        let peer = rng.gen();
+        readiness.peer_connected(peer);
+        let response = service.call(rpc_request("chain_get_block")).await.unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+
+        readiness.peer_disconnected(&peer);
+        let response = service.call(rpc_request("chain_get_block")).await.unwrap();
+        assert_eq!(response.status(), StatusCode::SERVICE_UNAVAILABLE);
+
+        readiness.block_added();
+        readiness.peer_connected(peer);
+        let response = service.call(rpc_request("chain_get_block")).await.unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+    }
 }
@@ -13,7 +13,9 @@ use warp_json_rpc::Builder;
 
 use super::{ApiRequest, Error, ErrorCode, ReactorEventT, RpcWithParams, RpcWithParamsExt};
 use crate::{
-    components::api_server::CLIENT_API_VERSION, effect::EffectBuilder, reactor::QueueKind,
+    components::{api_server::CLIENT_API_VERSION, deploy_acceptor},
+    effect::EffectBuilder,
+    reactor::QueueKind,
     types::Deploy,
 };
 
@@ -65,7 +67,7 @@ impl RpcWithParamsExt for PutDeploy {
             let deploy_hash = *deploy.id();
 
             // Submit the new deploy to be announced.
-            effect_builder
+            let result = effect_builder
                 .make_request(
                     |responder| ApiRequest::SubmitDeploy {
                         deploy: Box::new(deploy),
@@ -74,6 +76,17 @@ impl RpcWithParamsExt for PutDeploy {
                     QueueKind::Api,
                 )
                 .await;
+            if let Err(error) = result {
+                info!("failed to put deploy {}: {}", deploy_hash, error);
+                let error_code = match error {
+                    deploy_acceptor::Error::DeployBufferFull => ErrorCode::DeployBufferFull,
+                    _ => ErrorCode::DeployRejected,
+                };
+                return Ok(response_builder.error(warp_json_rpc::Error::custom(
+                    error_code as i64,
+                    error.to_string(),
+                ))?);
+            }
 
             // Return the result.
             let result = Self::ResponseResult {
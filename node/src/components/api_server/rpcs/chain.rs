@@ -122,6 +122,155 @@ impl RpcWithOptionalParamsExt for GetGlobalStateHash {
     }
 }
 
+/// Number of blocks returned by "chain_get_block_range" if no limit is given.
+const DEFAULT_BLOCK_RANGE_LIMIT: u64 = 10;
+/// Maximum number of blocks returned by a single "chain_get_block_range" request.  Larger limits
+/// are clamped to this value.
+const MAX_BLOCK_RANGE_LIMIT: u64 = 100;
+/// Maximum number of blocks a single "chain_get_block_range" request may skip.  Every skipped block
+/// is read from storage, so pages further back have to be requested via `start_block_hash`.
+const MAX_BLOCK_RANGE_OFFSET: u64 = 1_000;
+
+/// Params for "chain_get_block_range" RPC request.
+#[derive(Serialize, Deserialize, Debug, Default)]
+pub struct GetBlockRangeParams {
+    /// Hex-encoded hash of the block to start from, e.g. the `next_block_hash` of the previous
+    /// page.  Defaults to the last finalized block.
+    #[serde(default)]
+    pub start_block_hash: Option<String>,
+    /// Number of blocks to skip, counting backwards from the starting block.
+    #[serde(default)]
+    pub offset: u64,
+    /// Maximum number of blocks to return.
+    #[serde(default)]
+    pub limit: Option<u64>,
+}
+
+/// Pagination metadata for "chain_get_block_range" RPC response.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct Pagination {
+    /// Number of blocks skipped, counting backwards from the starting block.
+    pub offset: u64,
+    /// Maximum number of blocks returned, after applying the default and maximum page size.
+    pub limit: u64,
+    /// Offset of the next page, or `None` if there are no further blocks.
+    pub next_offset: Option<u64>,
+    /// Hex-encoded hash of the first block of the next page, or `None` if there are no further
+    /// blocks.
+    pub next_block_hash: Option<String>,
+}
+
+impl Pagination {
+    /// Returns the pagination for the given request params, with `next_offset` unset.
+    fn new(maybe_params: Option<&GetBlockRangeParams>) -> Self {
+        let offset = maybe_params.map_or(0, |params| params.offset);
+        let limit = maybe_params
+            .and_then(|params| params.limit)
+            .unwrap_or(DEFAULT_BLOCK_RANGE_LIMIT)
+            .min(MAX_BLOCK_RANGE_LIMIT);
+        Pagination {
+            offset,
+            limit,
+            next_offset: None,
+            next_block_hash: None,
+        }
+    }
+}
+
+/// Result for "chain_get_block_range" RPC response.
+#[derive(Serialize, Deserialize, Debug)]
+pub struct GetBlockRangeResult {
+    /// The RPC API version.
+    pub api_version: Version,
+    /// JSON-encoded blocks, starting with the most recent one.
+    pub blocks: Vec<Value>,
+    /// Pagination metadata.
+    pub pagination: Pagination,
+}
+
+/// "chain_get_block_range" RPC.
+pub struct GetBlockRange {}
+
+impl RpcWithOptionalParams for GetBlockRange {
+    const METHOD: &'static str = "chain_get_block_range";
+    type OptionalRequestParams = GetBlockRangeParams;
+    type ResponseResult = GetBlockRangeResult;
+}
+
+impl RpcWithOptionalParamsExt for GetBlockRange {
+    fn handle_request<REv: ReactorEventT>(
+        effect_builder: EffectBuilder<REv>,
+        response_builder: Builder,
+        maybe_params: Option<Self::OptionalRequestParams>,
+    ) -> BoxFuture<'static, Result<Response<Body>, Error>> {
+        async move {
+            let mut pagination = Pagination::new(maybe_params.as_ref());
+            if pagination.offset > MAX_BLOCK_RANGE_OFFSET {
+                info!(offset = pagination.offset, "block range offset too large");
+                let error_msg = format!(
+                    "offset must not exceed {}, use start_block_hash to page further back",
+                    MAX_BLOCK_RANGE_OFFSET
+                );
+                return Ok(response_builder.error(warp_json_rpc::Error::custom(
+                    ErrorCode::BlockRangeOffsetTooLarge as i64,
+                    error_msg,
+                ))?);
+            }
+
+            // Walk back from the starting block, skipping `offset` blocks.
+            let maybe_start_block_hash = maybe_params.and_then(|params| params.start_block_hash);
+            let mut maybe_block = match get_block(maybe_start_block_hash, effect_builder).await {
+                Ok(maybe_block) => maybe_block,
+                Err(error) => return Ok(response_builder.error(error)?),
+            };
+            let mut skipped = 0;
+            let mut blocks = vec![];
+            while let Some(block) = maybe_block {
+                if blocks.len() as u64 == pagination.limit {
+                    pagination.next_offset = Some(pagination.offset + pagination.limit);
+                    pagination.next_block_hash = Some(hex::encode(block.hash().inner()));
+                    break;
+                }
+                if skipped < pagination.offset {
+                    skipped += 1;
+                } else {
+                    blocks.push(block.to_json());
+                }
+                maybe_block = get_parent_block(&block, effect_builder).await;
+            }
+
+            // Return the result.
+            let result = Self::ResponseResult {
+                api_version: CLIENT_API_VERSION.clone(),
+                blocks,
+                pagination,
+            };
+            Ok(response_builder.success(result)?)
+        }
+        .boxed()
+    }
+}
+
+/// Gets the parent of `block` from storage, or `None` if `block` is the first block.
+async fn get_parent_block<REv: ReactorEventT>(
+    block: &Block,
+    effect_builder: EffectBuilder<REv>,
+) -> Option<Block> {
+    if block.is_genesis_child() {
+        return None;
+    }
+    let parent_hash = *block.parent_hash();
+    effect_builder
+        .make_request(
+            |responder| ApiRequest::GetBlock {
+                maybe_hash: Some(parent_hash),
+                responder,
+            },
+            QueueKind::Api,
+        )
+        .await
+}
+
 async fn get_block<REv: ReactorEventT>(
     maybe_hex_block_hash: Option<String>,
     effect_builder: EffectBuilder<REv>,
@@ -174,3 +323,188 @@ async fn get_block<REv: ReactorEventT>(
             .await)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use derive_more::From;
+    use futures::future;
+    use http::{Request, StatusCode};
+    use hyper::service::Service;
+    use serde_json::json;
+
+    use super::{super::RPC_API_PATH, *};
+    use crate::{
+        components::{
+            api_server::Event, consensus::EraId, small_network::NodeId, storage::Storage,
+        },
+        crypto::asymmetric_key::{PublicKey, SecretKey},
+        effect::requests::{ContractRuntimeRequest, LinearChainRequest, StorageRequest},
+        reactor::{EventQueueHandle, Scheduler},
+        testing::TestRng,
+        types::{FinalizedBlock, ProtoBlock, Timestamp},
+        utils,
+    };
+
+    #[derive(Debug, From)]
+    enum ReactorEvent {
+        #[from]
+        ApiServer(Event),
+        #[from]
+        ApiRequest(ApiRequest<NodeId>),
+        #[from]
+        Storage(StorageRequest<Storage>),
+        #[from]
+        LinearChain(LinearChainRequest<NodeId>),
+        #[from]
+        ContractRuntime(ContractRuntimeRequest),
+    }
+
+    /// Creates a linear chain of `count` blocks, starting with the most recent one.
+    fn linear_chain(rng: &mut TestRng, count: u64) -> Vec<Block> {
+        let proposer = PublicKey::from(&SecretKey::random(rng));
+        let mut parent_hash = BlockHash::new(Digest::random(rng));
+        let mut blocks = vec![];
+        for height in 0..count {
+            let finalized_block = FinalizedBlock::new(
+                ProtoBlock::new(vec![], false),
+                Timestamp::now(),
+                vec![],
+                false,
+                EraId(0),
+                height,
+                proposer,
+            );
+            let block = Block::new(parent_hash, Digest::random(rng), finalized_block);
+            parent_hash = *block.hash();
+            blocks.push(block);
+        }
+        blocks.reverse();
+        blocks
+    }
+
+    /// Calls "chain_get_block_range" with the given params, answering the storage requests it makes
+    /// from `blocks`, and returns the response's JSON body and the number of blocks requested.
+    async fn get_block_range(blocks: Vec<Block>, params: Value) -> (Value, usize) {
+        let scheduler = utils::leak(Scheduler::<ReactorEvent>::new(QueueKind::weights()));
+        let effect_builder = EffectBuilder::new(EventQueueHandle::new(scheduler));
+        let mut service = warp_json_rpc::service(GetBlockRange::create_filter(effect_builder));
+        future::poll_fn(|cx| service.poll_ready(cx)).await.unwrap();
+
+        let body =
+            json!({ "jsonrpc": "2.0", "id": 1, "method": GetBlockRange::METHOD, "params": params });
+        let request = Request::post(format!("/{}", RPC_API_PATH))
+            .header("content-type", "application/json")
+            .body(Body::from(body.to_string()))
+            .unwrap();
+        let mut response = tokio::spawn(service.call(request));
+
+        let mut requested = 0;
+        let response = loop {
+            tokio::select! {
+                response = &mut response => break response.unwrap().unwrap(),
+                (event, _) = scheduler.pop() => match event {
+                    ReactorEvent::ApiRequest(ApiRequest::GetBlock { maybe_hash, responder }) => {
+                        requested += 1;
+                        let maybe_block = match maybe_hash {
+                            None => blocks.first(),
+                            Some(hash) => blocks.iter().find(|block| *block.hash() == hash),
+                        };
+                        responder.respond(maybe_block.cloned()).await
+                    }
+                    other => panic!("unexpected event {:?}", other),
+                },
+            }
+        };
+        assert_eq!(response.status(), StatusCode::OK);
+        let body = hyper::body::to_bytes(response.into_body()).await.unwrap();
+        (serde_json::from_slice(&body).unwrap(), requested)
+    }
+
+    fn heights(response: &Value) -> Vec<u64> {
+        response["result"]["blocks"]
+            .as_array()
+            .expect("should contain blocks")
+            .iter()
+            .map(|json_block| {
+                Block::from_json(json_block.clone())
+                    .expect("should parse block")
+                    .height()
+            })
+            .collect()
+    }
+
+    #[test]
+    fn should_use_default_page() {
+        let expected = Pagination {
+            offset: 0,
+            limit: DEFAULT_BLOCK_RANGE_LIMIT,
+            next_offset: None,
+            next_block_hash: None,
+        };
+        assert_eq!(Pagination::new(None), expected);
+        assert_eq!(
+            Pagination::new(Some(&GetBlockRangeParams::default())),
+            expected
+        );
+    }
+
+    #[test]
+    fn should_use_custom_limit() {
+        let params = GetBlockRangeParams {
+            start_block_hash: None,
+            offset: 20,
+            limit: Some(5),
+        };
+        let pagination = Pagination::new(Some(&params));
+        assert_eq!(pagination.offset, 20);
+        assert_eq!(pagination.limit, 5);
+    }
+
+    #[test]
+    fn should_clamp_limit_exceeding_max() {
+        let params: GetBlockRangeParams =
+            serde_json::from_str(r#"{"limit": 1000000}"#).expect("should parse params");
+        let pagination = Pagination::new(Some(&params));
+        assert_eq!(pagination.offset, 0);
+        assert_eq!(pagination.limit, MAX_BLOCK_RANGE_LIMIT);
+    }
+
+    #[tokio::test]
+    async fn should_page_through_block_range() {
+        let mut rng = TestRng::new();
+        let blocks = linear_chain(&mut rng, 12);
+
+        let (response, _) = get_block_range(blocks.clone(), json!({ "limit": 5 })).await;
+        assert_eq!(heights(&response), vec![11, 10, 9, 8, 7]);
+        let pagination = &response["result"]["pagination"];
+        assert_eq!(pagination["next_offset"], json!(5));
+        let next_block_hash = hex::encode(blocks[5].hash().inner());
+        assert_eq!(pagination["next_block_hash"], json!(next_block_hash));
+
+        // Continuing from the cursor only reads the blocks of the requested page.
+        let params = json!({ "start_block_hash": next_block_hash, "limit": 5 });
+        let (response, requested) = get_block_range(blocks.clone(), params).await;
+        assert_eq!(heights(&response), vec![6, 5, 4, 3, 2]);
+        assert_eq!(requested, 6);
+
+        let (response, _) = get_block_range(blocks, json!({ "offset": 10, "limit": 5 })).await;
+        assert_eq!(heights(&response), vec![1, 0]);
+        let pagination = &response["result"]["pagination"];
+        assert_eq!(pagination["next_offset"], Value::Null);
+        assert_eq!(pagination["next_block_hash"], Value::Null);
+    }
+
+    #[tokio::test]
+    async fn should_reject_offset_exceeding_max() {
+        let mut rng = TestRng::new();
+        let blocks = linear_chain(&mut rng, 2);
+
+        let params = json!({ "offset": MAX_BLOCK_RANGE_OFFSET + 1 });
+        let (response, requested) = get_block_range(blocks, params).await;
+        assert_eq!(
+            response["error"]["code"],
+            json!(ErrorCode::BlockRangeOffsetTooLarge as i64)
+        );
+        assert_eq!(requested, 0);
+    }
+}
@@ -1,6 +1,7 @@
 //! RPCs returning ancillary information.
 
 use std::{
+    cmp::Reverse,
     collections::{BTreeMap, HashMap},
     net::SocketAddr,
     str,
@@ -16,15 +17,20 @@ use tracing::info;
 use warp_json_rpc::Builder;
 
 use super::{
-    ApiRequest, Error, ErrorCode, ReactorEventT, RpcWithParams, RpcWithParamsExt, RpcWithoutParams,
-    RpcWithoutParamsExt,
+    ApiRequest, Error, ErrorCode, ReactorEventT, RpcWithOptionalParams, RpcWithOptionalParamsExt,
+    RpcWithParams, RpcWithParamsExt, RpcWithoutParams, RpcWithoutParamsExt,
 };
 use crate::{
-    components::{api_server::CLIENT_API_VERSION, small_network::NodeId},
-    crypto::hash::Digest,
+    components::{
+        api_server::{DeployState, CLIENT_API_VERSION},
+        consensus::ParticipationReport,
+        deploy_buffer::PendingDeploy,
+        small_network::{NodeId, PeerLatency},
+    },
+    crypto::{asymmetric_key::PublicKey, hash::Digest},
     effect::EffectBuilder,
     reactor::QueueKind,
-    types::{json_compatibility::ExecutionResult, DeployHash},
+    types::{json_compatibility::ExecutionResult, DeployHash, Timestamp},
 };
 
 /// Params for "info_get_deploy" RPC request.
@@ -127,6 +133,195 @@ impl RpcWithParamsExt for GetDeploy {
     }
 }
 
+/// Params for "info_get_deploy_inclusion_proof" RPC request.
+#[derive(Serialize, Deserialize, Debug)]
+pub struct GetDeployInclusionProofParams {
+    /// Hex-encoded deploy hash.
+    pub deploy_hash: String,
+}
+
+/// Result for "info_get_deploy_inclusion_proof" RPC response.
+///
+/// The proof can be verified by hashing up from the deploy hash, pairing it with the siblings in
+/// order, and comparing the result against `deploy_root`.
+#[derive(Serialize, Deserialize, Debug)]
+pub struct GetDeployInclusionProofResult {
+    /// The RPC API version.
+    pub api_version: Version,
+    /// Hex-encoded hash of the block containing the deploy.
+    pub block_hash: String,
+    /// Hex-encoded Merkle root over the block's deploy hashes, as found in its header.
+    pub deploy_root: String,
+    /// The position of the deploy among the block's deploys.
+    pub index: u64,
+    /// The number of deploys in the block.
+    pub deploy_count: u64,
+    /// Hex-encoded sibling hashes on the path from the deploy to the root, starting at the bottom.
+    pub siblings: Vec<String>,
+}
+
+/// "info_get_deploy_inclusion_proof" RPC.
+pub struct GetDeployInclusionProof {}
+
+impl RpcWithParams for GetDeployInclusionProof {
+    const METHOD: &'static str = "info_get_deploy_inclusion_proof";
+    type RequestParams = GetDeployInclusionProofParams;
+    type ResponseResult = GetDeployInclusionProofResult;
+}
+
+impl RpcWithParamsExt for GetDeployInclusionProof {
+    fn handle_request<REv: ReactorEventT>(
+        effect_builder: EffectBuilder<REv>,
+        response_builder: Builder,
+        params: Self::RequestParams,
+    ) -> BoxFuture<'static, Result<Response<Body>, Error>> {
+        async move {
+            // Try to parse a deploy hash from the params.
+            let deploy_hash =
+                match Digest::from_hex(&params.deploy_hash).map_err(|error| error.to_string()) {
+                    Ok(digest) => DeployHash::new(digest),
+                    Err(error_msg) => {
+                        info!("failed to get deploy inclusion proof: {}", error_msg);
+                        return Ok(response_builder.error(warp_json_rpc::Error::custom(
+                            ErrorCode::ParseDeployHash as i64,
+                            error_msg,
+                        ))?);
+                    }
+                };
+
+            // Try to find a stored block containing the deploy.
+            let maybe_header_and_proof = effect_builder
+                .make_request(
+                    |responder| ApiRequest::GetDeployInclusionProof {
+                        hash: deploy_hash,
+                        responder,
+                    },
+                    QueueKind::Api,
+                )
+                .await;
+
+            let (header, proof) = match maybe_header_and_proof {
+                Some((header, proof)) => (header, proof),
+                None => {
+                    info!("failed to find a stored block containing {}", deploy_hash);
+                    return Ok(response_builder.error(warp_json_rpc::Error::custom(
+                        ErrorCode::NoSuchDeploy as i64,
+                        "deploy not included in any known block",
+                    ))?);
+                }
+            };
+
+            // Return the result.
+            let result = Self::ResponseResult {
+                api_version: CLIENT_API_VERSION.clone(),
+                block_hash: hex::encode(header.hash().as_ref()),
+                deploy_root: hex::encode(header.deploy_root()),
+                index: proof.index(),
+                deploy_count: proof.leaf_count(),
+                siblings: proof.siblings().iter().map(hex::encode).collect(),
+            };
+            Ok(response_builder.success(result)?)
+        }
+        .boxed()
+    }
+}
+
+/// Params for "info_get_deploy_status" RPC request.
+#[derive(Serialize, Deserialize, Debug)]
+pub struct GetDeployStatusParams {
+    /// Hex-encoded deploy hash.
+    pub deploy_hash: String,
+}
+
+/// Result for "info_get_deploy_status" RPC response.
+///
+/// The times are in milliseconds since the Unix epoch, and absent for states not entered yet or
+/// skipped, e.g. because the deploy was first seen in a block.
+#[derive(Serialize, Deserialize, Debug)]
+pub struct GetDeployStatusResult {
+    /// The RPC API version.
+    pub api_version: Version,
+    /// The current lifecycle state.
+    pub state: DeployState,
+    /// When the deploy was accepted.
+    pub accepted: Option<Timestamp>,
+    /// When gossiping the deploy finished.
+    pub gossiped: Option<Timestamp>,
+    /// When the deploy was included in a block.
+    pub included: Option<Timestamp>,
+    /// When the block including the deploy became final.
+    pub finalized: Option<Timestamp>,
+    /// Hex-encoded hash of the block including the deploy.
+    pub block_hash: Option<String>,
+}
+
+/// "info_get_deploy_status" RPC.
+pub struct GetDeployStatus {}
+
+impl RpcWithParams for GetDeployStatus {
+    const METHOD: &'static str = "info_get_deploy_status";
+    type RequestParams = GetDeployStatusParams;
+    type ResponseResult = GetDeployStatusResult;
+}
+
+impl RpcWithParamsExt for GetDeployStatus {
+    fn handle_request<REv: ReactorEventT>(
+        effect_builder: EffectBuilder<REv>,
+        response_builder: Builder,
+        params: Self::RequestParams,
+    ) -> BoxFuture<'static, Result<Response<Body>, Error>> {
+        async move {
+            // Try to parse a deploy hash from the params.
+            let deploy_hash =
+                match Digest::from_hex(&params.deploy_hash).map_err(|error| error.to_string()) {
+                    Ok(digest) => DeployHash::new(digest),
+                    Err(error_msg) => {
+                        info!("failed to get deploy status: {}", error_msg);
+                        return Ok(response_builder.error(warp_json_rpc::Error::custom(
+                            ErrorCode::ParseDeployHash as i64,
+                            error_msg,
+                        ))?);
+                    }
+                };
+
+            let maybe_status = effect_builder
+                .make_request(
+                    |responder| ApiRequest::GetDeployStatus {
+                        hash: deploy_hash,
+                        responder,
+                    },
+                    QueueKind::Api,
+                )
+                .await;
+
+            let status = match maybe_status {
+                Some(status) => status,
+                None => {
+                    info!("no status known for {}", deploy_hash);
+                    return Ok(response_builder.error(warp_json_rpc::Error::custom(
+                        ErrorCode::NoSuchDeploy as i64,
+                        "deploy status not known",
+                    ))?);
+                }
+            };
+
+            let result = Self::ResponseResult {
+                api_version: CLIENT_API_VERSION.clone(),
+                state: status.state,
+                accepted: status.accepted,
+                gossiped: status.gossiped,
+                included: status.included,
+                finalized: status.finalized,
+                block_hash: status
+                    .block_hash
+                    .map(|block_hash| hex::encode(block_hash.inner())),
+            };
+            Ok(response_builder.success(result)?)
+        }
+        .boxed()
+    }
+}
+
 /// Result for "info_get_peers" RPC response.
 #[derive(Serialize, Deserialize, Debug)]
 pub struct GetPeersResult {
@@ -177,6 +372,10 @@ pub struct GetStatusResult {
     pub peers: BTreeMap<String, SocketAddr>,
     /// The last block from the linear chain, JSON-encoded.
     pub last_finalized_block: Option<Value>,
+    /// Whether the node is ready to serve chain queries.
+    pub is_ready: bool,
+    /// If consensus has stalled, when the last block was finalized before.
+    pub consensus_stalled_since: Option<Timestamp>,
 }
 
 /// "info_get_status" RPC.
@@ -211,6 +410,8 @@ impl RpcWithoutParamsExt for GetStatus {
                 api_version: CLIENT_API_VERSION.clone(),
                 peers,
                 last_finalized_block,
+                is_ready: status_feed.is_ready,
+                consensus_stalled_since: status_feed.consensus_stalled_since,
             };
             Ok(response_builder.success(result)?)
         }
@@ -269,9 +470,567 @@ impl RpcWithoutParamsExt for GetMetrics {
     }
 }
 
+/// Number of deploys returned by "info_get_mempool" if no limit is given.
+const DEFAULT_MEMPOOL_LIMIT: u64 = 100;
+/// Maximum number of deploys returned by a single "info_get_mempool" request.  Larger limits are
+/// clamped to this value.
+const MAX_MEMPOOL_LIMIT: u64 = 1_000;
+
+/// The order in which "info_get_mempool" returns pending deploys.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum MempoolSortOrder {
+    /// Highest gas price first.
+    Fee,
+    /// Oldest first.
+    Age,
+}
+
+impl Default for MempoolSortOrder {
+    fn default() -> Self {
+        MempoolSortOrder::Fee
+    }
+}
+
+/// Params for "info_get_mempool" RPC request.
+#[derive(Serialize, Deserialize, Debug, Default)]
+pub struct GetMempoolParams {
+    /// Maximum number of deploys to return.
+    #[serde(default)]
+    pub limit: Option<u64>,
+    /// The order in which to return the deploys.
+    #[serde(default)]
+    pub sort_by: MempoolSortOrder,
+}
+
+/// A deploy pending in the deploy buffer.
+#[derive(Serialize, Deserialize, Debug, PartialEq, Eq)]
+pub struct JsonPendingDeploy {
+    /// Hex-encoded deploy hash.
+    pub deploy_hash: String,
+    /// The gas price offered by the deploy.
+    pub gas_price: u64,
+    /// Milliseconds elapsed since the deploy's creation.
+    pub age_ms: u64,
+}
+
+/// Result for "info_get_mempool" RPC response.
+#[derive(Serialize, Deserialize, Debug)]
+pub struct GetMempoolResult {
+    /// The RPC API version.
+    pub api_version: Version,
+    /// The total number of pending deploys.
+    pub pending_count: u64,
+    /// The pending deploys in the requested order, up to the requested limit.
+    pub deploys: Vec<JsonPendingDeploy>,
+}
+
+/// "info_get_mempool" RPC.
+pub struct GetMempool {}
+
+impl RpcWithOptionalParams for GetMempool {
+    const METHOD: &'static str = "info_get_mempool";
+    type OptionalRequestParams = GetMempoolParams;
+    type ResponseResult = GetMempoolResult;
+}
+
+impl RpcWithOptionalParamsExt for GetMempool {
+    fn handle_request<REv: ReactorEventT>(
+        effect_builder: EffectBuilder<REv>,
+        response_builder: Builder,
+        maybe_params: Option<Self::OptionalRequestParams>,
+    ) -> BoxFuture<'static, Result<Response<Body>, Error>> {
+        async move {
+            let pending_deploys = effect_builder
+                .make_request(
+                    |responder| ApiRequest::GetMempool { responder },
+                    QueueKind::Api,
+                )
+                .await;
+
+            let result = Self::ResponseResult {
+                api_version: CLIENT_API_VERSION.clone(),
+                pending_count: pending_deploys.len() as u64,
+                deploys: select_pending_deploys(
+                    pending_deploys,
+                    maybe_params.as_ref(),
+                    Timestamp::now(),
+                ),
+            };
+            Ok(response_builder.success(result)?)
+        }
+        .boxed()
+    }
+}
+
+/// Returns the pending deploys in the requested order, up to the requested limit, with their ages
+/// as of `now`.
+fn select_pending_deploys(
+    mut pending_deploys: Vec<PendingDeploy>,
+    maybe_params: Option<&GetMempoolParams>,
+    now: Timestamp,
+) -> Vec<JsonPendingDeploy> {
+    let limit = maybe_params
+        .and_then(|params| params.limit)
+        .unwrap_or(DEFAULT_MEMPOOL_LIMIT)
+        .min(MAX_MEMPOOL_LIMIT);
+    // Ties are broken by the other criterion, then by hash, so the order is deterministic.
+    match maybe_params
+        .map(|params| params.sort_by)
+        .unwrap_or_default()
+    {
+        MempoolSortOrder::Fee => pending_deploys.sort_unstable_by_key(|deploy| {
+            (Reverse(deploy.gas_price), deploy.timestamp, deploy.hash)
+        }),
+        MempoolSortOrder::Age => pending_deploys.sort_unstable_by_key(|deploy| {
+            (deploy.timestamp, Reverse(deploy.gas_price), deploy.hash)
+        }),
+    }
+    pending_deploys
+        .into_iter()
+        .take(limit as usize)
+        .map(|deploy| JsonPendingDeploy {
+            deploy_hash: hex::encode(deploy.hash.inner()),
+            gas_price: deploy.gas_price,
+            age_ms: now.saturating_sub(deploy.timestamp).millis(),
+        })
+        .collect()
+}
+
+/// The latest round-trip time measured to a peer.
+#[derive(Serialize, Deserialize, Debug, PartialEq, Eq)]
+pub struct JsonPeerLatency {
+    /// The round-trip time in milliseconds.
+    pub rtt_ms: u64,
+    /// When the round-trip time was measured, in milliseconds since the Unix epoch.
+    pub last_measured: Timestamp,
+    /// Whether the measurement is outdated, e.g. because the peer has stopped answering pings.
+    pub stale: bool,
+}
+
+/// Result for "info_get_peer_latencies" RPC response.
+#[derive(Serialize, Deserialize, Debug)]
+pub struct GetPeerLatenciesResult {
+    /// The RPC API version.
+    pub api_version: Version,
+    /// The latest round-trip time measured to each peer, by node ID.
+    pub latencies: BTreeMap<String, JsonPeerLatency>,
+}
+
+/// "info_get_peer_latencies" RPC.
+pub struct GetPeerLatencies {}
+
+impl RpcWithoutParams for GetPeerLatencies {
+    const METHOD: &'static str = "info_get_peer_latencies";
+    type ResponseResult = GetPeerLatenciesResult;
+}
+
+impl RpcWithoutParamsExt for GetPeerLatencies {
+    fn handle_request<REv: ReactorEventT>(
+        effect_builder: EffectBuilder<REv>,
+        response_builder: Builder,
+    ) -> BoxFuture<'static, Result<Response<Body>, Error>> {
+        async move {
+            let latencies = effect_builder
+                .make_request(
+                    |responder| ApiRequest::GetPeerLatencies { responder },
+                    QueueKind::Api,
+                )
+                .await;
+
+            let result = Self::ResponseResult {
+                api_version: CLIENT_API_VERSION.clone(),
+                latencies: latencies_to_json(latencies),
+            };
+            Ok(response_builder.success(result)?)
+        }
+        .boxed()
+    }
+}
+
+/// The participation of a validator in the current era.
+#[derive(Serialize, Deserialize, Debug, PartialEq)]
+pub struct JsonValidatorParticipation {
+    /// Hex-encoded public key of the validator.
+    pub public_key: String,
+    /// The validator's weight in the era.
+    pub weight: u64,
+    /// The number of finalized blocks the validator proposed.
+    pub proposals: u64,
+    /// The number of finalized blocks which rewarded the validator for its votes.
+    pub votes: u64,
+    /// The number of blocks the validator signed as final.
+    pub finality_signatures: u64,
+    /// The fraction of the era's finalized blocks the validator contributed to, absent until the
+    /// era's first block is finalized.
+    pub participation_rate: Option<f64>,
+    /// Whether the validator's participation rate is below the inactivity threshold.
+    pub inactive: bool,
+}
+
+/// Result for "info_get_validator_participation" RPC response.
+#[derive(Serialize, Deserialize, Debug)]
+pub struct GetValidatorParticipationResult {
+    /// The RPC API version.
+    pub api_version: Version,
+    /// The current era.
+    pub era_id: u64,
+    /// The number of blocks finalized in the current era.
+    pub block_count: u64,
+    /// The participation rate below which a validator is reported as inactive.
+    pub inactivity_threshold: f64,
+    /// The participation of each validator in the current era.
+    pub validators: Vec<JsonValidatorParticipation>,
+}
+
+/// "info_get_validator_participation" RPC.
+pub struct GetValidatorParticipation {}
+
+impl RpcWithoutParams for GetValidatorParticipation {
+    const METHOD: &'static str = "info_get_validator_participation";
+    type ResponseResult = GetValidatorParticipationResult;
+}
+
+impl RpcWithoutParamsExt for GetValidatorParticipation {
+    fn handle_request<REv: ReactorEventT>(
+        effect_builder: EffectBuilder<REv>,
+        response_builder: Builder,
+    ) -> BoxFuture<'static, Result<Response<Body>, Error>> {
+        async move {
+            let maybe_report = effect_builder
+                .make_request(
+                    |responder| ApiRequest::GetValidatorParticipation { responder },
+                    QueueKind::Api,
+                )
+                .await;
+
+            match maybe_report {
+                Some(report) => Ok(response_builder.success(participation_to_json(report))?),
+                None => {
+                    info!("validator participation not available");
+                    Ok(response_builder.error(warp_json_rpc::Error::custom(
+                        ErrorCode::ParticipationNotAvailable as i64,
+                        "validator participation not available",
+                    ))?)
+                }
+            }
+        }
+        .boxed()
+    }
+}
+
+fn participation_to_json(
+    report: ParticipationReport<PublicKey>,
+) -> GetValidatorParticipationResult {
+    let validators = report
+        .validators
+        .into_iter()
+        .map(|participation| JsonValidatorParticipation {
+            public_key: participation.validator.to_hex(),
+            weight: participation.weight,
+            proposals: participation.proposals,
+            votes: participation.votes,
+            finality_signatures: participation.finality_signatures,
+            participation_rate: participation.participation_rate,
+            inactive: participation.inactive,
+        })
+        .collect();
+    GetValidatorParticipationResult {
+        api_version: CLIENT_API_VERSION.clone(),
+        era_id: report.era_id.0,
+        block_count: report.block_count,
+        inactivity_threshold: report.inactivity_threshold,
+        validators,
+    }
+}
+
+fn latencies_to_json(latencies: HashMap<NodeId, PeerLatency>) -> BTreeMap<String, JsonPeerLatency> {
+    latencies
+        .into_iter()
+        .map(|(node_id, latency)| {
+            let json_latency = JsonPeerLatency {
+                rtt_ms: latency.rtt.as_millis() as u64,
+                last_measured: latency.measured_at,
+                stale: latency.stale,
+            };
+            (format!("{}", node_id), json_latency)
+        })
+        .collect()
+}
+
 fn peers_hashmap_to_btreemap(peers: HashMap<NodeId, SocketAddr>) -> BTreeMap<String, SocketAddr> {
     peers
         .into_iter()
         .map(|(node_id, address)| (format!("{}", node_id), address))
         .collect()
 }
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use derive_more::From;
+    use futures::future;
+    use http::{Request, StatusCode};
+    use hyper::service::Service;
+    use rand::Rng;
+    use serde_json::json;
+    use warp::filters::BoxedFilter;
+
+    use super::{super::RPC_API_PATH, *};
+    use crate::{
+        components::{
+            api_server::{DeployStatus, Event},
+            storage::Storage,
+        },
+        effect::requests::{ContractRuntimeRequest, LinearChainRequest, StorageRequest},
+        reactor::{EventQueueHandle, Scheduler},
+        testing::TestRng,
+        types::BlockHash,
+        utils,
+    };
+
+    #[derive(Debug, From)]
+    enum ReactorEvent {
+        #[from]
+        ApiServer(Event),
+        #[from]
+        ApiRequest(ApiRequest<NodeId>),
+        #[from]
+        Storage(StorageRequest<Storage>),
+        #[from]
+        LinearChain(LinearChainRequest<NodeId>),
+        #[from]
+        ContractRuntime(ContractRuntimeRequest),
+    }
+
+    /// Calls the RPC `method` served by the filter `create_filter` returns with the given params,
+    /// answering the API requests it makes with `pending_deploys` and `deploy_status`, and returns
+    /// the response's JSON body.
+    async fn call<F>(
+        create_filter: F,
+        method: &str,
+        params: Value,
+        pending_deploys: Vec<PendingDeploy>,
+        deploy_status: Option<DeployStatus>,
+    ) -> Value
+    where
+        F: FnOnce(EffectBuilder<ReactorEvent>) -> BoxedFilter<(Response<Body>,)>,
+    {
+        let scheduler = utils::leak(Scheduler::<ReactorEvent>::new(QueueKind::weights()));
+        let effect_builder = EffectBuilder::new(EventQueueHandle::new(scheduler));
+        let mut service = warp_json_rpc::service(create_filter(effect_builder));
+        future::poll_fn(|cx| service.poll_ready(cx)).await.unwrap();
+
+        let body = json!({ "jsonrpc": "2.0", "id": 1, "method": method, "params": params });
+        let request = Request::post(format!("/{}", RPC_API_PATH))
+            .header("content-type", "application/json")
+            .body(Body::from(body.to_string()))
+            .unwrap();
+        let mut response = tokio::spawn(service.call(request));
+
+        let response = loop {
+            tokio::select! {
+                response = &mut response => break response.unwrap().unwrap(),
+                (event, _) = scheduler.pop() => match event {
+                    ReactorEvent::ApiRequest(ApiRequest::GetMempool { responder }) => {
+                        responder.respond(pending_deploys.clone()).await
+                    }
+                    ReactorEvent::ApiRequest(ApiRequest::GetDeployStatus { responder, .. }) => {
+                        responder.respond(deploy_status.clone()).await
+                    }
+                    other => panic!("unexpected event {:?}", other),
+                },
+            }
+        };
+        assert_eq!(response.status(), StatusCode::OK);
+        let body = hyper::body::to_bytes(response.into_body()).await.unwrap();
+        serde_json::from_slice(&body).unwrap()
+    }
+
+    fn pending_deploy(rng: &mut TestRng, gas_price: u64, timestamp: u64) -> PendingDeploy {
+        PendingDeploy {
+            hash: DeployHash::new(Digest::random(rng)),
+            gas_price,
+            timestamp: Timestamp::from(timestamp),
+        }
+    }
+
+    #[test]
+    fn should_sort_pending_deploys_by_fee_or_age() {
+        let mut rng = TestRng::new();
+        let cheap_old = pending_deploy(&mut rng, 1, 100);
+        let pricey_new = pending_deploy(&mut rng, 5, 300);
+        let medium = pending_deploy(&mut rng, 3, 200);
+        let pending = vec![cheap_old, pricey_new, medium];
+        let now = Timestamp::from(1_000);
+        let gas_prices = |deploys: Vec<JsonPendingDeploy>| {
+            deploys
+                .iter()
+                .map(|deploy| deploy.gas_price)
+                .collect::<Vec<_>>()
+        };
+
+        let by_fee = select_pending_deploys(pending.clone(), None, now);
+        assert_eq!(gas_prices(by_fee), vec![5, 3, 1]);
+
+        let params: GetMempoolParams =
+            serde_json::from_str(r#"{"sort_by": "age"}"#).expect("should parse params");
+        let by_age = select_pending_deploys(pending, Some(&params), now);
+        assert_eq!(by_age[0].deploy_hash, hex::encode(cheap_old.hash.inner()));
+        assert_eq!(by_age[0].age_ms, 900);
+        assert_eq!(gas_prices(by_age), vec![1, 3, 5]);
+    }
+
+    #[test]
+    fn should_respect_mempool_limit() {
+        let mut rng = TestRng::new();
+        let pending: Vec<_> = (0..5)
+            .map(|gas_price| pending_deploy(&mut rng, gas_price, 100))
+            .collect();
+        let now = Timestamp::from(1_000);
+
+        let params = GetMempoolParams {
+            limit: Some(2),
+            sort_by: MempoolSortOrder::Fee,
+        };
+        let deploys = select_pending_deploys(pending.clone(), Some(&params), now);
+        assert_eq!(deploys.len(), 2);
+        assert_eq!(deploys[0].gas_price, 4);
+        assert_eq!(deploys[1].gas_price, 3);
+
+        let params: GetMempoolParams =
+            serde_json::from_str(r#"{"limit": 1000000}"#).expect("should parse params");
+        assert_eq!(params.sort_by, MempoolSortOrder::Fee);
+        let deploys = select_pending_deploys(pending, Some(&params), now);
+        assert_eq!(deploys.len(), 5);
+    }
+
+    #[tokio::test]
+    async fn should_serve_pending_deploys_up_to_limit() {
+        let mut rng = TestRng::new();
+        let pending: Vec<_> = (1..=3)
+            .map(|gas_price| pending_deploy(&mut rng, gas_price, 100))
+            .collect();
+
+        let params = json!({ "limit": 2, "sort_by": "fee" });
+        let response = call(
+            GetMempool::create_filter,
+            GetMempool::METHOD,
+            params,
+            pending.clone(),
+            None,
+        )
+        .await;
+        let result = &response["result"];
+        assert_eq!(result["pending_count"], json!(3));
+        let deploys: Vec<JsonPendingDeploy> =
+            serde_json::from_value(result["deploys"].clone()).expect("should parse deploys");
+        assert_eq!(deploys.len(), 2);
+        assert_eq!(deploys[0].deploy_hash, hex::encode(pending[2].hash.inner()));
+        assert_eq!(deploys[1].gas_price, 2);
+
+        // Without params, all deploys are returned, as the default limit isn't reached.
+        let response = call(
+            GetMempool::create_filter,
+            GetMempool::METHOD,
+            json!({}),
+            pending,
+            None,
+        )
+        .await;
+        assert_eq!(response["result"]["deploys"].as_array().unwrap().len(), 3);
+    }
+
+    #[tokio::test]
+    async fn should_serve_deploy_status() {
+        let mut rng = TestRng::new();
+        let deploy_hash = hex::encode(Digest::random(&mut rng).as_ref());
+        let block_hash = BlockHash::new(Digest::random(&mut rng));
+        let status = DeployStatus {
+            state: DeployState::Included,
+            accepted: Some(Timestamp::from(1_000)),
+            gossiped: None,
+            included: Some(Timestamp::from(3_000)),
+            finalized: None,
+            block_hash: Some(block_hash),
+        };
+        let params = json!({ "deploy_hash": deploy_hash });
+
+        let response = call(
+            GetDeployStatus::create_filter,
+            GetDeployStatus::METHOD,
+            params.clone(),
+            vec![],
+            Some(status),
+        )
+        .await;
+        let result = &response["result"];
+        assert_eq!(result["state"], json!("included"));
+        assert_eq!(result["accepted"], json!(1_000));
+        assert_eq!(result["gossiped"], Value::Null);
+        assert_eq!(result["included"], json!(3_000));
+        assert_eq!(result["block_hash"], json!(hex::encode(block_hash.inner())));
+
+        let response = call(
+            GetDeployStatus::create_filter,
+            GetDeployStatus::METHOD,
+            params,
+            vec![],
+            None,
+        )
+        .await;
+        assert_eq!(
+            response["error"]["code"],
+            json!(ErrorCode::NoSuchDeploy as i64)
+        );
+
+        let params = json!({ "deploy_hash": "not a hash" });
+        let response = call(
+            GetDeployStatus::create_filter,
+            GetDeployStatus::METHOD,
+            params,
+            vec![],
+            None,
+        )
+        .await;
+        assert_eq!(
+            response["error"]["code"],
+            json!(ErrorCode::ParseDeployHash as i64)
+        );
+    }
+
+    #[test]
+    fn should_return_peer_latencies_with_freshness() {
+        let mut rng = TestRng::new();
+        let fresh_peer: NodeId = rng.gen();
+        let stale_peer: NodeId = rng.gen();
+        let mut latencies = HashMap::new();
+        let _ = latencies.insert(
+            fresh_peer,
+            PeerLatency {
+                rtt: Duration::from_millis(12),
+                measured_at: Timestamp::from(2_000),
+                stale: false,
+            },
+        );
+        let _ = latencies.insert(
+            stale_peer,
+            PeerLatency {
+                rtt: Duration::from_millis(250),
+                measured_at: Timestamp::from(1_000),
+                stale: true,
+            },
+        );
+
+        let json = serde_json::to_value(latencies_to_json(latencies)).expect("should serialize");
+        assert_eq!(
+            json[fresh_peer.to_string()],
+            serde_json::json!({"rtt_ms": 12, "last_measured": 2000, "stale": false})
+        );
+        assert_eq!(
+            json[stale_peer.to_string()],
+            serde_json::json!({"rtt_ms": 250, "last_measured": 1000, "stale": true})
+        );
+    }
+}
@@ -24,7 +24,10 @@ use casper_execution_engine::{
 use casper_types::ProtocolVersion;
 
 use crate::{
-    components::{storage::Storage, Component},
+    components::{
+        storage::{Storage, StorageFailure},
+        Component,
+    },
     crypto::hash::Digest,
     effect::{
         announcements::BlockExecutorAnnouncement,
@@ -66,8 +69,9 @@ pub enum Event {
     GetDeploysResult {
         /// The block that needs the deploys for execution.
         finalized_block: FinalizedBlock,
-        /// Contents of deploys. All deploys are expected to be present in the storage component.
-        deploys: VecDeque<Deploy>,
+        /// Contents of deploys. All deploys are expected to be present in the storage component,
+        /// unless storage failed and the node is shutting down.
+        deploys: Result<VecDeque<Deploy>, StorageFailure>,
     },
     /// The result of executing a single deploy.
     DeployExecutionResult {
@@ -93,13 +97,22 @@ impl Display for Event {
             Event::Request(req) => write!(f, "{}", req),
             Event::GetDeploysResult {
                 finalized_block,
-                deploys,
+                deploys: Ok(deploys),
             } => write!(
                 f,
                 "fetch deploys for finalized block with height {} has {} deploys",
                 finalized_block.height(),
                 deploys.len()
             ),
+            Event::GetDeploysResult {
+                finalized_block,
+                deploys: Err(error),
+            } => write!(
+                f,
+                "fetch deploys for finalized block with height {} failed: {}",
+                finalized_block.height(),
+                error
+            ),
             Event::DeployExecutionResult {
                 state,
                 deploy_hash,
@@ -225,13 +238,15 @@ impl BlockExecutor {
             .get_deploys_from_storage(deploy_hashes)
             .event(move |result| Event::GetDeploysResult {
                 finalized_block,
-                deploys: result
-                    .into_iter()
-                    // Assumes all deploys are present
-                    .map(|maybe_deploy| {
-                        maybe_deploy.expect("deploy is expected to exist in the storage")
-                    })
-                    .collect(),
+                deploys: result.map(|results| {
+                    results
+                        .into_iter()
+                        // Assumes all deploys are present
+                        .map(|maybe_deploy| {
+                            maybe_deploy.expect("deploy is expected to exist in the storage")
+                        })
+                        .collect()
+                }),
             })
     }
 
@@ -398,7 +413,7 @@ impl<REv: ReactorEventT, R: Rng + CryptoRng + ?Sized> Component<REv, R> for Bloc
                         .immediately()
                         .event(move |_| Event::GetDeploysResult {
                             finalized_block,
-                            deploys: VecDeque::new(),
+                            deploys: Ok(VecDeque::new()),
                         })
                 } else {
                     self.get_deploys(effect_builder, finalized_block)
@@ -407,12 +422,20 @@ impl<REv: ReactorEventT, R: Rng + CryptoRng + ?Sized> Component<REv, R> for Bloc
 
             Event::GetDeploysResult {
                 finalized_block,
-                deploys,
+                deploys: Ok(deploys),
             } => {
                 trace!(total = %deploys.len(), ?deploys, "fetched deploys");
                 self.handle_get_deploys_result(effect_builder, finalized_block, deploys)
             }
 
+            Event::GetDeploysResult {
+                finalized_block,
+                deploys: Err(error),
+            } => {
+                debug!(height = finalized_block.height(), %error, "not executing block");
+                Effects::new()
+            }
+
             Event::DeployExecutionResult {
                 state,
                 deploy_hash,
@@ -0,0 +1,261 @@
+//! Chain follower.
+//!
+//! Observers don't run consensus, so they don't learn of new blocks by finalizing them.  Instead,
+//! they follow the linear chain built by the validators: Once a validator has executed a block, it
+//! broadcasts its finality signature of the block's hash to all its peers.  On receiving a valid
+//! signature by a known validator of a block it doesn't hold yet, the chain follower fetches the
+//! block from the peer that sent the signature, fetches the block's deploys and hands the block to
+//! the block executor.  Executing it adds the block to our own linear chain.
+//!
+//! If the parent of a fetched block is missing too, e.g. because the observer was disconnected for
+//! a while, the parent is fetched as well, until a block already held in storage is reached.  The
+//! block executor holds back every block until its parent has been executed, so blocks may be
+//! fetched in any order.
+//!
+//! Every block is requested at most once at a time, and once it has been handed to the block
+//! executor, it isn't requested again.  If fetching a block or its deploys fails, the block is
+//! requested again once the next signature of it arrives.
+
+use std::{
+    collections::{BTreeMap, HashSet},
+    fmt::{self, Display, Formatter},
+};
+
+use rand::{CryptoRng, Rng};
+use tracing::{debug, warn};
+
+use super::{fetcher::FetchResult, small_network::NodeId, Component};
+use crate::{
+    crypto::asymmetric_key::PublicKey,
+    effect::{
+        requests::{BlockExecutorRequest, BlockValidationRequest, FetcherRequest},
+        EffectBuilder, EffectExt, EffectOptionExt, Effects,
+    },
+    types::{Block, BlockHash, FinalitySignature},
+};
+
+/// A chain follower event.
+#[derive(Debug)]
+pub enum Event {
+    /// A peer has sent a validator's finality signature of a block.
+    BlockSigned {
+        /// The signature.
+        finality_signature: Box<FinalitySignature>,
+        /// The peer which sent the signature.
+        sender: NodeId,
+    },
+    /// The result of fetching a block.
+    GetBlockResult {
+        /// The hash of the requested block.
+        block_hash: BlockHash,
+        /// The fetched block, or `None` if fetching it failed.
+        fetch_result: Option<FetchResult<Block>>,
+    },
+    /// The result of fetching the deploys of a block.
+    BlockValidated {
+        /// The block.
+        block: Box<Block>,
+        /// Whether all its deploys have been found.
+        deploys_found: bool,
+    },
+    /// A block has been added to our linear chain.
+    BlockAdded {
+        /// The block's height.
+        height: u64,
+    },
+}
+
+impl Display for Event {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            Event::BlockSigned {
+                finality_signature,
+                sender,
+            } => write!(
+                f,
+                "chain-follower received {} from {}",
+                finality_signature, sender
+            ),
+            Event::GetBlockResult {
+                block_hash,
+                fetch_result,
+            } => write!(
+                f,
+                "chain-follower get-block for {} found: {}",
+                block_hash,
+                fetch_result.is_some()
+            ),
+            Event::BlockValidated {
+                block,
+                deploys_found,
+            } => write!(
+                f,
+                "chain-follower validated {}, deploys found: {}",
+                block.hash(),
+                deploys_found
+            ),
+            Event::BlockAdded { height } => {
+                write!(f, "chain-follower block at height {} added", height)
+            }
+        }
+    }
+}
+
+/// Chain follower.
+#[derive(Debug)]
+pub(crate) struct ChainFollower {
+    /// The validators whose signatures are followed.
+    validators: HashSet<PublicKey>,
+    /// The height of the highest block in our linear chain, if any.
+    highest_height: Option<u64>,
+    /// The blocks being fetched, having their deploys fetched, or being executed.
+    in_flight: HashSet<BlockHash>,
+    /// The blocks handed to the block executor but not yet added to our linear chain, by height.
+    executing: BTreeMap<u64, BlockHash>,
+}
+
+impl ChainFollower {
+    /// Creates a new chain follower following the signatures of `validators`, where `linear_chain`
+    /// is the part of the linear chain we already hold.
+    pub(crate) fn new<V>(validators: V, linear_chain: &[Block]) -> Self
+    where
+        V: IntoIterator<Item = PublicKey>,
+    {
+        ChainFollower {
+            validators: validators.into_iter().collect(),
+            highest_height: linear_chain.iter().map(Block::height).max(),
+            in_flight: HashSet::new(),
+            executing: BTreeMap::new(),
+        }
+    }
+
+    /// Returns whether the block at `height` has already been added to our linear chain or handed
+    /// to the block executor.
+    fn is_known_height(&self, height: u64) -> bool {
+        self.highest_height
+            .map_or(false, |highest_height| height <= highest_height)
+            || self.executing.contains_key(&height)
+    }
+
+    /// Fetches the block with the given hash from `peer`, unless it is already in flight.
+    fn fetch_block<REv>(
+        &mut self,
+        effect_builder: EffectBuilder<REv>,
+        block_hash: BlockHash,
+        peer: NodeId,
+    ) -> Effects<Event>
+    where
+        REv: From<FetcherRequest<NodeId, Block>> + Send,
+    {
+        if !self.in_flight.insert(block_hash) {
+            return Effects::new();
+        }
+        effect_builder.fetch_block(block_hash, peer).option(
+            move |fetch_result| Event::GetBlockResult {
+                block_hash,
+                fetch_result: Some(fetch_result),
+            },
+            move || Event::GetBlockResult {
+                block_hash,
+                fetch_result: None,
+            },
+        )
+    }
+}
+
+impl<REv, R> Component<REv, R> for ChainFollower
+where
+    REv: From<FetcherRequest<NodeId, Block>>
+        + From<BlockValidationRequest<Box<Block>, NodeId>>
+        + From<BlockExecutorRequest>
+        + Send,
+    R: Rng + CryptoRng + ?Sized,
+{
+    type Event = Event;
+
+    fn handle_event(
+        &mut self,
+        effect_builder: EffectBuilder<REv>,
+        _rng: &mut R,
+        event: Self::Event,
+    ) -> Effects<Self::Event> {
+        match event {
+            Event::BlockSigned {
+                finality_signature,
+                sender,
+            } => {
+                if !self.validators.contains(&finality_signature.public_key) {
+                    debug!(%finality_signature, %sender, "ignoring signature by unknown validator");
+                    return Effects::new();
+                }
+                if let Err(error) = finality_signature.verify() {
+                    warn!(%finality_signature, %sender, %error, "ignoring invalid signature");
+                    return Effects::new();
+                }
+                self.fetch_block(effect_builder, finality_signature.block_hash, sender)
+            }
+            Event::GetBlockResult {
+                block_hash,
+                fetch_result,
+            } => match fetch_result {
+                None | Some(FetchResult::FromStorage(_)) => {
+                    // Either we'll retry on the next signature, or we already hold the block.
+                    self.in_flight.remove(&block_hash);
+                    Effects::new()
+                }
+                Some(FetchResult::FromPeer(block, peer)) => {
+                    if *block.hash() != block_hash {
+                        warn!(%block_hash, %peer, "received block with unexpected hash");
+                        self.in_flight.remove(&block_hash);
+                        return Effects::new();
+                    }
+                    if self.is_known_height(block.height()) {
+                        self.in_flight.remove(&block_hash);
+                        return Effects::new();
+                    }
+                    let mut effects = Effects::new();
+                    if !block.is_genesis_child() && !self.is_known_height(block.height() - 1) {
+                        effects.extend(self.fetch_block(
+                            effect_builder,
+                            *block.parent_hash(),
+                            peer,
+                        ));
+                    }
+                    effects.extend(effect_builder.validate_block(peer, block).event(
+                        |(deploys_found, block)| Event::BlockValidated {
+                            block,
+                            deploys_found,
+                        },
+                    ));
+                    effects
+                }
+            },
+            Event::BlockValidated {
+                block,
+                deploys_found,
+            } => {
+                let block_hash = *block.hash();
+                if !deploys_found {
+                    warn!(%block_hash, "could not fetch the deploys of the block");
+                    self.in_flight.remove(&block_hash);
+                    return Effects::new();
+                }
+                if self.is_known_height(block.height()) {
+                    self.in_flight.remove(&block_hash);
+                    return Effects::new();
+                }
+                let _ = self.executing.insert(block.height(), block_hash);
+                effect_builder.execute_block((*block).into()).ignore()
+            }
+            Event::BlockAdded { height } => {
+                self.highest_height = Some(self.highest_height.map_or(height, |h| h.max(height)));
+                let still_executing = self.executing.split_off(&(height + 1));
+                for block_hash in self.executing.values() {
+                    self.in_flight.remove(block_hash);
+                }
+                self.executing = still_executing;
+                Effects::new()
+            }
+        }
+    }
+}
@@ -21,6 +21,7 @@ use super::{config, error::GenesisLoadError, Error};
 #[cfg(test)]
 use crate::testing::TestRng;
 use crate::{
+    components::consensus::LeaderSeed,
     crypto::asymmetric_key::PublicKey,
     types::{TimeDiff, Timestamp},
     utils::Loadable,
@@ -35,6 +36,9 @@ pub(crate) struct DeployConfig {
     pub(crate) max_dependencies: u8,
     pub(crate) max_block_size: u32,
     pub(crate) block_gas_limit: u64,
+    /// Deploys with a timestamp before this must be hashed in the legacy layout, all others in the
+    /// canonical layout.
+    pub(crate) canonical_hash_timestamp: Timestamp,
 }
 
 impl Default for DeployConfig {
@@ -45,6 +49,7 @@ impl Default for DeployConfig {
             max_dependencies: 10,
             max_block_size: 10_485_760,
             block_gas_limit: 10_000_000_000_000,
+            canonical_hash_timestamp: Timestamp::zero(),
         }
     }
 }
@@ -60,6 +65,7 @@ impl DeployConfig {
         let max_dependencies = rng.gen();
         let max_block_size = rng.gen_range(1_000_000, 1_000_000_000);
         let block_gas_limit = rng.gen_range(100_000_000_000, 1_000_000_000_000_000);
+        let canonical_hash_timestamp = Timestamp::random(rng);
 
         DeployConfig {
             max_payment_cost,
@@ -67,6 +73,7 @@ impl DeployConfig {
             max_dependencies,
             max_block_size,
             block_gas_limit,
+            canonical_hash_timestamp,
         }
     }
 }
@@ -88,6 +95,9 @@ pub(crate) struct HighwayConfig {
     pub(crate) voting_period_duration: TimeDiff,
     pub(crate) finality_threshold_percent: u8,
     pub(crate) minimum_round_exponent: u8,
+    pub(crate) leader_seed: LeaderSeed,
+    pub(crate) max_proposal_deploy_count: u32,
+    pub(crate) max_proposal_size: u32,
 }
 
 impl Default for HighwayConfig {
@@ -101,6 +111,9 @@ impl Default for HighwayConfig {
             voting_period_duration: TimeDiff::from(172_800_000), // 2 days
             finality_threshold_percent: 10,
             minimum_round_exponent: 14, // 2**14 ms = ~16 seconds
+            leader_seed: LeaderSeed::default(),
+            max_proposal_deploy_count: 1_000,
+            max_proposal_size: 1_048_576, // 1 MiB
         }
     }
 }
@@ -118,6 +131,13 @@ impl HighwayConfig {
             voting_period_duration: TimeDiff::from(rng.gen_range(600_000, 172_800_000)),
             finality_threshold_percent: rng.gen_range(0, 101),
             minimum_round_exponent: rng.gen_range(0, 20),
+            leader_seed: if rng.gen() {
+                LeaderSeed::Constant
+            } else {
+                LeaderSeed::PreviousEraBlock
+            },
+            max_proposal_deploy_count: rng.gen_range(0, 10_000),
+            max_proposal_size: rng.gen_range(0, 10_485_760),
         }
     }
 }
@@ -412,6 +432,12 @@ mod tests {
         );
         assert_eq!(spec.genesis.highway_config.finality_threshold_percent, 8);
         assert_eq!(spec.genesis.highway_config.minimum_round_exponent, 13);
+        assert_eq!(
+            spec.genesis.highway_config.leader_seed,
+            LeaderSeed::Constant
+        );
+        assert_eq!(spec.genesis.highway_config.max_proposal_deploy_count, 14);
+        assert_eq!(spec.genesis.highway_config.max_proposal_size, 15);
 
         assert_eq!(
             spec.genesis.deploy_config.max_payment_cost,
@@ -421,6 +447,10 @@ mod tests {
         assert_eq!(spec.genesis.deploy_config.max_dependencies, 11);
         assert_eq!(spec.genesis.deploy_config.max_block_size, 12);
         assert_eq!(spec.genesis.deploy_config.block_gas_limit, 13);
+        assert_eq!(
+            spec.genesis.deploy_config.canonical_hash_timestamp,
+            Timestamp::from(16)
+        );
 
         assert_eq!(spec.genesis.costs.regular, 13);
         assert_eq!(spec.genesis.costs.div, 14);
@@ -464,6 +494,10 @@ mod tests {
         assert_eq!(upgrade0.new_deploy_config.unwrap().max_dependencies, 36);
         assert_eq!(upgrade0.new_deploy_config.unwrap().max_block_size, 37);
         assert_eq!(upgrade0.new_deploy_config.unwrap().block_gas_limit, 38);
+        assert_eq!(
+            upgrade0.new_deploy_config.unwrap().canonical_hash_timestamp,
+            Timestamp::from(40)
+        );
 
         let upgrade1 = &spec.upgrades[1];
         assert_eq!(upgrade1.activation_point, ActivationPoint { rank: 39 });
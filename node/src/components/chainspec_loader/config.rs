@@ -16,6 +16,7 @@ use casper_types::U512;
 
 use super::{chainspec, Error};
 use crate::{
+    components::consensus::LeaderSeed,
     types::{TimeDiff, Timestamp},
     utils::{read_file, External},
 };
@@ -37,6 +38,7 @@ pub struct DeployConfig {
     max_dependencies: u8,
     max_block_size: u32,
     block_gas_limit: u64,
+    canonical_hash_timestamp: Timestamp,
 }
 
 impl Default for DeployConfig {
@@ -53,6 +55,7 @@ impl From<chainspec::DeployConfig> for DeployConfig {
             max_dependencies: cfg.max_dependencies,
             max_block_size: cfg.max_block_size,
             block_gas_limit: cfg.block_gas_limit,
+            canonical_hash_timestamp: cfg.canonical_hash_timestamp,
         }
     }
 }
@@ -68,6 +71,7 @@ impl TryFrom<DeployConfig> for chainspec::DeployConfig {
             max_dependencies: cfg.max_dependencies,
             max_block_size: cfg.max_block_size,
             block_gas_limit: cfg.block_gas_limit,
+            canonical_hash_timestamp: cfg.canonical_hash_timestamp,
         })
     }
 }
@@ -113,6 +117,9 @@ struct HighwayConfig {
     voting_period_duration_millis: u64,
     finality_threshold_percent: u8,
     minimum_round_exponent: u8,
+    leader_seed: LeaderSeed,
+    max_proposal_deploy_count: u32,
+    max_proposal_size: u32,
 }
 
 impl Default for HighwayConfig {
@@ -132,6 +139,9 @@ impl From<chainspec::HighwayConfig> for HighwayConfig {
             voting_period_duration_millis: cfg.voting_period_duration.millis(),
             finality_threshold_percent: cfg.finality_threshold_percent,
             minimum_round_exponent: cfg.minimum_round_exponent,
+            leader_seed: cfg.leader_seed,
+            max_proposal_deploy_count: cfg.max_proposal_deploy_count,
+            max_proposal_size: cfg.max_proposal_size,
         }
     }
 }
@@ -228,6 +238,9 @@ impl From<&chainspec::Chainspec> for ChainspecConfig {
                 .millis(),
             finality_threshold_percent: chainspec.genesis.highway_config.finality_threshold_percent,
             minimum_round_exponent: chainspec.genesis.highway_config.minimum_round_exponent,
+            leader_seed: chainspec.genesis.highway_config.leader_seed,
+            max_proposal_deploy_count: chainspec.genesis.highway_config.max_proposal_deploy_count,
+            max_proposal_size: chainspec.genesis.highway_config.max_proposal_size,
         };
 
         let deploys = chainspec.genesis.deploy_config.into();
@@ -301,6 +314,9 @@ pub(super) fn parse_toml<P: AsRef<Path>>(chainspec_path: P) -> Result<chainspec:
         voting_period_duration: TimeDiff::from(chainspec.highway.voting_period_duration_millis),
         finality_threshold_percent: chainspec.highway.finality_threshold_percent,
         minimum_round_exponent: chainspec.highway.minimum_round_exponent,
+        leader_seed: chainspec.highway.leader_seed,
+        max_proposal_deploy_count: chainspec.highway.max_proposal_deploy_count,
+        max_proposal_size: chainspec.highway.max_proposal_size,
     };
 
     let genesis = chainspec::GenesisConfig {
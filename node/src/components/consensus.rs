@@ -1,33 +1,46 @@
 //! The consensus component. Provides distributed consensus among the nodes in the network.
 mod config;
 mod consensus_protocol;
+mod emergency_restart;
+mod era_metrics;
 mod era_supervisor;
 mod highway_core;
+mod leader_seed;
+mod message_ordering;
+mod message_tracing;
+mod proposal_limits;
 mod protocols;
+mod stall_monitor;
 #[cfg(test)]
 mod tests;
 mod traits;
+mod verification;
 
 use std::fmt::{self, Debug, Display, Formatter};
 
 use crate::{
     components::{storage::Storage, Component},
+    crypto::asymmetric_key::PublicKey,
     effect::{
-        announcements::ConsensusAnnouncement,
+        announcements::{ConsensusAnnouncement, ControlAnnouncement},
         requests::{
-            self, BlockExecutorRequest, BlockValidationRequest, DeployBufferRequest,
-            NetworkRequest, StorageRequest,
+            self, BlockExecutorRequest, BlockValidationRequest, NetworkRequest,
+            ProposalBuilderRequest, StorageRequest,
         },
         EffectBuilder, EffectExt, Effects,
     },
     protocol::Message,
     types::{ProtoBlock, Timestamp},
 };
-pub use config::Config;
+pub use config::{Config, QuorumFraction};
 pub(crate) use consensus_protocol::BlockContext;
 use derive_more::From;
+pub use emergency_restart::EmergencyRestart;
+pub(crate) use era_metrics::ParticipationReport;
 pub(crate) use era_supervisor::{EraId, EraSupervisor};
 use hex_fmt::HexFmt;
+pub use leader_seed::LeaderSeed;
+pub use message_tracing::toggle as toggle_message_tracing;
 use rand::{CryptoRng, Rng};
 use serde::{Deserialize, Serialize};
 use traits::NodeIdT;
@@ -35,10 +48,26 @@ use traits::NodeIdT;
 #[derive(Clone, Serialize, Deserialize)]
 pub struct ConsensusMessage {
     era_id: EraId,
+    /// The sender's sequence number of the message within the era, if it was broadcast.
+    sequence_number: Option<u64>,
     payload: Vec<u8>,
 }
 
 impl ConsensusMessage {
+    /// The era the message belongs to.
+    pub(crate) fn era_id(&self) -> EraId {
+        self.era_id
+    }
+
+    /// Returns the message without its sequence number, e.g. to pass on another node's message,
+    /// which is not part of our own sequence.
+    pub(crate) fn without_sequence_number(self) -> Self {
+        ConsensusMessage {
+            sequence_number: None,
+            ..self
+        }
+    }
+
     fn payload(&self) -> &[u8] {
         &self.payload
     }
@@ -49,6 +78,12 @@ impl ConsensusMessage {
 pub enum Event<I> {
     /// An incoming network message.
     MessageReceived { sender: I, msg: ConsensusMessage },
+    /// The signature verification of an incoming message has completed.
+    MessageVerified {
+        era_id: EraId,
+        seq: u64,
+        valid: bool,
+    },
     /// A scheduled event to be handled by a specified era
     Timer { era_id: EraId, timestamp: Timestamp },
     /// We are receiving the data we require to propose a new block
@@ -70,6 +105,18 @@ pub enum Event<I> {
         sender: I,
         proto_block: ProtoBlock,
     },
+    /// An operator's order to restart consensus has been received.
+    EmergencyRestart {
+        sender: I,
+        restart: EmergencyRestart,
+    },
+    /// It is time to check whether consensus has stalled.
+    CheckStall,
+    /// Validators have signed a block of the given era as final.
+    FinalitySignatures {
+        era_id: EraId,
+        signers: Vec<PublicKey>,
+    },
 }
 
 impl Display for ConsensusMessage {
@@ -98,6 +145,11 @@ impl<I: Debug> Display for Event<I> {
     fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
         match self {
             Event::MessageReceived { sender, msg } => write!(f, "msg from {:?}: {}", sender, msg),
+            Event::MessageVerified { era_id, seq, valid } => write!(
+                f,
+                "verified msg {} in era {:?}, valid: {}",
+                seq, era_id, valid
+            ),
             Event::Timer { era_id, timestamp } => write!(
                 f,
                 "timer for era {:?} scheduled for timestamp {}",
@@ -134,6 +186,13 @@ impl<I: Debug> Display for Event<I> {
                 "A proto-block received from {:?} turned out to be invalid for era {:?}: {:?}",
                 sender, era_id, proto_block
             ),
+            Event::EmergencyRestart { sender, restart } => {
+                write!(f, "{} received from {:?}", restart, sender)
+            }
+            Event::CheckStall => write!(f, "check whether consensus has stalled"),
+            Event::FinalitySignatures { era_id, signers } => {
+                write!(f, "{} finality signatures in {}", signers.len(), era_id)
+            }
         }
     }
 }
@@ -144,8 +203,9 @@ pub trait ReactorEventT<I>:
     From<Event<I>>
     + Send
     + From<NetworkRequest<I, Message>>
-    + From<DeployBufferRequest>
+    + From<ProposalBuilderRequest>
     + From<ConsensusAnnouncement>
+    + From<ControlAnnouncement>
     + From<BlockExecutorRequest>
     + From<BlockValidationRequest<ProtoBlock, I>>
     + From<StorageRequest<Storage>>
@@ -156,8 +216,9 @@ impl<REv, I> ReactorEventT<I> for REv where
     REv: From<Event<I>>
         + Send
         + From<NetworkRequest<I, Message>>
-        + From<DeployBufferRequest>
+        + From<ProposalBuilderRequest>
         + From<ConsensusAnnouncement>
+        + From<ControlAnnouncement>
         + From<BlockExecutorRequest>
         + From<BlockValidationRequest<ProtoBlock, I>>
         + From<StorageRequest<Storage>>
@@ -186,6 +247,9 @@ where
                 effects.extend(handling_es.handle_message(sender, msg));
                 effects
             }
+            Event::MessageVerified { era_id, seq, valid } => {
+                handling_es.handle_message_verified(era_id, seq, valid)
+            }
             Event::NewProtoBlock {
                 era_id,
                 proto_block,
@@ -195,6 +259,9 @@ where
                 block_header,
                 responder,
             )) => handling_es.handle_linear_chain_block(*block_header, responder),
+            Event::ConsensusRequest(requests::ConsensusRequest::GetValidatorParticipation(
+                responder,
+            )) => handling_es.handle_get_validator_participation(responder),
             Event::AcceptProtoBlock {
                 era_id,
                 proto_block,
@@ -204,6 +271,13 @@ where
                 sender,
                 proto_block,
             } => handling_es.handle_invalid_proto_block(era_id, sender, proto_block),
+            Event::EmergencyRestart { sender, restart } => {
+                handling_es.handle_emergency_restart(sender, restart)
+            }
+            Event::CheckStall => handling_es.handle_check_stall(),
+            Event::FinalitySignatures { era_id, signers } => {
+                handling_es.handle_finality_signatures(era_id, signers)
+            }
         }
     }
 }
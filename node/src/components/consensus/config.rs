@@ -1,12 +1,250 @@
-use serde::{Deserialize, Serialize};
+use std::{
+    fmt::{self, Display, Formatter},
+    str::FromStr,
+    time::Duration,
+};
 
-use crate::{crypto::asymmetric_key::SecretKey, utils::External};
+use serde::{
+    de::{Deserializer, Error as SerdeError, Unexpected},
+    Deserialize, Serialize, Serializer,
+};
+use tracing::error;
+
+use crate::{
+    crypto::asymmetric_key::{PublicKey, SecretKey, SignatureScheme},
+    utils::External,
+};
+
+const DEFAULT_VERIFICATION_POOL_SIZE: usize = 4;
+const DEFAULT_MAX_PENDING_VERIFICATIONS: usize = 1_024;
+// TODO: This needs to be in sync with AUCTION_DELAY/booking_duration_millis.
+const DEFAULT_RETAINED_ERAS: u64 = 4;
+const DEFAULT_STALL_TIMEOUT: Duration = Duration::from_secs(300);
+const DEFAULT_FINALITY_QUORUM: QuorumFraction = QuorumFraction::new(2, 3);
+const DEFAULT_MAX_HELD_MESSAGES_PER_SENDER: usize = 64;
+const DEFAULT_INACTIVE_VALIDATOR_THRESHOLD: f64 = 0.5;
 
 /// Consensus configuration.
-#[derive(Debug, Deserialize, Serialize, Default, Clone)]
+#[derive(Debug, Deserialize, Serialize, Clone)]
 // Disallow unknown fields to ensure config files and command-line overrides contain valid keys.
 #[serde(deny_unknown_fields)]
 pub struct Config {
     /// Path to secret key file.
     pub secret_key_path: External<SecretKey>,
+    /// Maximum number of incoming messages whose signatures are verified concurrently, off the
+    /// reactor thread.
+    pub verification_pool_size: usize,
+    /// Maximum number of incoming messages, across all eras, which are waiting for or undergoing
+    /// verification.  Further messages are dropped until earlier ones have been verified.
+    pub max_pending_verifications: usize,
+    /// Public keys of the operators authorized to order an emergency restart of consensus.
+    #[serde(with = "hex_public_keys")]
+    pub emergency_restart_operators: Vec<PublicKey>,
+    /// Number of distinct operators required to order an emergency restart.
+    ///
+    /// If zero, emergency restarts are disabled.
+    pub emergency_restart_threshold: usize,
+    /// Number of past eras whose state is retained, in addition to the current era.
+    ///
+    /// Older eras are dropped, except while incoming messages for them are still being verified.
+    pub retained_eras: u64,
+    /// Duration without a finalized block after which consensus is announced as stalled.
+    ///
+    /// If zero, stalls are not detected.
+    #[serde(with = "crate::utils::milliseconds")]
+    pub stall_timeout: Duration,
+    /// The signature scheme all consensus messages of the network must be signed with.  Messages
+    /// signed with any other scheme are rejected.
+    ///
+    /// If unset, all supported schemes are accepted.
+    #[serde(default)]
+    pub signature_scheme: Option<SignatureScheme>,
+    /// Whether to log every stage of the handling of each incoming consensus message.
+    ///
+    /// Tracing can also be toggled at runtime by sending the node `SIGUSR1`.
+    #[serde(default)]
+    pub trace_messages: bool,
+    /// The fraction of the total validator weight which has to be exceeded by the signers of a
+    /// block's finality signatures for the block to be final, e.g. `'2/3'`.
+    ///
+    /// Must be greater than 1/2 and at most 1.
+    pub finality_quorum: QuorumFraction,
+    /// Maximum number of a sender's messages in an era held back while waiting for an earlier
+    /// message from the sender, so that a sender's messages are handled in the order they were
+    /// sent.  Once exceeded, the missing messages are skipped.
+    ///
+    /// If zero, messages are handled in the order they arrive in.
+    pub max_held_messages_per_sender: usize,
+    /// The fraction of an era's finalized blocks a validator has to contribute to, by proposing
+    /// them, voting for them or signing them as final, to not be reported as inactive.
+    ///
+    /// Must be between 0 and 1.  If zero, no validator is reported as inactive.
+    pub inactive_validator_threshold: f64,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Config {
+            secret_key_path: External::default(),
+            verification_pool_size: DEFAULT_VERIFICATION_POOL_SIZE,
+            max_pending_verifications: DEFAULT_MAX_PENDING_VERIFICATIONS,
+            emergency_restart_operators: Vec::new(),
+            emergency_restart_threshold: 0,
+            retained_eras: DEFAULT_RETAINED_ERAS,
+            stall_timeout: DEFAULT_STALL_TIMEOUT,
+            signature_scheme: None,
+            trace_messages: false,
+            finality_quorum: DEFAULT_FINALITY_QUORUM,
+            max_held_messages_per_sender: DEFAULT_MAX_HELD_MESSAGES_PER_SENDER,
+            inactive_validator_threshold: DEFAULT_INACTIVE_VALIDATOR_THRESHOLD,
+        }
+    }
+}
+
+/// A fraction of the total validator weight, written as `"<numerator>/<denominator>"`.
+///
+/// A weight reaches the quorum if it exceeds the fraction of the total weight, or if it is the
+/// total weight itself, so that a quorum of `1/1` requires all validators.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct QuorumFraction {
+    numerator: u64,
+    denominator: u64,
+}
+
+impl QuorumFraction {
+    /// Creates the fraction `numerator / denominator`.
+    pub(crate) const fn new(numerator: u64, denominator: u64) -> Self {
+        QuorumFraction {
+            numerator,
+            denominator,
+        }
+    }
+
+    /// Returns the numerator.
+    pub(crate) fn numerator(&self) -> u64 {
+        self.numerator
+    }
+
+    /// Returns the denominator.
+    pub(crate) fn denominator(&self) -> u64 {
+        self.denominator
+    }
+
+    /// Returns whether the fraction is greater than 1/2 and at most 1.
+    ///
+    /// Smaller quorums could finalize conflicting blocks, and larger ones could never be reached.
+    fn is_valid(&self) -> bool {
+        u128::from(self.numerator) * 2 > u128::from(self.denominator)
+            && self.numerator <= self.denominator
+    }
+}
+
+impl Display for QuorumFraction {
+    fn fmt(&self, formatter: &mut Formatter<'_>) -> fmt::Result {
+        write!(formatter, "{}/{}", self.numerator, self.denominator)
+    }
+}
+
+impl FromStr for QuorumFraction {
+    type Err = String;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        let mut parts = value.splitn(2, '/');
+        let mut parse_part = || {
+            parts
+                .next()
+                .and_then(|part| part.trim().parse().ok())
+                .ok_or_else(|| format!("'{}' is not a fraction", value))
+        };
+        let numerator = parse_part()?;
+        let denominator = parse_part()?;
+        Ok(QuorumFraction::new(numerator, denominator))
+    }
+}
+
+impl Serialize for QuorumFraction {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_str(self)
+    }
+}
+
+/// Deserializes a fraction, but fails if it's not greater than 1/2 and at most 1.
+impl<'de> Deserialize<'de> for QuorumFraction {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let value = String::deserialize(deserializer)?;
+        match value.parse::<QuorumFraction>() {
+            Ok(fraction) if fraction.is_valid() => Ok(fraction),
+            _ => {
+                error!("finality_quorum of {} is invalid", value);
+                Err(SerdeError::invalid_value(
+                    Unexpected::Str(&value),
+                    &"a fraction greater than 1/2 and at most 1, e.g. '2/3'",
+                ))
+            }
+        }
+    }
+}
+
+/// (De)serializes public keys as hex strings.
+mod hex_public_keys {
+    use serde::{de::Error as SerdeError, Deserialize, Deserializer, Serializer};
+
+    use crate::crypto::asymmetric_key::PublicKey;
+
+    pub(super) fn serialize<S: Serializer>(
+        public_keys: &[PublicKey],
+        serializer: S,
+    ) -> Result<S::Ok, S::Error> {
+        serializer.collect_seq(public_keys.iter().map(PublicKey::to_hex))
+    }
+
+    pub(super) fn deserialize<'de, D: Deserializer<'de>>(
+        deserializer: D,
+    ) -> Result<Vec<PublicKey>, D::Error> {
+        Vec::<String>::deserialize(deserializer)?
+            .iter()
+            .map(|hex| PublicKey::from_hex(hex).map_err(SerdeError::custom))
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[derive(Debug, Deserialize, Serialize)]
+    struct QuorumConfig {
+        finality_quorum: QuorumFraction,
+    }
+
+    #[test]
+    fn should_reject_invalid_finality_quorum() {
+        let parse = |fraction: &str| {
+            toml::from_str::<QuorumConfig>(&format!("finality_quorum = '{}'", fraction))
+                .map(|config| config.finality_quorum)
+        };
+        for valid in &["2/3", "51/100", "1/1", " 3 / 4 "] {
+            assert!(parse(valid).is_ok(), "{} should be valid", valid);
+        }
+        for invalid in &[
+            "1/2",
+            "2/4",
+            "0/1",
+            "4/3",
+            "1/0",
+            "2",
+            "2/3/4",
+            "two thirds",
+        ] {
+            assert!(parse(invalid).is_err(), "{} should be invalid", invalid);
+        }
+        assert_eq!(parse("3/4").unwrap(), QuorumFraction::new(3, 4));
+
+        let config = QuorumConfig {
+            finality_quorum: DEFAULT_FINALITY_QUORUM,
+        };
+        let serialized = toml::to_string(&config).unwrap();
+        assert_eq!(serialized.trim(), "finality_quorum = \"2/3\"");
+        assert_eq!(parse("2/3").unwrap(), DEFAULT_FINALITY_QUORUM);
+    }
 }
@@ -3,7 +3,10 @@ use std::{collections::BTreeMap, fmt::Debug};
 use anyhow::Error;
 use rand::{CryptoRng, Rng};
 
-use crate::{components::consensus::traits::ConsensusValueT, types::Timestamp};
+use crate::{
+    components::consensus::traits::ConsensusValueT, crypto::asymmetric_key::SignatureScheme,
+    types::Timestamp,
+};
 
 /// Information about the context in which a new block is created.
 #[derive(Clone, Eq, PartialEq, Debug, Ord, PartialOrd)]
@@ -55,6 +58,12 @@ pub(crate) struct FinalizedBlock<C: ConsensusValueT, VID> {
     pub(crate) proposer: VID,
 }
 
+/// A function checking the signatures contained in a serialized incoming message.
+///
+/// It doesn't need access to the protocol state, so it can be sent to and run on a worker thread.
+/// It returns `false` if any signature is invalid.
+pub(crate) type MessageVerifier = Box<dyn FnOnce(&[u8]) -> bool + Send>;
+
 #[derive(Debug)]
 pub(crate) enum ConsensusProtocolResult<I, C: ConsensusValueT, VID> {
     CreatedGossipMessage(Vec<u8>),
@@ -65,16 +74,18 @@ pub(crate) enum ConsensusProtocolResult<I, C: ConsensusValueT, VID> {
     /// TODO: Add more details that are necessary for block creation.
     CreateNewBlock {
         block_context: BlockContext,
+        /// The values of the new block's ancestors, parent first.
+        past_values: Vec<C>,
     },
     /// A block was finalized.
     FinalizedBlock(FinalizedBlock<C, VID>),
     /// Request validation of the consensus value, contained in a message received from the given
-    /// node.
+    /// node and proposed by the given validator.
     ///
     /// The domain logic should verify any intrinsic validity conditions of consensus values, e.g.
     /// that it has the expected structure, or that deploys that are mentioned by hash actually
     /// exist, and then call `ConsensusProtocol::resolve_validity`.
-    ValidateConsensusValue(I, C),
+    ValidateConsensusValue(I, C, VID),
 }
 
 /// An API for a single instance of the consensus.
@@ -87,6 +98,23 @@ pub(crate) trait ConsensusProtocol<I, C: ConsensusValueT, VID, R: Rng + CryptoRn
         rng: &mut R,
     ) -> Result<Vec<ConsensusProtocolResult<I, C, VID>>, Error>;
 
+    /// Returns a function that verifies the signatures in an incoming message.
+    ///
+    /// If `signature_scheme` is given, signatures made with any other scheme are rejected.
+    fn message_verifier(&self, signature_scheme: Option<SignatureScheme>) -> MessageVerifier;
+
+    /// Returns the round an incoming message belongs to, if any.
+    fn message_round(&self, msg: &[u8]) -> Option<Timestamp>;
+
+    /// Handles an incoming message whose signatures have already been checked using the function
+    /// returned by `message_verifier`.
+    fn handle_verified_message(
+        &mut self,
+        sender: I,
+        msg: Vec<u8>,
+        rng: &mut R,
+    ) -> Result<Vec<ConsensusProtocolResult<I, C, VID>>, Error>;
+
     /// Triggers consensus' timer.
     fn handle_timer(
         &mut self,
@@ -113,4 +141,7 @@ pub(crate) trait ConsensusProtocol<I, C: ConsensusValueT, VID, R: Rng + CryptoRn
 
     /// Turns this instance into a passive observer, that does not create any new vertices.
     fn deactivate_validator(&mut self);
+
+    /// Returns the leader of the round starting at `timestamp`.
+    fn leader(&self, timestamp: Timestamp) -> VID;
 }
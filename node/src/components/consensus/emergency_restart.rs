@@ -0,0 +1,319 @@
+//! Emergency restarts of consensus.
+//!
+//! If consensus stalls irrecoverably, the operators of the network can order all nodes to restart
+//! consensus from a known-good block: Each operator signs the block's header, and every node
+//! relays the signed orders it receives.  Once a node has observed valid orders for the same block
+//! from at least the configured threshold of distinct operators, it discards all of its eras and
+//! starts a fresh one following that block.
+//!
+//! Orders by keys other than the configured operators' and orders with invalid signatures are
+//! ignored, as is everything once a restart at the given block has been triggered.
+//!
+//! Every order carries an expiry time covered by its signature, and expired orders are ignored.
+//! This bounds how long a captured order can be replayed: The record of triggered restarts is kept
+//! in memory only, so a node that is itself restarted before the orders expire could be made to
+//! restart consensus again.  Operators should therefore choose an expiry just long enough for the
+//! orders to propagate.
+
+use std::{
+    collections::{HashMap, HashSet},
+    fmt::{self, Display, Formatter},
+};
+
+#[cfg(test)]
+use rand::{CryptoRng, Rng};
+use serde::{Deserialize, Serialize};
+use tracing::{debug, warn};
+
+#[cfg(test)]
+use crate::crypto::asymmetric_key::SecretKey;
+use crate::{
+    crypto::asymmetric_key::{self, PublicKey, Signature, SigningPurpose},
+    types::{BlockHash, BlockHeader, Timestamp},
+};
+
+/// An operator's signed order to restart consensus following the given block.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct EmergencyRestart {
+    /// The header of the known-good block to restart from.
+    block_header: Box<BlockHeader>,
+    /// The time after which the order is void.
+    valid_until: Timestamp,
+    /// The public key of the operator.
+    public_key: PublicKey,
+    /// The operator's signature of the block's hash and the expiry time.
+    signature: Signature,
+}
+
+/// Returns the message an operator signs to order a restart following the given block.
+fn signed_message(block_hash: &BlockHash, valid_until: Timestamp) -> Vec<u8> {
+    let mut message = block_hash.inner().to_vec();
+    message.extend_from_slice(&valid_until.millis().to_be_bytes());
+    message
+}
+
+impl EmergencyRestart {
+    /// Creates an order to restart following the given block, signed by an operator and valid
+    /// until `valid_until`.
+    #[cfg(test)]
+    pub(crate) fn new<R: Rng + CryptoRng + ?Sized>(
+        block_header: BlockHeader,
+        valid_until: Timestamp,
+        secret_key: &SecretKey,
+        public_key: PublicKey,
+        rng: &mut R,
+    ) -> Self {
+        // The domain-separation tag prevents passing off the order as a signature of the block for
+        // any other purpose.
+        let signature = asymmetric_key::sign(
+            signed_message(&block_header.hash(), valid_until),
+            secret_key,
+            &public_key,
+            SigningPurpose::EmergencyRestart,
+            rng,
+        );
+        EmergencyRestart {
+            block_header: Box::new(block_header),
+            valid_until,
+            public_key,
+            signature,
+        }
+    }
+
+    /// Returns whether the signature is valid.
+    fn is_valid(&self, block_hash: &BlockHash) -> bool {
+        asymmetric_key::verify(
+            signed_message(block_hash, self.valid_until),
+            &self.signature,
+            &self.public_key,
+            SigningPurpose::EmergencyRestart,
+        )
+        .is_ok()
+    }
+}
+
+impl Display for EmergencyRestart {
+    fn fmt(&self, formatter: &mut Formatter<'_>) -> fmt::Result {
+        write!(
+            formatter,
+            "emergency restart at block {} until {} ordered by {}",
+            self.block_header.hash(),
+            self.valid_until,
+            self.public_key
+        )
+    }
+}
+
+/// The result of adding an order to the `EmergencyRestarts`.
+#[derive(Debug)]
+pub(crate) enum Outcome {
+    /// The order was unauthorized, invalid or redundant.
+    Ignored,
+    /// The order was valid and new, but there are not enough orders for a restart yet.
+    Counted,
+    /// The order completed the quorum: Consensus should be restarted following the given block.
+    QuorumReached(Box<BlockHeader>),
+}
+
+/// Collects emergency restart orders until a quorum of operators has ordered the same restart.
+#[derive(Debug)]
+pub(crate) struct EmergencyRestarts {
+    /// The operators authorized to order a restart.
+    operators: HashSet<PublicKey>,
+    /// The number of distinct operators required to order a restart.  If zero, restarts are
+    /// disabled.
+    threshold: usize,
+    /// The operators that have ordered a restart and the expiry of their orders, by the block to
+    /// restart from.
+    orders: HashMap<BlockHash, HashMap<PublicKey, Timestamp>>,
+    /// The blocks at which a restart has been triggered already, with the latest expiry of the
+    /// orders that triggered it.
+    triggered: HashMap<BlockHash, Timestamp>,
+}
+
+impl EmergencyRestarts {
+    /// Creates a new collector for orders by the given operators.
+    pub(crate) fn new<I>(operators: I, threshold: usize) -> Self
+    where
+        I: IntoIterator<Item = PublicKey>,
+    {
+        EmergencyRestarts {
+            operators: operators.into_iter().collect(),
+            threshold,
+            orders: HashMap::new(),
+            triggered: HashMap::new(),
+        }
+    }
+
+    /// Adds an order received at `now`, returning whether it completes the quorum for a restart.
+    ///
+    /// Only orders that haven't expired yet count, and a quorum is reached at most once per block.
+    pub(crate) fn add(&mut self, restart: EmergencyRestart, now: Timestamp) -> Outcome {
+        if self.threshold == 0 {
+            debug!(%restart, "ignoring order since emergency restarts are disabled");
+            return Outcome::Ignored;
+        }
+        if !self.operators.contains(&restart.public_key) {
+            warn!(%restart, "ignoring unauthorized order");
+            return Outcome::Ignored;
+        }
+        self.prune_expired(now);
+        if restart.valid_until < now {
+            debug!(%restart, "ignoring expired order");
+            return Outcome::Ignored;
+        }
+        let block_hash = restart.block_header.hash();
+        let is_known = self.orders.get(&block_hash).map_or(false, |operators| {
+            operators.contains_key(&restart.public_key)
+        });
+        if is_known || self.triggered.contains_key(&block_hash) {
+            return Outcome::Ignored;
+        }
+        if !restart.is_valid(&block_hash) {
+            warn!(%restart, "ignoring order with invalid signature");
+            return Outcome::Ignored;
+        }
+
+        let operators = self.orders.entry(block_hash).or_default();
+        let _ = operators.insert(restart.public_key, restart.valid_until);
+        if operators.len() < self.threshold {
+            return Outcome::Counted;
+        }
+        let valid_until = operators
+            .values()
+            .max()
+            .copied()
+            .unwrap_or(restart.valid_until);
+        let _ = self.orders.remove(&block_hash);
+        let _ = self.triggered.insert(block_hash, valid_until);
+        Outcome::QuorumReached(restart.block_header)
+    }
+
+    /// Forgets all orders and triggered restarts that have expired as of `now`.
+    ///
+    /// A triggered restart can be forgotten once all orders for it have expired, since they will
+    /// be ignored from then on anyway.
+    fn prune_expired(&mut self, now: Timestamp) {
+        for operators in self.orders.values_mut() {
+            operators.retain(|_, valid_until| *valid_until >= now);
+        }
+        self.orders.retain(|_, operators| !operators.is_empty());
+        self.triggered.retain(|_, valid_until| *valid_until >= now);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{components::storage::Value, testing::TestRng, types::Block};
+
+    fn operator(rng: &mut TestRng) -> (SecretKey, PublicKey) {
+        let secret_key = SecretKey::random(rng);
+        let public_key = PublicKey::from(&secret_key);
+        (secret_key, public_key)
+    }
+
+    #[test]
+    fn should_restart_once_with_quorum_of_authorized_orders() {
+        let mut rng = TestRng::new();
+        let operators: Vec<_> = (0..3).map(|_| operator(&mut rng)).collect();
+        let mut restarts =
+            EmergencyRestarts::new(operators.iter().map(|(_, public_key)| *public_key), 2);
+        let block_header = Block::random(&mut rng).take_header();
+        let now = Timestamp::from(1_000);
+        let valid_until = Timestamp::from(2_000);
+        let order = |rng: &mut TestRng, (secret_key, public_key): &(SecretKey, PublicKey)| {
+            EmergencyRestart::new(
+                block_header.clone(),
+                valid_until,
+                secret_key,
+                *public_key,
+                rng,
+            )
+        };
+
+        // An order by a single operator isn't enough, even if repeated.
+        let first = order(&mut rng, &operators[0]);
+        assert!(matches!(restarts.add(first.clone(), now), Outcome::Counted));
+        assert!(matches!(restarts.add(first, now), Outcome::Ignored));
+
+        // Orders by unauthorized keys or with invalid signatures don't count either.
+        let stranger = operator(&mut rng);
+        let unauthorized = order(&mut rng, &stranger);
+        assert!(matches!(restarts.add(unauthorized, now), Outcome::Ignored));
+        let mut forged = order(&mut rng, &operators[1]);
+        forged.signature = order(&mut rng, &operators[2]).signature;
+        assert!(matches!(restarts.add(forged, now), Outcome::Ignored));
+
+        // Extending the expiry invalidates the signature.
+        let mut extended = order(&mut rng, &operators[1]);
+        extended.valid_until = Timestamp::from(3_000);
+        assert!(matches!(restarts.add(extended, now), Outcome::Ignored));
+
+        // The second authorized operator completes the quorum.
+        match restarts.add(order(&mut rng, &operators[1]), now) {
+            Outcome::QuorumReached(restart_header) => assert_eq!(*restart_header, block_header),
+            other => panic!("expected quorum, got {:?}", other),
+        }
+
+        // Once triggered, no further restart happens at the same block.
+        assert!(matches!(
+            restarts.add(order(&mut rng, &operators[2]), now),
+            Outcome::Ignored
+        ));
+    }
+
+    #[test]
+    fn should_ignore_expired_orders() {
+        let mut rng = TestRng::new();
+        let operators: Vec<_> = (0..2).map(|_| operator(&mut rng)).collect();
+        let mut restarts =
+            EmergencyRestarts::new(operators.iter().map(|(_, public_key)| *public_key), 2);
+        let block_header = Block::random(&mut rng).take_header();
+        let valid_until = Timestamp::from(2_000);
+        let order = |rng: &mut TestRng, (secret_key, public_key): &(SecretKey, PublicKey)| {
+            EmergencyRestart::new(
+                block_header.clone(),
+                valid_until,
+                secret_key,
+                *public_key,
+                rng,
+            )
+        };
+
+        // The first order expires before the second one arrives, so there is no quorum.
+        let first = order(&mut rng, &operators[0]);
+        assert!(matches!(
+            restarts.add(first.clone(), Timestamp::from(1_000)),
+            Outcome::Counted
+        ));
+        let late = Timestamp::from(2_001);
+        assert!(matches!(
+            restarts.add(order(&mut rng, &operators[1]), late),
+            Outcome::Ignored
+        ));
+
+        // Replaying the expired order doesn't count either.
+        assert!(matches!(restarts.add(first, late), Outcome::Ignored));
+        assert!(restarts.orders.is_empty());
+    }
+
+    #[test]
+    fn should_not_restart_when_disabled() {
+        let mut rng = TestRng::new();
+        let (secret_key, public_key) = operator(&mut rng);
+        let mut restarts = EmergencyRestarts::new(vec![public_key], 0);
+        let block_header = Block::random(&mut rng).take_header();
+        let restart = EmergencyRestart::new(
+            block_header,
+            Timestamp::from(2_000),
+            &secret_key,
+            public_key,
+            &mut rng,
+        );
+        assert!(matches!(
+            restarts.add(restart, Timestamp::from(1_000)),
+            Outcome::Ignored
+        ));
+    }
+}
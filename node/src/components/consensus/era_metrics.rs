@@ -0,0 +1,347 @@
+//! Per-era consensus metrics.
+//!
+//! For each era, the number of finalized blocks and the fraction of the validators' weight that
+//! participated are tracked, as well as the time it took to finalize each block after it was
+//! proposed.  A validator counts as participating if it proposed a finalized block, earned a
+//! reward for contributing to finality with its votes, or signed a block as final.  The gauges
+//! always refer to the current era; they are reset whenever a new era starts.
+//!
+//! A validator which contributed to fewer than the configured fraction of the era's finalized
+//! blocks is reported as inactive, e.g. because it is online but its votes don't reach the other
+//! validators.  Until the era's first block is finalized, no validator is reported as inactive.
+
+use std::{
+    collections::{HashMap, HashSet},
+    hash::Hash,
+    time::Duration,
+};
+
+use prometheus::{self, Gauge, Histogram, HistogramOpts, IntGauge, Registry};
+
+use super::EraId;
+
+/// Value of upper bound of the first finalization time histogram bucket (100 ms).
+const EXPONENTIAL_BUCKET_START: f64 = 0.1;
+/// Multiplier of previous upper bound for next bound.
+const EXPONENTIAL_BUCKET_FACTOR: f64 = 2.0;
+/// Bucket count, with last going to +Inf.
+const EXPONENTIAL_BUCKET_COUNT: usize = 12;
+
+/// A validator's weight and contributions to a single era.
+#[derive(Clone, Copy, Debug, Default)]
+struct Contributions {
+    /// The validator's weight in the era.
+    weight: u64,
+    /// The number of finalized blocks the validator proposed.
+    proposals: u64,
+    /// The number of finalized blocks which rewarded the validator for its votes.
+    votes: u64,
+    /// The number of blocks the validator signed as final.
+    finality_signatures: u64,
+    /// The number of finalized blocks the validator proposed or was rewarded by.
+    blocks: u64,
+}
+
+impl Contributions {
+    /// Returns whether the validator has contributed anything.
+    fn any(&self) -> bool {
+        self.proposals > 0 || self.votes > 0 || self.finality_signatures > 0
+    }
+}
+
+/// The participation of a single validator in an era.
+#[derive(Clone, Debug, PartialEq)]
+pub struct ValidatorParticipation<VID> {
+    /// The validator.
+    pub validator: VID,
+    /// The validator's weight in the era.
+    pub weight: u64,
+    /// The number of finalized blocks the validator proposed.
+    pub proposals: u64,
+    /// The number of finalized blocks which rewarded the validator for its votes.
+    pub votes: u64,
+    /// The number of blocks the validator signed as final.
+    pub finality_signatures: u64,
+    /// The fraction of the era's finalized blocks the validator contributed to, or `None` if no
+    /// block has been finalized yet.
+    pub participation_rate: Option<f64>,
+    /// Whether the participation rate is below the inactivity threshold.
+    pub inactive: bool,
+}
+
+/// The participation of all validators in an era.
+#[derive(Clone, Debug, PartialEq)]
+pub struct ParticipationReport<VID> {
+    /// The era.
+    pub era_id: EraId,
+    /// The number of blocks finalized in the era.
+    pub block_count: u64,
+    /// The participation rate below which a validator is reported as inactive.
+    pub inactivity_threshold: f64,
+    /// The participation of each validator.
+    pub validators: Vec<ValidatorParticipation<VID>>,
+}
+
+/// Block count and validator participation of a single era.
+#[derive(Debug)]
+pub(crate) struct EraStats<VID> {
+    /// The weight and contributions of each validator.
+    validators: HashMap<VID, Contributions>,
+    /// The total weight of all validators.
+    total_weight: u64,
+    /// The sum of the weights of all participating validators.
+    participating_weight: u64,
+    /// The number of blocks finalized in the era.
+    block_count: u64,
+    /// The participation rate below which a validator is reported as inactive.
+    inactivity_threshold: f64,
+}
+
+impl<VID: Eq + Hash> EraStats<VID> {
+    /// Creates the statistics of an era with the given validators and their weights, reporting
+    /// validators with a participation rate below `inactivity_threshold` as inactive.
+    pub(crate) fn new<I: IntoIterator<Item = (VID, u64)>>(
+        validators: I,
+        inactivity_threshold: f64,
+    ) -> Self {
+        let validators: HashMap<_, _> = validators
+            .into_iter()
+            .map(|(validator, weight)| {
+                let contributions = Contributions {
+                    weight,
+                    ..Contributions::default()
+                };
+                (validator, contributions)
+            })
+            .collect();
+        let total_weight = validators
+            .values()
+            .map(|contributions| contributions.weight)
+            .sum();
+        EraStats {
+            validators,
+            total_weight,
+            participating_weight: 0,
+            block_count: 0,
+            inactivity_threshold,
+        }
+    }
+
+    /// Records a finalized block, with its proposer and the validators rewarded by it.
+    pub(crate) fn record_block<'a, I>(&mut self, proposer: VID, rewarded: I)
+    where
+        VID: 'a,
+        I: IntoIterator<Item = &'a VID>,
+    {
+        self.block_count += 1;
+        let mut contributors: HashSet<&VID> = rewarded.into_iter().collect();
+        for validator in &contributors {
+            self.record_contribution(validator, |contributions| contributions.votes += 1);
+        }
+        self.record_contribution(&proposer, |contributions| contributions.proposals += 1);
+        contributors.insert(&proposer);
+        for validator in contributors {
+            self.record_contribution(validator, |contributions| contributions.blocks += 1);
+        }
+    }
+
+    /// Records a finality signature by `validator` of a block in the era.
+    pub(crate) fn record_finality_signature(&mut self, validator: &VID) {
+        self.record_contribution(validator, |contributions| {
+            contributions.finality_signatures += 1
+        });
+    }
+
+    /// Returns the number of blocks finalized in the era.
+    pub(crate) fn block_count(&self) -> u64 {
+        self.block_count
+    }
+
+    /// Returns the fraction of the validators' total weight that has participated, between 0 and 1.
+    pub(crate) fn participation(&self) -> f64 {
+        if self.total_weight == 0 {
+            return 0.0;
+        }
+        self.participating_weight as f64 / self.total_weight as f64
+    }
+
+    /// Returns the fraction of the era's finalized blocks a validator contributed to, by proposing
+    /// them, voting for them or signing them as final, or `None` if no block has been finalized.
+    fn participation_rate(&self, contributions: &Contributions) -> Option<f64> {
+        if self.block_count == 0 {
+            return None;
+        }
+        // Finality signatures are counted separately, as they are collected independently of the
+        // blocks' rewards, and for blocks of the previous era right after an era change.
+        let blocks = contributions.blocks.max(contributions.finality_signatures);
+        Some((blocks as f64 / self.block_count as f64).min(1.0))
+    }
+
+    /// Returns whether a validator with the given contributions is inactive.
+    fn is_inactive(&self, contributions: &Contributions) -> bool {
+        self.participation_rate(contributions)
+            .map_or(false, |rate| rate < self.inactivity_threshold)
+    }
+
+    /// Returns the validators whose participation rate is below the inactivity threshold.
+    pub(crate) fn inactive_validators(&self) -> impl Iterator<Item = &VID> {
+        self.validators
+            .iter()
+            .filter(move |(_, contributions)| self.is_inactive(contributions))
+            .map(|(validator, _)| validator)
+    }
+
+    /// Returns the participation of each validator in the era, ordered by validator.
+    pub(crate) fn report(&self, era_id: EraId) -> ParticipationReport<VID>
+    where
+        VID: Clone + Ord,
+    {
+        let mut validators: Vec<_> = self
+            .validators
+            .iter()
+            .map(|(validator, contributions)| ValidatorParticipation {
+                validator: validator.clone(),
+                weight: contributions.weight,
+                proposals: contributions.proposals,
+                votes: contributions.votes,
+                finality_signatures: contributions.finality_signatures,
+                participation_rate: self.participation_rate(contributions),
+                inactive: self.is_inactive(contributions),
+            })
+            .collect();
+        validators.sort_by(|left, right| left.validator.cmp(&right.validator));
+        ParticipationReport {
+            era_id,
+            block_count: self.block_count,
+            inactivity_threshold: self.inactivity_threshold,
+            validators,
+        }
+    }
+
+    /// Records a contribution of `validator`, marking it as participating, unless it isn't one of
+    /// the era's validators.
+    fn record_contribution<F>(&mut self, validator: &VID, record: F)
+    where
+        F: FnOnce(&mut Contributions),
+    {
+        if let Some(contributions) = self.validators.get_mut(validator) {
+            if !contributions.any() {
+                self.participating_weight += contributions.weight;
+            }
+            record(contributions);
+        }
+    }
+}
+
+/// Metrics of the era supervisor.
+#[derive(Debug)]
+pub(crate) struct EraMetrics {
+    /// Histogram of the time from proposing a block to finalizing it.
+    finalization_time: Histogram,
+    /// Number of blocks finalized in the current era.
+    block_count: IntGauge,
+    /// Fraction of the validators' weight that participated in the current era.
+    participation: Gauge,
+    /// Number of validators participating below the inactivity threshold in the current era.
+    inactive_validators: IntGauge,
+
+    /// Handle to the metrics registry, in case we need to unregister.
+    registry: Registry,
+}
+
+impl EraMetrics {
+    /// Create and register new era metrics.
+    pub(crate) fn new(registry: &Registry) -> Result<Self, prometheus::Error> {
+        let buckets = prometheus::exponential_buckets(
+            EXPONENTIAL_BUCKET_START,
+            EXPONENTIAL_BUCKET_FACTOR,
+            EXPONENTIAL_BUCKET_COUNT,
+        )?;
+        let finalization_time = Histogram::with_opts(
+            HistogramOpts::new(
+                "consensus_finalization_time_seconds",
+                "time from proposing a block until it is finalized",
+            )
+            .buckets(buckets),
+        )?;
+        let block_count = IntGauge::new(
+            "consensus_era_block_count",
+            "number of blocks finalized in the current era",
+        )?;
+        let participation = Gauge::new(
+            "consensus_era_participation",
+            "fraction of validator weight that participated in the current era",
+        )?;
+        let inactive_validators = IntGauge::new(
+            "consensus_era_inactive_validators",
+            "number of validators participating below the inactivity threshold in the current era",
+        )?;
+        registry.register(Box::new(finalization_time.clone()))?;
+        registry.register(Box::new(block_count.clone()))?;
+        registry.register(Box::new(participation.clone()))?;
+        registry.register(Box::new(inactive_validators.clone()))?;
+
+        Ok(EraMetrics {
+            finalization_time,
+            block_count,
+            participation,
+            inactive_validators,
+            registry: registry.clone(),
+        })
+    }
+
+    /// Records that a block was finalized `finalization_time` after it was proposed.
+    pub(crate) fn observe_finalization(&self, finalization_time: Duration) {
+        self.finalization_time
+            .observe(finalization_time.as_secs_f64());
+    }
+
+    /// Updates the gauges to the current era's statistics.
+    pub(crate) fn update<VID: Eq + Hash>(&self, stats: &EraStats<VID>) {
+        self.block_count.set(stats.block_count() as i64);
+        self.participation.set(stats.participation());
+        self.inactive_validators
+            .set(stats.inactive_validators().count() as i64);
+    }
+
+    /// Returns the number of blocks finalized in the current era.
+    #[cfg(test)]
+    pub(crate) fn block_count(&self) -> i64 {
+        self.block_count.get()
+    }
+
+    /// Returns the fraction of the validators' weight that participated in the current era.
+    #[cfg(test)]
+    pub(crate) fn participation(&self) -> f64 {
+        self.participation.get()
+    }
+
+    /// Returns the number of inactive validators in the current era.
+    #[cfg(test)]
+    pub(crate) fn inactive_validators(&self) -> i64 {
+        self.inactive_validators.get()
+    }
+
+    /// Returns the number of recorded finalization times.
+    #[cfg(test)]
+    pub(crate) fn finalization_count(&self) -> u64 {
+        self.finalization_time.get_sample_count()
+    }
+}
+
+impl Drop for EraMetrics {
+    fn drop(&mut self) {
+        self.registry
+            .unregister(Box::new(self.finalization_time.clone()))
+            .expect("did not expect deregistering finalization time to fail");
+        self.registry
+            .unregister(Box::new(self.block_count.clone()))
+            .expect("did not expect deregistering block count to fail");
+        self.registry
+            .unregister(Box::new(self.participation.clone()))
+            .expect("did not expect deregistering participation to fail");
+        self.registry
+            .unregister(Box::new(self.inactive_validators.clone()))
+            .expect("did not expect deregistering inactive validators to fail");
+    }
+}
@@ -6,9 +6,10 @@
 //! Most importantly, it doesn't care about what messages it's forwarding.
 
 use std::{
-    collections::HashMap,
+    collections::{BTreeMap, HashMap, HashSet},
     fmt::{self, Debug, Formatter},
     rc::Rc,
+    sync::Arc,
 };
 
 use anyhow::Error;
@@ -19,9 +20,11 @@ use blake2::{
 use casper_types::U512;
 use fmt::Display;
 use num_traits::AsPrimitive;
+use prometheus::Registry;
 use rand::{CryptoRng, Rng};
 use serde::{Deserialize, Serialize};
-use tracing::{error, info};
+use tokio::sync::Semaphore;
+use tracing::{debug, error, info, warn};
 
 use casper_execution_engine::shared::motes::Motes;
 
@@ -33,35 +36,54 @@ use crate::{
                 BlockContext, ConsensusProtocol, ConsensusProtocolResult,
                 FinalizedBlock as CpFinalizedBlock,
             },
+            emergency_restart::{EmergencyRestart, EmergencyRestarts, Outcome},
+            era_metrics::{EraMetrics, EraStats, ParticipationReport},
             highway_core::{highway::Params, validators::Validators},
+            message_ordering::MessageOrdering,
+            message_tracing::{self, MessageTrace, Stage},
+            proposal_limits::ProposalLimits,
             protocols::highway::{HighwayContext, HighwayProtocol, HighwaySecret},
+            stall_monitor::StallMonitor,
             traits::NodeIdT,
+            verification::{self, VerificationQueue, VerifiedMessage},
             Config, ConsensusMessage, Event, ReactorEventT,
         },
     },
     crypto::{
-        asymmetric_key::{self, PublicKey, SecretKey, Signature},
+        asymmetric_key::{self, PublicKey, SecretKey, Signature, SignatureScheme, SigningPurpose},
         hash,
     },
     effect::{EffectBuilder, EffectExt, Effects, Responder},
-    types::{BlockHeader, FinalizedBlock, ProtoBlock, SystemTransaction, Timestamp},
+    protocol::Message,
+    types::{
+        BlockHeader, FinalitySignature, FinalizedBlock, ProtoBlock, ShutdownReason,
+        SystemTransaction, Timestamp,
+    },
     utils::WithDir,
 };
 
 // We use one trillion as a block reward unit because it's large enough to allow precise
 // fractions, and small enough for many block rewards to fit into a u64.
 const BLOCK_REWARD: u64 = 1_000_000_000_000;
-/// The number of recent eras to retain. Eras older than this are dropped from memory.
-// TODO: This needs to be in sync with AUCTION_DELAY/booking_duration_millis. (Already duplicated!)
-const RETAIN_ERAS: u64 = 4;
 
 #[derive(Debug, Clone, Copy, Hash, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
 pub struct EraId(pub(crate) u64);
 
 impl EraId {
+    /// Returns a message of this era without a sequence number, e.g. to send to a single peer.
     fn message(self, payload: Vec<u8>) -> ConsensusMessage {
         ConsensusMessage {
             era_id: self,
+            sequence_number: None,
+            payload,
+        }
+    }
+
+    /// Returns a message of this era with the given sequence number, to broadcast.
+    fn sequenced_message(self, sequence_number: u64, payload: Vec<u8>) -> ConsensusMessage {
+        ConsensusMessage {
+            era_id: self,
+            sequence_number: Some(sequence_number),
             payload,
         }
     }
@@ -82,6 +104,14 @@ pub(crate) struct Era<I, R: Rng + CryptoRng + ?Sized> {
     consensus: Box<dyn ConsensusProtocol<I, ProtoBlock, PublicKey, R>>,
     /// The height of this era's first block.
     start_height: u64,
+    /// Incoming messages held back until the earlier messages from the same sender arrive.
+    message_ordering: MessageOrdering<I>,
+    /// Incoming messages whose signatures are being verified.
+    verification_queue: VerificationQueue<I>,
+    /// The sequence number of the next message we broadcast in this era.
+    next_sequence_number: u64,
+    /// The era's block count and validator participation.
+    stats: EraStats<PublicKey>,
 }
 
 pub(crate) struct EraSupervisor<I, R: Rng + CryptoRng + ?Sized> {
@@ -93,6 +123,28 @@ pub(crate) struct EraSupervisor<I, R: Rng + CryptoRng + ?Sized> {
     validator_stakes: Vec<(PublicKey, Motes)>,
     current_era: EraId,
     chainspec: Chainspec,
+    /// Limits the number of incoming messages being verified concurrently.
+    verification_permits: Arc<Semaphore>,
+    /// The maximum number of incoming messages waiting for or undergoing verification.
+    max_pending_verifications: usize,
+    /// The emergency restart orders received so far.
+    emergency_restarts: EmergencyRestarts,
+    /// The number of past eras to retain. Eras older than this are dropped from memory.
+    retained_eras: u64,
+    /// Detects when no block has been finalized for too long.
+    stall_monitor: StallMonitor,
+    /// Per-era metrics.
+    metrics: EraMetrics,
+    /// The maximum size of block proposals, as set in the chainspec.
+    proposal_limits: ProposalLimits,
+    /// The validators which have proposed blocks exceeding the limits, by era.
+    flagged_proposers: HashMap<EraId, HashSet<PublicKey>>,
+    /// The signature scheme incoming messages must be signed with, if restricted.
+    signature_scheme: Option<SignatureScheme>,
+    /// The maximum number of a sender's messages held back per era to handle them in order.
+    max_held_messages_per_sender: usize,
+    /// The participation rate below which a validator is reported as inactive.
+    inactive_validator_threshold: f64,
 }
 
 impl<I, R: Rng + CryptoRng + ?Sized> Debug for EraSupervisor<I, R> {
@@ -108,6 +160,7 @@ where
     R: Rng + CryptoRng + ?Sized,
 {
     /// Creates a new `EraSupervisor`, starting in era 0.
+    #[allow(clippy::too_many_arguments)]
     pub(crate) fn new<REv: ReactorEventT<I>>(
         timestamp: Timestamp,
         config: WithDir<Config>,
@@ -115,11 +168,22 @@ where
         validator_stakes: Vec<(PublicKey, Motes)>,
         chainspec: &Chainspec,
         genesis_post_state_hash: hash::Digest,
+        registry: &Registry,
         rng: &mut R,
     ) -> Result<(Self, Effects<Event<I>>), Error> {
         let (root, config) = config.into_parts();
         let secret_signing_key = Rc::new(config.secret_key_path.load(root)?);
         let public_signing_key = PublicKey::from(secret_signing_key.as_ref());
+        if let Some(signature_scheme) = config.signature_scheme {
+            if public_signing_key.scheme() != signature_scheme {
+                return Err(Error::msg(format!(
+                    "secret key uses {}, but the network's signature scheme is {}",
+                    public_signing_key.scheme(),
+                    signature_scheme
+                )));
+            }
+        }
+        message_tracing::set_enabled(config.trace_messages);
 
         let mut era_supervisor = Self {
             active_eras: Default::default(),
@@ -128,6 +192,23 @@ where
             current_era: EraId(0),
             validator_stakes: validator_stakes.clone(),
             chainspec: chainspec.clone(),
+            verification_permits: Arc::new(Semaphore::new(config.verification_pool_size)),
+            max_pending_verifications: config.max_pending_verifications,
+            emergency_restarts: EmergencyRestarts::new(
+                config.emergency_restart_operators,
+                config.emergency_restart_threshold,
+            ),
+            retained_eras: config.retained_eras,
+            stall_monitor: StallMonitor::new(config.stall_timeout, timestamp),
+            metrics: EraMetrics::new(registry)?,
+            proposal_limits: ProposalLimits::new(
+                chainspec.genesis.highway_config.max_proposal_deploy_count as usize,
+                chainspec.genesis.highway_config.max_proposal_size as usize,
+            ),
+            flagged_proposers: HashMap::new(),
+            signature_scheme: config.signature_scheme,
+            max_held_messages_per_sender: config.max_held_messages_per_sender,
+            inactive_validator_threshold: config.inactive_validator_threshold,
         };
 
         let results = era_supervisor.new_era(
@@ -137,10 +218,12 @@ where
             chainspec.genesis.highway_config.genesis_era_start_timestamp,
             0,
             genesis_post_state_hash,
+            // The genesis era has no previous era, so its seed is derived from the genesis state.
+            genesis_post_state_hash,
         );
-        let effects = era_supervisor
-            .handling_wrapper(effect_builder, rng)
-            .handle_consensus_results(EraId(0), results);
+        let mut handling_es = era_supervisor.handling_wrapper(effect_builder, rng);
+        let mut effects = handling_es.handle_consensus_results(EraId(0), results);
+        effects.extend(handling_es.schedule_stall_check());
 
         Ok((era_supervisor, effects))
     }
@@ -193,6 +276,10 @@ where
     }
 
     /// Starts a new era; panics if it already exists.
+    ///
+    /// The seed for selecting the era's leaders is derived from `previous_era_hash`, the hash of
+    /// the previous era's switch block, as configured in the chainspec.
+    #[allow(clippy::too_many_arguments)]
     fn new_era(
         &mut self,
         era_id: EraId,
@@ -201,10 +288,12 @@ where
         start_time: Timestamp,
         start_height: u64,
         post_state_hash: hash::Digest,
+        previous_era_hash: hash::Digest,
     ) -> Vec<ConsensusProtocolResult<I, ProtoBlock, PublicKey>> {
         if self.active_eras.contains_key(&era_id) {
             panic!("{:?} already exists", era_id);
         }
+        self.report_inactive_validators();
         self.current_era = era_id;
 
         let sum_stakes: Motes = validator_stakes.iter().map(|(_, stake)| *stake).sum();
@@ -221,15 +310,24 @@ where
         let validators: Validators<PublicKey> =
             validator_stakes.into_iter().map(scale_stake).collect();
 
+        let stats = EraStats::new(
+            validators.iter().map(|v| (*v.id(), v.weight().0)),
+            self.inactive_validator_threshold,
+        );
         let ftt = validators.total_weight()
             * u64::from(self.highway_config().finality_threshold_percent)
             / 100;
         // The number of rounds after which a block reward is paid out.
         // TODO: Make this configurable?
         let reward_delay = 8;
+        let seed = self
+            .highway_config()
+            .leader_seed
+            .derive(era_id, &previous_era_hash);
+        debug!(?era_id, seed, "derived leader seed");
         // TODO: The initial round length should be the observed median of the switch block.
         let params = Params::new(
-            0, // TODO: get a proper seed.
+            seed,
             BLOCK_REWARD,
             BLOCK_REWARD / 5, // TODO: Make reduced block reward configurable?
             reward_delay,
@@ -266,17 +364,33 @@ where
         let era = Era {
             consensus: Box::new(highway),
             start_height,
+            message_ordering: MessageOrdering::new(self.max_held_messages_per_sender),
+            verification_queue: VerificationQueue::default(),
+            next_sequence_number: 0,
+            stats,
         };
+        self.metrics.update(&era.stats);
         let _ = self.active_eras.insert(era_id, era);
-
-        // Remove the era that has become obsolete now.
-        if era_id.0 > RETAIN_ERAS {
-            self.active_eras.remove(&EraId(era_id.0 - RETAIN_ERAS - 1));
-        }
+        self.drop_obsolete_eras();
 
         results
     }
 
+    /// Drops the state of all eras older than the retention window, including the proposers
+    /// flagged in them.
+    ///
+    /// Eras with incoming messages still being verified, e.g. while a peer is catching up, are
+    /// kept until verification has completed.
+    fn drop_obsolete_eras(&mut self) {
+        let oldest_retained = self.current_era.0.saturating_sub(self.retained_eras);
+        self.active_eras.retain(|era_id, era| {
+            era_id.0 >= oldest_retained || !era.verification_queue.is_empty()
+        });
+        let active_eras = &self.active_eras;
+        self.flagged_proposers
+            .retain(|era_id, _| active_eras.contains_key(era_id));
+    }
+
     /// Returns the current era.
     fn current_era_mut(&mut self) -> &mut Era<I, R> {
         self.active_eras
@@ -284,6 +398,116 @@ where
             .expect("current era does not exist")
     }
 
+    /// Returns our consensus signing key pair.
+    pub(crate) fn signing_key_pair(&self) -> (&SecretKey, &PublicKey) {
+        (&self.secret_signing_key, &self.public_signing_key)
+    }
+
+    /// Returns the current era's ID.
+    pub(crate) fn current_era(&self) -> EraId {
+        self.current_era
+    }
+
+    /// Returns the weights of the current era's validators, by consensus public key.
+    pub(crate) fn current_validator_weights(&self) -> BTreeMap<PublicKey, Motes> {
+        self.validator_stakes.iter().copied().collect()
+    }
+
+    /// Updates the era's statistics and the metrics with a block finalized in it, which was
+    /// proposed at `timestamp`.
+    fn record_finalized_block<'a, V>(
+        &mut self,
+        era_id: EraId,
+        proposer: PublicKey,
+        rewarded: V,
+        timestamp: Timestamp,
+    ) where
+        V: IntoIterator<Item = &'a PublicKey>,
+    {
+        self.metrics
+            .observe_finalization(Timestamp::now().saturating_sub(timestamp).into());
+        if let Some(era) = self.active_eras.get_mut(&era_id) {
+            era.stats.record_block(proposer, rewarded);
+            if era_id == self.current_era {
+                self.metrics.update(&era.stats);
+            }
+        }
+    }
+
+    /// Updates the statistics of the era `era_id` with finality signatures by `signers` of one of
+    /// its blocks, and the metrics if it is the current era.
+    fn record_finality_signatures<'a, V>(&mut self, era_id: EraId, signers: V)
+    where
+        V: IntoIterator<Item = &'a PublicKey>,
+    {
+        if let Some(era) = self.active_eras.get_mut(&era_id) {
+            for signer in signers {
+                era.stats.record_finality_signature(signer);
+            }
+            if era_id == self.current_era {
+                self.metrics.update(&era.stats);
+            }
+        }
+    }
+
+    /// Logs the validators which have been inactive in the current era, before it ends.
+    fn report_inactive_validators(&self) {
+        if let Some(era) = self.active_eras.get(&self.current_era) {
+            for validator in era.stats.inactive_validators() {
+                warn!(era_id = ?self.current_era, %validator, "validator was inactive in era");
+            }
+        }
+    }
+
+    /// Returns the participation of the validators in the current era, if it has started.
+    pub(crate) fn participation_report(&self) -> Option<ParticipationReport<PublicKey>> {
+        self.active_eras
+            .get(&self.current_era)
+            .map(|era| era.stats.report(self.current_era))
+    }
+
+    /// Checks `proto_block`, proposed by `proposer` in era `era_id` and received from `sender`,
+    /// against the proposal limits.
+    ///
+    /// Returns whether the proposal is within the limits.  Otherwise `proposer` is flagged in the
+    /// era: The oversized proposal is signed by it, so this doesn't depend on which peer relayed
+    /// it.  All further proposals by a flagged proposer in the same era are rejected without
+    /// validating them.
+    fn admit_proposal(
+        &mut self,
+        era_id: EraId,
+        sender: &I,
+        proposer: &PublicKey,
+        proto_block: &ProtoBlock,
+    ) -> bool {
+        if self.is_flagged_proposer(era_id, proposer) {
+            info!(?era_id, ?sender, %proposer, "rejected proposal by flagged proposer");
+            return false;
+        }
+        match self.proposal_limits.check(proto_block) {
+            Ok(()) => true,
+            Err(error) => {
+                warn!(
+                    ?era_id, ?sender, %proposer, %error,
+                    "rejected oversized proposal, flagging proposer"
+                );
+                let _ = self
+                    .flagged_proposers
+                    .entry(era_id)
+                    .or_default()
+                    .insert(*proposer);
+                false
+            }
+        }
+    }
+
+    /// Returns whether `proposer` has proposed a block exceeding the limits in era `era_id`.
+    fn is_flagged_proposer(&self, era_id: EraId, proposer: &PublicKey) -> bool {
+        self.flagged_proposers
+            .get(&era_id)
+            .map_or(false, |flagged| flagged.contains(proposer))
+    }
+
     /// Inspect the active eras.
     #[cfg(test)]
     pub(crate) fn active_eras(&self) -> &HashMap<EraId, Era<I, R>> {
@@ -318,23 +542,43 @@ where
     {
         match self.era_supervisor.active_eras.get_mut(&era_id) {
             None => {
-                if era_id > self.era_supervisor.current_era {
-                    info!("received message for future {:?}", era_id);
-                } else {
-                    info!("received message for obsolete {:?}", era_id);
-                }
+                self.log_missing_era(era_id);
                 Effects::new()
             }
             Some(era) => match f(&mut *era.consensus, self.rng) {
                 Ok(results) => self.handle_consensus_results(era_id, results),
                 Err(error) => {
+                    // The protocol state can't be trusted anymore, so the node has to shut down.
                     error!(%error, ?era_id, "got error from era id {:?}: {:?}", era_id, error);
-                    Effects::new()
+                    let reason = format!("error in era {}: {}", era_id, error);
+                    self.effect_builder
+                        .request_shutdown(ShutdownReason::ConsensusFailure(reason))
+                        .ignore()
                 }
             },
         }
     }
 
+    /// Returns the details needed to trace the handling of the given message, or `None` if
+    /// tracing is disabled or the era isn't active.
+    fn trace_message(&self, era_id: EraId, payload: &[u8], sender: &I) -> Option<MessageTrace<I>> {
+        if !message_tracing::is_enabled() {
+            return None;
+        }
+        let era = self.era_supervisor.active_eras.get(&era_id)?;
+        let round = era.consensus.message_round(payload);
+        Some(MessageTrace::new(era_id, round, sender.clone()))
+    }
+
+    /// Logs that an event for an era which isn't active was received.
+    fn log_missing_era(&self, era_id: EraId) {
+        if era_id > self.era_supervisor.current_era {
+            info!("received message for future {:?}", era_id);
+        } else {
+            info!("received message for obsolete {:?}", era_id);
+        }
+    }
+
     pub(super) fn handle_timer(
         &mut self,
         era_id: EraId,
@@ -345,11 +589,108 @@ where
         })
     }
 
+    /// Starts verifying the signatures of an incoming message on the worker pool, once the earlier
+    /// messages from the same sender have arrived.
+    ///
+    /// Messages exceeding the maximum number of pending verifications are dropped.
     pub(super) fn handle_message(&mut self, sender: I, msg: ConsensusMessage) -> Effects<Event<I>> {
-        let ConsensusMessage { era_id, payload } = msg;
-        self.delegate_to_era(era_id, move |consensus, rng| {
-            consensus.handle_message(sender, payload, rng)
-        })
+        let ConsensusMessage {
+            era_id,
+            sequence_number,
+            payload,
+        } = msg;
+        if let Some(trace) = self.trace_message(era_id, &payload, &sender) {
+            let _ = trace.stage(Stage::Received);
+        }
+        let permits = Arc::clone(&self.era_supervisor.verification_permits);
+        let signature_scheme = self.era_supervisor.signature_scheme;
+        let max_pending = self.era_supervisor.max_pending_verifications;
+        let mut pending: usize = self
+            .era_supervisor
+            .active_eras
+            .values()
+            .map(|era| era.verification_queue.len())
+            .sum();
+        let era = match self.era_supervisor.active_eras.get_mut(&era_id) {
+            Some(era) => era,
+            None => {
+                self.log_missing_era(era_id);
+                return Effects::new();
+            }
+        };
+        let ready = era
+            .message_ordering
+            .push(sender.clone(), sequence_number, payload);
+        let mut effects = Effects::new();
+        for payload in ready {
+            if pending >= max_pending {
+                warn!(%era_id, ?sender, "too many messages awaiting verification, dropping message");
+                continue;
+            }
+            pending += 1;
+            let verifier = era.consensus.message_verifier(signature_scheme);
+            let seq = era.verification_queue.push(sender.clone(), payload.clone());
+            effects.extend(
+                verification::verify(Arc::clone(&permits), verifier, payload)
+                    .event(move |valid| Event::MessageVerified { era_id, seq, valid }),
+            );
+        }
+        effects
+    }
+
+    /// Passes all messages of the era that are ready after this verification result to the
+    /// consensus protocol, in the order they were received.
+    pub(super) fn handle_message_verified(
+        &mut self,
+        era_id: EraId,
+        seq: u64,
+        valid: bool,
+    ) -> Effects<Event<I>> {
+        let verified_messages = match self.era_supervisor.active_eras.get_mut(&era_id) {
+            Some(era) => era.verification_queue.complete(seq, valid),
+            // The era has been dropped while the message was being verified.
+            None => return Effects::new(),
+        };
+        let mut effects = Effects::new();
+        for VerifiedMessage {
+            sender,
+            payload,
+            valid,
+        } in verified_messages
+        {
+            let trace = self.trace_message(era_id, &payload, &sender);
+            if valid {
+                effects.extend(self.delegate_to_era(era_id, move |consensus, rng| {
+                    let trace = match trace {
+                        Some(trace) => trace,
+                        None => return consensus.handle_verified_message(sender, payload, rng),
+                    };
+                    let _ = trace.stage(Stage::Verified);
+                    let results = trace
+                        .stage(Stage::Processed)
+                        .in_scope(|| consensus.handle_verified_message(sender, payload, rng))?;
+                    for result in &results {
+                        if let ConsensusProtocolResult::CreatedGossipMessage(_)
+                        | ConsensusProtocolResult::CreatedTargetedMessage(..) = result
+                        {
+                            let _ = trace.stage(Stage::Responded);
+                        }
+                    }
+                    Ok(results)
+                }));
+            } else {
+                if let Some(trace) = trace {
+                    let _ = trace.stage(Stage::Rejected);
+                }
+                let error = Error::msg("invalid signature");
+                let result =
+                    ConsensusProtocolResult::InvalidIncomingMessage(payload, sender, error);
+                effects.extend(self.handle_consensus_result(era_id, result));
+            }
+        }
+        // An obsolete era kept only for the sake of verification can be dropped once it is done.
+        self.era_supervisor.drop_obsolete_eras();
+        effects
     }
 
     pub(super) fn handle_new_proto_block(
@@ -379,31 +720,31 @@ where
             "executed block in unexpected era"
         );
         // TODO - we should only sign if we're a validator for the given era ID.
+        let block_hash = block_header.hash();
         let signature = asymmetric_key::sign(
-            block_header.hash().inner(),
+            block_hash.inner(),
             &self.era_supervisor.secret_signing_key,
             &self.era_supervisor.public_signing_key,
+            SigningPurpose::FinalitySignature,
             self.rng,
         );
         let mut effects = responder.respond(signature).ignore();
+        let finality_signature = FinalitySignature {
+            block_hash,
+            public_key: self.era_supervisor.public_signing_key,
+            signature,
+        };
+        effects.extend(
+            self.effect_builder
+                .announce_block_signed(finality_signature)
+                .ignore(),
+        );
         if block_header.switch_block() {
-            // TODO: Learn the new weights from contract (validator rotation).
-            let validator_stakes = self.era_supervisor.validator_stakes.clone();
             self.era_supervisor
                 .current_era_mut()
                 .consensus
                 .deactivate_validator();
-            let new_era_id = block_header.era_id().successor();
-            info!(?new_era_id, "Era created");
-            let results = self.era_supervisor.new_era(
-                new_era_id,
-                Timestamp::now(), // TODO: This should be passed in.
-                validator_stakes,
-                block_header.timestamp(),
-                block_header.height() + 1,
-                *block_header.global_state_hash(),
-            );
-            effects.extend(self.handle_consensus_results(new_era_id, results));
+            effects.extend(self.start_era_after(&block_header));
         }
         effects.extend(
             self.effect_builder
@@ -413,6 +754,133 @@ where
         effects
     }
 
+    /// Starts the era following the one that the given switch block or restart block belongs to.
+    fn start_era_after(&mut self, block_header: &BlockHeader) -> Effects<Event<I>> {
+        // TODO: Learn the new weights from contract (validator rotation).
+        let validator_stakes = self.era_supervisor.validator_stakes.clone();
+        let new_era_id = block_header.era_id().successor();
+        info!(?new_era_id, "Era created");
+        let results = self.era_supervisor.new_era(
+            new_era_id,
+            Timestamp::now(), // TODO: This should be passed in.
+            validator_stakes,
+            block_header.timestamp(),
+            block_header.height() + 1,
+            *block_header.global_state_hash(),
+            *block_header.hash().inner(),
+        );
+        let mut effects = self.handle_consensus_results(new_era_id, results);
+        effects.extend(
+            self.effect_builder
+                .announce_era_started(new_era_id, self.era_supervisor.current_validator_weights())
+                .ignore(),
+        );
+        effects
+    }
+
+    /// Counts an operator's emergency restart order, relaying it to our peers if it is valid and
+    /// new.
+    ///
+    /// Once a quorum of operators has ordered a restart at the same block, all eras are dropped
+    /// and a new one is started following that block.  Dropping an era discards all of its state,
+    /// including held back and queued messages and statistics.  The stall monitor counts from the
+    /// restart, since the new era hasn't had a chance to finalize anything yet.  The flagged
+    /// proposers are kept: Their oversized proposals are signed, so the flag doesn't depend on
+    /// the era.  Timer and verification events for a dropped era that has the same ID as the new
+    /// one are handled by the new era's protocol instance, which ignores them as it would any
+    /// spurious timer or invalid message.
+    pub(super) fn handle_emergency_restart(
+        &mut self,
+        _sender: I,
+        restart: EmergencyRestart,
+    ) -> Effects<Event<I>> {
+        let now = Timestamp::now();
+        let outcome = self
+            .era_supervisor
+            .emergency_restarts
+            .add(restart.clone(), now);
+        let block_header = match outcome {
+            Outcome::Ignored => return Effects::new(),
+            Outcome::Counted => {
+                return self
+                    .effect_builder
+                    .broadcast_message::<I, _>(Message::EmergencyRestart(Box::new(restart)))
+                    .ignore();
+            }
+            Outcome::QuorumReached(block_header) => block_header,
+        };
+        warn!(
+            block_hash = %block_header.hash(),
+            height = block_header.height(),
+            "emergency restart ordered by quorum of operators; dropping all eras"
+        );
+        let mut effects = self
+            .effect_builder
+            .broadcast_message::<I, _>(Message::EmergencyRestart(Box::new(restart)))
+            .ignore();
+        self.era_supervisor.active_eras.clear();
+        if self.era_supervisor.stall_monitor.record_progress(now) {
+            let era_id = self.era_supervisor.current_era;
+            info!(?era_id, "consensus resumed after emergency restart");
+            effects.extend(
+                self.effect_builder
+                    .announce_consensus_resumed(era_id)
+                    .ignore(),
+            );
+        }
+        effects.extend(self.start_era_after(&block_header));
+        effects
+    }
+
+    /// Records finality signatures by `signers` of a block of era `era_id` in its statistics.
+    pub(super) fn handle_finality_signatures(
+        &mut self,
+        era_id: EraId,
+        signers: Vec<PublicKey>,
+    ) -> Effects<Event<I>> {
+        self.era_supervisor
+            .record_finality_signatures(era_id, &signers);
+        Effects::new()
+    }
+
+    /// Responds with the participation of the validators in the current era.
+    pub(super) fn handle_get_validator_participation(
+        &mut self,
+        responder: Responder<Option<ParticipationReport<PublicKey>>>,
+    ) -> Effects<Event<I>> {
+        responder
+            .respond(self.era_supervisor.participation_report())
+            .ignore()
+    }
+
+    /// Announces if consensus has stalled, and schedules the next check.
+    pub(super) fn handle_check_stall(&mut self) -> Effects<Event<I>> {
+        let now = Timestamp::now();
+        let mut effects = Effects::new();
+        if let Some(last_progress) = self.era_supervisor.stall_monitor.check(now) {
+            let era_id = self.era_supervisor.current_era;
+            warn!(?era_id, %last_progress, "consensus stalled");
+            effects.extend(
+                self.effect_builder
+                    .announce_consensus_stalled(era_id, last_progress)
+                    .ignore(),
+            );
+        }
+        effects.extend(self.schedule_stall_check());
+        effects
+    }
+
+    /// Schedules the next check whether consensus has stalled, if stall detection is enabled.
+    fn schedule_stall_check(&mut self) -> Effects<Event<I>> {
+        let stall_monitor = &self.era_supervisor.stall_monitor;
+        if !stall_monitor.is_enabled() {
+            return Effects::new();
+        }
+        self.effect_builder
+            .set_timeout(stall_monitor.next_check_delay(Timestamp::now()))
+            .event(|_| Event::CheckStall)
+    }
+
     pub(super) fn handle_accept_proto_block(
         &mut self,
         era_id: EraId,
@@ -468,9 +936,17 @@ where
                 Default::default()
             }
             ConsensusProtocolResult::CreatedGossipMessage(out_msg) => {
+                let message = match self.era_supervisor.active_eras.get_mut(&era_id) {
+                    Some(era) => {
+                        let sequence_number = era.next_sequence_number;
+                        era.next_sequence_number += 1;
+                        era_id.sequenced_message(sequence_number, out_msg)
+                    }
+                    None => era_id.message(out_msg),
+                };
                 // TODO: we'll want to gossip instead of broadcast here
                 self.effect_builder
-                    .broadcast_message(era_id.message(out_msg).into())
+                    .broadcast_message(message.into())
                     .ignore()
             }
             ConsensusProtocolResult::CreatedTargetedMessage(out_msg, to) => self
@@ -483,13 +959,28 @@ where
                     .set_timeout(timediff.into())
                     .event(move |_| Event::Timer { era_id, timestamp })
             }
-            ConsensusProtocolResult::CreateNewBlock { block_context } => self
+            ConsensusProtocolResult::CreateNewBlock {
+                block_context,
+                past_values,
+            } => self
                 .effect_builder
-                .request_proto_block(block_context, self.rng.gen())
-                .event(move |(proto_block, block_context)| Event::NewProtoBlock {
-                    era_id,
-                    proto_block,
+                .request_proposal(
                     block_context,
+                    past_values.iter().map(|value| *value.hash()).collect(),
+                    self.rng.gen(),
+                )
+                .event(move |proposal| {
+                    let (proto_block, block_context) = proposal.destructure();
+                    debug!(
+                        %era_id,
+                        deploy_root = %proto_block.deploy_root(),
+                        "received proposal for new block"
+                    );
+                    Event::NewProtoBlock {
+                        era_id,
+                        proto_block,
+                        block_context,
+                    }
                 }),
             ConsensusProtocolResult::FinalizedBlock(CpFinalizedBlock {
                 value: proto_block,
@@ -505,6 +996,15 @@ where
                     .effect_builder
                     .announce_finalized_proto_block(proto_block.clone())
                     .ignore();
+                self.era_supervisor.record_finalized_block(
+                    era_id,
+                    proposer,
+                    rewards
+                        .iter()
+                        .filter(|(_, reward)| **reward > 0)
+                        .map(|(validator, _)| validator),
+                    timestamp,
+                );
                 // Create instructions for slashing equivocators.
                 let mut system_transactions: Vec<_> = new_equivocators
                     .into_iter()
@@ -524,25 +1024,648 @@ where
                 );
                 // Request execution of the finalized block.
                 effects.extend(self.effect_builder.execute_block(fb).ignore());
+                if self
+                    .era_supervisor
+                    .stall_monitor
+                    .record_progress(Timestamp::now())
+                {
+                    info!(?era_id, "consensus resumed");
+                    effects.extend(
+                        self.effect_builder
+                            .announce_consensus_resumed(era_id)
+                            .ignore(),
+                    );
+                }
                 effects
             }
-            ConsensusProtocolResult::ValidateConsensusValue(sender, proto_block) => self
-                .effect_builder
-                .validate_block(sender.clone(), proto_block)
-                .event(move |(is_valid, proto_block)| {
-                    if is_valid {
-                        Event::AcceptProtoBlock {
-                            era_id,
-                            proto_block,
-                        }
-                    } else {
-                        Event::InvalidProtoBlock {
-                            era_id,
-                            sender,
-                            proto_block,
+            ConsensusProtocolResult::ValidateConsensusValue(sender, proto_block, proposer) => {
+                if !self
+                    .era_supervisor
+                    .admit_proposal(era_id, &sender, &proposer, &proto_block)
+                {
+                    return self.handle_invalid_proto_block(era_id, sender, proto_block);
+                }
+                self.effect_builder
+                    .validate_block(sender.clone(), proto_block)
+                    .event(move |(is_valid, proto_block)| {
+                        if is_valid {
+                            Event::AcceptProtoBlock {
+                                era_id,
+                                proto_block,
+                            }
+                        } else {
+                            Event::InvalidProtoBlock {
+                                era_id,
+                                sender,
+                                proto_block,
+                            }
                         }
-                    }
-                }),
+                    })
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{sync::Mutex, time::Duration};
+
+    use derive_more::From;
+    use tracing::{
+        field::{Field, Visit},
+        span::{Attributes, Id},
+        Subscriber,
+    };
+    use tracing_subscriber::{
+        layer::{Context, Layer, SubscriberExt},
+        registry,
+    };
+
+    use super::*;
+    use crate::{
+        components::{consensus::LeaderSeed, small_network::NodeId, storage::Storage},
+        effect::{
+            announcements::{ConsensusAnnouncement, ControlAnnouncement},
+            requests::{
+                BlockExecutorRequest, BlockValidationRequest, NetworkRequest,
+                ProposalBuilderRequest, StorageRequest,
+            },
+        },
+        reactor::{EventQueueHandle, QueueKind, Scheduler},
+        testing::TestRng,
+        types::DeployHash,
+        utils,
+    };
+
+    #[derive(Debug, From)]
+    enum ReactorEvent {
+        #[from]
+        Consensus(Event<NodeId>),
+        #[from]
+        Network(NetworkRequest<NodeId, Message>),
+        #[from]
+        ProposalBuilder(ProposalBuilderRequest),
+        #[from]
+        ConsensusAnnouncement(ConsensusAnnouncement),
+        #[from]
+        ControlAnnouncement(ControlAnnouncement),
+        #[from]
+        BlockExecutor(BlockExecutorRequest),
+        #[from]
+        BlockValidator(BlockValidationRequest<ProtoBlock, NodeId>),
+        #[from]
+        Storage(StorageRequest<Storage>),
+    }
+
+    /// A layer recording the stage of every consensus message span it sees.
+    #[derive(Clone, Default)]
+    struct StageCapturingLayer(Arc<Mutex<Vec<String>>>);
+
+    impl<S: Subscriber> Layer<S> for StageCapturingLayer {
+        fn new_span(&self, attrs: &Attributes<'_>, _id: &Id, _ctx: Context<'_, S>) {
+            if attrs.metadata().name() != "consensus_message" {
+                return;
+            }
+            let mut visitor = StageVisitor(None);
+            attrs.record(&mut visitor);
+            if let Some(stage) = visitor.0 {
+                self.0.lock().unwrap().push(stage);
+            }
+        }
+    }
+
+    /// Extracts the `stage` field of a span.
+    struct StageVisitor(Option<String>);
+
+    impl Visit for StageVisitor {
+        fn record_debug(&mut self, field: &Field, value: &dyn Debug) {
+            if field.name() == "stage" {
+                self.0 = Some(format!("{:?}", value));
+            }
+        }
+    }
+
+    /// Creates an era supervisor with the given validators, without starting any era.  We are not
+    /// one of the validators.
+    fn new_era_supervisor(
+        rng: &mut TestRng,
+        validator_stakes: Vec<(PublicKey, Motes)>,
+        registry: &Registry,
+    ) -> EraSupervisor<NodeId, TestRng> {
+        let secret_signing_key = Rc::new(SecretKey::random(rng));
+        let public_signing_key = PublicKey::from(secret_signing_key.as_ref());
+        let mut chainspec = Chainspec::random(rng);
+        chainspec.genesis.highway_config.finality_threshold_percent = 10;
+        EraSupervisor {
+            active_eras: Default::default(),
+            secret_signing_key,
+            public_signing_key,
+            validator_stakes,
+            current_era: EraId(0),
+            chainspec,
+            verification_permits: Arc::new(Semaphore::new(1)),
+            max_pending_verifications: 16,
+            emergency_restarts: EmergencyRestarts::new(vec![], 0),
+            retained_eras: 4,
+            stall_monitor: StallMonitor::new(Duration::from_secs(0), Timestamp::zero()),
+            metrics: EraMetrics::new(registry).expect("should create metrics"),
+            proposal_limits: ProposalLimits::new(2, 1024),
+            flagged_proposers: HashMap::new(),
+            signature_scheme: Some(SignatureScheme::Ed25519),
+            max_held_messages_per_sender: 64,
+            inactive_validator_threshold: 0.5,
+        }
+    }
+
+    /// Starts era 0 with us as the only validator, using the given key, and runs our validator
+    /// until it has created `count` signed votes.
+    fn create_votes(
+        era_supervisor: &mut EraSupervisor<NodeId, TestRng>,
+        secret_signing_key: SecretKey,
+        post_state_hash: hash::Digest,
+        count: usize,
+        rng: &mut TestRng,
+    ) -> Vec<Vec<u8>> {
+        let secret_signing_key = Rc::new(secret_signing_key);
+        era_supervisor.public_signing_key = PublicKey::from(secret_signing_key.as_ref());
+        era_supervisor.secret_signing_key = secret_signing_key;
+        let validator_stakes = vec![(
+            era_supervisor.public_signing_key,
+            Motes::new(U512::from(100)),
+        )];
+        let mut results = era_supervisor.new_era(
+            EraId(0),
+            Timestamp::zero(),
+            validator_stakes,
+            Timestamp::zero(),
+            0,
+            post_state_hash,
+            post_state_hash,
+        );
+
+        let consensus = &mut era_supervisor
+            .active_eras
+            .get_mut(&EraId(0))
+            .unwrap()
+            .consensus;
+        let mut votes = Vec::new();
+        while votes.len() < count {
+            match results.pop().expect("should create votes") {
+                ConsensusProtocolResult::CreatedGossipMessage(message) => votes.push(message),
+                ConsensusProtocolResult::ScheduleTimer(timestamp) => results.extend(
+                    consensus
+                        .handle_timer(timestamp, rng)
+                        .expect("should handle timer"),
+                ),
+                ConsensusProtocolResult::CreateNewBlock { block_context, .. } => results.extend(
+                    consensus
+                        .propose(ProtoBlock::new(vec![], false), block_context, rng)
+                        .expect("should propose"),
+                ),
+                _ => (),
+            }
+        }
+        votes
+    }
+
+    #[test]
+    fn should_drop_eras_outside_retention_window() {
+        let mut rng = TestRng::new();
+        let validator = PublicKey::from(&SecretKey::random(&mut rng));
+        let validator_stakes = vec![(validator, Motes::new(U512::from(100)))];
+        let mut era_supervisor =
+            new_era_supervisor(&mut rng, validator_stakes.clone(), &Registry::new());
+        let start_new_era =
+            |era_supervisor: &mut EraSupervisor<NodeId, TestRng>, rng: &mut TestRng, era_id| {
+                let timestamp = Timestamp::zero();
+                let post_state_hash = hash::Digest::random(rng);
+                let results = era_supervisor.new_era(
+                    EraId(era_id),
+                    timestamp,
+                    validator_stakes.clone(),
+                    timestamp,
+                    era_id * 10,
+                    post_state_hash,
+                    post_state_hash,
+                );
+                assert!(results.is_empty());
+            };
+        let active_era_ids = |era_supervisor: &EraSupervisor<NodeId, TestRng>| {
+            let mut era_ids: Vec<_> = era_supervisor
+                .active_eras
+                .keys()
+                .map(|era_id| era_id.0)
+                .collect();
+            era_ids.sort_unstable();
+            era_ids
+        };
+
+        for era_id in 0..=6 {
+            start_new_era(&mut era_supervisor, &mut rng, era_id);
+        }
+        assert_eq!(active_era_ids(&era_supervisor), vec![2, 3, 4, 5, 6]);
+
+        // An era with a message still being verified is kept beyond the retention window.
+        let sender: NodeId = rng.gen();
+        let seq = era_supervisor
+            .active_eras
+            .get_mut(&EraId(2))
+            .unwrap()
+            .verification_queue
+            .push(sender, vec![]);
+        start_new_era(&mut era_supervisor, &mut rng, 7);
+        assert_eq!(active_era_ids(&era_supervisor), vec![2, 3, 4, 5, 6, 7]);
+
+        // Once verification has completed, it is dropped, too.
+        let _ = era_supervisor
+            .active_eras
+            .get_mut(&EraId(2))
+            .unwrap()
+            .verification_queue
+            .complete(seq, true);
+        era_supervisor.drop_obsolete_eras();
+        assert_eq!(active_era_ids(&era_supervisor), vec![3, 4, 5, 6, 7]);
+    }
+
+    #[test]
+    fn should_record_block_count_and_participation_of_era() {
+        let mut rng = TestRng::new();
+        let validators: Vec<_> = (0..3)
+            .map(|_| PublicKey::from(&SecretKey::random(&mut rng)))
+            .collect();
+        let validator_stakes: Vec<_> = validators
+            .iter()
+            .zip(&[100, 300, 600])
+            .map(|(validator, stake)| (*validator, Motes::new(U512::from(*stake))))
+            .collect();
+        let stranger = PublicKey::from(&SecretKey::random(&mut rng));
+        let mut era_supervisor =
+            new_era_supervisor(&mut rng, validator_stakes.clone(), &Registry::new());
+        let mut start_new_era = |era_supervisor: &mut EraSupervisor<NodeId, TestRng>, era_id| {
+            let timestamp = Timestamp::zero();
+            let results = era_supervisor.new_era(
+                EraId(era_id),
+                timestamp,
+                validator_stakes.clone(),
+                timestamp,
+                era_id * 10,
+                hash::Digest::random(&mut rng),
+                hash::Digest::random(&mut rng),
+            );
+            assert!(results.is_empty());
+        };
+
+        // In the first era, only the first two validators participate: The first proposes both
+        // blocks, and the second is rewarded once.  Rewards for non-validators are disregarded.
+        start_new_era(&mut era_supervisor, 0);
+        assert_eq!(era_supervisor.metrics.block_count(), 0);
+        let proposed = Timestamp::now();
+        let none: &[PublicKey] = &[];
+        era_supervisor.record_finalized_block(
+            EraId(0),
+            validators[0],
+            &[validators[1], stranger],
+            proposed,
+        );
+        era_supervisor.record_finalized_block(EraId(0), validators[0], none, proposed);
+        assert_eq!(era_supervisor.metrics.block_count(), 2);
+        assert!((era_supervisor.metrics.participation() - 0.4).abs() < 1e-9);
+        assert_eq!(era_supervisor.metrics.finalization_count(), 2);
+
+        // A new era resets the gauges, and late blocks of the previous era don't affect them.
+        start_new_era(&mut era_supervisor, 1);
+        assert_eq!(era_supervisor.metrics.block_count(), 0);
+        assert!(era_supervisor.metrics.participation().abs() < 1e-9);
+        era_supervisor.record_finalized_block(EraId(0), validators[2], none, proposed);
+        assert_eq!(era_supervisor.metrics.block_count(), 0);
+        era_supervisor.record_finalized_block(EraId(1), validators[2], none, proposed);
+        assert_eq!(era_supervisor.metrics.block_count(), 1);
+        assert!((era_supervisor.metrics.participation() - 0.6).abs() < 1e-9);
+        assert_eq!(era_supervisor.metrics.finalization_count(), 4);
+    }
+
+    #[test]
+    fn should_report_validators_absent_from_era_as_inactive() {
+        let mut rng = TestRng::new();
+        let validators: Vec<_> = (0..4)
+            .map(|_| PublicKey::from(&SecretKey::random(&mut rng)))
+            .collect();
+        let validator_stakes: Vec<_> = validators
+            .iter()
+            .map(|validator| (*validator, Motes::new(U512::from(100))))
+            .collect();
+        let mut era_supervisor =
+            new_era_supervisor(&mut rng, validator_stakes.clone(), &Registry::new());
+        let timestamp = Timestamp::zero();
+        let results = era_supervisor.new_era(
+            EraId(0),
+            timestamp,
+            validator_stakes,
+            timestamp,
+            0,
+            hash::Digest::random(&mut rng),
+            hash::Digest::random(&mut rng),
+        );
+        assert!(results.is_empty());
+        let participation = |era_supervisor: &EraSupervisor<NodeId, TestRng>, index: usize| {
+            era_supervisor
+                .participation_report()
+                .expect("should have current era")
+                .validators
+                .into_iter()
+                .find(|participation| participation.validator == validators[index])
+                .expect("should report every validator")
+        };
+
+        // Before any block is finalized, nobody is reported as inactive.
+        assert_eq!(participation(&era_supervisor, 2).participation_rate, None);
+        assert!(!participation(&era_supervisor, 2).inactive);
+
+        // The first two validators propose and vote, and the fourth only signs the blocks as final,
+        // but the third validator doesn't contribute at all.
+        let proposed = Timestamp::now();
+        let voters = [validators[0], validators[1]];
+        era_supervisor.record_finalized_block(EraId(0), validators[0], &voters, proposed);
+        era_supervisor.record_finalized_block(EraId(0), validators[1], &voters, proposed);
+        era_supervisor.record_finality_signatures(EraId(0), &[validators[0], validators[3]]);
+        era_supervisor.record_finality_signatures(EraId(0), &[validators[0], validators[3]]);
+        // Signatures of blocks of other eras don't count towards the current one.
+        era_supervisor.record_finality_signatures(EraId(1), &[validators[2]]);
+
+        let report = era_supervisor
+            .participation_report()
+            .expect("should have current era");
+        assert_eq!(report.era_id, EraId(0));
+        assert_eq!(report.block_count, 2);
+        let inactive: Vec<_> = report
+            .validators
+            .iter()
+            .filter(|participation| participation.inactive)
+            .map(|participation| participation.validator)
+            .collect();
+        assert_eq!(inactive, vec![validators[2]]);
+        assert_eq!(
+            participation(&era_supervisor, 2).participation_rate,
+            Some(0.0)
+        );
+        assert_eq!(era_supervisor.metrics.inactive_validators(), 1);
+
+        let first = participation(&era_supervisor, 0);
+        assert_eq!(
+            (first.proposals, first.votes, first.finality_signatures),
+            (1, 2, 2)
+        );
+        assert_eq!(first.participation_rate, Some(1.0));
+        assert_eq!(
+            participation(&era_supervisor, 3).participation_rate,
+            Some(1.0)
+        );
+    }
+
+    #[test]
+    fn should_reject_oversized_proposals_and_flag_proposer() {
+        let mut rng = TestRng::new();
+        let validator = PublicKey::from(&SecretKey::random(&mut rng));
+        let validator_stakes = vec![(validator, Motes::new(U512::from(100)))];
+        // The test supervisor allows at most two deploys per proposal.
+        let mut era_supervisor =
+            new_era_supervisor(&mut rng, validator_stakes.clone(), &Registry::new());
+        let post_state_hash = hash::Digest::random(&mut rng);
+        let _ = era_supervisor.new_era(
+            EraId(0),
+            Timestamp::zero(),
+            validator_stakes.clone(),
+            Timestamp::zero(),
+            0,
+            post_state_hash,
+            post_state_hash,
+        );
+        let proposal = |rng: &mut TestRng, deploy_count| {
+            let deploys = (0..deploy_count)
+                .map(|_| DeployHash::new(hash::Digest::random(rng)))
+                .collect();
+            ProtoBlock::new(deploys, false)
+        };
+        let era_id = EraId(0);
+        let peer: NodeId = rng.gen();
+        let honest = PublicKey::from(&SecretKey::random(&mut rng));
+        let malicious = PublicKey::from(&SecretKey::random(&mut rng));
+
+        let within_limit = proposal(&mut rng, 2);
+        assert!(era_supervisor.admit_proposal(era_id, &peer, &honest, &within_limit));
+        assert!(!era_supervisor.is_flagged_proposer(era_id, &honest));
+
+        // The proposer is flagged, not the peer relaying the proposal.
+        let oversized = proposal(&mut rng, 3);
+        assert!(!era_supervisor.admit_proposal(era_id, &peer, &malicious, &oversized));
+        assert!(era_supervisor.is_flagged_proposer(era_id, &malicious));
+        assert!(!era_supervisor.is_flagged_proposer(era_id, &honest));
+        assert!(era_supervisor.admit_proposal(era_id, &peer, &honest, &within_limit));
+
+        // Once flagged, even proposals within the limits are rejected, but only in the same era.
+        assert!(!era_supervisor.admit_proposal(era_id, &peer, &malicious, &within_limit));
+        assert!(era_supervisor.admit_proposal(EraId(1), &peer, &malicious, &within_limit));
+
+        // The size is limited as well.
+        let small = proposal(&mut rng, 1);
+        let size = rmp_serde::to_vec(&small).expect("should serialize").len();
+        era_supervisor.proposal_limits = ProposalLimits::new(0, size);
+        assert!(era_supervisor.admit_proposal(era_id, &peer, &honest, &small));
+        let large = proposal(&mut rng, 10);
+        assert!(!era_supervisor.admit_proposal(era_id, &peer, &honest, &large));
+        assert!(era_supervisor.is_flagged_proposer(era_id, &honest));
+
+        // The flags are dropped along with their era.
+        let _ = era_supervisor.new_era(
+            EraId(5),
+            Timestamp::zero(),
+            validator_stakes,
+            Timestamp::zero(),
+            0,
+            post_state_hash,
+            post_state_hash,
+        );
+        assert!(!era_supervisor.active_eras.contains_key(&era_id));
+        assert!(!era_supervisor.is_flagged_proposer(era_id, &malicious));
+        assert!(era_supervisor.flagged_proposers.is_empty());
+    }
+
+    #[test]
+    fn should_derive_same_leader_schedule_from_same_previous_era() {
+        let mut rng = TestRng::new();
+        let validator_stakes: Vec<_> = (1..=5)
+            .map(|stake| {
+                let validator = PublicKey::from(&SecretKey::random(&mut rng));
+                (validator, Motes::new(U512::from(stake * 100)))
+            })
+            .collect();
+        let post_state_hash = hash::Digest::random(&mut rng);
+        let start_era = |era_supervisor: &mut EraSupervisor<NodeId, TestRng>, previous_era_hash| {
+            let timestamp = Timestamp::zero();
+            let results = era_supervisor.new_era(
+                EraId(1),
+                timestamp,
+                validator_stakes.clone(),
+                timestamp,
+                10,
+                post_state_hash,
+                previous_era_hash,
+            );
+            assert!(results.is_empty());
+        };
+        let leaders = |era_supervisor: &EraSupervisor<NodeId, TestRng>| -> Vec<PublicKey> {
+            let consensus = &era_supervisor.active_eras[&EraId(1)].consensus;
+            (0..100)
+                .map(|round| consensus.leader(Timestamp::from(round << 14)))
+                .collect()
+        };
+
+        // Two nodes with the same chainspec and the same previous era select the same leaders.
+        let mut first = new_era_supervisor(&mut rng, validator_stakes.clone(), &Registry::new());
+        first.chainspec.genesis.highway_config.leader_seed = LeaderSeed::PreviousEraBlock;
+        let mut second = new_era_supervisor(&mut rng, validator_stakes.clone(), &Registry::new());
+        second.chainspec = first.chainspec.clone();
+        let previous_era_hash = hash::Digest::random(&mut rng);
+        start_era(&mut first, previous_era_hash);
+        start_era(&mut second, previous_era_hash);
+        let schedule = leaders(&first);
+        assert_eq!(schedule, leaders(&second));
+        assert!(schedule.iter().collect::<HashSet<_>>().len() > 1);
+
+        // A different previous era results in a different schedule.
+        let mut third = new_era_supervisor(&mut rng, validator_stakes.clone(), &Registry::new());
+        third.chainspec = first.chainspec;
+        start_era(&mut third, hash::Digest::random(&mut rng));
+        assert_ne!(schedule, leaders(&third));
+    }
+
+    #[test]
+    fn should_reject_messages_with_unexpected_signature_scheme() {
+        let mut rng = TestRng::new();
+        // The test supervisor expects Ed25519, but we are the only validator and use secp256k1.
+        let mut era_supervisor = new_era_supervisor(&mut rng, vec![], &Registry::new());
+        let secret_signing_key = SecretKey::random_secp256k1(&mut rng);
+        let post_state_hash = hash::Digest::random(&mut rng);
+        let message = create_votes(
+            &mut era_supervisor,
+            secret_signing_key,
+            post_state_hash,
+            1,
+            &mut rng,
+        )
+        .remove(0);
+
+        let consensus = &era_supervisor.active_eras[&EraId(0)].consensus;
+        let verifier = consensus.message_verifier(era_supervisor.signature_scheme);
+        assert!(!verifier(&message));
+        let verifier = consensus.message_verifier(Some(SignatureScheme::Secp256k1));
+        assert!(verifier(&message));
+        let verifier = consensus.message_verifier(None);
+        assert!(verifier(&message));
+    }
+
+    #[tokio::test]
+    async fn should_drop_messages_exceeding_max_pending_verifications() {
+        let mut rng = TestRng::new();
+        let post_state_hash = hash::Digest::random(&mut rng);
+        let mut validator = new_era_supervisor(&mut rng, vec![], &Registry::new());
+        let secret_signing_key = SecretKey::random_ed25519(&mut rng);
+        let votes = create_votes(
+            &mut validator,
+            secret_signing_key,
+            post_state_hash,
+            3,
+            &mut rng,
+        );
+
+        let validator_stakes = vec![(validator.public_signing_key, Motes::new(U512::from(100)))];
+        let mut observer = new_era_supervisor(&mut rng, validator_stakes.clone(), &Registry::new());
+        observer.chainspec = validator.chainspec;
+        observer.max_pending_verifications = 2;
+        let _ = observer.new_era(
+            EraId(0),
+            Timestamp::zero(),
+            validator_stakes,
+            Timestamp::zero(),
+            0,
+            post_state_hash,
+            post_state_hash,
+        );
+
+        let scheduler = utils::leak(Scheduler::<ReactorEvent>::new(QueueKind::weights()));
+        let effect_builder = EffectBuilder::new(EventQueueHandle::new(scheduler));
+        let mut verifications = Vec::new();
+        for vote in &votes {
+            let sender: NodeId = rng.gen();
+            verifications.extend(
+                observer
+                    .handling_wrapper(effect_builder, &mut rng)
+                    .handle_message(sender, EraId(0).message(vote.clone())),
+            );
+        }
+        // Only the first two messages are verified, the third one is dropped.
+        assert_eq!(verifications.len(), 2);
+        assert_eq!(observer.active_eras[&EraId(0)].verification_queue.len(), 2);
+    }
+
+    #[tokio::test]
+    async fn should_trace_each_stage_of_message_handling() {
+        let mut rng = TestRng::new();
+        let post_state_hash = hash::Digest::random(&mut rng);
+        let mut validator = new_era_supervisor(&mut rng, vec![], &Registry::new());
+        let secret_signing_key = SecretKey::random_ed25519(&mut rng);
+        let votes = create_votes(
+            &mut validator,
+            secret_signing_key,
+            post_state_hash,
+            2,
+            &mut rng,
+        );
+
+        // The observer has to run the same era as the validator to accept its votes.
+        let validator_stakes = vec![(validator.public_signing_key, Motes::new(U512::from(100)))];
+        let mut observer = new_era_supervisor(&mut rng, validator_stakes.clone(), &Registry::new());
+        observer.chainspec = validator.chainspec.clone();
+        let results = observer.new_era(
+            EraId(0),
+            Timestamp::zero(),
+            validator_stakes,
+            Timestamp::zero(),
+            0,
+            post_state_hash,
+            post_state_hash,
+        );
+        assert!(results.is_empty());
+
+        let capturing_layer = StageCapturingLayer::default();
+        let _guard = tracing::subscriber::set_default(registry().with(capturing_layer.clone()));
+        message_tracing::set_enabled(true);
+
+        // The second vote cites the first one, which the observer doesn't know yet, so it
+        // requests that dependency from the sender in response.
+        let scheduler = utils::leak(Scheduler::<ReactorEvent>::new(QueueKind::weights()));
+        let effect_builder = EffectBuilder::new(EventQueueHandle::new(scheduler));
+        let sender: NodeId = rng.gen();
+        let message = EraId(0).message(votes[1].clone());
+        let effects = observer
+            .handling_wrapper(effect_builder, &mut rng)
+            .handle_message(sender, message);
+        for effect in effects {
+            for event in effect.await {
+                if let Event::MessageVerified { era_id, seq, valid } = event {
+                    assert!(valid);
+                    let _ = observer
+                        .handling_wrapper(effect_builder, &mut rng)
+                        .handle_message_verified(era_id, seq, valid);
+                }
+            }
         }
+
+        message_tracing::set_enabled(false);
+        let stages = capturing_layer.0.lock().unwrap().clone();
+        assert_eq!(
+            stages,
+            vec!["received", "verified", "processed", "responded"]
+        );
     }
 }
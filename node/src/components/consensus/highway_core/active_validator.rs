@@ -1,4 +1,7 @@
-use std::fmt::{self, Debug};
+use std::{
+    fmt::{self, Debug},
+    iter,
+};
 
 use rand::{CryptoRng, Rng};
 use tracing::{error, warn};
@@ -24,9 +27,10 @@ pub(crate) enum Effect<C: Context> {
     NewVertex(ValidVertex<C>),
     /// `handle_timer` needs to be called at the specified time.
     ScheduleTimer(Timestamp),
-    /// `propose` needs to be called with a value for a new block with the specified block context
-    /// and parent value.
-    RequestNewBlock(BlockContext),
+    /// `propose` needs to be called with a value for a new block with the specified block context.
+    /// The values of the new block's ancestors are given too, parent first, so that the new value
+    /// can avoid repeating their contents.
+    RequestNewBlock(BlockContext, Vec<C::ConsensusValue>),
     /// This validator produced an equivocation.
     ///
     /// When this is returned, the validator automatically deactivates.
@@ -176,9 +180,12 @@ impl<C: Context> ActiveValidator<C> {
         }
         let opt_parent = opt_parent_hash.map(|bh| state.block(bh));
         let height = opt_parent.map_or(0, |block| block.height);
+        let past_values = iter::successors(opt_parent_hash, |bh| state.block(bh).parent())
+            .map(|bh| state.block(bh).value.clone())
+            .collect();
         self.next_proposal = Some((timestamp, panorama));
         let bctx = BlockContext::new(timestamp, height);
-        Some(Effect::RequestNewBlock(bctx))
+        Some(Effect::RequestNewBlock(bctx, past_values))
     }
 
     /// Proposes a new block with the given consensus value.
@@ -419,8 +426,8 @@ mod tests {
 
         // Alice wants to propose a block, and also make her witness vote at 426.
         let bctx = match &*alice_av.handle_timer(416.into(), &state, instance_id, &mut rng) {
-            [Eff::ScheduleTimer(timestamp), Eff::RequestNewBlock(bctx)]
-                if *timestamp == 426.into() =>
+            [Eff::ScheduleTimer(timestamp), Eff::RequestNewBlock(bctx, past_values)]
+                if *timestamp == 426.into() && past_values.is_empty() =>
             {
                 bctx.clone()
             }
@@ -462,6 +469,14 @@ mod tests {
 
         // Payment finalized! "One Pumpkin Spice Mochaccino for Corbyn!"
         assert_eq!(Some(&prop_hash), fd.next_finalized(&state, 0.into()));
+
+        // Bob's proposal builds on Alice's, so he must not include its deploy again.
+        match &*bob_av.handle_timer(432.into(), &state, instance_id, &mut rng) {
+            [Eff::ScheduleTimer(_), Eff::RequestNewBlock(_, past_values)] => {
+                assert_eq!(vec![0xC0FFEE], *past_values)
+            }
+            effects => panic!("unexpected effects {:?}", effects),
+        }
         Ok(())
     }
 }
@@ -26,6 +26,13 @@ impl<C: Context> Evidence<C> {
         }
     }
 
+    /// Returns the votes the evidence consists of.
+    pub(crate) fn votes(&self) -> impl Iterator<Item = &SignedWireVote<C>> {
+        match self {
+            Evidence::Equivocation(vote0, vote1) => vec![vote0, vote1].into_iter(),
+        }
+    }
+
     /// Validates the evidence and returns `Ok(())` if it is valid.
     /// "Validation" can mean different things for different type of evidence.
     ///
@@ -40,6 +40,8 @@ pub(crate) enum EvidenceError {
     EquivocationDifferentInstances,
     #[error("The perpetrator is not a validator.")]
     UnknownPerpetrator,
+    #[error("A vote in the evidence has an invalid signature.")]
+    Signature,
 }
 
 /// A vertex that has passed initial validation.
@@ -166,7 +168,19 @@ impl<C: Context> Highway<C> {
         &self,
         vertex: Vertex<C>,
     ) -> Result<PreValidatedVertex<C>, (Vertex<C>, VertexError)> {
-        match self.do_pre_validate_vertex(&vertex) {
+        match self.do_pre_validate_vertex(&vertex, true) {
+            Err(err) => Err((vertex, err)),
+            Ok(()) => Ok(PreValidatedVertex(vertex)),
+        }
+    }
+
+    /// Does initial validation of a vertex whose signature has already been verified, e.g. on a
+    /// worker thread. Returns an error if the vertex is invalid.
+    pub(crate) fn pre_validate_verified_vertex(
+        &self,
+        vertex: Vertex<C>,
+    ) -> Result<PreValidatedVertex<C>, (Vertex<C>, VertexError)> {
+        match self.do_pre_validate_vertex(&vertex, false) {
             Err(err) => Err((vertex, err)),
             Ok(()) => Ok(PreValidatedVertex(vertex)),
         }
@@ -290,6 +304,15 @@ impl<C: Context> Highway<C> {
         &self.state
     }
 
+    /// Returns the leader of the round starting at `timestamp`.
+    pub(crate) fn leader(&self, timestamp: Timestamp) -> &C::ValidatorId {
+        let idx = self.state.leader(timestamp);
+        self.validators
+            .get_by_index(idx)
+            .expect("leader should be a validator")
+            .id()
+    }
+
     fn on_new_vote<R: Rng + CryptoRng + ?Sized>(
         &mut self,
         vhash: &C::Hash,
@@ -319,7 +342,7 @@ impl<C: Context> Highway<C> {
             match effect {
                 Effect::NewVertex(vv) => result.extend(self.add_valid_vertex(vv.clone(), rng)),
                 Effect::WeEquivocated(_) => self.deactivate_validator(),
-                Effect::ScheduleTimer(_) | Effect::RequestNewBlock(_) => (),
+                Effect::ScheduleTimer(_) | Effect::RequestNewBlock(..) => (),
             }
         }
         result.extend(effects);
@@ -327,23 +350,37 @@ impl<C: Context> Highway<C> {
     }
 
     /// Performs initial validation and returns an error if `vertex` is invalid. (See
-    /// `PreValidatedVertex` and `validate_vertex`.)
-    fn do_pre_validate_vertex(&self, vertex: &Vertex<C>) -> Result<(), VertexError> {
+    /// `PreValidatedVertex` and `validate_vertex`.) The vote signatures, including those in
+    /// evidence, are only checked if `check_signature` is `true`.
+    fn do_pre_validate_vertex(
+        &self,
+        vertex: &Vertex<C>,
+        check_signature: bool,
+    ) -> Result<(), VertexError> {
         match vertex {
             Vertex::Vote(vote) => {
                 let v_id = self.validator_id(&vote).ok_or(VoteError::Creator)?;
                 if vote.wire_vote.instance_id != self.instance_id {
                     return Err(VoteError::InstanceId.into());
                 }
-                if !C::verify_signature(&vote.hash(), v_id, &vote.signature) {
+                if check_signature && !C::verify_signature(&vote.hash(), v_id, &vote.signature) {
                     return Err(VoteError::Signature.into());
                 }
                 Ok(self.state.pre_validate_vote(vote)?)
             }
             Vertex::Evidence(evidence) => {
                 evidence.validate()?;
-                if !self.validators.contains(evidence.perpetrator()) {
-                    return Err(EvidenceError::UnknownPerpetrator.into());
+                let v_id = self
+                    .validators
+                    .get_by_index(evidence.perpetrator())
+                    .map(Validator::id)
+                    .ok_or(EvidenceError::UnknownPerpetrator)?;
+                if check_signature
+                    && !evidence
+                        .votes()
+                        .all(|vote| C::verify_signature(&vote.hash(), v_id, &vote.signature))
+                {
+                    return Err(EvidenceError::Signature.into());
                 }
                 Ok(())
             }
@@ -389,7 +426,11 @@ pub(crate) mod tests {
     use crate::{
         components::consensus::{
             highway_core::{
-                highway::{Highway, SignedWireVote, Vertex, VertexError, VoteError, WireVote},
+                evidence::Evidence,
+                highway::{
+                    EvidenceError, Highway, SignedWireVote, Vertex, VertexError, VoteError,
+                    WireVote,
+                },
                 state::{
                     tests::{
                         TestContext, ALICE, ALICE_SEC, BOB, BOB_SEC, CAROL, CAROL_SEC, WEIGHTS,
@@ -458,4 +499,54 @@ pub(crate) mod tests {
         let vv = highway.validate_vertex(pvv).unwrap();
         assert!(highway.add_valid_vertex(vv, &mut rng).is_empty());
     }
+
+    #[test]
+    fn invalid_evidence_signature_error() {
+        let mut rng = TestRng::new();
+
+        let state: State<TestContext> = State::new_test(WEIGHTS, 0);
+        let validators = Validators::from_iter(
+            vec![ALICE, BOB, CAROL]
+                .into_iter()
+                .map(|vid| (vid.0, WEIGHTS[vid.0 as usize].0)),
+        );
+        let highway = Highway {
+            instance_id: 1u64,
+            validators,
+            state,
+            active_validator: None,
+        };
+        let wvote = |value| WireVote {
+            panorama: Panorama::new(WEIGHTS.len()),
+            creator: CAROL,
+            instance_id: highway.instance_id,
+            value: Some(value),
+            seq_number: 0,
+            timestamp: Timestamp::zero(),
+            round_exp: 4,
+        };
+        let signed = |wire_vote: WireVote<TestContext>, rng: &mut TestRng| SignedWireVote {
+            signature: CAROL_SEC.sign(&wire_vote.hash(), rng),
+            wire_vote,
+        };
+        let vote0 = signed(wvote(0), &mut rng);
+        let vote1 = signed(wvote(1), &mut rng);
+
+        // A forged second vote must not be accepted as proof of an equivocation.
+        let forged_vote1 = SignedWireVote {
+            signature: 1u64,
+            ..vote1.clone()
+        };
+        let forged = Vertex::Evidence(Evidence::Equivocation(vote0.clone(), forged_vote1));
+        let err = VertexError::Evidence(EvidenceError::Signature);
+        assert_eq!(
+            Err((forged.clone(), err)),
+            highway.pre_validate_vertex(forged.clone())
+        );
+        // Unless the signatures have been verified already.
+        assert!(highway.pre_validate_verified_vertex(forged).is_ok());
+
+        let evidence = Vertex::Evidence(Evidence::Equivocation(vote0, vote1));
+        assert!(highway.pre_validate_vertex(evidence).is_ok());
+    }
 }
@@ -52,6 +52,14 @@ impl<C: Context> Vertex<C> {
             Vertex::Evidence(_) => None,
         }
     }
+
+    /// Returns the index of the validator who created this vertex, if it is a vote.
+    pub(crate) fn creator(&self) -> Option<ValidatorIndex> {
+        match self {
+            Vertex::Vote(swvote) => Some(swvote.wire_vote.creator),
+            Vertex::Evidence(_) => None,
+        }
+    }
 }
 
 #[derive(Clone, Debug, Eq, PartialEq, Serialize, Deserialize)]
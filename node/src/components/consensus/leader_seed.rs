@@ -0,0 +1,68 @@
+//! Derivation of the seed for selecting an era's leaders.
+//!
+//! The leader of each round is chosen at random, weighted by stake, from a seed all nodes have to
+//! agree on.  With `LeaderSeed::PreviousEraBlock`, the seed of an era is derived as follows:
+//!
+//! 1. Start a BLAKE2b hash with a 32-byte output.
+//! 2. Input the domain separator `b"leader-seed"`.
+//! 3. Input the era ID as 8 little-endian bytes.
+//! 4. Input the 32 bytes of the hash of the previous era's switch block.  The genesis era has no
+//!    previous era, so the genesis post-state hash takes its place.
+//! 5. The seed is the first 8 bytes of the output, read as a little-endian `u64`.
+//!
+//! The switch block is final before the era starts, so the seed varies from era to era and isn't
+//! known before the previous era has ended.  It isn't unbiasable, though: The proposer of the
+//! switch block chooses its contents, e.g. its deploys and timestamp, and can try out different
+//! ones to grind for a hash giving a schedule it prefers.  With `LeaderSeed::Constant`, every era
+//! uses the seed 0, i.e. the same schedule.
+
+use std::convert::TryInto;
+
+use blake2::{
+    digest::{Input, VariableOutput},
+    VarBlake2b,
+};
+use serde::{Deserialize, Serialize};
+
+use super::EraId;
+use crate::crypto::hash::Digest;
+
+/// The domain separator for hashing the leader seed.
+const DOMAIN: &[u8] = b"leader-seed";
+
+/// How the seed for selecting an era's leaders is derived.
+#[derive(Copy, Clone, Debug, Eq, PartialEq, Deserialize, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum LeaderSeed {
+    /// Every era uses the same seed.
+    Constant,
+    /// The seed is derived from the hash of the previous era's switch block.
+    PreviousEraBlock,
+}
+
+impl Default for LeaderSeed {
+    fn default() -> Self {
+        LeaderSeed::PreviousEraBlock
+    }
+}
+
+impl LeaderSeed {
+    /// Returns the seed for selecting the leaders of `era_id`, whose previous era ended with the
+    /// switch block with hash `previous_era_hash`.
+    pub(crate) fn derive(self, era_id: EraId, previous_era_hash: &Digest) -> u64 {
+        match self {
+            LeaderSeed::Constant => 0,
+            LeaderSeed::PreviousEraBlock => {
+                let mut hasher = VarBlake2b::new(Digest::LENGTH).expect("should create hasher");
+                hasher.input(DOMAIN);
+                hasher.input(era_id.0.to_le_bytes());
+                hasher.input(previous_era_hash);
+                let mut seed = 0;
+                hasher.variable_result(|slice| {
+                    seed = u64::from_le_bytes(slice[..8].try_into().expect("should have 8 bytes"));
+                });
+                seed
+            }
+        }
+    }
+}
@@ -0,0 +1,171 @@
+//! Ordering of incoming consensus messages per sender.
+//!
+//! Each node numbers the messages it broadcasts in an era consecutively, starting from zero.  The
+//! network doesn't preserve their order, and the consensus protocol may reject a message it
+//! receives before one of its predecessors, so a sender's later messages are held back until the
+//! earlier ones have arrived.  The first message received from a sender determines where its
+//! sequence starts, since we may have connected to it mid-era.  At most `max_held` messages are
+//! held per sender: Beyond that, the missing ones are assumed lost and skipped.
+//!
+//! Messages without a sequence number, e.g. responses sent to a single peer, and messages with a
+//! sequence number lower than expected, e.g. after the sender restarted, are passed on right away.
+//!
+//! An honest sender never exhausts the sequence numbers, so a sender whose sequence reaches the
+//! maximum is forgotten, along with all its held messages, and its numbered messages are dropped.
+
+use std::{
+    collections::{BTreeMap, HashMap},
+    hash::Hash,
+};
+
+use tracing::{debug, warn};
+
+/// The messages held back for a single sender.
+#[derive(Debug)]
+struct SenderQueue {
+    /// The sequence number of the next message to pass on, or `None` if the sender has exhausted
+    /// the sequence numbers.
+    next: Option<u64>,
+    /// The messages received ahead of `next`, by sequence number.
+    held: BTreeMap<u64, Vec<u8>>,
+}
+
+/// Puts the incoming messages of a single era into each sender's order.
+#[derive(Debug)]
+pub(crate) struct MessageOrdering<I> {
+    /// The maximum number of messages held back per sender.  If 0, messages are passed on in the
+    /// order they arrive in.
+    max_held: usize,
+    senders: HashMap<I, SenderQueue>,
+}
+
+impl<I: Clone + Eq + Hash> MessageOrdering<I> {
+    /// Creates an ordering holding back up to `max_held` messages per sender.
+    pub(crate) fn new(max_held: usize) -> Self {
+        MessageOrdering {
+            max_held,
+            senders: HashMap::new(),
+        }
+    }
+
+    /// Adds a message from `sender` with the given sequence number.
+    ///
+    /// Returns the messages from `sender` which are now ready to be handled, in order.
+    pub(crate) fn push(
+        &mut self,
+        sender: I,
+        sequence_number: Option<u64>,
+        payload: Vec<u8>,
+    ) -> Vec<Vec<u8>> {
+        let sequence_number = match sequence_number {
+            Some(sequence_number) if self.max_held != 0 => sequence_number,
+            _ => return vec![payload],
+        };
+        let queue = self.senders.entry(sender).or_insert_with(|| SenderQueue {
+            next: Some(sequence_number),
+            held: BTreeMap::new(),
+        });
+        let mut next = match queue.next {
+            Some(next) if sequence_number < next => return vec![payload],
+            Some(next) => next,
+            None => return Vec::new(),
+        };
+        let _ = queue.held.insert(sequence_number, payload);
+        if queue.held.len() > self.max_held {
+            // Skip the missing messages up to the earliest one held.
+            let earliest = *queue.held.keys().next().expect("should hold messages");
+            debug!(
+                missing = earliest - next,
+                "skipping consensus messages which failed to arrive"
+            );
+            next = earliest;
+        }
+
+        let mut ready = Vec::new();
+        while let Some(payload) = queue.held.remove(&next) {
+            ready.push(payload);
+            next = match next.checked_add(1) {
+                Some(next) => next,
+                None => {
+                    warn!("sender exhausted the consensus message sequence numbers; dropping it");
+                    queue.held.clear();
+                    queue.next = None;
+                    return ready;
+                }
+            };
+        }
+        queue.next = Some(next);
+        ready
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn should_pass_on_messages_in_sequence_order() {
+        let mut ordering = MessageOrdering::new(10);
+        let (alice, bob) = (0u8, 1u8);
+
+        assert_eq!(ordering.push(alice, Some(0), vec![0]), vec![vec![0]]);
+        // Alice's messages 3 and 2 overtake message 1, and are held back until it arrives.
+        assert!(ordering.push(alice, Some(3), vec![3]).is_empty());
+        assert!(ordering.push(alice, Some(2), vec![2]).is_empty());
+        // Bob's messages are ordered independently.
+        assert_eq!(ordering.push(bob, Some(0), vec![10]), vec![vec![10]]);
+        assert_eq!(
+            ordering.push(alice, Some(1), vec![1]),
+            vec![vec![1], vec![2], vec![3]]
+        );
+        assert_eq!(ordering.push(alice, Some(4), vec![4]), vec![vec![4]]);
+    }
+
+    #[test]
+    fn should_start_sequence_at_first_message_received() {
+        let mut ordering = MessageOrdering::new(10);
+
+        // We connected mid-era: The sender's first message is number 100, and isn't held back
+        // waiting for the earlier ones.
+        assert_eq!(ordering.push(0u8, Some(100), vec![100]), vec![vec![100]]);
+        assert!(ordering.push(0, Some(102), vec![102]).is_empty());
+        assert_eq!(
+            ordering.push(0, Some(101), vec![101]),
+            vec![vec![101], vec![102]]
+        );
+    }
+
+    #[test]
+    fn should_drop_sender_exhausting_sequence_numbers() {
+        let mut ordering = MessageOrdering::new(10);
+        assert_eq!(
+            ordering.push(0u8, Some(u64::MAX - 1), vec![0]),
+            vec![vec![0]]
+        );
+        assert_eq!(ordering.push(0, Some(u64::MAX), vec![1]), vec![vec![1]]);
+
+        // All further numbered messages by the sender are dropped, while other senders and
+        // unnumbered messages are unaffected.
+        assert!(ordering.push(0, Some(u64::MAX), vec![2]).is_empty());
+        assert_eq!(ordering.push(0, None, vec![3]), vec![vec![3]]);
+        assert_eq!(ordering.push(1, Some(0), vec![4]), vec![vec![4]]);
+    }
+
+    #[test]
+    fn should_skip_missing_messages_beyond_bound() {
+        let mut ordering = MessageOrdering::new(2);
+        assert_eq!(ordering.push(0u8, Some(0), vec![0]), vec![vec![0]]);
+
+        // Message 1 never arrives.
+        assert!(ordering.push(0, Some(2), vec![2]).is_empty());
+        assert!(ordering.push(0, Some(3), vec![3]).is_empty());
+        assert_eq!(
+            ordering.push(0, Some(4), vec![4]),
+            vec![vec![2], vec![3], vec![4]]
+        );
+
+        // Late and unnumbered messages are passed on right away.
+        assert_eq!(ordering.push(0, Some(1), vec![1]), vec![vec![1]]);
+        assert_eq!(ordering.push(0, None, vec![5]), vec![vec![5]]);
+    }
+}
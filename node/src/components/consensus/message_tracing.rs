@@ -0,0 +1,99 @@
+//! Tracing of the lifecycle of incoming consensus messages.
+//!
+//! If enabled, every incoming consensus message is logged at each stage of its handling: when it
+//! is received, when its signatures have been verified (or rejected), when it is processed by the
+//! consensus protocol, and for every message emitted in response.  Each stage is recorded in a
+//! span named `consensus_message` carrying the message's era, round and sender, so that the
+//! lifecycle of a single message can be followed through the logs.
+//!
+//! Tracing is off by default, since it is very verbose.  It is enabled initially via the
+//! consensus config, and can be toggled at runtime without restarting the node.
+
+use std::{
+    fmt::{self, Debug, Display, Formatter},
+    sync::atomic::{AtomicBool, Ordering},
+};
+
+use tracing::{info, info_span, Span};
+
+use super::EraId;
+use crate::types::Timestamp;
+
+/// Whether consensus message tracing is enabled.
+static ENABLED: AtomicBool = AtomicBool::new(false);
+
+/// Toggles consensus message tracing, and returns whether it is enabled now.
+pub fn toggle() -> bool {
+    !ENABLED.fetch_xor(true, Ordering::SeqCst)
+}
+
+/// Enables or disables consensus message tracing.
+pub(crate) fn set_enabled(enabled: bool) {
+    ENABLED.store(enabled, Ordering::SeqCst);
+}
+
+/// Returns whether consensus message tracing is enabled.
+pub(crate) fn is_enabled() -> bool {
+    ENABLED.load(Ordering::SeqCst)
+}
+
+/// A stage in the handling of an incoming consensus message.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub(crate) enum Stage {
+    /// The message was received from a peer.
+    Received,
+    /// The message's signatures were verified.
+    Verified,
+    /// The message's signatures were invalid.
+    Rejected,
+    /// The message is being processed by the consensus protocol.
+    Processed,
+    /// A message was emitted in response.
+    Responded,
+}
+
+impl Display for Stage {
+    fn fmt(&self, formatter: &mut Formatter<'_>) -> fmt::Result {
+        let stage = match self {
+            Stage::Received => "received",
+            Stage::Verified => "verified",
+            Stage::Rejected => "rejected",
+            Stage::Processed => "processed",
+            Stage::Responded => "responded",
+        };
+        formatter.write_str(stage)
+    }
+}
+
+/// The identifying details of an incoming consensus message, used to trace its stages.
+#[derive(Clone, Debug)]
+pub(crate) struct MessageTrace<I> {
+    era_id: EraId,
+    /// The round the message belongs to, if it is a vote.
+    round: Option<Timestamp>,
+    sender: I,
+}
+
+impl<I: Debug> MessageTrace<I> {
+    /// Creates the details of a message in the given era and round, from the given sender.
+    pub(crate) fn new(era_id: EraId, round: Option<Timestamp>, sender: I) -> Self {
+        MessageTrace {
+            era_id,
+            round,
+            sender,
+        }
+    }
+
+    /// Returns a span for the given stage of the message's handling, and logs entering it.
+    pub(crate) fn stage(&self, stage: Stage) -> Span {
+        let span = info_span!(
+            "consensus_message",
+            %stage,
+            era = self.era_id.0,
+            round = ?self.round,
+            sender = ?self.sender
+        );
+        span.in_scope(|| info!("consensus message {}", stage));
+        span
+    }
+}
@@ -0,0 +1,65 @@
+//! Limits on the size of block proposals.
+//!
+//! Validating a proposal means fetching and checking every deploy it contains, so a leader
+//! proposing an enormous block could stall validation for everyone.  Proposals exceeding the
+//! number of deploys or serialized size set in the chainspec are therefore rejected outright,
+//! before any deploy is fetched.  Since the limits are part of the chainspec, all honest nodes
+//! agree on which proposals they reject.
+
+use thiserror::Error;
+
+use crate::types::ProtoBlock;
+
+/// Why a proposal was rejected as oversized.
+#[derive(Debug, Error, PartialEq, Eq)]
+pub(crate) enum OversizedProposal {
+    /// The proposal contains too many deploys.
+    #[error("proposal contains {count} deploys, exceeding the limit of {limit}")]
+    TooManyDeploys { count: usize, limit: usize },
+    /// The serialized proposal is too large.
+    #[error("proposal has {size} bytes, exceeding the limit of {limit}")]
+    TooLarge { size: usize, limit: usize },
+}
+
+/// The maximum size of block proposals.
+#[derive(Debug)]
+pub(crate) struct ProposalLimits {
+    /// The maximum number of deploys in a proposal.  If zero, the number is unlimited.
+    max_deploy_count: usize,
+    /// The maximum size in bytes of a serialized proposal.  If zero, the size is unlimited.
+    max_size: usize,
+}
+
+impl ProposalLimits {
+    /// Creates limits allowing at most `max_deploy_count` deploys and `max_size` bytes per
+    /// proposal.
+    pub(crate) fn new(max_deploy_count: usize, max_size: usize) -> Self {
+        ProposalLimits {
+            max_deploy_count,
+            max_size,
+        }
+    }
+
+    /// Checks that `proto_block` is within the limits.
+    pub(crate) fn check(&self, proto_block: &ProtoBlock) -> Result<(), OversizedProposal> {
+        let count = proto_block.deploys().len();
+        if self.max_deploy_count > 0 && count > self.max_deploy_count {
+            return Err(OversizedProposal::TooManyDeploys {
+                count,
+                limit: self.max_deploy_count,
+            });
+        }
+        if self.max_size > 0 {
+            let size = rmp_serde::to_vec(proto_block)
+                .expect("should serialize proto block")
+                .len();
+            if size > self.max_size {
+                return Err(OversizedProposal::TooLarge {
+                    size,
+                    limit: self.max_size,
+                });
+            }
+        }
+        Ok(())
+    }
+}
@@ -3,6 +3,7 @@ use std::{
     fmt::Debug,
     iter,
     rc::Rc,
+    sync::Arc,
 };
 
 use anyhow::Error;
@@ -13,18 +14,23 @@ use tracing::info;
 
 use crate::{
     components::consensus::{
-        consensus_protocol::{BlockContext, ConsensusProtocol, ConsensusProtocolResult},
+        consensus_protocol::{
+            BlockContext, ConsensusProtocol, ConsensusProtocolResult, MessageVerifier,
+        },
         highway_core::{
             active_validator::Effect as AvEffect,
             finality_detector::FinalityDetector,
-            highway::{Dependency, Highway, Params, PreValidatedVertex, ValidVertex, Vertex},
+            highway::{
+                Dependency, Highway, Params, PreValidatedVertex, SignedWireVote, ValidVertex,
+                Vertex,
+            },
             validators::Validators,
             Weight,
         },
         traits::{Context, NodeIdT, ValidatorSecret},
     },
     crypto::{
-        asymmetric_key::{self, PublicKey, SecretKey, Signature},
+        asymmetric_key::{self, PublicKey, SecretKey, Signature, SignatureScheme, SigningPurpose},
         hash::{self, Digest},
     },
     types::{ProtoBlock, Timestamp},
@@ -38,6 +44,8 @@ pub(crate) struct HighwayProtocol<I, C: Context> {
     pending_values: HashMap<C::ConsensusValue, Vec<ValidVertex<C>>>,
     finality_detector: FinalityDetector<C>,
     highway: Highway<C>,
+    /// A shared copy of the validator set, used to verify signatures outside of the protocol.
+    validators: Arc<Validators<C::ValidatorId>>,
 }
 
 impl<I: NodeIdT, C: Context> HighwayProtocol<I, C> {
@@ -51,6 +59,7 @@ impl<I: NodeIdT, C: Context> HighwayProtocol<I, C> {
             vertex_deps: BTreeMap::new(),
             pending_values: HashMap::new(),
             finality_detector: FinalityDetector::new(ftt),
+            validators: Arc::new(validators.clone()),
             highway: Highway::new(instance_id, validators, params),
         }
     }
@@ -81,8 +90,11 @@ impl<I: NodeIdT, C: Context> HighwayProtocol<I, C> {
             AvEffect::ScheduleTimer(timestamp) => {
                 vec![ConsensusProtocolResult::ScheduleTimer(timestamp)]
             }
-            AvEffect::RequestNewBlock(block_context) => {
-                vec![ConsensusProtocolResult::CreateNewBlock { block_context }]
+            AvEffect::RequestNewBlock(block_context, past_values) => {
+                vec![ConsensusProtocolResult::CreateNewBlock {
+                    block_context,
+                    past_values,
+                }]
             }
             AvEffect::WeEquivocated(evidence) => {
                 panic!("this validator equivocated: {:?}", evidence);
@@ -140,12 +152,18 @@ impl<I: NodeIdT, C: Context> HighwayProtocol<I, C> {
                         Ok(vv) => {
                             if let Some(value) = vv.inner().value().cloned() {
                                 // It's a block: Request validation before adding it to the state.
+                                let proposer = vv
+                                    .inner()
+                                    .creator()
+                                    .and_then(|idx| self.highway.validators().get_by_index(idx))
+                                    .map(|validator| validator.id().clone())
+                                    .expect("validated vote should have a known creator");
                                 self.pending_values
                                     .entry(value.clone())
                                     .or_default()
                                     .push(vv);
                                 results.push(ConsensusProtocolResult::ValidateConsensusValue(
-                                    sender, value,
+                                    sender, value, proposer,
                                 ));
                             } else {
                                 // It's not a block: Add it to the state.
@@ -184,6 +202,57 @@ impl<I: NodeIdT, C: Context> HighwayProtocol<I, C> {
         results
     }
 
+    /// Handles an incoming message. Vote signatures are only checked if `check_signature` is
+    /// `true`.
+    fn handle_incoming<R: Rng + CryptoRng + ?Sized>(
+        &mut self,
+        sender: I,
+        msg: Vec<u8>,
+        check_signature: bool,
+        rng: &mut R,
+    ) -> Vec<CpResult<I, C>> {
+        match rmp_serde::from_read_ref(msg.as_slice()) {
+            Err(err) => vec![ConsensusProtocolResult::InvalidIncomingMessage(
+                msg,
+                sender,
+                err.into(),
+            )],
+            Ok(HighwayMessage::NewVertex(ref v)) if self.highway.has_vertex(v) => vec![],
+            Ok(HighwayMessage::NewVertex(v)) => {
+                let pre_validated = if check_signature {
+                    self.highway.pre_validate_vertex(v)
+                } else {
+                    self.highway.pre_validate_verified_vertex(v)
+                };
+                match pre_validated {
+                    Ok(pvv) => self.add_vertices(vec![(sender, pvv)], rng),
+                    Err((_, err)) => {
+                        // TODO: Disconnect from senders.
+                        vec![ConsensusProtocolResult::InvalidIncomingMessage(
+                            msg,
+                            sender,
+                            err.into(),
+                        )]
+                    }
+                }
+            }
+            Ok(HighwayMessage::RequestDependency(dep)) => {
+                if let Some(vv) = self.highway.get_dependency(&dep) {
+                    let msg = HighwayMessage::NewVertex(vv.into());
+                    let serialized_msg = rmp_serde::to_vec(&msg).expect("should serialize message");
+                    // TODO: Should this be done via a gossip service?
+                    vec![ConsensusProtocolResult::CreatedTargetedMessage(
+                        serialized_msg,
+                        sender,
+                    )]
+                } else {
+                    info!(?dep, ?sender, "requested dependency doesn't exist");
+                    vec![]
+                }
+            }
+        }
+    }
+
     fn remove_satisfied_deps(&mut self) -> impl Iterator<Item = (I, PreValidatedVertex<C>)> + '_ {
         let satisfied_deps = self
             .vertex_deps
@@ -213,7 +282,8 @@ type CpResult<I, C> =
 impl<I, C, R> ConsensusProtocol<I, C::ConsensusValue, C::ValidatorId, R> for HighwayProtocol<I, C>
 where
     I: NodeIdT,
-    C: Context,
+    C: Context + 'static,
+    C::ValidatorId: Send + Sync,
     R: Rng + CryptoRng + ?Sized,
 {
     fn handle_message(
@@ -222,43 +292,56 @@ where
         msg: Vec<u8>,
         rng: &mut R,
     ) -> Result<Vec<CpResult<I, C>>, Error> {
-        match rmp_serde::from_read_ref(msg.as_slice()) {
-            Err(err) => Ok(vec![ConsensusProtocolResult::InvalidIncomingMessage(
-                msg,
-                sender,
-                err.into(),
-            )]),
-            Ok(HighwayMessage::NewVertex(ref v)) if self.highway.has_vertex(v) => Ok(vec![]),
-            Ok(HighwayMessage::NewVertex(v)) => {
-                match self.highway.pre_validate_vertex(v) {
-                    Ok(pvv) => Ok(self.add_vertices(vec![(sender, pvv)], rng)),
-                    Err((_, err)) => {
-                        // TODO: Disconnect from senders.
-                        Ok(vec![ConsensusProtocolResult::InvalidIncomingMessage(
-                            msg,
-                            sender,
-                            err.into(),
-                        )])
-                    }
+        Ok(self.handle_incoming(sender, msg, true, rng))
+    }
+
+    fn message_verifier(&self, signature_scheme: Option<SignatureScheme>) -> MessageVerifier {
+        let validators = Arc::clone(&self.validators);
+        let verify_vote = move |vote: &SignedWireVote<C>| {
+            let scheme = C::signature_scheme(&vote.signature);
+            if let (Some(scheme), Some(expected)) = (scheme, signature_scheme) {
+                if scheme != expected {
+                    info!(%scheme, %expected, "rejected vote with unexpected signature scheme");
+                    return false;
                 }
             }
-            Ok(HighwayMessage::RequestDependency(dep)) => {
-                if let Some(vv) = self.highway.get_dependency(&dep) {
-                    let msg = HighwayMessage::NewVertex(vv.into());
-                    let serialized_msg = rmp_serde::to_vec(&msg).expect("should serialize message");
-                    // TODO: Should this be done via a gossip service?
-                    Ok(vec![ConsensusProtocolResult::CreatedTargetedMessage(
-                        serialized_msg,
-                        sender,
-                    )])
-                } else {
-                    info!(?dep, ?sender, "requested dependency doesn't exist");
-                    Ok(vec![])
-                }
+            // An unknown creator is reported by the protocol itself during pre-validation.
+            validators
+                .get_by_index(vote.wire_vote.creator)
+                .map_or(true, |validator| {
+                    C::verify_signature(&vote.hash(), validator.id(), &vote.signature)
+                })
+        };
+        Box::new(move |msg: &[u8]| match rmp_serde::from_read_ref(msg) {
+            Ok(HighwayMessage::<C>::NewVertex(Vertex::Vote(vote))) => verify_vote(&vote),
+            Ok(HighwayMessage::<C>::NewVertex(Vertex::Evidence(evidence))) => {
+                evidence.votes().all(verify_vote)
             }
+            // Dependency requests carry no signatures.
+            Ok(HighwayMessage::<C>::RequestDependency(_)) => true,
+            // A message that can't be decoded can't be checked either.
+            Err(_) => false,
+        })
+    }
+
+    fn message_round(&self, msg: &[u8]) -> Option<Timestamp> {
+        match rmp_serde::from_read_ref(msg) {
+            Ok(HighwayMessage::<C>::NewVertex(Vertex::Vote(vote))) => {
+                Some(vote.wire_vote.round_id())
+            }
+            _ => None,
         }
     }
 
+    fn handle_verified_message(
+        &mut self,
+        sender: I,
+        msg: Vec<u8>,
+        rng: &mut R,
+    ) -> Result<Vec<CpResult<I, C>>, Error> {
+        Ok(self.handle_incoming(sender, msg, false, rng))
+    }
+
     fn handle_timer(
         &mut self,
         timestamp: Timestamp,
@@ -309,6 +392,10 @@ where
     fn deactivate_validator(&mut self) {
         self.highway.deactivate_validator()
     }
+
+    fn leader(&self, timestamp: Timestamp) -> C::ValidatorId {
+        self.highway.leader(timestamp).clone()
+    }
 }
 
 pub(crate) struct HighwaySecret {
@@ -330,7 +417,13 @@ impl ValidatorSecret for HighwaySecret {
     type Signature = Signature;
 
     fn sign<R: Rng + CryptoRng + ?Sized>(&self, hash: &Digest, rng: &mut R) -> Signature {
-        asymmetric_key::sign(hash, self.secret_key.as_ref(), &self.public_key, rng)
+        asymmetric_key::sign(
+            hash,
+            self.secret_key.as_ref(),
+            &self.public_key,
+            SigningPurpose::ConsensusVote,
+            rng,
+        )
     }
 }
 
@@ -350,10 +443,16 @@ impl Context for HighwayContext {
     }
 
     fn verify_signature(hash: &Digest, public_key: &PublicKey, signature: &Signature) -> bool {
-        if let Err(error) = asymmetric_key::verify(hash, signature, public_key) {
+        if let Err(error) =
+            asymmetric_key::verify(hash, signature, public_key, SigningPurpose::ConsensusVote)
+        {
             info!(%error, %signature, %public_key, %hash, "failed to validate signature");
             return false;
         }
         true
     }
+
+    fn signature_scheme(signature: &Signature) -> Option<SignatureScheme> {
+        Some(signature.scheme())
+    }
 }
@@ -0,0 +1,122 @@
+//! Detection of stalled consensus.
+//!
+//! Consensus is considered stalled if no block has been finalized for longer than the configured
+//! stall timeout.  This is announced once when it happens, and again once the next block is
+//! finalized and consensus has resumed, so that operators can be alerted.
+
+use std::time::Duration;
+
+use crate::types::Timestamp;
+
+/// Tracks when consensus last made progress.
+#[derive(Debug)]
+pub(crate) struct StallMonitor {
+    /// The duration without progress after which consensus is stalled.  If zero, stalls are not
+    /// detected.
+    stall_timeout: Duration,
+    /// When the last block was finalized, or when monitoring started.
+    last_progress: Timestamp,
+    /// Whether the stall has been detected already.
+    stalled: bool,
+}
+
+impl StallMonitor {
+    /// Creates a new monitor, counting from `now`.
+    pub(crate) fn new(stall_timeout: Duration, now: Timestamp) -> Self {
+        StallMonitor {
+            stall_timeout,
+            last_progress: now,
+            stalled: false,
+        }
+    }
+
+    /// Returns whether stall detection is enabled.
+    pub(crate) fn is_enabled(&self) -> bool {
+        self.stall_timeout > Duration::from_secs(0)
+    }
+
+    /// Records that a block was finalized at `now`.
+    ///
+    /// Returns `true` if consensus had been stalled and has resumed.
+    pub(crate) fn record_progress(&mut self, now: Timestamp) -> bool {
+        self.last_progress = now;
+        let resumed = self.stalled;
+        self.stalled = false;
+        resumed
+    }
+
+    /// Checks whether consensus has stalled as of `now`.
+    ///
+    /// Returns the time of the last progress if consensus has newly stalled, i.e. at most once
+    /// until it resumes.
+    pub(crate) fn check(&mut self, now: Timestamp) -> Option<Timestamp> {
+        if !self.is_enabled() || self.stalled || self.idle_time(now) < self.stall_timeout {
+            return None;
+        }
+        self.stalled = true;
+        Some(self.last_progress)
+    }
+
+    /// Returns the delay after which the next check should happen.
+    pub(crate) fn next_check_delay(&self, now: Timestamp) -> Duration {
+        if self.stalled {
+            self.stall_timeout
+        } else {
+            self.stall_timeout
+                .checked_sub(self.idle_time(now))
+                .unwrap_or_default()
+                .max(Duration::from_millis(1))
+        }
+    }
+
+    /// Returns the time since the last progress.
+    fn idle_time(&self, now: Timestamp) -> Duration {
+        Duration::from(now.saturating_sub(self.last_progress))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn should_announce_stall_once_until_progress_resumes() {
+        let stall_timeout = Duration::from_secs(60);
+        let mut monitor = StallMonitor::new(stall_timeout, Timestamp::from(0));
+
+        // Blocks finalized in time keep consensus going.
+        assert_eq!(monitor.check(Timestamp::from(59_999)), None);
+        assert!(!monitor.record_progress(Timestamp::from(50_000)));
+        assert_eq!(monitor.check(Timestamp::from(100_000)), None);
+        assert_eq!(
+            monitor.next_check_delay(Timestamp::from(100_000)),
+            Duration::from_secs(10)
+        );
+
+        // After a minute of inactivity, the stall is reported once.
+        assert_eq!(
+            monitor.check(Timestamp::from(110_000)),
+            Some(Timestamp::from(50_000))
+        );
+        assert_eq!(monitor.check(Timestamp::from(500_000)), None);
+        assert_eq!(
+            monitor.next_check_delay(Timestamp::from(500_000)),
+            stall_timeout
+        );
+
+        // The next finalized block clears the stall, so that a later one is reported again.
+        assert!(monitor.record_progress(Timestamp::from(600_000)));
+        assert!(!monitor.record_progress(Timestamp::from(610_000)));
+        assert_eq!(
+            monitor.check(Timestamp::from(670_000)),
+            Some(Timestamp::from(610_000))
+        );
+    }
+
+    #[test]
+    fn should_not_detect_stalls_when_disabled() {
+        let mut monitor = StallMonitor::new(Duration::from_secs(0), Timestamp::from(0));
+        assert!(!monitor.is_enabled());
+        assert_eq!(monitor.check(Timestamp::from(1_000_000)), None);
+    }
+}
@@ -6,6 +6,8 @@ use std::{
 use rand::{CryptoRng, Rng};
 use serde::{de::DeserializeOwned, Serialize};
 
+use crate::crypto::asymmetric_key::SignatureScheme;
+
 pub(crate) trait NodeIdT: Clone + Debug + Send + Eq + Hash + 'static {}
 impl<I> NodeIdT for I where I: Clone + Debug + Send + Eq + Hash + 'static {}
 
@@ -61,4 +63,10 @@ pub(crate) trait Context: Clone + Debug + Eq + Ord + Hash {
         public_key: &Self::ValidatorId,
         signature: &<Self::ValidatorSecret as ValidatorSecret>::Signature,
     ) -> bool;
+
+    /// Returns the scheme `signature` was made with, or `None` if it doesn't belong to any of the
+    /// supported schemes.
+    fn signature_scheme(_signature: &Self::Signature) -> Option<SignatureScheme> {
+        None
+    }
 }
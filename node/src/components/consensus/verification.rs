@@ -0,0 +1,189 @@
+//! Off-thread verification of incoming consensus messages.
+//!
+//! Checking the signatures of incoming messages is comparatively expensive, so it is done on
+//! tokio's blocking thread pool instead of the reactor thread. The number of verifications running
+//! concurrently is bounded by a semaphore. Since verifications can complete in any order, each era
+//! keeps a `VerificationQueue` which releases the results in the order the messages arrived in.
+//! The total number of messages in these queues is bounded, too: Once it is reached, further
+//! incoming messages are dropped until earlier ones have been verified.
+
+use std::{collections::VecDeque, sync::Arc};
+
+use tokio::sync::Semaphore;
+use tracing::error;
+
+use super::consensus_protocol::MessageVerifier;
+use crate::utils;
+
+/// An incoming message awaiting verification or delivery to the consensus protocol.
+#[derive(Debug)]
+struct PendingMessage<I> {
+    sender: I,
+    payload: Vec<u8>,
+    /// The verification result, or `None` if verification hasn't completed yet.
+    valid: Option<bool>,
+}
+
+/// A message whose verification has completed, ready to be handled by the consensus protocol.
+#[derive(Debug, PartialEq)]
+pub(crate) struct VerifiedMessage<I> {
+    pub(crate) sender: I,
+    pub(crate) payload: Vec<u8>,
+    pub(crate) valid: bool,
+}
+
+/// The messages of a single era which are being verified, in order of arrival.
+#[derive(Debug)]
+pub(crate) struct VerificationQueue<I> {
+    /// The sequence number of the first message in `pending`.
+    first_seq: u64,
+    pending: VecDeque<PendingMessage<I>>,
+}
+
+impl<I> Default for VerificationQueue<I> {
+    fn default() -> Self {
+        VerificationQueue {
+            first_seq: 0,
+            pending: VecDeque::new(),
+        }
+    }
+}
+
+impl<I> VerificationQueue<I> {
+    /// Adds a message which is about to be verified, and returns its sequence number.
+    pub(crate) fn push(&mut self, sender: I, payload: Vec<u8>) -> u64 {
+        self.pending.push_back(PendingMessage {
+            sender,
+            payload,
+            valid: None,
+        });
+        self.first_seq + self.pending.len() as u64 - 1
+    }
+
+    /// Returns whether no messages are being verified.
+    pub(crate) fn is_empty(&self) -> bool {
+        self.pending.is_empty()
+    }
+
+    /// Returns the number of messages being verified or waiting for earlier ones.
+    pub(crate) fn len(&self) -> usize {
+        self.pending.len()
+    }
+
+    /// Records the verification result of the message with sequence number `seq`.
+    ///
+    /// Returns all messages which are now ready to be handled, in the order they were pushed. A
+    /// message is only released once all earlier ones have been verified, too.
+    pub(crate) fn complete(&mut self, seq: u64, valid: bool) -> Vec<VerifiedMessage<I>> {
+        let index = seq.checked_sub(self.first_seq).map(|index| index as usize);
+        match index.and_then(|index| self.pending.get_mut(index)) {
+            Some(pending) => pending.valid = Some(valid),
+            None => {
+                error!(seq, "unexpected verification result");
+                return Vec::new();
+            }
+        }
+
+        let mut ready = Vec::new();
+        while let Some(valid) = self.pending.front().and_then(|pending| pending.valid) {
+            let pending = self.pending.pop_front().expect("should have first message");
+            self.first_seq += 1;
+            ready.push(VerifiedMessage {
+                sender: pending.sender,
+                payload: pending.payload,
+                valid,
+            });
+        }
+        ready
+    }
+}
+
+/// Runs `verifier` on `payload` on the blocking thread pool, once one of the `permits` is free.
+///
+/// Returns whether the message is valid. A verifier which panics is treated as having rejected the
+/// message.
+pub(crate) async fn verify(
+    permits: Arc<Semaphore>,
+    verifier: MessageVerifier,
+    payload: Vec<u8>,
+) -> bool {
+    let _permit = permits.acquire().await;
+    match utils::spawn_blocking(move || verifier(&payload)).await {
+        Ok(valid) => valid,
+        Err(error) => {
+            error!(%error, "message verification failed");
+            false
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        sync::Mutex,
+        thread::{self, ThreadId},
+    };
+
+    use super::*;
+
+    #[test]
+    fn should_release_results_in_arrival_order() {
+        let mut queue = VerificationQueue::default();
+        let seqs: Vec<_> = (0..4u8).map(|i| queue.push(i, vec![i])).collect();
+        assert_eq!(seqs, vec![0, 1, 2, 3]);
+
+        let verified = |sender: u8, valid| VerifiedMessage {
+            sender,
+            payload: vec![sender],
+            valid,
+        };
+
+        // Later results are held back until all earlier ones are available.
+        assert!(queue.complete(2, true).is_empty());
+        assert!(queue.complete(1, false).is_empty());
+        assert_eq!(
+            queue.complete(0, true),
+            vec![verified(0, true), verified(1, false), verified(2, true)]
+        );
+        assert_eq!(queue.complete(3, true), vec![verified(3, true)]);
+
+        // Sequence numbers continue after the queue has been drained.
+        assert_eq!(queue.push(4, vec![4]), 4);
+        assert!(queue.complete(3, true).is_empty());
+        assert_eq!(queue.complete(4, false), vec![verified(4, false)]);
+    }
+
+    #[test]
+    fn should_order_eras_independently() {
+        let mut era_0 = VerificationQueue::default();
+        let mut era_1 = VerificationQueue::default();
+        let seq_0 = era_0.push("alice", vec![0]);
+        let seq_1 = era_1.push("bob", vec![1]);
+
+        // A pending message in one era doesn't block the other.
+        assert_eq!(era_1.complete(seq_1, true).len(), 1);
+        assert_eq!(era_0.complete(seq_0, true).len(), 1);
+    }
+
+    #[tokio::test]
+    async fn should_verify_off_dispatch_thread() {
+        let dispatch_thread = thread::current().id();
+        let verifier_thread: Arc<Mutex<Option<ThreadId>>> = Arc::new(Mutex::new(None));
+
+        let recorded_thread = Arc::clone(&verifier_thread);
+        let verifier: MessageVerifier = Box::new(move |payload: &[u8]| {
+            *recorded_thread.lock().unwrap() = Some(thread::current().id());
+            payload == [1, 2, 3]
+        });
+
+        let permits = Arc::new(Semaphore::new(1));
+        assert!(verify(Arc::clone(&permits), verifier, vec![1, 2, 3]).await);
+        assert_eq!(permits.available_permits(), 1);
+
+        let verifier_thread = verifier_thread
+            .lock()
+            .unwrap()
+            .expect("verifier should have run");
+        assert_ne!(verifier_thread, dispatch_thread);
+    }
+}
@@ -14,7 +14,6 @@ use lmdb::DatabaseFlags;
 use prometheus::{self, Histogram, HistogramOpts, Registry};
 use rand::{CryptoRng, Rng};
 use thiserror::Error;
-use tokio::task;
 use tracing::trace;
 
 use casper_execution_engine::{
@@ -32,7 +31,7 @@ use crate::{
     components::Component,
     crypto::hash,
     effect::{requests::ContractRuntimeRequest, EffectBuilder, EffectExt, Effects},
-    Chainspec, StorageConfig,
+    utils, Chainspec, StorageConfig,
 };
 
 /// The contract runtime components.
@@ -159,7 +158,7 @@ where
                 let metrics = Arc::clone(&self.metrics);
                 async move {
                     let correlation_id = CorrelationId::new();
-                    let result = task::spawn_blocking(move || {
+                    let result = utils::spawn_blocking(move || {
                         let start = Instant::now();
                         let execution_result =
                             engine_state.run_execute(correlation_id, execute_request);
@@ -183,7 +182,7 @@ where
                 let metrics = Arc::clone(&self.metrics);
                 async move {
                     let correlation_id = CorrelationId::new();
-                    let result = task::spawn_blocking(move || {
+                    let result = utils::spawn_blocking(move || {
                         let start = Instant::now();
                         let apply_result = engine_state.apply_effect(
                             correlation_id,
@@ -209,7 +208,7 @@ where
                 let metrics = Arc::clone(&self.metrics);
                 async move {
                     let correlation_id = CorrelationId::new();
-                    let result = task::spawn_blocking(move || {
+                    let result = utils::spawn_blocking(move || {
                         let start = Instant::now();
                         let result = engine_state.commit_upgrade(correlation_id, upgrade_config);
                         metrics
@@ -233,7 +232,7 @@ where
                 let metrics = Arc::clone(&self.metrics);
                 async move {
                     let correlation_id = CorrelationId::new();
-                    let result = task::spawn_blocking(move || {
+                    let result = utils::spawn_blocking(move || {
                         let start = Instant::now();
                         let result = engine_state.run_query(correlation_id, query_request);
                         metrics.run_query.observe(start.elapsed().as_secs_f64());
@@ -255,7 +254,7 @@ where
                 let metrics = Arc::clone(&self.metrics);
                 async move {
                     let correlation_id = CorrelationId::new();
-                    let result = task::spawn_blocking(move || {
+                    let result = utils::spawn_blocking(move || {
                         let start = Instant::now();
                         let result = engine_state.get_purse_balance(
                             correlation_id,
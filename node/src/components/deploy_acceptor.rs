@@ -1,28 +1,74 @@
+mod config;
 mod event;
+mod filter;
 // mod tests;
 
-use std::fmt::Debug;
+use std::{collections::BTreeSet, fmt::Debug, sync::Arc};
 
+use prometheus::{self, Histogram, HistogramOpts, Registry};
 use rand::{CryptoRng, Rng};
 use semver::Version;
-use tracing::{debug, error, warn};
+use thiserror::Error;
+use tokio::sync::Semaphore;
+use tracing::{debug, error, info, warn};
 
 use crate::{
     components::{chainspec_loader::Chainspec, storage::Storage, Component},
     effect::{
-        announcements::DeployAcceptorAnnouncement, requests::StorageRequest, EffectBuilder,
-        EffectExt, Effects,
+        announcements::DeployAcceptorAnnouncement,
+        requests::{DeployBufferRequest, FetcherRequest, StorageRequest},
+        EffectBuilder, EffectExt, Effects, Responder,
     },
     small_network::NodeId,
-    types::{Deploy, Timestamp},
-    utils::Source,
+    types::{Deploy, DeployHash, TimeDiff, Timestamp},
+    utils::{self, Source},
 };
 
+pub use config::Config;
 pub use event::Event;
+pub use filter::{AcceptAll, ConfiguredFilter, DeployFilter};
+
+/// The percentage of the maximum block size from which an accepted deploy is logged as large.
+const LARGE_DEPLOY_PERCENT: u64 = 90;
+
+/// Value of upper bound of the first deploy size histogram bucket (256 bytes).
+const EXPONENTIAL_BUCKET_START: f64 = 256.0;
+/// Multiplier of previous upper bound for next bound.
+const EXPONENTIAL_BUCKET_FACTOR: f64 = 4.0;
+/// Bucket count, with last going to +Inf.
+const EXPONENTIAL_BUCKET_COUNT: usize = 10;
+
+/// The reason a `Deploy` submitted by a client is refused.
+#[derive(Debug, Error)]
+pub enum Error {
+    /// The node is shedding load.
+    #[error("node is overloaded")]
+    Overloaded,
+    /// The deploy buffer has no capacity left for the deploy.
+    #[error("deploy buffer is full")]
+    DeployBufferFull,
+    /// Storage has run out of space.
+    #[error("storage is full")]
+    StorageFull,
+    /// The operator's `DeployFilter` rejected the deploy.
+    #[error("rejected by deploy filter: {0}")]
+    Filtered(String),
+    /// The chainspec to validate the deploy against is unavailable.
+    #[error("chainspec is unavailable")]
+    ChainspecUnavailable,
+    /// The deploy is invalid.
+    #[error("deploy is invalid")]
+    Invalid,
+}
 
 /// A helper trait constraining `DeployAcceptor` compatible reactor events.
 pub trait ReactorEventT:
-    From<Event> + From<DeployAcceptorAnnouncement<NodeId>> + From<StorageRequest<Storage>> + Send
+    From<Event>
+    + From<DeployAcceptorAnnouncement<NodeId>>
+    + From<StorageRequest<Storage>>
+    + From<FetcherRequest<NodeId, Deploy>>
+    + From<DeployBufferRequest>
+    + Send
 {
 }
 
@@ -30,21 +76,134 @@ impl<REv> ReactorEventT for REv where
     REv: From<Event>
         + From<DeployAcceptorAnnouncement<NodeId>>
         + From<StorageRequest<Storage>>
+        + From<FetcherRequest<NodeId, Deploy>>
+        + From<DeployBufferRequest>
         + Send
 {
 }
 
+/// Metrics of the deploy acceptor.
+#[derive(Debug)]
+struct DeployAcceptorMetrics {
+    /// Histogram of the serialized sizes of accepted deploys.
+    deploy_size: Histogram,
+
+    /// Handle to the metrics registry, in case we need to unregister.
+    registry: Registry,
+}
+
+impl DeployAcceptorMetrics {
+    /// Create and register new deploy acceptor metrics.
+    fn new(registry: &Registry) -> Result<Self, prometheus::Error> {
+        let buckets = prometheus::exponential_buckets(
+            EXPONENTIAL_BUCKET_START,
+            EXPONENTIAL_BUCKET_FACTOR,
+            EXPONENTIAL_BUCKET_COUNT,
+        )?;
+        let deploy_size = Histogram::with_opts(
+            HistogramOpts::new(
+                "deploy_acceptor_deploy_size_bytes",
+                "serialized size of accepted deploys in bytes",
+            )
+            .buckets(buckets),
+        )?;
+        registry.register(Box::new(deploy_size.clone()))?;
+
+        Ok(DeployAcceptorMetrics {
+            deploy_size,
+            registry: registry.clone(),
+        })
+    }
+}
+
+impl Drop for DeployAcceptorMetrics {
+    fn drop(&mut self) {
+        self.registry
+            .unregister(Box::new(self.deploy_size.clone()))
+            .expect("did not expect deregistering deploy size to fail");
+    }
+}
+
 /// The `DeployAcceptor` is the component which handles all new `Deploy`s immediately after they're
 /// received by this node, regardless of whether they were provided by a peer or a client.
 ///
-/// It validates a new `Deploy` as far as possible, stores it if valid, then announces the newly-
-/// accepted `Deploy`.
-#[derive(Debug, Default)]
-pub(crate) struct DeployAcceptor {}
+/// It checks a new `Deploy` against the operator's `DeployFilter` and validates it as far as
+/// possible.  A valid `Deploy` is then added to the deploy buffer, which checks and reserves room
+/// for it.  Once buffered, it is announced as accepted right away, so that other components can
+/// react to it without waiting for storage, and announced again once it has been newly stored.
+///
+/// A `Deploy` the full deploy buffer has no room for is refused if it was submitted by a client.
+/// One from a peer is still stored, since it may be needed to validate a block, but not announced
+/// as accepted, so that it isn't gossiped any further.
+///
+/// Validation, in particular checking the hash, is done on tokio's blocking thread pool instead of
+/// the reactor thread.  The number of `Deploy`s validated concurrently is bounded, and further ones
+/// wait for a validation to finish, so that a flood of submissions doesn't starve consensus of CPU.
+///
+/// A client is told whether its `Deploy` was accepted, or why not, as soon as it has been
+/// validated. While storage is full, new `Deploy`s from clients are refused, while those from peers
+/// are still accepted since they may be needed to validate blocks.
+///
+/// Once a `Deploy` from a peer has been newly stored, its dependencies are fetched from the same
+/// peer, unless they are stored already.  Fetched dependencies are accepted like any other
+/// `Deploy` from a peer, so their own dependencies are fetched in turn, until the whole dependency
+/// closure is stored ahead of the `Deploy` being included in a block.
+///
+/// The sizes of accepted `Deploy`s are recorded in a histogram, and a warning is logged for those
+/// approaching the maximum block size, so that operators can anticipate the need for changing it.
+#[derive(Debug)]
+pub(crate) struct DeployAcceptor {
+    filter: Box<dyn DeployFilter>,
+    /// Whether storage has run out of space.
+    is_storage_full: bool,
+    /// Permits to validate a `Deploy`, one per concurrent validation.
+    validation_permits: Arc<Semaphore>,
+    /// The number of `Deploy`s being validated or waiting for a permit.
+    pending_validations: usize,
+    /// The maximum number of `Deploy`s being validated or waiting for a permit.
+    max_pending_validations: usize,
+    /// How far ahead of the current time a deploy's timestamp may be.
+    max_future_skew: TimeDiff,
+    /// Whether the dependencies of newly stored deploys from peers are fetched.
+    prefetch_dependencies: bool,
+    metrics: DeployAcceptorMetrics,
+}
 
 impl DeployAcceptor {
-    pub(crate) fn new() -> Self {
-        Self::default()
+    /// Creates a new `DeployAcceptor`, only accepting deploys which pass `filter` and whose
+    /// timestamp is at most `max_future_skew` ahead of the current time, and validating at most
+    /// `max_concurrent_validations` deploys at a time, with at most `max_queued_validations` more
+    /// waiting.  If `prefetch_dependencies` is set, the dependencies of deploys from peers are
+    /// fetched once the deploys are stored.
+    pub(crate) fn new(
+        filter: Box<dyn DeployFilter>,
+        max_concurrent_validations: usize,
+        max_queued_validations: usize,
+        max_future_skew: TimeDiff,
+        prefetch_dependencies: bool,
+        registry: &Registry,
+    ) -> Result<Self, prometheus::Error> {
+        Ok(DeployAcceptor {
+            filter,
+            is_storage_full: false,
+            validation_permits: Arc::new(Semaphore::new(max_concurrent_validations)),
+            pending_validations: 0,
+            max_pending_validations: max_concurrent_validations + max_queued_validations,
+            max_future_skew,
+            prefetch_dependencies,
+            metrics: DeployAcceptorMetrics::new(registry)?,
+        })
+    }
+
+    /// Sets whether storage has run out of space.
+    pub(crate) fn set_storage_full(&mut self, is_storage_full: bool) {
+        if is_storage_full != self.is_storage_full {
+            info!(
+                is_storage_full,
+                "changed acceptance of deploys from clients"
+            );
+        }
+        self.is_storage_full = is_storage_full;
     }
 
     /// Handles receiving a new `Deploy` from a peer or client.
@@ -53,7 +212,36 @@ impl DeployAcceptor {
         effect_builder: EffectBuilder<REv>,
         deploy: Box<Deploy>,
         source: Source<NodeId>,
+        responder: Option<Responder<Result<(), Error>>>,
     ) -> Effects<Event> {
+        if self.is_storage_full && matches!(source, Source::Client) {
+            warn!(deploy_hash = %deploy.id(), "storage is full, dropping deploy from client");
+            return respond(responder, Err(Error::StorageFull));
+        }
+
+        // The size is cached on the deploy, so it is serialized only once.
+        if let Err(error) = deploy.serialized_size() {
+            warn!(deploy_hash = %deploy.id(), %source, %error, "failed to serialize deploy");
+            let mut effects = respond(responder, Err(Error::Invalid));
+            effects.extend(
+                effect_builder
+                    .announce_invalid_deploy(deploy, source)
+                    .ignore(),
+            );
+            return effects;
+        }
+
+        if let Err(reason) = self.filter.check(&deploy) {
+            info!(deploy_hash = %deploy.id(), %source, %reason, "deploy rejected by filter");
+            let mut effects = respond(responder, Err(Error::Filtered(reason)));
+            effects.extend(
+                effect_builder
+                    .announce_invalid_deploy(deploy, source)
+                    .ignore(),
+            );
+            return effects;
+        }
+
         // TODO - where to get version from?
         let chainspec_version = Version::new(1, 0, 0);
         effect_builder
@@ -63,29 +251,123 @@ impl DeployAcceptor {
                 source,
                 chainspec_version,
                 maybe_chainspec: Box::new(maybe_chainspec),
+                responder,
             })
     }
 
-    fn validate<REv: ReactorEventT>(
+    /// Validates `deploy` against `chainspec` off the reactor thread, once a permit is free.
+    ///
+    /// If too many deploys are already waiting for a permit, `deploy` is dropped instead.
+    fn validate(
         &mut self,
-        effect_builder: EffectBuilder<REv>,
         deploy: Box<Deploy>,
         source: Source<NodeId>,
         chainspec: Chainspec,
+        responder: Option<Responder<Result<(), Error>>>,
     ) -> Effects<Event> {
-        if is_valid(&*deploy, chainspec) {
-            let cloned_deploy = deploy.clone();
+        if self.pending_validations >= self.max_pending_validations {
+            warn!(
+                deploy_hash = %deploy.id(),
+                %source,
+                pending_validations = self.pending_validations,
+                "too many deploys awaiting validation, dropping deploy"
+            );
+            return respond(responder, Err(Error::Overloaded));
+        }
+        self.pending_validations += 1;
+        let max_block_size = chainspec.genesis.deploy_config.max_block_size;
+        let max_future_skew = self.max_future_skew;
+        run_validation(Arc::clone(&self.validation_permits), move || {
+            let is_valid = is_valid(&*deploy, chainspec, Timestamp::now(), max_future_skew);
+            (deploy, is_valid)
+        })
+        .event(move |(deploy, is_valid)| Event::ValidationResult {
+            deploy,
+            source,
+            max_block_size,
+            is_valid,
+            responder,
+        })
+    }
+
+    fn handle_validation_result<REv: ReactorEventT>(
+        &mut self,
+        effect_builder: EffectBuilder<REv>,
+        deploy: Box<Deploy>,
+        source: Source<NodeId>,
+        max_block_size: u32,
+        is_valid: bool,
+        responder: Option<Responder<Result<(), Error>>>,
+    ) -> Effects<Event> {
+        self.pending_validations -= 1;
+        if is_valid {
+            self.record_size(&deploy, max_block_size);
             effect_builder
-                .put_deploy_to_storage(cloned_deploy)
-                .event(move |is_new| Event::PutToStorageResult {
+                .buffer_deploy(deploy.clone())
+                .event(move |is_buffered| Event::BufferResult {
                     deploy,
                     source,
-                    is_new,
+                    is_buffered,
+                    responder,
                 })
         } else {
+            let mut effects = respond(responder, Err(Error::Invalid));
+            effects.extend(
+                effect_builder
+                    .announce_invalid_deploy(deploy, source)
+                    .ignore(),
+            );
+            effects
+        }
+    }
+
+    fn handle_buffer_result<REv: ReactorEventT>(
+        &mut self,
+        effect_builder: EffectBuilder<REv>,
+        deploy: Box<Deploy>,
+        source: Source<NodeId>,
+        is_buffered: bool,
+        responder: Option<Responder<Result<(), Error>>>,
+    ) -> Effects<Event> {
+        let mut effects = if is_buffered {
+            let mut effects = respond(responder, Ok(()));
+            effects.extend(
+                effect_builder
+                    .announce_deploy_accepted(deploy.clone(), source)
+                    .ignore(),
+            );
+            effects
+        } else if let Source::Peer(_) = source {
+            info!(deploy_hash = %deploy.id(), %source, "deploy buffer is full, only storing deploy");
+            Effects::new()
+        } else {
+            info!(deploy_hash = %deploy.id(), %source, "deploy buffer is full, refusing deploy");
+            return respond(responder, Err(Error::DeployBufferFull));
+        };
+        effects.extend(
             effect_builder
-                .announce_invalid_deploy(deploy, source)
-                .ignore()
+                .put_deploy_to_storage(deploy.clone())
+                .event(move |result| Event::PutToStorageResult {
+                    deploy,
+                    source,
+                    result,
+                }),
+        );
+        effects
+    }
+
+    /// Records the size of the accepted `deploy`, and warns if it approaches `max_block_size`.
+    fn record_size(&self, deploy: &Deploy, max_block_size: u32) {
+        // The size was computed when the deploy was received, and has been cached since.
+        let size = deploy.serialized_size().unwrap_or_default();
+        self.metrics.deploy_size.observe(size as f64);
+        if size as u64 * 100 >= u64::from(max_block_size) * LARGE_DEPLOY_PERCENT {
+            warn!(
+                deploy_hash = %deploy.id(),
+                size,
+                max_block_size,
+                "accepted deploy approaching the maximum block size"
+            );
         }
     }
 
@@ -94,9 +376,10 @@ impl DeployAcceptor {
         deploy: Box<Deploy>,
         source: Source<NodeId>,
         chainspec_version: Version,
+        responder: Option<Responder<Result<(), Error>>>,
     ) -> Effects<Event> {
         error!(%deploy, %source, %chainspec_version, "failed to get chainspec");
-        Effects::new()
+        respond(responder, Err(Error::ChainspecUnavailable))
     }
 
     fn handle_put_to_storage<REv: ReactorEventT>(
@@ -106,12 +389,58 @@ impl DeployAcceptor {
         source: Source<NodeId>,
         is_new: bool,
     ) -> Effects<Event> {
-        if is_new {
-            return effect_builder
-                .announce_new_deploy_accepted(deploy, source)
-                .ignore();
+        if !is_new {
+            return Effects::new();
+        }
+        let mut effects = match source {
+            Source::Peer(peer) if self.prefetch_dependencies => {
+                self.fetch_dependencies(effect_builder, &deploy, peer)
+            }
+            _ => Effects::new(),
+        };
+        effects.extend(
+            effect_builder
+                .announce_deploy_stored(deploy, source)
+                .ignore(),
+        );
+        effects
+    }
+
+    /// Fetches the dependencies of `deploy` from `peer`, unless they are stored already.
+    ///
+    /// Only newly stored deploys have their dependencies fetched, so fetching stops at deploys
+    /// which are already stored, even if dependencies form a cycle.
+    fn fetch_dependencies<REv: ReactorEventT>(
+        &self,
+        effect_builder: EffectBuilder<REv>,
+        deploy: &Deploy,
+        peer: NodeId,
+    ) -> Effects<Event> {
+        let deploy_hash = *deploy.id();
+        let dependencies: BTreeSet<DeployHash> = deploy
+            .header()
+            .dependencies()
+            .iter()
+            .filter(|dependency| **dependency != deploy_hash)
+            .copied()
+            .collect();
+        let mut effects = Effects::new();
+        for dependency in dependencies {
+            debug!(%deploy_hash, %dependency, %peer, "prefetching deploy dependency");
+            effects.extend(
+                async move {
+                    if effect_builder
+                        .fetch_deploy(dependency, peer)
+                        .await
+                        .is_none()
+                    {
+                        info!(%deploy_hash, %dependency, %peer, "failed to prefetch dependency");
+                    }
+                }
+                .ignore(),
+            );
         }
-        Effects::new()
+        effects
     }
 }
 
@@ -126,26 +455,96 @@ impl<REv: ReactorEventT, R: Rng + CryptoRng + ?Sized> Component<REv, R> for Depl
     ) -> Effects<Self::Event> {
         debug!(?event, "handling event");
         match event {
-            Event::Accept { deploy, source } => self.accept(effect_builder, deploy, source),
+            Event::Accept {
+                deploy,
+                source,
+                responder,
+            } => self.accept(effect_builder, deploy, source, responder),
             Event::GetChainspecResult {
                 deploy,
                 source,
                 chainspec_version,
                 maybe_chainspec,
+                responder,
             } => match *maybe_chainspec {
-                Some(chainspec) => self.validate(effect_builder, deploy, source, chainspec),
-                None => self.failed_to_get_chainspec(deploy, source, chainspec_version),
+                Some(chainspec) => self.validate(deploy, source, chainspec, responder),
+                None => self.failed_to_get_chainspec(deploy, source, chainspec_version, responder),
             },
+            Event::ValidationResult {
+                deploy,
+                source,
+                max_block_size,
+                is_valid,
+                responder,
+            } => self.handle_validation_result(
+                effect_builder,
+                deploy,
+                source,
+                max_block_size,
+                is_valid,
+                responder,
+            ),
+            Event::BufferResult {
+                deploy,
+                source,
+                is_buffered,
+                responder,
+            } => self.handle_buffer_result(effect_builder, deploy, source, is_buffered, responder),
             Event::PutToStorageResult {
                 deploy,
                 source,
-                is_new,
+                result: Ok(is_new),
             } => self.handle_put_to_storage(effect_builder, deploy, source, is_new),
+            Event::PutToStorageResult {
+                deploy,
+                result: Err(error),
+                ..
+            } => {
+                debug!(deploy_hash = %deploy.id(), %error, "not announcing deploy");
+                Effects::new()
+            }
         }
     }
 }
 
-fn is_valid(deploy: &Deploy, chainspec: Chainspec) -> bool {
+/// Replies to the client which submitted a `Deploy` with `result`, if there is one.
+fn respond(
+    responder: Option<Responder<Result<(), Error>>>,
+    result: Result<(), Error>,
+) -> Effects<Event> {
+    match responder {
+        Some(responder) => responder.respond(result).ignore(),
+        None => Effects::new(),
+    }
+}
+
+/// Runs `validation` on the blocking thread pool, once one of the `permits` is free.
+async fn run_validation<T, F>(permits: Arc<Semaphore>, validation: F) -> T
+where
+    F: FnOnce() -> T + Send + 'static,
+    T: Send + 'static,
+{
+    let _permit = permits.acquire().await;
+    utils::spawn_blocking(validation).await.expect("should run")
+}
+
+/// Checks `deploy` against `chainspec` and the current time `now`, allowing its timestamp to be up
+/// to `max_future_skew` ahead.
+fn is_valid(
+    deploy: &Deploy,
+    chainspec: Chainspec,
+    now: Timestamp,
+    max_future_skew: TimeDiff,
+) -> bool {
+    if deploy.hash() != deploy.id() {
+        warn!(
+            deploy_hash = %deploy.id(),
+            computed_hash = %deploy.hash(),
+            "deploy hash mismatch"
+        );
+        return false;
+    }
+
     if deploy.header().chain_name() != chainspec.genesis.name {
         warn!(
             deploy_hash = %deploy.id(),
@@ -178,13 +577,24 @@ fn is_valid(deploy: &Deploy, chainspec: Chainspec) -> bool {
         return false;
     }
 
-    let now = Timestamp::now();
-    if now > deploy.header().expires() {
+    if let Err(error) =
+        deploy.validate_hash_layout(chainspec.genesis.deploy_config.canonical_hash_timestamp)
+    {
         warn!(
             deploy_hash = %deploy.id(),
             deploy_header = %deploy.header(),
-            %now,
-            "deploy expired"
+            %error,
+            "invalid deploy hash layout"
+        );
+        return false;
+    }
+
+    if let Err(error) = deploy.validate_timestamp(now, max_future_skew) {
+        warn!(
+            deploy_hash = %deploy.id(),
+            deploy_header = %deploy.header(),
+            %error,
+            "invalid deploy timestamp"
         );
         return false;
     }
@@ -193,3 +603,644 @@ fn is_valid(deploy: &Deploy, chainspec: Chainspec) -> bool {
 
     true
 }
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        sync::{
+            atomic::{AtomicUsize, Ordering},
+            mpsc, Mutex,
+        },
+        time::Duration,
+    };
+
+    use casper_execution_engine::core::engine_state::executable_deploy_item::ExecutableDeployItem;
+    use derive_more::From;
+    use futures::{future, FutureExt};
+    use tokio::time;
+    use tracing::{
+        field::{Field, Visit},
+        Event as TracingEvent, Level, Subscriber,
+    };
+    use tracing_subscriber::{
+        layer::{Context, Layer, SubscriberExt},
+        registry,
+    };
+
+    use super::*;
+    use crate::{
+        crypto::asymmetric_key::SecretKey,
+        reactor::{EventQueueHandle, QueueKind, Scheduler},
+        testing::TestRng,
+        utils,
+    };
+
+    #[derive(Debug, From)]
+    enum ReactorEvent {
+        #[from]
+        DeployAcceptor(Event),
+        #[from]
+        DeployAcceptorAnnouncement(DeployAcceptorAnnouncement<NodeId>),
+        #[from]
+        Storage(StorageRequest<Storage>),
+        #[from]
+        DeployFetcher(FetcherRequest<NodeId, Deploy>),
+        #[from]
+        DeployBuffer(DeployBufferRequest),
+    }
+
+    /// A layer recording the message of every warning it sees.
+    #[derive(Clone, Default)]
+    struct WarningCapturingLayer(Arc<Mutex<Vec<String>>>);
+
+    impl<S: Subscriber> Layer<S> for WarningCapturingLayer {
+        fn on_event(&self, event: &TracingEvent<'_>, _ctx: Context<'_, S>) {
+            if *event.metadata().level() != Level::WARN {
+                return;
+            }
+            let mut visitor = MessageVisitor(None);
+            event.record(&mut visitor);
+            if let Some(message) = visitor.0 {
+                self.0.lock().unwrap().push(message);
+            }
+        }
+    }
+
+    /// Extracts the `message` field of an event.
+    struct MessageVisitor(Option<String>);
+
+    impl Visit for MessageVisitor {
+        fn record_debug(&mut self, field: &Field, value: &dyn Debug) {
+            if field.name() == "message" {
+                self.0 = Some(format!("{:?}", value));
+            }
+        }
+    }
+
+    /// Handles `event`, which starts validating a deploy, and returns the validation result.
+    async fn validation_result(
+        deploy_acceptor: &mut DeployAcceptor,
+        effect_builder: EffectBuilder<ReactorEvent>,
+        rng: &mut TestRng,
+        event: Event,
+    ) -> Event {
+        let mut effects = deploy_acceptor.handle_event(effect_builder, rng, event);
+        assert_eq!(effects.len(), 1);
+        let mut events = effects.pop().unwrap().await;
+        assert_eq!(events.len(), 1);
+        events.pop().unwrap()
+    }
+
+    /// Handles `event`, which adds a validated deploy to the deploy buffer, and returns the result
+    /// of the deploy buffer responding with `is_buffered`.
+    async fn buffer_result(
+        deploy_acceptor: &mut DeployAcceptor,
+        scheduler: &Scheduler<ReactorEvent>,
+        effect_builder: EffectBuilder<ReactorEvent>,
+        rng: &mut TestRng,
+        event: Event,
+        is_buffered: bool,
+    ) -> Event {
+        let mut effects = deploy_acceptor.handle_event(effect_builder, rng, event);
+        assert_eq!(effects.len(), 1);
+        let effect = effects.pop().unwrap();
+        let buffer = async {
+            match scheduler.pop().await.0 {
+                ReactorEvent::DeployBuffer(DeployBufferRequest::Buffer { responder, .. }) => {
+                    responder.respond(is_buffered).await
+                }
+                other => panic!("unexpected event {:?}", other),
+            }
+        };
+        let (mut events, ()) = future::join(effect, buffer).await;
+        assert_eq!(events.len(), 1);
+        events.pop().unwrap()
+    }
+
+    fn module_bytes(size: usize) -> ExecutableDeployItem {
+        ExecutableDeployItem::ModuleBytes {
+            module_bytes: vec![0; size],
+            args: vec![],
+        }
+    }
+
+    fn large_deploy(rng: &mut TestRng) -> Deploy {
+        Deploy::new(
+            Timestamp::now(),
+            TimeDiff::from(60_000),
+            1,
+            vec![],
+            String::from("casper-example"),
+            module_bytes(0),
+            module_bytes(10_000),
+            &SecretKey::random(rng),
+            rng,
+        )
+    }
+
+    #[tokio::test]
+    async fn should_refuse_filtered_deploys_from_clients_and_peers() {
+        let mut rng = TestRng::new();
+        let scheduler = utils::leak(Scheduler::<ReactorEvent>::new(QueueKind::weights()));
+        let effect_builder = EffectBuilder::new(EventQueueHandle::new(scheduler));
+
+        let small_deploy = Deploy::random(&mut rng);
+        let large_deploy = large_deploy(&mut rng);
+        let config = Config {
+            max_deploy_size: small_deploy.serialized_size().unwrap() as u32,
+            accepted_accounts: vec![],
+        };
+        let mut deploy_acceptor = DeployAcceptor::new(
+            Box::new(ConfiguredFilter::new(&config).unwrap()),
+            1,
+            1,
+            TimeDiff::from(60_000),
+            true,
+            &Registry::new(),
+        )
+        .unwrap();
+
+        let peer: NodeId = rng.gen();
+        for &source in &[Source::Client, Source::Peer(peer)] {
+            for deploy in &[&small_deploy, &large_deploy] {
+                let is_large = deploy.id() == large_deploy.id();
+                let event = Event::Accept {
+                    deploy: Box::new((*deploy).clone()),
+                    source,
+                    responder: None,
+                };
+                // Polling each effect once is enough for it to schedule its event.
+                for effect in deploy_acceptor.handle_event(effect_builder, &mut rng, event) {
+                    let _ = effect.now_or_never();
+                }
+
+                match scheduler.pop().await.0 {
+                    // Accepted deploys are validated against the chainspec next.
+                    ReactorEvent::Storage(StorageRequest::GetChainspec { .. }) => {
+                        assert!(!is_large, "large deploy from {} was accepted", source)
+                    }
+                    ReactorEvent::DeployAcceptorAnnouncement(
+                        DeployAcceptorAnnouncement::InvalidDeploy { .. },
+                    ) => assert!(is_large, "small deploy from {} was refused", source),
+                    other => panic!("unexpected event {:?}", other),
+                }
+            }
+        }
+        assert_eq!(scheduler.item_count(), 0);
+
+        // The client is told why its deploy was refused.
+        let deploy = Box::new(large_deploy.clone());
+        let response = tokio::spawn(effect_builder.make_request(
+            move |responder| Event::Accept {
+                deploy,
+                source: Source::Client,
+                responder: Some(responder),
+            },
+            QueueKind::Api,
+        ));
+        let event = match scheduler.pop().await.0 {
+            ReactorEvent::DeployAcceptor(event) => event,
+            other => panic!("unexpected event {:?}", other),
+        };
+        for effect in deploy_acceptor.handle_event(effect_builder, &mut rng, event) {
+            let _ = effect.await;
+        }
+        let result = response.await.expect("should join");
+        assert!(matches!(result, Err(Error::Filtered(_))), "{:?}", result);
+    }
+
+    #[tokio::test]
+    async fn should_refuse_deploys_from_clients_while_storage_is_full() {
+        let mut rng = TestRng::new();
+        let scheduler = utils::leak(Scheduler::<ReactorEvent>::new(QueueKind::weights()));
+        let effect_builder = EffectBuilder::new(EventQueueHandle::new(scheduler));
+        let mut deploy_acceptor = DeployAcceptor::new(
+            Box::new(ConfiguredFilter::new(&Config::default()).unwrap()),
+            1,
+            1,
+            TimeDiff::from(60_000),
+            true,
+            &Registry::new(),
+        )
+        .unwrap();
+        deploy_acceptor.set_storage_full(true);
+
+        // The client is told that its deploy was refused.
+        let deploy = Box::new(Deploy::random(&mut rng));
+        let response = tokio::spawn(effect_builder.make_request(
+            move |responder| Event::Accept {
+                deploy,
+                source: Source::Client,
+                responder: Some(responder),
+            },
+            QueueKind::Api,
+        ));
+        let event = match scheduler.pop().await.0 {
+            ReactorEvent::DeployAcceptor(event) => event,
+            other => panic!("unexpected event {:?}", other),
+        };
+        for effect in deploy_acceptor.handle_event(effect_builder, &mut rng, event) {
+            let _ = effect.await;
+        }
+        let result = response.await.expect("should join");
+        assert!(matches!(result, Err(Error::StorageFull)), "{:?}", result);
+
+        // Deploys from peers are still accepted.
+        let event = Event::Accept {
+            deploy: Box::new(Deploy::random(&mut rng)),
+            source: Source::Peer(rng.gen()),
+            responder: None,
+        };
+        for effect in deploy_acceptor.handle_event(effect_builder, &mut rng, event) {
+            let _ = effect.now_or_never();
+        }
+        match scheduler.pop().await.0 {
+            ReactorEvent::Storage(StorageRequest::GetChainspec { .. }) => (),
+            other => panic!("unexpected event {:?}", other),
+        }
+    }
+
+    #[tokio::test]
+    async fn should_announce_accepted_deploy_before_stored_deploy() {
+        let mut rng = TestRng::new();
+        let scheduler = utils::leak(Scheduler::<ReactorEvent>::new(QueueKind::weights()));
+        let effect_builder = EffectBuilder::new(EventQueueHandle::new(scheduler));
+
+        let deploy = Deploy::random(&mut rng);
+        let mut chainspec = Chainspec::random(&mut rng);
+        chainspec.genesis.name = deploy.header().chain_name().to_string();
+        chainspec.genesis.deploy_config.max_dependencies = 10;
+        chainspec.genesis.deploy_config.max_ttl = deploy.header().ttl();
+
+        let mut deploy_acceptor = DeployAcceptor::new(
+            Box::new(AcceptAll),
+            1,
+            1,
+            TimeDiff::from(60_000),
+            true,
+            &Registry::new(),
+        )
+        .unwrap();
+        let event = Event::GetChainspecResult {
+            deploy: Box::new(deploy.clone()),
+            source: Source::Client,
+            chainspec_version: Version::new(1, 0, 0),
+            maybe_chainspec: Box::new(Some(chainspec)),
+            responder: None,
+        };
+        let event = validation_result(&mut deploy_acceptor, effect_builder, &mut rng, event).await;
+        let event = buffer_result(
+            &mut deploy_acceptor,
+            scheduler,
+            effect_builder,
+            &mut rng,
+            event,
+            true,
+        )
+        .await;
+        // Polling each effect once is enough for it to schedule its event.
+        let mut pending_effects = Vec::new();
+        for mut effect in deploy_acceptor.handle_event(effect_builder, &mut rng, event) {
+            if (&mut effect).now_or_never().is_none() {
+                pending_effects.push(effect);
+            }
+        }
+
+        // The deploy is announced as accepted before storing it has even begun.
+        match scheduler.pop().await.0 {
+            ReactorEvent::DeployAcceptorAnnouncement(
+                DeployAcceptorAnnouncement::DeployAccepted {
+                    deploy: accepted, ..
+                },
+            ) => assert_eq!(*accepted, deploy),
+            other => panic!("unexpected event {:?}", other),
+        }
+        match scheduler.pop().await.0 {
+            ReactorEvent::Storage(StorageRequest::PutDeploy { responder, .. }) => {
+                responder.respond(Ok(true)).await
+            }
+            other => panic!("unexpected event {:?}", other),
+        }
+        assert_eq!(scheduler.item_count(), 0);
+
+        // Once stored, the deploy is announced as stored.
+        assert_eq!(pending_effects.len(), 1);
+        for event in pending_effects.pop().unwrap().await {
+            for effect in deploy_acceptor.handle_event(effect_builder, &mut rng, event) {
+                let _ = effect.now_or_never();
+            }
+        }
+        match scheduler.pop().await.0 {
+            ReactorEvent::DeployAcceptorAnnouncement(
+                DeployAcceptorAnnouncement::DeployStored { deploy: stored, .. },
+            ) => assert_eq!(*stored, deploy),
+            other => panic!("unexpected event {:?}", other),
+        }
+        assert_eq!(scheduler.item_count(), 0);
+    }
+
+    #[tokio::test]
+    async fn should_only_store_deploys_without_room_in_deploy_buffer_from_peers() {
+        let mut rng = TestRng::new();
+        let scheduler = utils::leak(Scheduler::<ReactorEvent>::new(QueueKind::weights()));
+        let effect_builder = EffectBuilder::new(EventQueueHandle::new(scheduler));
+        let mut deploy_acceptor = DeployAcceptor::new(
+            Box::new(AcceptAll),
+            1,
+            1,
+            TimeDiff::from(60_000),
+            true,
+            &Registry::new(),
+        )
+        .unwrap();
+        let deploy = Deploy::random(&mut rng);
+
+        // A client is told that the deploy buffer is full, and the deploy is dropped.
+        let client_deploy = Box::new(deploy.clone());
+        let response = tokio::spawn(effect_builder.make_request(
+            move |responder| Event::BufferResult {
+                deploy: client_deploy,
+                source: Source::Client,
+                is_buffered: false,
+                responder: Some(responder),
+            },
+            QueueKind::Api,
+        ));
+        let event = match scheduler.pop().await.0 {
+            ReactorEvent::DeployAcceptor(event) => event,
+            other => panic!("unexpected event {:?}", other),
+        };
+        for effect in deploy_acceptor.handle_event(effect_builder, &mut rng, event) {
+            let _ = effect.await;
+        }
+        let result = response.await.expect("should join");
+        assert!(
+            matches!(result, Err(Error::DeployBufferFull)),
+            "{:?}",
+            result
+        );
+        assert_eq!(scheduler.item_count(), 0);
+
+        // A deploy from a peer is stored, but not announced as accepted.
+        let event = Event::BufferResult {
+            deploy: Box::new(deploy.clone()),
+            source: Source::Peer(rng.gen()),
+            is_buffered: false,
+            responder: None,
+        };
+        // Polling each effect once is enough for it to schedule its event.
+        for effect in deploy_acceptor.handle_event(effect_builder, &mut rng, event) {
+            let _ = effect.now_or_never();
+        }
+        match scheduler.pop().await.0 {
+            ReactorEvent::Storage(StorageRequest::PutDeploy { deploy: stored, .. }) => {
+                assert_eq!(*stored, deploy)
+            }
+            other => panic!("unexpected event {:?}", other),
+        }
+        assert_eq!(scheduler.item_count(), 0);
+    }
+
+    #[tokio::test]
+    async fn should_fetch_missing_dependencies_of_stored_deploys_from_peers() {
+        let mut rng = TestRng::new();
+        let scheduler = utils::leak(Scheduler::<ReactorEvent>::new(QueueKind::weights()));
+        let effect_builder = EffectBuilder::new(EventQueueHandle::new(scheduler));
+
+        // The dependency is listed twice, but only fetched once.
+        let missing_dependency = *Deploy::random(&mut rng).id();
+        let deploy = Deploy::new(
+            Timestamp::now(),
+            TimeDiff::from(60_000),
+            1,
+            vec![missing_dependency, missing_dependency],
+            String::from("casper-example"),
+            module_bytes(0),
+            module_bytes(0),
+            &SecretKey::random(&mut rng),
+            &mut rng,
+        );
+        let mut deploy_acceptor = DeployAcceptor::new(
+            Box::new(AcceptAll),
+            1,
+            1,
+            TimeDiff::from(60_000),
+            true,
+            &Registry::new(),
+        )
+        .unwrap();
+        let peer: NodeId = rng.gen();
+
+        // Dependencies are only fetched for deploys from peers which weren't stored before, so that
+        // fetching stops at deploys already stored, even if the dependencies form a cycle.
+        for &(source, is_new) in &[(Source::Client, true), (Source::Peer(peer), false)] {
+            let event = Event::PutToStorageResult {
+                deploy: Box::new(deploy.clone()),
+                source,
+                result: Ok(is_new),
+            };
+            for effect in deploy_acceptor.handle_event(effect_builder, &mut rng, event) {
+                let _ = effect.now_or_never();
+            }
+            while scheduler.item_count() > 0 {
+                match scheduler.pop().await.0 {
+                    ReactorEvent::DeployAcceptorAnnouncement(_) => (),
+                    other => panic!("unexpected event {:?}", other),
+                }
+            }
+        }
+
+        let event = Event::PutToStorageResult {
+            deploy: Box::new(deploy.clone()),
+            source: Source::Peer(peer),
+            result: Ok(true),
+        };
+        // Polling each effect once is enough for it to schedule its event.
+        let mut pending_effects = Vec::new();
+        for mut effect in deploy_acceptor.handle_event(effect_builder, &mut rng, event) {
+            if (&mut effect).now_or_never().is_none() {
+                pending_effects.push(effect);
+            }
+        }
+        match scheduler.pop().await.0 {
+            ReactorEvent::DeployFetcher(FetcherRequest::Fetch {
+                id, peer: target, ..
+            }) => {
+                assert_eq!(id, missing_dependency);
+                assert_eq!(target, peer);
+            }
+            other => panic!("unexpected event {:?}", other),
+        }
+        match scheduler.pop().await.0 {
+            ReactorEvent::DeployAcceptorAnnouncement(
+                DeployAcceptorAnnouncement::DeployStored { deploy: stored, .. },
+            ) => assert_eq!(*stored, deploy),
+            other => panic!("unexpected event {:?}", other),
+        }
+        assert_eq!(scheduler.item_count(), 0);
+        assert_eq!(pending_effects.len(), 1);
+    }
+
+    #[tokio::test]
+    async fn should_record_sizes_and_warn_about_large_deploys() {
+        let mut rng = TestRng::new();
+        let scheduler = utils::leak(Scheduler::<ReactorEvent>::new(QueueKind::weights()));
+        let effect_builder = EffectBuilder::new(EventQueueHandle::new(scheduler));
+
+        let small_deploy = Deploy::random(&mut rng);
+        let large_deploy = large_deploy(&mut rng);
+        let large_size = large_deploy.serialized_size().unwrap();
+        assert!(small_deploy.serialized_size().unwrap() * 2 < large_size);
+        let mut chainspec = Chainspec::random(&mut rng);
+        chainspec.genesis.deploy_config.max_dependencies = 10;
+        chainspec.genesis.deploy_config.max_ttl =
+            small_deploy.header().ttl().max(large_deploy.header().ttl());
+        // The large deploy is just within the warning threshold.
+        chainspec.genesis.deploy_config.max_block_size =
+            (large_size as u64 * 100 / LARGE_DEPLOY_PERCENT) as u32;
+
+        let mut deploy_acceptor = DeployAcceptor::new(
+            Box::new(AcceptAll),
+            1,
+            1,
+            TimeDiff::from(60_000),
+            true,
+            &Registry::new(),
+        )
+        .unwrap();
+        let mut results = Vec::new();
+        for deploy in &[&small_deploy, &large_deploy] {
+            let mut chainspec = chainspec.clone();
+            chainspec.genesis.name = deploy.header().chain_name().to_string();
+            let event = Event::GetChainspecResult {
+                deploy: Box::new((*deploy).clone()),
+                source: Source::Client,
+                chainspec_version: Version::new(1, 0, 0),
+                maybe_chainspec: Box::new(Some(chainspec)),
+                responder: None,
+            };
+            results.push(
+                validation_result(&mut deploy_acceptor, effect_builder, &mut rng, event).await,
+            );
+        }
+
+        let capturing_layer = WarningCapturingLayer::default();
+        let subscriber = registry().with(capturing_layer.clone());
+        tracing::subscriber::with_default(subscriber, || {
+            for event in results {
+                let _ = deploy_acceptor.handle_event(effect_builder, &mut rng, event);
+            }
+        });
+
+        let deploy_size = &deploy_acceptor.metrics.deploy_size;
+        assert_eq!(deploy_size.get_sample_count(), 2);
+        let total_size = small_deploy.serialized_size().unwrap() + large_size;
+        assert_eq!(deploy_size.get_sample_sum() as usize, total_size);
+        assert_eq!(
+            *capturing_layer.0.lock().unwrap(),
+            vec!["accepted deploy approaching the maximum block size".to_string()]
+        );
+    }
+
+    #[tokio::test]
+    async fn should_queue_validations_beyond_concurrency_limit() {
+        let permits = Arc::new(Semaphore::new(2));
+        let started = Arc::new(AtomicUsize::new(0));
+
+        // Each validation blocks until it is released.
+        let mut releases = Vec::new();
+        let mut validations = Vec::new();
+        for _ in 0..3 {
+            let (release, released) = mpsc::channel::<()>();
+            let started = Arc::clone(&started);
+            releases.push(release);
+            validations.push(tokio::spawn(run_validation(
+                Arc::clone(&permits),
+                move || {
+                    let _ = started.fetch_add(1, Ordering::SeqCst);
+                    released.recv().is_ok()
+                },
+            )));
+        }
+        let wait_for_started = |count| {
+            let started = Arc::clone(&started);
+            async move {
+                for _ in 0..500 {
+                    if started.load(Ordering::SeqCst) >= count {
+                        return;
+                    }
+                    time::delay_for(Duration::from_millis(10)).await;
+                }
+                panic!("{} validations should have started", count);
+            }
+        };
+
+        // Two validations run, while the third waits for a slot.
+        wait_for_started(2).await;
+        time::delay_for(Duration::from_millis(100)).await;
+        assert_eq!(started.load(Ordering::SeqCst), 2);
+        assert_eq!(permits.available_permits(), 0);
+
+        // Finishing one validation lets the third one start.
+        releases[0].send(()).unwrap();
+        wait_for_started(3).await;
+
+        for release in &releases[1..] {
+            release.send(()).unwrap();
+        }
+        for validation in validations {
+            assert!(validation.await.unwrap());
+        }
+        assert_eq!(permits.available_permits(), 2);
+    }
+
+    #[tokio::test]
+    async fn should_drop_deploys_beyond_queued_validations_limit() {
+        let mut rng = TestRng::new();
+        let scheduler = utils::leak(Scheduler::<ReactorEvent>::new(QueueKind::weights()));
+        let effect_builder = EffectBuilder::new(EventQueueHandle::new(scheduler));
+
+        // One validation may run, and one more may wait.
+        let mut deploy_acceptor = DeployAcceptor::new(
+            Box::new(AcceptAll),
+            1,
+            1,
+            TimeDiff::from(60_000),
+            true,
+            &Registry::new(),
+        )
+        .unwrap();
+        let chainspec = Chainspec::random(&mut rng);
+        let get_chainspec_result = |rng: &mut TestRng| Event::GetChainspecResult {
+            deploy: Box::new(Deploy::random(rng)),
+            source: Source::Peer(rng.gen()),
+            chainspec_version: Version::new(1, 0, 0),
+            maybe_chainspec: Box::new(Some(chainspec.clone())),
+            responder: None,
+        };
+
+        let mut validations = Vec::new();
+        for _ in 0..2 {
+            let event = get_chainspec_result(&mut rng);
+            let mut effects = deploy_acceptor.handle_event(effect_builder, &mut rng, event);
+            assert_eq!(effects.len(), 1);
+            validations.push(effects.pop().unwrap());
+        }
+
+        // A third deploy is dropped without being validated.
+        let event = get_chainspec_result(&mut rng);
+        let effects = deploy_acceptor.handle_event(effect_builder, &mut rng, event);
+        assert!(effects.is_empty());
+        assert_eq!(deploy_acceptor.pending_validations, 2);
+
+        // Once a validation has finished, deploys are validated again.
+        let mut events = validations.remove(0).await;
+        assert_eq!(events.len(), 1);
+        let _ = deploy_acceptor.handle_event(effect_builder, &mut rng, events.pop().unwrap());
+        assert_eq!(deploy_acceptor.pending_validations, 1);
+        let event = get_chainspec_result(&mut rng);
+        let effects = deploy_acceptor.handle_event(effect_builder, &mut rng, event);
+        assert_eq!(effects.len(), 1);
+        assert_eq!(deploy_acceptor.pending_validations, 2);
+    }
+}
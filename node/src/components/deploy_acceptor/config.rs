@@ -0,0 +1,15 @@
+use serde::{Deserialize, Serialize};
+
+/// Deploy acceptor configuration, i.e. the operator's acceptance policy for deploys.
+#[derive(Clone, Debug, Default, Deserialize, Serialize)]
+// Disallow unknown fields to ensure config files and command-line overrides contain valid keys.
+#[serde(deny_unknown_fields)]
+pub struct Config {
+    /// The maximum serialized size in bytes of accepted deploys.  Use 0 to accept deploys of any
+    /// size.
+    pub max_deploy_size: u32,
+
+    /// The hex-encoded public keys of the only accounts whose deploys are accepted.  If empty,
+    /// deploys of all accounts are accepted.
+    pub accepted_accounts: Vec<String>,
+}
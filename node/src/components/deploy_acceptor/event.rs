@@ -2,8 +2,13 @@ use std::fmt::{self, Display, Formatter};
 
 use semver::Version;
 
-use super::Source;
-use crate::{components::chainspec_loader::Chainspec, small_network::NodeId, types::Deploy};
+use super::{Error, Source};
+use crate::{
+    components::{chainspec_loader::Chainspec, storage::StorageFailure},
+    effect::Responder,
+    small_network::NodeId,
+    types::Deploy,
+};
 
 /// `DeployAcceptor` events.
 #[derive(Debug)]
@@ -12,6 +17,8 @@ pub enum Event {
     Accept {
         deploy: Box<Deploy>,
         source: Source<NodeId>,
+        /// Responder to call with whether the deploy was accepted, if submitted by a client.
+        responder: Option<Responder<Result<(), Error>>>,
     },
     /// The result of getting the chainspec from the storage component.
     GetChainspecResult {
@@ -19,19 +26,38 @@ pub enum Event {
         source: Source<NodeId>,
         chainspec_version: Version,
         maybe_chainspec: Box<Option<Chainspec>>,
+        responder: Option<Responder<Result<(), Error>>>,
+    },
+    /// The result of validating a `Deploy` against the chainspec.
+    ValidationResult {
+        deploy: Box<Deploy>,
+        source: Source<NodeId>,
+        /// The maximum block size of the chainspec validated against.
+        max_block_size: u32,
+        is_valid: bool,
+        responder: Option<Responder<Result<(), Error>>>,
+    },
+    /// The result of adding a valid `Deploy` to the deploy buffer.
+    BufferResult {
+        deploy: Box<Deploy>,
+        source: Source<NodeId>,
+        /// Whether the deploy buffer holds the deploy, i.e. whether it had room for it.
+        is_buffered: bool,
+        responder: Option<Responder<Result<(), Error>>>,
     },
     /// The result of the `DeployAcceptor` putting a `Deploy` to the storage component.
     PutToStorageResult {
         deploy: Box<Deploy>,
         source: Source<NodeId>,
-        is_new: bool,
+        /// Whether the deploy is new, or the failure of storage making the node shut down.
+        result: Result<bool, StorageFailure>,
     },
 }
 
 impl Display for Event {
     fn fmt(&self, formatter: &mut Formatter<'_>) -> fmt::Result {
         match self {
-            Event::Accept { deploy, source } => {
+            Event::Accept { deploy, source, .. } => {
                 write!(formatter, "accept {} from {}", deploy.id(), source)
             }
             Event::GetChainspecResult {
@@ -49,13 +75,36 @@ impl Display for Event {
                     )
                 }
             }
-            Event::PutToStorageResult { deploy, is_new, .. } => {
-                if *is_new {
-                    write!(formatter, "put new {} to storage", deploy.id())
+            Event::ValidationResult {
+                deploy, is_valid, ..
+            } => {
+                if *is_valid {
+                    write!(formatter, "validated {}", deploy.id())
+                } else {
+                    write!(formatter, "found {} invalid", deploy.id())
+                }
+            }
+            Event::BufferResult {
+                deploy,
+                is_buffered,
+                ..
+            } => {
+                if *is_buffered {
+                    write!(formatter, "buffered {}", deploy.id())
                 } else {
-                    write!(formatter, "had already stored {}", deploy.id())
+                    write!(formatter, "deploy buffer has no room for {}", deploy.id())
                 }
             }
+            Event::PutToStorageResult { deploy, result, .. } => match result {
+                Ok(true) => write!(formatter, "put new {} to storage", deploy.id()),
+                Ok(false) => write!(formatter, "had already stored {}", deploy.id()),
+                Err(error) => write!(
+                    formatter,
+                    "failed to put {} to storage: {}",
+                    deploy.id(),
+                    error
+                ),
+            },
         }
     }
 }
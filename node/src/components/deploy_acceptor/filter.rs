@@ -0,0 +1,72 @@
+//! Operator-defined acceptance policies for deploys.
+
+use std::{collections::HashSet, fmt::Debug};
+
+use super::Config;
+use crate::{
+    crypto::{self, asymmetric_key::PublicKey},
+    types::Deploy,
+};
+
+/// A policy deciding which deploys are accepted by this node.
+///
+/// The filter is consulted for every deploy received, whether submitted by a client via the API or
+/// received from a peer through gossip, before it is validated and stored.  Rejected deploys are
+/// neither stored nor gossiped any further.
+pub trait DeployFilter: Debug + Send {
+    /// Checks whether `deploy` is to be accepted, returning the reason if not.
+    fn check(&self, deploy: &Deploy) -> Result<(), String>;
+}
+
+/// The default filter, accepting every deploy.
+#[derive(Debug, Default)]
+pub struct AcceptAll;
+
+impl DeployFilter for AcceptAll {
+    fn check(&self, _deploy: &Deploy) -> Result<(), String> {
+        Ok(())
+    }
+}
+
+/// The filter applying the policy set in the node's configuration.
+#[derive(Debug)]
+pub struct ConfiguredFilter {
+    /// The maximum serialized size of accepted deploys, or 0 if unlimited.
+    max_deploy_size: u32,
+    /// The only accounts whose deploys are accepted, or empty if all are.
+    accepted_accounts: HashSet<PublicKey>,
+}
+
+impl ConfiguredFilter {
+    /// Creates the filter of `config`, failing if an accepted account is not a valid public key.
+    pub fn new(config: &Config) -> Result<Self, crypto::Error> {
+        let accepted_accounts = config
+            .accepted_accounts
+            .iter()
+            .map(PublicKey::from_hex)
+            .collect::<Result<_, _>>()?;
+        Ok(ConfiguredFilter {
+            max_deploy_size: config.max_deploy_size,
+            accepted_accounts,
+        })
+    }
+}
+
+impl DeployFilter for ConfiguredFilter {
+    fn check(&self, deploy: &Deploy) -> Result<(), String> {
+        let size = deploy
+            .serialized_size()
+            .map_err(|error| format!("failed to serialize: {}", error))?;
+        if self.max_deploy_size != 0 && size > self.max_deploy_size as usize {
+            return Err(format!(
+                "size of {} bytes exceeds {} bytes",
+                size, self.max_deploy_size
+            ));
+        }
+        let account = deploy.header().account();
+        if !self.accepted_accounts.is_empty() && !self.accepted_accounts.contains(account) {
+            return Err(format!("account {} is not accepted", account));
+        }
+        Ok(())
+    }
+}
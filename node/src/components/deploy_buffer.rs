@@ -1,58 +1,116 @@
 //! Deploy buffer.
 //!
 //! The deploy buffer stores deploy hashes in memory, tracking their suitability for inclusion into
-//! a new block. Upon request, it returns a list of candidates that can be included.
+//! a new block. Upon request, it returns a list of candidates that can be included, or a list of
+//! all pending deploys for inspection by clients.
+//!
+//! Deploys which stay pending for longer than the configured rebroadcast threshold are announced
+//! for rebroadcasting, in case they were lost while being gossiped. Each deploy is rebroadcast at
+//! most a configured number of times, and never once it has been included in a block or is within
+//! a configured margin of its expiry.
+//!
+//! The number and total serialized size of pending deploys are capped. Once the buffer is full, a
+//! new deploy is only accepted if evicting pending deploys with a strictly lower gas price frees
+//! enough room for it; the lowest-priced deploys are evicted first.  The limits are checked when a
+//! deploy is added, regardless of whether it was submitted by a client or received from a peer, so
+//! a deploy accepted by the deploy acceptor has its room reserved right away.
+//!
+//! Candidates for inclusion are returned in the order of their estimated fee, i.e. their gas price
+//! times the gas they are estimated to consume.  The estimate is made by a pluggable
+//! `GasEstimator` when a deploy enters the buffer.
+//!
+//! If a finalized block is orphaned by a fork of the linear chain, its deploys become pending
+//! again, unless they have been included in another finalized or proposed block in the meantime.
+//!
+//! If a persistence path is configured, the deploys not yet finalized are saved whenever pending
+//! deploys are checked for rebroadcasting and on shutdown, and restored from storage on startup.
+
+mod gas_estimator;
+mod persisted_deploys;
 
 use std::{
+    cmp::Reverse,
     collections::{HashMap, HashSet},
     fmt::{self, Display, Formatter},
+    mem,
+    path::PathBuf,
+    time::Duration,
 };
 
 use derive_more::From;
+use prometheus::{self, IntGauge, Registry};
 use rand::{CryptoRng, Rng};
 use semver::Version;
-use tracing::{error, info};
+use tracing::{error, info, warn};
 
 use crate::{
-    components::{chainspec_loader::DeployConfig, storage::Storage, Component},
+    components::{chainspec_loader::DeployConfig, storage::Storage, Component, ComponentLifecycle},
     effect::{
+        announcements::DeployBufferAnnouncement,
         requests::{DeployBufferRequest, StorageRequest},
         EffectBuilder, EffectExt, Effects, Responder,
     },
-    types::{DeployHash, DeployHeader, ProtoBlock, ProtoBlockHash, Timestamp},
+    types::{
+        Block, Deploy, DeployHash, DeployHeader, NodeConfig, ProtoBlock, ProtoBlockHash, TimeDiff,
+        Timestamp,
+    },
     Chainspec,
 };
+pub use gas_estimator::{DefaultGasEstimator, GasEstimator};
+use persisted_deploys::PersistedDeploys;
 
 /// An event for when using the deploy buffer as a component.
 #[derive(Debug, From)]
 pub enum Event {
     #[from]
     Request(DeployBufferRequest),
-    /// A new deploy should be buffered.
-    Buffer {
-        hash: DeployHash,
-        header: Box<DeployHeader>,
-    },
     /// A proto block has been proposed. We should not propose duplicates of its deploys.
     ProposedProtoBlock(ProtoBlock),
     /// A proto block has been finalized. We should never propose its deploys again.
     FinalizedProtoBlock(ProtoBlock),
     /// A proto block has been orphaned. Its deploys should be re-proposed.
     OrphanedProtoBlock(ProtoBlock),
+    /// A finalized block has been orphaned. Its deploys not included elsewhere should be
+    /// re-proposed.
+    OrphanedBlock(Box<Block>),
+    /// Pending deploys should be checked for whether they need to be rebroadcast.
+    CheckRebroadcast,
+    /// The deploys persisted before the last shutdown have been restored from storage, except
+    /// those included in a block in the meantime.
+    Restored { deploys: Vec<Deploy> },
     /// The result of the `DeployBuffer` getting the chainspec from the storage component.
     GetChainspecResult {
         maybe_chainspec: Box<Option<Chainspec>>,
         current_instant: Timestamp,
         past_blocks: HashSet<ProtoBlockHash>,
-        responder: Responder<HashSet<DeployHash>>,
+        responder: InclusionResponder,
     },
 }
 
+/// The outcome of adding a deploy to the deploy buffer.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum Insertion {
+    /// The deploy has been added to the pending deploys.
+    Added,
+    /// The deploy is already pending, or has been included in a finalized block.
+    Known,
+    /// The deploy has been refused, as the buffer is full and it can't displace any other.
+    Refused,
+}
+
+/// The responder of a request for deploys to include in a new block.
+#[derive(Debug)]
+pub enum InclusionResponder {
+    /// Responds with the hashes of the deploys to include.
+    Hashes(Responder<HashSet<DeployHash>>),
+    /// Responds with the candidates for a proposal.
+    Candidates(Responder<Vec<DeployCandidate>>),
+}
+
 impl Display for Event {
     fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
         match self {
             Event::Request(req) => write!(f, "deploy-buffer request: {}", req),
-            Event::Buffer { hash, .. } => write!(f, "deploy-buffer add {}", hash),
             Event::ProposedProtoBlock(block) => {
                 write!(f, "deploy-buffer proposed proto block {}", block)
             }
@@ -62,6 +120,13 @@ impl Display for Event {
             Event::OrphanedProtoBlock(block) => {
                 write!(f, "deploy-buffer orphaned proto block {}", block)
             }
+            Event::OrphanedBlock(block) => {
+                write!(f, "deploy-buffer orphaned block {}", block.hash())
+            }
+            Event::CheckRebroadcast => write!(f, "deploy-buffer check rebroadcast"),
+            Event::Restored { deploys } => {
+                write!(f, "deploy-buffer restored {} deploys", deploys.len())
+            }
             Event::GetChainspecResult {
                 maybe_chainspec, ..
             } => {
@@ -75,50 +140,461 @@ impl Display for Event {
     }
 }
 
-/// Deploy buffer.
+/// A deploy pending in the buffer.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct PendingDeploy {
+    /// The deploy's hash.
+    pub hash: DeployHash,
+    /// The gas price offered by the deploy.
+    pub gas_price: u64,
+    /// The deploy's creation time.
+    pub timestamp: Timestamp,
+}
+
+/// A pending deploy which can be included in a new block.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct DeployCandidate {
+    /// The deploy's hash.
+    pub hash: DeployHash,
+    /// The deploy's dependencies which are pending too, and have to precede it in the block.
+    pub pending_dependencies: Vec<DeployHash>,
+    /// The deploy's serialized size in bytes.
+    pub size: u64,
+    /// The gas the deploy is estimated to consume.
+    pub gas_estimate: u64,
+}
+
+/// A deploy included in a finalized block, with what is needed to buffer it again if the block is
+/// orphaned.
+#[derive(Debug, Clone)]
+struct FinalizedDeploy {
+    header: DeployHeader,
+    /// The deploy's serialized size in bytes.
+    size: u64,
+    /// The gas the deploy is estimated to consume.
+    gas_estimate: u64,
+}
+
+/// Rebroadcast bookkeeping of a buffered deploy.
 #[derive(Debug, Clone)]
+struct RebroadcastState {
+    /// When the deploy was last (re)broadcast.
+    last_broadcast: Timestamp,
+    /// The number of times the deploy has been rebroadcast.
+    count: u32,
+}
+
+/// Metrics of the deploy buffer.
+#[derive(Debug)]
+struct DeployBufferMetrics {
+    /// Number of pending deploys.
+    pending_count: IntGauge,
+    /// Total serialized size in bytes of the pending deploys.
+    pending_bytes: IntGauge,
+
+    /// Handle to the metrics registry, in case we need to unregister.
+    registry: Registry,
+}
+
+impl DeployBufferMetrics {
+    /// Create and register new deploy buffer metrics.
+    fn new(registry: &Registry) -> Result<Self, prometheus::Error> {
+        let pending_count =
+            IntGauge::new("deploy_buffer_pending_count", "number of pending deploys")?;
+        let pending_bytes = IntGauge::new(
+            "deploy_buffer_pending_bytes",
+            "total serialized size of pending deploys in bytes",
+        )?;
+        registry.register(Box::new(pending_count.clone()))?;
+        registry.register(Box::new(pending_bytes.clone()))?;
+
+        Ok(DeployBufferMetrics {
+            pending_count,
+            pending_bytes,
+            registry: registry.clone(),
+        })
+    }
+}
+
+impl Drop for DeployBufferMetrics {
+    fn drop(&mut self) {
+        self.registry
+            .unregister(Box::new(self.pending_count.clone()))
+            .expect("did not expect deregistering pending count to fail");
+        self.registry
+            .unregister(Box::new(self.pending_bytes.clone()))
+            .expect("did not expect deregistering pending bytes to fail");
+    }
+}
+
+/// Deploy buffer.
+#[derive(Debug)]
 pub(crate) struct DeployBuffer {
     block_max_deploy_count: usize,
     collected_deploys: HashMap<DeployHash, DeployHeader>,
     processed: HashMap<ProtoBlockHash, HashMap<DeployHash, DeployHeader>>,
-    finalized: HashMap<ProtoBlockHash, HashMap<DeployHash, DeployHeader>>,
+    finalized: HashMap<ProtoBlockHash, HashMap<DeployHash, FinalizedDeploy>>,
+    /// Time after which a pending deploy is rebroadcast.
+    rebroadcast_threshold: TimeDiff,
+    /// The maximum number of times a pending deploy is rebroadcast.
+    max_rebroadcasts: u32,
+    /// Time before its expiry after which a pending deploy is no longer rebroadcast.
+    rebroadcast_expiry_margin: TimeDiff,
+    /// Rebroadcast bookkeeping of all deploys not yet finalized.
+    rebroadcasts: HashMap<DeployHash, RebroadcastState>,
+    /// Whether a `CheckRebroadcast` event is currently scheduled.
+    is_rebroadcast_check_scheduled: bool,
+    /// The maximum number of pending deploys.
+    max_pending_count: usize,
+    /// The maximum total serialized size in bytes of the pending deploys.
+    max_pending_bytes: u64,
+    /// Serialized sizes of all deploys not yet finalized.  Those of finalized deploys are kept in
+    /// `finalized`.
+    sizes: HashMap<DeployHash, u64>,
+    /// Total serialized size in bytes of the deploys in `collected_deploys`.
+    pending_bytes: u64,
+    /// Estimates the gas consumed by deploys entering the buffer.
+    gas_estimator: Box<dyn GasEstimator>,
+    /// Estimated gas of all deploys not yet finalized.
+    gas_estimates: HashMap<DeployHash, u64>,
+    /// The path the deploys not yet finalized are persisted to, if any.
+    persistence_path: Option<PathBuf>,
+    /// Whether the deploys not yet finalized have changed since they were last persisted.
+    needs_persisting: bool,
+    metrics: DeployBufferMetrics,
 }
 
 impl DeployBuffer {
-    /// Creates a new, empty deploy buffer instance.
-    pub(crate) fn new(block_max_deploy_count: usize) -> Self {
-        DeployBuffer {
-            block_max_deploy_count,
+    /// Creates a new, empty deploy buffer instance, estimating the gas of deploys via
+    /// `gas_estimator`.
+    pub(crate) fn new(
+        config: &NodeConfig,
+        gas_estimator: Box<dyn GasEstimator>,
+        registry: &Registry,
+    ) -> Result<Self, prometheus::Error> {
+        Ok(DeployBuffer {
+            block_max_deploy_count: config.block_max_deploy_count as usize,
             collected_deploys: HashMap::new(),
             processed: HashMap::new(),
             finalized: HashMap::new(),
+            rebroadcast_threshold: TimeDiff::from(config.deploy_rebroadcast_threshold_secs * 1000),
+            max_rebroadcasts: config.deploy_max_rebroadcasts,
+            rebroadcast_expiry_margin: TimeDiff::from(
+                config.deploy_rebroadcast_expiry_margin_secs * 1000,
+            ),
+            rebroadcasts: HashMap::new(),
+            is_rebroadcast_check_scheduled: false,
+            max_pending_count: config.deploy_buffer_max_count as usize,
+            max_pending_bytes: config.deploy_buffer_max_bytes,
+            sizes: HashMap::new(),
+            pending_bytes: 0,
+            gas_estimator,
+            gas_estimates: HashMap::new(),
+            persistence_path: config.deploy_buffer_persistence_path.clone(),
+            needs_persisting: false,
+            metrics: DeployBufferMetrics::new(registry)?,
+        })
+    }
+
+    /// Restores the deploys persisted before the last shutdown from storage, if a persistence path
+    /// is configured.
+    ///
+    /// Deploys which have been included in a block in the meantime are dropped.
+    pub(crate) fn restore_persisted<REv>(
+        &self,
+        effect_builder: EffectBuilder<REv>,
+    ) -> Effects<Event>
+    where
+        REv: From<StorageRequest<Storage>> + Send,
+    {
+        let deploy_hashes = self.persisted_deploys();
+        if deploy_hashes.is_empty() {
+            return Effects::new();
+        }
+        info!(count = deploy_hashes.len(), "restoring persisted deploys");
+        async move {
+            let mut deploys = Vec::new();
+            for deploy_hash in deploy_hashes {
+                match effect_builder
+                    .get_deploy_and_metadata_from_storage::<Storage>(deploy_hash)
+                    .await
+                {
+                    Some((deploy, metadata)) if metadata.execution_results.is_empty() => {
+                        deploys.push(deploy)
+                    }
+                    Some(_) => info!(%deploy_hash, "dropped persisted deploy included in a block"),
+                    None => warn!(%deploy_hash, "persisted deploy missing from storage"),
+                }
+            }
+            deploys
         }
+        .event(|deploys| Event::Restored { deploys })
     }
 
-    /// Adds a deploy to the deploy buffer.
+    /// Returns the hashes of the deploys persisted before the last shutdown.
+    fn persisted_deploys(&self) -> Vec<DeployHash> {
+        let path = match self.persistence_path {
+            Some(ref path) => path,
+            None => return Vec::new(),
+        };
+        match PersistedDeploys::load(path) {
+            Ok(persisted) => persisted.into_deploys(),
+            Err(error) => {
+                warn!(path = %path.display(), %error, "failed to load persisted deploys");
+                Vec::new()
+            }
+        }
+    }
+
+    /// Buffers the deploys restored from storage, except those which have expired by
+    /// `current_instant`.
     ///
-    /// Returns `false` if the deploy has been rejected.
-    fn add_deploy(&mut self, hash: DeployHash, header: DeployHeader) {
-        // only add the deploy if it isn't contained in a finalized block
-        if !self
-            .finalized
-            .values()
-            .any(|block| block.contains_key(&hash))
+    /// Returns `true` if any deploy has been buffered.
+    fn restore(&mut self, deploys: Vec<Deploy>, current_instant: Timestamp) -> bool {
+        let mut restored = false;
+        for deploy in deploys {
+            if deploy.header().expires() < current_instant {
+                info!(deploy_hash = %deploy.id(), "dropped expired persisted deploy");
+                continue;
+            }
+            restored |= self.buffer_deploy(&deploy) == Insertion::Added;
+        }
+        restored
+    }
+
+    /// Returns the hashes of the deploys not yet finalized, i.e. those which are pending or
+    /// included in a proposed block.
+    fn unfinalized_deploys(&self) -> impl Iterator<Item = DeployHash> + '_ {
+        self.collected_deploys
+            .keys()
+            .chain(self.processed.values().flat_map(|deploys| deploys.keys()))
+            .copied()
+    }
+
+    /// Saves the deploys not yet finalized if a persistence path is configured and they have
+    /// changed since they were last saved.
+    fn persist(&mut self) {
+        if !mem::replace(&mut self.needs_persisting, false) {
+            return;
+        }
+        let path = match self.persistence_path {
+            Some(ref path) => path,
+            None => return,
+        };
+        if let Err(error) = PersistedDeploys::new(self.unfinalized_deploys()).save(path) {
+            warn!(path = %path.display(), %error, "failed to save deploys");
+            self.needs_persisting = true;
+        }
+    }
+
+    /// Estimates the gas of a new deploy, and adds it to the deploy buffer.
+    fn buffer_deploy(&mut self, deploy: &Deploy) -> Insertion {
+        let hash = *deploy.id();
+        let size = match deploy.serialized_size() {
+            Ok(size) => size,
+            Err(error) => {
+                warn!(%hash, %error, "failed to serialize deploy, rejected from the buffer");
+                return Insertion::Refused;
+            }
+        };
+        let gas_estimator = &self.gas_estimator;
+        let gas_estimate = deploy.gas_estimate(|deploy| gas_estimator.estimate(deploy));
+        let insertion = self.add_deploy(hash, deploy.header().clone(), size);
+        if insertion == Insertion::Added {
+            self.gas_estimates.insert(hash, gas_estimate);
+        }
+        insertion
+    }
+
+    /// Adds a deploy to the deploy buffer.
+    fn add_deploy(&mut self, hash: DeployHash, header: DeployHeader, size: usize) -> Insertion {
+        // only add the deploy if it isn't pending or contained in a finalized block
+        if self.collected_deploys.contains_key(&hash)
+            || self
+                .finalized
+                .values()
+                .any(|block| block.contains_key(&hash))
         {
-            self.collected_deploys.insert(hash, header);
-            info!("added deploy {} to the buffer", hash);
+            return Insertion::Known;
+        }
+        let evictions = match self.evictions_for(size, header.gas_price()) {
+            Some(evictions) => evictions,
+            None => {
+                info!("deploy {} rejected from the full buffer", hash);
+                return Insertion::Refused;
+            }
+        };
+        for evicted in evictions {
+            self.remove_pending(&evicted);
+            self.sizes.remove(&evicted);
+            self.gas_estimates.remove(&evicted);
+            self.rebroadcasts.remove(&evicted);
+            info!("evicted deploy {} from the full buffer", evicted);
+        }
+
+        self.sizes.insert(hash, size as u64);
+        self.insert_pending(hash, header);
+        self.rebroadcasts
+            .entry(hash)
+            .or_insert_with(|| RebroadcastState {
+                last_broadcast: Timestamp::now(),
+                count: 0,
+            });
+        info!("added deploy {} to the buffer", hash);
+        Insertion::Added
+    }
+
+    /// Returns the pending deploys which would have to be evicted to make room for a deploy of the
+    /// given size and gas price, cheapest first, or `None` if evicting all pending deploys with a
+    /// strictly lower gas price wouldn't free enough room.
+    fn evictions_for(&self, size: usize, gas_price: u64) -> Option<Vec<DeployHash>> {
+        let fits = |count: usize, bytes: u64| {
+            count <= self.max_pending_count && bytes <= self.max_pending_bytes
+        };
+        let mut count = self.collected_deploys.len() + 1;
+        let mut bytes = self.pending_bytes + size as u64;
+        if fits(count, bytes) {
+            return Some(Vec::new());
+        }
+
+        let mut candidates: Vec<_> = self
+            .collected_deploys
+            .iter()
+            .filter(|(_, header)| header.gas_price() < gas_price)
+            .map(|(hash, header)| (header.gas_price(), *hash))
+            .collect();
+        candidates.sort_unstable();
+
+        let mut evictions = Vec::new();
+        for (_, hash) in candidates {
+            if fits(count, bytes) {
+                break;
+            }
+            count -= 1;
+            bytes -= self.size(&hash);
+            evictions.push(hash);
+        }
+        if fits(count, bytes) {
+            Some(evictions)
         } else {
-            info!("deploy {} rejected from the buffer", hash);
+            None
+        }
+    }
+
+    /// Returns all pending deploys, in no particular order.
+    fn pending_deploys(&self) -> Vec<PendingDeploy> {
+        self.collected_deploys
+            .iter()
+            .map(|(hash, header)| PendingDeploy {
+                hash: *hash,
+                gas_price: header.gas_price(),
+                timestamp: header.timestamp(),
+            })
+            .collect()
+    }
+
+    /// Returns the serialized size of a deploy not yet finalized.
+    fn size(&self, hash: &DeployHash) -> u64 {
+        self.sizes.get(hash).copied().unwrap_or_default()
+    }
+
+    /// Adds a deploy to the pending deploys, unless it is pending already.
+    fn insert_pending(&mut self, hash: DeployHash, header: DeployHeader) {
+        if self.collected_deploys.insert(hash, header).is_none() {
+            self.pending_bytes += self.size(&hash);
+            self.needs_persisting = true;
+            self.update_metrics();
+        }
+    }
+
+    /// Removes a deploy from the pending deploys, returning its header if it was pending.
+    fn remove_pending(&mut self, hash: &DeployHash) -> Option<DeployHeader> {
+        let header = self.collected_deploys.remove(hash)?;
+        self.pending_bytes -= self.size(hash);
+        self.needs_persisting = true;
+        self.update_metrics();
+        Some(header)
+    }
+
+    fn update_metrics(&self) {
+        self.metrics
+            .pending_count
+            .set(self.collected_deploys.len() as i64);
+        self.metrics.pending_bytes.set(self.pending_bytes as i64);
+    }
+
+    /// Schedules a `CheckRebroadcast` event, unless one is already scheduled.
+    fn schedule_rebroadcast_check<REv>(
+        &mut self,
+        effect_builder: EffectBuilder<REv>,
+    ) -> Effects<Event>
+    where
+        REv: Send,
+    {
+        if self.is_rebroadcast_check_scheduled {
+            return Effects::new();
+        }
+        self.is_rebroadcast_check_scheduled = true;
+        effect_builder
+            .set_timeout(Duration::from_millis(self.rebroadcast_threshold.millis()))
+            .event(|_| Event::CheckRebroadcast)
+    }
+
+    /// Returns the pending deploys which have aged past the rebroadcast threshold as of
+    /// `current_instant`, haven't yet been rebroadcast the maximum number of times and don't expire
+    /// within the rebroadcast expiry margin.
+    ///
+    /// The returned deploys are recorded as having been rebroadcast at `current_instant`.  The
+    /// bookkeeping of deploys which have expired by `current_instant` is dropped, since they will
+    /// never be rebroadcast again.
+    fn deploys_to_rebroadcast(&mut self, current_instant: Timestamp) -> Vec<DeployHash> {
+        let mut aged_deploys = vec![];
+        let mut expired_deploys = vec![];
+        for (hash, state) in self.rebroadcasts.iter_mut() {
+            // Deploys which have been included in a proposed block are not pending.
+            let header = match self.collected_deploys.get(hash) {
+                Some(header) => header,
+                None => {
+                    if self
+                        .processed
+                        .values()
+                        .filter_map(|deploys| deploys.get(hash))
+                        .any(|header| header.expires() < current_instant)
+                    {
+                        expired_deploys.push(*hash);
+                    }
+                    continue;
+                }
+            };
+            if header.expires() < current_instant {
+                expired_deploys.push(*hash);
+                continue;
+            }
+            // Deploys close to their expiry will likely not be included anymore.
+            if header.expires() <= current_instant + self.rebroadcast_expiry_margin
+                || state.count >= self.max_rebroadcasts
+                || state.last_broadcast + self.rebroadcast_threshold > current_instant
+            {
+                continue;
+            }
+            state.last_broadcast = current_instant;
+            state.count += 1;
+            aged_deploys.push(*hash);
+        }
+        for hash in expired_deploys {
+            self.rebroadcasts.remove(&hash);
         }
+        aged_deploys
     }
 
-    /// Gets the chainspec from storage in order to call `remaining_deploys()`.
+    /// Gets the chainspec from storage in order to call `remaining_deploys()` or
+    /// `proposal_candidates()`.
     fn get_chainspec_from_storage<REv>(
         &mut self,
         effect_builder: EffectBuilder<REv>,
         current_instant: Timestamp,
         past_blocks: HashSet<ProtoBlockHash>,
-        responder: Responder<HashSet<DeployHash>>,
+        responder: InclusionResponder,
     ) -> Effects<Event>
     where
         REv: From<StorageRequest<Storage>> + Send,
@@ -135,33 +611,96 @@ impl DeployBuffer {
             })
     }
 
-    /// Returns a list of candidates for inclusion into a block.
+    /// Returns the estimated fee of a pending deploy, i.e. its gas price times its estimated gas.
+    ///
+    /// Deploys without an estimate are assumed to consume no gas.
+    fn estimated_fee(&self, hash: &DeployHash, header: &DeployHeader) -> u64 {
+        let gas_estimate = self.gas_estimates.get(hash).copied().unwrap_or_default();
+        header.gas_price().saturating_mul(gas_estimate)
+    }
+
+    /// Returns the deploys included in finalized blocks or in the processed blocks `past_blocks`.
+    fn past_deploys(&self, past_blocks: &HashSet<ProtoBlockHash>) -> HashSet<&DeployHash> {
+        past_blocks
+            .iter()
+            .filter_map(|block_hash| self.processed.get(block_hash))
+            .flat_map(|deploys| deploys.keys())
+            .chain(self.finalized.values().flat_map(|deploys| deploys.keys()))
+            .collect()
+    }
+
+    /// Returns a list of candidates for inclusion into a block, preferring those with the highest
+    /// estimated fee.
     fn remaining_deploys(
         &mut self,
         deploy_config: DeployConfig,
         current_instant: Timestamp,
         past_blocks: HashSet<ProtoBlockHash>,
     ) -> HashSet<DeployHash> {
-        let past_deploys = past_blocks
-            .iter()
-            .filter_map(|block_hash| self.processed.get(block_hash))
-            .chain(self.finalized.values())
-            .flat_map(|deploys| deploys.keys())
-            .collect::<HashSet<_>>();
+        let past_deploys = self.past_deploys(&past_blocks);
         // deploys_to_return = all deploys in collected_deploys that aren't in finalized blocks or
         // processed blocks from the set `past_blocks`
-        self.collected_deploys
+        let mut candidates: Vec<_> = self
+            .collected_deploys
             .iter()
             .filter(|&(hash, deploy)| {
                 self.is_deploy_valid(deploy, current_instant, &deploy_config, &past_deploys)
                     && !past_deploys.contains(hash)
             })
-            .map(|(hash, _deploy)| *hash)
+            .map(|(hash, deploy)| (Reverse(self.estimated_fee(hash, deploy)), *hash))
+            .collect();
+        candidates.sort_unstable();
+        candidates
+            .into_iter()
+            .map(|(_, hash)| hash)
             .take(self.block_max_deploy_count)
             .collect::<HashSet<_>>()
         // TODO: check gas and block size limits
     }
 
+    /// Returns the candidates for inclusion into a block proposed at `current_instant`, highest
+    /// estimated fee first.
+    ///
+    /// Unlike `remaining_deploys`, this includes deploys whose dependencies are still pending, as
+    /// they can be proposed together with their dependencies.  Selecting the deploys within the
+    /// block limits is left to the proposal builder.
+    fn proposal_candidates(
+        &self,
+        deploy_config: DeployConfig,
+        current_instant: Timestamp,
+        past_blocks: HashSet<ProtoBlockHash>,
+    ) -> Vec<DeployCandidate> {
+        let past_deploys = self.past_deploys(&past_blocks);
+        let is_available = |dep: &DeployHash| {
+            past_deploys.contains(dep) || self.collected_deploys.contains_key(dep)
+        };
+        let mut candidates: Vec<_> = self
+            .collected_deploys
+            .iter()
+            .filter(|&(hash, deploy)| {
+                !past_deploys.contains(hash)
+                    && self.is_deploy_includable(deploy, current_instant, &deploy_config)
+                    && deploy.dependencies().iter().all(&is_available)
+            })
+            .map(|(hash, deploy)| (Reverse(self.estimated_fee(hash, deploy)), *hash, deploy))
+            .collect();
+        candidates.sort_unstable_by_key(|&(fee, hash, _)| (fee, hash));
+        candidates
+            .into_iter()
+            .map(|(_, hash, deploy)| DeployCandidate {
+                hash,
+                pending_dependencies: deploy
+                    .dependencies()
+                    .iter()
+                    .filter(|dep| !past_deploys.contains(dep))
+                    .copied()
+                    .collect(),
+                size: self.size(&hash),
+                gas_estimate: self.gas_estimates.get(&hash).copied().unwrap_or_default(),
+            })
+            .collect()
+    }
+
     /// Checks if a deploy is valid (for inclusion into the next block).
     fn is_deploy_valid(
         &self,
@@ -176,11 +715,22 @@ impl DeployBuffer {
                 .iter()
                 .all(|dep| past_deploys.contains(dep))
         };
+        self.is_deploy_includable(deploy, current_instant, deploy_config) && all_deps_resolved()
+    }
+
+    /// Checks if a deploy can be included into a block at `current_instant`, provided its
+    /// dependencies are included before it.
+    fn is_deploy_includable(
+        &self,
+        deploy: &DeployHeader,
+        current_instant: Timestamp,
+        deploy_config: &DeployConfig,
+    ) -> bool {
         let ttl_valid = deploy.ttl() <= deploy_config.max_ttl;
         let timestamp_valid = deploy.timestamp() <= current_instant;
         let deploy_valid = deploy.timestamp() + deploy.ttl() >= current_instant;
         let num_deps_valid = deploy.dependencies().len() <= deploy_config.max_dependencies as usize;
-        ttl_valid && timestamp_valid && deploy_valid && num_deps_valid && all_deps_resolved()
+        ttl_valid && timestamp_valid && deploy_valid && num_deps_valid
     }
 
     /// Notifies the deploy buffer of a new block that has been proposed, so that the block's
@@ -194,22 +744,30 @@ impl DeployBuffer {
         let deploy_map: HashMap<_, _> = deploys
             .into_iter()
             .filter_map(|deploy_hash| {
-                self.collected_deploys
-                    .get(&deploy_hash)
-                    .map(|deploy| (deploy_hash, deploy.clone()))
+                self.remove_pending(&deploy_hash)
+                    .map(|deploy| (deploy_hash, deploy))
             })
             .collect();
-        self.collected_deploys
-            .retain(|deploy_hash, _| !deploy_map.contains_key(deploy_hash));
         self.processed.insert(block, deploy_map);
     }
 
     /// Notifies the deploy buffer that a block has been finalized.
     fn finalized_block(&mut self, block: ProtoBlockHash) {
         if let Some(deploys) = self.processed.remove(&block) {
-            self.collected_deploys
-                .retain(|deploy_hash, _| !deploys.contains_key(deploy_hash));
-            self.finalized.insert(block, deploys);
+            let mut finalized_deploys = HashMap::new();
+            for (deploy_hash, header) in deploys {
+                self.remove_pending(&deploy_hash);
+                let finalized_deploy = FinalizedDeploy {
+                    header,
+                    size: self.sizes.remove(&deploy_hash).unwrap_or_default(),
+                    gas_estimate: self.gas_estimates.remove(&deploy_hash).unwrap_or_default(),
+                };
+                finalized_deploys.insert(deploy_hash, finalized_deploy);
+            }
+            self.rebroadcasts
+                .retain(|deploy_hash, _| !finalized_deploys.contains_key(deploy_hash));
+            self.needs_persisting |= !finalized_deploys.is_empty();
+            self.finalized.insert(block, finalized_deploys);
         } else if !block.is_empty() {
             // TODO: Events are not guaranteed to be handled in order, so this could happen!
             error!("finalized block that hasn't been processed!");
@@ -217,19 +775,75 @@ impl DeployBuffer {
     }
 
     /// Notifies the deploy buffer that a block has been orphaned.
+    ///
+    /// The block's deploys become pending again, even if that exceeds the buffer's limits.
     fn orphaned_block(&mut self, block: ProtoBlockHash) {
         if let Some(deploys) = self.processed.remove(&block) {
-            self.collected_deploys.extend(deploys);
+            for (deploy_hash, deploy) in deploys {
+                self.insert_pending(deploy_hash, deploy);
+            }
         } else {
             // TODO: Events are not guaranteed to be handled in order, so this could happen!
             error!("orphaned block that hasn't been processed!");
         }
     }
+
+    /// Notifies the deploy buffer that a finalized block has been orphaned.
+    ///
+    /// The block's deploys become pending again, except those included in another finalized or
+    /// proposed block and those which have expired by `current_instant`, with the sizes and gas
+    /// estimates they had when finalized, even if that exceeds the buffer's limits.  Returns
+    /// whether any deploy became pending.
+    fn orphaned_finalized_block(&mut self, block: &Block, current_instant: Timestamp) -> bool {
+        let deploys = match self.finalized.remove(&block.proto_block_hash()) {
+            Some(deploys) => deploys,
+            None => {
+                error!(block_hash = %block.hash(), "orphaned block that hasn't been finalized!");
+                return false;
+            }
+        };
+        let mut reinstated = false;
+        for (deploy_hash, deploy) in deploys {
+            let is_included_elsewhere = self
+                .finalized
+                .values()
+                .any(|deploys| deploys.contains_key(&deploy_hash))
+                || self
+                    .processed
+                    .values()
+                    .any(|deploys| deploys.contains_key(&deploy_hash));
+            if is_included_elsewhere {
+                continue;
+            }
+            if deploy.header.expires() < current_instant {
+                info!(%deploy_hash, "dropped expired deploy of orphaned block");
+                continue;
+            }
+            self.sizes.insert(deploy_hash, deploy.size);
+            self.gas_estimates.insert(deploy_hash, deploy.gas_estimate);
+            self.insert_pending(deploy_hash, deploy.header);
+            self.rebroadcasts
+                .entry(deploy_hash)
+                .or_insert_with(|| RebroadcastState {
+                    last_broadcast: Timestamp::now(),
+                    count: 0,
+                });
+            info!(%deploy_hash, "returned deploy of orphaned block to the buffer");
+            reinstated = true;
+        }
+        reinstated
+    }
+}
+
+impl ComponentLifecycle for DeployBuffer {
+    fn on_stop(&mut self) {
+        self.persist();
+    }
 }
 
 impl<REv, R> Component<REv, R> for DeployBuffer
 where
-    REv: From<StorageRequest<Storage>> + Send,
+    REv: From<StorageRequest<Storage>> + From<DeployBufferAnnouncement> + Send,
     R: Rng + CryptoRng + ?Sized,
 {
     type Event = Event;
@@ -250,16 +864,61 @@ where
                     effect_builder,
                     current_instant,
                     past_blocks,
-                    responder,
+                    InclusionResponder::Hashes(responder),
+                );
+            }
+            Event::Request(DeployBufferRequest::ListCandidates {
+                current_instant,
+                past_blocks,
+                responder,
+            }) => {
+                return self.get_chainspec_from_storage(
+                    effect_builder,
+                    current_instant,
+                    past_blocks,
+                    InclusionResponder::Candidates(responder),
                 );
             }
-            Event::Buffer { hash, header } => self.add_deploy(hash, *header),
+            Event::Request(DeployBufferRequest::Buffer { deploy, responder }) => {
+                let insertion = self.buffer_deploy(&deploy);
+                let mut effects = responder.respond(insertion != Insertion::Refused).ignore();
+                if insertion == Insertion::Added {
+                    effects.extend(self.schedule_rebroadcast_check(effect_builder));
+                }
+                return effects;
+            }
+            Event::Request(DeployBufferRequest::ListPending { responder }) => {
+                return responder.respond(self.pending_deploys()).ignore();
+            }
             Event::ProposedProtoBlock(block) => {
                 let (hash, deploys, _) = block.destructure();
                 self.added_block(hash, deploys)
             }
             Event::FinalizedProtoBlock(block) => self.finalized_block(*block.hash()),
             Event::OrphanedProtoBlock(block) => self.orphaned_block(*block.hash()),
+            Event::OrphanedBlock(block) => {
+                if self.orphaned_finalized_block(&block, Timestamp::now()) {
+                    return self.schedule_rebroadcast_check(effect_builder);
+                }
+            }
+            Event::CheckRebroadcast => {
+                self.is_rebroadcast_check_scheduled = false;
+                self.persist();
+                let mut effects: Effects<Event> = self
+                    .deploys_to_rebroadcast(Timestamp::now())
+                    .into_iter()
+                    .flat_map(|hash| effect_builder.announce_deploy_rebroadcast(hash).ignore())
+                    .collect();
+                if !self.collected_deploys.is_empty() {
+                    effects.extend(self.schedule_rebroadcast_check(effect_builder));
+                }
+                return effects;
+            }
+            Event::Restored { deploys } => {
+                if self.restore(deploys, Timestamp::now()) {
+                    return self.schedule_rebroadcast_check(effect_builder);
+                }
+            }
             Event::GetChainspecResult {
                 maybe_chainspec,
                 current_instant,
@@ -267,12 +926,19 @@ where
                 responder,
             } => {
                 let chainspec = maybe_chainspec.expect("should return chainspec");
-                let deploys = self.remaining_deploys(
-                    chainspec.genesis.deploy_config,
-                    current_instant,
-                    past_blocks,
-                );
-                return responder.respond(deploys).ignore();
+                let deploy_config = chainspec.genesis.deploy_config;
+                return match responder {
+                    InclusionResponder::Hashes(responder) => {
+                        let deploys =
+                            self.remaining_deploys(deploy_config, current_instant, past_blocks);
+                        responder.respond(deploys).ignore()
+                    }
+                    InclusionResponder::Candidates(responder) => {
+                        let candidates =
+                            self.proposal_candidates(deploy_config, current_instant, past_blocks);
+                        responder.respond(candidates).ignore()
+                    }
+                };
             }
         }
         Effects::new()
@@ -288,19 +954,39 @@ mod tests {
 
     use super::*;
     use crate::{
-        crypto::{asymmetric_key::SecretKey, hash::hash},
+        components::consensus::EraId,
+        crypto::{
+            asymmetric_key::{PublicKey, SecretKey},
+            hash::{hash, Digest},
+        },
         testing::TestRng,
-        types::{Deploy, DeployHash, DeployHeader, NodeConfig, ProtoBlockHash, TimeDiff},
+        types::{
+            BlockHash, Deploy, DeployHash, DeployHeader, FinalizedBlock, NodeConfig,
+            ProtoBlockHash, TimeDiff,
+        },
     };
 
+    /// Nominal serialized size of deploys in tests which don't exercise the size limit.
+    const DEPLOY_SIZE: usize = 1_000;
+
     fn generate_deploy(
         rng: &mut TestRng,
         timestamp: Timestamp,
         ttl: TimeDiff,
         dependencies: Vec<DeployHash>,
     ) -> (DeployHash, DeployHeader) {
+        let deploy = generate_deploy_with_gas_price(rng, timestamp, ttl, dependencies, 10);
+        (*deploy.id(), deploy.take_header())
+    }
+
+    fn generate_deploy_with_gas_price(
+        rng: &mut TestRng,
+        timestamp: Timestamp,
+        ttl: TimeDiff,
+        dependencies: Vec<DeployHash>,
+        gas_price: u64,
+    ) -> Deploy {
         let secret_key = SecretKey::random(rng);
-        let gas_price = 10;
         let chain_name = "chain".to_string();
         let payment = ExecutableDeployItem::ModuleBytes {
             module_bytes: vec![],
@@ -311,7 +997,7 @@ mod tests {
             args: vec![],
         };
 
-        let deploy = Deploy::new(
+        Deploy::new(
             timestamp,
             ttl,
             gas_price,
@@ -321,9 +1007,12 @@ mod tests {
             session,
             &secret_key,
             rng,
-        );
+        )
+    }
 
-        (*deploy.id(), deploy.take_header())
+    fn new_buffer(config: &NodeConfig) -> DeployBuffer {
+        DeployBuffer::new(config, Box::new(DefaultGasEstimator), &Registry::new())
+            .expect("should create deploy buffer")
     }
 
     #[test]
@@ -335,7 +1024,7 @@ mod tests {
         let block_time3 = Timestamp::from(220);
 
         let no_blocks = HashSet::new();
-        let mut buffer = DeployBuffer::new(NodeConfig::default().block_max_deploy_count as usize);
+        let mut buffer = new_buffer(&NodeConfig::default());
         let mut rng = TestRng::new();
         let (hash1, deploy1) = generate_deploy(&mut rng, creation_time, ttl, vec![]);
         let (hash2, deploy2) = generate_deploy(&mut rng, creation_time, ttl, vec![]);
@@ -347,8 +1036,8 @@ mod tests {
             .is_empty());
 
         // add two deploys
-        buffer.add_deploy(hash1, deploy1);
-        buffer.add_deploy(hash2, deploy2.clone());
+        buffer.add_deploy(hash1, deploy1, DEPLOY_SIZE);
+        buffer.add_deploy(hash2, deploy2.clone(), DEPLOY_SIZE);
 
         // if we try to create a block with a timestamp that is too early, we shouldn't get any
         // deploys
@@ -392,7 +1081,7 @@ mod tests {
             .is_empty());
 
         // try adding the same deploy again
-        buffer.add_deploy(hash2, deploy2.clone());
+        buffer.add_deploy(hash2, deploy2.clone(), DEPLOY_SIZE);
 
         // it shouldn't be returned if we include block 1 in the past blocks
         assert!(buffer
@@ -407,14 +1096,14 @@ mod tests {
         );
 
         // the previous check removed the deploy from the buffer, let's re-add it
-        buffer.add_deploy(hash2, deploy2);
+        buffer.add_deploy(hash2, deploy2, DEPLOY_SIZE);
 
         // finalize the block
         buffer.finalized_block(block_hash1);
 
         // add more deploys
-        buffer.add_deploy(hash3, deploy3);
-        buffer.add_deploy(hash4, deploy4);
+        buffer.add_deploy(hash3, deploy3, DEPLOY_SIZE);
+        buffer.add_deploy(hash4, deploy4, DEPLOY_SIZE);
 
         let deploys = buffer.remaining_deploys(DeployConfig::default(), block_time2, no_blocks);
 
@@ -424,6 +1113,256 @@ mod tests {
         assert!(deploys.contains(&hash4));
     }
 
+    #[test]
+    fn should_return_unique_deploys_of_orphaned_block() {
+        let creation_time = Timestamp::from(100);
+        let ttl = TimeDiff::from(100);
+        let block_time = Timestamp::from(120);
+
+        let mut buffer = new_buffer(&NodeConfig::default());
+        let mut rng = TestRng::new();
+        let (hash1, deploy1) = generate_deploy(&mut rng, creation_time, ttl, vec![]);
+        let (hash2, deploy2) = generate_deploy(&mut rng, creation_time, ttl, vec![]);
+        let (shared_hash, shared_deploy) = generate_deploy(&mut rng, creation_time, ttl, vec![]);
+        let (expired_hash, expired_deploy) =
+            generate_deploy(&mut rng, creation_time, TimeDiff::from(10), vec![]);
+
+        // All four deploys are finalized in the block which will be orphaned.
+        buffer.add_deploy(hash1, deploy1, DEPLOY_SIZE);
+        buffer.add_deploy(hash2, deploy2, DEPLOY_SIZE);
+        buffer.add_deploy(shared_hash, shared_deploy.clone(), DEPLOY_SIZE);
+        buffer.add_deploy(expired_hash, expired_deploy, DEPLOY_SIZE);
+        let proto_block = ProtoBlock::new(vec![hash1, hash2, shared_hash, expired_hash], false);
+        buffer.added_block(*proto_block.hash(), proto_block.deploys().clone());
+
+        // The shared deploy is also included in a finalized block of the fork.
+        buffer.add_deploy(shared_hash, shared_deploy, DEPLOY_SIZE);
+        let fork_block_hash = ProtoBlockHash::new(hash(random::<[u8; 16]>()));
+        buffer.added_block(fork_block_hash, vec![shared_hash]);
+        buffer.finalized_block(fork_block_hash);
+        buffer.finalized_block(*proto_block.hash());
+        assert!(buffer.pending_deploys().is_empty());
+
+        let finalized_block = FinalizedBlock::new(
+            proto_block,
+            block_time,
+            vec![],
+            false,
+            EraId(0),
+            0,
+            PublicKey::random(&mut rng),
+        );
+        let block = Block::new(
+            BlockHash::new(Digest::random(&mut rng)),
+            Digest::random(&mut rng),
+            finalized_block,
+        );
+        assert!(buffer.orphaned_finalized_block(&block, block_time));
+
+        // Only the deploys not included elsewhere and not expired by the time the block is
+        // orphaned are pending again, and can be proposed.
+        let mut pending: Vec<_> = buffer
+            .pending_deploys()
+            .into_iter()
+            .map(|pending_deploy| pending_deploy.hash)
+            .collect();
+        pending.sort();
+        let mut expected = vec![hash1, hash2];
+        expected.sort();
+        assert_eq!(pending, expected);
+        let deploys = buffer.remaining_deploys(DeployConfig::default(), block_time, HashSet::new());
+        assert_eq!(deploys, expected.into_iter().collect::<HashSet<_>>());
+
+        // Orphaning the block again doesn't duplicate them.
+        assert!(!buffer.orphaned_finalized_block(&block, block_time));
+        assert_eq!(buffer.pending_deploys().len(), 2);
+    }
+
+    #[test]
+    fn should_rebroadcast_aged_pending_deploys_only() {
+        let creation_time = Timestamp::now();
+        let ttl = TimeDiff::from(24 * 60 * 60 * 1000);
+
+        let config = NodeConfig::default();
+        let mut buffer = new_buffer(&config);
+        let mut rng = TestRng::new();
+        let (pending_hash, pending_deploy) = generate_deploy(&mut rng, creation_time, ttl, vec![]);
+        let (included_hash, included_deploy) =
+            generate_deploy(&mut rng, creation_time, ttl, vec![]);
+
+        buffer.add_deploy(pending_hash, pending_deploy, DEPLOY_SIZE);
+        buffer.add_deploy(included_hash, included_deploy, DEPLOY_SIZE);
+
+        // `included_deploy` is included in a block.
+        let block_hash = ProtoBlockHash::new(hash(random::<[u8; 16]>()));
+        buffer.added_block(block_hash, vec![included_hash]);
+
+        // Nothing should be rebroadcast before the threshold has elapsed.
+        let threshold = TimeDiff::from(config.deploy_rebroadcast_threshold_secs * 1000);
+        let mut now = Timestamp::now() + threshold;
+        assert!(buffer.deploys_to_rebroadcast(now - threshold).is_empty());
+
+        // Once aged, only the pending deploy should be rebroadcast, and only up to the limit.
+        for _ in 0..config.deploy_max_rebroadcasts {
+            assert_eq!(buffer.deploys_to_rebroadcast(now), vec![pending_hash]);
+            // Not again until another threshold has elapsed.
+            assert!(buffer.deploys_to_rebroadcast(now).is_empty());
+            now += threshold;
+        }
+        assert!(buffer.deploys_to_rebroadcast(now).is_empty());
+
+        // A finalized deploy should be forgotten entirely.
+        buffer.finalized_block(block_hash);
+        assert!(!buffer.rebroadcasts.contains_key(&included_hash));
+    }
+
+    #[test]
+    fn should_not_rebroadcast_deploys_close_to_expiry() {
+        let config = NodeConfig::default();
+        let mut buffer = new_buffer(&config);
+        let mut rng = TestRng::new();
+        let threshold_millis = config.deploy_rebroadcast_threshold_secs * 1000;
+        let margin_millis = config.deploy_rebroadcast_expiry_margin_secs * 1000;
+        let creation_time = Timestamp::now();
+
+        // One deploy with ample TTL, one expiring within the margin and one expired already.
+        let ample_ttl = TimeDiff::from(threshold_millis + margin_millis + 60_000);
+        let (ample_hash, ample_deploy) =
+            generate_deploy(&mut rng, creation_time, ample_ttl, vec![]);
+        let closing_ttl = TimeDiff::from(threshold_millis + margin_millis - 1);
+        let (closing_hash, closing_deploy) =
+            generate_deploy(&mut rng, creation_time, closing_ttl, vec![]);
+        let (expired_hash, expired_deploy) =
+            generate_deploy(&mut rng, creation_time, TimeDiff::from(1_000), vec![]);
+
+        buffer.add_deploy(ample_hash, ample_deploy, DEPLOY_SIZE);
+        buffer.add_deploy(closing_hash, closing_deploy.clone(), DEPLOY_SIZE);
+        buffer.add_deploy(expired_hash, expired_deploy.clone(), DEPLOY_SIZE);
+
+        let now = Timestamp::now() + TimeDiff::from(threshold_millis);
+        assert_eq!(buffer.deploys_to_rebroadcast(now), vec![ample_hash]);
+        // The bookkeeping of the expired deploy is dropped, that of the others kept.
+        assert!(!buffer.rebroadcasts.contains_key(&expired_hash));
+        assert!(buffer.rebroadcasts.contains_key(&closing_hash));
+
+        // Without a margin, the deploy close to expiry is rebroadcast, but the expired one never
+        // is.
+        let config = NodeConfig {
+            deploy_rebroadcast_expiry_margin_secs: 0,
+            ..NodeConfig::default()
+        };
+        let mut buffer = new_buffer(&config);
+        buffer.add_deploy(closing_hash, closing_deploy, DEPLOY_SIZE);
+        buffer.add_deploy(expired_hash, expired_deploy, DEPLOY_SIZE);
+
+        let now = Timestamp::now() + TimeDiff::from(threshold_millis);
+        assert_eq!(buffer.deploys_to_rebroadcast(now), vec![closing_hash]);
+        assert_eq!(buffer.rebroadcasts.len(), 1);
+    }
+
+    #[test]
+    fn should_evict_lowest_fee_deploys_when_full() {
+        let creation_time = Timestamp::from(100);
+        let ttl = TimeDiff::from(100);
+        let mut rng = TestRng::new();
+        let mut generate = |gas_price| {
+            let deploy =
+                generate_deploy_with_gas_price(&mut rng, creation_time, ttl, vec![], gas_price);
+            let size = deploy.serialized_size().expect("should serialize deploy");
+            (*deploy.id(), deploy.take_header(), size)
+        };
+        let (cheap_hash, cheap_deploy, cheap_size) = generate(1);
+        let (medium_hash, medium_deploy, medium_size) = generate(2);
+        let (pricey_hash, pricey_deploy, pricey_size) = generate(3);
+
+        let mut config = NodeConfig::default();
+        config.deploy_buffer_max_count = 2;
+        let mut buffer = new_buffer(&config);
+        assert_eq!(
+            buffer.add_deploy(medium_hash, medium_deploy, medium_size),
+            Insertion::Added
+        );
+        assert_eq!(
+            buffer.add_deploy(cheap_hash, cheap_deploy, cheap_size),
+            Insertion::Added
+        );
+
+        // The buffer is full, so the cheapest deploy should make room for the pricey one.
+        assert_eq!(
+            buffer.add_deploy(pricey_hash, pricey_deploy, pricey_size),
+            Insertion::Added
+        );
+        assert_eq!(buffer.collected_deploys.len(), 2);
+        assert!(!buffer.collected_deploys.contains_key(&cheap_hash));
+        assert!(!buffer.rebroadcasts.contains_key(&cheap_hash));
+        assert_eq!(buffer.pending_bytes, (medium_size + pricey_size) as u64);
+        assert_eq!(buffer.metrics.pending_count.get(), 2);
+        assert_eq!(
+            buffer.metrics.pending_bytes.get(),
+            (medium_size + pricey_size) as i64
+        );
+    }
+
+    #[test]
+    fn should_reject_deploy_which_cannot_displace_any() {
+        let creation_time = Timestamp::from(100);
+        let ttl = TimeDiff::from(100);
+        let mut rng = TestRng::new();
+        let mut generate = |gas_price| {
+            let deploy =
+                generate_deploy_with_gas_price(&mut rng, creation_time, ttl, vec![], gas_price);
+            let size = deploy.serialized_size().expect("should serialize deploy");
+            (*deploy.id(), deploy.take_header(), size)
+        };
+        let (first_hash, first_deploy, first_size) = generate(5);
+        let (second_hash, second_deploy, second_size) = generate(5);
+        let (cheap_hash, cheap_deploy, cheap_size) = generate(1);
+        let (equal_hash, equal_deploy, equal_size) = generate(5);
+        let (huge_hash, huge_deploy, _) = generate(u64::MAX);
+
+        // Limit the buffer by size such that exactly two deploys fit.
+        let mut config = NodeConfig::default();
+        config.deploy_buffer_max_bytes = (first_size + second_size) as u64;
+        let mut buffer = new_buffer(&config);
+        assert_eq!(
+            buffer.add_deploy(first_hash, first_deploy, first_size),
+            Insertion::Added
+        );
+        assert_eq!(
+            buffer.add_deploy(second_hash, second_deploy, second_size),
+            Insertion::Added
+        );
+
+        // Neither a cheaper deploy nor one with an equal gas price can displace a pending one.
+        assert_eq!(
+            buffer.add_deploy(cheap_hash, cheap_deploy, cheap_size),
+            Insertion::Refused
+        );
+        assert_eq!(
+            buffer.add_deploy(equal_hash, equal_deploy, equal_size),
+            Insertion::Refused
+        );
+        assert_eq!(buffer.collected_deploys.len(), 2);
+        assert!(buffer.collected_deploys.contains_key(&first_hash));
+        assert!(buffer.collected_deploys.contains_key(&second_hash));
+        assert!(!buffer.rebroadcasts.contains_key(&cheap_hash));
+
+        // A deploy larger than the whole buffer is rejected regardless of its gas price.
+        let huge_size = config.deploy_buffer_max_bytes as usize + 1;
+        assert_eq!(
+            buffer.add_deploy(huge_hash, huge_deploy, huge_size),
+            Insertion::Refused
+        );
+
+        // A deploy already pending needs no further room.
+        let first_deploy = buffer.collected_deploys[&first_hash].clone();
+        assert_eq!(
+            buffer.add_deploy(first_hash, first_deploy, first_size),
+            Insertion::Known
+        );
+        assert_eq!(buffer.collected_deploys.len(), 2);
+    }
+
     #[test]
     fn test_deploy_dependencies() {
         let creation_time = Timestamp::from(100);
@@ -436,10 +1375,10 @@ mod tests {
         let (hash2, deploy2) = generate_deploy(&mut rng, creation_time, ttl, vec![hash1]);
 
         let mut blocks = HashSet::new();
-        let mut buffer = DeployBuffer::new(NodeConfig::default().block_max_deploy_count as usize);
+        let mut buffer = new_buffer(&NodeConfig::default());
 
         // add deploy2
-        buffer.add_deploy(hash2, deploy2);
+        buffer.add_deploy(hash2, deploy2, DEPLOY_SIZE);
 
         // deploy2 has an unsatisfied dependency
         assert!(buffer
@@ -447,7 +1386,7 @@ mod tests {
             .is_empty());
 
         // add deploy1
-        buffer.add_deploy(hash1, deploy1);
+        buffer.add_deploy(hash1, deploy1, DEPLOY_SIZE);
 
         let deploys = buffer.remaining_deploys(DeployConfig::default(), block_time, blocks.clone());
         // only deploy1 should be returned, as it has no dependencies
@@ -464,4 +1403,242 @@ mod tests {
         assert_eq!(deploys2.len(), 1);
         assert!(deploys2.contains(&hash2));
     }
+
+    #[test]
+    fn should_list_candidates_with_pending_dependencies() {
+        let creation_time = Timestamp::from(100);
+        let ttl = TimeDiff::from(100);
+        let block_time = Timestamp::from(120);
+
+        let mut rng = TestRng::new();
+        let (hash1, deploy1) = generate_deploy(&mut rng, creation_time, ttl, vec![]);
+        let (hash2, deploy2) = generate_deploy(&mut rng, creation_time, ttl, vec![hash1]);
+        let unknown_hash = DeployHash::new(hash(random::<[u8; 16]>()));
+        let (hash3, deploy3) = generate_deploy(&mut rng, creation_time, ttl, vec![unknown_hash]);
+
+        let mut buffer = new_buffer(&NodeConfig::default());
+        buffer.add_deploy(hash2, deploy2, DEPLOY_SIZE);
+        buffer.add_deploy(hash1, deploy1, DEPLOY_SIZE);
+        buffer.add_deploy(hash3, deploy3, DEPLOY_SIZE);
+
+        // deploy2 can be proposed along with deploy1, but deploy3's dependency is unknown.
+        let mut candidates =
+            buffer.proposal_candidates(DeployConfig::default(), block_time, HashSet::new());
+        candidates.sort_by_key(|candidate| candidate.pending_dependencies.len());
+        assert_eq!(candidates.len(), 2);
+        assert_eq!(candidates[0].hash, hash1);
+        assert_eq!(candidates[1].hash, hash2);
+        assert_eq!(candidates[1].pending_dependencies, vec![hash1]);
+        assert_eq!(candidates[1].size, DEPLOY_SIZE as u64);
+
+        // Once deploy1 is in a past block, deploy2's dependency is no longer pending.
+        let block_hash = ProtoBlockHash::new(hash(random::<[u8; 16]>()));
+        buffer.added_block(block_hash, vec![hash1]);
+        let blocks = vec![block_hash].into_iter().collect();
+        let candidates = buffer.proposal_candidates(DeployConfig::default(), block_time, blocks);
+        assert_eq!(candidates.len(), 1);
+        assert_eq!(candidates[0].hash, hash2);
+        assert!(candidates[0].pending_dependencies.is_empty());
+    }
+
+    #[test]
+    fn should_list_pending_deploys() {
+        let creation_time = Timestamp::from(100);
+        let ttl = TimeDiff::from(100);
+        let mut rng = TestRng::new();
+        let mut buffer = new_buffer(&NodeConfig::default());
+        assert!(buffer.pending_deploys().is_empty());
+
+        let cheap = generate_deploy_with_gas_price(&mut rng, creation_time, ttl, vec![], 1);
+        let pricey = generate_deploy_with_gas_price(&mut rng, creation_time, ttl, vec![], 5);
+        let cheap_hash = *cheap.id();
+        let pricey_hash = *pricey.id();
+        buffer.add_deploy(cheap_hash, cheap.take_header(), DEPLOY_SIZE);
+        buffer.add_deploy(pricey_hash, pricey.take_header(), DEPLOY_SIZE);
+
+        let mut pending = buffer.pending_deploys();
+        pending.sort_unstable_by_key(|deploy| deploy.gas_price);
+        let expected = |hash, gas_price| PendingDeploy {
+            hash,
+            gas_price,
+            timestamp: creation_time,
+        };
+        assert_eq!(
+            pending,
+            vec![expected(cheap_hash, 1), expected(pricey_hash, 5)]
+        );
+
+        // Deploys included in a proposed block are no longer pending.
+        let block_hash = ProtoBlockHash::new(hash(random::<[u8; 16]>()));
+        buffer.added_block(block_hash, vec![pricey_hash]);
+        assert_eq!(buffer.pending_deploys(), vec![expected(cheap_hash, 1)]);
+    }
+
+    /// Estimates the gas of deploys according to a fixed table.
+    #[derive(Debug)]
+    struct FixedGasEstimates(HashMap<DeployHash, u64>);
+
+    impl GasEstimator for FixedGasEstimates {
+        fn estimate(&self, deploy: &Deploy) -> u64 {
+            self.0[deploy.id()]
+        }
+    }
+
+    #[test]
+    fn should_order_candidates_by_custom_gas_estimates() {
+        let creation_time = Timestamp::from(100);
+        let ttl = TimeDiff::from(100);
+        let block_time = Timestamp::from(120);
+        let mut rng = TestRng::new();
+        let config = NodeConfig {
+            block_max_deploy_count: 1,
+            ..NodeConfig::default()
+        };
+
+        // The cheap deploy is estimated to consume so much more gas that its fee is higher.
+        let cheap = generate_deploy_with_gas_price(&mut rng, creation_time, ttl, vec![], 1);
+        let pricey = generate_deploy_with_gas_price(&mut rng, creation_time, ttl, vec![], 5);
+        let estimates = vec![(*cheap.id(), 1_000), (*pricey.id(), 10)];
+        let mut custom_buffer = DeployBuffer::new(
+            &config,
+            Box::new(FixedGasEstimates(estimates.into_iter().collect())),
+            &Registry::new(),
+        )
+        .unwrap();
+        let mut default_buffer = new_buffer(&config);
+
+        for deploy in &[&cheap, &pricey] {
+            // Each buffer gets its own copy, as the estimate is cached on the deploy.
+            assert_eq!(
+                default_buffer.buffer_deploy(&(*deploy).clone()),
+                Insertion::Added
+            );
+            assert_eq!(custom_buffer.buffer_deploy(deploy), Insertion::Added);
+        }
+        assert_eq!(cheap.gas_estimate(|_| unreachable!()), 1_000);
+
+        let candidates =
+            default_buffer.remaining_deploys(DeployConfig::default(), block_time, HashSet::new());
+        assert_eq!(candidates, vec![*pricey.id()].into_iter().collect());
+        let candidates =
+            custom_buffer.remaining_deploys(DeployConfig::default(), block_time, HashSet::new());
+        assert_eq!(candidates, vec![*cheap.id()].into_iter().collect());
+    }
+
+    #[test]
+    fn should_keep_size_and_gas_estimate_of_orphaned_deploys() {
+        let creation_time = Timestamp::from(100);
+        let ttl = TimeDiff::from(100);
+        let block_time = Timestamp::from(120);
+        let mut rng = TestRng::new();
+
+        let deploy = generate_deploy_with_gas_price(&mut rng, creation_time, ttl, vec![], 10);
+        let deploy_hash = *deploy.id();
+        let size = deploy.serialized_size().unwrap() as u64;
+        let estimates = vec![(deploy_hash, 1_000)];
+        let mut buffer = DeployBuffer::new(
+            &NodeConfig::default(),
+            Box::new(FixedGasEstimates(estimates.into_iter().collect())),
+            &Registry::new(),
+        )
+        .unwrap();
+        assert_eq!(buffer.buffer_deploy(&deploy), Insertion::Added);
+
+        let proto_block = ProtoBlock::new(vec![deploy_hash], false);
+        buffer.added_block(*proto_block.hash(), proto_block.deploys().clone());
+        buffer.finalized_block(*proto_block.hash());
+        assert_eq!(buffer.pending_bytes, 0);
+
+        let finalized_block = FinalizedBlock::new(
+            proto_block,
+            block_time,
+            vec![],
+            false,
+            EraId(0),
+            0,
+            PublicKey::random(&mut rng),
+        );
+        let block = Block::new(
+            BlockHash::new(Digest::random(&mut rng)),
+            Digest::random(&mut rng),
+            finalized_block,
+        );
+        assert!(buffer.orphaned_finalized_block(&block, block_time));
+
+        // The orphaned deploy counts towards the buffer's and the next block's limits as before.
+        assert_eq!(buffer.pending_bytes, size);
+        let candidates =
+            buffer.proposal_candidates(DeployConfig::default(), block_time, HashSet::new());
+        assert_eq!(
+            candidates,
+            vec![DeployCandidate {
+                hash: deploy_hash,
+                pending_dependencies: vec![],
+                size,
+                gas_estimate: 1_000,
+            }]
+        );
+    }
+
+    #[test]
+    fn should_restore_unexpired_pending_deploys_after_restart() {
+        let temp_dir = tempfile::tempdir().expect("should get tempdir");
+        let config = NodeConfig {
+            deploy_buffer_persistence_path: Some(temp_dir.path().join("pending_deploys.json")),
+            ..NodeConfig::default()
+        };
+        let mut rng = TestRng::new();
+        let creation_time = Timestamp::from(100_000);
+        let ttl = TimeDiff::from(60_000);
+        let mut generate =
+            |timestamp, ttl| generate_deploy_with_gas_price(&mut rng, timestamp, ttl, vec![], 10);
+        let pending = generate(creation_time, ttl);
+        let proposed = generate(creation_time, ttl);
+        let finalized = generate(creation_time, ttl);
+        let expired = generate(Timestamp::from(1_000), TimeDiff::from(1_000));
+        let deploys = vec![
+            pending.clone(),
+            proposed.clone(),
+            finalized.clone(),
+            expired.clone(),
+        ];
+
+        let mut buffer = new_buffer(&config);
+        for deploy in &deploys {
+            assert_eq!(buffer.buffer_deploy(deploy), Insertion::Added);
+        }
+        let proposed_block = ProtoBlockHash::new(hash(random::<[u8; 16]>()));
+        buffer.added_block(proposed_block, vec![*proposed.id()]);
+        let finalized_block = ProtoBlockHash::new(hash(random::<[u8; 16]>()));
+        buffer.added_block(finalized_block, vec![*finalized.id()]);
+        buffer.finalized_block(finalized_block);
+        buffer.on_stop();
+        drop(buffer);
+
+        // After the restart, all deploys not yet finalized are looked up in storage.  The deploy
+        // included in a proposed block is pending again, as the proposal didn't survive.
+        let mut buffer = new_buffer(&config);
+        assert!(buffer.pending_deploys().is_empty());
+        let mut persisted = buffer.persisted_deploys();
+        persisted.sort();
+        let mut expected = vec![*pending.id(), *proposed.id(), *expired.id()];
+        expected.sort();
+        assert_eq!(persisted, expected);
+
+        // Only the deploys which haven't expired yet are restored.
+        let restored: Vec<_> = deploys
+            .into_iter()
+            .filter(|deploy| persisted.contains(deploy.id()))
+            .collect();
+        assert!(buffer.restore(restored, creation_time + TimeDiff::from(1_000)));
+        let mut pending_hashes: Vec<_> = buffer
+            .pending_deploys()
+            .into_iter()
+            .map(|deploy| deploy.hash)
+            .collect();
+        pending_hashes.sort();
+        let mut expected = vec![*pending.id(), *proposed.id()];
+        expected.sort();
+        assert_eq!(pending_hashes, expected);
+    }
 }
@@ -0,0 +1,36 @@
+//! Estimation of the gas deploys will consume.
+//!
+//! The gas a deploy consumes is only known once it has been executed, but the deploy buffer needs
+//! an estimate beforehand to prioritize pending deploys by the fee they are expected to pay.  How
+//! well the gas can be estimated from a deploy's contents depends on the contracts deployed on the
+//! network, so the estimator can be replaced by a network-specific one.
+
+use std::fmt::Debug;
+
+use crate::types::Deploy;
+
+/// Base gas assumed to be consumed by every deploy.
+const BASE_GAS: u64 = 10_000;
+/// Gas assumed to be consumed per byte of a serialized deploy.
+const GAS_PER_BYTE: u64 = 100;
+
+/// A hook estimating the gas a deploy will consume.
+///
+/// The estimator is consulted once for every deploy entering the deploy buffer; the estimate is
+/// cached on the deploy.
+pub trait GasEstimator: Debug + Send {
+    /// Returns the estimated gas `deploy` will consume.
+    fn estimate(&self, deploy: &Deploy) -> u64;
+}
+
+/// The default estimator, assuming the consumed gas grows with the size of the deploy.
+#[derive(Debug, Default)]
+pub struct DefaultGasEstimator;
+
+impl GasEstimator for DefaultGasEstimator {
+    fn estimate(&self, deploy: &Deploy) -> u64 {
+        // Deploys which fail to serialize never enter the deploy buffer.
+        let size = deploy.serialized_size().unwrap_or_default();
+        BASE_GAS.saturating_add(GAS_PER_BYTE.saturating_mul(size as u64))
+    }
+}
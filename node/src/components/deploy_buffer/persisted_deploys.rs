@@ -0,0 +1,80 @@
+//! Persisted pending deploys.
+//!
+//! The hashes of all deploys not yet finalized, i.e. the pending ones and those included in
+//! proposed blocks, are saved to disk, so that on restart the buffer is refilled with their bodies
+//! from storage instead of starting out empty.  Deploys which have expired or have been included in
+//! a block in the meantime are dropped once restored.
+
+use std::{collections::BTreeSet, fs, io, path::Path};
+
+use serde::{Deserialize, Serialize};
+
+use crate::types::DeployHash;
+
+/// The hashes of the deploys not yet finalized.
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub(super) struct PersistedDeploys {
+    deploys: BTreeSet<DeployHash>,
+}
+
+impl PersistedDeploys {
+    pub(super) fn new<I: IntoIterator<Item = DeployHash>>(deploys: I) -> Self {
+        PersistedDeploys {
+            deploys: deploys.into_iter().collect(),
+        }
+    }
+
+    /// Loads the persisted deploys from `path`.
+    ///
+    /// Returns no deploys if the file does not exist.
+    pub(super) fn load(path: &Path) -> io::Result<Self> {
+        match fs::read(path) {
+            Ok(bytes) => Ok(serde_json::from_slice(&bytes)?),
+            Err(error) if error.kind() == io::ErrorKind::NotFound => {
+                Ok(PersistedDeploys::default())
+            }
+            Err(error) => Err(error),
+        }
+    }
+
+    /// Saves the deploys to `path`, replacing any previous version.
+    pub(super) fn save(&self, path: &Path) -> io::Result<()> {
+        // Write to a temporary file first, so that a crash doesn't leave a truncated file.
+        let temp_path = path.with_extension("tmp");
+        fs::write(&temp_path, serde_json::to_vec(self)?)?;
+        fs::rename(temp_path, path)
+    }
+
+    /// Returns the hashes of the persisted deploys.
+    pub(super) fn into_deploys(self) -> Vec<DeployHash> {
+        self.deploys.into_iter().collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{crypto::hash::Digest, testing::TestRng};
+
+    #[test]
+    fn should_survive_round_trip() {
+        let mut rng = TestRng::new();
+        let temp_dir = tempfile::tempdir().expect("should get tempdir");
+        let path = temp_dir.path().join("pending_deploys.json");
+        assert!(PersistedDeploys::load(&path)
+            .expect("missing file should load")
+            .into_deploys()
+            .is_empty());
+
+        let mut deploys: Vec<_> = (0..3)
+            .map(|_| DeployHash::new(Digest::random(&mut rng)))
+            .collect();
+        PersistedDeploys::new(deploys.clone())
+            .save(&path)
+            .expect("should save");
+
+        deploys.sort();
+        let loaded = PersistedDeploys::load(&path).expect("should load");
+        assert_eq!(loaded.into_deploys(), deploys);
+    }
+}
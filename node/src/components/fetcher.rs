@@ -1,10 +1,13 @@
+mod balancer;
 mod event;
+mod limiter;
 mod tests;
 
 use std::{collections::HashMap, fmt::Debug, time::Duration};
 
 use rand::{CryptoRng, Rng};
 use smallvec::smallvec;
+use tokio::time::Instant;
 use tracing::{debug, error};
 
 use crate::{
@@ -20,7 +23,9 @@ use crate::{
     GossipConfig,
 };
 
+use balancer::PeerBalancer;
 pub use event::{Event, FetchResult};
+use limiter::FetchLimiter;
 
 /// A helper trait constraining `Fetcher` compatible reactor events.
 pub trait ReactorEventT<T>:
@@ -52,6 +57,30 @@ pub trait ItemFetcher<T: Item + 'static> {
 
     fn peer_timeout(&self) -> Duration;
 
+    /// Returns whether fetching the item from a peer has failed recently, in which case the
+    /// fetch should fail immediately rather than ask a peer again.
+    fn has_recently_failed(&mut self, _id: &T::Id) -> bool {
+        false
+    }
+
+    /// Records that fetching the item from a peer has failed.
+    fn record_failure(&mut self, _id: T::Id) {}
+
+    /// Returns the limiter of concurrent fetches from peers, if the number is limited.
+    fn limiter(&mut self) -> Option<&mut FetchLimiter<T::Id>> {
+        None
+    }
+
+    /// Returns the balancer spreading fetches across peers, if fetches are balanced.
+    fn balancer(&mut self) -> Option<&mut PeerBalancer<T::Id>> {
+        None
+    }
+
+    /// Returns the peer to send the fetch of the item requested from `peer` to.
+    fn choose_peer(&mut self, _id: T::Id, peer: NodeId) -> NodeId {
+        peer
+    }
+
     /// We've been asked to fetch the item by another component of this node.  We'll try to get it
     /// from our own storage component first, and if that fails, we'll send a request to `peer` for
     /// the item.
@@ -70,6 +99,9 @@ pub trait ItemFetcher<T: Item + 'static> {
             .entry(peer)
             .or_default()
             .push(responder);
+        if let Some(balancer) = self.balancer() {
+            balancer.add_holder(id, peer);
+        }
 
         // Get the item from the storage component.
         self.get_from_storage(effect_builder, id, peer)
@@ -100,6 +132,57 @@ pub trait ItemFetcher<T: Item + 'static> {
         effect_builder: EffectBuilder<REv>,
         id: T::Id,
         peer: NodeId,
+    ) -> Effects<Event<T>> {
+        if self.has_recently_failed(&id) {
+            debug!(%id, "not fetching item which recently failed to be fetched");
+            return self.signal(id, None, peer);
+        }
+        let target = self.choose_peer(id, peer);
+        if target != peer {
+            debug!(%id, requested = %peer, %target, "fetching from another peer to spread load");
+            self.move_responders(id, peer, target);
+        }
+        self.start_fetch(effect_builder, id, target)
+    }
+
+    /// Sends a request for the item to `peer` if a slot is free, or queues the fetch otherwise.
+    fn start_fetch<REv: ReactorEventT<T>>(
+        &mut self,
+        effect_builder: EffectBuilder<REv>,
+        id: T::Id,
+        peer: NodeId,
+    ) -> Effects<Event<T>> {
+        if let Some(limiter) = self.limiter() {
+            if limiter.is_in_flight(id, peer) {
+                // The request in flight answers this fetch as well.
+                debug!(%id, %peer, "already fetching item from peer");
+                return Effects::new();
+            }
+            if !limiter.try_start(id, peer) {
+                debug!(%id, %peer, "queueing fetch until a slot is free");
+                return effect_builder
+                    .set_timeout(limiter.queue_timeout())
+                    .event(move |_| Event::TimeoutQueued { id, peer });
+            }
+        }
+        self.request_from_peer(effect_builder, id, peer)
+    }
+
+    /// Moves the responders waiting for the item from `from` to `to`.
+    fn move_responders(&mut self, id: T::Id, from: NodeId, to: NodeId) {
+        if let Some(responders) = self.responders().get_mut(&id) {
+            if let Some(moved) = responders.remove(&from) {
+                responders.entry(to).or_default().extend(moved);
+            }
+        }
+    }
+
+    /// Sends a request for the item to `peer`.
+    fn request_from_peer<REv: ReactorEventT<T>>(
+        &mut self,
+        effect_builder: EffectBuilder<REv>,
+        id: T::Id,
+        peer: NodeId,
     ) -> Effects<Event<T>> {
         match Message::new_get_request::<T>(&id) {
             Ok(message) => {
@@ -120,6 +203,64 @@ pub trait ItemFetcher<T: Item + 'static> {
         }
     }
 
+    /// Handles the timeout for getting the item from `peer`, recording a failure if the peer hasn't
+    /// provided it in time.
+    ///
+    /// If the fetch was sent to `peer` in place of the peer it was requested from, the item is
+    /// fetched from the requested peer instead.
+    fn timed_out<REv: ReactorEventT<T>>(
+        &mut self,
+        effect_builder: EffectBuilder<REv>,
+        id: T::Id,
+        peer: NodeId,
+    ) -> Effects<Event<T>> {
+        let failed = self
+            .responders()
+            .get(&id)
+            .map_or(false, |responders| responders.contains_key(&peer));
+        if failed {
+            let requested = self.balancer().and_then(|balancer| {
+                let requested = balancer.take_substituted(id, peer);
+                balancer.failed(id, peer);
+                requested
+            });
+            if let Some(requested) = requested {
+                debug!(%id, %peer, %requested, "falling back to fetching from requested peer");
+                if let Some(limiter) = self.limiter() {
+                    limiter.finish(&id, Some(peer));
+                }
+                self.move_responders(id, peer, requested);
+                return self.start_fetch(effect_builder, id, requested);
+            }
+            self.record_failure(id);
+        }
+        self.signal(id, None, peer)
+    }
+
+    /// Handles the timeout for a fetch waiting for a free slot, failing it if it's still queued.
+    fn queue_timed_out(&mut self, id: T::Id, peer: NodeId) -> Effects<Event<T>> {
+        let expired = self
+            .limiter()
+            .map_or(false, |limiter| limiter.expire_queued(&id, peer));
+        if !expired {
+            return Effects::new();
+        }
+        debug!(%id, %peer, "queued fetch timed out");
+        self.signal(id, None, peer)
+    }
+
+    /// Sends requests for queued fetches as long as there are free slots.
+    fn start_queued<REv: ReactorEventT<T>>(
+        &mut self,
+        effect_builder: EffectBuilder<REv>,
+    ) -> Effects<Event<T>> {
+        let mut effects = Effects::new();
+        while let Some((id, peer)) = self.limiter().and_then(FetchLimiter::next_queued) {
+            effects.extend(self.request_from_peer(effect_builder, id, peer));
+        }
+        effects
+    }
+
     /// Handles signalling responders with the item or `None`.
     fn signal(
         &mut self,
@@ -127,6 +268,18 @@ pub trait ItemFetcher<T: Item + 'static> {
         result: Option<FetchResult<T>>,
         peer: NodeId,
     ) -> Effects<Event<T>> {
+        if let Some(limiter) = self.limiter() {
+            let finished_peer = if result.is_some() { None } else { Some(peer) };
+            limiter.finish(&id, finished_peer);
+        }
+        if let Some(balancer) = self.balancer() {
+            if result.is_some() {
+                balancer.finish(&id);
+            } else {
+                balancer.failed(id, peer);
+            }
+        }
+
         let mut effects = Effects::new();
         let mut all_responders = self.responders().remove(&id).unwrap_or_default();
         match result {
@@ -147,6 +300,8 @@ pub trait ItemFetcher<T: Item + 'static> {
                 }
                 if !all_responders.is_empty() {
                     self.responders().insert(id, all_responders);
+                } else if let Some(balancer) = self.balancer() {
+                    balancer.finish(&id);
                 }
             }
         }
@@ -159,6 +314,15 @@ pub trait ItemFetcher<T: Item + 'static> {
 pub(crate) struct Fetcher<T: Item + 'static> {
     get_from_peer_timeout: Duration,
     responders: HashMap<T::Id, HashMap<NodeId, Vec<FetchResponder<T>>>>,
+    /// The duration for which failures to fetch an item are remembered.
+    failure_cache_duration: Duration,
+    /// The items which recently failed to be fetched, with the time at which to forget the
+    /// failure.
+    failed: HashMap<T::Id, Instant>,
+    /// The limiter of concurrent fetches from peers.
+    limiter: FetchLimiter<T::Id>,
+    /// The balancer spreading fetches across peers, or `None` if fetches aren't balanced.
+    balancer: Option<PeerBalancer<T::Id>>,
 }
 
 impl<T: Item> Fetcher<T> {
@@ -166,6 +330,18 @@ impl<T: Item> Fetcher<T> {
         Fetcher {
             get_from_peer_timeout: Duration::from_secs(config.get_remainder_timeout_secs()),
             responders: HashMap::new(),
+            failure_cache_duration: Duration::from_secs(config.fetch_failure_cache_secs()),
+            failed: HashMap::new(),
+            limiter: FetchLimiter::new(
+                config.max_concurrent_fetches(),
+                config.max_concurrent_fetches_per_peer(),
+                Duration::from_secs(config.fetch_queue_timeout_secs()),
+            ),
+            balancer: if config.balance_fetches() {
+                Some(PeerBalancer::new())
+            } else {
+                None
+            },
         }
     }
 }
@@ -181,6 +357,47 @@ impl ItemFetcher<Deploy> for Fetcher<Deploy> {
         self.get_from_peer_timeout
     }
 
+    fn has_recently_failed(&mut self, id: &DeployHash) -> bool {
+        match self.failed.get(id) {
+            Some(expiry) if *expiry > Instant::now() => true,
+            Some(_) => {
+                let _ = self.failed.remove(id);
+                false
+            }
+            None => false,
+        }
+    }
+
+    fn record_failure(&mut self, id: DeployHash) {
+        if self.failure_cache_duration == Duration::from_secs(0) {
+            return;
+        }
+        let now = Instant::now();
+        self.failed.retain(|_, expiry| *expiry > now);
+        let _ = self.failed.insert(id, now + self.failure_cache_duration);
+    }
+
+    fn limiter(&mut self) -> Option<&mut FetchLimiter<DeployHash>> {
+        Some(&mut self.limiter)
+    }
+
+    fn balancer(&mut self) -> Option<&mut PeerBalancer<DeployHash>> {
+        self.balancer.as_mut()
+    }
+
+    fn choose_peer(&mut self, id: DeployHash, peer: NodeId) -> NodeId {
+        let limiter = &self.limiter;
+        match self.balancer.as_mut() {
+            Some(balancer) => balancer.choose(
+                id,
+                peer,
+                |candidate| limiter.in_flight_from(candidate),
+                |candidate| limiter.is_in_flight(id, *candidate),
+            ),
+            None => peer,
+        }
+    }
+
     /// Gets a `Deploy` from the storage component.
     fn get_from_storage<REv: ReactorEventT<Deploy>>(
         &mut self,
@@ -190,14 +407,22 @@ impl ItemFetcher<Deploy> for Fetcher<Deploy> {
     ) -> Effects<Event<Deploy>> {
         effect_builder
             .get_deploys_from_storage(smallvec![id])
-            .event(move |mut results| Event::GetFromStorageResult {
+            .event(move |result| Event::GetFromStorageResult {
                 id,
                 peer,
-                maybe_item: Box::new(results.pop().expect("can only contain one result")),
+                // If storage failed, the node is shutting down, so fetching from the peer instead
+                // doesn't matter.
+                maybe_item: Box::new(
+                    result.ok().and_then(|mut results| {
+                        results.pop().expect("can only contain one result")
+                    }),
+                ),
             })
     }
 }
 
+// Failures to fetch blocks aren't cached, since the linear chain sync retries with another peer
+// straight away.  Neither are concurrent fetches of blocks limited or balanced across peers.
 impl ItemFetcher<Block> for Fetcher<Block> {
     fn responders(
         &mut self,
@@ -241,7 +466,7 @@ where
         event: Self::Event,
     ) -> Effects<Self::Event> {
         debug!(?event, "handling event");
-        match event {
+        let mut effects = match event {
             Event::Fetch {
                 id,
                 peer,
@@ -266,7 +491,23 @@ where
                     }
                 }
             }
-            Event::TimeoutPeer { id, peer } => self.signal(id, None, peer),
-        }
+            Event::TimeoutPeer { id, peer } => self.timed_out(effect_builder, id, peer),
+            Event::TimeoutQueued { id, peer } => self.queue_timed_out(id, peer),
+            Event::PeerConnected(peer) => {
+                if let Some(balancer) = self.balancer() {
+                    balancer.peer_connected(peer);
+                }
+                Effects::new()
+            }
+            Event::PeerDisconnected(peer) => {
+                if let Some(balancer) = self.balancer() {
+                    balancer.peer_disconnected(&peer);
+                }
+                Effects::new()
+            }
+        };
+        // Any of the above may have freed a slot for a queued fetch.
+        effects.extend(self.start_queued(effect_builder));
+        effects
     }
 }
@@ -0,0 +1,117 @@
+//! Spreads fetches across the peers known to hold the items.
+//!
+//! Fetches name the peer known to hold the item, and the same item is often requested from several
+//! peers, e.g. when several peers relay a proposal whose deploys we are missing.  Instead of
+//! sending the fetch to whichever peer was named first, it is sent to the connected holder with
+//! the fewest fetches in flight, taking turns between equally loaded ones.  Only peers an item was
+//! requested from are considered holders of it, since any other peer would just let the request
+//! time out.  If the chosen peer fails to provide the item in time anyway, it is fetched from the
+//! requested peer after all.
+
+use std::{
+    collections::{HashMap, HashSet},
+    hash::Hash,
+};
+
+use crate::small_network::NodeId;
+
+/// Chooses the peers to send fetches to.
+#[derive(Debug)]
+pub struct PeerBalancer<I> {
+    /// The connected peers, in the order they take turns in.
+    peers: Vec<NodeId>,
+    /// The peers known to hold each item being fetched.
+    holders: HashMap<I, HashSet<NodeId>>,
+    /// The index of the peer whose turn it is next.
+    next: usize,
+    /// The peers fetches were requested from, by item and the peer they were sent to instead.
+    substituted: HashMap<(I, NodeId), NodeId>,
+}
+
+impl<I: Copy + Eq + Hash> PeerBalancer<I> {
+    /// Creates a balancer without any connected peers.
+    pub(super) fn new() -> Self {
+        PeerBalancer {
+            peers: Vec::new(),
+            holders: HashMap::new(),
+            next: 0,
+            substituted: HashMap::new(),
+        }
+    }
+
+    /// Adds `peer` to the connected peers.
+    pub(super) fn peer_connected(&mut self, peer: NodeId) {
+        if !self.peers.contains(&peer) {
+            self.peers.push(peer);
+        }
+    }
+
+    /// Removes `peer` from the connected peers.
+    pub(super) fn peer_disconnected(&mut self, peer: &NodeId) {
+        self.peers.retain(|other| other != peer);
+    }
+
+    /// Records that `id` has been requested from `peer`, which must therefore hold it.
+    pub(super) fn add_holder(&mut self, id: I, peer: NodeId) {
+        let _ = self.holders.entry(id).or_default().insert(peer);
+    }
+
+    /// Chooses the peer to send the fetch of `id` requested from `requested` to.
+    ///
+    /// A holder `id` is already being fetched from is chosen to avoid duplicate requests.
+    /// Otherwise, the connected holder with the lowest `load`, i.e. the fewest fetches in flight,
+    /// is chosen, where the requested peer is considered a connected holder in any case.
+    pub(super) fn choose<L, F>(&mut self, id: I, requested: NodeId, load: L, in_flight: F) -> NodeId
+    where
+        L: Fn(&NodeId) -> usize,
+        F: Fn(&NodeId) -> bool,
+    {
+        let holders = self.holders.get(&id);
+        let mut candidates: Vec<_> = self
+            .peers
+            .iter()
+            .filter(|peer| holders.map_or(false, |holders| holders.contains(peer)))
+            .copied()
+            .collect();
+        if !candidates.contains(&requested) {
+            candidates.push(requested);
+        }
+        if let Some(peer) = candidates.iter().find(|peer| in_flight(peer)) {
+            return *peer;
+        }
+
+        let count = candidates.len();
+        let start = self.next % count;
+        let (index, chosen) = (0..count)
+            .map(|offset| (start + offset) % count)
+            .map(|index| (index, candidates[index]))
+            .min_by_key(|(_, peer)| load(peer))
+            .expect("there is at least the requested peer");
+        self.next = index + 1;
+        if chosen != requested {
+            let _ = self.substituted.insert((id, chosen), requested);
+        }
+        chosen
+    }
+
+    /// Returns the peer the fetch of `id` sent to `peer` was originally requested from, if it
+    /// wasn't `peer` itself, and forgets the substitution.
+    pub(super) fn take_substituted(&mut self, id: I, peer: NodeId) -> Option<NodeId> {
+        self.substituted.remove(&(id, peer))
+    }
+
+    /// Records that `peer` has failed to provide `id`, so it isn't chosen for it again.
+    pub(super) fn failed(&mut self, id: I, peer: NodeId) {
+        let _ = self.substituted.remove(&(id, peer));
+        if let Some(holders) = self.holders.get_mut(&id) {
+            let _ = holders.remove(&peer);
+        }
+    }
+
+    /// Forgets the holders of `id` and every substitution for fetches of it, e.g. once the item has
+    /// been fetched or no fetch of it is outstanding.
+    pub(super) fn finish(&mut self, id: &I) {
+        let _ = self.holders.remove(id);
+        self.substituted.retain(|(other_id, _), _| other_id != id);
+    }
+}
@@ -38,6 +38,12 @@ pub enum Event<T: Item> {
     },
     /// The timeout has elapsed and we should clean up state.
     TimeoutPeer { id: T::Id, peer: NodeId },
+    /// The timeout for a fetch waiting for a free slot has elapsed.
+    TimeoutQueued { id: T::Id, peer: NodeId },
+    /// A new peer connected, which fetches can be sent to.
+    PeerConnected(NodeId),
+    /// A peer disconnected.
+    PeerDisconnected(NodeId),
 }
 
 impl<T: Item> From<FetcherRequest<NodeId, T>> for Event<T> {
@@ -75,6 +81,13 @@ impl<T: Item> Display for Event<T> {
                 "check get from peer timeout for {} with {}",
                 id, peer
             ),
+            Event::TimeoutQueued { id, peer } => write!(
+                formatter,
+                "check queued fetch timeout for {} with {}",
+                id, peer
+            ),
+            Event::PeerConnected(peer) => write!(formatter, "new peer {} connected", peer),
+            Event::PeerDisconnected(peer) => write!(formatter, "peer {} disconnected", peer),
         }
     }
 }
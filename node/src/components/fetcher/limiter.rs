@@ -0,0 +1,171 @@
+//! Limits the number of items being fetched from peers at the same time.
+//!
+//! A fetch occupies a slot from the moment the request is sent to the peer until the item arrives
+//! or the request times out.  Besides the total number of slots, the number of slots occupied by
+//! fetches from any single peer can be limited.  While no slot is available, further fetches wait
+//! in a queue, in the order they were requested, and fail if they haven't obtained a slot within
+//! the queue timeout.
+
+use std::{
+    collections::{HashMap, HashSet, VecDeque},
+    hash::Hash,
+    time::Duration,
+};
+
+use tokio::time::Instant;
+
+use crate::small_network::NodeId;
+
+/// Limits the number of concurrent fetches, queueing the excess ones.
+#[derive(Debug)]
+pub struct FetchLimiter<I> {
+    /// The maximum number of concurrent fetches.  If 0, the number is unlimited.
+    max_concurrent: usize,
+    /// The maximum number of concurrent fetches from a single peer.  If 0, the number is
+    /// unlimited.
+    max_concurrent_per_peer: usize,
+    /// The duration for which a fetch is queued before it fails.
+    queue_timeout: Duration,
+    /// The fetches currently occupying a slot.
+    in_flight: HashSet<(I, NodeId)>,
+    /// The number of fetches occupying a slot, by peer.
+    in_flight_per_peer: HashMap<NodeId, usize>,
+    /// The fetches waiting for a slot, with the time at which they fail.
+    queued: VecDeque<(I, NodeId, Instant)>,
+}
+
+impl<I: Copy + Eq + Hash> FetchLimiter<I> {
+    /// Creates a new limiter allowing `max_concurrent` fetches at a time, and
+    /// `max_concurrent_per_peer` from any single peer.
+    pub(super) fn new(
+        max_concurrent: usize,
+        max_concurrent_per_peer: usize,
+        queue_timeout: Duration,
+    ) -> Self {
+        FetchLimiter {
+            max_concurrent,
+            max_concurrent_per_peer,
+            queue_timeout,
+            in_flight: HashSet::new(),
+            in_flight_per_peer: HashMap::new(),
+            queued: VecDeque::new(),
+        }
+    }
+
+    /// Returns the duration for which a fetch is queued before it fails.
+    pub(super) fn queue_timeout(&self) -> Duration {
+        self.queue_timeout
+    }
+
+    /// Tries to occupy a slot for fetching `id` from `peer`.
+    ///
+    /// Returns `false` if all slots, or all slots for `peer`, are occupied, in which case the fetch
+    /// has been queued.
+    pub(super) fn try_start(&mut self, id: I, peer: NodeId) -> bool {
+        if self.in_flight.contains(&(id, peer)) {
+            return true;
+        }
+        if !self.is_full() && !self.is_peer_full(&peer) {
+            self.occupy(id, peer);
+            return true;
+        }
+        if !self
+            .queued
+            .iter()
+            .any(|(queued_id, queued_peer, _)| *queued_id == id && *queued_peer == peer)
+        {
+            let deadline = Instant::now() + self.queue_timeout;
+            self.queued.push_back((id, peer, deadline));
+        }
+        false
+    }
+
+    /// Frees the slot for fetching `id` from `peer`, or from any peer if `peer` is `None`.
+    ///
+    /// Queued fetches of `id` from the affected peers are dropped as well.
+    pub(super) fn finish(&mut self, id: &I, peer: Option<NodeId>) {
+        let matches = |other_id: &I, other_peer: &NodeId| {
+            other_id == id && peer.map_or(true, |peer| peer == *other_peer)
+        };
+        let finished: Vec<_> = match peer {
+            Some(peer) => vec![peer],
+            None => self.in_flight_per_peer.keys().copied().collect(),
+        };
+        for peer in finished {
+            if self.in_flight.remove(&(*id, peer)) {
+                self.release(&peer);
+            }
+        }
+        self.queued
+            .retain(|(other_id, other_peer, _)| !matches(other_id, other_peer));
+    }
+
+    /// Moves the longest-queued fetch from a peer with a free slot into a free slot, returning it.
+    ///
+    /// Returns `None` if all slots are occupied or no such fetch is queued.
+    pub(super) fn next_queued(&mut self) -> Option<(I, NodeId)> {
+        if self.is_full() {
+            return None;
+        }
+        let index = self
+            .queued
+            .iter()
+            .position(|(_, peer, _)| !self.is_peer_full(peer))?;
+        let (id, peer, _) = self.queued.remove(index)?;
+        self.occupy(id, peer);
+        Some((id, peer))
+    }
+
+    /// Returns the number of fetches from `peer` occupying a slot.
+    pub(super) fn in_flight_from(&self, peer: &NodeId) -> usize {
+        self.in_flight_per_peer
+            .get(peer)
+            .copied()
+            .unwrap_or_default()
+    }
+
+    /// Returns whether `id` is being fetched from `peer`.
+    pub(super) fn is_in_flight(&self, id: I, peer: NodeId) -> bool {
+        self.in_flight.contains(&(id, peer))
+    }
+
+    /// Removes the fetch of `id` from `peer` from the queue if it has been waiting for longer than
+    /// the queue timeout.
+    ///
+    /// Returns `true` if the fetch was removed and should fail.
+    pub(super) fn expire_queued(&mut self, id: &I, peer: NodeId) -> bool {
+        let now = Instant::now();
+        let count_before = self.queued.len();
+        self.queued.retain(|(queued_id, queued_peer, deadline)| {
+            !(queued_id == id && *queued_peer == peer && *deadline <= now)
+        });
+        self.queued.len() < count_before
+    }
+
+    /// Occupies a slot for fetching `id` from `peer`, which must not be occupying one yet.
+    fn occupy(&mut self, id: I, peer: NodeId) {
+        let _ = self.in_flight.insert((id, peer));
+        *self.in_flight_per_peer.entry(peer).or_default() += 1;
+    }
+
+    /// Releases one of the slots occupied by fetches from `peer`.
+    fn release(&mut self, peer: &NodeId) {
+        if let Some(count) = self.in_flight_per_peer.get_mut(peer) {
+            *count -= 1;
+            if *count == 0 {
+                let _ = self.in_flight_per_peer.remove(peer);
+            }
+        }
+    }
+
+    /// Returns whether all slots are occupied.
+    fn is_full(&self) -> bool {
+        self.max_concurrent != 0 && self.in_flight.len() >= self.max_concurrent
+    }
+
+    /// Returns whether all slots for fetches from `peer` are occupied.
+    fn is_peer_full(&self, peer: &NodeId) -> bool {
+        self.max_concurrent_per_peer != 0
+            && self.in_flight_from(peer) >= self.max_concurrent_per_peer
+    }
+}
@@ -1,6 +1,7 @@
 #![cfg(test)]
 use std::{
     fmt::{self, Debug, Display, Formatter},
+    iter,
     sync::{Arc, Mutex},
 };
 
@@ -15,13 +16,16 @@ use super::*;
 use crate::{
     components::{
         chainspec_loader::Chainspec,
-        deploy_acceptor::{self, DeployAcceptor},
+        deploy_acceptor::{self, AcceptAll, DeployAcceptor},
         in_memory_network::{InMemoryNetwork, NetworkController, NodeId},
         storage::{self, Storage, StorageType},
     },
     effect::{
-        announcements::{ApiServerAnnouncement, DeployAcceptorAnnouncement, NetworkAnnouncement},
-        requests::FetcherRequest,
+        announcements::{
+            ApiServerAnnouncement, ControlAnnouncement, DeployAcceptorAnnouncement,
+            NetworkAnnouncement, StorageAnnouncement,
+        },
+        requests::{DeployBufferRequest, FetcherRequest},
     },
     protocol::Message,
     reactor::{self, EventQueueHandle, Runner},
@@ -29,7 +33,7 @@ use crate::{
         network::{Network, NetworkedReactor},
         ConditionCheckReactor, TestRng,
     },
-    types::{Deploy, DeployHash, Tag},
+    types::{Deploy, DeployHash, NodeConfig, Tag, TimeDiff},
     utils::Loadable,
 };
 
@@ -55,6 +59,12 @@ enum Event {
     ApiServerAnnouncement(ApiServerAnnouncement),
     #[from]
     DeployAcceptorAnnouncement(DeployAcceptorAnnouncement<NodeId>),
+    #[from]
+    StorageAnnouncement(StorageAnnouncement),
+    #[from]
+    ControlAnnouncement(ControlAnnouncement),
+    #[from]
+    DeployBufferRequest(DeployBufferRequest),
 }
 
 impl From<StorageRequest<Storage>> for Event {
@@ -78,6 +88,9 @@ impl Display for Event {
             Event::DeployAcceptorAnnouncement(ann) => {
                 write!(formatter, "deploy-acceptor announcement: {}", ann)
             }
+            Event::StorageAnnouncement(ann) => write!(formatter, "storage announcement: {}", ann),
+            Event::ControlAnnouncement(ann) => write!(formatter, "control announcement: {}", ann),
+            Event::DeployBufferRequest(req) => write!(formatter, "deploy buffer request: {}", req),
         }
     }
 }
@@ -94,6 +107,10 @@ struct Reactor {
     storage: Storage,
     deploy_acceptor: DeployAcceptor,
     deploy_fetcher: Fetcher<Deploy>,
+    /// The number of get requests sent to peers.
+    get_requests_sent: usize,
+    /// The number of get requests sent to each peer.
+    get_requests_sent_to: HashMap<NodeId, usize>,
     _storage_tempdir: TempDir,
 }
 
@@ -110,7 +127,7 @@ impl reactor::Reactor<TestRng> for Reactor {
 
     fn new(
         config: Self::Config,
-        _registry: &Registry,
+        registry: &Registry,
         event_queue: EventQueueHandle<Self::Event>,
         rng: &mut TestRng,
     ) -> Result<(Self, Effects<Self::Event>), Self::Error> {
@@ -119,7 +136,15 @@ impl reactor::Reactor<TestRng> for Reactor {
         let (storage_config, _storage_tempdir) = storage::Config::default_for_tests();
         let storage = Storage::new(&storage_config).unwrap();
 
-        let deploy_acceptor = DeployAcceptor::new();
+        let deploy_acceptor = DeployAcceptor::new(
+            Box::new(AcceptAll),
+            NodeConfig::default().max_concurrent_deploy_validations,
+            NodeConfig::default().max_queued_deploy_validations,
+            TimeDiff::from(NodeConfig::default().deploy_max_future_skew_secs * 1000),
+            // Test deploys depend on random deploys, which no peer holds.
+            false,
+            registry,
+        )?;
         let deploy_fetcher = Fetcher::<Deploy>::new(config);
 
         let reactor = Reactor {
@@ -127,6 +152,8 @@ impl reactor::Reactor<TestRng> for Reactor {
             storage,
             deploy_acceptor,
             deploy_fetcher,
+            get_requests_sent: 0,
+            get_requests_sent_to: HashMap::new(),
             _storage_tempdir,
         };
 
@@ -162,10 +189,21 @@ impl reactor::Reactor<TestRng> for Reactor {
                 self.deploy_fetcher
                     .handle_event(effect_builder, rng, deploy_event),
             ),
-            Event::NetworkRequest(request) => reactor::wrap_effects(
-                Event::NetworkRequest,
-                self.network.handle_event(effect_builder, rng, request),
-            ),
+            Event::NetworkRequest(request) => {
+                if let NetworkRequest::SendMessage {
+                    dest,
+                    payload: Message::GetRequest { .. },
+                    ..
+                } = &request
+                {
+                    self.get_requests_sent += 1;
+                    *self.get_requests_sent_to.entry(*dest).or_default() += 1;
+                }
+                reactor::wrap_effects(
+                    Event::NetworkRequest,
+                    self.network.handle_event(effect_builder, rng, request),
+                )
+            }
             Event::DeployFetcherRequest(request) => reactor::wrap_effects(
                 Event::DeployFetcher,
                 self.deploy_fetcher
@@ -209,6 +247,7 @@ impl reactor::Reactor<TestRng> for Reactor {
                         Event::DeployAcceptor(deploy_acceptor::Event::Accept {
                             deploy,
                             source: Source::Peer(sender),
+                            responder: None,
                         })
                     }
                     msg => panic!("should not get {}", msg),
@@ -218,14 +257,21 @@ impl reactor::Reactor<TestRng> for Reactor {
             Event::NetworkAnnouncement(ann) => {
                 unreachable!("should not receive announcements of type {:?}", ann);
             }
-            Event::ApiServerAnnouncement(ApiServerAnnouncement::DeployReceived { deploy }) => {
+            Event::ApiServerAnnouncement(ApiServerAnnouncement::DeployReceived {
+                deploy,
+                responder,
+            }) => {
                 let event = deploy_acceptor::Event::Accept {
                     deploy,
                     source: Source::<NodeId>::Client,
+                    responder,
                 };
                 self.dispatch_event(effect_builder, rng, Event::DeployAcceptor(event))
             }
-            Event::DeployAcceptorAnnouncement(DeployAcceptorAnnouncement::AcceptedNewDeploy {
+            Event::DeployAcceptorAnnouncement(DeployAcceptorAnnouncement::DeployAccepted {
+                ..
+            }) => Effects::new(),
+            Event::DeployAcceptorAnnouncement(DeployAcceptorAnnouncement::DeployStored {
                 deploy,
                 source,
             }) => {
@@ -239,6 +285,13 @@ impl reactor::Reactor<TestRng> for Reactor {
                 deploy: _,
                 source: _,
             }) => Effects::new(),
+            Event::StorageAnnouncement(_) => Effects::new(),
+            Event::ControlAnnouncement(ann) => panic!("unexpected {}", ann),
+            // There is no deploy buffer, so every deploy is treated as buffered.
+            Event::DeployBufferRequest(DeployBufferRequest::Buffer { responder, .. }) => {
+                responder.respond(true).ignore()
+            }
+            Event::DeployBufferRequest(req) => panic!("unexpected {}", req),
         }
     }
 }
@@ -254,7 +307,7 @@ impl NetworkedReactor for Reactor {
 fn announce_deploy_received(deploy: Deploy) -> impl FnOnce(EffectBuilder<Event>) -> Effects<Event> {
     |effect_builder: EffectBuilder<Event>| {
         effect_builder
-            .announce_deploy_received(Box::new(deploy))
+            .announce_deploy_received(Box::new(deploy), None)
             .ignore()
     }
 }
@@ -295,7 +348,7 @@ async fn store_deploy(
             move |event: &Event| -> bool {
                 match event {
                     Event::DeployAcceptorAnnouncement(
-                        DeployAcceptorAnnouncement::AcceptedNewDeploy { .. },
+                        DeployAcceptorAnnouncement::DeployStored { .. },
                     ) => true,
                     _ => false,
                 }
@@ -520,3 +573,304 @@ async fn should_timeout_fetch_from_peer() {
 
     NetworkController::<Message>::remove_active();
 }
+
+#[tokio::test]
+async fn should_not_refetch_recently_failed_deploy_until_expiry() {
+    const NETWORK_SIZE: usize = 2;
+
+    NetworkController::<Message>::create_active();
+    let (mut network, mut rng, node_ids) = {
+        let mut network = Network::<Reactor>::new();
+        let mut rng = TestRng::new();
+        let node_ids = network.add_nodes(&mut rng, NETWORK_SIZE).await;
+        (network, rng, node_ids)
+    };
+
+    // A deploy which no node holds.
+    let deploy_hash = *Deploy::random(&mut rng).id();
+    let peer = node_ids[0];
+    let requesting_node = node_ids[1];
+    let get_requests_sent = |network: &Network<Reactor>| {
+        network
+            .nodes()
+            .get(&requesting_node)
+            .unwrap()
+            .reactor()
+            .inner()
+            .get_requests_sent
+    };
+    let is_get_request = |event: &Event| -> bool {
+        if let Event::NetworkRequest(NetworkRequest::SendMessage {
+            payload: Message::GetRequest { .. },
+            ..
+        }) = event
+        {
+            true
+        } else {
+            false
+        }
+    };
+
+    // The first fetch asks the peer, and fails once the peer times out.
+    let fetched = Arc::new(Mutex::new((false, None)));
+    network
+        .process_injected_effect_on(
+            &requesting_node,
+            fetch_deploy(deploy_hash, peer, Arc::clone(&fetched)),
+        )
+        .await;
+    network
+        .crank_until(&requesting_node, &mut rng, is_get_request, TIMEOUT)
+        .await;
+    let secs_to_advance = GossipConfig::default().get_remainder_timeout_secs();
+    time::pause();
+    time::advance(Duration::from_secs(secs_to_advance + 1)).await;
+    time::resume();
+    assert_settled(
+        &requesting_node,
+        deploy_hash,
+        None,
+        fetched,
+        &mut network,
+        &mut rng,
+        TIMEOUT,
+    )
+    .await;
+    assert_eq!(get_requests_sent(&network), 1);
+
+    // Fetching again fails straight away, without asking the peer.
+    let fetched = Arc::new(Mutex::new((false, None)));
+    network
+        .process_injected_effect_on(
+            &requesting_node,
+            fetch_deploy(deploy_hash, peer, Arc::clone(&fetched)),
+        )
+        .await;
+    assert_settled(
+        &requesting_node,
+        deploy_hash,
+        None,
+        fetched,
+        &mut network,
+        &mut rng,
+        TIMEOUT,
+    )
+    .await;
+    assert_eq!(get_requests_sent(&network), 1);
+
+    // Once the failure has expired, the peer is asked again.
+    let secs_to_advance = GossipConfig::default().fetch_failure_cache_secs();
+    time::pause();
+    time::advance(Duration::from_secs(secs_to_advance + 1)).await;
+    time::resume();
+    let fetched = Arc::new(Mutex::new((false, None)));
+    network
+        .process_injected_effect_on(
+            &requesting_node,
+            fetch_deploy(deploy_hash, peer, Arc::clone(&fetched)),
+        )
+        .await;
+    network
+        .crank_until(&requesting_node, &mut rng, is_get_request, TIMEOUT)
+        .await;
+    assert_eq!(get_requests_sent(&network), 2);
+
+    NetworkController::<Message>::remove_active();
+}
+
+#[tokio::test]
+async fn should_queue_fetches_beyond_concurrency_limit() {
+    NetworkController::<Message>::create_active();
+    let mut network = Network::<Reactor>::new();
+    let mut rng = TestRng::new();
+    let holding_node = network.add_node(&mut rng).await.unwrap().0;
+    let config = GossipConfig::default().with_max_concurrent_fetches(2);
+    let requesting_node = network
+        .add_node_with_config(config, &mut rng)
+        .await
+        .unwrap()
+        .0;
+
+    // The holding node holds the first two deploys, but nobody holds the third.
+    let deploys: Vec<_> = (0..3).map(|_| Deploy::random(&mut rng)).collect();
+    for deploy in &deploys[..2] {
+        store_deploy(deploy, &holding_node, &mut network, &mut rng).await;
+    }
+
+    let get_requests_sent = |nodes: &HashMap<NodeId, Runner<ConditionCheckReactor<Reactor>, _>>| {
+        nodes
+            .get(&requesting_node)
+            .unwrap()
+            .reactor()
+            .inner()
+            .get_requests_sent
+    };
+    let is_get_request = |event: &Event| -> bool {
+        if let Event::NetworkRequest(NetworkRequest::SendMessage {
+            payload: Message::GetRequest { .. },
+            ..
+        }) = event
+        {
+            true
+        } else {
+            false
+        }
+    };
+
+    // Only cranking the requesting node, the first two fetches are sent to the holding node.
+    let fetched: Vec<_> = (0..3)
+        .map(|_| Arc::new(Mutex::new((false, None))))
+        .collect();
+    for (deploy, fetched) in deploys[..2].iter().zip(&fetched) {
+        network
+            .process_injected_effect_on(
+                &requesting_node,
+                fetch_deploy(*deploy.id(), holding_node, Arc::clone(fetched)),
+            )
+            .await;
+        network
+            .crank_until(&requesting_node, &mut rng, is_get_request, TIMEOUT)
+            .await;
+    }
+
+    // The third fetch is queued rather than sent, since both slots are occupied.
+    let queued_hash = *deploys[2].id();
+    network
+        .process_injected_effect_on(
+            &requesting_node,
+            fetch_deploy(queued_hash, holding_node, Arc::clone(&fetched[2])),
+        )
+        .await;
+    network
+        .crank_until(
+            &requesting_node,
+            &mut rng,
+            move |event: &Event| -> bool {
+                match event {
+                    Event::DeployFetcher(super::Event::GetFromStorageResult { id, .. }) => {
+                        *id == queued_hash
+                    }
+                    _ => false,
+                }
+            },
+            TIMEOUT,
+        )
+        .await;
+    assert_eq!(get_requests_sent(network.nodes()), 2);
+    assert!(!fetched[2].lock().unwrap().0);
+
+    // Once the first two fetches complete, the third one is sent.
+    network
+        .settle_on(
+            &mut rng,
+            |nodes| {
+                fetched[..2].iter().all(|fetched| fetched.lock().unwrap().0)
+                    && get_requests_sent(nodes) == 3
+            },
+            TIMEOUT,
+        )
+        .await;
+    for (deploy, fetched) in deploys[..2].iter().zip(&fetched) {
+        assert_eq!(
+            fetched.lock().unwrap().1,
+            Some(FetchResult::FromPeer(
+                Box::new(deploy.clone()),
+                holding_node
+            ))
+        );
+    }
+    assert!(!fetched[2].lock().unwrap().0);
+
+    NetworkController::<Message>::remove_active();
+}
+
+#[tokio::test]
+async fn should_spread_fetches_across_peers() {
+    NetworkController::<Message>::create_active();
+    let mut network = Network::<Reactor>::new();
+    let mut rng = TestRng::new();
+    let mut holding_nodes = Vec::new();
+    for _ in 0..3 {
+        holding_nodes.push(network.add_node(&mut rng).await.unwrap().0);
+    }
+    let requesting_node = network.add_node(&mut rng).await.unwrap().0;
+
+    // All three peers hold all deploys.
+    const DEPLOY_COUNT: usize = 30;
+    let deploys: Vec<_> = (0..DEPLOY_COUNT)
+        .map(|_| Deploy::random(&mut rng))
+        .collect();
+    for deploy in &deploys {
+        for holding_node in &holding_nodes {
+            store_deploy(deploy, holding_node, &mut network, &mut rng).await;
+        }
+    }
+
+    for peer in holding_nodes.iter().copied() {
+        network
+            .process_injected_effect_on(&requesting_node, move |effect_builder| {
+                effect_builder
+                    .immediately()
+                    .event(move |_| Event::DeployFetcher(super::Event::PeerConnected(peer)))
+            })
+            .await;
+    }
+
+    // Every deploy is requested from each of the peers, the first peer always being named first.
+    let fetched: Vec<_> = (0..DEPLOY_COUNT * holding_nodes.len())
+        .map(|_| Arc::new(Mutex::new((false, None))))
+        .collect();
+    let requests = deploys
+        .iter()
+        .flat_map(|deploy| holding_nodes.iter().map(move |peer| (deploy, *peer)));
+    for ((deploy, peer), fetched) in requests.zip(&fetched) {
+        network
+            .process_injected_effect_on(
+                &requesting_node,
+                fetch_deploy(*deploy.id(), peer, Arc::clone(fetched)),
+            )
+            .await;
+    }
+    network
+        .settle_on(
+            &mut rng,
+            |_| fetched.iter().all(|fetched| fetched.lock().unwrap().0),
+            TIMEOUT,
+        )
+        .await;
+
+    for (deploy, fetched) in deploys
+        .iter()
+        .flat_map(|deploy| iter::repeat(deploy).take(holding_nodes.len()))
+        .zip(&fetched)
+    {
+        match &fetched.lock().unwrap().1 {
+            Some(FetchResult::FromPeer(fetched_deploy, peer)) => {
+                assert_eq!(**fetched_deploy, *deploy);
+                assert!(holding_nodes.contains(peer));
+            }
+            other => panic!("expected deploy from a peer, got {:?}", other),
+        }
+    }
+
+    // Each peer was sent roughly a third of the requests, rather than the first peer all of them.
+    let sent_to = &network
+        .nodes()
+        .get(&requesting_node)
+        .unwrap()
+        .reactor()
+        .inner()
+        .get_requests_sent_to;
+    for holding_node in &holding_nodes {
+        let sent = sent_to.get(holding_node).copied().unwrap_or_default();
+        assert!(
+            sent >= DEPLOY_COUNT / 6 && sent <= DEPLOY_COUNT / 2,
+            "{} of {} requests sent to {}",
+            sent,
+            DEPLOY_COUNT,
+            holding_node
+        );
+    }
+
+    NetworkController::<Message>::remove_active();
+}
@@ -0,0 +1,829 @@
+//! Finality signature collector.
+//!
+//! Once a validator has executed a block, it signs the block's hash and broadcasts the signature
+//! to its peers. The finality signature collector accumulates the signatures received for each
+//! block and verifies each one against the signer's public key. As soon as validators with more
+//! than the configured quorum fraction of the block's era's total weight, by default two thirds,
+//! have signed a block, it announces the collected signatures, which then form a proof of the
+//! block's finality.
+//!
+//! Signatures are only collected for blocks which have been added to our linear chain, and only
+//! for the blocks of the latest two eras: Older blocks are forgotten together with the weights of
+//! their eras.  Validators which are faster to execute a block may sign it before we have added it,
+//! so a few valid signatures of unknown blocks are held back per validator, until the block is
+//! added or they are displaced by the validator's later signatures.  The weights of each era are
+//! learned when it starts.  Until they are known, e.g. on observers which don't run consensus, a
+//! block is counted with the weights of the latest earlier era known.
+//!
+//! To let observers track the progress towards finality, every valid signature of a block is
+//! announced together with the weight of all signers so far and the weight needed for finality.
+//! The last of these announcements is made right before the block's signatures are announced.  The
+//! progress of the latest signed block is also recorded in the metrics, in percent.
+//!
+//! Signatures by unknown validators, invalid signatures and duplicates are ignored, as are all
+//! signatures arriving after a block's signatures have been announced.
+
+use std::{
+    collections::{BTreeMap, HashMap, VecDeque},
+    fmt::{self, Display, Formatter},
+};
+
+use derive_more::From;
+use prometheus::{IntGauge, Registry};
+use rand::{CryptoRng, Rng};
+use tracing::{debug, info, warn};
+
+use casper_execution_engine::shared::motes::Motes;
+use casper_types::U512;
+
+use crate::{
+    components::{
+        consensus::{EraId, QuorumFraction},
+        Component,
+    },
+    crypto::asymmetric_key::{PublicKey, Signature},
+    effect::{announcements::FinalitySignatureAnnouncement, EffectBuilder, EffectExt, Effects},
+    types::{BlockHash, FinalitySignature},
+};
+
+/// The maximum number of signatures of blocks not yet added to our linear chain held back per
+/// validator.
+const MAX_EARLY_SIGNATURES_PER_VALIDATOR: usize = 10;
+
+/// An event for when using the finality signature collector as a component.
+#[derive(Debug, From)]
+pub enum Event {
+    /// A finality signature has been received, either from a peer or from ourselves.
+    #[from]
+    SignatureReceived(FinalitySignature),
+    /// A block has been added to our linear chain.
+    BlockAdded {
+        /// The block's hash.
+        block_hash: BlockHash,
+        /// The era the block belongs to.
+        era_id: EraId,
+    },
+    /// A new era has started.
+    EraStarted {
+        /// The ID of the new era.
+        era_id: EraId,
+        /// The weights of the new era's validators.
+        validator_weights: BTreeMap<PublicKey, Motes>,
+    },
+}
+
+impl Display for Event {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            Event::SignatureReceived(finality_signature) => write!(
+                f,
+                "finality-signature-collector received {}",
+                finality_signature
+            ),
+            Event::BlockAdded { block_hash, era_id } => write!(
+                f,
+                "finality-signature-collector block {} of era {} added",
+                block_hash, era_id
+            ),
+            Event::EraStarted { era_id, .. } => {
+                write!(f, "finality-signature-collector era {} started", era_id)
+            }
+        }
+    }
+}
+
+/// The weights of the validators of an era.
+#[derive(Debug)]
+struct EraWeights {
+    /// The weights of the validators whose signatures count towards finality.
+    validator_weights: HashMap<PublicKey, Motes>,
+    /// The total weight of all validators.
+    total_weight: Motes,
+    /// The smallest weight of signers exceeding the quorum.
+    weight_needed: Motes,
+}
+
+impl EraWeights {
+    fn new<I>(validator_weights: I, quorum: QuorumFraction) -> Self
+    where
+        I: IntoIterator<Item = (PublicKey, Motes)>,
+    {
+        let validator_weights: HashMap<_, _> = validator_weights.into_iter().collect();
+        let total_weight = validator_weights.values().copied().sum();
+        EraWeights {
+            validator_weights,
+            total_weight,
+            weight_needed: quorum_weight(total_weight, quorum),
+        }
+    }
+}
+
+/// The signatures collected for a single block.
+#[derive(Debug)]
+struct BlockSignatures {
+    /// The era the block belongs to.
+    era_id: EraId,
+    /// The valid signatures received so far, by signer.
+    signatures: BTreeMap<PublicKey, Signature>,
+    /// The total weight of the signers.
+    weight: Motes,
+    /// Whether the signatures have been announced already.
+    complete: bool,
+}
+
+/// The progress of a block towards finality after adding a signature.
+#[derive(Debug)]
+struct Progress {
+    /// The era of the block.
+    era_id: EraId,
+    /// The total weight of the block's signers so far.
+    weight_so_far: Motes,
+    /// The smallest weight of signers making the block final.
+    weight_needed: Motes,
+    /// All signatures of the block, if the signature completed the quorum.
+    signatures: Option<BTreeMap<PublicKey, Signature>>,
+}
+
+/// Metrics of the finality signature collector.
+#[derive(Debug)]
+struct FinalitySignatureCollectorMetrics {
+    /// The signature weight of the latest signed block, in percent of the weight needed for it to
+    /// be final.
+    finality_progress: IntGauge,
+
+    /// Handle to the metrics registry, in case we need to unregister.
+    registry: Registry,
+}
+
+impl FinalitySignatureCollectorMetrics {
+    /// Create and register new finality signature collector metrics.
+    fn new(registry: &Registry) -> Result<Self, prometheus::Error> {
+        let finality_progress = IntGauge::new(
+            "finality_progress_percent",
+            "signature weight of the latest signed block in percent of the weight needed for \
+             finality",
+        )?;
+        registry.register(Box::new(finality_progress.clone()))?;
+
+        Ok(FinalitySignatureCollectorMetrics {
+            finality_progress,
+            registry: registry.clone(),
+        })
+    }
+}
+
+impl Drop for FinalitySignatureCollectorMetrics {
+    fn drop(&mut self) {
+        self.registry
+            .unregister(Box::new(self.finality_progress.clone()))
+            .expect("did not expect deregistering finality progress to fail");
+    }
+}
+
+/// Finality signature collector.
+#[derive(Debug)]
+pub(crate) struct FinalitySignatureCollector {
+    /// The weights of the validators of the latest eras, by era.
+    eras: BTreeMap<EraId, EraWeights>,
+    /// The latest era whose start or blocks we know of.
+    current_era: EraId,
+    /// The fraction of the total weight the signers of a block have to exceed.
+    quorum: QuorumFraction,
+    /// The signatures collected so far, by block.
+    blocks: HashMap<BlockHash, BlockSignatures>,
+    /// The valid signatures of blocks not yet added to our linear chain, by signer, oldest first.
+    early_signatures: HashMap<PublicKey, VecDeque<FinalitySignature>>,
+    metrics: FinalitySignatureCollectorMetrics,
+}
+
+impl FinalitySignatureCollector {
+    /// Creates a new finality signature collector starting in the era with the given validators,
+    /// considering blocks final once signed by validators exceeding `quorum` of the total weight.
+    pub(crate) fn new<I>(
+        era_id: EraId,
+        validator_weights: I,
+        quorum: QuorumFraction,
+        registry: &Registry,
+    ) -> Result<Self, prometheus::Error>
+    where
+        I: IntoIterator<Item = (PublicKey, Motes)>,
+    {
+        let mut eras = BTreeMap::new();
+        eras.insert(era_id, EraWeights::new(validator_weights, quorum));
+        Ok(FinalitySignatureCollector {
+            eras,
+            current_era: era_id,
+            quorum,
+            blocks: HashMap::new(),
+            early_signatures: HashMap::new(),
+            metrics: FinalitySignatureCollectorMetrics::new(registry)?,
+        })
+    }
+
+    /// Returns the weights of the given era, or of the latest earlier one known.
+    fn era_weights(&self, era_id: EraId) -> Option<&EraWeights> {
+        self.eras
+            .range(..=era_id)
+            .next_back()
+            .map(|(_, weights)| weights)
+    }
+
+    /// Returns the oldest era whose blocks are collected.
+    fn oldest_retained_era(&self) -> EraId {
+        EraId(self.current_era.0.saturating_sub(1))
+    }
+
+    /// Advances to `era_id` if it is later than the current era, forgetting all but the latest two
+    /// eras and their blocks.
+    fn advance_to(&mut self, era_id: EraId) {
+        if era_id <= self.current_era {
+            return;
+        }
+        self.current_era = era_id;
+        let oldest_retained = self.oldest_retained_era();
+        // The weights applying to the oldest retained era are kept, even if of an earlier era.
+        if let Some(oldest_weights) = self
+            .eras
+            .range(..=oldest_retained)
+            .next_back()
+            .map(|(era_id, _)| *era_id)
+        {
+            self.eras = self.eras.split_off(&oldest_weights);
+        }
+        self.blocks
+            .retain(|_, block| block.era_id >= oldest_retained);
+        let eras = &self.eras;
+        self.early_signatures.retain(|public_key, _| {
+            eras.values()
+                .any(|era| era.validator_weights.contains_key(public_key))
+        });
+    }
+
+    /// Records the weights of a new era.
+    fn start_era<I>(&mut self, era_id: EraId, validator_weights: I)
+    where
+        I: IntoIterator<Item = (PublicKey, Motes)>,
+    {
+        self.eras
+            .insert(era_id, EraWeights::new(validator_weights, self.quorum));
+        self.advance_to(era_id);
+    }
+
+    /// Adds a block which has been added to our linear chain, so that its signatures are collected.
+    ///
+    /// Returns the progress of the block for each signature held back for it.
+    fn add_block(&mut self, block_hash: BlockHash, era_id: EraId) -> Vec<Progress> {
+        self.advance_to(era_id);
+        if era_id < self.oldest_retained_era() || self.blocks.contains_key(&block_hash) {
+            return Vec::new();
+        }
+        let _ = self.blocks.insert(
+            block_hash,
+            BlockSignatures {
+                era_id,
+                signatures: BTreeMap::new(),
+                weight: Motes::new(U512::zero()),
+                complete: false,
+            },
+        );
+        let mut early_signatures = Vec::new();
+        for signatures in self.early_signatures.values_mut() {
+            if let Some(index) = signatures
+                .iter()
+                .position(|signature| signature.block_hash == block_hash)
+            {
+                early_signatures.extend(signatures.remove(index));
+            }
+        }
+        self.early_signatures
+            .retain(|_, signatures| !signatures.is_empty());
+        early_signatures
+            .into_iter()
+            .filter_map(|finality_signature| {
+                let weight = self.counted_weight(&finality_signature)?;
+                Some(self.count(finality_signature, weight))
+            })
+            .collect()
+    }
+
+    /// Adds a finality signature.
+    ///
+    /// Returns the block's progress if the signature counts towards its finality, including all
+    /// its signatures if the signature completes the quorum, i.e. at most once per block.
+    fn add_signature(&mut self, finality_signature: FinalitySignature) -> Option<Progress> {
+        if !self.blocks.contains_key(&finality_signature.block_hash) {
+            self.hold_back(finality_signature);
+            return None;
+        }
+        let weight = self.counted_weight(&finality_signature)?;
+        if let Err(error) = finality_signature.verify() {
+            warn!(
+                block_hash = %finality_signature.block_hash,
+                public_key = %finality_signature.public_key,
+                %error,
+                "ignoring invalid finality signature"
+            );
+            return None;
+        }
+        Some(self.count(finality_signature, weight))
+    }
+
+    /// Holds back a signature of a block not yet added to our linear chain, if it is valid and by a
+    /// validator of a known era.
+    fn hold_back(&mut self, finality_signature: FinalitySignature) {
+        let FinalitySignature {
+            block_hash,
+            public_key,
+            ..
+        } = finality_signature;
+        if !self
+            .eras
+            .values()
+            .any(|era| era.validator_weights.contains_key(&public_key))
+        {
+            debug!(%public_key, "ignoring finality signature by unknown validator");
+            return;
+        }
+        let is_held_back = self
+            .early_signatures
+            .get(&public_key)
+            .map_or(false, |signatures| {
+                signatures
+                    .iter()
+                    .any(|signature| signature.block_hash == block_hash)
+            });
+        if is_held_back {
+            return;
+        }
+        if let Err(error) = finality_signature.verify() {
+            warn!(%block_hash, %public_key, %error, "ignoring invalid finality signature");
+            return;
+        }
+        debug!(%block_hash, %public_key, "holding back finality signature of unknown block");
+        let signatures = self.early_signatures.entry(public_key).or_default();
+        signatures.push_back(finality_signature);
+        if signatures.len() > MAX_EARLY_SIGNATURES_PER_VALIDATOR {
+            let _ = signatures.pop_front();
+        }
+    }
+
+    /// Returns the weight the signature adds to its block, unless the block is unknown or already
+    /// final, the signer is not a validator of the block's era, or has signed the block already.
+    ///
+    /// The signature itself is not verified.
+    fn counted_weight(&self, finality_signature: &FinalitySignature) -> Option<Motes> {
+        let FinalitySignature {
+            block_hash,
+            public_key,
+            ..
+        } = finality_signature;
+        let block = self.blocks.get(block_hash)?;
+        if block.complete || block.signatures.contains_key(public_key) {
+            return None;
+        }
+        let weight = self
+            .era_weights(block.era_id)
+            .and_then(|era| era.validator_weights.get(public_key))
+            .copied();
+        if weight.is_none() {
+            debug!(%public_key, "ignoring finality signature by unknown validator");
+        }
+        weight
+    }
+
+    /// Counts a verified signature with the given weight towards its block's finality.
+    fn count(&mut self, finality_signature: FinalitySignature, weight: Motes) -> Progress {
+        let FinalitySignature {
+            block_hash,
+            public_key,
+            signature,
+        } = finality_signature;
+        let block = self
+            .blocks
+            .get_mut(&block_hash)
+            .expect("should have block of counted signature");
+        let era = self
+            .eras
+            .range(..=block.era_id)
+            .next_back()
+            .map(|(_, era)| era)
+            .expect("should have era of counted signature");
+        let weight_needed = era.weight_needed;
+
+        block.signatures.insert(public_key, signature);
+        block.weight = block.weight + weight;
+        let weight_so_far = block.weight;
+        self.metrics
+            .finality_progress
+            .set(progress_percent(weight_so_far, weight_needed));
+        if !is_quorum(block.weight, era.total_weight, self.quorum) {
+            return Progress {
+                era_id: block.era_id,
+                weight_so_far,
+                weight_needed,
+                signatures: None,
+            };
+        }
+        block.complete = true;
+        info!(
+            %block_hash,
+            signatures = block.signatures.len(),
+            "collected finality signatures"
+        );
+        Progress {
+            era_id: block.era_id,
+            weight_so_far,
+            weight_needed,
+            signatures: Some(std::mem::take(&mut block.signatures)),
+        }
+    }
+}
+
+/// Returns the smallest weight which is more than `quorum` of `total_weight`, or all of it.
+fn quorum_weight(total_weight: Motes, quorum: QuorumFraction) -> Motes {
+    let exceeding = total_weight.value() * U512::from(quorum.numerator())
+        / U512::from(quorum.denominator())
+        + U512::from(1);
+    Motes::new(exceeding.min(total_weight.value()))
+}
+
+/// Returns whether `weight` is more than `quorum` of `total_weight`, or all of it.
+fn is_quorum(weight: Motes, total_weight: Motes, quorum: QuorumFraction) -> bool {
+    weight == total_weight
+        || weight.value() * U512::from(quorum.denominator())
+            > total_weight.value() * U512::from(quorum.numerator())
+}
+
+/// Returns `weight_so_far` in percent of `weight_needed`, at most 100.
+fn progress_percent(weight_so_far: Motes, weight_needed: Motes) -> i64 {
+    if weight_so_far.value() >= weight_needed.value() {
+        return 100;
+    }
+    (weight_so_far.value() * U512::from(100) / weight_needed.value()).as_u64() as i64
+}
+
+/// Announces the progress of a block towards finality, and its signatures once it is final.
+fn announce<REv>(
+    effect_builder: EffectBuilder<REv>,
+    block_hash: BlockHash,
+    progress: Vec<Progress>,
+) -> Effects<Event>
+where
+    REv: From<FinalitySignatureAnnouncement> + Send,
+{
+    if progress.is_empty() {
+        return Effects::new();
+    }
+    // The progress is announced first, so it is seen before the block is final.
+    async move {
+        for Progress {
+            era_id,
+            weight_so_far,
+            weight_needed,
+            signatures,
+        } in progress
+        {
+            effect_builder
+                .announce_finality_progress(block_hash, weight_so_far, weight_needed)
+                .await;
+            if let Some(signatures) = signatures {
+                effect_builder
+                    .announce_finality_signatures_complete(block_hash, era_id, signatures)
+                    .await;
+            }
+        }
+    }
+    .ignore()
+}
+
+impl<REv, R> Component<REv, R> for FinalitySignatureCollector
+where
+    REv: From<FinalitySignatureAnnouncement> + Send,
+    R: Rng + CryptoRng + ?Sized,
+{
+    type Event = Event;
+
+    fn handle_event(
+        &mut self,
+        effect_builder: EffectBuilder<REv>,
+        _rng: &mut R,
+        event: Self::Event,
+    ) -> Effects<Self::Event> {
+        match event {
+            Event::SignatureReceived(finality_signature) => {
+                let block_hash = finality_signature.block_hash;
+                let progress = self.add_signature(finality_signature).into_iter().collect();
+                announce(effect_builder, block_hash, progress)
+            }
+            Event::BlockAdded { block_hash, era_id } => {
+                let progress = self.add_block(block_hash, era_id);
+                announce(effect_builder, block_hash, progress)
+            }
+            Event::EraStarted {
+                era_id,
+                validator_weights,
+            } => {
+                self.start_era(era_id, validator_weights);
+                Effects::new()
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        crypto::{
+            asymmetric_key::{self, SecretKey, SigningPurpose},
+            hash::Digest,
+        },
+        reactor::{EventQueueHandle, QueueKind, Scheduler},
+        testing::TestRng,
+        utils,
+    };
+
+    #[derive(Debug, From)]
+    enum ReactorEvent {
+        #[from]
+        FinalitySignatureAnnouncement(FinalitySignatureAnnouncement),
+    }
+
+    fn sign(rng: &mut TestRng, block_hash: BlockHash, secret_key: &SecretKey) -> FinalitySignature {
+        let public_key = PublicKey::from(secret_key);
+        let signature = asymmetric_key::sign(
+            block_hash.inner(),
+            secret_key,
+            &public_key,
+            SigningPurpose::FinalitySignature,
+            rng,
+        );
+        FinalitySignature {
+            block_hash,
+            public_key,
+            signature,
+        }
+    }
+
+    /// Adds a signature, returning all signatures of the block if it completes the quorum.
+    fn complete(
+        collector: &mut FinalitySignatureCollector,
+        finality_signature: FinalitySignature,
+    ) -> Option<BTreeMap<PublicKey, Signature>> {
+        collector
+            .add_signature(finality_signature)
+            .and_then(|progress| progress.signatures)
+    }
+
+    #[test]
+    fn should_complete_once_with_quorum_of_valid_signatures() {
+        let mut rng = TestRng::new();
+        let secret_keys: Vec<_> = (0..4).map(|_| SecretKey::random(&mut rng)).collect();
+        let mut collector = FinalitySignatureCollector::new(
+            EraId(0),
+            secret_keys
+                .iter()
+                .map(|secret_key| (PublicKey::from(secret_key), Motes::new(U512::from(10)))),
+            QuorumFraction::new(2, 3),
+            &Registry::new(),
+        )
+        .unwrap();
+        let block_hash = BlockHash::new(Digest::random(&mut rng));
+        let other_block_hash = BlockHash::new(Digest::random(&mut rng));
+        assert!(collector.add_block(block_hash, EraId(0)).is_empty());
+
+        // Two out of four validators aren't a quorum, and duplicates don't add any weight.
+        let first = sign(&mut rng, block_hash, &secret_keys[0]);
+        assert!(complete(&mut collector, first).is_none());
+        assert!(complete(&mut collector, first).is_none());
+        let second = sign(&mut rng, block_hash, &secret_keys[1]);
+        assert!(complete(&mut collector, second).is_none());
+
+        // Neither a signature of another block nor one by an unknown validator counts.
+        let mut forged = sign(&mut rng, other_block_hash, &secret_keys[2]);
+        forged.block_hash = block_hash;
+        assert!(complete(&mut collector, forged).is_none());
+        let unknown_key = SecretKey::random(&mut rng);
+        let unknown = sign(&mut rng, block_hash, &unknown_key);
+        assert!(complete(&mut collector, unknown).is_none());
+
+        // The third valid signature completes the quorum.
+        let third = sign(&mut rng, block_hash, &secret_keys[2]);
+        let signatures = complete(&mut collector, third).expect("should complete quorum");
+        assert_eq!(signatures.len(), 3);
+        for finality_signature in &[first, second, third] {
+            assert_eq!(
+                signatures.get(&finality_signature.public_key),
+                Some(&finality_signature.signature)
+            );
+        }
+
+        // Further signatures don't complete the block again.
+        let fourth = sign(&mut rng, block_hash, &secret_keys[3]);
+        assert!(complete(&mut collector, fourth).is_none());
+    }
+
+    #[test]
+    fn should_complete_just_over_configured_quorum() {
+        let mut rng = TestRng::new();
+        let secret_keys: Vec<_> = (0..2).map(|_| SecretKey::random(&mut rng)).collect();
+        // The quorum is 3/4 of a total weight of 100: 76 exceeds it, 75 doesn't.
+        let quorum = QuorumFraction::new(3, 4);
+        let collector_with_weights = |weights: &[u64]| {
+            FinalitySignatureCollector::new(
+                EraId(0),
+                secret_keys.iter().zip(weights).map(|(secret_key, weight)| {
+                    (PublicKey::from(secret_key), Motes::new(U512::from(*weight)))
+                }),
+                quorum,
+                &Registry::new(),
+            )
+            .unwrap()
+        };
+        let block_hash = BlockHash::new(Digest::random(&mut rng));
+        let signature = sign(&mut rng, block_hash, &secret_keys[0]);
+
+        let mut collector = collector_with_weights(&[76, 24]);
+        assert!(collector.add_block(block_hash, EraId(0)).is_empty());
+        assert!(complete(&mut collector, signature).is_some());
+
+        let mut collector = collector_with_weights(&[75, 25]);
+        assert!(collector.add_block(block_hash, EraId(0)).is_empty());
+        assert!(complete(&mut collector, signature).is_none());
+        // All validators together always reach the quorum.
+        let other_signature = sign(&mut rng, block_hash, &secret_keys[1]);
+        assert!(complete(&mut collector, other_signature).is_some());
+    }
+
+    #[test]
+    fn should_compute_progress_in_percent() {
+        let motes = |value: u64| Motes::new(U512::from(value));
+        assert_eq!(progress_percent(motes(10), motes(27)), 37);
+        assert_eq!(progress_percent(motes(27), motes(27)), 100);
+        assert_eq!(progress_percent(motes(30), motes(27)), 100);
+        assert_eq!(progress_percent(motes(0), motes(0)), 100);
+    }
+
+    #[tokio::test]
+    async fn should_announce_increasing_progress_until_finality() {
+        let mut rng = TestRng::new();
+        let scheduler = utils::leak(Scheduler::<ReactorEvent>::new(QueueKind::weights()));
+        let effect_builder = EffectBuilder::new(EventQueueHandle::new(scheduler));
+        let secret_keys: Vec<_> = (0..4).map(|_| SecretKey::random(&mut rng)).collect();
+        // The quorum is 2/3 of a total weight of 40, so a weight of 27 is needed.
+        let mut collector = FinalitySignatureCollector::new(
+            EraId(0),
+            secret_keys
+                .iter()
+                .map(|secret_key| (PublicKey::from(secret_key), Motes::new(U512::from(10)))),
+            QuorumFraction::new(2, 3),
+            &Registry::new(),
+        )
+        .unwrap();
+        let block_hash = BlockHash::new(Digest::random(&mut rng));
+        assert!(collector.add_block(block_hash, EraId(0)).is_empty());
+
+        let mut progress = Vec::new();
+        let mut is_final = false;
+        for secret_key in &secret_keys {
+            let event = Event::SignatureReceived(sign(&mut rng, block_hash, secret_key));
+            for effect in collector.handle_event(effect_builder, &mut rng, event) {
+                let _ = effect.await;
+            }
+            while scheduler.item_count() > 0 {
+                match scheduler.pop().await.0 {
+                    ReactorEvent::FinalitySignatureAnnouncement(
+                        FinalitySignatureAnnouncement::FinalityProgress {
+                            block_hash: announced_hash,
+                            weight_so_far,
+                            weight_needed,
+                        },
+                    ) => {
+                        assert_eq!(announced_hash, block_hash);
+                        assert!(!is_final, "progress announced after finality");
+                        assert_eq!(weight_needed, Motes::new(U512::from(27)));
+                        progress.push(weight_so_far.value().as_u64());
+                    }
+                    ReactorEvent::FinalitySignatureAnnouncement(
+                        FinalitySignatureAnnouncement::FinalitySignaturesComplete {
+                            block_hash: announced_hash,
+                            era_id,
+                            signatures,
+                        },
+                    ) => {
+                        assert_eq!(announced_hash, block_hash);
+                        assert_eq!(era_id, EraId(0));
+                        assert_eq!(signatures.len(), 3);
+                        is_final = true;
+                    }
+                }
+            }
+        }
+
+        // The weight grows with every signature, and the last progress coincides with finality.
+        // The fourth signature arrives after finality and isn't announced anymore.
+        assert_eq!(progress, vec![10, 20, 30]);
+        assert!(is_final);
+        assert_eq!(collector.metrics.finality_progress.get(), 100);
+    }
+
+    /// Returns a collector starting in era 0 with the given validators and weights.
+    fn collector_with_weights(
+        secret_keys: &[SecretKey],
+        weights: &[u64],
+    ) -> FinalitySignatureCollector {
+        FinalitySignatureCollector::new(
+            EraId(0),
+            weights_of(secret_keys, weights),
+            QuorumFraction::new(2, 3),
+            &Registry::new(),
+        )
+        .unwrap()
+    }
+
+    fn weights_of(secret_keys: &[SecretKey], weights: &[u64]) -> BTreeMap<PublicKey, Motes> {
+        secret_keys
+            .iter()
+            .zip(weights)
+            .map(|(secret_key, weight)| {
+                (PublicKey::from(secret_key), Motes::new(U512::from(*weight)))
+            })
+            .collect()
+    }
+
+    #[test]
+    fn should_only_count_signatures_of_added_blocks_and_hold_back_early_ones() {
+        let mut rng = TestRng::new();
+        let secret_keys: Vec<_> = (0..3).map(|_| SecretKey::random(&mut rng)).collect();
+        let mut collector = collector_with_weights(&secret_keys, &[10, 10, 10]);
+        let block_hash = BlockHash::new(Digest::random(&mut rng));
+
+        // Signatures of a block not yet added don't count, and no entry is created for it.
+        let first = sign(&mut rng, block_hash, &secret_keys[0]);
+        assert!(collector.add_signature(first).is_none());
+        let other_block_hash = BlockHash::new(Digest::random(&mut rng));
+        let mut forged = sign(&mut rng, other_block_hash, &secret_keys[1]);
+        forged.block_hash = block_hash;
+        assert!(collector.add_signature(forged).is_none());
+        let unknown_key = SecretKey::random(&mut rng);
+        let unknown = sign(&mut rng, block_hash, &unknown_key);
+        assert!(collector.add_signature(unknown).is_none());
+        assert!(collector.blocks.is_empty());
+        assert_eq!(collector.early_signatures.len(), 1);
+
+        // Once the block is added, only the valid signature held back is counted.
+        let progress = collector.add_block(block_hash, EraId(0));
+        assert_eq!(progress.len(), 1);
+        assert_eq!(progress[0].weight_so_far, Motes::new(U512::from(10)));
+        assert!(collector.early_signatures.is_empty());
+
+        // Only the latest few signatures of unknown blocks are held back per validator.
+        for _ in 0..=MAX_EARLY_SIGNATURES_PER_VALIDATOR {
+            let unknown_block = BlockHash::new(Digest::random(&mut rng));
+            let signature = sign(&mut rng, unknown_block, &secret_keys[2]);
+            assert!(collector.add_signature(signature).is_none());
+        }
+        let held_back = &collector.early_signatures[&PublicKey::from(&secret_keys[2])];
+        assert_eq!(held_back.len(), MAX_EARLY_SIGNATURES_PER_VALIDATOR);
+    }
+
+    #[test]
+    fn should_use_weights_of_block_era_and_forget_old_eras() {
+        let mut rng = TestRng::new();
+        let secret_keys: Vec<_> = (0..2).map(|_| SecretKey::random(&mut rng)).collect();
+        let mut collector = collector_with_weights(&secret_keys, &[10, 10]);
+
+        // In era 1, the first validator alone exceeds the quorum.
+        collector.start_era(EraId(1), weights_of(&secret_keys, &[90, 10]));
+        let old_block = BlockHash::new(Digest::random(&mut rng));
+        let new_block = BlockHash::new(Digest::random(&mut rng));
+        assert!(collector.add_block(old_block, EraId(0)).is_empty());
+        assert!(collector.add_block(new_block, EraId(1)).is_empty());
+        let old_signature = sign(&mut rng, old_block, &secret_keys[0]);
+        assert!(complete(&mut collector, old_signature).is_none());
+        let new_signature = sign(&mut rng, new_block, &secret_keys[0]);
+        assert!(complete(&mut collector, new_signature).is_some());
+
+        // Blocks of an era without known weights are counted with those of the latest era before.
+        let later_block = BlockHash::new(Digest::random(&mut rng));
+        assert!(collector.add_block(later_block, EraId(2)).is_empty());
+        let later_signature = sign(&mut rng, later_block, &secret_keys[0]);
+        assert!(complete(&mut collector, later_signature).is_some());
+
+        // Only the latest two eras and their blocks are kept.
+        assert!(!collector.blocks.contains_key(&old_block));
+        assert!(collector.blocks.contains_key(&new_block));
+        collector.start_era(EraId(3), weights_of(&secret_keys, &[10, 10]));
+        assert_eq!(
+            collector.eras.keys().copied().collect::<Vec<_>>(),
+            vec![EraId(1), EraId(3)]
+        );
+        assert_eq!(
+            collector.blocks.keys().copied().collect::<Vec<_>>(),
+            vec![later_block]
+        );
+        let obsolete_block = BlockHash::new(Digest::random(&mut rng));
+        assert!(collector.add_block(obsolete_block, EraId(1)).is_empty());
+        assert!(!collector.blocks.contains_key(&obsolete_block));
+    }
+}
@@ -1,23 +1,32 @@
+mod bloom_filter;
 mod config;
 mod error;
 mod event;
+mod fan_out;
 mod gossip_table;
 mod message;
+mod peer_scores;
+mod propagation;
 mod tests;
 
 use std::{
-    collections::HashSet,
+    collections::{HashMap, HashSet, VecDeque},
     fmt::{self, Debug, Formatter},
+    iter,
     time::Duration,
 };
 
 use futures::FutureExt;
 use rand::{CryptoRng, Rng};
-use smallvec::smallvec;
-use tracing::{debug, error};
+use smallvec::{smallvec, SmallVec};
+use tracing::{debug, error, info, warn};
 
 use crate::{
-    components::{small_network::NodeId, storage::Storage, Component},
+    components::{
+        small_network::{LatencyStore, NodeId},
+        storage::Storage,
+        Component, ComponentLifecycle,
+    },
     effect::{
         announcements::GossiperAnnouncement,
         requests::{NetworkRequest, StorageRequest},
@@ -27,11 +36,14 @@ use crate::{
     types::{Deploy, DeployHash, Item},
     utils::Source,
 };
+pub use bloom_filter::BloomFilter;
 pub use config::Config;
 pub use error::Error;
 pub use event::Event;
-use gossip_table::{GossipAction, GossipTable};
+use gossip_table::{GossipAction, GossipTable, ShouldGossip};
 pub use message::Message;
+use peer_scores::PeerScores;
+use propagation::Propagation;
 
 /// A helper trait whose bounds represent the requirements for a reactor event that `Gossiper` can
 /// work with.
@@ -72,14 +84,14 @@ pub(crate) fn get_deploy_from_storage<T: Item + 'static, REv: ReactorEventT<T>>(
 ) -> Effects<Event<Deploy>> {
     effect_builder
         .get_deploys_from_storage(smallvec![deploy_hash])
-        .event(move |mut results| {
-            let result = if results.len() == 1 {
-                results
+        .event(move |result| {
+            let result = match result {
+                Ok(mut results) if results.len() == 1 => results
                     .pop()
                     .unwrap()
-                    .ok_or_else(|| String::from("failed to get deploy from storage"))
-            } else {
-                Err(String::from("expected a single result"))
+                    .ok_or_else(|| String::from("failed to get deploy from storage")),
+                Ok(_) => Err(String::from("expected a single result")),
+                Err(error) => Err(error.to_string()),
             };
             Event::GetFromHolderResult {
                 item_id: deploy_hash,
@@ -89,6 +101,14 @@ pub(crate) fn get_deploy_from_storage<T: Item + 'static, REv: ReactorEventT<T>>(
         })
 }
 
+/// Returns `interval` lengthened by a random delay of up to `jitter` times `interval`.
+fn jittered<R: Rng + ?Sized>(interval: Duration, jitter: f64, rng: &mut R) -> Duration {
+    if jitter <= 0.0 {
+        return interval;
+    }
+    interval + interval.mul_f64(rng.gen_range(0.0, jitter))
+}
+
 /// The component which gossips to peers and handles incoming gossip messages from peers.
 #[allow(clippy::type_complexity)]
 pub(crate) struct Gossiper<T: Item + 'static, REv: ReactorEventT<T>> {
@@ -97,6 +117,40 @@ pub(crate) struct Gossiper<T: Item + 'static, REv: ReactorEventT<T>> {
     get_from_peer_timeout: Duration,
     get_from_holder:
         Box<dyn Fn(EffectBuilder<REv>, T::Id, NodeId) -> Effects<Event<T>> + Send + 'static>,
+    /// Newly-received items submitted by clients, waiting to be gossiped.
+    local_queue: VecDeque<(T::Id, ShouldGossip)>,
+    /// Newly-received items from peers, waiting to be gossiped once `local_queue` is empty.
+    forwarded_queue: VecDeque<(T::Id, ShouldGossip)>,
+    /// Items most peers already hold, waiting to be gossiped once all other queues are empty.
+    downgraded_queue: VecDeque<(T::Id, ShouldGossip)>,
+    /// Whether an `Event::FlushGossipQueue` is currently scheduled.
+    is_flush_scheduled: bool,
+    /// Responsiveness scores of the peers we gossiped to.
+    peer_scores: PeerScores<T::Id>,
+    /// How strongly gossip targets are biased towards peers with high scores.
+    peer_selection_bias: f64,
+    /// The number of item IDs the digest sent to newly-connected peers is sized for.
+    digest_capacity: u32,
+    /// The false positive rate of the digest sent to newly-connected peers.
+    digest_false_positive_rate: f64,
+    /// The maximum random delay added to the gossip timeout, as a fraction of it.
+    gossip_interval_jitter: f64,
+    /// The peers known to hold the recently gossiped items.
+    propagation: Propagation<T::Id>,
+    /// The fraction of connected peers holding an item above which its gossip is downgraded.
+    widely_seen_fraction: f64,
+    /// The interval between anti-entropy rounds, or zero if they are disabled.
+    anti_entropy_interval: Duration,
+    /// Whether to prefer gossip targets in distinct localities.
+    spread_localities: bool,
+    /// The redundancy target the fan-out is computed for, or `None` if the fan-out is fixed.
+    redundancy_target: Option<f64>,
+    /// The configured fan-out, used if it isn't computed for a redundancy target.
+    infection_target: usize,
+    /// Whether anti-entropy rounds are paused to shed load.
+    is_anti_entropy_paused: bool,
+    /// Whether the fan-out is halved to shed load.
+    is_fan_out_reduced: bool,
 }
 
 impl<T: Item + 'static, REv: ReactorEventT<T>> Gossiper<T, REv> {
@@ -109,61 +163,199 @@ impl<T: Item + 'static, REv: ReactorEventT<T>> Gossiper<T, REv> {
     ///
     /// For an example of how `get_from_holder` should be implemented, see
     /// `gossiper::get_deploy_from_store()` which is used by `Gossiper<Deploy>`.
+    ///
+    /// Gossip targets are biased towards peers with short round-trip times in `latencies`.
     pub(crate) fn new_for_partial_items(
         config: Config,
         get_from_holder: impl Fn(EffectBuilder<REv>, T::Id, NodeId) -> Effects<Event<T>>
             + Send
             + 'static,
+        latencies: LatencyStore,
     ) -> Self {
         assert!(
             !T::ID_IS_COMPLETE_ITEM,
             "this should only be called for types where T::ID_IS_COMPLETE_ITEM is false"
         );
-        Gossiper {
-            table: GossipTable::new(config),
-            gossip_timeout: Duration::from_secs(config.gossip_request_timeout_secs()),
-            get_from_peer_timeout: Duration::from_secs(config.get_remainder_timeout_secs()),
-            get_from_holder: Box::new(get_from_holder),
-        }
+        Self::new(config, get_from_holder, latencies)
     }
 
     /// Constructs a new gossiper component for use where `T::ID_IS_COMPLETE_ITEM == true`, i.e.
     /// where the gossip messages themselves contain the actual data being gossiped.
-    pub(crate) fn new_for_complete_items(config: Config) -> Self {
+    ///
+    /// Gossip targets are biased towards peers with short round-trip times in `latencies`.
+    pub(crate) fn new_for_complete_items(config: Config, latencies: LatencyStore) -> Self {
         assert!(
             T::ID_IS_COMPLETE_ITEM,
             "this should only be called for types where T::ID_IS_COMPLETE_ITEM is true"
         );
+        Self::new(
+            config,
+            |_, item, _| panic!("gossiper should never try to get {}", item),
+            latencies,
+        )
+    }
+
+    /// Constructs a new gossiper component calling `get_from_holder` to get items to send to peers.
+    fn new(
+        config: Config,
+        get_from_holder: impl Fn(EffectBuilder<REv>, T::Id, NodeId) -> Effects<Event<T>>
+            + Send
+            + 'static,
+        latencies: LatencyStore,
+    ) -> Self {
         Gossiper {
             table: GossipTable::new(config),
             gossip_timeout: Duration::from_secs(config.gossip_request_timeout_secs()),
             get_from_peer_timeout: Duration::from_secs(config.get_remainder_timeout_secs()),
-            get_from_holder: Box::new(|_, item, _| {
-                panic!("gossiper should never try to get {}", item)
-            }),
+            get_from_holder: Box::new(get_from_holder),
+            local_queue: VecDeque::new(),
+            forwarded_queue: VecDeque::new(),
+            downgraded_queue: VecDeque::new(),
+            is_flush_scheduled: false,
+            peer_scores: PeerScores::new(
+                latencies,
+                Duration::from_secs(config.gossip_request_timeout_secs()),
+            ),
+            peer_selection_bias: config.peer_selection_bias(),
+            digest_capacity: config.digest_capacity(),
+            digest_false_positive_rate: config.digest_false_positive_rate(),
+            gossip_interval_jitter: config.gossip_interval_jitter(),
+            propagation: Propagation::new(config.digest_capacity() as usize),
+            widely_seen_fraction: config.widely_seen_fraction(),
+            anti_entropy_interval: Duration::from_secs(config.anti_entropy_interval_secs()),
+            spread_localities: config.spread_localities(),
+            redundancy_target: config.redundancy_target(),
+            infection_target: usize::from(config.infection_target()),
+            is_anti_entropy_paused: false,
+            is_fan_out_reduced: false,
         }
     }
 
     /// Handles a new item received from a peer or client.
+    ///
+    /// Rather than being gossiped immediately, the item is queued.  Items submitted by a client
+    /// are gossiped ahead of those received from peers, and with a higher fan-out.  Items most
+    /// peers already hold are gossiped last.
     fn handle_item_received(
         &mut self,
         effect_builder: EffectBuilder<REv>,
         item_id: T::Id,
         source: Source<NodeId>,
     ) -> Effects<Event<T>> {
-        if let Some(should_gossip) = self.table.new_complete_data(&item_id, source.node_id()) {
-            self.gossip(
-                effect_builder,
-                item_id,
-                should_gossip.count,
-                should_gossip.exclude_peers,
-            )
-        } else {
-            Effects::new()
+        match source {
+            Source::Client => match self.table.new_local_data(&item_id) {
+                Some(should_gossip) if self.is_widely_seen(&item_id) => {
+                    self.downgraded_queue.push_back((item_id, should_gossip))
+                }
+                Some(should_gossip) => self.local_queue.push_back((item_id, should_gossip)),
+                None => return Effects::new(),
+            },
+            Source::Peer(sender) => {
+                self.propagation.holds(item_id, sender);
+                match self.table.new_complete_data(&item_id, Some(sender)) {
+                    Some(should_gossip) if self.is_widely_seen(&item_id) => {
+                        self.downgraded_queue.push_back((item_id, should_gossip))
+                    }
+                    Some(should_gossip) => self.forwarded_queue.push_back((item_id, should_gossip)),
+                    None => return Effects::new(),
+                }
+            }
+        }
+
+        self.schedule_flush(effect_builder)
+    }
+
+    /// Handles a request to gossip an item we hold again, treating it like a local submission.
+    fn regossip(
+        &mut self,
+        effect_builder: EffectBuilder<REv>,
+        item_id: T::Id,
+    ) -> Effects<Event<T>> {
+        match self.table.regossip(&item_id) {
+            Some(should_gossip) => {
+                if self.is_widely_seen(&item_id) {
+                    self.downgraded_queue.push_back((item_id, should_gossip));
+                } else {
+                    self.local_queue.push_back((item_id, should_gossip));
+                }
+                self.schedule_flush(effect_builder)
+            }
+            None => Effects::new(),
         }
     }
 
+    /// Returns whether enough of the connected peers are known to hold the item for its gossip to
+    /// be downgraded.
+    fn is_widely_seen(&self, item_id: &T::Id) -> bool {
+        self.propagation
+            .is_widely_seen(item_id, self.widely_seen_fraction)
+    }
+
+    /// Schedules flushing the gossip queues, unless a flush is already scheduled.
+    fn schedule_flush(&mut self, effect_builder: EffectBuilder<REv>) -> Effects<Event<T>> {
+        if self.is_flush_scheduled {
+            // The already-scheduled flush will pick up this item too.
+            return Effects::new();
+        }
+
+        self.is_flush_scheduled = true;
+        effect_builder
+            .immediately()
+            .event(|_| Event::FlushGossipQueue)
+    }
+
+    /// Gossips all queued items, locally-submitted ones first and widely-seen ones last.
+    ///
+    /// The gossip requests are made one after the other, so that the network component handles
+    /// them in the order in which they were dequeued.
+    fn flush_gossip_queue(&mut self, effect_builder: EffectBuilder<REv>) -> Effects<Event<T>> {
+        self.is_flush_scheduled = false;
+        let queued: Vec<_> = self
+            .local_queue
+            .drain(..)
+            .chain(self.forwarded_queue.drain(..))
+            .chain(self.downgraded_queue.drain(..))
+            .collect();
+        if queued.is_empty() {
+            return Effects::new();
+        }
+
+        let weights = self.peer_scores.weights(self.peer_selection_bias);
+        let spread_localities = self.spread_localities;
+        let gossip_all = async move {
+            let mut events: SmallVec<[Event<T>; 2]> = SmallVec::new();
+            for (item_id, should_gossip) in queued {
+                let peers = effect_builder
+                    .gossip_message(
+                        Message::Gossip(item_id),
+                        should_gossip.count,
+                        should_gossip.exclude_peers,
+                        weights.clone(),
+                        spread_localities,
+                    )
+                    .await;
+                events.push(Event::GossipedTo { item_id, peers });
+            }
+            events
+        };
+        smallvec![gossip_all.boxed()]
+    }
+
+    /// Returns the IDs of the items queued for gossiping, in the order they will be gossiped.
+    #[cfg(test)]
+    pub(crate) fn queued_item_ids(&self) -> Vec<T::Id> {
+        self.local_queue
+            .iter()
+            .chain(self.forwarded_queue.iter())
+            .chain(self.downgraded_queue.iter())
+            .map(|(item_id, _)| *item_id)
+            .collect()
+    }
+
     /// Gossips the given item ID to `count` random peers excluding the indicated ones.
+    ///
+    /// Peers are chosen with a bias towards those with high scores.  If most peers already hold
+    /// the item, it is queued behind all other items instead.
     fn gossip(
         &mut self,
         effect_builder: EffectBuilder<REv>,
@@ -171,19 +363,176 @@ impl<T: Item + 'static, REv: ReactorEventT<T>> Gossiper<T, REv> {
         count: usize,
         exclude_peers: HashSet<NodeId>,
     ) -> Effects<Event<T>> {
+        if self.is_widely_seen(&item_id) {
+            let should_gossip = ShouldGossip {
+                count,
+                exclude_peers,
+                is_already_held: true,
+            };
+            self.downgraded_queue.push_back((item_id, should_gossip));
+            return self.schedule_flush(effect_builder);
+        }
+
         let message = Message::Gossip(item_id);
+        let weights = self.peer_scores.weights(self.peer_selection_bias);
         effect_builder
-            .gossip_message(message, count, exclude_peers)
+            .gossip_message(
+                message,
+                count,
+                exclude_peers,
+                weights,
+                self.spread_localities,
+            )
             .event(move |peers| Event::GossipedTo { item_id, peers })
     }
 
+    /// Recomputes the fan-out for the current number of connected peers, if it adapts to the size
+    /// of the network, and halves it while shedding load.
+    ///
+    /// Until the first peer connects, the configured `infection_target` is used.
+    fn update_fan_out(&mut self) {
+        let fan_out = match self.redundancy_target {
+            Some(redundancy_target) => {
+                // We are connected to every other node, so the network consists of them and us.
+                let network_size = self.propagation.connected_peer_count() + 1;
+                fan_out::fan_out(redundancy_target, network_size)
+            }
+            None => self.infection_target,
+        };
+        let fan_out = if self.is_fan_out_reduced {
+            ((fan_out + 1) / 2).max(1)
+        } else {
+            fan_out
+        };
+        debug!(
+            fan_out,
+            is_reduced = self.is_fan_out_reduced,
+            "updated gossip fan-out"
+        );
+        self.table.set_infection_target(fan_out);
+    }
+
+    /// Sets whether anti-entropy rounds are paused to shed load.
+    pub(crate) fn set_anti_entropy_paused(&mut self, is_anti_entropy_paused: bool) {
+        if is_anti_entropy_paused != self.is_anti_entropy_paused {
+            info!(is_anti_entropy_paused, "changed anti-entropy rounds");
+        }
+        self.is_anti_entropy_paused = is_anti_entropy_paused;
+    }
+
+    /// Sets whether the fan-out is halved to shed load.
+    pub(crate) fn set_fan_out_reduced(&mut self, is_fan_out_reduced: bool) {
+        if is_fan_out_reduced != self.is_fan_out_reduced {
+            info!(is_fan_out_reduced, "changed gossip fan-out");
+            self.is_fan_out_reduced = is_fan_out_reduced;
+            self.update_fan_out();
+        }
+    }
+
+    /// Returns a digest of the IDs of all items we hold.
+    fn held_items_digest(&self) -> BloomFilter {
+        let mut digest = BloomFilter::new(self.digest_capacity, self.digest_false_positive_rate);
+        for item_id in self.table.held_data_ids() {
+            digest.insert(item_id);
+        }
+        digest
+    }
+
+    /// Sends a digest of the IDs of all items we hold to a newly-connected peer.
+    fn send_digest(&self, effect_builder: EffectBuilder<REv>, peer: NodeId) -> Effects<Event<T>> {
+        effect_builder
+            .send_message(peer, Message::HeldItemsDigest(self.held_items_digest()))
+            .ignore()
+    }
+
+    /// Schedules the first anti-entropy round, unless they are disabled.
+    pub(crate) fn start_anti_entropy(
+        &self,
+        effect_builder: EffectBuilder<REv>,
+    ) -> Effects<Event<T>> {
+        if self.anti_entropy_interval.as_secs() == 0 {
+            return Effects::new();
+        }
+        effect_builder
+            .delay(self.anti_entropy_interval, Event::AntiEntropyRound)
+            .ignore()
+    }
+
+    /// Sends a digest of the IDs of all items we hold to a random peer, which gossips the items we
+    /// lack to us in response, then schedules the next anti-entropy round.
+    ///
+    /// While anti-entropy is paused, the round is skipped, but the next one is still scheduled.
+    fn anti_entropy_round(&self, effect_builder: EffectBuilder<REv>) -> Effects<Event<T>> {
+        if self.is_anti_entropy_paused {
+            return self.start_anti_entropy(effect_builder);
+        }
+        let message = Message::HeldItemsDigest(self.held_items_digest());
+        let mut effects = effect_builder
+            .gossip_message(
+                message,
+                1,
+                HashSet::<NodeId>::new(),
+                HashMap::new(),
+                self.spread_localities,
+            )
+            .ignore();
+        effects.extend(self.start_anti_entropy(effect_builder));
+        effects
+    }
+
+    /// Handles the digest of items held by a peer, sent on connecting or in an anti-entropy round,
+    /// gossiping all items we hold which are missing from it directly to the peer.
+    fn handle_digest<R: Rng + ?Sized>(
+        &mut self,
+        effect_builder: EffectBuilder<REv>,
+        rng: &mut R,
+        digest: BloomFilter,
+        sender: NodeId,
+    ) -> Effects<Event<T>> {
+        if !digest.is_valid() {
+            warn!(%sender, "received invalid held-items digest");
+            return Effects::new();
+        }
+
+        let lacking = self
+            .table
+            .reconcile(sender, |item_id| digest.contains(item_id));
+        debug!(
+            %sender,
+            count = lacking.len(),
+            "gossiping items missing from peer's digest"
+        );
+        lacking
+            .into_iter()
+            .flat_map(|item_id| {
+                let mut effects = effect_builder
+                    .send_message(sender, Message::Gossip(item_id))
+                    .ignore();
+                effects.extend(self.gossiped_to(
+                    effect_builder,
+                    rng,
+                    item_id,
+                    iter::once(sender).collect(),
+                ));
+                effects
+            })
+            .collect()
+    }
+
     /// Handles the response from the network component detailing which peers it gossiped to.
-    fn gossiped_to(
+    ///
+    /// The timeout of each peer is jittered individually, so that the next gossip round doesn't
+    /// start for all unresponsive peers at once.
+    fn gossiped_to<R: Rng + ?Sized>(
         &mut self,
         effect_builder: EffectBuilder<REv>,
+        rng: &mut R,
         item_id: T::Id,
         peers: HashSet<NodeId>,
     ) -> Effects<Event<T>> {
+        // Never gossip the item to these peers again, so that it doesn't loop back to them.
+        self.table.gossiped_to(&item_id, &peers);
+
         // We don't have any peers to gossip to, so pause the process, which will eventually result
         // in the entry being removed.
         if peers.is_empty() {
@@ -199,8 +548,10 @@ impl<T: Item + 'static, REv: ReactorEventT<T>> Gossiper<T, REv> {
         peers
             .into_iter()
             .map(|peer| {
+                self.peer_scores.gossiped_to(item_id, peer);
+                let timeout = jittered(self.gossip_timeout, self.gossip_interval_jitter, rng);
                 effect_builder
-                    .set_timeout(self.gossip_timeout)
+                    .set_timeout(timeout)
                     .map(move |_| smallvec![Event::CheckGossipTimeout { item_id, peer }])
                     .boxed()
             })
@@ -214,6 +565,7 @@ impl<T: Item + 'static, REv: ReactorEventT<T>> Gossiper<T, REv> {
         item_id: T::Id,
         peer: NodeId,
     ) -> Effects<Event<T>> {
+        self.peer_scores.check_timeout(item_id, peer);
         match self.table.check_timeout(&item_id, peer) {
             GossipAction::ShouldGossip(should_gossip) => self.gossip(
                 effect_builder,
@@ -280,6 +632,7 @@ impl<T: Item + 'static, REv: ReactorEventT<T>> Gossiper<T, REv> {
         item_id: T::Id,
         sender: NodeId,
     ) -> Effects<Event<T>> {
+        self.propagation.holds(item_id, sender);
         let action = if T::ID_IS_COMPLETE_ITEM {
             self.table
                 .new_complete_data(&item_id, Some(sender))
@@ -352,7 +705,11 @@ impl<T: Item + 'static, REv: ReactorEventT<T>> Gossiper<T, REv> {
         is_already_held: bool,
         sender: NodeId,
     ) -> Effects<Event<T>> {
+        self.peer_scores.response_received(item_id, sender);
+        // Whether it held the item already or not, the sender holds it now.
+        self.propagation.holds(item_id, sender);
         let mut effects: Effects<_> = Effects::new();
+        let was_finished = self.table.has_finished(&item_id);
         let action = if is_already_held {
             self.table.already_infected(&item_id, sender)
         } else {
@@ -376,6 +733,9 @@ impl<T: Item + 'static, REv: ReactorEventT<T>> Gossiper<T, REv> {
                 unreachable!("can't have gossiped if we don't hold the complete item")
             }
         }
+        if !was_finished && self.table.has_finished(&item_id) {
+            effects.extend(effect_builder.announce_finished_gossiping(item_id).ignore());
+        }
 
         effects
     }
@@ -409,6 +769,18 @@ impl<T: Item + 'static, REv: ReactorEventT<T>> Gossiper<T, REv> {
     }
 }
 
+impl<T: Item + 'static, REv: ReactorEventT<T>> ComponentLifecycle for Gossiper<T, REv> {
+    fn on_restart(&mut self) {
+        // Responses and timeouts of requests sent before the restart are ignored, as the items
+        // they refer to are no longer being gossiped.
+        self.table.clear_current();
+        self.peer_scores.clear_in_flight();
+        self.local_queue.clear();
+        self.forwarded_queue.clear();
+        self.downgraded_queue.clear();
+    }
+}
+
 impl<T, REv, R> Component<REv, R> for Gossiper<T, REv>
 where
     T: Item + 'static,
@@ -420,7 +792,7 @@ where
     fn handle_event(
         &mut self,
         effect_builder: EffectBuilder<REv>,
-        _rng: &mut R,
+        rng: &mut R,
         event: Self::Event,
     ) -> Effects<Self::Event> {
         debug!(?event, "handling event");
@@ -428,8 +800,22 @@ where
             Event::ItemReceived { item_id, source } => {
                 self.handle_item_received(effect_builder, item_id, source)
             }
+            Event::Regossip { item_id } => self.regossip(effect_builder, item_id),
+            Event::FlushGossipQueue => self.flush_gossip_queue(effect_builder),
+            Event::PeerConnected(peer) => {
+                self.propagation.peer_connected(peer);
+                self.update_fan_out();
+                self.send_digest(effect_builder, peer)
+            }
+            Event::PeerDisconnected(peer) => {
+                self.propagation.peer_disconnected(&peer);
+                self.peer_scores.peer_disconnected(&peer);
+                self.update_fan_out();
+                Effects::new()
+            }
+            Event::AntiEntropyRound => self.anti_entropy_round(effect_builder),
             Event::GossipedTo { item_id, peers } => {
-                self.gossiped_to(effect_builder, item_id, peers)
+                self.gossiped_to(effect_builder, rng, item_id, peers)
             }
             Event::CheckGossipTimeout { item_id, peer } => {
                 self.check_gossip_timeout(effect_builder, item_id, peer)
@@ -443,6 +829,9 @@ where
                     item_id,
                     is_already_held,
                 } => self.handle_gossip_response(effect_builder, item_id, is_already_held, sender),
+                Message::HeldItemsDigest(digest) => {
+                    self.handle_digest(effect_builder, rng, digest, sender)
+                }
             },
             Event::GetFromHolderResult {
                 item_id,
@@ -463,6 +852,19 @@ impl<T: Item + 'static, REv: ReactorEventT<T>> Debug for Gossiper<T, REv> {
             .field("table", &self.table)
             .field("gossip_timeout", &self.gossip_timeout)
             .field("get_from_peer_timeout", &self.get_from_peer_timeout)
+            .field("local_queue", &self.local_queue)
+            .field("forwarded_queue", &self.forwarded_queue)
+            .field("downgraded_queue", &self.downgraded_queue)
+            .field("peer_scores", &self.peer_scores)
+            .field("peer_selection_bias", &self.peer_selection_bias)
+            .field("gossip_interval_jitter", &self.gossip_interval_jitter)
+            .field("propagation", &self.propagation)
+            .field("widely_seen_fraction", &self.widely_seen_fraction)
+            .field("anti_entropy_interval", &self.anti_entropy_interval)
+            .field("redundancy_target", &self.redundancy_target)
+            .field("infection_target", &self.infection_target)
+            .field("is_anti_entropy_paused", &self.is_anti_entropy_paused)
+            .field("is_fan_out_reduced", &self.is_fan_out_reduced)
             .finish()
     }
 }
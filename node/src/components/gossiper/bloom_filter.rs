@@ -0,0 +1,129 @@
+//! Bloom filters over item IDs.
+//!
+//! When two nodes connect, each sends the other a Bloom filter of the IDs of the items it holds,
+//! so that only items the peer lacks are gossiped to it.  A filter never yields false negatives,
+//! but may yield false positives, in which case the item is not offered to the peer directly and
+//! will reach it through regular gossip instead.
+
+use std::f64::consts::LN_2;
+
+use serde::{Deserialize, Serialize};
+
+use crate::crypto::hash;
+
+/// Maximum number of hash functions used by a filter.
+const MAX_HASH_COUNT: u8 = 32;
+/// Maximum size in bytes of a filter received from a peer.
+const MAX_BYTES: usize = 1 << 20;
+
+/// A Bloom filter over item IDs.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct BloomFilter {
+    /// The filter's bits.
+    bits: Vec<u8>,
+    /// The number of bits set per item.
+    hash_count: u8,
+}
+
+impl BloomFilter {
+    /// Constructs an empty filter sized to hold `capacity` items with the given false positive
+    /// rate.
+    ///
+    /// If more items are inserted, the false positive rate increases.
+    pub(crate) fn new(capacity: u32, false_positive_rate: f64) -> Self {
+        let capacity = f64::from(capacity.max(1));
+        let bit_count = (-capacity * false_positive_rate.ln() / (LN_2 * LN_2))
+            .ceil()
+            .max(8.0);
+        let hash_count = (bit_count / capacity * LN_2)
+            .round()
+            .max(1.0)
+            .min(f64::from(MAX_HASH_COUNT)) as u8;
+        BloomFilter {
+            bits: vec![0; (bit_count / 8.0).ceil() as usize],
+            hash_count,
+        }
+    }
+
+    /// Inserts `item_id` into the filter.
+    pub(crate) fn insert<I: Serialize>(&mut self, item_id: &I) {
+        for index in self.bit_indices(item_id) {
+            self.bits[index / 8] |= 1 << (index % 8);
+        }
+    }
+
+    /// Returns whether `item_id` might have been inserted into the filter.
+    ///
+    /// Returns `true` for all inserted items, as well as with the false positive rate for others.
+    pub(crate) fn contains<I: Serialize>(&self, item_id: &I) -> bool {
+        self.bit_indices(item_id)
+            .all(|index| self.bits[index / 8] & (1 << (index % 8)) != 0)
+    }
+
+    /// Returns whether the filter is well-formed and small enough to be checked against.
+    pub(crate) fn is_valid(&self) -> bool {
+        !self.bits.is_empty()
+            && self.bits.len() <= MAX_BYTES
+            && self.hash_count > 0
+            && self.hash_count <= MAX_HASH_COUNT
+    }
+
+    /// Returns the indices of the bits representing `item_id`.
+    ///
+    /// These are derived from two 64-bit values taken from the hash of the serialized ID, using
+    /// double hashing.
+    fn bit_indices<I: Serialize>(&self, item_id: &I) -> impl Iterator<Item = usize> {
+        let serialized = rmp_serde::to_vec(item_id).expect("should serialize item ID");
+        let digest = hash::hash(serialized).to_bytes();
+        let mut first = [0; 8];
+        first.copy_from_slice(&digest[..8]);
+        let mut second = [0; 8];
+        second.copy_from_slice(&digest[8..16]);
+        let first = u64::from_le_bytes(first);
+        // An odd step ensures the indices don't all coincide.
+        let second = u64::from_le_bytes(second) | 1;
+
+        let bit_count = self.bits.len() as u64 * 8;
+        (0..u64::from(self.hash_count))
+            .map(move |i| (first.wrapping_add(i.wrapping_mul(second)) % bit_count) as usize)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn should_contain_inserted_items_only() {
+        let mut filter = BloomFilter::new(1_000, 0.01);
+        assert!(filter.is_valid());
+        for item_id in 0..1_000_u64 {
+            filter.insert(&item_id);
+        }
+        assert!((0..1_000_u64).all(|item_id| filter.contains(&item_id)));
+
+        let false_positives = (1_000..11_000_u64)
+            .filter(|item_id| filter.contains(item_id))
+            .count();
+        assert!(
+            false_positives < 200,
+            "{} false positives out of 10000",
+            false_positives
+        );
+    }
+
+    #[test]
+    fn should_reject_malformed_filters() {
+        let empty = BloomFilter {
+            bits: vec![],
+            hash_count: 1,
+        };
+        assert!(!empty.is_valid());
+
+        let too_many_hashes = BloomFilter {
+            bits: vec![0; 8],
+            hash_count: MAX_HASH_COUNT + 1,
+        };
+        assert!(!too_many_hashes.is_valid());
+    }
+}
@@ -8,17 +8,37 @@ use tracing::error;
 use super::Error;
 
 const DEFAULT_INFECTION_TARGET: u8 = 3;
+const DEFAULT_LOCAL_INFECTION_TARGET: u8 = 6;
 const DEFAULT_SATURATION_LIMIT_PERCENT: u8 = 80;
 pub(super) const MAX_SATURATION_LIMIT_PERCENT: u8 = 99;
 pub(super) const DEFAULT_FINISHED_ENTRY_DURATION_SECS: u64 = 3_600;
+const DEFAULT_MAX_FINISHED_ENTRIES: usize = 100_000;
 const DEFAULT_GOSSIP_REQUEST_TIMEOUT_SECS: u64 = 10;
 const DEFAULT_GET_REMAINDER_TIMEOUT_SECS: u64 = 60;
+const DEFAULT_FETCH_FAILURE_CACHE_SECS: u64 = 30;
+const DEFAULT_MAX_CONCURRENT_FETCHES: usize = 64;
+const DEFAULT_MAX_CONCURRENT_FETCHES_PER_PEER: usize = 16;
+const DEFAULT_BALANCE_FETCHES: bool = true;
+const DEFAULT_FETCH_QUEUE_TIMEOUT_SECS: u64 = 30;
+const DEFAULT_PEER_SELECTION_BIAS: f64 = 1.0;
+const DEFAULT_DIGEST_CAPACITY: u32 = 10_000;
+const DEFAULT_DIGEST_FALSE_POSITIVE_RATE: f64 = 0.01;
+const DEFAULT_GOSSIP_INTERVAL_JITTER: f64 = 0.0;
+const DEFAULT_WIDELY_SEEN_FRACTION: f64 = 0.5;
+const DEFAULT_ANTI_ENTROPY_INTERVAL_SECS: u64 = 120;
+const DEFAULT_SPREAD_LOCALITIES: bool = true;
 
 /// Configuration options for gossiping.
 #[derive(Copy, Clone, Debug, Deserialize, Serialize)]
 pub struct Config {
     /// Target number of peers to infect with a given piece of data.
     infection_target: u8,
+    /// Target number of peers to infect with a given piece of data submitted to this node by a
+    /// client.
+    ///
+    /// Since this node is the entry point of such data into the network, it is generally higher
+    /// than `infection_target` so the data propagates faster.
+    local_infection_target: u8,
     /// The saturation limit as a percentage, with a maximum value of 99.  Used as a termination
     /// condition.
     ///
@@ -32,33 +52,142 @@ pub struct Config {
     /// The longer they are retained, the lower the likelihood of re-gossiping a piece of data.
     /// However, the longer they are retained, the larger the list of finished entries can grow.
     finished_entry_duration_secs: u64,
+    /// The maximum number of finished entries to keep.
+    ///
+    /// Once exceeded, the oldest finished entries are dropped before they reach
+    /// `finished_entry_duration_secs`.  A dropped piece of data is gossiped again if it is
+    /// received again.  If 0, the number is unlimited.
+    max_finished_entries: usize,
     /// The timeout duration in seconds for a single gossip request, i.e. for a single gossip
     /// message sent from this node, it will be considered timed out if the expected response from
     /// that peer is not received within this specified duration.
+    ///
+    /// Must be above zero.  Peers whose round-trip time reaches it are considered as slow as
+    /// possible when choosing gossip targets.
+    #[serde(deserialize_with = "deserialize_gossip_request_timeout_secs")]
     gossip_request_timeout_secs: u64,
     /// The timeout duration in seconds for retrieving the remaining part(s) of newly-discovered
     /// data from a peer which gossiped information about that data to this node.
     get_remainder_timeout_secs: u64,
+    /// The duration in seconds for which a deploy which could not be fetched from a peer is
+    /// reported as missing to further fetch requests, without asking any peer again.
+    ///
+    /// This avoids repeated fetch attempts for deploys which are genuinely unavailable.  If 0,
+    /// failed fetches are not cached.
+    fetch_failure_cache_secs: u64,
+    /// The maximum number of deploys being fetched from peers at the same time.
+    ///
+    /// Further fetches are queued until one of the outstanding ones completes.  If 0, the number
+    /// of concurrent fetches is unlimited.
+    max_concurrent_fetches: usize,
+    /// The maximum number of deploys being fetched from a single peer at the same time.
+    ///
+    /// Further fetches from the peer are queued, like those beyond `max_concurrent_fetches`.  If
+    /// 0, the number of concurrent fetches per peer is unlimited.
+    max_concurrent_fetches_per_peer: usize,
+    /// Whether deploy fetches are spread across the connected peers known to hold the deploy
+    /// rather than being sent to the peer they were first requested from.
+    ///
+    /// Each fetch is sent to the least-loaded peer the deploy has been requested from, taking
+    /// turns between equally loaded ones.  If that peer doesn't provide the deploy in time, it is
+    /// fetched from the requested peer after all.
+    balance_fetches: bool,
+    /// The duration in seconds for which a deploy fetch waits in the queue for a free slot before
+    /// it fails.
+    fetch_queue_timeout_secs: u64,
+    /// How strongly the choice of gossip targets favors peers which respond reliably and quickly.
+    ///
+    /// Must not be negative.  With a bias of 0, targets are chosen uniformly at random.  With a
+    /// bias of `b`, the best possible peer is `e^(2b)` times as likely to be chosen as the worst.
+    #[serde(deserialize_with = "deserialize_peer_selection_bias")]
+    peer_selection_bias: f64,
+    /// The number of item IDs the digest exchanged with newly connected peers is sized for.
+    ///
+    /// The digest is a Bloom filter of the IDs of all items held, letting the peer gossip only
+    /// those items to us which we lack.  If more items are held, the false positive rate rises.
+    digest_capacity: u32,
+    /// The rate of false positives of the digest when holding `digest_capacity` items, i.e. the
+    /// probability of the peer wrongly assuming we hold an item.
+    ///
+    /// Must be greater than 0 and less than 1.  Lower rates require larger digests.
+    #[serde(deserialize_with = "deserialize_digest_false_positive_rate")]
+    digest_false_positive_rate: f64,
+    /// The maximum random delay added to the interval after which unresponsive peers are gossiped
+    /// to again, as a fraction of `gossip_request_timeout_secs`.
+    ///
+    /// Must be between 0 and 1.  Jitter spreads out the gossip rounds of different nodes and items
+    /// which would otherwise be in lockstep.  If 0, the interval is not randomized.
+    #[serde(deserialize_with = "deserialize_gossip_interval_jitter")]
+    gossip_interval_jitter: f64,
+    /// The fraction of connected peers known to hold an item, via their gossip messages and
+    /// responses, above which the item is gossiped after all other queued items.
+    ///
+    /// Must be greater than 0 and at most 1.  Gossiping an item most peers already hold is of
+    /// little value, so fresher items are given precedence.
+    #[serde(deserialize_with = "deserialize_widely_seen_fraction")]
+    widely_seen_fraction: f64,
+    /// The interval in seconds between anti-entropy rounds, in each of which the digest of items
+    /// held is sent to a random peer, which then gossips the items we lack to us.
+    ///
+    /// This recovers items whose gossip was missed, e.g. during a transient disconnect.  If 0, no
+    /// anti-entropy rounds are run.
+    anti_entropy_interval_secs: u64,
+    /// Whether gossip targets are spread across as many distinct localities as possible, if the
+    /// network component knows the localities of peers.
+    ///
+    /// This avoids gossiping redundantly to several peers in the same datacenter.
+    spread_localities: bool,
+    /// The number of copies of each item every node is expected to receive beyond the `ln(n)`
+    /// needed to reach all `n` nodes of the network.
+    ///
+    /// If set, the fan-out is computed from the number of connected peers to meet this target,
+    /// replacing `infection_target`, and locally-submitted items are gossiped to at least that
+    /// many peers too.  Must not be negative.  If unset, `infection_target` is used.
+    #[serde(default, deserialize_with = "deserialize_redundancy_target")]
+    redundancy_target: Option<f64>,
 }
 
 impl Config {
     #[cfg(test)]
     pub(crate) fn new(
         infection_target: u8,
+        local_infection_target: u8,
         saturation_limit_percent: u8,
         finished_entry_duration_secs: u64,
         gossip_request_timeout_secs: u64,
         get_remainder_timeout_secs: u64,
+        peer_selection_bias: f64,
     ) -> Result<Self, Error> {
         if saturation_limit_percent > MAX_SATURATION_LIMIT_PERCENT {
             return Err(Error::InvalidSaturationLimit);
         }
+        if gossip_request_timeout_secs == 0 {
+            return Err(Error::InvalidGossipRequestTimeout);
+        }
+        if !is_valid_peer_selection_bias(peer_selection_bias) {
+            return Err(Error::InvalidPeerSelectionBias);
+        }
         Ok(Config {
             infection_target,
+            local_infection_target,
             saturation_limit_percent,
             finished_entry_duration_secs,
+            max_finished_entries: DEFAULT_MAX_FINISHED_ENTRIES,
             gossip_request_timeout_secs,
             get_remainder_timeout_secs,
+            fetch_failure_cache_secs: DEFAULT_FETCH_FAILURE_CACHE_SECS,
+            max_concurrent_fetches: DEFAULT_MAX_CONCURRENT_FETCHES,
+            max_concurrent_fetches_per_peer: DEFAULT_MAX_CONCURRENT_FETCHES_PER_PEER,
+            balance_fetches: DEFAULT_BALANCE_FETCHES,
+            fetch_queue_timeout_secs: DEFAULT_FETCH_QUEUE_TIMEOUT_SECS,
+            peer_selection_bias,
+            digest_capacity: DEFAULT_DIGEST_CAPACITY,
+            digest_false_positive_rate: DEFAULT_DIGEST_FALSE_POSITIVE_RATE,
+            gossip_interval_jitter: DEFAULT_GOSSIP_INTERVAL_JITTER,
+            widely_seen_fraction: DEFAULT_WIDELY_SEEN_FRACTION,
+            anti_entropy_interval_secs: DEFAULT_ANTI_ENTROPY_INTERVAL_SECS,
+            spread_localities: DEFAULT_SPREAD_LOCALITIES,
+            redundancy_target: None,
         })
     }
 
@@ -66,6 +195,10 @@ impl Config {
         self.infection_target
     }
 
+    pub(crate) fn local_infection_target(&self) -> u8 {
+        self.local_infection_target
+    }
+
     pub(crate) fn saturation_limit_percent(&self) -> u8 {
         self.saturation_limit_percent
     }
@@ -74,6 +207,17 @@ impl Config {
         self.finished_entry_duration_secs
     }
 
+    pub(crate) fn max_finished_entries(&self) -> usize {
+        self.max_finished_entries
+    }
+
+    /// Returns a copy of this config with the given maximum number of finished entries.
+    #[cfg(test)]
+    pub(crate) fn with_max_finished_entries(mut self, max_finished_entries: usize) -> Self {
+        self.max_finished_entries = max_finished_entries;
+        self
+    }
+
     pub(crate) fn gossip_request_timeout_secs(&self) -> u64 {
         self.gossip_request_timeout_secs
     }
@@ -81,16 +225,100 @@ impl Config {
     pub(crate) fn get_remainder_timeout_secs(&self) -> u64 {
         self.get_remainder_timeout_secs
     }
+
+    pub(crate) fn fetch_failure_cache_secs(&self) -> u64 {
+        self.fetch_failure_cache_secs
+    }
+
+    pub(crate) fn max_concurrent_fetches(&self) -> usize {
+        self.max_concurrent_fetches
+    }
+
+    pub(crate) fn max_concurrent_fetches_per_peer(&self) -> usize {
+        self.max_concurrent_fetches_per_peer
+    }
+
+    pub(crate) fn balance_fetches(&self) -> bool {
+        self.balance_fetches
+    }
+
+    pub(crate) fn fetch_queue_timeout_secs(&self) -> u64 {
+        self.fetch_queue_timeout_secs
+    }
+
+    /// Returns a copy of this config with the given maximum number of concurrent fetches.
+    #[cfg(test)]
+    pub(crate) fn with_max_concurrent_fetches(mut self, max_concurrent_fetches: usize) -> Self {
+        self.max_concurrent_fetches = max_concurrent_fetches;
+        self
+    }
+
+    pub(crate) fn peer_selection_bias(&self) -> f64 {
+        self.peer_selection_bias
+    }
+
+    pub(crate) fn digest_capacity(&self) -> u32 {
+        self.digest_capacity
+    }
+
+    pub(crate) fn digest_false_positive_rate(&self) -> f64 {
+        self.digest_false_positive_rate
+    }
+
+    pub(crate) fn gossip_interval_jitter(&self) -> f64 {
+        self.gossip_interval_jitter
+    }
+
+    pub(crate) fn widely_seen_fraction(&self) -> f64 {
+        self.widely_seen_fraction
+    }
+
+    pub(crate) fn anti_entropy_interval_secs(&self) -> u64 {
+        self.anti_entropy_interval_secs
+    }
+
+    /// Returns a copy of this config with the given interval between anti-entropy rounds.
+    #[cfg(test)]
+    pub(crate) fn with_anti_entropy_interval_secs(
+        mut self,
+        anti_entropy_interval_secs: u64,
+    ) -> Self {
+        self.anti_entropy_interval_secs = anti_entropy_interval_secs;
+        self
+    }
+
+    pub(crate) fn spread_localities(&self) -> bool {
+        self.spread_localities
+    }
+
+    pub(crate) fn redundancy_target(&self) -> Option<f64> {
+        self.redundancy_target
+    }
 }
 
 impl Default for Config {
     fn default() -> Self {
         Config {
             infection_target: DEFAULT_INFECTION_TARGET,
+            local_infection_target: DEFAULT_LOCAL_INFECTION_TARGET,
             saturation_limit_percent: DEFAULT_SATURATION_LIMIT_PERCENT,
             finished_entry_duration_secs: DEFAULT_FINISHED_ENTRY_DURATION_SECS,
+            max_finished_entries: DEFAULT_MAX_FINISHED_ENTRIES,
             gossip_request_timeout_secs: DEFAULT_GOSSIP_REQUEST_TIMEOUT_SECS,
             get_remainder_timeout_secs: DEFAULT_GET_REMAINDER_TIMEOUT_SECS,
+            fetch_failure_cache_secs: DEFAULT_FETCH_FAILURE_CACHE_SECS,
+            max_concurrent_fetches: DEFAULT_MAX_CONCURRENT_FETCHES,
+            max_concurrent_fetches_per_peer: DEFAULT_MAX_CONCURRENT_FETCHES_PER_PEER,
+            balance_fetches: DEFAULT_BALANCE_FETCHES,
+            fetch_queue_timeout_secs: DEFAULT_FETCH_QUEUE_TIMEOUT_SECS,
+            peer_selection_bias: DEFAULT_PEER_SELECTION_BIAS,
+            digest_capacity: DEFAULT_DIGEST_CAPACITY,
+            digest_false_positive_rate: DEFAULT_DIGEST_FALSE_POSITIVE_RATE,
+            gossip_interval_jitter: DEFAULT_GOSSIP_INTERVAL_JITTER,
+            widely_seen_fraction: DEFAULT_WIDELY_SEEN_FRACTION,
+            anti_entropy_interval_secs: DEFAULT_ANTI_ENTROPY_INTERVAL_SECS,
+            spread_localities: DEFAULT_SPREAD_LOCALITIES,
+            redundancy_target: None,
         }
     }
 }
@@ -115,6 +343,133 @@ where
     Ok(saturation_limit_percent)
 }
 
+/// Deserializes a `u64` but fails if it's zero.
+fn deserialize_gossip_request_timeout_secs<'de, D>(deserializer: D) -> Result<u64, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    let gossip_request_timeout_secs = u64::deserialize(deserializer)?;
+    if gossip_request_timeout_secs == 0 {
+        error!("gossip_request_timeout_secs of 0 is invalid");
+        return Err(SerdeError::invalid_value(
+            Unexpected::Unsigned(gossip_request_timeout_secs),
+            &"a value above zero",
+        ));
+    }
+
+    Ok(gossip_request_timeout_secs)
+}
+
+fn is_valid_peer_selection_bias(peer_selection_bias: f64) -> bool {
+    peer_selection_bias.is_finite() && peer_selection_bias >= 0.0
+}
+
+/// Deserializes an `f64` but fails if it's negative or not finite.
+fn deserialize_peer_selection_bias<'de, D>(deserializer: D) -> Result<f64, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    let peer_selection_bias = f64::deserialize(deserializer)?;
+    if !is_valid_peer_selection_bias(peer_selection_bias) {
+        error!("peer_selection_bias of {} is invalid", peer_selection_bias);
+        return Err(SerdeError::invalid_value(
+            Unexpected::Float(peer_selection_bias),
+            &"a finite, non-negative number",
+        ));
+    }
+
+    Ok(peer_selection_bias)
+}
+
+fn is_valid_false_positive_rate(false_positive_rate: f64) -> bool {
+    false_positive_rate > 0.0 && false_positive_rate < 1.0
+}
+
+/// Deserializes an `f64` but fails if it's not strictly between 0 and 1.
+fn deserialize_digest_false_positive_rate<'de, D>(deserializer: D) -> Result<f64, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    let false_positive_rate = f64::deserialize(deserializer)?;
+    if !is_valid_false_positive_rate(false_positive_rate) {
+        error!(
+            "digest_false_positive_rate of {} is invalid",
+            false_positive_rate
+        );
+        return Err(SerdeError::invalid_value(
+            Unexpected::Float(false_positive_rate),
+            &"a number greater than 0 and less than 1",
+        ));
+    }
+
+    Ok(false_positive_rate)
+}
+
+fn is_valid_gossip_interval_jitter(jitter: f64) -> bool {
+    (0.0..=1.0).contains(&jitter)
+}
+
+/// Deserializes an `f64` but fails if it's not between 0 and 1 inclusive.
+fn deserialize_gossip_interval_jitter<'de, D>(deserializer: D) -> Result<f64, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    let jitter = f64::deserialize(deserializer)?;
+    if !is_valid_gossip_interval_jitter(jitter) {
+        error!("gossip_interval_jitter of {} is invalid", jitter);
+        return Err(SerdeError::invalid_value(
+            Unexpected::Float(jitter),
+            &"a number between 0 and 1 inclusive",
+        ));
+    }
+
+    Ok(jitter)
+}
+
+fn is_valid_widely_seen_fraction(fraction: f64) -> bool {
+    fraction > 0.0 && fraction <= 1.0
+}
+
+/// Deserializes an `f64` but fails if it's not greater than 0 and at most 1.
+fn deserialize_widely_seen_fraction<'de, D>(deserializer: D) -> Result<f64, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    let fraction = f64::deserialize(deserializer)?;
+    if !is_valid_widely_seen_fraction(fraction) {
+        error!("widely_seen_fraction of {} is invalid", fraction);
+        return Err(SerdeError::invalid_value(
+            Unexpected::Float(fraction),
+            &"a number greater than 0 and at most 1",
+        ));
+    }
+
+    Ok(fraction)
+}
+
+fn is_valid_redundancy_target(redundancy_target: f64) -> bool {
+    redundancy_target.is_finite() && redundancy_target >= 0.0
+}
+
+/// Deserializes an optional `f64` but fails if it's negative or not finite.
+fn deserialize_redundancy_target<'de, D>(deserializer: D) -> Result<Option<f64>, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    let redundancy_target = Option::<f64>::deserialize(deserializer)?;
+    if let Some(redundancy_target) = redundancy_target {
+        if !is_valid_redundancy_target(redundancy_target) {
+            error!("redundancy_target of {} is invalid", redundancy_target);
+            return Err(SerdeError::invalid_value(
+                Unexpected::Float(redundancy_target),
+                &"a finite, non-negative number",
+            ));
+        }
+    }
+
+    Ok(redundancy_target)
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -124,10 +479,25 @@ mod tests {
         // saturation_limit_percent > MAX_SATURATION_LIMIT_PERCENT
         let invalid_config = Config {
             infection_target: 3,
+            local_infection_target: 6,
             saturation_limit_percent: MAX_SATURATION_LIMIT_PERCENT + 1,
             finished_entry_duration_secs: DEFAULT_FINISHED_ENTRY_DURATION_SECS,
+            max_finished_entries: DEFAULT_MAX_FINISHED_ENTRIES,
             gossip_request_timeout_secs: DEFAULT_GOSSIP_REQUEST_TIMEOUT_SECS,
             get_remainder_timeout_secs: DEFAULT_GET_REMAINDER_TIMEOUT_SECS,
+            fetch_failure_cache_secs: DEFAULT_FETCH_FAILURE_CACHE_SECS,
+            max_concurrent_fetches: DEFAULT_MAX_CONCURRENT_FETCHES,
+            max_concurrent_fetches_per_peer: DEFAULT_MAX_CONCURRENT_FETCHES_PER_PEER,
+            balance_fetches: DEFAULT_BALANCE_FETCHES,
+            fetch_queue_timeout_secs: DEFAULT_FETCH_QUEUE_TIMEOUT_SECS,
+            peer_selection_bias: DEFAULT_PEER_SELECTION_BIAS,
+            digest_capacity: DEFAULT_DIGEST_CAPACITY,
+            digest_false_positive_rate: DEFAULT_DIGEST_FALSE_POSITIVE_RATE,
+            gossip_interval_jitter: DEFAULT_GOSSIP_INTERVAL_JITTER,
+            widely_seen_fraction: DEFAULT_WIDELY_SEEN_FRACTION,
+            anti_entropy_interval_secs: DEFAULT_ANTI_ENTROPY_INTERVAL_SECS,
+            spread_localities: DEFAULT_SPREAD_LOCALITIES,
+            redundancy_target: None,
         };
 
         // Parsing should fail.
@@ -137,11 +507,87 @@ mod tests {
         // Construction should fail.
         assert!(Config::new(
             3,
+            6,
             MAX_SATURATION_LIMIT_PERCENT + 1,
             DEFAULT_FINISHED_ENTRY_DURATION_SECS,
             DEFAULT_GOSSIP_REQUEST_TIMEOUT_SECS,
             DEFAULT_GET_REMAINDER_TIMEOUT_SECS,
+            DEFAULT_PEER_SELECTION_BIAS,
+        )
+        .is_err());
+
+        // gossip_request_timeout_secs == 0
+        let invalid_config = Config {
+            gossip_request_timeout_secs: 0,
+            ..Config::default()
+        };
+        let config_as_json = serde_json::to_string(&invalid_config).unwrap();
+        assert!(serde_json::from_str::<Config>(&config_as_json).is_err());
+        assert!(Config::new(
+            3,
+            6,
+            DEFAULT_SATURATION_LIMIT_PERCENT,
+            DEFAULT_FINISHED_ENTRY_DURATION_SECS,
+            0,
+            DEFAULT_GET_REMAINDER_TIMEOUT_SECS,
+            DEFAULT_PEER_SELECTION_BIAS,
+        )
+        .is_err());
+
+        // peer_selection_bias < 0
+        let invalid_config = Config {
+            peer_selection_bias: -1.0,
+            ..Config::default()
+        };
+        let config_as_json = serde_json::to_string(&invalid_config).unwrap();
+        assert!(serde_json::from_str::<Config>(&config_as_json).is_err());
+        assert!(Config::new(
+            3,
+            6,
+            DEFAULT_SATURATION_LIMIT_PERCENT,
+            DEFAULT_FINISHED_ENTRY_DURATION_SECS,
+            DEFAULT_GOSSIP_REQUEST_TIMEOUT_SECS,
+            DEFAULT_GET_REMAINDER_TIMEOUT_SECS,
+            -1.0,
         )
-        .is_err())
+        .is_err());
+
+        // digest_false_positive_rate outside of (0, 1)
+        for &digest_false_positive_rate in &[0.0, 1.0] {
+            let invalid_config = Config {
+                digest_false_positive_rate,
+                ..Config::default()
+            };
+            let config_as_json = serde_json::to_string(&invalid_config).unwrap();
+            assert!(serde_json::from_str::<Config>(&config_as_json).is_err());
+        }
+
+        // gossip_interval_jitter outside of [0, 1]
+        for &gossip_interval_jitter in &[-0.1, 1.1] {
+            let invalid_config = Config {
+                gossip_interval_jitter,
+                ..Config::default()
+            };
+            let config_as_json = serde_json::to_string(&invalid_config).unwrap();
+            assert!(serde_json::from_str::<Config>(&config_as_json).is_err());
+        }
+
+        // widely_seen_fraction outside of (0, 1]
+        for &widely_seen_fraction in &[0.0, 1.1] {
+            let invalid_config = Config {
+                widely_seen_fraction,
+                ..Config::default()
+            };
+            let config_as_json = serde_json::to_string(&invalid_config).unwrap();
+            assert!(serde_json::from_str::<Config>(&config_as_json).is_err());
+        }
+
+        // redundancy_target < 0
+        let invalid_config = Config {
+            redundancy_target: Some(-1.0),
+            ..Config::default()
+        };
+        let config_as_json = serde_json::to_string(&invalid_config).unwrap();
+        assert!(serde_json::from_str::<Config>(&config_as_json).is_err());
     }
 }
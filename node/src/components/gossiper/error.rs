@@ -12,6 +12,14 @@ pub enum Error {
     )]
     InvalidSaturationLimit,
 
+    /// Invalid configuration value for `gossip_request_timeout_secs`.
+    #[error("invalid gossip_request_timeout_secs - should be above zero")]
+    InvalidGossipRequestTimeout,
+
+    /// Invalid configuration value for `peer_selection_bias`.
+    #[error("invalid peer_selection_bias - should be finite and non-negative")]
+    InvalidPeerSelectionBias,
+
     /// Attempted to reset data which had not been paused.
     #[error("gossiping is not paused for this data")]
     NotPaused,
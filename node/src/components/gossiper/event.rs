@@ -17,6 +17,16 @@ pub enum Event<T: Item> {
         item_id: T::Id,
         source: Source<NodeId>,
     },
+    /// An item we hold should be gossiped again, as it might have been lost.
+    Regossip { item_id: T::Id },
+    /// Queued items should be gossiped.
+    FlushGossipQueue,
+    /// A connection to a new peer has been established.
+    PeerConnected(NodeId),
+    /// The connection to a peer has been lost.
+    PeerDisconnected(NodeId),
+    /// The items held should be reconciled with those of a random peer.
+    AntiEntropyRound,
     /// The network component gossiped to the included peers.
     GossipedTo {
         item_id: T::Id,
@@ -45,6 +55,11 @@ impl<T: Item> Display for Event<T> {
             Event::ItemReceived { item_id, source } => {
                 write!(formatter, "new item {} received from {}", item_id, source)
             }
+            Event::Regossip { item_id } => write!(formatter, "regossip {}", item_id),
+            Event::FlushGossipQueue => write!(formatter, "flush gossip queue"),
+            Event::PeerConnected(peer) => write!(formatter, "new peer {} connected", peer),
+            Event::PeerDisconnected(peer) => write!(formatter, "peer {} disconnected", peer),
+            Event::AntiEntropyRound => write!(formatter, "anti-entropy round"),
             Event::GossipedTo { item_id, peers } => write!(
                 formatter,
                 "gossiped {} to {}",
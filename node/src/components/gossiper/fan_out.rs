@@ -0,0 +1,54 @@
+//! Fan-out adapting to the size of the network to meet a redundancy target.
+//!
+//! If every node gossips an item to `f` random peers, each of the `n` nodes receives about `f`
+//! copies of it, and the number of copies a node receives is approximately Poisson-distributed.  A
+//! node then misses the item with probability `e^(-f)`, so `n * e^(-f)` nodes are expected to miss
+//! it.  A fan-out of `ln(n) + c` keeps this at `e^(-c)` however large the network grows, and
+//! reaches every node with probability `e^(-e^(-c))`.  The redundancy target is `c`: the number of
+//! copies every node is expected to receive beyond the `ln(n)` needed to reach all of them.
+
+/// Returns the fan-out meeting `redundancy_target` in a network of `network_size` nodes, including
+/// us.
+///
+/// The fan-out is rounded up, and is at least 1 and at most the number of peers.
+pub(super) fn fan_out(redundancy_target: f64, network_size: usize) -> usize {
+    let peer_count = network_size.saturating_sub(1);
+    let fan_out = ((network_size.max(1) as f64).ln() + redundancy_target).ceil();
+    // Casting saturates, so a negative or huge fan-out is clamped as well.
+    (fan_out as usize).min(peer_count).max(1)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const REDUNDANCY_TARGET: f64 = 2.0;
+
+    #[test]
+    fn should_keep_redundancy_near_target_as_network_grows() {
+        let mut previous_fan_out = 0;
+        for &network_size in &[10, 100, 1_000, 10_000, 100_000] {
+            let fan_out = fan_out(REDUNDANCY_TARGET, network_size);
+            assert!(fan_out > previous_fan_out);
+            previous_fan_out = fan_out;
+
+            // Rounding up the fan-out adds less than one copy to the redundancy.
+            let redundancy = fan_out as f64 - (network_size as f64).ln();
+            assert!(
+                redundancy >= REDUNDANCY_TARGET && redundancy < REDUNDANCY_TARGET + 1.0,
+                "redundancy {} with fan-out {} in a network of {}",
+                redundancy,
+                fan_out,
+                network_size
+            );
+        }
+    }
+
+    #[test]
+    fn should_not_exceed_number_of_peers() {
+        assert_eq!(fan_out(REDUNDANCY_TARGET, 0), 1);
+        assert_eq!(fan_out(REDUNDANCY_TARGET, 1), 1);
+        assert_eq!(fan_out(REDUNDANCY_TARGET, 3), 2);
+        assert_eq!(fan_out(1_000.0, 50), 49);
+    }
+}
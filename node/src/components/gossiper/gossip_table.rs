@@ -1,7 +1,7 @@
 #[cfg(not(test))]
 use std::time::Instant;
 use std::{
-    collections::{hash_map::Entry, HashMap, HashSet},
+    collections::{hash_map::Entry, HashMap, HashSet, VecDeque},
     fmt::Display,
     hash::Hash,
     time::Duration,
@@ -9,7 +9,7 @@ use std::{
 
 #[cfg(test)]
 use fake_instant::FakeClock as Instant;
-use tracing::warn;
+use tracing::{debug, warn};
 
 use super::{Config, Error};
 use crate::small_network::NodeId;
@@ -36,13 +36,14 @@ pub(crate) enum GossipAction {
 pub(crate) struct ShouldGossip {
     /// The number of copies of the gossip message to send.
     pub(crate) count: usize,
-    /// Peers we should avoid gossiping this data to, since they already hold it.
+    /// Peers we should avoid gossiping this data to, since they already hold it or we have
+    /// gossiped it to them before.
     pub(crate) exclude_peers: HashSet<NodeId>,
     /// Whether we already held the full data or not.
     pub(crate) is_already_held: bool,
 }
 
-#[derive(Debug, Default)]
+#[derive(Debug)]
 struct State {
     /// The peers excluding us which hold the data.
     holders: HashSet<NodeId>,
@@ -51,34 +52,53 @@ struct State {
     /// The subset of `holders` we have infected.  Not just a count so we don't attribute the same
     /// peer multiple times.
     infected_by_us: HashSet<NodeId>,
+    /// The peers we have gossiped this data to, whether they responded or not.  They are never
+    /// gossiped it again, so that it doesn't loop back to them.
+    gossiped_to: HashSet<NodeId>,
+    /// Whether we are the origin of this data, i.e. it was submitted to or generated on this node.
+    is_local: bool,
     /// The count of in-flight gossip messages sent by us for this data.
     in_flight_count: usize,
+    /// The target number of peers to infect with this data.
+    infection_target: usize,
+    /// We gossip this data while the number of holders doesn't exceed `holders_limit`.
+    holders_limit: usize,
 }
 
 impl State {
+    /// Returns a new, empty `State` with the given termination conditions.
+    fn new(infection_target: usize, holders_limit: usize, is_local: bool) -> Self {
+        State {
+            holders: HashSet::new(),
+            held_by_us: false,
+            infected_by_us: HashSet::new(),
+            gossiped_to: HashSet::new(),
+            is_local,
+            in_flight_count: 0,
+            infection_target,
+            holders_limit,
+        }
+    }
+
     /// Returns whether we should finish gossiping this data.
-    fn is_finished(&self, infection_target: usize, holders_limit: usize) -> bool {
-        self.infected_by_us.len() >= infection_target || self.holders.len() >= holders_limit
+    fn is_finished(&self) -> bool {
+        self.infected_by_us.len() >= self.infection_target
+            || self.holders.len() >= self.holders_limit
     }
 
     /// Returns a `GossipAction` derived from the given state.
-    fn action(
-        &mut self,
-        infection_target: usize,
-        holders_limit: usize,
-        is_new: bool,
-    ) -> GossipAction {
-        if self.is_finished(infection_target, holders_limit) {
+    fn action(&mut self, is_new: bool) -> GossipAction {
+        if self.is_finished() {
             return GossipAction::Noop;
         }
 
         if self.held_by_us {
-            let count = infection_target.saturating_sub(self.in_flight_count);
+            let count = self.infection_target.saturating_sub(self.in_flight_count);
             if count > 0 {
                 self.in_flight_count += count;
                 return GossipAction::ShouldGossip(ShouldGossip {
                     count,
-                    exclude_peers: self.holders.clone(),
+                    exclude_peers: self.holders.union(&self.gossiped_to).copied().collect(),
                     is_already_held: !is_new,
                 });
             } else {
@@ -104,8 +124,14 @@ pub(crate) struct GossipTable<T> {
     /// Data IDs for which gossiping is still ongoing.
     current: HashMap<T, State>,
     /// Data IDs for which gossiping is complete.  The map's values are the times after which the
-    /// relevant entries can be removed.
+    /// relevant entries can be removed.  The oldest entries are removed earlier if there are more
+    /// than `max_finished_entries`.
     finished: HashMap<T, Instant>,
+    /// The finished entries in the order they finished, with their timeouts.
+    ///
+    /// Used to drop the oldest entries once there are more than `max_finished_entries`.  Entries
+    /// since removed from or replaced in `finished` are skipped, as their timeouts don't match.
+    finished_order: VecDeque<(T, Instant)>,
     /// Data IDs for which gossiping has been paused (likely due to detecting that the data was not
     /// correct as per our current knowledge).  Such data could later be decided as still requiring
     /// to be gossiped, so we retain the `State` part here in order to resume gossiping.
@@ -115,25 +141,66 @@ pub(crate) struct GossipTable<T> {
     /// Derived from `Config::saturation_limit_percent` - we gossip data while the number of
     /// holders doesn't exceed `holders_limit`.
     holders_limit: usize,
+    /// See `Config::local_infection_target`.
+    ///
+    /// Raised to `infection_target` if that is set higher.
+    local_infection_target: usize,
+    /// Derived from `Config::saturation_limit_percent` and `Config::local_infection_target` - we
+    /// gossip locally-submitted data while the number of holders doesn't exceed
+    /// `local_holders_limit`.
+    local_holders_limit: usize,
     /// See `Config::finished_entry_duration`.
     finished_entry_duration: Duration,
+    /// See `Config::max_finished_entries`.
+    max_finished_entries: usize,
+    /// See `Config::local_infection_target`.
+    configured_local_infection_target: usize,
+    /// See `Config::saturation_limit_percent`.
+    saturation_limit_percent: u8,
+}
+
+/// Returns the number of holders at which gossiping to `infection_target` peers stops, given the
+/// saturation limit.
+fn holders_limit(infection_target: usize, saturation_limit_percent: u8) -> usize {
+    (100 * infection_target) / (100 - usize::from(saturation_limit_percent))
 }
 
 impl<T: Copy + Eq + Hash + Display> GossipTable<T> {
     /// Returns a new `GossipTable` using the provided configuration.
     pub(crate) fn new(config: Config) -> Self {
-        let holders_limit = (100 * usize::from(config.infection_target()))
-            / (100 - usize::from(config.saturation_limit_percent()));
+        let infection_target = usize::from(config.infection_target());
+        let local_infection_target = usize::from(config.local_infection_target());
+        let saturation_limit_percent = config.saturation_limit_percent();
         GossipTable {
             current: HashMap::new(),
             finished: HashMap::new(),
+            finished_order: VecDeque::new(),
             paused: HashMap::new(),
-            infection_target: usize::from(config.infection_target()),
-            holders_limit,
+            infection_target,
+            holders_limit: holders_limit(infection_target, saturation_limit_percent),
+            local_infection_target,
+            local_holders_limit: holders_limit(local_infection_target, saturation_limit_percent),
             finished_entry_duration: Duration::from_secs(config.finished_entry_duration_secs()),
+            max_finished_entries: config.max_finished_entries(),
+            configured_local_infection_target: local_infection_target,
+            saturation_limit_percent,
         }
     }
 
+    /// Sets the target number of peers to infect with data entering the table from now on, e.g.
+    /// as computed from the size of the network.
+    ///
+    /// Locally-submitted data is gossiped to at least as many peers.  Data already being gossiped
+    /// keeps its target.
+    pub(crate) fn set_infection_target(&mut self, infection_target: usize) {
+        let saturation_limit_percent = self.saturation_limit_percent;
+        self.infection_target = infection_target;
+        self.holders_limit = holders_limit(infection_target, saturation_limit_percent);
+        self.local_infection_target = infection_target.max(self.configured_local_infection_target);
+        self.local_holders_limit =
+            holders_limit(self.local_infection_target, saturation_limit_percent);
+    }
+
     /// We received knowledge about potentially new data with given ID from the given peer.  This
     /// should only be called where we don't already hold everything locally we need to be able to
     /// gossip it onwards.  If we are able to gossip the data already, call `new_data` instead.
@@ -159,13 +226,18 @@ impl<T: Copy + Eq + Hash + Display> GossipTable<T> {
                 let is_new = false;
                 let state = entry.get_mut();
                 let _ = state.holders.insert(holder);
-                state.action(self.infection_target, self.holders_limit, is_new)
+                state.action(is_new)
             }
             Entry::Vacant(entry) => {
                 let is_new = true;
-                let state = entry.insert(State::default());
+                let is_local = false;
+                let state = entry.insert(State::new(
+                    self.infection_target,
+                    self.holders_limit,
+                    is_local,
+                ));
                 let _ = state.holders.insert(holder);
-                state.action(self.infection_target, self.holders_limit, is_new)
+                state.action(is_new)
             }
         }
     }
@@ -177,11 +249,51 @@ impl<T: Copy + Eq + Hash + Display> GossipTable<T> {
     /// This should only be called once we hold everything locally we need to be able to gossip it
     /// onwards.  If we aren't able to gossip this data yet, call `new_data_id` instead.
     ///
+    /// If we originated the data and are still gossiping it, receiving it from a peer is an echo
+    /// of our own gossip: The peer is recorded as a holder, but the data isn't gossiped any further
+    /// because of it.
+    ///
     /// Returns whether we should gossip it, and a list of peers to exclude.
     pub(crate) fn new_complete_data(
         &mut self,
         data_id: &T,
         maybe_holder: Option<NodeId>,
+    ) -> Option<ShouldGossip> {
+        let is_local = false;
+        self.complete_data(data_id, maybe_holder, is_local)
+    }
+
+    /// We received potentially new data with given ID from a client submitting it to this node.
+    ///
+    /// As for `new_complete_data`, but if the data is new to us, it is gossiped to
+    /// `local_infection_target` peers rather than `infection_target` ones, since we are the entry
+    /// point of this data into the network.
+    ///
+    /// Returns whether we should gossip it, and a list of peers to exclude.
+    pub(crate) fn new_local_data(&mut self, data_id: &T) -> Option<ShouldGossip> {
+        let is_local = true;
+        self.complete_data(data_id, None, is_local)
+    }
+
+    /// We want to gossip data we hold again, as it may have been lost in the network.
+    ///
+    /// A finished entry is restarted as though the data had just been submitted locally.  Entries
+    /// which are still being gossiped or have been paused are left untouched.
+    ///
+    /// Returns whether we should gossip it, and a list of peers to exclude.
+    pub(crate) fn regossip(&mut self, data_id: &T) -> Option<ShouldGossip> {
+        if self.current.contains_key(data_id) || self.paused.contains_key(data_id) {
+            return None;
+        }
+        let _ = self.finished.remove(data_id);
+        self.new_local_data(data_id)
+    }
+
+    fn complete_data(
+        &mut self,
+        data_id: &T,
+        maybe_holder: Option<NodeId>,
+        is_local: bool,
     ) -> Option<ShouldGossip> {
         self.purge_finished();
 
@@ -203,14 +315,27 @@ impl<T: Copy + Eq + Hash + Display> GossipTable<T> {
             Entry::Occupied(mut entry) => {
                 let state = entry.get_mut();
                 update(state);
+                if state.is_local && maybe_holder.is_some() {
+                    debug!(%data_id, "ignoring echo of data we originated");
+                    return None;
+                }
                 let is_new = false;
-                state.action(self.infection_target, self.holders_limit, is_new)
+                state.action(is_new)
             }
             Entry::Vacant(entry) => {
-                let state = entry.insert(State::default());
+                let state = if is_local {
+                    State::new(
+                        self.local_infection_target,
+                        self.local_holders_limit,
+                        is_local,
+                    )
+                } else {
+                    State::new(self.infection_target, self.holders_limit, is_local)
+                };
+                let state = entry.insert(state);
                 update(state);
                 let is_new = true;
-                state.action(self.infection_target, self.holders_limit, is_new)
+                state.action(is_new)
             }
         };
 
@@ -223,6 +348,20 @@ impl<T: Copy + Eq + Hash + Display> GossipTable<T> {
         }
     }
 
+    /// We gossiped the data to the given peers.
+    ///
+    /// They are excluded from all further gossip of the data, even if they don't respond.
+    pub(crate) fn gossiped_to(&mut self, data_id: &T, peers: &HashSet<NodeId>) {
+        let state = match self.current.get_mut(data_id) {
+            Some(state) => state,
+            None => match self.paused.get_mut(data_id) {
+                Some((state, _timeout)) => state,
+                None => return,
+            },
+        };
+        state.gossiped_to.extend(peers);
+    }
+
     /// We got a response from a peer we gossiped to indicating we infected it (it didn't previously
     /// know of this data).
     ///
@@ -246,8 +385,6 @@ impl<T: Copy + Eq + Hash + Display> GossipTable<T> {
     }
 
     fn infected(&mut self, data_id: &T, peer: NodeId, by_us: bool) -> GossipAction {
-        let infection_target = self.infection_target;
-        let holders_limit = self.holders_limit;
         let update = |state: &mut State| {
             if !state.held_by_us {
                 warn!(
@@ -261,7 +398,7 @@ impl<T: Copy + Eq + Hash + Display> GossipTable<T> {
                 let _ = state.infected_by_us.insert(peer);
             }
             state.in_flight_count = state.in_flight_count.saturating_sub(1);
-            Some(state.is_finished(infection_target, holders_limit))
+            Some(state.is_finished())
         };
 
         let is_finished = if let Some(state) = self.current.get_mut(data_id) {
@@ -271,7 +408,7 @@ impl<T: Copy + Eq + Hash + Display> GossipTable<T> {
             };
             if !is_finished {
                 let is_new = false;
-                return state.action(self.infection_target, self.holders_limit, is_new);
+                return state.action(is_new);
             }
             true
         } else {
@@ -280,8 +417,7 @@ impl<T: Copy + Eq + Hash + Display> GossipTable<T> {
 
         if is_finished {
             let _ = self.current.remove(data_id);
-            let timeout = Instant::now() + self.finished_entry_duration;
-            let _ = self.finished.insert(*data_id, timeout);
+            self.insert_finished(*data_id);
             return GossipAction::Noop;
         }
 
@@ -296,13 +432,23 @@ impl<T: Copy + Eq + Hash + Display> GossipTable<T> {
 
         if is_finished {
             let _ = self.paused.remove(data_id);
-            let timeout = Instant::now() + self.finished_entry_duration;
-            let _ = self.finished.insert(*data_id, timeout);
+            self.insert_finished(*data_id);
         }
 
         GossipAction::Noop
     }
 
+    /// Forgets all data for which gossiping is ongoing or paused, keeping that which has finished.
+    pub(crate) fn clear_current(&mut self) {
+        self.current.clear();
+        self.paused.clear();
+    }
+
+    /// Returns whether gossiping `data_id` has finished.
+    pub(crate) fn has_finished(&self, data_id: &T) -> bool {
+        self.finished.contains_key(data_id)
+    }
+
     /// Checks if gossip request we sent timed out.
     ///
     /// If the peer is already counted as a holder, it has previously responded and this method
@@ -317,7 +463,7 @@ impl<T: Copy + Eq + Hash + Display> GossipTable<T> {
             if !state.holders.contains(&peer) {
                 state.in_flight_count = state.in_flight_count.saturating_sub(1);
                 let is_new = false;
-                return state.action(self.infection_target, self.holders_limit, is_new);
+                return state.action(is_new);
             }
         }
 
@@ -343,7 +489,7 @@ impl<T: Copy + Eq + Hash + Display> GossipTable<T> {
                 }
             }
             let is_new = !state.held_by_us;
-            let action = state.action(self.infection_target, self.holders_limit, is_new);
+            let action = state.action(is_new);
             let _ = self.current.insert(*data_id, state);
             return action;
         }
@@ -375,14 +521,98 @@ impl<T: Copy + Eq + Hash + Display> GossipTable<T> {
     pub(crate) fn resume(&mut self, data_id: &T) -> Result<GossipAction, Error> {
         let (mut state, _timeout) = self.paused.remove(data_id).ok_or(Error::NotPaused)?;
         let is_new = !state.held_by_us;
-        let action = state.action(self.infection_target, self.holders_limit, is_new);
+        let action = state.action(is_new);
         let _ = self.current.insert(*data_id, state);
         Ok(action)
     }
 
+    /// Returns the IDs of all data we hold which is being, or has recently been, gossiped.
+    pub(crate) fn held_data_ids(&self) -> impl Iterator<Item = &T> {
+        self.current
+            .iter()
+            .chain(
+                self.paused
+                    .iter()
+                    .map(|(data_id, (state, _))| (data_id, state)),
+            )
+            .filter(|(_, state)| state.held_by_us)
+            .map(|(data_id, _)| data_id)
+            .chain(self.finished.keys())
+    }
+
+    /// Reconciles the data we hold with that held by `peer`, which is assumed to hold all data for
+    /// which `peer_holds` returns `true`.
+    ///
+    /// The peer is recorded as a holder of such data, so that it isn't gossiped to it.  Returns the
+    /// IDs of the data the peer lacks, which should be gossiped to it directly.  Those which are
+    /// still being gossiped are counted as in-flight gossip messages.
+    pub(crate) fn reconcile<F: Fn(&T) -> bool>(&mut self, peer: NodeId, peer_holds: F) -> Vec<T> {
+        self.purge_finished();
+
+        let mut lacking = Vec::new();
+        for (data_id, state) in self.current.iter_mut() {
+            if !state.held_by_us || state.holders.contains(&peer) {
+                continue;
+            }
+            if peer_holds(data_id) {
+                let _ = state.holders.insert(peer);
+            } else {
+                state.in_flight_count += 1;
+                lacking.push(*data_id);
+            }
+        }
+        // Paused data is offered too, as its gossip may have stopped for lack of peers.
+        for (data_id, (state, _)) in self.paused.iter_mut() {
+            if !state.held_by_us || state.holders.contains(&peer) {
+                continue;
+            }
+            if peer_holds(data_id) {
+                let _ = state.holders.insert(peer);
+            } else {
+                lacking.push(*data_id);
+            }
+        }
+        lacking.extend(
+            self.finished
+                .keys()
+                .filter(|&data_id| !peer_holds(data_id))
+                .copied(),
+        );
+        lacking
+    }
+
+    /// Records gossiping `data_id` as finished, dropping the oldest finished entries if there are
+    /// more than `max_finished_entries`.
+    fn insert_finished(&mut self, data_id: T) {
+        let timeout = Instant::now() + self.finished_entry_duration;
+        let _ = self.finished.insert(data_id, timeout);
+        self.finished_order.push_back((data_id, timeout));
+
+        if self.max_finished_entries == 0 {
+            return;
+        }
+        while self.finished.len() > self.max_finished_entries {
+            let (oldest, oldest_timeout) = match self.finished_order.pop_front() {
+                Some(entry) => entry,
+                None => break,
+            };
+            if self.finished.get(&oldest) == Some(&oldest_timeout) {
+                let _ = self.finished.remove(&oldest);
+                debug!(data_id = %oldest, "dropped finished entry beyond maximum count");
+            }
+        }
+    }
+
     /// Retains only those finished entries which still haven't timed out.
     fn purge_finished(&mut self) {
         let now = Instant::now();
+        // Timeouts are ascending in finishing order, so the timed-out entries are at the front.
+        while let Some((_, timeout)) = self.finished_order.front() {
+            if *timeout > now {
+                break;
+            }
+            let _ = self.finished_order.pop_front();
+        }
         self.finished = self
             .finished
             .drain()
@@ -402,11 +632,15 @@ mod tests {
 
     use rand::Rng;
 
-    use super::{super::config::DEFAULT_FINISHED_ENTRY_DURATION_SECS, *};
+    use super::{
+        super::{bloom_filter::BloomFilter, config::DEFAULT_FINISHED_ENTRY_DURATION_SECS},
+        *,
+    };
     use crate::{testing::TestRng, utils::DisplayIter};
 
     const EXPECTED_DEFAULT_INFECTION_TARGET: usize = 3;
     const EXPECTED_DEFAULT_HOLDERS_LIMIT: usize = 15;
+    const EXPECTED_DEFAULT_LOCAL_INFECTION_TARGET: usize = 6;
 
     fn random_node_ids(rng: &mut TestRng) -> Vec<NodeId> {
         iter::repeat_with(|| rng.gen::<NodeId>())
@@ -519,6 +753,61 @@ mod tests {
         assert_eq!(GossipAction::Noop, action);
     }
 
+    #[test]
+    fn should_only_offer_data_missing_from_peer_digest() {
+        let mut rng = TestRng::new();
+        let peer: NodeId = rng.gen();
+        let mut gossip_table = GossipTable::new(Config::default());
+
+        for data_id in 0..10_u64 {
+            let _ = gossip_table.new_complete_data(&data_id, None);
+        }
+        // Data we only hold partially can't be offered.
+        let partial_data_id = 10_u64;
+        let _ = gossip_table.new_partial_data(&partial_data_id, rng.gen());
+
+        // The peer's digest contains half of the data we hold.
+        let mut digest = BloomFilter::new(100, 0.001);
+        for data_id in 0..5_u64 {
+            digest.insert(&data_id);
+        }
+
+        let lacking: BTreeSet<_> = gossip_table
+            .reconcile(peer, |data_id| digest.contains(data_id))
+            .into_iter()
+            .collect();
+        assert_eq!(lacking, (5..10).collect());
+
+        // The peer is now known to hold the data in its digest, so won't be gossiped to.
+        for data_id in 0..5 {
+            check_holders(&[peer], &gossip_table, &data_id);
+        }
+        for data_id in 5..10 {
+            check_holders(&[], &gossip_table, &data_id);
+        }
+    }
+
+    #[test]
+    fn should_offer_paused_data_missing_from_peer_digest() {
+        let mut rng = TestRng::new();
+        let holder: NodeId = rng.gen();
+        let peer: NodeId = rng.gen();
+        let mut gossip_table = GossipTable::new(Config::default());
+
+        // Gossiping the data stopped since there were no peers to gossip to.
+        let data_id: u64 = rng.gen();
+        let _ = gossip_table.new_complete_data(&data_id, None);
+        gossip_table.pause(&data_id);
+        assert_eq!(
+            gossip_table.held_data_ids().collect::<Vec<_>>(),
+            vec![&data_id]
+        );
+
+        assert!(gossip_table.reconcile(holder, |_| true).is_empty());
+        check_holders(&[holder], &gossip_table, &data_id);
+        assert_eq!(gossip_table.reconcile(peer, |_| false), vec![data_id]);
+    }
+
     #[test]
     fn new_complete_data() {
         let mut rng = TestRng::new();
@@ -598,6 +887,127 @@ mod tests {
         check_holders(&node_ids[..1], &gossip_table, &data_id);
     }
 
+    #[test]
+    fn new_local_data() {
+        let mut rng = TestRng::new();
+        let node_ids = random_node_ids(&mut rng);
+        let data_id: u64 = rng.gen();
+
+        let mut gossip_table = GossipTable::new(Config::default());
+
+        // Check new data from a client causes `ShouldGossip` to be returned with the higher local
+        // fan-out.
+        let action = gossip_table.new_local_data(&data_id);
+        let expected = Some(ShouldGossip {
+            count: EXPECTED_DEFAULT_LOCAL_INFECTION_TARGET,
+            exclude_peers: HashSet::new(),
+            is_already_held: false,
+        });
+        assert_eq!(expected, action);
+        check_holders(&node_ids[..0], &gossip_table, &data_id);
+
+        // Check the same data received again from a client causes `Noop` to be returned since we
+        // still have all gossip requests in flight.
+        let action = gossip_table.new_local_data(&data_id);
+        assert!(action.is_none());
+
+        // Infecting `infection_target` peers should not be enough to finish gossiping.
+        for node_id in &node_ids[..EXPECTED_DEFAULT_INFECTION_TARGET] {
+            let action = gossip_table.we_infected(&data_id, *node_id);
+            assert!(matches!(action, GossipAction::ShouldGossip(_)));
+        }
+
+        // Infecting `local_infection_target` peers should finish gossiping.
+        for node_id in
+            &node_ids[EXPECTED_DEFAULT_INFECTION_TARGET..EXPECTED_DEFAULT_LOCAL_INFECTION_TARGET]
+        {
+            let _ = gossip_table.we_infected(&data_id, *node_id);
+        }
+        assert!(gossip_table.finished.contains_key(&data_id));
+
+        // Data received from a peer for the first time should still use the regular fan-out.
+        let other_data_id: u64 = rng.gen();
+        let action = gossip_table.new_complete_data(&other_data_id, Some(node_ids[0]));
+        let expected = Some(ShouldGossip {
+            count: EXPECTED_DEFAULT_INFECTION_TARGET,
+            exclude_peers: node_ids[..1].iter().copied().collect(),
+            is_already_held: false,
+        });
+        assert_eq!(expected, action);
+    }
+
+    #[test]
+    fn regossip() {
+        let mut rng = TestRng::new();
+        let node_ids = random_node_ids(&mut rng);
+        let data_id: u64 = rng.gen();
+
+        let mut gossip_table = GossipTable::new(Config::default());
+
+        // Regossiping data which is still being gossiped should be a no-op.
+        let _ = gossip_table.new_local_data(&data_id);
+        assert!(gossip_table.regossip(&data_id).is_none());
+
+        // Once finished, regossiping should restart with the local fan-out and no exclusions.
+        for node_id in &node_ids[..EXPECTED_DEFAULT_LOCAL_INFECTION_TARGET] {
+            let _ = gossip_table.we_infected(&data_id, *node_id);
+        }
+        assert!(gossip_table.finished.contains_key(&data_id));
+
+        let action = gossip_table.regossip(&data_id);
+        let expected = Some(ShouldGossip {
+            count: EXPECTED_DEFAULT_LOCAL_INFECTION_TARGET,
+            exclude_peers: HashSet::new(),
+            is_already_held: false,
+        });
+        assert_eq!(expected, action);
+        assert!(!gossip_table.finished.contains_key(&data_id));
+
+        // Paused data should not be regossiped.
+        gossip_table.pause(&data_id);
+        assert!(gossip_table.regossip(&data_id).is_none());
+    }
+
+    #[test]
+    fn should_use_updated_infection_target_for_new_data() {
+        let mut rng = TestRng::new();
+        let node_ids = random_node_ids(&mut rng);
+        let data_id: u64 = rng.gen();
+
+        let mut gossip_table = GossipTable::new(Config::default());
+        let _ = gossip_table.new_complete_data(&data_id, Some(node_ids[0]));
+
+        gossip_table.set_infection_target(EXPECTED_DEFAULT_LOCAL_INFECTION_TARGET + 2);
+        let other_data_id: u64 = rng.gen();
+        let action = gossip_table.new_complete_data(&other_data_id, Some(node_ids[0]));
+        assert_eq!(
+            action.map(|should_gossip| should_gossip.count),
+            Some(EXPECTED_DEFAULT_LOCAL_INFECTION_TARGET + 2)
+        );
+        let local_data_id: u64 = rng.gen();
+        let action = gossip_table.new_local_data(&local_data_id);
+        assert_eq!(
+            action.map(|should_gossip| should_gossip.count),
+            Some(EXPECTED_DEFAULT_LOCAL_INFECTION_TARGET + 2)
+        );
+
+        // A lower target never lowers the fan-out of locally-submitted data below the configured
+        // one.
+        gossip_table.set_infection_target(1);
+        let local_data_id: u64 = rng.gen();
+        let action = gossip_table.new_local_data(&local_data_id);
+        assert_eq!(
+            action.map(|should_gossip| should_gossip.count),
+            Some(EXPECTED_DEFAULT_LOCAL_INFECTION_TARGET)
+        );
+
+        // The data already being gossiped keeps its original target.
+        assert_eq!(
+            gossip_table.current[&data_id].infection_target,
+            EXPECTED_DEFAULT_INFECTION_TARGET
+        );
+    }
+
     #[test]
     fn should_terminate_via_infection_limit() {
         let mut rng = TestRng::new();
@@ -728,6 +1138,36 @@ mod tests {
         assert_eq!(expected, action);
     }
 
+    #[test]
+    fn should_not_gossip_echoed_data_back_to_peers_gossiped_to() {
+        let mut rng = TestRng::new();
+        let node_ids = random_node_ids(&mut rng);
+        let data_id: u64 = rng.gen();
+
+        let mut gossip_table = GossipTable::new(Config::default());
+
+        // We originate the data and gossip it to nodes 0 and 1.
+        let action = gossip_table.new_local_data(&data_id).unwrap();
+        assert_eq!(EXPECTED_DEFAULT_LOCAL_INFECTION_TARGET, action.count);
+        gossip_table.gossiped_to(&data_id, &node_ids[..2].iter().copied().collect());
+
+        // Node 0 gossips it back to us, which is ignored as an echo.
+        assert!(gossip_table
+            .new_complete_data(&data_id, Some(node_ids[0]))
+            .is_none());
+        check_holders(&node_ids[..1], &gossip_table, &data_id);
+
+        // Node 1 doesn't respond, so the data is gossiped to another peer, excluding both nodes
+        // it was gossiped to already.
+        let action = gossip_table.check_timeout(&data_id, node_ids[1]);
+        let expected = GossipAction::ShouldGossip(ShouldGossip {
+            count: 1,
+            exclude_peers: node_ids[..2].iter().copied().collect(),
+            is_already_held: true,
+        });
+        assert_eq!(expected, action);
+    }
+
     #[test]
     #[cfg_attr(
         debug_assertions,
@@ -873,4 +1313,82 @@ mod tests {
         gossip_table.purge_finished();
         assert!(!gossip_table.paused.contains_key(&data_id));
     }
+
+    /// Finishes gossiping each of `data_ids` via the infection limit, advancing time by
+    /// `interval_ms` after each.
+    fn finish_all(
+        gossip_table: &mut GossipTable<u64>,
+        data_ids: &[u64],
+        node_ids: &[NodeId],
+        interval_ms: u64,
+    ) {
+        for data_id in data_ids {
+            let _ = gossip_table.new_complete_data(data_id, None);
+            for node_id in &node_ids[0..EXPECTED_DEFAULT_INFECTION_TARGET] {
+                let _ = gossip_table.we_infected(data_id, *node_id);
+            }
+            assert!(gossip_table.has_finished(data_id));
+            Instant::advance_time(interval_ms);
+        }
+    }
+
+    #[test]
+    fn should_evict_oldest_finished_entries_by_count() {
+        let mut rng = TestRng::new();
+        let node_ids = random_node_ids(&mut rng);
+        let data_ids: Vec<u64> = (0..5).collect();
+
+        let config = Config::default().with_max_finished_entries(3);
+        let mut gossip_table = GossipTable::new(config);
+        finish_all(&mut gossip_table, &data_ids, &node_ids, 1_000);
+
+        // Only the three most recently finished entries are kept, well before any timed out.
+        assert_eq!(gossip_table.finished.len(), 3);
+        for data_id in &data_ids[0..2] {
+            assert!(!gossip_table.has_finished(data_id));
+        }
+        for data_id in &data_ids[2..] {
+            assert!(gossip_table.has_finished(data_id));
+        }
+
+        // A regossiped entry which finishes again counts as the newest one.
+        assert!(gossip_table.regossip(&data_ids[2]).is_some());
+        for node_id in &node_ids[0..EXPECTED_DEFAULT_LOCAL_INFECTION_TARGET] {
+            let _ = gossip_table.we_infected(&data_ids[2], *node_id);
+        }
+        assert!(gossip_table.has_finished(&data_ids[2]));
+        Instant::advance_time(1_000);
+        finish_all(&mut gossip_table, &[5], &node_ids, 1_000);
+        assert!(!gossip_table.has_finished(&data_ids[3]));
+        assert!(gossip_table.has_finished(&data_ids[2]));
+        assert!(gossip_table.has_finished(&data_ids[4]));
+        assert!(gossip_table.has_finished(&5));
+
+        // An evicted entry is gossiped again if received again.
+        let action = gossip_table.new_complete_data(&data_ids[0], Some(node_ids[0]));
+        assert!(action.is_some());
+    }
+
+    #[test]
+    fn should_evict_finished_entries_by_age() {
+        let mut rng = TestRng::new();
+        let node_ids = random_node_ids(&mut rng);
+        let data_ids: Vec<u64> = (0..3).collect();
+
+        let config = Config::default().with_max_finished_entries(10);
+        let mut gossip_table = GossipTable::new(config);
+        let half_duration_ms = DEFAULT_FINISHED_ENTRY_DURATION_SECS * 1_000 / 2;
+        finish_all(&mut gossip_table, &data_ids, &node_ids, half_duration_ms);
+
+        // The count is well below the maximum, but the first two entries have timed out.
+        gossip_table.purge_finished();
+        assert!(!gossip_table.has_finished(&data_ids[0]));
+        assert!(!gossip_table.has_finished(&data_ids[1]));
+        assert!(gossip_table.has_finished(&data_ids[2]));
+        assert_eq!(gossip_table.finished_order.len(), 1);
+
+        // An evicted entry is gossiped again if received again.
+        let action = gossip_table.new_complete_data(&data_ids[0], Some(node_ids[0]));
+        assert!(action.is_some());
+    }
 }
@@ -2,7 +2,7 @@ use std::fmt::{self, Display, Formatter};
 
 use serde::{Deserialize, Serialize};
 
-use super::Item;
+use super::{BloomFilter, Item};
 
 #[derive(Clone, Debug, Deserialize, Serialize)]
 #[serde(bound = "for<'a> T: Deserialize<'a>")]
@@ -15,6 +15,9 @@ pub enum Message<T: Item> {
         item_id: T::Id,
         is_already_held: bool,
     },
+    /// Sent to a newly-connected peer, containing a digest of the IDs of all items we hold.  The
+    /// recipient should only gossip items to us which are not contained in the digest.
+    HeldItemsDigest(BloomFilter),
 }
 
 impl<T: Item> Display for Message<T> {
@@ -29,6 +32,7 @@ impl<T: Item> Display for Message<T> {
                 "gossip-response({}, {})",
                 item_id, is_already_held
             ),
+            Message::HeldItemsDigest(_) => write!(formatter, "held-items-digest"),
         }
     }
 }
@@ -0,0 +1,233 @@
+//! Scoring of peers by their responsiveness to gossip requests.
+//!
+//! Every connected peer has a reputation, which increases whenever it responds to a gossip request
+//! in time and decreases whenever a request times out.  Together with the round-trip time the
+//! network has measured to the peer, it determines the weight with which a peer is chosen as a
+//! gossip target.  The scores of a peer are forgotten once it disconnects.
+
+use std::{
+    collections::{HashMap, HashSet},
+    hash::Hash,
+    time::Duration,
+};
+
+use crate::components::small_network::{LatencyStore, NodeId};
+
+/// Bound on the absolute value of a peer's reputation.
+const MAX_REPUTATION: i32 = 20;
+
+/// Returns the quality of a peer with the given reputation and round-trip time, in the range -1
+/// (worst) to 1 (best).
+///
+/// Round-trip times are judged relative to `latency_reference`, which must be above zero: an
+/// immediate response is best, one that takes `latency_reference` or longer is worst.
+fn quality(reputation: i32, rtt: Option<Duration>, latency_reference: Duration) -> f64 {
+    let reputation_quality = f64::from(reputation) / f64::from(MAX_REPUTATION);
+    match rtt {
+        Some(rtt) => {
+            let relative_latency = (rtt.as_secs_f64() / latency_reference.as_secs_f64()).min(1.0);
+            (reputation_quality + 1.0 - 2.0 * relative_latency) / 2.0
+        }
+        None => reputation_quality,
+    }
+}
+
+/// Scores of all connected peers we have gossiped to.
+#[derive(Debug)]
+pub(super) struct PeerScores<K> {
+    /// Number of timely responses minus the number of timeouts of each peer, clamped to
+    /// `±MAX_REPUTATION`.
+    reputations: HashMap<NodeId, i32>,
+    /// Gossip requests which haven't been responded to yet.
+    in_flight: HashSet<(K, NodeId)>,
+    /// The round-trip times measured by the network.
+    latencies: LatencyStore,
+    /// The latency at or above which a peer is considered as slow as possible.
+    latency_reference: Duration,
+}
+
+impl<K: Eq + Hash> PeerScores<K> {
+    /// Creates scores judging the round-trip times in `latencies` relative to `latency_reference`,
+    /// which must be above zero.
+    pub(super) fn new(latencies: LatencyStore, latency_reference: Duration) -> Self {
+        assert!(
+            latency_reference > Duration::from_secs(0),
+            "latency reference should be above zero"
+        );
+        PeerScores {
+            reputations: HashMap::new(),
+            in_flight: HashSet::new(),
+            latencies,
+            latency_reference,
+        }
+    }
+
+    /// Records that `item_id` was gossiped to `peer`.
+    pub(super) fn gossiped_to(&mut self, item_id: K, peer: NodeId) {
+        let _ = self.in_flight.insert((item_id, peer));
+    }
+
+    /// Records that `peer` responded to the gossip request for `item_id`.
+    pub(super) fn response_received(&mut self, item_id: K, peer: NodeId) {
+        if self.in_flight.remove(&(item_id, peer)) {
+            let reputation = self.reputations.entry(peer).or_default();
+            *reputation = (*reputation + 1).min(MAX_REPUTATION);
+        }
+    }
+
+    /// Records that the gossip request for `item_id` sent to `peer` timed out, unless `peer` has
+    /// responded already.
+    pub(super) fn check_timeout(&mut self, item_id: K, peer: NodeId) {
+        if self.in_flight.remove(&(item_id, peer)) {
+            let reputation = self.reputations.entry(peer).or_default();
+            *reputation = (*reputation - 1).max(-MAX_REPUTATION);
+        }
+    }
+
+    /// Forgets the reputation of and the requests in flight to `peer`, which has disconnected.
+    pub(super) fn peer_disconnected(&mut self, peer: &NodeId) {
+        let _ = self.reputations.remove(peer);
+        self.in_flight
+            .retain(|(_, in_flight_peer)| in_flight_peer != peer);
+    }
+
+    /// Forgets all gossip requests which haven't been responded to yet, without penalizing the
+    /// peers they were sent to.
+    pub(super) fn clear_in_flight(&mut self) {
+        self.in_flight.clear();
+    }
+
+    /// Returns the weights with which peers should be chosen as gossip targets.
+    ///
+    /// A peer's weight is `exp(bias * quality)`, so the weights of all peers are equal if `bias` is
+    /// zero, in which case an empty map is returned and targets are chosen uniformly at random.
+    /// Peers with neither a reputation nor a round-trip time have a quality of zero, i.e. a weight
+    /// of 1.
+    pub(super) fn weights(&self, bias: f64) -> HashMap<NodeId, f64> {
+        if bias == 0.0 {
+            return HashMap::new();
+        }
+        let rtts = self.latencies.round_trip_times();
+        self.reputations
+            .keys()
+            .chain(rtts.keys())
+            .map(|peer| {
+                let reputation = self.reputations.get(peer).copied().unwrap_or_default();
+                let quality = quality(reputation, rtts.get(peer).copied(), self.latency_reference);
+                (*peer, (bias * quality).exp())
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use rand::Rng;
+
+    use super::*;
+    use crate::{testing::TestRng, utils};
+
+    const ROUNDS: usize = 10_000;
+    const COUNT: usize = 3;
+
+    #[test]
+    fn should_prefer_high_reputation_peers_with_strong_bias() {
+        let mut rng = TestRng::new();
+        let good_peers: Vec<NodeId> = (0..5).map(|_| rng.gen()).collect();
+        let bad_peers: Vec<NodeId> = (0..5).map(|_| rng.gen()).collect();
+
+        let mut scores = PeerScores::new(LatencyStore::default(), Duration::from_secs(10));
+        for item_id in 0..5 {
+            for &peer in &good_peers {
+                scores.gossiped_to(item_id, peer);
+                scores.response_received(item_id, peer);
+            }
+            for &peer in &bad_peers {
+                scores.gossiped_to(item_id, peer);
+                scores.check_timeout(item_id, peer);
+            }
+        }
+
+        let weights = scores.weights(3.0);
+        let mut good_count = 0;
+        let mut bad_count = 0;
+        for _ in 0..ROUNDS {
+            let candidates = good_peers.iter().chain(bad_peers.iter());
+            for peer in
+                utils::choose_weighted_multiple(&mut rng, candidates, COUNT, |peer| weights[*peer])
+            {
+                if good_peers.contains(peer) {
+                    good_count += 1;
+                } else {
+                    bad_count += 1;
+                }
+            }
+        }
+
+        assert_eq!(good_count + bad_count, ROUNDS * COUNT);
+        assert!(
+            good_count > 3 * bad_count,
+            "good peers chosen {} times, bad peers {} times",
+            good_count,
+            bad_count
+        );
+    }
+
+    #[test]
+    fn should_not_weight_peers_without_bias() {
+        let mut rng = TestRng::new();
+        let peer: NodeId = rng.gen();
+        let mut scores = PeerScores::new(LatencyStore::default(), Duration::from_secs(10));
+        scores.gossiped_to(0, peer);
+        scores.check_timeout(0, peer);
+
+        assert!(scores.weights(0.0).is_empty());
+        assert!(scores.weights(1.0)[&peer] < 1.0);
+    }
+
+    #[test]
+    fn should_ignore_timeout_after_response() {
+        let mut rng = TestRng::new();
+        let peer: NodeId = rng.gen();
+        let mut scores = PeerScores::new(LatencyStore::default(), Duration::from_secs(10));
+        scores.gossiped_to(0, peer);
+        scores.response_received(0, peer);
+        scores.check_timeout(0, peer);
+
+        assert_eq!(scores.reputations[&peer], 1);
+        assert!(scores.in_flight.is_empty());
+    }
+
+    #[test]
+    fn should_forget_disconnected_peers() {
+        let mut rng = TestRng::new();
+        let peer: NodeId = rng.gen();
+        let other_peer: NodeId = rng.gen();
+        let mut scores = PeerScores::new(LatencyStore::default(), Duration::from_secs(10));
+        scores.gossiped_to(0, peer);
+        scores.response_received(0, peer);
+        scores.gossiped_to(1, peer);
+        scores.gossiped_to(1, other_peer);
+
+        scores.peer_disconnected(&peer);
+        assert!(!scores.reputations.contains_key(&peer));
+        assert_eq!(scores.in_flight.len(), 1);
+        assert!(scores.weights(1.0).is_empty());
+
+        // A late timeout of a request to the disconnected peer doesn't bring it back.
+        scores.check_timeout(1, peer);
+        assert!(scores.reputations.is_empty());
+    }
+
+    #[test]
+    fn should_prefer_peers_with_short_round_trip_times() {
+        let reference = Duration::from_secs(10);
+        let fast = quality(0, Some(Duration::from_millis(100)), reference);
+        let slow = quality(0, Some(Duration::from_secs(20)), reference);
+        assert!(fast > quality(0, None, reference));
+        assert!(slow < quality(0, None, reference));
+        assert!((slow + 0.5).abs() < f64::EPSILON);
+        let best = quality(MAX_REPUTATION, Some(Duration::from_secs(0)), reference);
+        assert!((best - 1.0).abs() < f64::EPSILON);
+    }
+}
@@ -0,0 +1,131 @@
+//! Estimates of how widely items have propagated through the network.
+//!
+//! Every gossip message or response received from a peer shows that the peer holds the item in
+//! question.  Once the peers known to hold an item make up a large enough fraction of the connected
+//! peers, gossiping it further is of little value, so it is gossiped at a lower priority.
+
+use std::{
+    collections::HashSet,
+    fmt::{self, Debug, Formatter},
+    hash::Hash,
+};
+
+use linked_hash_map::LinkedHashMap;
+
+use crate::small_network::NodeId;
+
+/// The peers known to hold each of the recently gossiped items.
+pub(super) struct Propagation<I: Eq + Hash> {
+    /// The currently connected peers.
+    connected_peers: HashSet<NodeId>,
+    /// The peers known to hold each item, the least recently updated item first.
+    holders: LinkedHashMap<I, HashSet<NodeId>>,
+    /// The maximum number of items tracked.
+    capacity: usize,
+}
+
+impl<I: Copy + Eq + Hash> Propagation<I> {
+    /// Creates an empty estimate tracking at most `capacity` items.
+    pub(super) fn new(capacity: usize) -> Self {
+        Propagation {
+            connected_peers: HashSet::new(),
+            holders: LinkedHashMap::new(),
+            capacity,
+        }
+    }
+
+    /// Records that `peer` has connected.
+    pub(super) fn peer_connected(&mut self, peer: NodeId) {
+        let _ = self.connected_peers.insert(peer);
+    }
+
+    /// Records that `peer` has disconnected.
+    pub(super) fn peer_disconnected(&mut self, peer: &NodeId) {
+        let _ = self.connected_peers.remove(peer);
+    }
+
+    /// Returns the number of currently connected peers.
+    pub(super) fn connected_peer_count(&self) -> usize {
+        self.connected_peers.len()
+    }
+
+    /// Records that `peer` holds `item_id`.
+    ///
+    /// If this starts tracking a new item and the capacity is exceeded, the least recently updated
+    /// item is forgotten.
+    pub(super) fn holds(&mut self, item_id: I, peer: NodeId) {
+        if let Some(holders) = self.holders.get_refresh(&item_id) {
+            let _ = holders.insert(peer);
+            return;
+        }
+        let _ = self
+            .holders
+            .insert(item_id, Some(peer).into_iter().collect());
+        while self.holders.len() > self.capacity {
+            let _ = self.holders.pop_front();
+        }
+    }
+
+    /// Returns whether at least `fraction` of the connected peers are known to hold `item_id`.
+    pub(super) fn is_widely_seen(&self, item_id: &I, fraction: f64) -> bool {
+        if self.connected_peers.is_empty() {
+            return false;
+        }
+        let holder_count = self.holders.get(item_id).map_or(0, |holders| {
+            holders
+                .iter()
+                .filter(|peer| self.connected_peers.contains(peer))
+                .count()
+        });
+        holder_count as f64 >= fraction * self.connected_peers.len() as f64
+    }
+}
+
+impl<I: Eq + Hash> Debug for Propagation<I> {
+    fn fmt(&self, formatter: &mut Formatter) -> fmt::Result {
+        formatter
+            .debug_struct("Propagation")
+            .field("connected_peers", &self.connected_peers.len())
+            .field("tracked_items", &self.holders.len())
+            .field("capacity", &self.capacity)
+            .finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use rand::Rng;
+
+    use super::*;
+    use crate::testing::TestRng;
+
+    #[test]
+    fn should_only_count_connected_holders_of_tracked_items() {
+        let mut rng = TestRng::new();
+        let peers: Vec<NodeId> = (0..4).map(|_| rng.gen()).collect();
+        let mut propagation = Propagation::new(2);
+
+        // Without connected peers, nothing is widely seen.
+        propagation.holds(0, peers[0]);
+        assert!(!propagation.is_widely_seen(&0, 0.5));
+
+        for &peer in &peers {
+            propagation.peer_connected(peer);
+        }
+        propagation.holds(0, peers[1]);
+        assert!(propagation.is_widely_seen(&0, 0.5));
+        assert!(!propagation.is_widely_seen(&0, 0.75));
+
+        // Holders which disconnected no longer count.
+        propagation.peer_disconnected(&peers[1]);
+        assert!(!propagation.is_widely_seen(&0, 0.5));
+
+        // Tracking more items than the capacity forgets the least recently updated one.
+        propagation.holds(1, peers[0]);
+        propagation.holds(0, peers[2]);
+        propagation.holds(2, peers[0]);
+        assert!(propagation.is_widely_seen(&0, 0.6));
+        assert!(!propagation.is_widely_seen(&1, 0.3));
+        assert!(propagation.is_widely_seen(&2, 0.3));
+    }
+}
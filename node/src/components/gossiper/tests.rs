@@ -17,21 +17,26 @@ use super::*;
 use crate::{
     components::{
         chainspec_loader::Chainspec,
-        deploy_acceptor::{self, DeployAcceptor},
-        in_memory_network::{InMemoryNetwork, NetworkController, NodeId},
+        deploy_acceptor::{self, AcceptAll, DeployAcceptor},
+        in_memory_network::{InMemoryNetwork, LinkConditions, NetworkController, NodeId},
+        small_network::LatencyStore,
         storage::{self, Storage, StorageType},
     },
-    effect::announcements::{
-        ApiServerAnnouncement, DeployAcceptorAnnouncement, GossiperAnnouncement,
-        NetworkAnnouncement,
+    effect::{
+        announcements::{
+            ApiServerAnnouncement, ControlAnnouncement, DeployAcceptorAnnouncement,
+            GossiperAnnouncement, NetworkAnnouncement, StorageAnnouncement,
+        },
+        requests::{DeployBufferRequest, FetcherRequest},
     },
     protocol::Message as NodeMessage,
     reactor::{self, EventQueueHandle, Runner},
     testing::{
-        network::{Network, NetworkedReactor},
+        harness::Harness,
+        network::{Network, NetworkedReactor, Nodes},
         ConditionCheckReactor, TestRng,
     },
-    types::{Deploy, Tag},
+    types::{Deploy, NodeConfig, Tag, TimeDiff},
     utils::Loadable,
 };
 
@@ -46,6 +51,8 @@ enum Event {
     #[from]
     DeployGossiper(super::Event<Deploy>),
     #[from]
+    DeployFetcherRequest(FetcherRequest<NodeId, Deploy>),
+    #[from]
     NetworkRequest(NetworkRequest<NodeId, NodeMessage>),
     #[from]
     NetworkAnnouncement(NetworkAnnouncement<NodeId, NodeMessage>),
@@ -55,6 +62,12 @@ enum Event {
     DeployAcceptorAnnouncement(DeployAcceptorAnnouncement<NodeId>),
     #[from]
     DeployGossiperAnnouncement(GossiperAnnouncement<Deploy>),
+    #[from]
+    StorageAnnouncement(StorageAnnouncement),
+    #[from]
+    ControlAnnouncement(ControlAnnouncement),
+    #[from]
+    DeployBufferRequest(DeployBufferRequest),
 }
 
 impl From<StorageRequest<Storage>> for Event {
@@ -75,6 +88,7 @@ impl Display for Event {
             Event::Storage(event) => write!(formatter, "storage: {}", event),
             Event::DeployAcceptor(event) => write!(formatter, "deploy acceptor: {}", event),
             Event::DeployGossiper(event) => write!(formatter, "deploy gossiper: {}", event),
+            Event::DeployFetcherRequest(req) => write!(formatter, "fetcher request: {}", req),
             Event::NetworkRequest(req) => write!(formatter, "network request: {}", req),
             Event::NetworkAnnouncement(ann) => write!(formatter, "network announcement: {}", ann),
             Event::ApiServerAnnouncement(ann) => {
@@ -86,6 +100,9 @@ impl Display for Event {
             Event::DeployGossiperAnnouncement(ann) => {
                 write!(formatter, "deploy-gossiper announcement: {}", ann)
             }
+            Event::StorageAnnouncement(ann) => write!(formatter, "storage announcement: {}", ann),
+            Event::ControlAnnouncement(ann) => write!(formatter, "control announcement: {}", ann),
+            Event::DeployBufferRequest(req) => write!(formatter, "deploy buffer request: {}", req),
         }
     }
 }
@@ -118,7 +135,7 @@ impl reactor::Reactor<TestRng> for Reactor {
 
     fn new(
         config: Self::Config,
-        _registry: &Registry,
+        registry: &Registry,
         event_queue: EventQueueHandle<Self::Event>,
         rng: &mut TestRng,
     ) -> Result<(Self, Effects<Self::Event>), Self::Error> {
@@ -127,8 +144,25 @@ impl reactor::Reactor<TestRng> for Reactor {
         let (storage_config, _storage_tempdir) = storage::Config::default_for_tests();
         let storage = Storage::new(&storage_config).unwrap();
 
-        let deploy_acceptor = DeployAcceptor::new();
-        let deploy_gossiper = Gossiper::new_for_partial_items(config, get_deploy_from_storage);
+        let deploy_acceptor = DeployAcceptor::new(
+            Box::new(AcceptAll),
+            NodeConfig::default().max_concurrent_deploy_validations,
+            NodeConfig::default().max_queued_deploy_validations,
+            TimeDiff::from(NodeConfig::default().deploy_max_future_skew_secs * 1000),
+            // Test deploys depend on random deploys, which no peer holds.
+            false,
+            registry,
+        )?;
+        // The in-memory network doesn't measure round-trip times.
+        let deploy_gossiper = Gossiper::new_for_partial_items(
+            config,
+            get_deploy_from_storage,
+            LatencyStore::default(),
+        );
+        let effects = reactor::wrap_effects(
+            Event::DeployGossiper,
+            deploy_gossiper.start_anti_entropy(EffectBuilder::new(event_queue)),
+        );
 
         let reactor = Reactor {
             network,
@@ -138,8 +172,6 @@ impl reactor::Reactor<TestRng> for Reactor {
             _storage_tempdir,
         };
 
-        let effects = Effects::new();
-
         Ok((reactor, effects))
     }
 
@@ -170,6 +202,9 @@ impl reactor::Reactor<TestRng> for Reactor {
                 self.deploy_gossiper
                     .handle_event(effect_builder, rng, event),
             ),
+            Event::DeployFetcherRequest(request) => {
+                unreachable!("dependencies should not be prefetched: {}", request)
+            }
             Event::NetworkRequest(request) => reactor::wrap_effects(
                 Event::NetworkRequest,
                 self.network.handle_event(effect_builder, rng, request),
@@ -212,6 +247,7 @@ impl reactor::Reactor<TestRng> for Reactor {
                         Event::DeployAcceptor(deploy_acceptor::Event::Accept {
                             deploy,
                             source: Source::Peer(sender),
+                            responder: None,
                         })
                     }
                     NodeMessage::DeployGossiper(message) => {
@@ -224,18 +260,27 @@ impl reactor::Reactor<TestRng> for Reactor {
             Event::NetworkAnnouncement(NetworkAnnouncement::GossipOurAddress(_)) => {
                 unreachable!("should not receive announcements of type GossipOurAddress");
             }
-            Event::NetworkAnnouncement(NetworkAnnouncement::NewPeer(_)) => {
+            Event::NetworkAnnouncement(NetworkAnnouncement::NewPeer(_))
+            | Event::NetworkAnnouncement(NetworkAnnouncement::PeerConnected { .. })
+            | Event::NetworkAnnouncement(NetworkAnnouncement::PeerDisconnected { .. }) => {
                 // We do not care about new peers in the gossiper test.
                 Effects::new()
             }
-            Event::ApiServerAnnouncement(ApiServerAnnouncement::DeployReceived { deploy }) => {
+            Event::ApiServerAnnouncement(ApiServerAnnouncement::DeployReceived {
+                deploy,
+                responder,
+            }) => {
                 let event = deploy_acceptor::Event::Accept {
                     deploy,
                     source: Source::<NodeId>::Client,
+                    responder,
                 };
                 self.dispatch_event(effect_builder, rng, Event::DeployAcceptor(event))
             }
-            Event::DeployAcceptorAnnouncement(DeployAcceptorAnnouncement::AcceptedNewDeploy {
+            Event::DeployAcceptorAnnouncement(DeployAcceptorAnnouncement::DeployAccepted {
+                ..
+            }) => Effects::new(),
+            Event::DeployAcceptorAnnouncement(DeployAcceptorAnnouncement::DeployStored {
                 deploy,
                 source,
             }) => {
@@ -249,9 +294,19 @@ impl reactor::Reactor<TestRng> for Reactor {
                 deploy: _,
                 source: _,
             }) => Effects::new(),
-            Event::DeployGossiperAnnouncement(_ann) => {
-                unreachable!("the deploy gossiper should never make an announcement")
+            Event::DeployGossiperAnnouncement(GossiperAnnouncement::NewCompleteItem(_)) => {
+                unreachable!("the deploy gossiper should never announce a complete item")
+            }
+            Event::DeployGossiperAnnouncement(GossiperAnnouncement::FinishedGossiping(_)) => {
+                Effects::new()
             }
+            Event::StorageAnnouncement(_) => Effects::new(),
+            Event::ControlAnnouncement(ann) => panic!("unexpected {}", ann),
+            // There is no deploy buffer, so every deploy is treated as buffered.
+            Event::DeployBufferRequest(DeployBufferRequest::Buffer { responder, .. }) => {
+                responder.respond(true).ignore()
+            }
+            Event::DeployBufferRequest(req) => panic!("unexpected {}", req),
         }
     }
 }
@@ -267,7 +322,11 @@ impl NetworkedReactor for Reactor {
 fn announce_deploy_received(
     deploy: Box<Deploy>,
 ) -> impl FnOnce(EffectBuilder<Event>) -> Effects<Event> {
-    |effect_builder: EffectBuilder<Event>| effect_builder.announce_deploy_received(deploy).ignore()
+    |effect_builder: EffectBuilder<Event>| {
+        effect_builder
+            .announce_deploy_received(deploy, None)
+            .ignore()
+    }
 }
 
 async fn run_gossip(rng: &mut TestRng, network_size: usize, deploy_count: usize) {
@@ -492,3 +551,468 @@ async fn should_timeout_gossip_response() {
 
     NetworkController::<NodeMessage>::remove_active();
 }
+
+#[tokio::test]
+async fn should_obtain_missed_deploy_via_anti_entropy() {
+    const TIMEOUT: Duration = Duration::from_secs(2);
+
+    NetworkController::<NodeMessage>::create_active();
+    let mut network = Network::<Reactor>::new();
+    let mut rng = TestRng::new();
+
+    // Give the deploy to node 0 while it has no peers, so its gossip is paused.
+    let (node_id_0, _runner) = network.add_node(&mut rng).await.unwrap();
+    let deploy = Box::new(Deploy::random(&mut rng));
+    let deploy_id = *deploy.id();
+    network
+        .process_injected_effect_on(&node_id_0, announce_deploy_received(deploy.clone()))
+        .await;
+    let paused_gossip = |event: &Event| -> bool {
+        match event {
+            Event::DeployGossiper(super::Event::GossipedTo { peers, .. }) => peers.is_empty(),
+            _ => false,
+        }
+    };
+    network
+        .crank_until(&node_id_0, &mut rng, paused_gossip, TIMEOUT)
+        .await;
+
+    // Node 1 joins after the deploy has been gossiped, so it misses it.
+    let (node_id_1, _runner) = network.add_node(&mut rng).await.unwrap();
+    let deploy_held = |nodes: &HashMap<NodeId, Runner<ConditionCheckReactor<Reactor>, _>>| {
+        nodes
+            .get(&node_id_1)
+            .unwrap()
+            .reactor()
+            .inner()
+            .storage
+            .deploy_store()
+            .get(smallvec![deploy_id])
+            .pop()
+            .expect("should only be a single result")
+            .expect("should not error while getting")
+            .map(|retrieved_deploy| retrieved_deploy == *deploy)
+            .unwrap_or_default()
+    };
+    network
+        .settle(&mut rng, Duration::from_millis(50), TIMEOUT)
+        .await;
+    assert!(!deploy_held(network.nodes()));
+
+    // Advance time to trigger the anti-entropy rounds, in which node 1 sends its digest to node 0.
+    let secs_to_advance = Config::default().anti_entropy_interval_secs();
+    time::pause();
+    time::advance(Duration::from_secs(secs_to_advance)).await;
+    time::resume();
+    debug!("advanced time by {} secs", secs_to_advance);
+
+    network.settle_on(&mut rng, deploy_held, TIMEOUT).await;
+
+    NetworkController::<NodeMessage>::remove_active();
+}
+
+#[tokio::test]
+async fn should_converge_via_anti_entropy_despite_lossy_links() {
+    const NETWORK_SIZE: usize = 5;
+    const MAX_ANTI_ENTROPY_ROUNDS: usize = 20;
+    // Settling sleeps for `QUIET_FOR` whenever the event queues are empty, which they are briefly
+    // after most events, so a long quiet period slows the spreading of the deploy down a lot.
+    const QUIET_FOR: Duration = Duration::from_millis(50);
+    const TIMEOUT: Duration = Duration::from_secs(20);
+
+    NetworkController::<NodeMessage>::create_active();
+    NetworkController::<NodeMessage>::set_default_link_conditions(LinkConditions {
+        latency: Duration::from_millis(5),
+        jitter: Duration::from_millis(10),
+        drop_rate: 0.2,
+    });
+    let mut network = Network::<Reactor>::new();
+    let mut rng = TestRng::new();
+
+    let node_ids = network.add_nodes(&mut rng, NETWORK_SIZE).await;
+    let deploy = Box::new(Deploy::random(&mut rng));
+    let deploy_id = *deploy.id();
+    network
+        .process_injected_effect_on(&node_ids[0], announce_deploy_received(deploy))
+        .await;
+    network.settle(&mut rng, QUIET_FOR, TIMEOUT).await;
+
+    let holders = |nodes: &HashMap<NodeId, Runner<ConditionCheckReactor<Reactor>, _>>| {
+        nodes
+            .values()
+            .filter(|runner| {
+                runner
+                    .reactor()
+                    .inner()
+                    .storage
+                    .deploy_store()
+                    .get(smallvec![deploy_id])
+                    .pop()
+                    .expect("should only be a single result")
+                    .expect("should not error while getting")
+                    .is_some()
+            })
+            .count()
+    };
+
+    // Nodes which missed the deploy due to lost messages obtain it in later anti-entropy rounds.
+    let interval = Duration::from_secs(Config::default().anti_entropy_interval_secs());
+    let mut rounds = 0;
+    while holders(network.nodes()) < NETWORK_SIZE {
+        assert!(
+            rounds < MAX_ANTI_ENTROPY_ROUNDS,
+            "only {} of {} nodes hold the deploy after {} anti-entropy rounds",
+            holders(network.nodes()),
+            NETWORK_SIZE,
+            rounds
+        );
+        time::pause();
+        time::advance(interval).await;
+        time::resume();
+        network.settle(&mut rng, QUIET_FOR, TIMEOUT).await;
+        rounds += 1;
+    }
+    debug!("deploy converged after {} anti-entropy rounds", rounds);
+
+    NetworkController::<NodeMessage>::remove_active();
+}
+
+#[tokio::test]
+async fn should_gossip_local_deploy_before_forwarded_one() {
+    const TIMEOUT: Duration = Duration::from_secs(2);
+
+    NetworkController::<NodeMessage>::create_active();
+    let mut network = Network::<Reactor>::new();
+    let mut rng = TestRng::new();
+
+    let node_ids = network.add_nodes(&mut rng, 3).await;
+
+    let forwarded_deploy_id = *Deploy::random(&mut rng).id();
+    let local_deploy_id = *Deploy::random(&mut rng).id();
+
+    // Queue a forwarded deploy and a locally-submitted one on node 0 at the same time, the
+    // forwarded one being received first.
+    let forwarded_source = Source::Peer(node_ids[1]);
+    network
+        .process_injected_effect_on(&node_ids[0], move |_effect_builder| {
+            let events = smallvec![
+                Event::DeployGossiper(super::Event::ItemReceived {
+                    item_id: forwarded_deploy_id,
+                    source: forwarded_source,
+                }),
+                Event::DeployGossiper(super::Event::ItemReceived {
+                    item_id: local_deploy_id,
+                    source: Source::Client,
+                }),
+            ];
+            smallvec![async move { events }.boxed()]
+        })
+        .await;
+
+    // Run node 0 until both items are queued, and check the local one is queued first.
+    let received_local_deploy = |event: &Event| -> bool {
+        match event {
+            Event::DeployGossiper(super::Event::ItemReceived {
+                source: Source::Client,
+                ..
+            }) => true,
+            _ => false,
+        }
+    };
+    network
+        .crank_until(&node_ids[0], &mut rng, received_local_deploy, TIMEOUT)
+        .await;
+    let queued = network.nodes()[&node_ids[0]]
+        .reactor()
+        .inner()
+        .deploy_gossiper
+        .queued_item_ids();
+    assert_eq!(queued, vec![local_deploy_id, forwarded_deploy_id]);
+
+    // Run node 0 until it makes its first gossip request, which should be for the local deploy
+    // and use the higher fan-out.
+    let local_infection_target = usize::from(Config::default().local_infection_target());
+    let gossiped_local_deploy_first = move |event: &Event| -> bool {
+        match event {
+            Event::NetworkRequest(NetworkRequest::Gossip {
+                payload: NodeMessage::DeployGossiper(Message::Gossip(deploy_id)),
+                count,
+                ..
+            }) => {
+                assert_eq!(
+                    *deploy_id, local_deploy_id,
+                    "forwarded deploy was gossiped first"
+                );
+                assert_eq!(*count, local_infection_target);
+                true
+            }
+            _ => false,
+        }
+    };
+    network
+        .crank_until(&node_ids[0], &mut rng, gossiped_local_deploy_first, TIMEOUT)
+        .await;
+
+    NetworkController::<NodeMessage>::remove_active();
+}
+
+#[tokio::test]
+async fn should_gossip_widely_seen_deploy_after_fresh_one() {
+    const TIMEOUT: Duration = Duration::from_secs(2);
+
+    NetworkController::<NodeMessage>::create_active();
+    let mut network = Network::<Reactor>::new();
+    let mut rng = TestRng::new();
+
+    let node_ids = network.add_nodes(&mut rng, 5).await;
+    let peers = node_ids[1..].to_vec();
+
+    let widely_seen_deploy_id = *Deploy::random(&mut rng).id();
+    let fresh_deploy_id = *Deploy::random(&mut rng).id();
+
+    // Node 0 is connected to four peers, three of which acknowledge holding one deploy, before
+    // both deploys are submitted to it, the widely-seen one first.
+    network
+        .process_injected_effect_on(&node_ids[0], move |_effect_builder| {
+            let mut events: Vec<Event> = peers
+                .iter()
+                .map(|peer| Event::DeployGossiper(super::Event::PeerConnected(*peer)))
+                .collect();
+            events.extend(peers[..3].iter().map(|peer| {
+                Event::DeployGossiper(super::Event::MessageReceived {
+                    sender: *peer,
+                    message: Message::GossipResponse {
+                        item_id: widely_seen_deploy_id,
+                        is_already_held: true,
+                    },
+                })
+            }));
+            for &item_id in &[widely_seen_deploy_id, fresh_deploy_id] {
+                events.push(Event::DeployGossiper(super::Event::ItemReceived {
+                    item_id,
+                    source: Source::Client,
+                }));
+            }
+            let events: SmallVec<[Event; 2]> = events.into_iter().collect();
+            smallvec![async move { events }.boxed()]
+        })
+        .await;
+
+    // Run node 0 until both deploys are queued, and check the fresh one is queued first.
+    let received_fresh_deploy = move |event: &Event| -> bool {
+        match event {
+            Event::DeployGossiper(super::Event::ItemReceived { item_id, .. }) => {
+                *item_id == fresh_deploy_id
+            }
+            _ => false,
+        }
+    };
+    network
+        .crank_until(&node_ids[0], &mut rng, received_fresh_deploy, TIMEOUT)
+        .await;
+    let queued = network.nodes()[&node_ids[0]]
+        .reactor()
+        .inner()
+        .deploy_gossiper
+        .queued_item_ids();
+    assert_eq!(queued, vec![fresh_deploy_id, widely_seen_deploy_id]);
+
+    // Run node 0 until it makes its first gossip request, which should be for the fresh deploy.
+    let gossiped_fresh_deploy_first = move |event: &Event| -> bool {
+        match event {
+            Event::NetworkRequest(NetworkRequest::Gossip {
+                payload: NodeMessage::DeployGossiper(Message::Gossip(deploy_id)),
+                ..
+            }) => {
+                assert_eq!(
+                    *deploy_id, fresh_deploy_id,
+                    "widely-seen deploy was gossiped first"
+                );
+                true
+            }
+            _ => false,
+        }
+    };
+    network
+        .crank_until(&node_ids[0], &mut rng, gossiped_fresh_deploy_first, TIMEOUT)
+        .await;
+
+    NetworkController::<NodeMessage>::remove_active();
+}
+
+#[tokio::test]
+async fn should_gossip_deploy_from_api_submission_deterministically() {
+    const NETWORK_SIZE: usize = 3;
+
+    NetworkController::<NodeMessage>::create_active();
+    let mut harness = Harness::<Reactor>::new(TestRng::new());
+    for _ in 0..NETWORK_SIZE {
+        // The clock of the harness advances to the next timer whenever the nodes are idle, so
+        // periodic anti-entropy rounds would keep them from ever becoming idle.
+        let config = Config::default().with_anti_entropy_interval_secs(0);
+        let _ = harness.add_node(config).await;
+    }
+    let node_ids = harness.node_ids().to_vec();
+
+    // Submit a deploy to node 0 as if via the API, and run until every node has stored it.
+    let deploy = Box::new(Deploy::random(harness.rng()));
+    let deploy_id = *deploy.id();
+    harness
+        .inject(&node_ids[0], announce_deploy_received(deploy))
+        .await;
+    let all_deploys_held = |nodes: &Nodes<Reactor>| {
+        nodes.values().all(|runner| {
+            runner
+                .reactor()
+                .inner()
+                .storage
+                .deploy_store()
+                .ids()
+                .unwrap()
+                == vec![deploy_id]
+        })
+    };
+    harness.run_until(&all_deploys_held).await;
+    harness.run_until_idle().await;
+
+    // Node 0 accepted the deploy from the client, stored it, and only then gossiped it.
+    let events = harness.events(&node_ids[0]);
+    let position = |prefix: &str| {
+        events
+            .iter()
+            .position(|event| event.starts_with(prefix))
+            .unwrap_or_else(|| panic!("no {} event in {:?}", prefix, events))
+    };
+    assert_eq!(position("api server announcement"), 0);
+    assert!(position("storage") < position("deploy-acceptor announcement"));
+    assert!(position("deploy-acceptor announcement") < position("network request"));
+
+    // Once the gossip timeouts fire, no node accepts the deploy again.
+    let event_counts: Vec<_> = node_ids
+        .iter()
+        .map(|node_id| harness.events(node_id).len())
+        .collect();
+    let secs_to_advance = Config::default().gossip_request_timeout_secs();
+    harness
+        .advance_time(Duration::from_secs(secs_to_advance))
+        .await;
+    harness.run_until_idle().await;
+    for (node_id, event_count) in node_ids.iter().zip(event_counts) {
+        assert!(!harness.events(node_id)[event_count..]
+            .iter()
+            .any(|event| event.starts_with("deploy-acceptor announcement")));
+    }
+    assert!(all_deploys_held(harness.nodes()));
+
+    NetworkController::<NodeMessage>::remove_active();
+}
+
+/// Check that a deploy is only gossiped once it is stored, so that peers can get it from storage as
+/// soon as they are told about it, and that a deploy stored already is not gossiped again.
+#[tokio::test]
+async fn should_gossip_deploy_only_once_stored() {
+    const NETWORK_SIZE: usize = 2;
+
+    NetworkController::<NodeMessage>::create_active();
+    let mut harness = Harness::<Reactor>::new(TestRng::new());
+    for _ in 0..NETWORK_SIZE {
+        // The clock of the harness advances to the next timer whenever the nodes are idle, so
+        // periodic anti-entropy rounds would keep them from ever becoming idle.
+        let config = Config::default().with_anti_entropy_interval_secs(0);
+        let _ = harness.add_node(config).await;
+    }
+    let node_ids = harness.node_ids().to_vec();
+
+    let deploy = Box::new(Deploy::random(harness.rng()));
+    harness
+        .inject(&node_ids[0], announce_deploy_received(deploy.clone()))
+        .await;
+    harness.run_until_idle().await;
+
+    {
+        let events = harness.events(&node_ids[0]);
+        let position = |prefix: &str| {
+            events
+                .iter()
+                .position(|event| event.starts_with(prefix))
+                .unwrap_or_else(|| panic!("no {} event in {:?}", prefix, events))
+        };
+        let accepted = position("deploy-acceptor announcement: accepted deploy");
+        let stored = position("deploy-acceptor announcement: stored new deploy");
+        assert!(accepted < stored);
+        assert!(stored < position("network request: gossip: DeployGossiper::gossip("));
+    }
+
+    // Submitting the stored deploy again has it accepted, but not gossiped again.
+    let event_count = harness.events(&node_ids[0]).len();
+    harness
+        .inject(&node_ids[0], announce_deploy_received(deploy))
+        .await;
+    harness.run_until_idle().await;
+    let new_events = &harness.events(&node_ids[0])[event_count..];
+    let has_event = |prefix: &str| new_events.iter().any(|event| event.starts_with(prefix));
+    assert!(has_event("deploy-acceptor announcement: accepted deploy"));
+    assert!(!has_event(
+        "deploy-acceptor announcement: stored new deploy"
+    ));
+    assert!(!has_event(
+        "network request: gossip: DeployGossiper::gossip("
+    ));
+
+    NetworkController::<NodeMessage>::remove_active();
+}
+
+#[test]
+fn should_forget_ongoing_gossip_on_restart() {
+    let mut rng = TestRng::new();
+    let mut gossiper = Gossiper::<Deploy, Event>::new_for_partial_items(
+        Config::default(),
+        get_deploy_from_storage,
+        LatencyStore::default(),
+    );
+    let peer: NodeId = rng.gen();
+    let deploy_hash = *Deploy::random(&mut rng).id();
+    let should_gossip = gossiper.table.new_local_data(&deploy_hash).unwrap();
+    gossiper.local_queue.push_back((deploy_hash, should_gossip));
+    gossiper.peer_scores.gossiped_to(deploy_hash, peer);
+
+    gossiper.on_restart();
+
+    assert!(gossiper.local_queue.is_empty());
+    // The deploy is new to the table again, and a late response doesn't count for the peer.
+    assert!(gossiper.table.new_local_data(&deploy_hash).is_some());
+    gossiper.peer_scores.response_received(deploy_hash, peer);
+    assert!(gossiper.peer_scores.weights(1.0).is_empty());
+}
+
+#[test]
+fn should_spread_gossip_timeouts_across_jitter_window() {
+    const SAMPLES: usize = 100;
+    let mut rng = TestRng::new();
+    let interval = Duration::from_secs(Config::default().gossip_request_timeout_secs());
+
+    // Without jitter, every timeout fires after exactly the interval.
+    assert!((0..SAMPLES).all(|_| jittered(interval, 0.0, &mut rng) == interval));
+
+    // With jitter, the timeouts lie within the window and are spread across it.
+    let jitter = 0.5;
+    let window_end = interval + interval.mul_f64(jitter);
+    let timeouts: BTreeSet<_> = (0..SAMPLES)
+        .map(|_| jittered(interval, jitter, &mut rng))
+        .collect();
+    assert!(timeouts.len() > SAMPLES / 2, "too few distinct timeouts");
+    let earliest = *timeouts.iter().next().unwrap();
+    let latest = *timeouts.iter().next_back().unwrap();
+    assert!(earliest >= interval);
+    assert!(latest <= window_end);
+    // Each quarter of the window should have been hit.
+    let quarter = (window_end - interval) / 4;
+    for index in 0..4 {
+        let start = interval + quarter * index;
+        assert!(
+            timeouts.range(start..start + quarter).next().is_some(),
+            "no timeout in quarter {} of the jitter window",
+            index
+        );
+    }
+}
@@ -3,6 +3,14 @@
 //! The `InMemoryNetwork` represents a full virtual network with flawless connectivity and delivery
 //! by default.
 //!
+//! # Adverse conditions
+//!
+//! To test how components cope with an unreliable network, the links to each node can be given
+//! `LinkConditions`: Messages sent to the node are delayed by a fixed latency plus a random jitter,
+//! and dropped at random with a given probability.  Conditions are set on the active network via
+//! `NetworkController::set_default_link_conditions` for all nodes, and overridden for individual
+//! nodes via `NetworkController::set_link_conditions`.
+//!
 //! # Setup
 //!
 //! The network itself is managed by a `NetworkController` that can be used to create networking
@@ -121,7 +129,9 @@
 //!                 if let Some(msg) = self.whispers.pop() {
 //!                     return effect_builder.gossip_message(msg,
 //!                                                          TEST_GOSSIP_COUNT,
-//!                                                          Default::default())
+//!                                                          Default::default(),
+//!                                                          Default::default(),
+//!                                                          false)
 //!                         .event(|_| ShouterEvent::ReadyToSend);
 //!                 }
 //!                 // Shouts get broadcast.
@@ -286,10 +296,14 @@ use std::{
     collections::{HashMap, HashSet},
     fmt::Display,
     sync::{Arc, RwLock},
+    time::Duration,
 };
 
 use rand::{seq::IteratorRandom, CryptoRng, Rng};
-use tokio::sync::mpsc::{self, error::SendError};
+use tokio::{
+    sync::mpsc::{self, error::SendError},
+    time,
+};
 use tracing::{debug, error, info, warn};
 
 use crate::{
@@ -301,6 +315,7 @@ use crate::{
     logging,
     reactor::{EventQueueHandle, QueueKind},
     tls::KeyFingerprint,
+    utils,
 };
 
 /// The node ID type used by the in-memory network.
@@ -308,6 +323,48 @@ pub type NodeId = KeyFingerprint;
 
 type Network<P> = Arc<RwLock<HashMap<NodeId, mpsc::UnboundedSender<(NodeId, P)>>>>;
 
+/// The link conditions of all nodes of a network.
+type Conditions = Arc<RwLock<NetworkConditions>>;
+
+/// The conditions of the links to a node, i.e. how messages sent to it are delayed and lost.
+///
+/// The default conditions are flawless: Messages are delivered immediately and never dropped.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct LinkConditions {
+    /// The delay of every message.
+    pub latency: Duration,
+    /// The maximum random delay added to the latency of each message.
+    pub jitter: Duration,
+    /// The probability of each message being dropped, between 0 and 1.
+    pub drop_rate: f64,
+}
+
+impl LinkConditions {
+    /// Returns the delay of a message sent over the link, or `None` if it is dropped.
+    fn delay<R: Rng + ?Sized>(&self, rng: &mut R) -> Option<Duration> {
+        if self.drop_rate > 0.0 && rng.gen_bool(self.drop_rate.min(1.0)) {
+            return None;
+        }
+        Some(self.latency + self.jitter.mul_f64(rng.gen()))
+    }
+}
+
+/// The link conditions of all nodes of a network.
+#[derive(Debug, Default)]
+struct NetworkConditions {
+    /// The conditions of the links to nodes without conditions of their own.
+    default: LinkConditions,
+    /// The conditions of the links to individual nodes.
+    nodes: HashMap<NodeId, LinkConditions>,
+}
+
+impl NetworkConditions {
+    /// Returns the conditions of the links to the given node.
+    fn get(&self, node_id: &NodeId) -> LinkConditions {
+        self.nodes.get(node_id).copied().unwrap_or(self.default)
+    }
+}
+
 thread_local! {
     /// The currently active network as a thread local.
     ///
@@ -320,6 +377,8 @@ thread_local! {
 pub struct NetworkController<P> {
     /// Channels for network communication.
     nodes: Network<P>,
+    /// The conditions of the links to the nodes.
+    conditions: Conditions,
 }
 
 impl<P> NetworkController<P>
@@ -331,6 +390,7 @@ where
         let _ = logging::init();
         NetworkController {
             nodes: Default::default(),
+            conditions: Default::default(),
         }
     }
 
@@ -410,6 +470,44 @@ where
         })
     }
 
+    /// Sets the conditions of the links to all nodes of the active network which have no
+    /// conditions of their own.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the internal lock has been poisoned, there is no active network or the active
+    /// network is not of the correct message type.
+    pub fn set_default_link_conditions(conditions: LinkConditions) {
+        Self::update_active_conditions(|network_conditions| {
+            network_conditions.default = conditions;
+        })
+    }
+
+    /// Sets the conditions of the links to the given node of the active network.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the internal lock has been poisoned, there is no active network or the active
+    /// network is not of the correct message type.
+    pub fn set_link_conditions(node_id: NodeId, conditions: LinkConditions) {
+        Self::update_active_conditions(|network_conditions| {
+            let _ = network_conditions.nodes.insert(node_id, conditions);
+        })
+    }
+
+    /// Applies `update` to the link conditions of the active network.
+    fn update_active_conditions<F: FnOnce(&mut NetworkConditions)>(update: F) {
+        ACTIVE_NETWORK.with(|active_network| {
+            let mut active_network = active_network.borrow_mut();
+            let controller = active_network
+                .as_mut()
+                .expect("tried to set link conditions without active network set")
+                .downcast_mut::<Self>()
+                .expect("active network has wrong message type");
+            update(&mut controller.conditions.write().expect("poisoned lock"));
+        })
+    }
+
     /// Creates a new networking node with a random node ID.
     ///
     /// Returns the already connected new networking component for new node.
@@ -422,7 +520,12 @@ where
         R: Rng + ?Sized,
         REv: From<NetworkAnnouncement<NodeId, P>> + Send,
     {
-        InMemoryNetwork::new(event_queue, rng.gen(), self.nodes.clone())
+        InMemoryNetwork::new(
+            event_queue,
+            rng.gen(),
+            self.nodes.clone(),
+            self.conditions.clone(),
+        )
     }
 }
 
@@ -434,13 +537,21 @@ pub struct InMemoryNetwork<P> {
 
     /// The nodes map, contains the incoming channel for each virtual node.
     nodes: Network<P>,
+
+    /// The conditions of the links to the nodes.
+    conditions: Conditions,
 }
 
 impl<P> InMemoryNetwork<P>
 where
     P: 'static + Send,
 {
-    fn new<REv>(event_queue: EventQueueHandle<REv>, node_id: NodeId, nodes: Network<P>) -> Self
+    fn new<REv>(
+        event_queue: EventQueueHandle<REv>,
+        node_id: NodeId,
+        nodes: Network<P>,
+        conditions: Conditions,
+    ) -> Self
     where
         REv: From<NetworkAnnouncement<NodeId, P>> + Send,
     {
@@ -455,7 +566,11 @@ where
 
         tokio::spawn(receiver_task(event_queue, receiver));
 
-        InMemoryNetwork { node_id, nodes }
+        InMemoryNetwork {
+            node_id,
+            nodes,
+            conditions,
+        }
     }
 
     /// Returns this node's ID.
@@ -467,35 +582,64 @@ where
 
 impl<P> InMemoryNetwork<P>
 where
-    P: Display,
+    P: 'static + Display + Send,
 {
     /// Internal helper, sends a payload to a node, ignoring but logging all errors.
-    fn send(
+    ///
+    /// The payload is delayed or dropped according to the conditions of the link to the node.
+    fn send<R: Rng + ?Sized>(
         &self,
         nodes: &HashMap<NodeId, mpsc::UnboundedSender<(NodeId, P)>>,
         dest: NodeId,
         payload: P,
+        rng: &mut R,
     ) {
         if dest == self.node_id {
             panic!("can't send message to self");
         }
 
-        match nodes.get(&dest) {
-            Some(sender) => {
-                if let Err(SendError((_, msg))) = sender.send((self.node_id, payload)) {
-                    warn!(%dest, %msg, "could not send message (send error)");
-
-                    // We do nothing else, the message is just dropped.
-                }
+        let sender = match nodes.get(&dest) {
+            Some(sender) => sender,
+            None => {
+                info!(%dest, %payload, "dropping message to non-existent recipient");
+                return;
+            }
+        };
+        let conditions = self.conditions.read().expect("poisoned lock").get(&dest);
+        match conditions.delay(rng) {
+            None => debug!(%dest, %payload, "dropping message due to simulated packet loss"),
+            Some(delay) if delay == Duration::from_secs(0) => {
+                deliver(sender, self.node_id, dest, payload)
+            }
+            Some(delay) => {
+                let sender = sender.clone();
+                let source = self.node_id;
+                tokio::spawn(async move {
+                    time::delay_for(delay).await;
+                    deliver(&sender, source, dest, payload)
+                });
             }
-            None => info!(%dest, %payload, "dropping message to non-existent recipient"),
         }
     }
 }
 
+/// Sends a payload from `source` to the channel of `dest`, ignoring but logging all errors.
+fn deliver<P: Display>(
+    sender: &mpsc::UnboundedSender<(NodeId, P)>,
+    source: NodeId,
+    dest: NodeId,
+    payload: P,
+) {
+    if let Err(SendError((_, msg))) = sender.send((source, payload)) {
+        warn!(%dest, %msg, "could not send message (send error)");
+
+        // We do nothing else, the message is just dropped.
+    }
+}
+
 impl<P, REv, R> Component<REv, R> for InMemoryNetwork<P>
 where
-    P: Display + Clone,
+    P: 'static + Display + Clone + Send,
     R: Rng + CryptoRng + ?Sized,
 {
     type Event = NetworkRequest<NodeId, P>;
@@ -517,17 +661,19 @@ where
                 }
 
                 if let Ok(guard) = self.nodes.read() {
-                    self.send(&guard, dest, payload);
+                    self.send(&guard, dest, payload, rng);
                 } else {
                     error!("network lock has been poisoned")
                 };
 
                 responder.respond(()).ignore()
             }
-            NetworkRequest::Broadcast { payload, responder } => {
+            // The in-memory network doesn't know the validators, so it broadcasts to all nodes.
+            NetworkRequest::Broadcast { payload, responder }
+            | NetworkRequest::BroadcastToValidators { payload, responder } => {
                 if let Ok(guard) = self.nodes.read() {
                     for dest in guard.keys().filter(|&node_id| node_id != &self.node_id) {
-                        self.send(&guard, *dest, payload.clone());
+                        self.send(&guard, *dest, payload.clone(), rng);
                     }
                 } else {
                     error!("network lock has been poisoned")
@@ -539,19 +685,28 @@ where
                 payload,
                 count,
                 exclude,
+                weights,
+                spread_localities: _,
                 responder,
             } => {
+                // Nodes of the in-memory network have no localities.
                 if let Ok(guard) = self.nodes.read() {
-                    let chosen: HashSet<_> = guard
+                    let candidates = guard
                         .keys()
                         .filter(|&node_id| !exclude.contains(node_id) && node_id != &self.node_id)
-                        .cloned()
-                        .choose_multiple(rng, count)
+                        .cloned();
+                    let chosen: HashSet<_> = if weights.is_empty() {
+                        candidates.choose_multiple(rng, count).into_iter().collect()
+                    } else {
+                        utils::choose_weighted_multiple(rng, candidates, count, |node_id| {
+                            weights.get(node_id).copied().unwrap_or(1.0)
+                        })
                         .into_iter()
-                        .collect();
+                        .collect()
+                    };
                     // Not terribly efficient, but will always get us the maximum amount of nodes.
                     for &dest in chosen.iter() {
-                        self.send(&guard, dest, payload.clone());
+                        self.send(&guard, dest, payload.clone(), rng);
                     }
                     responder.respond(chosen).ignore()
                 } else {
@@ -1,5 +1,5 @@
 use std::{
-    collections::HashMap,
+    collections::{BTreeMap, HashMap},
     fmt::{self, Display, Formatter},
     marker::PhantomData,
 };
@@ -9,11 +9,14 @@ use futures::FutureExt;
 use rand::{CryptoRng, Rng};
 use tracing::{debug, error, info, warn};
 
+use casper_execution_engine::shared::motes::Motes;
+
 use super::{storage::Storage, Component};
 use crate::{
-    components::storage::Value,
-    crypto::asymmetric_key::Signature,
+    components::storage::{EraSummary, StorageFailure, Value},
+    crypto::asymmetric_key::{PublicKey, Signature},
     effect::{
+        announcements::LinearChainAnnouncement,
         requests::{ConsensusRequest, LinearChainRequest, NetworkRequest, StorageRequest},
         EffectExt, Effects,
     },
@@ -29,20 +32,22 @@ pub enum Event<I> {
     /// New linear chain block has been produced.
     LinearChainBlock {
         /// The block.
-        block: Block,
+        block: Box<Block>,
         /// The deploys' execution results.
         execution_results: HashMap<DeployHash, ExecutionResult>,
     },
     /// A continuation for `GetBlock` scenario.
-    GetBlockResult(BlockHash, Option<Block>, I),
+    GetBlockResult(BlockHash, Option<Box<Block>>, I),
     /// New finality signature.
     NewFinalitySignature(BlockHash, Signature),
     /// The result of putting a block to storage.
     PutBlockResult {
         /// The block.
-        block: Block,
+        block: Box<Block>,
         /// The deploys' execution results.
         execution_results: HashMap<DeployHash, ExecutionResult>,
+        /// Whether the block is new, or the failure of storage making the node shut down.
+        result: Result<bool, StorageFailure>,
     },
 }
 
@@ -77,14 +82,20 @@ pub(crate) struct LinearChain<I> {
     /// The last block this component put to storage which is presumably the last block in the
     /// linear chain.
     last_block: Option<Block>,
+    /// The weights of the validators, recorded in the summary of each era.
+    validator_weights: BTreeMap<PublicKey, Motes>,
     _marker: PhantomData<I>,
 }
 
 impl<I> LinearChain<I> {
-    pub fn new() -> Self {
+    pub fn new<V>(validator_weights: V) -> Self
+    where
+        V: IntoIterator<Item = (PublicKey, Motes)>,
+    {
         LinearChain {
             linear_chain: Vec::new(),
             last_block: None,
+            validator_weights: validator_weights.into_iter().collect(),
             _marker: PhantomData,
         }
     }
@@ -92,6 +103,37 @@ impl<I> LinearChain<I> {
     pub fn linear_chain(&self) -> &Vec<Block> {
         &self.linear_chain
     }
+
+    /// Adds `block` to the linear chain, returning the blocks it orphans.
+    ///
+    /// If the linear chain already holds blocks at or above the height of `block`, a fork has been
+    /// finalized, and `block` supersedes them: They are removed and returned.  A block which is
+    /// already part of the linear chain doesn't change anything.
+    fn add_block(&mut self, block: &Block) -> Vec<Block> {
+        let index = self
+            .linear_chain
+            .iter()
+            .position(|existing| existing.height() >= block.height());
+        let orphaned = match index {
+            Some(index) if self.linear_chain[index].hash() == block.hash() => return Vec::new(),
+            Some(index) => self.linear_chain.split_off(index),
+            None => Vec::new(),
+        };
+        self.linear_chain.push(block.clone());
+        orphaned
+    }
+
+    /// Returns the summary of the era concluded by the given switch block.
+    ///
+    /// Until validators rotate, the validators of every era are the genesis validators.
+    fn era_summary(&self, switch_block: &Block) -> EraSummary<Block> {
+        EraSummary {
+            era_id: switch_block.era_id(),
+            validator_weights: self.validator_weights.clone(),
+            total_weight: self.validator_weights.values().copied().sum(),
+            last_block_hash: *switch_block.hash(),
+        }
+    }
 }
 
 impl<I, REv, R> Component<REv, R> for LinearChain<I>
@@ -99,6 +141,7 @@ where
     REv: From<StorageRequest<Storage>>
         + From<ConsensusRequest>
         + From<NetworkRequest<I, Message>>
+        + From<LinearChainAnnouncement>
         + Send,
     R: Rng + CryptoRng + ?Sized,
     I: Display + Send + 'static,
@@ -114,7 +157,9 @@ where
         match event {
             Event::Request(LinearChainRequest::BlockRequest(block_hash, sender)) => effect_builder
                 .get_block_from_storage(block_hash)
-                .event(move |maybe_block| Event::GetBlockResult(block_hash, maybe_block, sender)),
+                .event(move |maybe_block| {
+                    Event::GetBlockResult(block_hash, maybe_block.map(Box::new), sender)
+                }),
             Event::Request(LinearChainRequest::LastFinalizedBlock(responder)) => {
                 responder.respond(self.last_block.clone()).ignore()
             }
@@ -124,7 +169,7 @@ where
                         debug!("failed to get {} for {}", block_hash, sender);
                         Effects::new()
                     },
-                    Some(block) => match Message::new_get_response(&block) {
+                    Some(block) => match Message::new_get_response(&*block) {
                         Ok(message) => effect_builder.send_message(sender, message).ignore(),
                         Err(error) => {
                             error!("failed to create get-response {}", error);
@@ -134,13 +179,24 @@ where
                 }
             }
             Event::LinearChainBlock{ block, execution_results } => {
-                effect_builder
-                .put_block_to_storage(Box::new(block.clone()))
-                .event(move |_| Event::PutBlockResult{ block, execution_results })
+                // The last block of an era is stored together with the era's summary.
+                let put_block = if block.header().switch_block() {
+                    let era_summary = Box::new(self.era_summary(&block));
+                    effect_builder
+                        .put_switch_block_to_storage(block.clone(), era_summary)
+                        .boxed()
+                } else {
+                    effect_builder.put_block_to_storage(block.clone()).boxed()
+                };
+                put_block.event(move |result| Event::PutBlockResult{ block, execution_results, result })
+            },
+            Event::PutBlockResult { block, result: Err(error), .. } => {
+                debug!(block_hash = ?block.hash(), %error, "not adding block to linear chain");
+                Effects::new()
             },
-            Event::PutBlockResult { block, execution_results } => {
-                self.linear_chain.push(block.clone());
-                self.last_block = Some(block.clone());
+            Event::PutBlockResult { block, execution_results, result: Ok(_) } => {
+                let orphaned = self.add_block(&block);
+                self.last_block = Some((*block).clone());
 
                 let block_header = block.take_header();
                 let block_hash = block_header.hash();
@@ -150,7 +206,12 @@ where
                 // Using `Debug` impl for the `block_hash` to not truncate it.
                 info!(?block_hash, ?era_id, ?height, "Linear chain block stored.");
 
-                let mut effects = effect_builder.put_execution_results_to_storage(block_hash, execution_results).ignore();
+                let mut effects = Effects::new();
+                for orphan in orphaned {
+                    warn!(orphaned_block_hash = ?orphan.hash(), ?block_hash, ?height, "Linear chain forked, orphaning block.");
+                    effects.extend(effect_builder.announce_block_orphaned(Box::new(orphan)).ignore());
+                }
+                effects.extend(effect_builder.put_execution_results_to_storage(block_hash, execution_results).ignore());
                 effects.extend(
                     effect_builder.handle_linear_chain_block(block_header)
                     .event(move |signature| Event::NewFinalitySignature(block_hash, signature)));
@@ -175,3 +236,161 @@ where
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use casper_types::U512;
+
+    use super::*;
+    use crate::{
+        components::{
+            consensus::EraId,
+            small_network::NodeId,
+            storage::{self, Config, StorageType},
+        },
+        crypto::{asymmetric_key::SecretKey, hash::Digest},
+        effect::{
+            announcements::{ControlAnnouncement, LinearChainAnnouncement, StorageAnnouncement},
+            EffectBuilder,
+        },
+        reactor::{EventQueueHandle, QueueKind, Scheduler},
+        testing::TestRng,
+        types::{FinalizedBlock, ProtoBlock, Timestamp},
+        utils,
+    };
+
+    #[derive(Debug, From)]
+    enum ReactorEvent {
+        #[from]
+        Storage(StorageRequest<Storage>),
+        #[from]
+        Consensus(ConsensusRequest),
+        #[from]
+        Network(NetworkRequest<NodeId, Message>),
+        #[from]
+        StorageAnnouncement(StorageAnnouncement),
+        #[from]
+        LinearChainAnnouncement(LinearChainAnnouncement),
+        #[from]
+        ControlAnnouncement(ControlAnnouncement),
+    }
+
+    /// Pops the next event, which must be a storage request, and lets `storage` handle it.
+    async fn handle_storage_request(
+        scheduler: &Scheduler<ReactorEvent>,
+        storage: &mut Storage,
+        effect_builder: EffectBuilder<ReactorEvent>,
+        rng: &mut TestRng,
+    ) {
+        let request = match scheduler.pop().await.0 {
+            ReactorEvent::Storage(request) => request,
+            other => panic!("unexpected event {:?}", other),
+        };
+        for effect in storage.handle_event(effect_builder, rng, storage::Event::Request(request)) {
+            let _ = effect.await;
+        }
+    }
+
+    #[tokio::test]
+    async fn should_store_era_summary_with_switch_block() {
+        let mut rng = TestRng::new();
+        let scheduler = utils::leak(Scheduler::<ReactorEvent>::new(QueueKind::weights()));
+        let effect_builder = EffectBuilder::new(EventQueueHandle::new(scheduler));
+        let (config, _temp_dir) = Config::default_for_tests();
+        let mut storage = Storage::new(&config).expect("should create storage");
+
+        let validator_weights: BTreeMap<_, _> = (1..4_u64)
+            .map(|weight| {
+                let public_key = PublicKey::from(&SecretKey::random(&mut rng));
+                (public_key, Motes::new(U512::from(weight)))
+            })
+            .collect();
+        let mut linear_chain = LinearChain::<NodeId>::new(validator_weights.clone());
+
+        let era_id = EraId(3);
+        let finalized_block = FinalizedBlock::new(
+            ProtoBlock::new(vec![], false),
+            Timestamp::now(),
+            vec![],
+            true,
+            era_id,
+            30,
+            PublicKey::from(&SecretKey::random(&mut rng)),
+        );
+        let switch_block = Block::new(
+            BlockHash::new(Digest::random(&mut rng)),
+            Digest::random(&mut rng),
+            finalized_block,
+        );
+        let switch_block_hash = *switch_block.hash();
+
+        // Finalizing the era's last block stores it together with the era's summary.
+        let event = Event::LinearChainBlock {
+            block: Box::new(switch_block),
+            execution_results: HashMap::new(),
+        };
+        let mut effects = linear_chain.handle_event(effect_builder, &mut rng, event);
+        assert_eq!(effects.len(), 1);
+        let put_block = tokio::spawn(effects.pop().unwrap());
+        handle_storage_request(scheduler, &mut storage, effect_builder, &mut rng).await;
+        let events = put_block.await.expect("should join");
+        assert!(matches!(events.as_slice(), [Event::PutBlockResult { .. }]));
+
+        let get_era_summary =
+            tokio::spawn(effect_builder.get_era_summary_from_storage::<Storage>(era_id));
+        handle_storage_request(scheduler, &mut storage, effect_builder, &mut rng).await;
+        let era_summary = get_era_summary
+            .await
+            .expect("should join")
+            .expect("should have era summary");
+        assert_eq!(era_summary.era_id, era_id);
+        assert_eq!(era_summary.validator_weights, validator_weights);
+        assert_eq!(era_summary.total_weight, Motes::new(U512::from(6)));
+        assert_eq!(era_summary.last_block_hash, switch_block_hash);
+
+        // Eras whose last block hasn't been stored have no summary.
+        let get_era_summary =
+            tokio::spawn(effect_builder.get_era_summary_from_storage::<Storage>(EraId(4)));
+        handle_storage_request(scheduler, &mut storage, effect_builder, &mut rng).await;
+        assert!(get_era_summary.await.expect("should join").is_none());
+    }
+
+    fn block_at_height(height: u64, rng: &mut TestRng) -> Block {
+        let finalized_block = FinalizedBlock::new(
+            ProtoBlock::new(vec![], rng.gen()),
+            Timestamp::now(),
+            vec![],
+            false,
+            EraId(0),
+            height,
+            PublicKey::from(&SecretKey::random(rng)),
+        );
+        Block::new(
+            BlockHash::new(Digest::random(rng)),
+            Digest::random(rng),
+            finalized_block,
+        )
+    }
+
+    #[test]
+    fn should_orphan_blocks_replaced_by_fork() {
+        let mut rng = TestRng::new();
+        let mut linear_chain = LinearChain::<NodeId>::new(BTreeMap::new());
+
+        let blocks: Vec<_> = (0..3)
+            .map(|height| block_at_height(height, &mut rng))
+            .collect();
+        for block in &blocks {
+            assert!(linear_chain.add_block(block).is_empty());
+        }
+        // Adding a block again is a no-op.
+        assert!(linear_chain.add_block(&blocks[1]).is_empty());
+        assert_eq!(linear_chain.linear_chain, blocks);
+
+        // A different block at height 1 orphans the blocks from height 1 on.
+        let fork = block_at_height(1, &mut rng);
+        let orphaned = linear_chain.add_block(&fork);
+        assert_eq!(orphaned, blocks[1..].to_vec());
+        assert_eq!(linear_chain.linear_chain, vec![blocks[0].clone(), fork]);
+    }
+}
@@ -17,7 +17,7 @@ use tracing::{error, info, trace, warn};
 pub trait ReactorEventT<I>:
     From<StorageRequest<Storage>>
     + From<FetcherRequest<I, Block>>
-    + From<BlockValidationRequest<Block, I>>
+    + From<BlockValidationRequest<Box<Block>, I>>
     + From<BlockExecutorRequest>
     + Send
 {
@@ -26,7 +26,7 @@ pub trait ReactorEventT<I>:
 impl<I, REv> ReactorEventT<I> for REv where
     REv: From<StorageRequest<Storage>>
         + From<FetcherRequest<I, Block>>
-        + From<BlockValidationRequest<Block, I>>
+        + From<BlockValidationRequest<Box<Block>, I>>
         + From<BlockExecutorRequest>
         + Send
 {
@@ -122,7 +122,7 @@ impl<I: Clone + 'static> LinearChainSync<I> {
                 // We're done syncing but we have to wait for the execution of all blocks.
                 Effects::new()
             }
-            Some(block) => fetch_block_deploys(effect_builder, peer, block),
+            Some(block) => fetch_block_deploys(effect_builder, peer, Box::new(block)),
         }
     }
 
@@ -252,7 +252,7 @@ where
                     error!(%block_hash, "Could not download deploys from linear chain block.");
                     panic!("Failed to download linear chain deploys.")
                 }
-                Some(peer) => fetch_block_deploys(effect_builder, peer, *block),
+                Some(peer) => fetch_block_deploys(effect_builder, peer, block),
             },
             Event::LinearChainBlocksDownloaded => {
                 // Start downloading deploys from the first block of the linear chain.
@@ -287,7 +287,7 @@ where
 fn fetch_block_deploys<I: Send + Copy + 'static, REv>(
     effect_builder: EffectBuilder<REv>,
     peer: I,
-    block: Block,
+    block: Box<Block>,
 ) -> Effects<Event<I>>
 where
     REv: ReactorEventT<I>,
@@ -296,9 +296,9 @@ where
         .validate_block(peer, block)
         .event(move |(found, block)| {
             if found {
-                Event::DeploysFound(Box::new(block))
+                Event::DeploysFound(block)
             } else {
-                Event::DeploysNotFound(Box::new(block))
+                Event::DeploysNotFound(block)
             }
         })
 }
@@ -0,0 +1,483 @@
+//! Load shedder.
+//!
+//! The load shedder periodically samples signals of resource pressure: the depth of the event
+//! queue, the time storage takes to answer a request and the resident memory of the process.  As
+//! the pressure rises, it sheds load in stages, each one adding to the previous: first anti-entropy
+//! rounds are paused, then the gossip fan-out is reduced, and finally deploys submitted by clients
+//! are rejected.  The level moves by at most one stage per check, so a short spike doesn't shed
+//! everything at once, and a stage is only lifted once the pressure has fallen a margin below the
+//! threshold it was entered at, to avoid flapping between stages.
+//!
+//! Checks are scheduled independently of storage answering, and only one storage probe is
+//! outstanding at a time.  A probe not answered within the maximum storage latency counts as full
+//! pressure on storage at every check until it is answered.
+//!
+//! The load shedder only announces the level; the reactor applies it to the affected components.
+
+mod config;
+
+use std::{
+    fmt::{self, Display, Formatter},
+    fs, mem,
+    time::{Duration, Instant},
+};
+
+use rand::{CryptoRng, Rng};
+use tracing::{info, warn};
+
+use super::Component;
+use crate::{
+    components::storage::Storage,
+    crypto::hash::Digest,
+    effect::{
+        announcements::LoadShedderAnnouncement, requests::StorageRequest, EffectBuilder, EffectExt,
+        Effects,
+    },
+    types::BlockHash,
+    OS_PAGE_SIZE,
+};
+pub use config::Config;
+
+/// The stages of load shedding, in the order they are entered as the pressure rises.
+#[derive(Copy, Clone, Debug, Eq, PartialEq, Ord, PartialOrd)]
+pub enum ShedLevel {
+    /// No load is shed.
+    Normal,
+    /// Anti-entropy rounds are paused.
+    PauseAntiEntropy,
+    /// Additionally, the gossip fan-out is reduced.
+    ReduceFanOut,
+    /// Additionally, deploys submitted by clients are rejected.
+    RejectSubmissions,
+}
+
+impl ShedLevel {
+    /// Returns whether anti-entropy rounds are paused at this level.
+    pub(crate) fn pauses_anti_entropy(self) -> bool {
+        self >= ShedLevel::PauseAntiEntropy
+    }
+
+    /// Returns whether the gossip fan-out is reduced at this level.
+    pub(crate) fn reduces_fan_out(self) -> bool {
+        self >= ShedLevel::ReduceFanOut
+    }
+
+    /// Returns whether deploys submitted by clients are rejected at this level.
+    pub(crate) fn rejects_submissions(self) -> bool {
+        self >= ShedLevel::RejectSubmissions
+    }
+
+    /// Returns the next stage to enter as the pressure rises, if any.
+    fn escalated(self) -> Option<ShedLevel> {
+        match self {
+            ShedLevel::Normal => Some(ShedLevel::PauseAntiEntropy),
+            ShedLevel::PauseAntiEntropy => Some(ShedLevel::ReduceFanOut),
+            ShedLevel::ReduceFanOut => Some(ShedLevel::RejectSubmissions),
+            ShedLevel::RejectSubmissions => None,
+        }
+    }
+
+    /// Returns the stage to return to as the pressure eases, if any.
+    fn relaxed(self) -> Option<ShedLevel> {
+        match self {
+            ShedLevel::Normal => None,
+            ShedLevel::PauseAntiEntropy => Some(ShedLevel::Normal),
+            ShedLevel::ReduceFanOut => Some(ShedLevel::PauseAntiEntropy),
+            ShedLevel::RejectSubmissions => Some(ShedLevel::ReduceFanOut),
+        }
+    }
+}
+
+impl Display for ShedLevel {
+    fn fmt(&self, formatter: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            ShedLevel::Normal => write!(formatter, "normal"),
+            ShedLevel::PauseAntiEntropy => write!(formatter, "anti-entropy paused"),
+            ShedLevel::ReduceFanOut => write!(formatter, "gossip fan-out reduced"),
+            ShedLevel::RejectSubmissions => write!(formatter, "client submissions rejected"),
+        }
+    }
+}
+
+/// A sample of the signals of resource pressure.
+#[derive(Copy, Clone, Debug, Default)]
+struct Signals {
+    /// The number of events in the event queue.
+    event_queue_depth: usize,
+    /// The time storage took to answer a request.
+    storage_latency: Duration,
+    /// The resident memory of the process in bytes, if known.
+    memory_bytes: Option<u64>,
+}
+
+/// Load shedder events.
+#[derive(Debug)]
+pub enum Event {
+    /// The pressure should be checked.
+    CheckPressure,
+    /// The storage request sent to check the pressure has been answered.
+    StorageProbed {
+        /// The number of events in the event queue when checking the pressure.
+        event_queue_depth: usize,
+        /// The time storage took to answer.
+        storage_latency: Duration,
+    },
+}
+
+impl Display for Event {
+    fn fmt(&self, formatter: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            Event::CheckPressure => write!(formatter, "check pressure"),
+            Event::StorageProbed {
+                event_queue_depth,
+                storage_latency,
+            } => write!(
+                formatter,
+                "storage probed with {} queued events in {:?}",
+                event_queue_depth, storage_latency
+            ),
+        }
+    }
+}
+
+/// The load shedder.
+#[derive(Debug)]
+pub(crate) struct LoadShedder {
+    config: Config,
+    /// The current stage of load shedding.
+    level: ShedLevel,
+    /// The time the outstanding storage probe was sent, if any.
+    probe_sent: Option<Instant>,
+    /// Whether the outstanding storage probe has already been assessed as timed out.
+    probe_timed_out: bool,
+}
+
+impl LoadShedder {
+    /// Creates a load shedder which doesn't shed any load yet.
+    pub(crate) fn new(config: Config) -> Self {
+        LoadShedder {
+            config,
+            level: ShedLevel::Normal,
+            probe_sent: None,
+            probe_timed_out: false,
+        }
+    }
+
+    /// Schedules the next check of the pressure, unless load shedding is disabled.
+    pub(crate) fn start<REv>(&self, effect_builder: EffectBuilder<REv>) -> Effects<Event>
+    where
+        REv: From<Event> + Send,
+    {
+        if self.config.check_interval.as_millis() == 0 {
+            return Effects::new();
+        }
+        effect_builder
+            .delay(self.config.check_interval, Event::CheckPressure)
+            .ignore()
+    }
+
+    /// Returns the pressure indicated by `signals`, as a fraction of the configured maximum of the
+    /// most pressing signal.
+    fn pressure(&self, signals: &Signals) -> f64 {
+        let fraction = |value: f64, max: f64| if max <= 0.0 { 0.0 } else { value / max };
+        let event_queue = fraction(
+            signals.event_queue_depth as f64,
+            self.config.max_event_queue_depth as f64,
+        );
+        let storage = fraction(
+            signals.storage_latency.as_secs_f64(),
+            self.config.max_storage_latency.as_secs_f64(),
+        );
+        let memory = fraction(
+            signals.memory_bytes.unwrap_or(0) as f64,
+            self.config.max_memory_bytes as f64,
+        );
+        event_queue.max(storage).max(memory)
+    }
+
+    /// Returns the pressure at which `level` is entered.
+    fn threshold(&self, level: ShedLevel) -> f64 {
+        match level {
+            ShedLevel::Normal => 0.0,
+            ShedLevel::PauseAntiEntropy => self.config.pause_anti_entropy_at,
+            ShedLevel::ReduceFanOut => self.config.reduce_fan_out_at,
+            ShedLevel::RejectSubmissions => self.config.reject_submissions_at,
+        }
+    }
+
+    /// Moves the level by up to one stage for the pressure indicated by `signals`.
+    ///
+    /// Returns the new level if it changed.
+    fn assess(&mut self, signals: &Signals) -> Option<ShedLevel> {
+        let pressure = self.pressure(signals);
+        let new_level = match (self.level.escalated(), self.level.relaxed()) {
+            (Some(escalated), _) if pressure >= self.threshold(escalated) => escalated,
+            (_, Some(relaxed))
+                if pressure < self.threshold(self.level) - self.config.recovery_margin =>
+            {
+                relaxed
+            }
+            _ => return None,
+        };
+        if new_level > self.level {
+            warn!(%pressure, level = %new_level, ?signals, "shedding load");
+        } else {
+            info!(%pressure, level = %new_level, ?signals, "pressure eased, restoring load");
+        }
+        self.level = new_level;
+        Some(new_level)
+    }
+
+    /// Assesses the pressure from the given signals and the current memory usage, announcing the
+    /// new level if it changed.
+    fn check<REv>(
+        &mut self,
+        effect_builder: EffectBuilder<REv>,
+        event_queue_depth: usize,
+        storage_latency: Duration,
+    ) -> Effects<Event>
+    where
+        REv: From<LoadShedderAnnouncement> + Send,
+    {
+        let memory_bytes = if self.config.max_memory_bytes == 0 {
+            None
+        } else {
+            resident_memory_bytes()
+        };
+        let signals = Signals {
+            event_queue_depth,
+            storage_latency,
+            memory_bytes,
+        };
+        match self.assess(&signals) {
+            Some(level) => effect_builder.announce_load_shedding_level(level).ignore(),
+            None => Effects::new(),
+        }
+    }
+}
+
+/// Returns the resident memory of this process in bytes, if available.
+fn resident_memory_bytes() -> Option<u64> {
+    let statm = fs::read_to_string("/proc/self/statm").ok()?;
+    let resident_pages: u64 = statm.split_whitespace().nth(1)?.parse().ok()?;
+    Some(resident_pages * *OS_PAGE_SIZE as u64)
+}
+
+impl<REv, R> Component<REv, R> for LoadShedder
+where
+    REv: From<Event> + From<StorageRequest<Storage>> + From<LoadShedderAnnouncement> + Send,
+    R: Rng + CryptoRng + ?Sized,
+{
+    type Event = Event;
+
+    fn handle_event(
+        &mut self,
+        effect_builder: EffectBuilder<REv>,
+        _rng: &mut R,
+        event: Self::Event,
+    ) -> Effects<Self::Event> {
+        match event {
+            Event::CheckPressure => {
+                // The next check is due regardless of how long storage takes to answer.
+                let mut effects = self.start(effect_builder);
+                let event_queue_depth = effect_builder.event_queue_depth();
+                if self.config.max_storage_latency.as_millis() == 0 {
+                    effects.extend(self.check(
+                        effect_builder,
+                        event_queue_depth,
+                        Duration::default(),
+                    ));
+                } else if let Some(probe_sent) = self.probe_sent {
+                    let storage_latency = probe_sent.elapsed();
+                    if storage_latency >= self.config.max_storage_latency {
+                        warn!(?storage_latency, "storage probe timed out");
+                        self.probe_timed_out = true;
+                        effects.extend(self.check(
+                            effect_builder,
+                            event_queue_depth,
+                            storage_latency,
+                        ));
+                    }
+                } else {
+                    let started = Instant::now();
+                    self.probe_sent = Some(started);
+                    // Looking up a block which doesn't exist is cheap, so the time taken is mostly
+                    // spent waiting for storage.
+                    effects.extend(
+                        effect_builder
+                            .get_block_header_from_storage::<Storage>(BlockHash::new(
+                                Digest::default(),
+                            ))
+                            .event(move |_| Event::StorageProbed {
+                                event_queue_depth,
+                                storage_latency: started.elapsed(),
+                            }),
+                    );
+                }
+                effects
+            }
+            Event::StorageProbed {
+                event_queue_depth,
+                storage_latency,
+            } => {
+                self.probe_sent = None;
+                // A timed out probe has been accounted for by the checks since.
+                if mem::replace(&mut self.probe_timed_out, false) {
+                    return Effects::new();
+                }
+                self.check(effect_builder, event_queue_depth, storage_latency)
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use derive_more::From;
+    use futures::FutureExt;
+    use tokio::time;
+
+    use super::*;
+    use crate::{
+        reactor::{EventQueueHandle, QueueKind, Scheduler},
+        testing::TestRng,
+        utils,
+    };
+
+    #[derive(Debug, From)]
+    enum ReactorEvent {
+        #[from]
+        LoadShedder(Event),
+        #[from]
+        Storage(StorageRequest<Storage>),
+        #[from]
+        LoadShedderAnnouncement(LoadShedderAnnouncement),
+    }
+
+    fn queue_depth(event_queue_depth: usize) -> Signals {
+        Signals {
+            event_queue_depth,
+            ..Signals::default()
+        }
+    }
+
+    #[test]
+    fn should_shed_load_in_stages_and_restore_it_in_reverse() {
+        let config = Config {
+            max_event_queue_depth: 100,
+            pause_anti_entropy_at: 0.5,
+            reduce_fan_out_at: 0.7,
+            reject_submissions_at: 0.9,
+            recovery_margin: 0.1,
+            ..Config::default()
+        };
+        let mut load_shedder = LoadShedder::new(config);
+
+        // Rising pressure enters each stage in order, even when jumping past several thresholds.
+        assert_eq!(load_shedder.assess(&queue_depth(10)), None);
+        assert_eq!(
+            load_shedder.assess(&queue_depth(55)),
+            Some(ShedLevel::PauseAntiEntropy)
+        );
+        assert_eq!(
+            load_shedder.assess(&queue_depth(95)),
+            Some(ShedLevel::ReduceFanOut)
+        );
+        assert_eq!(
+            load_shedder.assess(&queue_depth(95)),
+            Some(ShedLevel::RejectSubmissions)
+        );
+        assert!(load_shedder.level.rejects_submissions());
+        assert!(load_shedder.level.reduces_fan_out());
+        assert!(load_shedder.level.pauses_anti_entropy());
+        assert_eq!(load_shedder.assess(&queue_depth(200)), None);
+
+        // Slow storage alone keeps up the pressure.
+        let slow_storage = Signals {
+            storage_latency: config.max_storage_latency,
+            ..Signals::default()
+        };
+        assert_eq!(load_shedder.assess(&slow_storage), None);
+
+        // Pressure just below a threshold doesn't lift its stage yet.
+        assert_eq!(load_shedder.assess(&queue_depth(85)), None);
+
+        // Easing pressure lifts the stages in reverse order.
+        assert_eq!(
+            load_shedder.assess(&queue_depth(75)),
+            Some(ShedLevel::ReduceFanOut)
+        );
+        assert!(!load_shedder.level.rejects_submissions());
+        assert_eq!(
+            load_shedder.assess(&queue_depth(10)),
+            Some(ShedLevel::PauseAntiEntropy)
+        );
+        assert_eq!(
+            load_shedder.assess(&queue_depth(10)),
+            Some(ShedLevel::Normal)
+        );
+        assert!(!load_shedder.level.pauses_anti_entropy());
+        assert_eq!(load_shedder.assess(&queue_depth(10)), None);
+    }
+
+    #[tokio::test]
+    async fn should_treat_storage_probe_timeout_as_full_pressure() {
+        let mut rng = TestRng::new();
+        let scheduler = utils::leak(Scheduler::<ReactorEvent>::new(QueueKind::weights()));
+        let effect_builder = EffectBuilder::new(EventQueueHandle::new(scheduler));
+        let config = Config {
+            check_interval: Duration::from_secs(60),
+            max_storage_latency: Duration::from_millis(10),
+            ..Config::default()
+        };
+        let mut load_shedder = LoadShedder::new(config);
+
+        // Polling each effect once is enough for it to schedule its event.
+        let mut pending_effects = Vec::new();
+        for mut effect in load_shedder.handle_event(effect_builder, &mut rng, Event::CheckPressure)
+        {
+            if (&mut effect).now_or_never().is_none() {
+                pending_effects.push(effect);
+            }
+        }
+        // Besides the probe, the next check has been scheduled.
+        assert_eq!(pending_effects.len(), 2);
+        let responder = match scheduler.pop().await.0 {
+            ReactorEvent::Storage(StorageRequest::GetBlockHeader { responder, .. }) => responder,
+            other => panic!("unexpected event {:?}", other),
+        };
+
+        // While storage doesn't answer, every check escalates by a stage without probing again.
+        time::delay_for(Duration::from_millis(20)).await;
+        for &expected in &[
+            ShedLevel::PauseAntiEntropy,
+            ShedLevel::ReduceFanOut,
+            ShedLevel::RejectSubmissions,
+        ] {
+            for effect in load_shedder.handle_event(effect_builder, &mut rng, Event::CheckPressure)
+            {
+                let _ = effect.now_or_never();
+            }
+            match scheduler.pop().await.0 {
+                ReactorEvent::LoadShedderAnnouncement(LoadShedderAnnouncement::LevelChanged(
+                    level,
+                )) => assert_eq!(level, expected),
+                other => panic!("unexpected event {:?}", other),
+            }
+            assert_eq!(scheduler.item_count(), 0);
+        }
+
+        // The late answer has been accounted for already.
+        responder.respond(None).await;
+        let probe = pending_effects
+            .into_iter()
+            .find_map(|effect| effect.now_or_never())
+            .expect("probe should have been answered");
+        for event in probe {
+            assert!(load_shedder
+                .handle_event(effect_builder, &mut rng, event)
+                .is_empty());
+        }
+        assert!(load_shedder.probe_sent.is_none());
+    }
+}
@@ -0,0 +1,66 @@
+use std::time::Duration;
+
+use serde::{Deserialize, Serialize};
+
+const DEFAULT_CHECK_INTERVAL: Duration = Duration::from_secs(5);
+const DEFAULT_MAX_EVENT_QUEUE_DEPTH: usize = 10_000;
+const DEFAULT_MAX_STORAGE_LATENCY: Duration = Duration::from_secs(2);
+const DEFAULT_MAX_MEMORY_BYTES: u64 = 0;
+const DEFAULT_PAUSE_ANTI_ENTROPY_AT: f64 = 0.6;
+const DEFAULT_REDUCE_FAN_OUT_AT: f64 = 0.8;
+const DEFAULT_REJECT_SUBMISSIONS_AT: f64 = 1.0;
+const DEFAULT_RECOVERY_MARGIN: f64 = 0.2;
+
+/// Load shedder configuration.
+///
+/// The pressure on the node is the highest of its signals as a fraction of their maximum, e.g. 0.5
+/// with half the maximum number of events queued.
+#[derive(Copy, Clone, Debug, Deserialize, Serialize)]
+// Disallow unknown fields to ensure config files and command-line overrides contain valid keys.
+#[serde(deny_unknown_fields)]
+pub struct Config {
+    /// Time in milliseconds between checks of the pressure.  Use 0 to disable load shedding.
+    #[serde(with = "crate::utils::milliseconds")]
+    pub check_interval: Duration,
+
+    /// Number of queued events at which the event queue is under full pressure.  Use 0 to ignore
+    /// the event queue.
+    pub max_event_queue_depth: usize,
+
+    /// Time in milliseconds for answering a storage request at which storage is under full
+    /// pressure.  Use 0 to ignore storage.
+    #[serde(with = "crate::utils::milliseconds")]
+    pub max_storage_latency: Duration,
+
+    /// Resident memory in bytes at which memory is under full pressure.  Use 0 to ignore memory.
+    pub max_memory_bytes: u64,
+
+    /// Pressure at which anti-entropy rounds are paused.  Must be lower than `reduce_fan_out_at`.
+    pub pause_anti_entropy_at: f64,
+
+    /// Pressure at which the gossip fan-out is halved.  Must be lower than
+    /// `reject_submissions_at`.
+    pub reduce_fan_out_at: f64,
+
+    /// Pressure at which deploys submitted by clients are rejected.
+    pub reject_submissions_at: f64,
+
+    /// How far the pressure has to fall below a stage's threshold before its load is taken on
+    /// again.
+    pub recovery_margin: f64,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Config {
+            check_interval: DEFAULT_CHECK_INTERVAL,
+            max_event_queue_depth: DEFAULT_MAX_EVENT_QUEUE_DEPTH,
+            max_storage_latency: DEFAULT_MAX_STORAGE_LATENCY,
+            max_memory_bytes: DEFAULT_MAX_MEMORY_BYTES,
+            pause_anti_entropy_at: DEFAULT_PAUSE_ANTI_ENTROPY_AT,
+            reduce_fan_out_at: DEFAULT_REDUCE_FAN_OUT_AT,
+            reject_submissions_at: DEFAULT_REJECT_SUBMISSIONS_AT,
+            recovery_margin: DEFAULT_RECOVERY_MARGIN,
+        }
+    }
+}
@@ -0,0 +1,265 @@
+//! Proposal builder.
+//!
+//! When consensus makes this node the proposer of a new block, the proposal builder assembles the
+//! block's contents: It takes the candidates for inclusion from the deploy buffer, highest
+//! estimated fee first, and selects as many as fit into the block limits of the chainspec, i.e.
+//! its gas limit and maximum size, and the configured maximum number of deploys.  A deploy whose
+//! dependencies are still pending is only selected after all of them, so it always follows them in
+//! the block; if any of them doesn't fit, the deploy is left out too.
+//!
+//! Deploys contained in the unfinalized blocks the new block builds on are left out.  The proposed
+//! proto block carries the Merkle root over its deploys, which becomes the block's deploy root.
+
+use std::{
+    collections::HashSet,
+    fmt::{self, Display, Formatter},
+};
+
+use derive_more::From;
+use rand::{CryptoRng, Rng};
+use tracing::debug;
+
+use crate::{
+    components::{
+        chainspec_loader::DeployConfig, consensus::BlockContext, deploy_buffer::DeployCandidate,
+        Component,
+    },
+    effect::{
+        requests::{DeployBufferRequest, ProposalBuilderRequest},
+        EffectBuilder, EffectExt, Effects, Responder,
+    },
+    types::{DeployHash, ProtoBlock},
+};
+
+/// A proposal for a new block.
+#[derive(Clone, Debug)]
+pub struct Proposal {
+    /// The proposed proto block.
+    proto_block: ProtoBlock,
+    /// The context of the proposed block.
+    block_context: BlockContext,
+}
+
+impl Proposal {
+    /// Returns the proposed proto block and its context.
+    pub(crate) fn destructure(self) -> (ProtoBlock, BlockContext) {
+        (self.proto_block, self.block_context)
+    }
+}
+
+/// Proposal builder events.
+#[derive(Debug, From)]
+pub enum Event {
+    /// A request to the proposal builder.
+    #[from]
+    Request(ProposalBuilderRequest),
+    /// The deploy buffer returned the candidates for a requested proposal.
+    GotCandidates {
+        /// The context of the new block.
+        block_context: BlockContext,
+        /// The random bit to include in the proposal.
+        random_bit: bool,
+        /// The candidates for inclusion, highest estimated fee first.
+        candidates: Vec<DeployCandidate>,
+        /// Responder to call with the proposal.
+        responder: Responder<Proposal>,
+    },
+}
+
+impl Display for Event {
+    fn fmt(&self, formatter: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            Event::Request(request) => write!(formatter, "proposal builder request: {}", request),
+            Event::GotCandidates { candidates, .. } => {
+                write!(formatter, "got {} proposal candidates", candidates.len())
+            }
+        }
+    }
+}
+
+/// The proposal builder.
+#[derive(Debug)]
+pub(crate) struct ProposalBuilder {
+    /// The chainspec's deploy configuration, containing the block limits.
+    deploy_config: DeployConfig,
+    /// The maximum number of deploys in a block.
+    max_deploy_count: usize,
+}
+
+impl ProposalBuilder {
+    /// Creates a proposal builder for blocks within the limits of `deploy_config`, containing at
+    /// most `max_deploy_count` deploys.
+    pub(crate) fn new(deploy_config: DeployConfig, max_deploy_count: usize) -> Self {
+        ProposalBuilder {
+            deploy_config,
+            max_deploy_count,
+        }
+    }
+
+    /// Selects the deploys to propose from `candidates`, such that each deploy follows its pending
+    /// dependencies and the block stays within the limits.
+    fn select(&self, candidates: Vec<DeployCandidate>) -> Vec<DeployHash> {
+        let max_size = u64::from(self.deploy_config.max_block_size);
+        let mut selected = Vec::new();
+        let mut included = HashSet::new();
+        let (mut gas, mut size) = (0u64, 0u64);
+        let mut remaining = candidates;
+        // Every pass selects the candidates whose dependencies have all been selected in earlier
+        // passes or earlier in the same pass, until no more can be selected.
+        loop {
+            let selected_count = selected.len();
+            remaining.retain(|candidate| {
+                if !candidate
+                    .pending_dependencies
+                    .iter()
+                    .all(|dependency| included.contains(dependency))
+                {
+                    return true;
+                }
+                let new_gas = gas.saturating_add(candidate.gas_estimate);
+                let new_size = size.saturating_add(candidate.size);
+                if selected.len() < self.max_deploy_count
+                    && new_gas <= self.deploy_config.block_gas_limit
+                    && new_size <= max_size
+                {
+                    gas = new_gas;
+                    size = new_size;
+                    included.insert(candidate.hash);
+                    selected.push(candidate.hash);
+                }
+                false
+            });
+            if selected.len() == selected_count {
+                break;
+            }
+        }
+        debug!(
+            count = selected.len(),
+            gas, size, "selected deploys to propose"
+        );
+        selected
+    }
+
+    /// Assembles a proposal for a block with the given context from `candidates`.
+    fn build(
+        &self,
+        block_context: BlockContext,
+        random_bit: bool,
+        candidates: Vec<DeployCandidate>,
+    ) -> Proposal {
+        Proposal {
+            proto_block: ProtoBlock::new(self.select(candidates), random_bit),
+            block_context,
+        }
+    }
+}
+
+impl<REv, R> Component<REv, R> for ProposalBuilder
+where
+    REv: From<Event> + From<DeployBufferRequest> + Send,
+    R: Rng + CryptoRng + ?Sized,
+{
+    type Event = Event;
+
+    fn handle_event(
+        &mut self,
+        effect_builder: EffectBuilder<REv>,
+        _rng: &mut R,
+        event: Self::Event,
+    ) -> Effects<Self::Event> {
+        match event {
+            Event::Request(ProposalBuilderRequest::BuildProposal {
+                block_context,
+                past_blocks,
+                random_bit,
+                responder,
+            }) => effect_builder
+                .list_proposal_candidates(block_context.timestamp(), past_blocks)
+                .event(move |candidates| Event::GotCandidates {
+                    block_context,
+                    random_bit,
+                    candidates,
+                    responder,
+                }),
+            Event::GotCandidates {
+                block_context,
+                random_bit,
+                candidates,
+                responder,
+            } => responder
+                .respond(self.build(block_context, random_bit, candidates))
+                .ignore(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{crypto::hash, types::Timestamp};
+
+    fn deploy_hash(index: u8) -> DeployHash {
+        DeployHash::new(hash::hash(&[index]))
+    }
+
+    fn candidate(index: u8, dependencies: &[u8], size: u64, gas_estimate: u64) -> DeployCandidate {
+        DeployCandidate {
+            hash: deploy_hash(index),
+            pending_dependencies: dependencies.iter().copied().map(deploy_hash).collect(),
+            size,
+            gas_estimate,
+        }
+    }
+
+    #[test]
+    fn should_build_proposal_within_limits_in_dependency_order() {
+        let deploy_config = DeployConfig {
+            max_block_size: 1_000,
+            block_gas_limit: 100,
+            ..DeployConfig::default()
+        };
+        let builder = ProposalBuilder::new(deploy_config, 4);
+
+        // The candidates as returned by the deploy buffer, highest estimated fee first.
+        let candidates = vec![
+            candidate(0, &[], 100, 50),
+            // Depends on deploy 3, so it has to follow it.
+            candidate(1, &[3], 100, 10),
+            // Also depends on deploy 3, but the block is full by the time it could follow it.
+            candidate(2, &[3], 100, 10),
+            candidate(3, &[], 100, 10),
+            // Exceeds the gas limit.
+            candidate(4, &[], 100, 60),
+            // Exceeds the size limit.
+            candidate(5, &[], 900, 10),
+            // Depends on deploy 4, which is left out.
+            candidate(6, &[4], 100, 1),
+            candidate(7, &[], 100, 10),
+        ];
+        let block_context = BlockContext::new(Timestamp::from(1_000), 5);
+        let (proto_block, returned_context) = builder
+            .build(block_context.clone(), true, candidates.clone())
+            .destructure();
+
+        let expected: Vec<_> = [0, 3, 7, 1].iter().copied().map(deploy_hash).collect();
+        assert_eq!(*proto_block.deploys(), expected);
+        assert!(proto_block.random_bit());
+        assert_eq!(returned_context, block_context);
+        assert_eq!(
+            proto_block.deploy_root(),
+            ProtoBlock::new(expected.clone(), true).deploy_root()
+        );
+
+        let selected: Vec<_> = candidates
+            .iter()
+            .filter(|candidate| expected.contains(&candidate.hash))
+            .collect();
+        let gas: u64 = selected
+            .iter()
+            .map(|candidate| candidate.gas_estimate)
+            .sum();
+        let size: u64 = selected.iter().map(|candidate| candidate.size).sum();
+        assert!(gas <= deploy_config.block_gas_limit);
+        assert!(size <= u64::from(deploy_config.max_block_size));
+    }
+}
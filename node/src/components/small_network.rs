@@ -16,6 +16,20 @@
 //!
 //! The network itself is best-effort, during regular operation, no messages should be lost.
 //!
+//! Each payload has a [`Priority`]. On every outgoing connection, queued high-priority messages,
+//! like consensus messages, are sent before any queued bulk messages, even if those were queued
+//! earlier.
+//!
+//! Every received frame is buffered completely before being decoded, and frames larger than
+//! `max_frame_size` are rejected, which bounds the memory needed to receive a message.
+//!
+//! Every outgoing message is encoded exactly once, and the encoded buffer is used to check it
+//! against `max_frame_size`, to record its size in the metrics and to write it to the connection.
+//! A message too large for the peer to accept is dropped instead of being sent.
+//!
+//! Every frame ends with a checksum of the encoded message. A received frame failing its checksum
+//! is rejected before being decoded, and the connection to the peer is closed.
+//!
 //! # Connection
 //!
 //! Every node has an ID and a public listening address. The objective of each node is to constantly
@@ -25,18 +39,91 @@
 //! Any incoming connection is strictly read from, while any outgoing connection is strictly used
 //! for sending messages.
 //!
+//! Connections are always TLS over TCP, and there is no way to select another transport: a QUIC
+//! transport would need its own TLS stack to derive the same node IDs from the peers'
+//! certificates, which is not available to the node.  Messages on a connection are therefore
+//! subject to head-of-line blocking, which the message priorities only mitigate.
+//!
 //! Nodes gossip their public listening addresses periodically, and on learning of a new address,
 //! a node will try to establish an outgoing connection.
 //!
+//! The first message sent on every outgoing connection is a hello carrying our protocol version and
+//! the optional [`Capabilities`] we implement. For each peer, the intersection with our own
+//! capabilities is recorded, and optional messages, i.e. pings and goodbyes, are only sent to peers
+//! which support them. A hello anywhere but as the first message closes the connection.
+//!
+//! If the node has a consensus key, the hello is followed by a [`HandshakeAttestation`] binding the
+//! node ID to that key. The receiving side verifies it and closes the connection if the signature
+//! is invalid, the attestation is for a different node, or it is sent anywhere but right after the
+//! hello. Attestations with keys other than those of the current validators are ignored.  Peers
+//! are forgotten once they disconnect, except for validators, which are remembered by their
+//! attested node ID for as long as they remain validators, so that they stay exempt from the
+//! inbound connection limits and are reconnected to.
+//!
+//! The node checks the expiry of its own certificate on startup and every
+//! `cert_expiry_check_interval`, and logs increasingly severe warnings from `cert_expiry_lead_time`
+//! ahead of it.
+//!
+//! The round-trip time to every peer is measured periodically by sending it a ping on the outgoing
+//! connection, which the peer answers with a pong on its own outgoing connection.
+//!
+//! Responsibilities which are sharded among peers, like the custody of deploys, are assigned by a
+//! consistent-hashing ring of ourselves and all peers we have an outgoing connection to, so that a
+//! peer connecting or disconnecting only moves the keys it gains or loses.
+//!
+//! Peers can be tagged with a locality, e.g. their datacenter, via the `peer_localities` mapping
+//! of their IP addresses.  Gossip requests asking for it then spread the chosen peers across as
+//! many distinct localities as possible.
+//!
+//! A connection on which no message is received within `read_timeout`, or on which sending a
+//! message takes longer than `write_timeout`, is considered stalled and closed. Since peers ping
+//! every `ping_interval`, the read timeout must be longer than the ping interval of all peers.
+//!
+//! Payloads which fail to be sent because the peer's outgoing queue is full or the connection is
+//! lost are kept in a small bounded retry buffer per peer, and sent again once a new outgoing
+//! connection to the peer is established or on the next round of pings.  When a peer's buffer is
+//! full, the oldest payload is dropped, though bulk payloads never displace high-priority ones, and
+//! high-priority payloads like consensus messages are retried first.
+//!
 //! On losing an incoming or outgoing connection for a given peer, the other connection is closed.
 //! No explicit reconnect is attempted. Instead, if the peer is still online, the normal gossiping
 //! process will cause both peers to connect again.
-
+//!
+//! Unless `report_shutdown_reason` is disabled, a node shutting down sends a goodbye carrying its
+//! [`ShutdownReason`](../../types/enum.ShutdownReason.html) to every peer, which logs it and
+//! includes it in the reason given when announcing the peer's disconnection.
+//!
+//! # Validators
+//!
+//! Consensus relies on validators being connected to each other directly, so connections to peers
+//! which have attested with the key of a current validator are pinned: They do not count towards
+//! the maximum number of inbound connections, and on losing one, reconnecting is retried for as
+//! long as the peer remains a validator, backing off exponentially from
+//! `validator_reconnect_interval` up to `validator_reconnect_ceiling`, with random jitter. A peer
+//! is only recognized as a validator once it has attested on an incoming connection, which is
+//! remembered across reconnections.
+//!
+//! Besides the total, the number of inbound connections from non-validators can be limited per
+//! source IP address and per subnet, so that a single host can't take up all inbound connection
+//! slots by connecting under many node IDs.
+
+mod address_book;
+mod attestation;
+mod capabilities;
+mod cert_expiry;
+mod checksum;
 mod config;
+mod connection_limits;
 mod error;
 mod event;
 mod gossiped_address;
+mod latency;
+mod locality;
 mod message;
+mod metrics;
+mod reconnect_backoff;
+mod retry_buffer;
+mod send_queue;
 #[cfg(test)]
 mod tests;
 
@@ -44,51 +131,76 @@ use std::{
     collections::{HashMap, HashSet},
     fmt::{self, Debug, Display, Formatter},
     io,
-    net::{SocketAddr, TcpListener},
+    net::{IpAddr, SocketAddr, TcpListener},
+    path::PathBuf,
     sync::Arc,
-    time::Duration,
+    time::{Duration, Instant},
 };
 
 use anyhow::Context;
+use bytes::Bytes;
 use futures::{
     future::{select, BoxFuture, Either},
-    stream::{SplitSink, SplitStream},
-    FutureExt, SinkExt, StreamExt,
+    stream::SplitStream,
+    FutureExt, Sink, SinkExt, StreamExt,
 };
 use openssl::pkey;
 use pkey::{PKey, Private};
-use rand::{seq::IteratorRandom, CryptoRng, Rng};
-use serde::{de::DeserializeOwned, Serialize};
-use tokio::{
-    net::TcpStream,
-    sync::{
-        mpsc::{self, UnboundedReceiver, UnboundedSender},
-        oneshot,
-    },
-    task::JoinHandle,
+use prometheus::{Histogram, Registry};
+use rand::{
+    seq::{IteratorRandom, SliceRandom},
+    CryptoRng, Rng,
 };
+use serde::{de::DeserializeOwned, Serialize};
+use tokio::{net::TcpStream, sync::oneshot, task::JoinHandle, time};
 use tokio_openssl::SslStream;
-use tokio_serde::{formats::SymmetricalMessagePack, SymmetricallyFramed};
+use tokio_serde::SymmetricallyFramed;
 use tokio_util::codec::{Framed, LengthDelimitedCodec};
 use tracing::{debug, error, info, trace, warn};
 
-use self::error::Result;
-pub(crate) use self::{event::Event, gossiped_address::GossipedAddress, message::Message};
+use self::{
+    address_book::AddressBook,
+    capabilities::PROTOCOL_VERSION,
+    cert_expiry::CertExpiry,
+    checksum::ChecksummedMessagePack,
+    connection_limits::{ConnectionLimit, ConnectionLimits},
+    error::Result,
+    latency::Pinger,
+    locality::{LocalityMap, LocalityTagger},
+    message::EncodedMessage,
+    metrics::NetworkMetrics,
+    reconnect_backoff::ReconnectBackoff,
+    retry_buffer::RetryBuffer,
+    send_queue::SendError,
+};
+pub(crate) use self::{
+    attestation::HandshakeAttestation,
+    capabilities::{Capabilities, Capability},
+    event::Event,
+    gossiped_address::GossipedAddress,
+    latency::{LatencyStore, PeerLatency},
+    message::{Message, Payload, Priority},
+};
 use crate::{
     components::Component,
+    crypto::asymmetric_key::PublicKey,
     effect::{
-        announcements::NetworkAnnouncement,
+        announcements::{ControlAnnouncement, NetworkAnnouncement},
         requests::{NetworkInfoRequest, NetworkRequest},
-        EffectBuilder, EffectExt, EffectResultExt, Effects,
+        EffectBuilder, EffectExt, EffectResultExt, Effects, RepeatingSchedule,
     },
     fatal,
     reactor::{EventQueueHandle, Finalize, QueueKind},
     tls::{self, KeyFingerprint, TlsCert},
-    utils,
+    types::{ShutdownReason, Timestamp},
+    utils::{self, HashRing},
 };
-pub use config::Config;
+pub use config::{Config, OverflowPolicy};
 pub use error::Error;
 
+/// The number of points each node is placed at on the consistent-hashing ring.
+const HASH_RING_VIRTUAL_NODES: u32 = 64;
+
 /// A node ID.
 ///
 /// The key fingerprint found on TLS certificates.
@@ -96,10 +208,19 @@ pub(crate) type NodeId = KeyFingerprint;
 
 #[derive(Debug)]
 struct OutgoingConnection<P> {
-    sender: UnboundedSender<Message<P>>,
+    sender: send_queue::Sender<P>,
     peer_address: SocketAddr,
 }
 
+/// A current validator which has been connected to us.
+#[derive(Debug)]
+struct KnownValidator {
+    /// The node ID the validator attested.
+    peer_id: NodeId,
+    /// The public listening address of the validator, if we have had an outgoing connection to it.
+    listening_address: Option<SocketAddr>,
+}
+
 pub(crate) struct SmallNetwork<REv: 'static, P> {
     /// Server certificate.
     certificate: Arc<TlsCert>,
@@ -117,11 +238,80 @@ pub(crate) struct SmallNetwork<REv: 'static, P> {
     outgoing: HashMap<NodeId, OutgoingConnection<P>>,
     /// Pending outgoing connections: ones for which we are currently trying to make a connection.
     pending: HashSet<SocketAddr>,
+    /// Consistent-hashing ring of ourselves and all peers we have an outgoing connection to.
+    ring: HashRing<NodeId>,
+    /// The optional capabilities we advertise to peers, i.e. all we implement.
+    capabilities: Capabilities,
+    /// The capabilities supported by both us and each connected peer, as learned from their
+    /// hellos.
+    peer_capabilities: HashMap<NodeId, Capabilities>,
+    /// Our handshake attestation, sent right after the hello on every outgoing connection.
+    attestation: Option<HandshakeAttestation>,
+    /// Consensus public keys of connected peers, as verified from their handshake attestations.
+    attested_keys: HashMap<NodeId, PublicKey>,
+    /// Public listening addresses of connected peers we have an outgoing connection to.
+    listening_addresses: HashMap<NodeId, SocketAddr>,
+    /// The current validators which have been connected to us, by consensus public key.
+    ///
+    /// Entries are kept after a validator disconnects, so that it is recognized and reconnected
+    /// to, but only for as long as it remains a validator.
+    known_validators: HashMap<PublicKey, KnownValidator>,
+    /// The addresses of peers we have been connected to, with the time they were last reachable.
+    address_book: AddressBook,
+    /// The path the address book is persisted to, if any.
+    address_book_path: Option<PathBuf>,
+    /// The maximum time since a peer was last reachable for it to be kept in the address book.
+    address_book_max_age: Duration,
+    /// Consensus public keys of the current validators.
+    validators: HashSet<PublicKey>,
+    /// Maximum number of inbound connections from non-validators, further ones are refused.
+    max_inbound_connections: usize,
+    /// Limits on inbound connections from non-validators per source IP address and subnet.
+    connection_limits: ConnectionLimits,
+    /// The delays between attempts to reconnect to validators.
+    reconnect_backoff: ReconnectBackoff,
+    /// The maximum number of messages queued for sending to a single peer, or zero if unlimited.
+    max_outgoing_queue_size: usize,
+    /// What to do when a message is sent to a peer whose outgoing queue is full.
+    outgoing_queue_overflow_policy: OverflowPolicy,
+    /// The maximum size in bytes of a single frame received from a peer.
+    max_frame_size: usize,
+    /// The maximum time to wait for the next message on an incoming connection.
+    read_timeout: Duration,
+    /// The maximum time sending a single message on an outgoing connection may take.
+    write_timeout: Duration,
+    /// The size in bytes of the send buffer of outgoing connections, or 0 for the OS default.
+    send_buffer_size: usize,
+    /// Payloads which failed to be sent, to be retried once the connection recovers.
+    retry_buffer: RetryBuffer<P>,
+    /// Whether to send peers a goodbye with the reason when shutting down.
+    report_shutdown_reason: bool,
+    /// Tags peers with their locality, or `None` if no localities are known.
+    locality_tagger: Option<Box<dyn LocalityTagger>>,
+    /// The reasons peers gave for shutting down in their goodbyes.
+    ///
+    /// Entries are removed when the peer says hello again on a new connection.
+    departing_peers: HashMap<NodeId, ShutdownReason>,
     /// The interval between each fresh round of gossiping the node's public listening address.
     gossip_interval: Duration,
+    /// The schedule producing a fresh round of gossiping our address every `gossip_interval`.
+    gossip_address_schedule: RepeatingSchedule,
     /// An index for an iteration of gossiping our own public listening address.  This is
     /// incremented by 1 on each iteration, and wraps on overflow.
     next_gossip_address_index: u32,
+    /// The interval between pings to measure the round-trip time to each peer.
+    ping_interval: Duration,
+    /// The schedule producing a round of pings every `ping_interval`.
+    ping_schedule: RepeatingSchedule,
+    /// The round-trip times measured so far.
+    pinger: Pinger,
+    /// The time ahead of the expiry of our certificate from which warnings are logged.
+    cert_expiry_lead_time: Duration,
+    /// The schedule producing a check of the expiry of our certificate every
+    /// `cert_expiry_check_interval`.
+    cert_expiry_schedule: RepeatingSchedule,
+    /// Metrics of the small network.
+    metrics: NetworkMetrics,
     /// Channel signaling a shutdown of the small network.
     // Note: This channel never sends anything, instead it is closed when `SmallNetwork` is dropped,
     //       signalling the receiver that it should cease operation.
@@ -134,14 +324,17 @@ pub(crate) struct SmallNetwork<REv: 'static, P> {
 
 impl<REv, P> SmallNetwork<REv, P>
 where
-    P: Serialize + DeserializeOwned + Clone + Debug + Display + Send + 'static,
-    REv: Send + From<Event<P>> + From<NetworkAnnouncement<NodeId, P>>,
+    P: Payload + Serialize + DeserializeOwned + Clone + Debug + Display + Send + 'static,
+    REv: Send + From<Event<P>> + From<NetworkAnnouncement<NodeId, P>> + From<ControlAnnouncement>,
 {
     #[allow(clippy::type_complexity)]
     pub(crate) fn new(
         event_queue: EventQueueHandle<REv>,
         cfg: Config,
+        registry: &Registry,
     ) -> Result<(SmallNetwork<REv, P>, Effects<Event<P>>)> {
+        let metrics = NetworkMetrics::new(registry)?;
+
         // First, we generate the TLS keys.
         let (cert, secret_key) = tls::generate_node_cert().map_err(Error::CertificateGeneration)?;
         let certificate = Arc::new(tls::validate_cert(cert).map_err(Error::OwnCertificateInvalid)?);
@@ -173,6 +366,15 @@ where
             our_id,
         ));
 
+        let mut address_book = match cfg.address_book_path {
+            Some(ref path) => AddressBook::load(path).unwrap_or_else(|error| {
+                warn!(path = %path.display(), %error, "{}: failed to load address book", our_id);
+                AddressBook::default()
+            }),
+            None => AddressBook::default(),
+        };
+        address_book.prune(Timestamp::now(), cfg.address_book_max_age);
+
         let mut model = SmallNetwork {
             certificate,
             secret_key: Arc::new(secret_key),
@@ -182,11 +384,56 @@ where
             incoming: HashMap::new(),
             outgoing: HashMap::new(),
             pending: HashSet::new(),
+            ring: HashRing::new(HASH_RING_VIRTUAL_NODES),
+            capabilities: Capabilities::implemented(),
+            peer_capabilities: HashMap::new(),
+            attestation: None,
+            attested_keys: HashMap::new(),
+            listening_addresses: HashMap::new(),
+            known_validators: HashMap::new(),
+            address_book,
+            address_book_path: cfg.address_book_path,
+            address_book_max_age: cfg.address_book_max_age,
+            validators: HashSet::new(),
+            max_inbound_connections: cfg.max_inbound_connections,
+            connection_limits: ConnectionLimits::new(
+                cfg.max_inbound_connections_per_ip,
+                cfg.max_inbound_connections_per_subnet,
+                cfg.ipv4_subnet_prefix_length,
+                cfg.ipv6_subnet_prefix_length,
+            ),
+            reconnect_backoff: ReconnectBackoff::new(
+                cfg.validator_reconnect_interval,
+                cfg.validator_reconnect_ceiling,
+                cfg.validator_reconnect_jitter,
+            ),
+            max_outgoing_queue_size: cfg.max_outgoing_queue_size,
+            outgoing_queue_overflow_policy: cfg.outgoing_queue_overflow_policy,
+            max_frame_size: cfg.max_frame_size,
+            read_timeout: cfg.read_timeout,
+            write_timeout: cfg.write_timeout,
+            send_buffer_size: cfg.send_buffer_size,
+            retry_buffer: RetryBuffer::new(cfg.retry_buffer_size, cfg.retry_buffer_max_age),
+            report_shutdown_reason: cfg.report_shutdown_reason,
+            locality_tagger: if cfg.peer_localities.is_empty() {
+                None
+            } else {
+                Some(Box::new(LocalityMap::new(cfg.peer_localities)))
+            },
+            departing_peers: HashMap::new(),
             gossip_interval: cfg.gossip_interval,
+            gossip_address_schedule: RepeatingSchedule::new(),
             next_gossip_address_index: 0,
+            ping_interval: cfg.ping_interval,
+            ping_schedule: RepeatingSchedule::new(),
+            pinger: Pinger::default(),
+            cert_expiry_lead_time: cfg.cert_expiry_lead_time,
+            cert_expiry_schedule: RepeatingSchedule::new(),
+            metrics,
             shutdown: Some(server_shutdown_sender),
             server_join_handle: Some(server_join_handle),
         };
+        model.ring.insert(our_id);
 
         // Bootstrap process.
         let mut effects = Effects::new();
@@ -197,16 +444,19 @@ where
                     model.pending.insert(known_address);
 
                     // We successfully resolved an address, add an effect to connect to it.
+                    let start = Instant::now();
                     effects.extend(
                         connect_outgoing(
                             known_address,
                             Arc::clone(&model.certificate),
                             Arc::clone(&model.secret_key),
+                            model.send_buffer_size,
                         )
                         .result(
                             move |(peer_id, transport)| Event::OutgoingEstablished {
                                 peer_id,
                                 transport,
+                                handshake_duration: start.elapsed(),
                             },
                             move |error| Event::BootstrappingFailed {
                                 address: known_address,
@@ -232,11 +482,142 @@ where
         } else {
             // Start broadcasting our public listening address.
             effects.extend(model.gossip_our_address(effect_builder));
+            effects.extend(
+                effect_builder
+                    .schedule_repeating(
+                        model.gossip_interval,
+                        model.gossip_address_schedule.clone(),
+                        || Event::GossipOurAddress,
+                    )
+                    .ignore(),
+            );
+        }
+        effects.extend(
+            effect_builder
+                .schedule_repeating(model.ping_interval, model.ping_schedule.clone(), || {
+                    Event::PingPeers
+                })
+                .ignore(),
+        );
+        model.check_cert_expiry();
+        effects.extend(
+            effect_builder
+                .schedule_repeating(
+                    cfg.cert_expiry_check_interval,
+                    model.cert_expiry_schedule.clone(),
+                    || Event::CheckCertExpiry,
+                )
+                .ignore(),
+        );
+
+        // Dial the peers from the address book alongside the known nodes.
+        let remembered_addresses: Vec<_> = model
+            .address_book
+            .addresses()
+            .filter(|address| *address != model.public_address)
+            .collect();
+        for address in remembered_addresses {
+            effects.extend(model.connect_to_peer_if_required(address));
         }
 
         Ok((model, effects))
     }
 
+    /// Sets the attestation to present to peers on all subsequently established outgoing
+    /// connections.
+    pub(crate) fn set_attestation(&mut self, attestation: HandshakeAttestation) {
+        self.attestation = Some(attestation);
+    }
+
+    /// Sets the consensus public keys of the current validators.
+    pub(crate) fn set_validators(&mut self, validators: HashSet<PublicKey>) {
+        self.known_validators
+            .retain(|public_key, _| validators.contains(public_key));
+        self.validators = validators;
+    }
+
+    /// Returns whether `peer_id` has attested with the key of a current validator, either on its
+    /// current connection or on an earlier one.
+    fn is_validator(&self, peer_id: &NodeId) -> bool {
+        self.attested_keys
+            .get(peer_id)
+            .map_or(false, |public_key| self.validators.contains(public_key))
+            || self
+                .known_validators
+                .values()
+                .any(|known| known.peer_id == *peer_id)
+    }
+
+    /// Returns the node IDs and public listening addresses of the current validators we know the
+    /// address of.
+    fn validator_addresses(&self) -> impl Iterator<Item = (NodeId, SocketAddr)> + '_ {
+        let connected = self
+            .listening_addresses
+            .iter()
+            .filter(move |(peer_id, _)| self.is_validator(peer_id))
+            .map(|(peer_id, address)| (*peer_id, *address));
+        let known = self.known_validators.values().filter_map(|known| {
+            known
+                .listening_address
+                .map(|address| (known.peer_id, address))
+        });
+        connected.chain(known)
+    }
+
+    /// Returns whether a current validator is listening on `peer_address`.
+    fn is_validator_address(&self, peer_address: &SocketAddr) -> bool {
+        self.validator_addresses()
+            .any(|(_, address)| address == *peer_address)
+    }
+
+    /// Returns whether a current validator is listening on an address with the given IP.
+    ///
+    /// Used to decide whether to start a TLS handshake while a limit is reached, before the peer's
+    /// identity is known.  Whether the connection is then exempt from the limit is decided on the
+    /// peer's attested identity once the handshake has completed, so a host sharing a validator's
+    /// IP gains nothing but the handshake.
+    fn is_validator_ip(&self, ip: IpAddr) -> bool {
+        self.validator_addresses()
+            .any(|(_, address)| address.ip() == ip)
+    }
+
+    /// Connects to all current validators we know the address of and aren't connected to yet.
+    fn connect_to_validators(&mut self) -> Effects<Event<P>> {
+        let addresses: Vec<SocketAddr> = self
+            .validator_addresses()
+            .filter(|(peer_id, _)| !self.outgoing.contains_key(peer_id))
+            .map(|(_, address)| address)
+            .collect();
+        addresses
+            .into_iter()
+            .flat_map(|address| self.connect_to_peer_if_required(address))
+            .collect()
+    }
+
+    /// Tells all peers we have an outgoing connection to that we are shutting down, and why.
+    ///
+    /// Does nothing if reporting the shutdown reason is disabled.
+    pub(crate) fn say_goodbye(&self, reason: &ShutdownReason) {
+        if !self.report_shutdown_reason {
+            return;
+        }
+        debug!(%reason, "{}: saying goodbye to peers", self.our_id);
+        for peer_id in self.outgoing.keys() {
+            // Peers without the capability couldn't decode a goodbye.
+            if self
+                .peer_capabilities(peer_id)
+                .contains(Capability::ShutdownNotices)
+            {
+                self.send_message(
+                    *peer_id,
+                    Message::Goodbye {
+                        reason: reason.clone(),
+                    },
+                );
+            }
+        }
+    }
+
     /// Queues a message to be sent to all nodes.
     fn broadcast_message(&self, msg: Message<P>) {
         for peer_id in self.outgoing.keys() {
@@ -244,19 +625,67 @@ where
         }
     }
 
+    /// Queues a message to be sent to all nodes which are current validators.
+    fn broadcast_to_validators(&self, msg: Message<P>) {
+        for peer_id in self.outgoing.keys() {
+            if self.is_validator(peer_id) {
+                self.send_message(*peer_id, msg.clone());
+            }
+        }
+    }
+
+    /// Pings all peers we have an outgoing connection to.
+    fn ping_peers(&mut self) {
+        let now = Instant::now();
+        let peer_ids: Vec<NodeId> = self.outgoing.keys().copied().collect();
+        for peer_id in peer_ids {
+            if self
+                .peer_capabilities(&peer_id)
+                .contains(Capability::LatencyProbes)
+            {
+                let nonce = self.pinger.ping(peer_id, now);
+                self.send_message(peer_id, Message::Ping { nonce });
+            }
+            // Payloads which didn't fit into a full queue may fit by now.
+            self.retry_failed_sends(peer_id);
+        }
+    }
+
     /// Queues a message to `count` random nodes on the network.
+    ///
+    /// Nodes are chosen with likelihoods proportional to their `weights`, or uniformly if `weights`
+    /// is empty.
     fn gossip_message<R: Rng + ?Sized>(
         &self,
         rng: &mut R,
         msg: Message<P>,
         count: usize,
         exclude: HashSet<NodeId>,
+        weights: HashMap<NodeId, f64>,
+        spread_localities: bool,
     ) -> HashSet<NodeId> {
-        let peer_ids = self
+        let candidates = self
             .outgoing
             .keys()
-            .filter(|&peer_id| !exclude.contains(peer_id))
-            .choose_multiple(rng, count);
+            .filter(|&peer_id| !exclude.contains(peer_id));
+        let weight = |peer_id: &&NodeId| weights.get(*peer_id).copied().unwrap_or(1.0);
+        let peer_ids = match self.locality_tagger.as_ref() {
+            Some(tagger) if spread_localities => {
+                // Rank all candidates, then choose the best ones in distinct localities.
+                let ranked = if weights.is_empty() {
+                    let mut ranked: Vec<_> = candidates.collect();
+                    ranked.shuffle(rng);
+                    ranked
+                } else {
+                    utils::choose_weighted_multiple(rng, candidates, usize::MAX, weight)
+                };
+                locality::spread_across_localities(ranked, count, |peer_id| {
+                    tagger.locality(self.outgoing[*peer_id].peer_address.ip())
+                })
+            }
+            _ if weights.is_empty() => candidates.choose_multiple(rng, count),
+            _ => utils::choose_weighted_multiple(rng, candidates, count, weight),
+        };
 
         if peer_ids.len() != count {
             // TODO - set this to `warn!` once we are normally testing with networks large enough to
@@ -279,15 +708,86 @@ where
 
     /// Queues a message to be sent to a specific node.
     fn send_message(&self, dest: NodeId, msg: Message<P>) {
+        if let Some(msg) = self.try_send_message(dest, msg) {
+            let now = Instant::now();
+            self.buffer_failed_send(dest, msg, now, now);
+        }
+    }
+
+    /// Tries to queue a message to be sent to a specific node.
+    ///
+    /// Returns the message if it failed to be sent and is worth retrying.
+    fn try_send_message(&self, dest: NodeId, msg: Message<P>) -> Option<Message<P>> {
         // Try to send the message.
         if let Some(connection) = self.outgoing.get(&dest) {
-            if let Err(msg) = connection.sender.send(msg) {
-                // We lost the connection, but that fact has not reached us yet.
-                warn!(%dest, ?msg, "{}: dropped outgoing message, lost connection", self.our_id);
+            let policy = self.outgoing_queue_overflow_policy;
+            match connection.sender.send(msg) {
+                Ok(()) => None,
+                Err(SendError::Closed(msg)) => {
+                    // We lost the connection, but that fact has not reached us yet.
+                    warn!(
+                        %dest, ?msg,
+                        "{}: failed to send outgoing message, lost connection", self.our_id
+                    );
+                    Some(msg)
+                }
+                Err(SendError::Dropped(msg)) => {
+                    let overflow_count = connection.sender.overflow_count();
+                    warn!(
+                        %dest, ?msg, ?policy, overflow_count,
+                        "{}: failed to send outgoing message, queue full", self.our_id
+                    );
+                    Some(msg)
+                }
+                Err(SendError::Disconnected) => {
+                    let overflow_count = connection.sender.overflow_count();
+                    warn!(
+                        %dest, ?policy, overflow_count,
+                        "{}: disconnecting, outgoing queue full", self.our_id
+                    );
+                    None
+                }
             }
         } else {
             // We are not connected, so the reconnection is likely already in progress.
-            debug!(%dest, ?msg, "{}: dropped outgoing message, no connection", self.our_id);
+            debug!(%dest, ?msg, "{}: failed to send outgoing message, no connection", self.our_id);
+            Some(msg)
+        }
+    }
+
+    /// Buffers a message which first failed to be sent at `buffered_at`, to retry it once the
+    /// connection recovers.
+    ///
+    /// Only payloads are retried: The other messages are specific to a single connection.
+    fn buffer_failed_send(
+        &self,
+        dest: NodeId,
+        msg: Message<P>,
+        buffered_at: Instant,
+        now: Instant,
+    ) {
+        let payload = match msg {
+            Message::Payload(payload) => payload,
+            _ => return,
+        };
+        if let Some(dropped) = self.retry_buffer.push(dest, payload, buffered_at, now) {
+            debug!(
+                %dest, %dropped,
+                "{}: dropped outgoing message, retry buffer full or message expired", self.our_id
+            );
+        }
+    }
+
+    /// Sends the buffered payloads which previously failed to be sent to `peer_id` again.
+    ///
+    /// Payloads failing again are buffered anew, keeping the time they were first buffered.
+    fn retry_failed_sends(&self, peer_id: NodeId) {
+        let now = Instant::now();
+        for (buffered_at, payload) in self.retry_buffer.take(&peer_id, now) {
+            trace!(%peer_id, %payload, "{}: retrying outgoing message", self.our_id);
+            if let Some(msg) = self.try_send_message(peer_id, Message::Payload(payload)) {
+                self.buffer_failed_send(peer_id, msg, buffered_at, now);
+            }
         }
     }
 
@@ -296,6 +796,7 @@ where
         effect_builder: EffectBuilder<REv>,
         result: Result<(NodeId, Transport)>,
         address: SocketAddr,
+        handshake_duration: Duration,
     ) -> Effects<Event<P>> {
         match result {
             Ok((peer_id, transport)) => {
@@ -304,23 +805,60 @@ where
                     return Effects::new();
                 }
 
+                // Handshakes run concurrently, so the limit needs to be checked again here.
+                if !self.incoming.contains_key(&peer_id)
+                    && !self.is_validator(&peer_id)
+                    && self.inbound_limit_reached()
+                {
+                    info!(
+                        %peer_id,
+                        %address,
+                        "{}: too many inbound connections - closing connection",
+                        self.our_id
+                    );
+                    return Effects::new();
+                }
+                if !self.incoming.contains_key(&peer_id) && !self.is_validator(&peer_id) {
+                    if let Some(limit) = self.connection_limit_exceeded_by(address.ip()) {
+                        info!(
+                            %peer_id,
+                            %address,
+                            %limit,
+                            "{}: too many inbound connections from source - closing connection",
+                            self.our_id
+                        );
+                        return Effects::new();
+                    }
+                }
+
                 debug!(%peer_id, %address, "{}: established incoming connection", self.our_id);
                 // The sink is never used, as we only read data from incoming connections.
-                let (_sink, stream) = framed::<P>(transport).split();
+                let (_sink, stream) = framed::<P>(transport, self.max_frame_size).split();
 
                 let _ = self.incoming.insert(peer_id, address);
 
                 // If the connection is now complete, announce the new peer before starting reader.
+                self.record_connection(handshake_duration);
                 let mut effects = self.check_connection_complete(effect_builder, peer_id);
+                effects.extend(
+                    effect_builder
+                        .announce_peer_connected(peer_id, handshake_duration, true)
+                        .ignore(),
+                );
 
                 effects.extend(
-                    message_reader(self.event_queue, stream, self.our_id, peer_id).event(
-                        move |result| Event::IncomingClosed {
-                            result,
-                            peer_id,
-                            address,
-                        },
-                    ),
+                    message_reader(
+                        self.event_queue,
+                        stream,
+                        self.read_timeout,
+                        self.our_id,
+                        peer_id,
+                    )
+                    .event(move |result| Event::IncomingClosed {
+                        result,
+                        peer_id,
+                        address,
+                    }),
                 );
 
                 effects
@@ -332,12 +870,21 @@ where
         }
     }
 
+    /// Records an established connection to or from a peer in the metrics.
+    fn record_connection(&self, handshake_duration: Duration) {
+        self.metrics.connections_established.inc();
+        self.metrics
+            .handshake_duration
+            .observe(handshake_duration.as_secs_f64());
+    }
+
     /// Sets up an established outgoing connection.
     fn setup_outgoing(
         &mut self,
         effect_builder: EffectBuilder<REv>,
         peer_id: NodeId,
         transport: Transport,
+        handshake_duration: Duration,
     ) -> Effects<Event<P>> {
         if peer_id == self.our_id {
             debug!("{}: connected to ourself - closing connection", self.our_id);
@@ -348,6 +895,7 @@ where
         let peer_address = transport
             .get_ref()
             .peer_addr()
+            .map(utils::normalize_address)
             .expect("should have peer address");
 
         assert!(
@@ -355,14 +903,32 @@ where
             "should always add outgoing connect attempts to pendings: {:?}",
             self
         );
-        let (sink, _stream) = framed::<P>(transport).split();
+        let (sink, _stream) = length_delimited(transport, self.max_frame_size).split();
         debug!(%peer_id, %peer_address, "{}: established outgoing connection", self.our_id);
 
-        let (sender, receiver) = mpsc::unbounded_channel();
+        let (sender, receiver) = send_queue::channel(
+            self.max_outgoing_queue_size,
+            self.outgoing_queue_overflow_policy,
+        );
+        // The receiver is alive, so queueing the hello and attestation as the first messages cannot
+        // fail.
+        let _ = sender.send(Message::Hello {
+            protocol_version: PROTOCOL_VERSION,
+            capabilities: self.capabilities,
+        });
+        if let Some(ref attestation) = self.attestation {
+            let _ = sender.send(Message::Handshake(Box::new(attestation.clone())));
+        }
+        let _ = self.listening_addresses.insert(peer_id, peer_address);
+        self.reconnect_backoff.reset(&peer_address);
+        if self.address_book.record(peer_address, Timestamp::now()) {
+            self.save_address_book();
+        }
         let connection = OutgoingConnection {
             peer_address,
             sender,
         };
+        self.ring.insert(peer_id);
         if self.outgoing.insert(peer_id, connection).is_some() {
             // We assume that for a reconnect to have happened, the outgoing entry must have
             // been either non-existent yet or cleaned up by the handler of the connection
@@ -370,10 +936,25 @@ where
             error!(%peer_id, "{}: did not expect leftover channel in outgoing map", self.our_id);
         }
 
+        self.retry_failed_sends(peer_id);
+
+        self.record_connection(handshake_duration);
         let mut effects = self.check_connection_complete(effect_builder, peer_id);
+        effects.extend(
+            effect_builder
+                .announce_peer_connected(peer_id, handshake_duration, false)
+                .ignore(),
+        );
 
         effects.extend(
-            message_sender(receiver, sink).event(move |result| Event::OutgoingFailed {
+            message_sender(
+                receiver,
+                sink,
+                self.max_frame_size,
+                self.write_timeout,
+                self.metrics.outgoing_message_size.clone(),
+            )
+            .event(move |result| Event::OutgoingFailed {
                 peer_id: Some(peer_id),
                 peer_address,
                 error: result.err().map(Into::into),
@@ -383,21 +964,51 @@ where
         effects
     }
 
-    fn handle_outgoing_lost(
+    fn handle_outgoing_lost<R: Rng + ?Sized>(
         &mut self,
+        effect_builder: EffectBuilder<REv>,
+        rng: &mut R,
         peer_id: Option<NodeId>,
         peer_address: SocketAddr,
         error: Option<Error>,
     ) -> Effects<Event<P>> {
         let _ = self.pending.remove(&peer_address);
 
+        let mut effects = Effects::new();
         if let Some(peer_id) = peer_id {
-            if let Some(err) = error {
-                warn!(%peer_id, %peer_address, %err, "{}: outgoing connection failed", self.our_id);
-            } else {
-                warn!(%peer_id, %peer_address, "{}: outgoing connection closed", self.our_id);
-            }
+            let reason = match error {
+                Some(Error::WriteTimeout) => {
+                    warn!(
+                        %peer_id,
+                        %peer_address,
+                        "{}: outgoing connection timed out",
+                        self.our_id
+                    );
+                    "outgoing connection timed out".to_string()
+                }
+                Some(err) => {
+                    warn!(
+                        %peer_id,
+                        %peer_address,
+                        %err,
+                        "{}: outgoing connection failed",
+                        self.our_id
+                    );
+                    format!("outgoing connection failed: {}", err)
+                }
+                None => {
+                    warn!(%peer_id, %peer_address, "{}: outgoing connection closed", self.our_id);
+                    "outgoing connection closed".to_string()
+                }
+            };
+            let reason = self.disconnect_reason(&peer_id, reason);
             self.remove(&peer_id);
+            self.metrics.peer_disconnections.inc();
+            effects.extend(
+                effect_builder
+                    .announce_peer_disconnected(peer_id, reason)
+                    .ignore(),
+            );
         } else {
             // If we don't have the node ID passed in here, it was never added as an
             // outgoing connection, hence no need to call `self.remove()`.
@@ -408,27 +1019,75 @@ where
             }
         }
 
-        Effects::new()
+        if self.is_validator_address(&peer_address) {
+            // Validators need to stay directly connected, so don't wait for gossip to reconnect.
+            let delay = self.reconnect_backoff.next_delay(peer_address, rng);
+            effects.extend(
+                effect_builder
+                    .set_timeout(delay)
+                    .event(move |_| Event::ReconnectValidator { peer_address }),
+            );
+        }
+        effects
+    }
+
+    /// Returns `reason` for `peer_id` disconnecting, extended by the reason the peer gave for
+    /// shutting down, if any.
+    fn disconnect_reason(&self, peer_id: &NodeId, reason: String) -> String {
+        match self.departing_peers.get(peer_id) {
+            Some(shutdown_reason) => format!("{}, peer shutting down: {}", reason, shutdown_reason),
+            None => reason,
+        }
     }
 
     fn remove(&mut self, peer_id: &NodeId) {
         let _ = self.incoming.remove(&peer_id);
         let _ = self.outgoing.remove(&peer_id);
+        let _ = self.peer_capabilities.remove(&peer_id);
+        self.pinger.peer_disconnected(peer_id);
+        self.ring.remove(peer_id);
+        let listening_address = self.listening_addresses.remove(&peer_id);
+        let public_key = match self.attested_keys.remove(&peer_id) {
+            Some(public_key) if self.validators.contains(&public_key) => public_key,
+            _ => return,
+        };
+        // Remember the validator, so that it is recognized and reconnected to.
+        let known = self
+            .known_validators
+            .entry(public_key)
+            .or_insert(KnownValidator {
+                peer_id: *peer_id,
+                listening_address: None,
+            });
+        if known.peer_id != *peer_id {
+            known.peer_id = *peer_id;
+            known.listening_address = None;
+        }
+        if listening_address.is_some() {
+            known.listening_address = listening_address;
+        }
+    }
+
+    /// Logs a warning if our certificate expires soon, and records the time until it does.
+    fn check_cert_expiry(&self) {
+        let now = cert_expiry::unix_now();
+        match cert_expiry::seconds_until_expiry(self.certificate.as_x509(), now) {
+            Ok(remaining_secs) => {
+                self.metrics.cert_expiry_seconds.set(remaining_secs);
+                CertExpiry::new(remaining_secs, self.cert_expiry_lead_time)
+                    .log(self.our_id, remaining_secs);
+            }
+            Err(error) => warn!(%error, "{}: failed to check certificate expiry", self.our_id),
+        }
     }
 
-    /// Gossips our public listening address, and schedules the next such gossip round.
+    /// Gossips our public listening address.
     fn gossip_our_address(&mut self, effect_builder: EffectBuilder<REv>) -> Effects<Event<P>> {
         self.next_gossip_address_index = self.next_gossip_address_index.wrapping_add(1);
         let our_address = GossipedAddress::new(self.public_address, self.next_gossip_address_index);
-        let mut effects = effect_builder
+        effect_builder
             .announce_gossip_our_address(our_address)
-            .ignore();
-        effects.extend(
-            effect_builder
-                .set_timeout(self.gossip_interval)
-                .event(|_| Event::GossipOurAddress),
-        );
-        effects
+            .ignore()
     }
 
     /// Handles a received message.
@@ -441,12 +1100,65 @@ where
     where
         REv: From<NetworkAnnouncement<NodeId, P>>,
     {
-        effect_builder
-            .announce_message_received(peer_id, msg.0)
-            .ignore()
+        match msg {
+            Message::Hello {
+                protocol_version,
+                capabilities,
+            } => {
+                let shared = self.capabilities.intersection(capabilities);
+                debug!(
+                    %peer_id, protocol_version, %shared,
+                    "{}: peer said hello", self.our_id
+                );
+                let _ = self.peer_capabilities.insert(peer_id, shared);
+                // A hello means the peer is back, so any goodbye it said before is obsolete.
+                let _ = self.departing_peers.remove(&peer_id);
+                Effects::new()
+            }
+            Message::Handshake(attestation) => {
+                // Attestations are verified by the message reader before being passed on, and only
+                // if sent right after the hello.
+                if !self.validators.contains(attestation.public_key()) {
+                    debug!(
+                        %peer_id, %attestation,
+                        "{}: ignoring attestation by non-validator", self.our_id
+                    );
+                    return Effects::new();
+                }
+                debug!(%peer_id, %attestation, "{}: peer attested", self.our_id);
+                let _ = self
+                    .attested_keys
+                    .insert(peer_id, *attestation.public_key());
+                Effects::new()
+            }
+            Message::Ping { nonce } => {
+                self.send_message(peer_id, Message::Pong { nonce });
+                Effects::new()
+            }
+            Message::Pong { nonce } => {
+                if let Some(rtt) =
+                    self.pinger
+                        .pong(peer_id, nonce, Instant::now(), Timestamp::now())
+                {
+                    trace!(%peer_id, ?rtt, "{}: measured round-trip time", self.our_id);
+                }
+                Effects::new()
+            }
+            Message::Payload(payload) => effect_builder
+                .announce_message_received(peer_id, payload)
+                .ignore(),
+            Message::Goodbye { reason } => {
+                info!(%peer_id, %reason, "{}: peer is shutting down", self.our_id);
+                let _ = self.departing_peers.insert(peer_id, reason);
+                Effects::new()
+            }
+        }
     }
 
     fn connect_to_peer_if_required(&mut self, peer_address: SocketAddr) -> Effects<Event<P>> {
+        // Addresses gossiped by peers may be in any form, so they are normalized before being
+        // compared to the ones we already know.
+        let peer_address = utils::normalize_address(peer_address);
         if self.pending.contains(&peer_address)
             || self
                 .outgoing
@@ -458,13 +1170,19 @@ where
         } else {
             // We need to connect.
             assert!(self.pending.insert(peer_address));
+            let start = Instant::now();
             connect_outgoing(
                 peer_address,
                 Arc::clone(&self.certificate),
                 Arc::clone(&self.secret_key),
+                self.send_buffer_size,
             )
             .result(
-                move |(peer_id, transport)| Event::OutgoingEstablished { peer_id, transport },
+                move |(peer_id, transport)| Event::OutgoingEstablished {
+                    peer_id,
+                    transport,
+                    handshake_duration: start.elapsed(),
+                },
                 move |error| Event::OutgoingFailed {
                     peer_id: None,
                     peer_address,
@@ -491,6 +1209,31 @@ where
         }
     }
 
+    /// Returns whether or not the maximum number of inbound connections has been reached.
+    ///
+    /// Connections from validators don't count towards the limit.
+    fn inbound_limit_reached(&self) -> bool {
+        let non_validators = self
+            .incoming
+            .keys()
+            .filter(|peer_id| !self.is_validator(peer_id))
+            .count();
+        non_validators >= self.max_inbound_connections
+    }
+
+    /// Returns the per-IP or per-subnet limit a further inbound connection from `ip` would exceed,
+    /// if any.
+    ///
+    /// Connections from validators don't count towards the limits.
+    fn connection_limit_exceeded_by(&self, ip: IpAddr) -> Option<ConnectionLimit> {
+        let non_validator_ips = self
+            .incoming
+            .iter()
+            .filter(|(peer_id, _)| !self.is_validator(peer_id))
+            .map(|(_, address)| address.ip());
+        self.connection_limits.exceeded_by(ip, non_validator_ips)
+    }
+
     /// Returns the set of connected nodes.
     #[cfg(test)]
     pub(crate) fn connected_nodes(&self) -> HashSet<NodeId> {
@@ -509,8 +1252,38 @@ where
         ret
     }
 
-    /// Returns the node id of this network node.
+    /// Returns the store of the round-trip times measured to the connected peers.
+    pub(crate) fn latency_store(&self) -> LatencyStore {
+        self.pinger.latency_store()
+    }
+
+    /// Returns the node responsible for `key` among ourselves and our connected peers.
+    ///
+    /// Responsibilities are assigned by consistent hashing, so when a peer connects or disconnects,
+    /// only the keys it gains or loses change hands.
+    pub(crate) fn responsible_node<K: AsRef<[u8]>>(&self, key: K) -> NodeId {
+        self.ring
+            .responsible_node(key)
+            .expect("ring always contains our own node ID")
+    }
+
+    /// Returns the capabilities supported by both us and `peer_id`.
+    ///
+    /// None are returned for a peer we haven't received a hello from yet.
+    pub(crate) fn peer_capabilities(&self, peer_id: &NodeId) -> Capabilities {
+        self.peer_capabilities
+            .get(peer_id)
+            .copied()
+            .unwrap_or_default()
+    }
+
+    /// Returns the reason `peer_id` gave for shutting down, if it said goodbye.
     #[cfg(test)]
+    pub(crate) fn peer_shutdown_reason(&self, peer_id: &NodeId) -> Option<&ShutdownReason> {
+        self.departing_peers.get(peer_id)
+    }
+
+    /// Returns the node id of this network node.
     pub(crate) fn node_id(&self) -> NodeId {
         self.our_id
     }
@@ -524,13 +1297,36 @@ where
     }
 }
 
+impl<REv, P> SmallNetwork<REv, P> {
+    /// Prunes stale entries from the address book and persists it, if a path is configured.
+    fn save_address_book(&mut self) {
+        let path = match self.address_book_path {
+            Some(ref path) => path,
+            None => return,
+        };
+        self.address_book
+            .prune(Timestamp::now(), self.address_book_max_age);
+        if let Err(error) = self.address_book.save(path) {
+            warn!(path = %path.display(), %error, "{}: failed to save address book", self.our_id);
+        }
+    }
+}
+
 impl<REv, P> Finalize for SmallNetwork<REv, P>
 where
     REv: Send + 'static,
     P: Send + 'static,
 {
     fn finalize(mut self) -> BoxFuture<'static, ()> {
+        // Persist when each peer was last reachable.
+        self.save_address_book();
+
         async move {
+            // Stop gossiping our address, pinging peers and checking our certificate.
+            self.gossip_address_schedule.cancel();
+            self.ping_schedule.cancel();
+            self.cert_expiry_schedule.cancel();
+
             // Close the shutdown socket, causing the server to exit.
             drop(self.shutdown.take());
 
@@ -550,9 +1346,9 @@ where
 
 impl<REv, R, P> Component<REv, R> for SmallNetwork<REv, P>
 where
-    REv: Send + From<Event<P>> + From<NetworkAnnouncement<NodeId, P>>,
+    REv: Send + From<Event<P>> + From<NetworkAnnouncement<NodeId, P>> + From<ControlAnnouncement>,
     R: Rng + CryptoRng + ?Sized,
-    P: Serialize + DeserializeOwned + Clone + Debug + Display + Send + 'static,
+    P: Payload + Serialize + DeserializeOwned + Clone + Debug + Display + Send + 'static,
 {
     type Event = Event<P>;
 
@@ -586,15 +1382,50 @@ where
                 }
             }
             Event::IncomingNew { stream, address } => {
+                if self.inbound_limit_reached() && !self.is_validator_ip(address.ip()) {
+                    // Dropping the stream closes the connection.
+                    info!(
+                        %address,
+                        "{}: too many inbound connections - refusing connection",
+                        self.our_id
+                    );
+                    drop(stream);
+                    return Effects::new();
+                }
+                if !self.is_validator_ip(address.ip()) {
+                    if let Some(limit) = self.connection_limit_exceeded_by(address.ip()) {
+                        info!(
+                            %address,
+                            %limit,
+                            "{}: too many inbound connections from source - refusing connection",
+                            self.our_id
+                        );
+                        drop(stream);
+                        return Effects::new();
+                    }
+                }
+
                 debug!(%address, "{}: incoming connection, starting TLS handshake", self.our_id);
 
+                let start = Instant::now();
                 setup_tls(stream, self.certificate.clone(), self.secret_key.clone())
                     .boxed()
-                    .event(move |result| Event::IncomingHandshakeCompleted { result, address })
-            }
-            Event::IncomingHandshakeCompleted { result, address } => {
-                self.handle_incoming_handshake_completed(effect_builder, result, address)
+                    .event(move |result| Event::IncomingHandshakeCompleted {
+                        result,
+                        address,
+                        handshake_duration: start.elapsed(),
+                    })
             }
+            Event::IncomingHandshakeCompleted {
+                result,
+                address,
+                handshake_duration,
+            } => self.handle_incoming_handshake_completed(
+                effect_builder,
+                result,
+                address,
+                handshake_duration,
+            ),
             Event::IncomingMessage { peer_id, msg } => {
                 self.handle_message(effect_builder, peer_id, msg)
             }
@@ -603,23 +1434,36 @@ where
                 peer_id,
                 address,
             } => {
-                match result {
-                    Ok(()) => info!(%peer_id, %address, "{}: connection closed", self.our_id),
+                let reason = match result {
+                    Ok(()) => {
+                        info!(%peer_id, %address, "{}: connection closed", self.our_id);
+                        "incoming connection closed".to_string()
+                    }
+                    Err(err) if err.kind() == io::ErrorKind::TimedOut => {
+                        warn!(%peer_id, %address, "{}: connection timed out", self.our_id);
+                        "incoming connection timed out".to_string()
+                    }
                     Err(err) => {
-                        warn!(%peer_id, %address, %err, "{}: connection dropped", self.our_id)
+                        warn!(%peer_id, %address, %err, "{}: connection dropped", self.our_id);
+                        format!("incoming connection dropped: {}", err)
                     }
-                }
+                };
+                let reason = self.disconnect_reason(&peer_id, reason);
                 self.remove(&peer_id);
-                Effects::new()
-            }
-            Event::OutgoingEstablished { peer_id, transport } => {
-                self.setup_outgoing(effect_builder, peer_id, transport)
+                effect_builder
+                    .announce_peer_disconnected(peer_id, reason)
+                    .ignore()
             }
+            Event::OutgoingEstablished {
+                peer_id,
+                transport,
+                handshake_duration,
+            } => self.setup_outgoing(effect_builder, peer_id, transport, handshake_duration),
             Event::OutgoingFailed {
                 peer_id,
                 peer_address,
                 error,
-            } => self.handle_outgoing_lost(peer_id, peer_address, error),
+            } => self.handle_outgoing_lost(effect_builder, rng, peer_id, peer_address, error),
             Event::NetworkRequest {
                 req:
                     NetworkRequest::SendMessage {
@@ -629,14 +1473,21 @@ where
                     },
             } => {
                 // We're given a message to send out.
-                self.send_message(dest, Message(payload));
+                self.send_message(dest, Message::Payload(payload));
                 responder.respond(()).ignore()
             }
             Event::NetworkRequest {
                 req: NetworkRequest::Broadcast { payload, responder },
             } => {
                 // We're given a message to broadcast.
-                self.broadcast_message(Message(payload));
+                self.broadcast_message(Message::Payload(payload));
+                responder.respond(()).ignore()
+            }
+            Event::NetworkRequest {
+                req: NetworkRequest::BroadcastToValidators { payload, responder },
+            } => {
+                // We're given a message to broadcast to validators only.
+                self.broadcast_to_validators(Message::Payload(payload));
                 responder.respond(()).ignore()
             }
             Event::NetworkRequest {
@@ -645,20 +1496,58 @@ where
                         payload,
                         count,
                         exclude,
+                        weights,
+                        spread_localities,
                         responder,
                     },
             } => {
                 // We're given a message to gossip.
-                let sent_to = self.gossip_message(rng, Message(payload), count, exclude);
+                let sent_to = self.gossip_message(
+                    rng,
+                    Message::Payload(payload),
+                    count,
+                    exclude,
+                    weights,
+                    spread_localities,
+                );
                 responder.respond(sent_to).ignore()
             }
             Event::NetworkInfoRequest {
                 req: NetworkInfoRequest::GetPeers { responder },
             } => responder.respond(self.peers()).ignore(),
+            Event::NetworkInfoRequest {
+                req: NetworkInfoRequest::GetPeerLatencies { responder },
+            } => {
+                let latencies = self.pinger.latencies(Timestamp::now(), self.ping_interval);
+                responder.respond(latencies).ignore()
+            }
+            Event::NetworkInfoRequest {
+                req: NetworkInfoRequest::GetResponsibleNode { key, responder },
+            } => responder.respond(self.responsible_node(key)).ignore(),
             Event::GossipOurAddress => self.gossip_our_address(effect_builder),
+            Event::CheckCertExpiry => {
+                self.check_cert_expiry();
+                Effects::new()
+            }
+            Event::PingPeers => {
+                self.ping_peers();
+                Effects::new()
+            }
             Event::PeerAddressReceived(gossiped_address) => {
                 self.connect_to_peer_if_required(gossiped_address.into())
             }
+            Event::ValidatorsChanged(validators) => {
+                self.set_validators(validators);
+                self.connect_to_validators()
+            }
+            Event::ReconnectValidator { peer_address } => {
+                if self.is_validator_address(&peer_address) {
+                    debug!(%peer_address, "{}: reconnecting to validator", self.our_id);
+                    self.connect_to_peer_if_required(peer_address)
+                } else {
+                    Effects::new()
+                }
+            }
         }
     }
 }
@@ -687,6 +1576,7 @@ async fn server_task<P, REv>(
             match listener.accept().await {
                 Ok((stream, address)) => {
                     // Move the incoming connection to the event queue for handling.
+                    let address = utils::normalize_address(address);
                     let event = Event::IncomingNew { stream, address };
                     event_queue
                         .schedule(event, QueueKind::NetworkIncoming)
@@ -744,10 +1634,14 @@ async fn setup_tls(
 
 /// Network message reader.
 ///
-/// Schedules all received messages until the stream is closed or an error occurs.
+/// Schedules all received messages until the stream is closed, no message is received within
+/// `read_timeout` or an error occurs. A hello is only accepted as the first message, and a
+/// handshake attestation only as the message right after it.  The attestation is verified against
+/// the peer's node ID, closing the connection on failure.
 async fn message_reader<REv, P>(
     event_queue: EventQueueHandle<REv>,
     mut stream: SplitStream<FramedTransport<P>>,
+    read_timeout: Duration,
     our_id: NodeId,
     peer_id: NodeId,
 ) -> io::Result<()>
@@ -755,10 +1649,56 @@ where
     P: DeserializeOwned + Send + Display,
     REv: From<Event<P>>,
 {
-    while let Some(msg_result) = stream.next().await {
+    // The number of messages received so far.
+    let mut received: u64 = 0;
+    loop {
+        let msg_result = match time::timeout(read_timeout, stream.next()).await {
+            Ok(Some(msg_result)) => msg_result,
+            Ok(None) => break,
+            Err(_) => {
+                warn!(%peer_id, "{}: no message received in time, closing connection", our_id);
+                return Err(io::Error::new(
+                    io::ErrorKind::TimedOut,
+                    "timed out receiving message",
+                ));
+            }
+        };
         match msg_result {
             Ok(msg) => {
                 debug!(%msg, %peer_id, "{}: message received", our_id);
+                if matches!(msg, Message::Hello { .. }) && received != 0 {
+                    warn!(
+                        %peer_id,
+                        "{}: hello not sent as first message, closing connection", our_id
+                    );
+                    return Err(io::Error::new(
+                        io::ErrorKind::InvalidData,
+                        "unexpected hello",
+                    ));
+                }
+                if let Message::Handshake(ref attestation) = msg {
+                    if received != 1 {
+                        warn!(
+                            %peer_id,
+                            "{}: attestation not sent right after hello, closing connection",
+                            our_id
+                        );
+                        return Err(io::Error::new(
+                            io::ErrorKind::InvalidData,
+                            "unexpected attestation",
+                        ));
+                    }
+                    if let Err(err) = attestation.verify(&peer_id) {
+                        warn!(
+                            %err,
+                            %peer_id,
+                            "{}: invalid attestation, closing connection",
+                            our_id
+                        );
+                        return Err(io::Error::new(io::ErrorKind::InvalidData, err));
+                    }
+                }
+                received = received.saturating_add(1);
                 // We've received a message, push it to the reactor.
                 event_queue
                     .schedule(
@@ -778,19 +1718,41 @@ where
 
 /// Network message sender.
 ///
-/// Reads from a channel and sends all messages, until the stream is closed or an error occurs.
-async fn message_sender<P>(
-    mut queue: UnboundedReceiver<Message<P>>,
-    mut sink: SplitSink<FramedTransport<P>, Message<P>>,
+/// Reads from a send queue and sends all messages by priority, until the queue is closed or an
+/// error occurs.  A queue closed due to an overflow is reported as an error, as is a message taking
+/// longer than `write_timeout` to send.
+///
+/// Each message is encoded once, and messages larger than `max_frame_size` are dropped, since the
+/// peer would reject them.  The encoded size of every message sent is recorded in `message_size`.
+async fn message_sender<P, S>(
+    mut queue: send_queue::Receiver<P>,
+    mut sink: S,
+    max_frame_size: usize,
+    write_timeout: Duration,
+    message_size: Histogram,
 ) -> Result<()>
 where
     P: Serialize + Send,
+    S: Sink<Bytes, Error = io::Error> + Unpin,
 {
-    while let Some(payload) = queue.recv().await {
+    while let Some(msg) = queue.recv().await {
+        let encoded = EncodedMessage::encode(&msg).map_err(Error::MessageNotEncoded)?;
+        let size = encoded.len();
+        if size > max_frame_size {
+            warn!(size, max_frame_size, "dropping message too large to send");
+            continue;
+        }
         // We simply error-out if the sink fails, it means that our connection broke.
-        sink.send(payload).await.map_err(Error::MessageNotSent)?;
+        time::timeout(write_timeout, sink.send(encoded.into_bytes()))
+            .await
+            .map_err(|_| Error::WriteTimeout)?
+            .map_err(Error::MessageNotSent)?;
+        message_size.observe(size as f64);
     }
 
+    if queue.has_overflowed() {
+        return Err(Error::OutgoingQueueOverflow);
+    }
     Ok(())
 }
 
@@ -801,23 +1763,43 @@ type Transport = SslStream<TcpStream>;
 type FramedTransport<P> = SymmetricallyFramed<
     Framed<Transport, LengthDelimitedCodec>,
     Message<P>,
-    SymmetricalMessagePack<Message<P>>,
+    ChecksummedMessagePack<Message<P>>,
 >;
 
+/// Constructs a new transport of length-delimited frames on a stream.
+///
+/// Frames larger than `max_frame_size` bytes are rejected without being buffered.
+fn length_delimited(
+    stream: Transport,
+    max_frame_size: usize,
+) -> Framed<Transport, LengthDelimitedCodec> {
+    Framed::new(
+        stream,
+        LengthDelimitedCodec::builder()
+            .max_frame_length(max_frame_size)
+            .new_codec(),
+    )
+}
+
 /// Constructs a new framed transport on a stream.
-fn framed<P>(stream: Transport) -> FramedTransport<P> {
-    let length_delimited = Framed::new(stream, LengthDelimitedCodec::new());
+///
+/// Frames larger than `max_frame_size` bytes are rejected without being buffered, and frames
+/// failing their checksum are rejected without being decoded.
+fn framed<P>(stream: Transport, max_frame_size: usize) -> FramedTransport<P> {
     SymmetricallyFramed::new(
-        length_delimited,
-        SymmetricalMessagePack::<Message<P>>::default(),
+        length_delimited(stream, max_frame_size),
+        ChecksummedMessagePack::<Message<P>>::default(),
     )
 }
 
 /// Initiates a TLS connection to a remote address.
+///
+/// Unless `send_buffer_size` is 0, the send buffer of the socket is set to the given size.
 async fn connect_outgoing(
     peer_address: SocketAddr,
     our_certificate: Arc<TlsCert>,
     secret_key: Arc<PKey<Private>>,
+    send_buffer_size: usize,
 ) -> Result<(NodeId, Transport)> {
     let mut config = tls::create_tls_connector(&our_certificate.as_x509(), &secret_key)
         .context("could not create TLS connector")?
@@ -828,6 +1810,11 @@ async fn connect_outgoing(
     let stream = tokio::net::TcpStream::connect(peer_address)
         .await
         .context("TCP connection failed")?;
+    if send_buffer_size != 0 {
+        stream
+            .set_send_buffer_size(send_buffer_size)
+            .context("could not set send buffer size")?;
+    }
 
     let tls_stream = tokio_openssl::connect(config, "this-will-not-be-checked.example.com", stream)
         .await
@@ -856,6 +1843,12 @@ where
             .field("incoming", &self.incoming)
             .field("outgoing", &self.outgoing)
             .field("pending", &self.pending)
+            .field("attestation", &self.attestation)
+            .field("attested_keys", &self.attested_keys)
+            .field("listening_addresses", &self.listening_addresses)
+            .field("known_validators", &self.known_validators)
+            .field("validators", &self.validators)
+            .field("max_inbound_connections", &self.max_inbound_connections)
             .finish()
     }
 }
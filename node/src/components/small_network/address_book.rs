@@ -0,0 +1,106 @@
+//! Persisted address book.
+//!
+//! The listening addresses of all peers we have established an outgoing connection to are recorded,
+//! together with the time they were last reachable.  The address book is saved to disk so that on
+//! restart, these peers can be dialed right away instead of being learned through gossip again.
+//! Entries which have not been reachable for longer than the configured maximum age are pruned.
+
+use std::{collections::BTreeMap, fs, io, net::SocketAddr, path::Path, time::Duration};
+
+use serde::{Deserialize, Serialize};
+
+use crate::{types::Timestamp, utils};
+
+/// The listening addresses of known peers, with the time each was last reachable.
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub(super) struct AddressBook {
+    entries: BTreeMap<SocketAddr, Timestamp>,
+}
+
+impl AddressBook {
+    /// Loads the address book from `path`.
+    ///
+    /// Returns an empty address book if the file does not exist.
+    pub(super) fn load(path: &Path) -> io::Result<Self> {
+        match fs::read(path) {
+            Ok(bytes) => {
+                let loaded: AddressBook = serde_json::from_slice(&bytes)?;
+                // Merge entries which only differ in the form of their address.
+                let mut address_book = AddressBook::default();
+                for (address, timestamp) in loaded.entries {
+                    let entry = address_book
+                        .entries
+                        .entry(utils::normalize_address(address))
+                        .or_insert(timestamp);
+                    *entry = (*entry).max(timestamp);
+                }
+                Ok(address_book)
+            }
+            Err(error) if error.kind() == io::ErrorKind::NotFound => Ok(AddressBook::default()),
+            Err(error) => Err(error),
+        }
+    }
+
+    /// Saves the address book to `path`, replacing any previous version.
+    pub(super) fn save(&self, path: &Path) -> io::Result<()> {
+        // Write to a temporary file first, so that a crash doesn't leave a truncated address book.
+        let temp_path = path.with_extension("tmp");
+        fs::write(&temp_path, serde_json::to_vec_pretty(self)?)?;
+        fs::rename(temp_path, path)
+    }
+
+    /// Records that the peer listening on `address` was reachable at `timestamp`.
+    ///
+    /// Returns `true` if the address was not in the address book before.
+    pub(super) fn record(&mut self, address: SocketAddr, timestamp: Timestamp) -> bool {
+        self.entries
+            .insert(utils::normalize_address(address), timestamp)
+            .is_none()
+    }
+
+    /// Removes all entries which have not been reachable within `max_age` before `now`.
+    pub(super) fn prune(&mut self, now: Timestamp, max_age: Duration) {
+        self.entries = self
+            .entries
+            .iter()
+            .filter(|(_, last_reachable)| {
+                Duration::from(now.saturating_sub(**last_reachable)) <= max_age
+            })
+            .map(|(address, last_reachable)| (*address, *last_reachable))
+            .collect();
+    }
+
+    /// Returns the addresses in the address book.
+    pub(super) fn addresses(&self) -> impl Iterator<Item = SocketAddr> + '_ {
+        self.entries.keys().copied()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn should_prune_stale_entries_and_survive_round_trip() {
+        let temp_dir = tempfile::tempdir().expect("should get tempdir");
+        let path = temp_dir.path().join("address_book.json");
+        assert!(AddressBook::load(&path)
+            .expect("missing file should load")
+            .entries
+            .is_empty());
+
+        let fresh: SocketAddr = "127.0.0.1:34553".parse().unwrap();
+        let stale: SocketAddr = "127.0.0.1:34554".parse().unwrap();
+        let now = Timestamp::from(10_000);
+        let mut address_book = AddressBook::default();
+        assert!(address_book.record(fresh, now));
+        assert!(!address_book.record(fresh, now));
+        assert!(address_book.record(stale, Timestamp::from(1_000)));
+
+        address_book.prune(now, Duration::from_secs(5));
+        address_book.save(&path).expect("should save");
+
+        let loaded = AddressBook::load(&path).expect("should load");
+        assert_eq!(loaded.addresses().collect::<Vec<_>>(), vec![fresh]);
+    }
+}
@@ -0,0 +1,130 @@
+//! Handshake attestations.
+//!
+//! An attestation binds a node's network identity, i.e. the fingerprint of its TLS certificate, to
+//! its consensus public key. It is sent as the very first message on every outgoing connection,
+//! allowing the receiving side to verify that the peer actually holds the consensus key it claims.
+
+use std::fmt::{self, Display, Formatter};
+
+use rand::{CryptoRng, Rng};
+use serde::{Deserialize, Serialize};
+
+use super::{Error, NodeId};
+use crate::crypto::asymmetric_key::{self, PublicKey, SecretKey, Signature, SigningPurpose};
+
+/// A signed statement that the holder of `public_key` controls the node `node_id`.
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct HandshakeAttestation {
+    /// The node ID being attested.
+    node_id: NodeId,
+    /// The consensus public key of the node.
+    public_key: PublicKey,
+    /// Signature over the node ID, created using the consensus secret key.
+    signature: Signature,
+}
+
+impl HandshakeAttestation {
+    /// Creates a new attestation for `node_id`, signed with the given consensus key pair.
+    pub(crate) fn new<R: Rng + CryptoRng + ?Sized>(
+        node_id: NodeId,
+        secret_key: &SecretKey,
+        public_key: &PublicKey,
+        rng: &mut R,
+    ) -> Self {
+        let signature = asymmetric_key::sign(
+            node_id,
+            secret_key,
+            public_key,
+            SigningPurpose::HandshakeAttestation,
+            rng,
+        );
+        HandshakeAttestation {
+            node_id,
+            public_key: *public_key,
+            signature,
+        }
+    }
+
+    /// Returns the attested consensus public key.
+    pub(crate) fn public_key(&self) -> &PublicKey {
+        &self.public_key
+    }
+
+    /// Verifies that the attestation is for `peer_id` and was signed by the attested key.
+    pub(crate) fn verify(&self, peer_id: &NodeId) -> Result<(), Error> {
+        if self.node_id != *peer_id {
+            return Err(Error::WrongId);
+        }
+        asymmetric_key::verify(
+            self.node_id,
+            &self.signature,
+            &self.public_key,
+            SigningPurpose::HandshakeAttestation,
+        )
+        .map_err(Error::InvalidAttestation)
+    }
+}
+
+impl Display for HandshakeAttestation {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "attestation binding {} to {}",
+            self.node_id, self.public_key
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use rand::Rng;
+
+    use super::*;
+    use crate::testing::TestRng;
+
+    #[test]
+    fn should_accept_valid_attestation() {
+        let mut rng = TestRng::new();
+        let node_id: NodeId = rng.gen();
+        let secret_key = SecretKey::random(&mut rng);
+        let public_key = PublicKey::from(&secret_key);
+
+        let attestation = HandshakeAttestation::new(node_id, &secret_key, &public_key, &mut rng);
+        assert!(attestation.verify(&node_id).is_ok());
+        assert_eq!(*attestation.public_key(), public_key);
+    }
+
+    #[test]
+    fn should_reject_attestation_signed_by_wrong_key() {
+        let mut rng = TestRng::new();
+        let node_id: NodeId = rng.gen();
+        let secret_key = SecretKey::random_ed25519(&mut rng);
+        let public_key = PublicKey::from(&secret_key);
+        let other_public_key = PublicKey::from(&SecretKey::random_ed25519(&mut rng));
+
+        // Claim a different key than the one used for signing.
+        let mut attestation =
+            HandshakeAttestation::new(node_id, &secret_key, &public_key, &mut rng);
+        attestation.public_key = other_public_key;
+
+        assert!(matches!(
+            attestation.verify(&node_id),
+            Err(Error::InvalidAttestation(_))
+        ));
+    }
+
+    #[test]
+    fn should_reject_attestation_for_other_node() {
+        let mut rng = TestRng::new();
+        let node_id: NodeId = rng.gen();
+        let other_node_id: NodeId = rng.gen();
+        let secret_key = SecretKey::random(&mut rng);
+        let public_key = PublicKey::from(&secret_key);
+
+        let attestation = HandshakeAttestation::new(node_id, &secret_key, &public_key, &mut rng);
+        assert!(matches!(
+            attestation.verify(&other_node_id),
+            Err(Error::WrongId)
+        ));
+    }
+}
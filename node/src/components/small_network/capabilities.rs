@@ -0,0 +1,102 @@
+//! Protocol version and optional capabilities of peers.
+//!
+//! The first message on every outgoing connection is a hello carrying the sender's protocol
+//! version and the set of optional capabilities it implements.  The receiving side records the
+//! intersection with its own capabilities, so that an optional feature is only used with a peer if
+//! both sides implement it.  Until a peer's hello has been received, it is assumed to support none.
+//!
+//! Every capability is a message a peer without it could not decode, so sending one would make it
+//! close the connection.
+
+use std::{
+    fmt::{self, Display, Formatter},
+    iter::FromIterator,
+};
+
+use serde::{Deserialize, Serialize};
+
+/// The version of the peer-to-peer protocol spoken by this node.
+pub(crate) const PROTOCOL_VERSION: u32 = 2;
+
+/// An optional capability a node can support.
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub(crate) enum Capability {
+    /// Answering pings with pongs, to measure the round-trip time.
+    LatencyProbes,
+    /// Receiving a goodbye giving the reason a peer shuts down.
+    ShutdownNotices,
+}
+
+impl Capability {
+    /// All capabilities implemented by this node.
+    const ALL: [Capability; 2] = [Capability::LatencyProbes, Capability::ShutdownNotices];
+
+    /// Returns the bit representing the capability.
+    fn bit(self) -> u32 {
+        match self {
+            Capability::LatencyProbes => 1,
+            Capability::ShutdownNotices => 1 << 1,
+        }
+    }
+}
+
+impl Display for Capability {
+    fn fmt(&self, formatter: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            Capability::LatencyProbes => write!(formatter, "latency probes"),
+            Capability::ShutdownNotices => write!(formatter, "shutdown notices"),
+        }
+    }
+}
+
+/// A set of capabilities.
+///
+/// Bits of capabilities unknown to this node are preserved, but never survive an intersection with
+/// our own capabilities.
+#[derive(Copy, Clone, Debug, Default, Eq, PartialEq, Deserialize, Serialize)]
+#[serde(transparent)]
+pub struct Capabilities(u32);
+
+impl Capabilities {
+    /// Returns all capabilities implemented by this node.
+    pub(crate) fn implemented() -> Capabilities {
+        Capability::ALL.iter().copied().collect()
+    }
+
+    /// Returns whether `capability` is in the set.
+    pub(crate) fn contains(self, capability: Capability) -> bool {
+        self.0 & capability.bit() != 0
+    }
+
+    /// Returns the capabilities contained in both sets.
+    pub(crate) fn intersection(self, other: Capabilities) -> Capabilities {
+        Capabilities(self.0 & other.0)
+    }
+}
+
+impl FromIterator<Capability> for Capabilities {
+    fn from_iter<I: IntoIterator<Item = Capability>>(iter: I) -> Self {
+        Capabilities(
+            iter.into_iter()
+                .fold(0, |bits, capability| bits | capability.bit()),
+        )
+    }
+}
+
+impl Display for Capabilities {
+    fn fmt(&self, formatter: &mut Formatter<'_>) -> fmt::Result {
+        let mut capabilities = Capability::ALL
+            .iter()
+            .filter(|capability| self.contains(**capability));
+        match capabilities.next() {
+            None => write!(formatter, "none"),
+            Some(first) => {
+                write!(formatter, "{}", first)?;
+                for capability in capabilities {
+                    write!(formatter, ", {}", capability)?;
+                }
+                Ok(())
+            }
+        }
+    }
+}
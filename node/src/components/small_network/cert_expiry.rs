@@ -0,0 +1,126 @@
+//! Warnings about the approaching expiry of our own TLS certificate.
+//!
+//! Peers refuse connections presenting an expired certificate, so the node checks its certificate
+//! on startup and periodically.  Once the remaining validity falls within the configured lead
+//! time, a warning is logged on every check, which becomes an error during the final day and once
+//! the certificate has expired.
+
+use std::{
+    convert::TryInto,
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+
+use openssl::{asn1::Asn1Time, error::ErrorStack, x509::X509Ref};
+use tracing::{debug, error, warn};
+
+use super::NodeId;
+
+/// The remaining validity from which an approaching expiry is logged as an error.
+const IMMINENT_EXPIRY: Duration = Duration::from_secs(24 * 60 * 60);
+
+/// How soon a certificate expires.
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub(super) enum CertExpiry {
+    /// The certificate doesn't expire within the lead time.
+    Valid,
+    /// The certificate expires within the lead time.
+    Approaching,
+    /// The certificate expires within a day.
+    Imminent,
+    /// The certificate has expired.
+    Expired,
+}
+
+impl CertExpiry {
+    /// Classifies a certificate expiring in `remaining_secs` seconds, warning `lead_time` ahead.
+    pub(super) fn new(remaining_secs: i64, lead_time: Duration) -> Self {
+        let remaining = Duration::from_secs(remaining_secs.max(0) as u64);
+        if remaining_secs <= 0 {
+            CertExpiry::Expired
+        } else if remaining <= IMMINENT_EXPIRY {
+            CertExpiry::Imminent
+        } else if remaining <= lead_time {
+            CertExpiry::Approaching
+        } else {
+            CertExpiry::Valid
+        }
+    }
+
+    /// Logs the expiry of our certificate, which expires in `remaining_secs` seconds.
+    pub(super) fn log(self, our_id: NodeId, remaining_secs: i64) {
+        let remaining_days = remaining_secs / (24 * 60 * 60);
+        match self {
+            CertExpiry::Valid => debug!(remaining_days, "{}: certificate is valid", our_id),
+            CertExpiry::Approaching => warn!(
+                remaining_days,
+                "{}: certificate expires soon, replace it to stay connected to peers", our_id
+            ),
+            CertExpiry::Imminent => error!(
+                remaining_secs,
+                "{}: certificate expires within a day, replace it to stay connected to peers",
+                our_id
+            ),
+            CertExpiry::Expired => error!(
+                expired_secs_ago = -remaining_secs,
+                "{}: certificate has expired, peers will refuse connections", our_id
+            ),
+        }
+    }
+}
+
+/// Returns the number of seconds from the UNIX timestamp `now` until `cert` expires, which is
+/// negative if it has expired already.
+pub(super) fn seconds_until_expiry(cert: &X509Ref, now: i64) -> Result<i64, ErrorStack> {
+    let diff = Asn1Time::from_unix(now)?.diff(cert.not_after())?;
+    Ok(i64::from(diff.days) * 24 * 60 * 60 + i64::from(diff.secs))
+}
+
+/// Returns the current UNIX timestamp in seconds.
+pub(super) fn unix_now() -> i64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .expect("clock should be set after 1970")
+        .as_secs()
+        .try_into()
+        .expect("UNIX timestamp should fit into an i64")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tls;
+
+    const DAY_SECS: i64 = 24 * 60 * 60;
+
+    #[test]
+    fn should_warn_about_cert_expiring_within_lead_time() {
+        let lead_time = Duration::from_secs(30 * DAY_SECS as u64);
+        let (cert, _secret_key) = tls::generate_node_cert().expect("should generate certificate");
+        let now = unix_now();
+        let remaining_secs = seconds_until_expiry(&cert, now).unwrap();
+
+        // A newly generated certificate is valid for years.
+        assert!(remaining_secs > 365 * DAY_SECS);
+        assert_eq!(
+            CertExpiry::new(remaining_secs, lead_time),
+            CertExpiry::Valid
+        );
+
+        // Five days before the certificate expires, it is within the lead time.
+        let later = now + remaining_secs - 5 * DAY_SECS;
+        let remaining_secs = seconds_until_expiry(&cert, later).unwrap();
+        assert_eq!(remaining_secs, 5 * DAY_SECS);
+        assert_eq!(
+            CertExpiry::new(remaining_secs, lead_time),
+            CertExpiry::Approaching
+        );
+
+        // The warning escalates during the final day, and once the certificate has expired.
+        assert_eq!(
+            CertExpiry::new(DAY_SECS / 2, lead_time),
+            CertExpiry::Imminent
+        );
+        assert_eq!(CertExpiry::new(0, lead_time), CertExpiry::Expired);
+        assert_eq!(CertExpiry::new(-DAY_SECS, lead_time), CertExpiry::Expired);
+    }
+}
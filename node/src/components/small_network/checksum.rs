@@ -0,0 +1,123 @@
+//! Checksums of network frames.
+//!
+//! Every frame ends with a checksum of its payload, i.e. of the encoded message: the first
+//! `CHECKSUM_LENGTH` bytes of its BLAKE2b hash.  TLS protects frames in transit, but a frame
+//! corrupted before encryption or after decryption would otherwise be decoded into a garbage
+//! message, or fail to decode with an obscure error.  A frame whose checksum doesn't match is
+//! rejected, closing the connection to the peer.
+
+use std::{io, marker::PhantomData, pin::Pin};
+
+use blake2::{
+    digest::{Input, VariableOutput},
+    VarBlake2b,
+};
+use bytes::{Bytes, BytesMut};
+use serde::{de::DeserializeOwned, Serialize};
+use thiserror::Error;
+use tokio_serde::{Deserializer, Serializer};
+
+/// The length in bytes of the checksum appended to every frame.
+const CHECKSUM_LENGTH: usize = 4;
+
+/// A frame failed its checksum verification.
+#[derive(Debug, Error)]
+pub(super) enum ChecksumError {
+    /// The frame is shorter than a checksum.
+    #[error("frame of {0} bytes too short to carry a checksum")]
+    FrameTooShort(usize),
+    /// The checksum doesn't match the payload, which has been corrupted.
+    #[error("frame checksum mismatch, frame is corrupted")]
+    Mismatch,
+}
+
+/// Returns the checksum of `payload`.
+fn checksum(payload: &[u8]) -> [u8; CHECKSUM_LENGTH] {
+    let mut result = [0; CHECKSUM_LENGTH];
+    let mut hasher = VarBlake2b::new(CHECKSUM_LENGTH).expect("should create hasher");
+    hasher.input(payload);
+    hasher.variable_result(|slice| result.copy_from_slice(slice));
+    result
+}
+
+/// Appends the checksum of `payload` to it, turning it into a frame.
+pub(super) fn seal(mut payload: Vec<u8>) -> Vec<u8> {
+    let checksum = checksum(&payload);
+    payload.extend_from_slice(&checksum);
+    payload
+}
+
+/// Verifies the checksum at the end of `frame`, returning the payload preceding it.
+pub(super) fn open(frame: &[u8]) -> Result<&[u8], ChecksumError> {
+    if frame.len() < CHECKSUM_LENGTH {
+        return Err(ChecksumError::FrameTooShort(frame.len()));
+    }
+    let (payload, expected) = frame.split_at(frame.len() - CHECKSUM_LENGTH);
+    if checksum(payload)[..] != *expected {
+        return Err(ChecksumError::Mismatch);
+    }
+    Ok(payload)
+}
+
+/// MessagePack encoding of values in frames ending with a checksum, for `tokio_serde`.
+pub(super) struct ChecksummedMessagePack<T>(PhantomData<T>);
+
+impl<T> Default for ChecksummedMessagePack<T> {
+    fn default() -> Self {
+        ChecksummedMessagePack(PhantomData)
+    }
+}
+
+impl<T: DeserializeOwned> Deserializer<T> for ChecksummedMessagePack<T> {
+    type Error = io::Error;
+
+    fn deserialize(self: Pin<&mut Self>, src: &BytesMut) -> io::Result<T> {
+        let payload =
+            open(src).map_err(|error| io::Error::new(io::ErrorKind::InvalidData, error))?;
+        rmp_serde::from_read_ref(payload)
+            .map_err(|error| io::Error::new(io::ErrorKind::InvalidData, error))
+    }
+}
+
+impl<T: Serialize> Serializer<T> for ChecksummedMessagePack<T> {
+    type Error = io::Error;
+
+    fn serialize(self: Pin<&mut Self>, item: &T) -> io::Result<Bytes> {
+        rmp_serde::to_vec(item)
+            .map(|payload| seal(payload).into())
+            .map_err(|error| io::Error::new(io::ErrorKind::InvalidInput, error))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn should_reject_frame_with_corrupted_payload() {
+        let mut codec = ChecksummedMessagePack::<Vec<u64>>::default();
+        let frame = Pin::new(&mut codec)
+            .serialize(&vec![1, 2, 3])
+            .expect("should serialize");
+        let decoded = Pin::new(&mut codec)
+            .deserialize(&BytesMut::from(&frame[..]))
+            .expect("intact frame should be accepted");
+        assert_eq!(decoded, vec![1, 2, 3]);
+
+        // The corrupted payload still decodes as MessagePack, but the checksum catches it.
+        let mut corrupted = BytesMut::from(&frame[..]);
+        corrupted[1] ^= 1;
+        let payload = &corrupted[..corrupted.len() - CHECKSUM_LENGTH];
+        assert!(rmp_serde::from_read_ref::<_, Vec<u64>>(payload).is_ok());
+        assert!(matches!(open(&corrupted), Err(ChecksumError::Mismatch)));
+        let error = Pin::new(&mut codec)
+            .deserialize(&corrupted)
+            .expect_err("corrupted frame should be rejected");
+        assert_eq!(error.kind(), io::ErrorKind::InvalidData);
+
+        assert!(matches!(
+            open(&frame[..2]),
+            Err(ChecksumError::FrameTooShort(2))
+        ));
+    }
+}
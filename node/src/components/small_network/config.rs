@@ -1,7 +1,7 @@
 #[cfg(test)]
 use std::net::{Ipv4Addr, SocketAddr};
 
-use std::time::Duration;
+use std::{collections::BTreeMap, net::IpAddr, path::PathBuf, time::Duration};
 
 use serde::{Deserialize, Serialize};
 
@@ -21,6 +21,85 @@ const DEFAULT_PUBLIC_ADDRESS: &str = "127.0.0.1:0";
 /// Default interval for gossiping network addresses.
 const DEFAULT_GOSSIP_INTERVAL: Duration = Duration::from_secs(30);
 
+/// Default maximum number of inbound connections.
+const DEFAULT_MAX_INBOUND_CONNECTIONS: usize = 1000;
+
+/// Default maximum number of inbound connections from a single IP address: unlimited.
+const DEFAULT_MAX_INBOUND_CONNECTIONS_PER_IP: usize = 0;
+
+/// Default maximum number of inbound connections from a single subnet: unlimited.
+const DEFAULT_MAX_INBOUND_CONNECTIONS_PER_SUBNET: usize = 0;
+
+/// Default prefix length of the IPv4 subnets inbound connections are limited per.
+const DEFAULT_IPV4_SUBNET_PREFIX_LENGTH: u8 = 24;
+
+/// Default prefix length of the IPv6 subnets inbound connections are limited per.
+const DEFAULT_IPV6_SUBNET_PREFIX_LENGTH: u8 = 48;
+
+/// Default interval between attempts to reconnect to a validator.
+const DEFAULT_VALIDATOR_RECONNECT_INTERVAL: Duration = Duration::from_secs(1);
+
+/// Default maximum delay between attempts to reconnect to a validator.
+const DEFAULT_VALIDATOR_RECONNECT_CEILING: Duration = Duration::from_secs(30);
+
+/// Default maximum fraction by which the delay before reconnecting to a validator is shortened.
+const DEFAULT_VALIDATOR_RECONNECT_JITTER: f64 = 0.5;
+
+/// Default interval between pings to measure the round-trip time to each peer.
+const DEFAULT_PING_INTERVAL: Duration = Duration::from_secs(30);
+
+/// Default maximum time since a peer was last reachable for it to be kept in the address book.
+const DEFAULT_ADDRESS_BOOK_MAX_AGE: Duration = Duration::from_secs(7 * 24 * 60 * 60);
+
+/// Default maximum number of messages queued for sending to a single peer.
+const DEFAULT_MAX_OUTGOING_QUEUE_SIZE: usize = 10_000;
+
+/// Default maximum size of a single frame received from a peer (16 MiB), leaving room for a block
+/// of the default maximum block size of 10 MiB along with its message envelope.
+const DEFAULT_MAX_FRAME_SIZE: usize = 16 * 1024 * 1024;
+
+/// Default maximum time to wait for the next message on an incoming connection.
+///
+/// Must be longer than the default ping interval, as the pings keep otherwise idle connections
+/// active.
+const DEFAULT_READ_TIMEOUT: Duration = Duration::from_secs(120);
+
+/// Default maximum time sending a single message to a peer may take.
+const DEFAULT_WRITE_TIMEOUT: Duration = Duration::from_secs(60);
+
+/// Default maximum number of payloads kept per peer to retry sending them.
+const DEFAULT_RETRY_BUFFER_SIZE: usize = 32;
+
+/// Default maximum time a payload is kept to retry sending it.
+const DEFAULT_RETRY_BUFFER_MAX_AGE: Duration = Duration::from_secs(30);
+
+/// Default time ahead of its expiry from which warnings about our certificate are logged.
+const DEFAULT_CERT_EXPIRY_LEAD_TIME: Duration = Duration::from_secs(30 * 24 * 60 * 60);
+
+/// Default interval between checks of the expiry of our certificate.
+const DEFAULT_CERT_EXPIRY_CHECK_INTERVAL: Duration = Duration::from_secs(60 * 60);
+
+/// What to do when a message is sent to a peer whose outgoing queue is full.
+#[derive(Copy, Clone, Debug, Eq, PartialEq, Deserialize, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum OverflowPolicy {
+    /// Drop the new message.
+    DropNewest,
+    /// Drop the longest-queued message to make room for the new one.
+    ///
+    /// A bulk message never displaces a high-priority one; if there is no queued message it may
+    /// displace, the new message is dropped instead.
+    DropOldest,
+    /// Drop all queued messages and close the connection to the peer.
+    Disconnect,
+}
+
+impl Default for OverflowPolicy {
+    fn default() -> Self {
+        OverflowPolicy::DropNewest
+    }
+}
+
 // Default values for networking configuration:
 impl Default for Config {
     fn default() -> Self {
@@ -29,6 +108,29 @@ impl Default for Config {
             public_address: DEFAULT_PUBLIC_ADDRESS.to_string(),
             known_addresses: Vec::new(),
             gossip_interval: DEFAULT_GOSSIP_INTERVAL,
+            max_inbound_connections: DEFAULT_MAX_INBOUND_CONNECTIONS,
+            max_inbound_connections_per_ip: DEFAULT_MAX_INBOUND_CONNECTIONS_PER_IP,
+            max_inbound_connections_per_subnet: DEFAULT_MAX_INBOUND_CONNECTIONS_PER_SUBNET,
+            ipv4_subnet_prefix_length: DEFAULT_IPV4_SUBNET_PREFIX_LENGTH,
+            ipv6_subnet_prefix_length: DEFAULT_IPV6_SUBNET_PREFIX_LENGTH,
+            validator_reconnect_interval: DEFAULT_VALIDATOR_RECONNECT_INTERVAL,
+            validator_reconnect_ceiling: DEFAULT_VALIDATOR_RECONNECT_CEILING,
+            validator_reconnect_jitter: DEFAULT_VALIDATOR_RECONNECT_JITTER,
+            address_book_path: None,
+            address_book_max_age: DEFAULT_ADDRESS_BOOK_MAX_AGE,
+            ping_interval: DEFAULT_PING_INTERVAL,
+            max_outgoing_queue_size: DEFAULT_MAX_OUTGOING_QUEUE_SIZE,
+            outgoing_queue_overflow_policy: OverflowPolicy::default(),
+            max_frame_size: DEFAULT_MAX_FRAME_SIZE,
+            read_timeout: DEFAULT_READ_TIMEOUT,
+            write_timeout: DEFAULT_WRITE_TIMEOUT,
+            send_buffer_size: 0,
+            retry_buffer_size: DEFAULT_RETRY_BUFFER_SIZE,
+            retry_buffer_max_age: DEFAULT_RETRY_BUFFER_MAX_AGE,
+            report_shutdown_reason: true,
+            peer_localities: BTreeMap::new(),
+            cert_expiry_lead_time: DEFAULT_CERT_EXPIRY_LEAD_TIME,
+            cert_expiry_check_interval: DEFAULT_CERT_EXPIRY_CHECK_INTERVAL,
         }
     }
 }
@@ -39,6 +141,9 @@ impl Default for Config {
 #[serde(deny_unknown_fields)]
 pub struct Config {
     /// Address to bind to.
+    ///
+    /// Like all addresses, this can be an IPv4 or IPv6 address, with the latter enclosed in
+    /// brackets if followed by a port, e.g. `[::]:34553`.
     pub bind_address: String,
     /// Publically advertised address, in case the node has a different external IP.
     ///
@@ -49,12 +154,106 @@ pub struct Config {
     /// Interval in milliseconds used for gossiping.
     #[serde(with = "crate::utils::milliseconds")]
     pub gossip_interval: Duration,
+    /// Maximum number of inbound connections.
+    ///
+    /// Once reached, any further incoming connection is closed immediately. Outgoing connections
+    /// are not affected, and neither are connections from known validators, which do not count
+    /// towards the limit.
+    pub max_inbound_connections: usize,
+    /// Maximum number of inbound connections from a single IP address.  If 0, the number is
+    /// unlimited.
+    ///
+    /// Any further incoming connection from the address is closed immediately.  Like for
+    /// `max_inbound_connections`, connections from known validators are exempt.
+    pub max_inbound_connections_per_ip: usize,
+    /// Maximum number of inbound connections from a single subnet, as given by
+    /// `ipv4_subnet_prefix_length` and `ipv6_subnet_prefix_length`.  If 0, the number is
+    /// unlimited.
+    pub max_inbound_connections_per_subnet: usize,
+    /// The prefix length of the IPv4 subnets inbound connections are limited per.
+    pub ipv4_subnet_prefix_length: u8,
+    /// The prefix length of the IPv6 subnets inbound connections are limited per.
+    pub ipv6_subnet_prefix_length: u8,
+    /// Interval in milliseconds between attempts to reconnect to a validator after losing the
+    /// connection to it.
+    #[serde(with = "crate::utils::milliseconds")]
+    pub validator_reconnect_interval: Duration,
+    /// Maximum delay in milliseconds between attempts to reconnect to a validator.  The delay
+    /// starts at `validator_reconnect_interval` and doubles with every failed attempt.
+    #[serde(with = "crate::utils::milliseconds")]
+    pub validator_reconnect_ceiling: Duration,
+    /// Maximum fraction, between 0 and 1, by which each delay before reconnecting to a validator
+    /// is shortened at random, so that nodes which lost the connection at the same time don't
+    /// all retry at once.
+    pub validator_reconnect_jitter: f64,
+    /// Path of the file in which the addresses of peers are persisted, so they can be dialed right
+    /// away on restart.
+    ///
+    /// If unset, the address book is not persisted.
+    #[serde(default)]
+    pub address_book_path: Option<PathBuf>,
+    /// Maximum time in milliseconds since a peer was last reachable for it to be kept in the
+    /// address book.
+    #[serde(with = "crate::utils::milliseconds")]
+    pub address_book_max_age: Duration,
+    /// Interval in milliseconds between pings to measure the round-trip time to each peer.
+    #[serde(with = "crate::utils::milliseconds")]
+    pub ping_interval: Duration,
+    /// Maximum number of messages queued for sending to a single peer.  If 0, the number is
+    /// unlimited.
+    pub max_outgoing_queue_size: usize,
+    /// What to do when a message is sent to a peer whose outgoing queue is full.
+    pub outgoing_queue_overflow_policy: OverflowPolicy,
+    /// Maximum size in bytes of a single frame received from a peer.
+    ///
+    /// A frame is buffered completely before being decoded, so this bounds the memory needed per
+    /// connection to receive a message.  A peer sending a larger frame is disconnected.  It must
+    /// exceed the chainspec's `max_block_size`, so that a full block can be received.
+    pub max_frame_size: usize,
+    /// Maximum time in milliseconds to wait for the next message on an incoming connection.
+    ///
+    /// Peers ping every `ping_interval`, so this must be longer than the ping interval of all
+    /// peers.  A connection exceeding it is closed.
+    #[serde(with = "crate::utils::milliseconds")]
+    pub read_timeout: Duration,
+    /// Maximum time in milliseconds sending a single message on an outgoing connection may take.
+    /// A connection exceeding it is closed.
+    #[serde(with = "crate::utils::milliseconds")]
+    pub write_timeout: Duration,
+    /// Size in bytes of the operating system's send buffer of outgoing connections.  If 0, the
+    /// operating system's default is used.
+    #[serde(default)]
+    pub send_buffer_size: usize,
+    /// Maximum number of payloads which failed to be sent kept per peer, to retry sending them
+    /// once the connection recovers.  If 0, failed sends are not retried.
+    pub retry_buffer_size: usize,
+    /// Maximum time in milliseconds a payload which failed to be sent is kept for retrying.
+    #[serde(with = "crate::utils::milliseconds")]
+    pub retry_buffer_max_age: Duration,
+    /// Whether to tell peers why this node is shutting down in a goodbye message.
+    pub report_shutdown_reason: bool,
+    /// The localities of peers, e.g. their datacenters or autonomous systems, by IP address.
+    ///
+    /// Gossip is spread across distinct localities where possible.  If empty, peers are chosen
+    /// regardless of their locality.
+    #[serde(default)]
+    pub peer_localities: BTreeMap<IpAddr, String>,
+    /// Time in milliseconds ahead of the expiry of our certificate from which warnings are logged.
+    #[serde(with = "crate::utils::milliseconds")]
+    pub cert_expiry_lead_time: Duration,
+    /// Interval in milliseconds between checks of the expiry of our certificate.
+    #[serde(with = "crate::utils::milliseconds")]
+    pub cert_expiry_check_interval: Duration,
 }
 
 #[cfg(test)]
 /// Reduced gossip interval for local testing.
 const DEFAULT_TEST_GOSSIP_INTERVAL: Duration = Duration::from_secs(1);
 
+#[cfg(test)]
+/// Reduced ping interval for local testing, long enough for test networks to settle in between.
+const DEFAULT_TEST_PING_INTERVAL: Duration = Duration::from_secs(10);
+
 #[cfg(test)]
 /// Address used to bind all local testing networking to by default.
 const TEST_BIND_INTERFACE: Ipv4Addr = Ipv4Addr::LOCALHOST;
@@ -69,6 +268,29 @@ impl Config {
             public_address: bind_address.to_string(),
             known_addresses: Vec::new(),
             gossip_interval: DEFAULT_TEST_GOSSIP_INTERVAL,
+            max_inbound_connections: DEFAULT_MAX_INBOUND_CONNECTIONS,
+            max_inbound_connections_per_ip: DEFAULT_MAX_INBOUND_CONNECTIONS_PER_IP,
+            max_inbound_connections_per_subnet: DEFAULT_MAX_INBOUND_CONNECTIONS_PER_SUBNET,
+            ipv4_subnet_prefix_length: DEFAULT_IPV4_SUBNET_PREFIX_LENGTH,
+            ipv6_subnet_prefix_length: DEFAULT_IPV6_SUBNET_PREFIX_LENGTH,
+            validator_reconnect_interval: DEFAULT_VALIDATOR_RECONNECT_INTERVAL,
+            validator_reconnect_ceiling: DEFAULT_VALIDATOR_RECONNECT_CEILING,
+            validator_reconnect_jitter: DEFAULT_VALIDATOR_RECONNECT_JITTER,
+            address_book_path: None,
+            address_book_max_age: DEFAULT_ADDRESS_BOOK_MAX_AGE,
+            ping_interval: DEFAULT_TEST_PING_INTERVAL,
+            max_outgoing_queue_size: DEFAULT_MAX_OUTGOING_QUEUE_SIZE,
+            outgoing_queue_overflow_policy: OverflowPolicy::default(),
+            max_frame_size: DEFAULT_MAX_FRAME_SIZE,
+            read_timeout: DEFAULT_READ_TIMEOUT,
+            write_timeout: DEFAULT_WRITE_TIMEOUT,
+            send_buffer_size: 0,
+            retry_buffer_size: DEFAULT_RETRY_BUFFER_SIZE,
+            retry_buffer_max_age: DEFAULT_RETRY_BUFFER_MAX_AGE,
+            report_shutdown_reason: true,
+            peer_localities: BTreeMap::new(),
+            cert_expiry_lead_time: DEFAULT_CERT_EXPIRY_LEAD_TIME,
+            cert_expiry_check_interval: DEFAULT_CERT_EXPIRY_CHECK_INTERVAL,
         }
     }
 
@@ -84,6 +306,29 @@ impl Config {
             public_address: format_address(TEST_BIND_INTERFACE, 0),
             known_addresses: vec![format_address(TEST_BIND_INTERFACE, known_peer_port)],
             gossip_interval: DEFAULT_TEST_GOSSIP_INTERVAL,
+            max_inbound_connections: DEFAULT_MAX_INBOUND_CONNECTIONS,
+            max_inbound_connections_per_ip: DEFAULT_MAX_INBOUND_CONNECTIONS_PER_IP,
+            max_inbound_connections_per_subnet: DEFAULT_MAX_INBOUND_CONNECTIONS_PER_SUBNET,
+            ipv4_subnet_prefix_length: DEFAULT_IPV4_SUBNET_PREFIX_LENGTH,
+            ipv6_subnet_prefix_length: DEFAULT_IPV6_SUBNET_PREFIX_LENGTH,
+            validator_reconnect_interval: DEFAULT_VALIDATOR_RECONNECT_INTERVAL,
+            validator_reconnect_ceiling: DEFAULT_VALIDATOR_RECONNECT_CEILING,
+            validator_reconnect_jitter: DEFAULT_VALIDATOR_RECONNECT_JITTER,
+            address_book_path: None,
+            address_book_max_age: DEFAULT_ADDRESS_BOOK_MAX_AGE,
+            ping_interval: DEFAULT_TEST_PING_INTERVAL,
+            max_outgoing_queue_size: DEFAULT_MAX_OUTGOING_QUEUE_SIZE,
+            outgoing_queue_overflow_policy: OverflowPolicy::default(),
+            max_frame_size: DEFAULT_MAX_FRAME_SIZE,
+            read_timeout: DEFAULT_READ_TIMEOUT,
+            write_timeout: DEFAULT_WRITE_TIMEOUT,
+            send_buffer_size: 0,
+            retry_buffer_size: DEFAULT_RETRY_BUFFER_SIZE,
+            retry_buffer_max_age: DEFAULT_RETRY_BUFFER_MAX_AGE,
+            report_shutdown_reason: true,
+            peer_localities: BTreeMap::new(),
+            cert_expiry_lead_time: DEFAULT_CERT_EXPIRY_LEAD_TIME,
+            cert_expiry_check_interval: DEFAULT_CERT_EXPIRY_CHECK_INTERVAL,
         }
     }
 }
@@ -0,0 +1,145 @@
+//! Limits on the number of inbound connections from a single IP address or subnet.
+//!
+//! A single host can open many connections under different node IDs, and so take up most of our
+//! inbound connection slots.  Capping the connections per source IP, and optionally per subnet,
+//! bounds the share of the slots any one host or network can occupy.
+
+use std::{
+    fmt::{self, Display, Formatter},
+    net::IpAddr,
+};
+
+/// One of the limits on inbound connections.
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub(super) enum ConnectionLimit {
+    /// The maximum number of connections from a single IP address.
+    PerIp,
+    /// The maximum number of connections from a single subnet.
+    PerSubnet,
+}
+
+impl Display for ConnectionLimit {
+    fn fmt(&self, formatter: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            ConnectionLimit::PerIp => write!(formatter, "per-IP"),
+            ConnectionLimit::PerSubnet => write!(formatter, "per-subnet"),
+        }
+    }
+}
+
+/// Limits on inbound connections per source IP address and subnet.
+#[derive(Copy, Clone, Debug)]
+pub(super) struct ConnectionLimits {
+    /// The maximum number of connections from a single IP address, or zero if unlimited.
+    per_ip: usize,
+    /// The maximum number of connections from a single subnet, or zero if unlimited.
+    per_subnet: usize,
+    /// The prefix length of IPv4 subnets.
+    ipv4_subnet_prefix_length: u8,
+    /// The prefix length of IPv6 subnets.
+    ipv6_subnet_prefix_length: u8,
+}
+
+impl ConnectionLimits {
+    /// Creates limits of `per_ip` connections per IP address and `per_subnet` per subnet, with
+    /// subnets of the given prefix lengths.  A limit of zero disables it.
+    pub(super) fn new(
+        per_ip: usize,
+        per_subnet: usize,
+        ipv4_subnet_prefix_length: u8,
+        ipv6_subnet_prefix_length: u8,
+    ) -> Self {
+        ConnectionLimits {
+            per_ip,
+            per_subnet,
+            ipv4_subnet_prefix_length,
+            ipv6_subnet_prefix_length,
+        }
+    }
+
+    /// Returns the limit a further connection from `ip` would exceed, if any, given the source IPs
+    /// of the existing connections counting towards the limits.
+    pub(super) fn exceeded_by<I>(&self, ip: IpAddr, existing: I) -> Option<ConnectionLimit>
+    where
+        I: IntoIterator<Item = IpAddr>,
+    {
+        if self.per_ip == 0 && self.per_subnet == 0 {
+            return None;
+        }
+        let subnet = self.subnet(ip);
+        let (mut same_ip, mut same_subnet) = (0, 0);
+        for existing_ip in existing {
+            if existing_ip == ip {
+                same_ip += 1;
+            }
+            if self.subnet(existing_ip) == subnet {
+                same_subnet += 1;
+            }
+        }
+        if self.per_ip != 0 && same_ip >= self.per_ip {
+            Some(ConnectionLimit::PerIp)
+        } else if self.per_subnet != 0 && same_subnet >= self.per_subnet {
+            Some(ConnectionLimit::PerSubnet)
+        } else {
+            None
+        }
+    }
+
+    /// Returns the subnet `ip` is in, i.e. `ip` with all bits beyond the prefix cleared.
+    fn subnet(&self, ip: IpAddr) -> IpAddr {
+        match ip {
+            IpAddr::V4(ipv4) => {
+                let shift = 32u32.saturating_sub(self.ipv4_subnet_prefix_length.into());
+                let mask = u32::MAX.checked_shl(shift).unwrap_or(0);
+                IpAddr::V4((u32::from(ipv4) & mask).into())
+            }
+            IpAddr::V6(ipv6) => {
+                let shift = 128u32.saturating_sub(self.ipv6_subnet_prefix_length.into());
+                let mask = u128::MAX.checked_shl(shift).unwrap_or(0);
+                IpAddr::V6((u128::from(ipv6) & mask).into())
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::net::{Ipv4Addr, Ipv6Addr};
+
+    use super::*;
+
+    fn ipv4(a: u8, b: u8, c: u8, d: u8) -> IpAddr {
+        Ipv4Addr::new(a, b, c, d).into()
+    }
+
+    #[test]
+    fn should_limit_connections_per_ip_and_subnet() {
+        let limits = ConnectionLimits::new(2, 3, 24, 64);
+        let existing = vec![ipv4(10, 0, 0, 1), ipv4(10, 0, 0, 1), ipv4(10, 0, 0, 2)];
+
+        assert_eq!(
+            limits.exceeded_by(ipv4(10, 0, 0, 1), existing.clone()),
+            Some(ConnectionLimit::PerIp)
+        );
+        assert_eq!(
+            limits.exceeded_by(ipv4(10, 0, 0, 3), existing.clone()),
+            Some(ConnectionLimit::PerSubnet)
+        );
+        assert_eq!(
+            limits.exceeded_by(ipv4(10, 0, 1, 1), existing.clone()),
+            None
+        );
+
+        let ipv6: IpAddr = "2001:db8:0:1::1".parse::<Ipv6Addr>().unwrap().into();
+        let same_ipv6_subnet: IpAddr = "2001:db8:0:1::2".parse::<Ipv6Addr>().unwrap().into();
+        let limits = ConnectionLimits::new(0, 1, 24, 64);
+        assert_eq!(
+            limits.exceeded_by(same_ipv6_subnet, vec![ipv6]),
+            Some(ConnectionLimit::PerSubnet)
+        );
+
+        // Without limits, nothing is refused.
+        let limits = ConnectionLimits::new(0, 0, 0, 0);
+        assert_eq!(limits.exceeded_by(ipv4(10, 0, 0, 1), existing), None);
+    }
+}
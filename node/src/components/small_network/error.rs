@@ -5,7 +5,7 @@ use thiserror::Error;
 use tokio::net::TcpStream;
 use tokio_openssl::HandshakeError;
 
-use crate::tls::ValidationError;
+use crate::{crypto, tls::ValidationError};
 
 pub(super) type Result<T> = result::Result<T, Error>;
 
@@ -21,6 +21,9 @@ pub enum Error {
     /// Peer ID presented does not match the expected one.
     #[error("remote node has wrong ID")]
     WrongId,
+    /// Handshake attestation signature failed to verify.
+    #[error("invalid handshake attestation")]
+    InvalidAttestation(#[source] crypto::Error),
     /// The config must have both or neither of certificate and secret key.
     #[error("need either both or none of cert, secret_key in network config")]
     InvalidConfig,
@@ -39,9 +42,18 @@ pub enum Error {
     /// Could not resolve root node address.
     #[error("failed to resolve network address")]
     ResolveAddr(#[source] io::Error),
+    /// Failed to encode a message for sending.
+    #[error("failed to encode message")]
+    MessageNotEncoded(#[source] rmp_serde::encode::Error),
     /// Failed to send message.
     #[error("failed to send message")]
     MessageNotSent(#[source] io::Error),
+    /// Sending a message took longer than the write timeout.
+    #[error("timed out sending message")]
+    WriteTimeout,
+    /// The queue of outgoing messages overflowed, and the overflow policy is to disconnect.
+    #[error("outgoing message queue overflowed")]
+    OutgoingQueueOverflow,
     /// Failed to create TLS acceptor.
     #[error("failed to create acceptor")]
     AcceptorCreation(#[source] ErrorStack),
@@ -57,6 +69,9 @@ pub enum Error {
     /// TLS validation error.
     #[error("TLS validation error: {0}")]
     TlsValidation(#[from] ValidationError),
+    /// Metrics-related error.
+    #[error("prometheus (metrics) error: {0}")]
+    Metrics(#[from] prometheus::Error),
     /// System time error.
     #[error("system time error: {0}")]
     SystemTime(#[from] SystemTimeError),
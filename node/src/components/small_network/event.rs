@@ -1,14 +1,19 @@
 use std::{
+    collections::HashSet,
     fmt::{self, Debug, Display, Formatter},
     io,
     net::SocketAddr,
+    time::Duration,
 };
 
 use derive_more::From;
 use tokio::net::TcpStream;
 
 use super::{Error, GossipedAddress, Message, NodeId, Transport};
-use crate::effect::requests::{NetworkInfoRequest, NetworkRequest};
+use crate::{
+    crypto::asymmetric_key::PublicKey,
+    effect::requests::{NetworkInfoRequest, NetworkRequest},
+};
 
 #[derive(Debug, From)]
 pub enum Event<P> {
@@ -23,6 +28,8 @@ pub enum Event<P> {
     IncomingHandshakeCompleted {
         result: Result<(NodeId, Transport), Error>,
         address: SocketAddr,
+        /// The time taken since the connection was accepted.
+        handshake_duration: Duration,
     },
     /// Received network message.
     IncomingMessage { peer_id: NodeId, msg: Message<P> },
@@ -37,6 +44,8 @@ pub enum Event<P> {
     OutgoingEstablished {
         peer_id: NodeId,
         transport: Transport,
+        /// The time taken to connect, including the TLS handshake.
+        handshake_duration: Duration,
     },
     /// An outgoing connection failed to connect or was terminated.
     OutgoingFailed {
@@ -55,8 +64,16 @@ pub enum Event<P> {
 
     /// The node should gossip its own public listening address.
     GossipOurAddress,
+    /// The node should ping all peers it has an outgoing connection to.
+    PingPeers,
+    /// The node should check how soon its certificate expires.
+    CheckCertExpiry,
     /// We received a peer's public listening address via gossip.
     PeerAddressReceived(GossipedAddress),
+    /// The set of validators, identified by their consensus public keys, has changed.
+    ValidatorsChanged(HashSet<PublicKey>),
+    /// The connection to the validator listening on the given address should be re-established.
+    ReconnectValidator { peer_address: SocketAddr },
 }
 
 impl<P: Display> Display for Event<P> {
@@ -66,9 +83,9 @@ impl<P: Display> Display for Event<P> {
                 write!(f, "bootstrapping failed for node {}: {}", address, error)
             }
             Event::IncomingNew { address, .. } => write!(f, "incoming connection from {}", address),
-            Event::IncomingHandshakeCompleted { result, address } => {
-                write!(f, "handshake from {}, is_err {}", address, result.is_err())
-            }
+            Event::IncomingHandshakeCompleted {
+                result, address, ..
+            } => write!(f, "handshake from {}, is_err {}", address, result.is_err()),
             Event::IncomingMessage {
                 peer_id: node_id,
                 msg,
@@ -103,9 +120,19 @@ impl<P: Display> Display for Event<P> {
             Event::NetworkRequest { req } => write!(f, "request: {}", req),
             Event::NetworkInfoRequest { req } => write!(f, "request: {}", req),
             Event::GossipOurAddress => write!(f, "gossip our address"),
+            Event::PingPeers => write!(f, "ping peers"),
+            Event::CheckCertExpiry => write!(f, "check certificate expiry"),
             Event::PeerAddressReceived(gossiped_address) => {
                 write!(f, "received gossiped peer address {}", gossiped_address)
             }
+            Event::ValidatorsChanged(validators) => write!(
+                f,
+                "validator set changed to {} validators",
+                validators.len()
+            ),
+            Event::ReconnectValidator { peer_address } => {
+                write!(f, "reconnect to validator at {}", peer_address)
+            }
         }
     }
 }
@@ -0,0 +1,213 @@
+//! Round-trip time measurements.
+//!
+//! Every ping interval, a ping carrying a fresh nonce is sent on each outgoing connection.  The
+//! peer answers with a pong carrying the same nonce on its own outgoing connection to us, and the
+//! time between sending the ping and receiving the matching pong is recorded as the peer's
+//! round-trip time.  Measurements are flagged as stale once they haven't been refreshed for a
+//! number of ping intervals, and are dropped once the peer disconnects.
+//!
+//! The measurements are kept in a `LatencyStore` shared with the gossipers, which prefer peers with
+//! short round-trip times as gossip targets.
+
+use std::{
+    collections::HashMap,
+    sync::{Arc, RwLock},
+    time::{Duration, Instant},
+};
+
+use super::NodeId;
+use crate::types::Timestamp;
+
+/// The number of ping intervals after which a measurement which hasn't been refreshed is stale.
+const STALE_AFTER_PINGS: u32 = 3;
+
+/// The latest round-trip time measured to a peer.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct PeerLatency {
+    /// The measured round-trip time.
+    pub rtt: Duration,
+    /// When the round-trip time was measured.
+    pub measured_at: Timestamp,
+    /// Whether the measurement is outdated, e.g. because the peer has stopped answering pings.
+    pub stale: bool,
+}
+
+/// The latest round-trip time measured to each connected peer, and when it was measured.
+///
+/// Clones share the same measurements.
+#[derive(Clone, Debug, Default)]
+pub(crate) struct LatencyStore(Arc<RwLock<HashMap<NodeId, (Duration, Timestamp)>>>);
+
+impl LatencyStore {
+    /// Returns the latest round-trip time measured to each peer.
+    pub(crate) fn round_trip_times(&self) -> HashMap<NodeId, Duration> {
+        self.0
+            .read()
+            .expect("latency store lock poisoned")
+            .iter()
+            .map(|(peer_id, &(rtt, _))| (*peer_id, rtt))
+            .collect()
+    }
+
+    fn insert(&self, peer_id: NodeId, rtt: Duration, measured_at: Timestamp) {
+        let _ = self
+            .0
+            .write()
+            .expect("latency store lock poisoned")
+            .insert(peer_id, (rtt, measured_at));
+    }
+
+    fn remove(&self, peer_id: &NodeId) {
+        let _ = self
+            .0
+            .write()
+            .expect("latency store lock poisoned")
+            .remove(peer_id);
+    }
+}
+
+/// Sends pings and measures the round-trip times of the matching pongs.
+#[derive(Debug, Default)]
+pub(super) struct Pinger {
+    /// The nonce of the next ping.
+    next_nonce: u64,
+    /// The nonce of the last ping sent to each peer, and when it was sent.
+    outstanding: HashMap<NodeId, (u64, Instant)>,
+    /// The latest measurement for each peer, and when it was made.
+    measurements: LatencyStore,
+}
+
+impl Pinger {
+    /// Returns the store of the measurements, shared with the pinger.
+    pub(super) fn latency_store(&self) -> LatencyStore {
+        self.measurements.clone()
+    }
+
+    /// Forgets the pings sent to and the measurement of `peer_id`, which has disconnected.
+    pub(super) fn peer_disconnected(&mut self, peer_id: &NodeId) {
+        let _ = self.outstanding.remove(peer_id);
+        self.measurements.remove(peer_id);
+    }
+
+    /// Records that a ping is sent to `peer_id` at `now`, and returns its nonce.
+    ///
+    /// Any earlier ping to the peer which hasn't been answered yet is forgotten.
+    pub(super) fn ping(&mut self, peer_id: NodeId, now: Instant) -> u64 {
+        let nonce = self.next_nonce;
+        self.next_nonce = self.next_nonce.wrapping_add(1);
+        let _ = self.outstanding.insert(peer_id, (nonce, now));
+        nonce
+    }
+
+    /// Records a pong from `peer_id` received at `now`, and returns the measured round-trip time.
+    ///
+    /// Returns `None` if the nonce doesn't match the last ping sent to the peer.
+    pub(super) fn pong(
+        &mut self,
+        peer_id: NodeId,
+        nonce: u64,
+        now: Instant,
+        timestamp: Timestamp,
+    ) -> Option<Duration> {
+        match self.outstanding.get(&peer_id) {
+            Some(&(expected_nonce, sent_at)) if expected_nonce == nonce => {
+                let _ = self.outstanding.remove(&peer_id);
+                let rtt = now.saturating_duration_since(sent_at);
+                self.measurements.insert(peer_id, rtt, timestamp);
+                Some(rtt)
+            }
+            _ => None,
+        }
+    }
+
+    /// Returns the latest measurement for each peer as of `now`, given the `ping_interval`.
+    pub(super) fn latencies(
+        &self,
+        now: Timestamp,
+        ping_interval: Duration,
+    ) -> HashMap<NodeId, PeerLatency> {
+        let stale_after = ping_interval * STALE_AFTER_PINGS;
+        self.measurements
+            .0
+            .read()
+            .expect("latency store lock poisoned")
+            .iter()
+            .map(|(peer_id, &(rtt, measured_at))| {
+                let age = Duration::from(now.saturating_sub(measured_at));
+                let latency = PeerLatency {
+                    rtt,
+                    measured_at,
+                    stale: age > stale_after,
+                };
+                (*peer_id, latency)
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use rand::Rng;
+
+    use super::*;
+    use crate::testing::TestRng;
+
+    #[test]
+    fn should_measure_round_trip_times_and_flag_stale_ones() {
+        let mut rng = TestRng::new();
+        let fresh_peer: NodeId = rng.gen();
+        let stale_peer: NodeId = rng.gen();
+        let ping_interval = Duration::from_secs(1);
+        let mut pinger = Pinger::default();
+
+        let start = Instant::now();
+        let stale_nonce = pinger.ping(stale_peer, start);
+        let fresh_nonce = pinger.ping(fresh_peer, start);
+        assert_ne!(stale_nonce, fresh_nonce);
+
+        // Pongs with the wrong nonce are ignored.
+        let received_at = start + Duration::from_millis(40);
+        assert_eq!(
+            pinger.pong(fresh_peer, stale_nonce, received_at, Timestamp::from(1_000)),
+            None
+        );
+        assert_eq!(
+            pinger.pong(stale_peer, stale_nonce, received_at, Timestamp::from(1_000)),
+            Some(Duration::from_millis(40))
+        );
+        assert_eq!(
+            pinger.pong(fresh_peer, fresh_nonce, received_at, Timestamp::from(4_000)),
+            Some(Duration::from_millis(40))
+        );
+        // A pong is only counted once.
+        assert_eq!(
+            pinger.pong(fresh_peer, fresh_nonce, received_at, Timestamp::from(4_000)),
+            None
+        );
+
+        let latencies = pinger.latencies(Timestamp::from(5_000), ping_interval);
+        assert_eq!(latencies.len(), 2);
+        assert_eq!(
+            latencies[&fresh_peer],
+            PeerLatency {
+                rtt: Duration::from_millis(40),
+                measured_at: Timestamp::from(4_000),
+                stale: false,
+            }
+        );
+        assert!(latencies[&stale_peer].stale);
+
+        // The measurements of disconnected peers are dropped, also from the shared store.
+        let latency_store = pinger.latency_store();
+        pinger.peer_disconnected(&stale_peer);
+        let latencies = pinger.latencies(Timestamp::from(5_000), ping_interval);
+        assert_eq!(latencies.len(), 1);
+        assert!(latencies.contains_key(&fresh_peer));
+        assert_eq!(
+            latency_store.round_trip_times(),
+            vec![(fresh_peer, Duration::from_millis(40))]
+                .into_iter()
+                .collect()
+        );
+    }
+}
@@ -0,0 +1,104 @@
+//! Tagging of peers with their locality, e.g. their datacenter or autonomous system.
+//!
+//! Peers in the same locality are likely to learn about new items from each other quickly, so
+//! gossiping the same item to several of them is largely redundant.  If peers are tagged with
+//! localities, gossip targets can therefore be spread across as many distinct localities as
+//! possible.
+
+use std::{
+    collections::{BTreeMap, HashSet},
+    fmt::Debug,
+    net::IpAddr,
+};
+
+/// A hook assigning peers a locality label based on their IP address.
+pub(crate) trait LocalityTagger: Debug + Send + Sync {
+    /// Returns the locality of the peer with the given address, or `None` if it is unknown.
+    fn locality(&self, address: IpAddr) -> Option<&str>;
+}
+
+/// Tags peers according to a fixed mapping from IP addresses to localities.
+#[derive(Debug)]
+pub(crate) struct LocalityMap(BTreeMap<IpAddr, String>);
+
+impl LocalityMap {
+    /// Creates a tagger using the given mapping.
+    pub(crate) fn new(localities: BTreeMap<IpAddr, String>) -> Self {
+        LocalityMap(localities)
+    }
+}
+
+impl LocalityTagger for LocalityMap {
+    fn locality(&self, address: IpAddr) -> Option<&str> {
+        self.0.get(&address).map(String::as_str)
+    }
+}
+
+/// Chooses up to `count` of `peers`, which are given in order of preference, preferring peers in
+/// localities none of the chosen peers is in so far.
+///
+/// Peers with an unknown locality are considered to be in a locality of their own.
+pub(super) fn spread_across_localities<'a, I, F>(peers: Vec<I>, count: usize, locality: F) -> Vec<I>
+where
+    F: Fn(&I) -> Option<&'a str>,
+{
+    let mut localities = HashSet::new();
+    let mut chosen = Vec::new();
+    let mut remaining = Vec::new();
+    for peer in peers {
+        let is_new_locality = locality(&peer).map_or(true, |label| localities.insert(label));
+        if is_new_locality && chosen.len() < count {
+            chosen.push(peer);
+        } else {
+            remaining.push(peer);
+        }
+    }
+    let missing = count.saturating_sub(chosen.len());
+    chosen.extend(remaining.into_iter().take(missing));
+    chosen
+}
+
+#[cfg(test)]
+mod tests {
+    use rand::seq::SliceRandom;
+
+    use super::*;
+    use crate::testing::TestRng;
+
+    #[test]
+    fn should_prefer_peers_in_distinct_localities() {
+        let mut rng = TestRng::new();
+        let addresses: Vec<IpAddr> = (1..=4)
+            .map(|index| format!("10.0.0.{}", index).parse().unwrap())
+            .collect();
+        let tagger = LocalityMap::new(
+            addresses
+                .iter()
+                .zip(&["A", "A", "B", "B"])
+                .map(|(address, label)| (*address, label.to_string()))
+                .collect(),
+        );
+
+        for _ in 0..20 {
+            let mut peers = addresses.clone();
+            peers.shuffle(&mut rng);
+            let chosen = spread_across_localities(peers, 2, |peer| tagger.locality(*peer));
+            let mut localities: Vec<_> = chosen
+                .iter()
+                .map(|peer| tagger.locality(*peer).unwrap())
+                .collect();
+            localities.sort_unstable();
+            assert_eq!(localities, vec!["A", "B"]);
+        }
+
+        // Once every locality is covered, further peers are chosen in order of preference.
+        let chosen = spread_across_localities(addresses.clone(), 3, |peer| tagger.locality(*peer));
+        assert_eq!(chosen, vec![addresses[0], addresses[2], addresses[1]]);
+
+        // Without a mapping, the most preferred peers are chosen.
+        let untagged = LocalityMap::new(BTreeMap::new());
+        let chosen =
+            spread_across_localities(addresses.clone(), 2, |peer| untagged.locality(*peer));
+        assert_eq!(chosen, addresses[..2].to_vec());
+    }
+}
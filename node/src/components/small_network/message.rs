@@ -1,12 +1,99 @@
 use std::fmt::{self, Debug, Display, Formatter};
 
+use bytes::Bytes;
 use serde::{Deserialize, Serialize};
 
+use super::{checksum, Capabilities, HandshakeAttestation};
+use crate::types::ShutdownReason;
+
 #[derive(Clone, Debug, Deserialize, Serialize)]
-pub struct Message<P>(pub(super) P);
+pub enum Message<P> {
+    /// Attestation of the sender's consensus key, sent right after the hello.
+    Handshake(Box<HandshakeAttestation>),
+    /// A request to answer with a pong carrying the same nonce, to measure the round-trip time.
+    Ping { nonce: u64 },
+    /// The answer to a ping.
+    Pong { nonce: u64 },
+    /// A payload message.
+    Payload(P),
+    /// Notice that the sender is shutting down, and why.
+    Goodbye { reason: ShutdownReason },
+    /// The sender's protocol version and optional capabilities, sent first on every connection.
+    Hello {
+        protocol_version: u32,
+        capabilities: Capabilities,
+    },
+}
+
+impl<P: Payload> Message<P> {
+    /// Returns the priority with which the message is sent.
+    pub(super) fn priority(&self) -> Priority {
+        match self {
+            Message::Handshake(_)
+            | Message::Ping { .. }
+            | Message::Pong { .. }
+            | Message::Goodbye { .. }
+            | Message::Hello { .. } => Priority::High,
+            Message::Payload(payload) => payload.priority(),
+        }
+    }
+}
 
 impl<P: Display> Display for Message<P> {
     fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
-        write!(f, "payload: {}", self.0)
+        match self {
+            Message::Handshake(attestation) => write!(f, "handshake: {}", attestation),
+            Message::Ping { nonce } => write!(f, "ping {}", nonce),
+            Message::Pong { nonce } => write!(f, "pong {}", nonce),
+            Message::Payload(payload) => write!(f, "payload: {}", payload),
+            Message::Goodbye { reason } => write!(f, "goodbye: {}", reason),
+            Message::Hello {
+                protocol_version,
+                capabilities,
+            } => write!(
+                f,
+                "hello: protocol version {}, capabilities: {}",
+                protocol_version, capabilities
+            ),
+        }
+    }
+}
+
+/// A message serialized for sending, followed by its checksum.
+///
+/// A message is encoded exactly once: The same buffer is checked against the maximum frame size,
+/// has its size recorded in the metrics and is written to the connection.
+#[derive(Debug)]
+pub(super) struct EncodedMessage(Bytes);
+
+impl EncodedMessage {
+    /// Encodes `msg` the way the receiving side decodes it.
+    pub(super) fn encode<P: Serialize>(msg: &Message<P>) -> Result<Self, rmp_serde::encode::Error> {
+        rmp_serde::to_vec(msg).map(|serialized| EncodedMessage(checksum::seal(serialized).into()))
     }
+
+    /// Returns the encoded size in bytes, including the checksum.
+    pub(super) fn len(&self) -> usize {
+        self.0.len()
+    }
+
+    /// Returns the encoded message, to be written as a single frame.
+    pub(super) fn into_bytes(self) -> Bytes {
+        self.0
+    }
+}
+
+/// How urgently a message is to be sent.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Priority {
+    /// Time-critical messages, sent ahead of any queued bulk messages on the same connection.
+    High,
+    /// Large or less time-critical messages, like transfers of blocks and deploys.
+    Bulk,
+}
+
+/// A payload sent over the network.
+pub trait Payload {
+    /// Returns the priority with which the payload is sent.
+    fn priority(&self) -> Priority;
 }
@@ -0,0 +1,113 @@
+//! Metrics of the small network.
+
+use prometheus::{self, Histogram, HistogramOpts, IntCounter, IntGauge, Registry};
+
+/// Value of upper bound of the first message size histogram bucket (64 bytes).
+const EXPONENTIAL_BUCKET_START: f64 = 64.0;
+/// Multiplier of previous upper bound for next bound.
+const EXPONENTIAL_BUCKET_FACTOR: f64 = 4.0;
+/// Bucket count, with last going to +Inf.
+const EXPONENTIAL_BUCKET_COUNT: usize = 10;
+
+/// Value of upper bound of the first handshake duration histogram bucket (1 ms).
+const HANDSHAKE_BUCKET_START: f64 = 0.001;
+/// Multiplier of previous upper bound for next bound.
+const HANDSHAKE_BUCKET_FACTOR: f64 = 2.0;
+/// Bucket count, with last going to +Inf.
+const HANDSHAKE_BUCKET_COUNT: usize = 14;
+
+/// Metrics of the small network.
+#[derive(Debug)]
+pub(super) struct NetworkMetrics {
+    /// Histogram of the encoded sizes of messages sent to peers.
+    ///
+    /// Shared with the tasks sending on each outgoing connection, which record the size of the
+    /// already encoded message rather than encoding it again.
+    pub(super) outgoing_message_size: Histogram,
+    /// Seconds until our certificate expires, negative once it has expired.
+    pub(super) cert_expiry_seconds: IntGauge,
+    /// Histogram of the time taken to establish connections, including the TLS handshake.
+    pub(super) handshake_duration: Histogram,
+    /// Number of connections established to or from peers.
+    pub(super) connections_established: IntCounter,
+    /// Number of times the connections to a peer were torn down.
+    pub(super) peer_disconnections: IntCounter,
+
+    /// Handle to the metrics registry, in case we need to unregister.
+    registry: Registry,
+}
+
+impl NetworkMetrics {
+    /// Create and register new small network metrics.
+    pub(super) fn new(registry: &Registry) -> Result<Self, prometheus::Error> {
+        let buckets = prometheus::exponential_buckets(
+            EXPONENTIAL_BUCKET_START,
+            EXPONENTIAL_BUCKET_FACTOR,
+            EXPONENTIAL_BUCKET_COUNT,
+        )?;
+        let outgoing_message_size = Histogram::with_opts(
+            HistogramOpts::new(
+                "net_outgoing_message_size_bytes",
+                "encoded size of messages sent to peers in bytes",
+            )
+            .buckets(buckets),
+        )?;
+        let cert_expiry_seconds = IntGauge::new(
+            "net_certificate_expiry_seconds",
+            "seconds until the node's certificate expires",
+        )?;
+        let handshake_duration = Histogram::with_opts(
+            HistogramOpts::new(
+                "net_handshake_duration_seconds",
+                "time taken to establish connections with peers in seconds",
+            )
+            .buckets(prometheus::exponential_buckets(
+                HANDSHAKE_BUCKET_START,
+                HANDSHAKE_BUCKET_FACTOR,
+                HANDSHAKE_BUCKET_COUNT,
+            )?),
+        )?;
+        let connections_established = IntCounter::new(
+            "net_connections_established_total",
+            "number of connections established to or from peers",
+        )?;
+        let peer_disconnections = IntCounter::new(
+            "net_peer_disconnections_total",
+            "number of times the connections to a peer were torn down",
+        )?;
+        registry.register(Box::new(outgoing_message_size.clone()))?;
+        registry.register(Box::new(cert_expiry_seconds.clone()))?;
+        registry.register(Box::new(handshake_duration.clone()))?;
+        registry.register(Box::new(connections_established.clone()))?;
+        registry.register(Box::new(peer_disconnections.clone()))?;
+
+        Ok(NetworkMetrics {
+            outgoing_message_size,
+            cert_expiry_seconds,
+            handshake_duration,
+            connections_established,
+            peer_disconnections,
+            registry: registry.clone(),
+        })
+    }
+}
+
+impl Drop for NetworkMetrics {
+    fn drop(&mut self) {
+        self.registry
+            .unregister(Box::new(self.outgoing_message_size.clone()))
+            .expect("did not expect deregistering outgoing message size to fail");
+        self.registry
+            .unregister(Box::new(self.cert_expiry_seconds.clone()))
+            .expect("did not expect deregistering certificate expiry to fail");
+        self.registry
+            .unregister(Box::new(self.handshake_duration.clone()))
+            .expect("did not expect deregistering handshake duration to fail");
+        self.registry
+            .unregister(Box::new(self.connections_established.clone()))
+            .expect("did not expect deregistering established connections to fail");
+        self.registry
+            .unregister(Box::new(self.peer_disconnections.clone()))
+            .expect("did not expect deregistering peer disconnections to fail");
+    }
+}
@@ -0,0 +1,102 @@
+//! Backoff between attempts to reconnect to a validator.
+//!
+//! After losing the connection to a validator, reconnecting is retried after
+//! `validator_reconnect_interval`, doubling the delay with every failed attempt up to
+//! `validator_reconnect_ceiling`.  If many nodes lose the connection to the same validator at
+//! once, e.g. when it restarts, they would otherwise all retry in lockstep, so each delay is
+//! shortened by a random fraction of up to `validator_reconnect_jitter`.  This spreads the attempts
+//! out without ever exceeding the ceiling.
+
+use std::{collections::HashMap, net::SocketAddr, time::Duration};
+
+use rand::Rng;
+
+/// The maximum number of times the delay is doubled, to avoid overflowing it.
+const MAX_DOUBLINGS: u32 = 16;
+
+/// Tracks the failed attempts to reconnect to each validator, and the delay before the next one.
+#[derive(Debug)]
+pub(super) struct ReconnectBackoff {
+    /// The delay before the first attempt.
+    interval: Duration,
+    /// The maximum delay between attempts.
+    ceiling: Duration,
+    /// The maximum fraction by which a delay is shortened at random.
+    jitter: f64,
+    /// The number of attempts made since the connection to each address was lost.
+    attempts: HashMap<SocketAddr, u32>,
+}
+
+impl ReconnectBackoff {
+    pub(super) fn new(interval: Duration, ceiling: Duration, jitter: f64) -> Self {
+        ReconnectBackoff {
+            interval,
+            ceiling,
+            jitter,
+            attempts: HashMap::new(),
+        }
+    }
+
+    /// Returns the delay before the next attempt to reconnect to `address`, and counts the attempt.
+    pub(super) fn next_delay<R: Rng + ?Sized>(
+        &mut self,
+        address: SocketAddr,
+        rng: &mut R,
+    ) -> Duration {
+        let ceiling = self.ceiling;
+        let attempts = self.attempts.entry(address).or_insert(0);
+        let delay = self
+            .interval
+            .checked_mul(1 << (*attempts).min(MAX_DOUBLINGS))
+            .map_or(ceiling, |delay| delay.min(ceiling));
+        *attempts += 1;
+        if self.jitter <= 0.0 {
+            return delay;
+        }
+        delay - delay.mul_f64(rng.gen_range(0.0, self.jitter))
+    }
+
+    /// Forgets the attempts to reconnect to `address`, e.g. once connected to it again.
+    pub(super) fn reset(&mut self, address: &SocketAddr) {
+        let _ = self.attempts.remove(address);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::BTreeSet;
+
+    use super::*;
+    use crate::testing::TestRng;
+
+    #[test]
+    fn should_jitter_reconnect_delays_below_ceiling() {
+        let mut rng = TestRng::from_seed([7; 16]);
+        let interval = Duration::from_secs(1);
+        let ceiling = Duration::from_secs(8);
+        let jitter = 0.5;
+        let mut backoff = ReconnectBackoff::new(interval, ceiling, jitter);
+
+        // A single node's delays double up to the ceiling, each shortened by up to half.
+        let address: SocketAddr = "127.0.0.1:34553".parse().unwrap();
+        for expected in &[1, 2, 4, 8, 8, 8] {
+            let full_delay = Duration::from_secs(*expected);
+            let delay = backoff.next_delay(address, &mut rng);
+            assert!(delay <= full_delay, "{:?} exceeds {:?}", delay, full_delay);
+            assert!(delay >= full_delay.mul_f64(1.0 - jitter));
+        }
+
+        // Many nodes retrying at the same time don't all wait for the same delay.
+        let delays: BTreeSet<_> = (0..100)
+            .map(|port| {
+                let address = SocketAddr::from(([127, 0, 0, 1], port));
+                backoff.next_delay(address, &mut rng)
+            })
+            .collect();
+        assert!(delays.len() > 50, "too few distinct delays");
+
+        // Once reconnected, the next loss starts over with the initial interval.
+        backoff.reset(&address);
+        assert!(backoff.next_delay(address, &mut rng) <= interval);
+    }
+}
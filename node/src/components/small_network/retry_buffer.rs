@@ -0,0 +1,284 @@
+//! Buffering of payloads which could not be sent, to retry them once the connection recovers.
+//!
+//! Sending a payload to a peer can fail transiently, e.g. because its outgoing queue is full or
+//! the connection was briefly lost.  Instead of dropping such payloads right away, up to
+//! `capacity` of them are kept per peer for at most `max_age` and sent again later.  When a peer's
+//! buffer is full, the oldest buffered payload is dropped to make room, with the same precedence as
+//! in the outgoing queues: A bulk payload never displaces a high-priority one, like a consensus
+//! message.  High-priority payloads are also retried first.
+//!
+//! A payload failing to be sent again when it is retried keeps the time it was first buffered, so
+//! it still expires `max_age` after its first failure.
+
+use std::{
+    collections::{HashMap, VecDeque},
+    sync::Mutex,
+    time::{Duration, Instant},
+};
+
+use super::{NodeId, Payload, Priority};
+
+/// The payloads buffered for a single peer, with the time they were buffered.
+#[derive(Debug)]
+struct PeerBuffer<P> {
+    high: VecDeque<(Instant, P)>,
+    bulk: VecDeque<(Instant, P)>,
+}
+
+impl<P> Default for PeerBuffer<P> {
+    fn default() -> Self {
+        PeerBuffer {
+            high: VecDeque::new(),
+            bulk: VecDeque::new(),
+        }
+    }
+}
+
+impl<P> PeerBuffer<P> {
+    fn len(&self) -> usize {
+        self.high.len() + self.bulk.len()
+    }
+
+    /// Removes all payloads buffered before `cutoff`.
+    fn purge(&mut self, cutoff: Instant) {
+        self.high.retain(|(buffered, _)| *buffered >= cutoff);
+        self.bulk.retain(|(buffered, _)| *buffered >= cutoff);
+    }
+}
+
+/// Inserts `payload` buffered at `buffered_at` into `queue`, keeping it ordered by age.
+fn insert_by_age<P>(queue: &mut VecDeque<(Instant, P)>, buffered_at: Instant, payload: P) {
+    let index = queue
+        .iter()
+        .rposition(|(buffered, _)| *buffered <= buffered_at)
+        .map_or(0, |index| index + 1);
+    queue.insert(index, (buffered_at, payload));
+}
+
+/// Bounded per-peer buffers of payloads to be retried.
+#[derive(Debug)]
+pub(super) struct RetryBuffer<P> {
+    /// The maximum number of payloads buffered per peer, or zero if retrying is disabled.
+    capacity: usize,
+    /// The maximum time a payload is kept for retrying.
+    max_age: Duration,
+    // Sending a message only borrows the network immutably, hence the mutex.
+    peers: Mutex<HashMap<NodeId, PeerBuffer<P>>>,
+}
+
+impl<P: Payload> RetryBuffer<P> {
+    /// Creates buffers holding up to `capacity` payloads per peer for at most `max_age`.
+    pub(super) fn new(capacity: usize, max_age: Duration) -> Self {
+        RetryBuffer {
+            capacity,
+            max_age,
+            peers: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// Removes the payloads of all peers which have expired at `now`.
+    fn purge(&self, peers: &mut HashMap<NodeId, PeerBuffer<P>>, now: Instant) {
+        if let Some(cutoff) = now.checked_sub(self.max_age) {
+            peers.retain(|_, buffer| {
+                buffer.purge(cutoff);
+                buffer.len() > 0
+            });
+        }
+    }
+
+    /// Buffers `payload` which first failed to be sent to `peer_id` at `buffered_at`, where `now`
+    /// is the current time.
+    ///
+    /// Returns the payload dropped to make room if the buffer is full: Either the oldest one the
+    /// new payload may displace, or the new payload itself.  The new payload is also returned if it
+    /// has already expired.
+    pub(super) fn push(
+        &self,
+        peer_id: NodeId,
+        payload: P,
+        buffered_at: Instant,
+        now: Instant,
+    ) -> Option<P> {
+        if self.capacity == 0 || now.duration_since(buffered_at) > self.max_age {
+            return Some(payload);
+        }
+        let mut peers = self.peers.lock().expect("lock poisoned");
+        self.purge(&mut peers, now);
+        let buffer = peers.entry(peer_id).or_default();
+        let priority = payload.priority();
+        let mut dropped = None;
+        if buffer.len() >= self.capacity {
+            let oldest = match priority {
+                Priority::High => buffer.bulk.pop_front().or_else(|| buffer.high.pop_front()),
+                Priority::Bulk => buffer.bulk.pop_front(),
+            };
+            match oldest {
+                Some((_, oldest)) => dropped = Some(oldest),
+                None => return Some(payload),
+            }
+        }
+        match priority {
+            Priority::High => insert_by_age(&mut buffer.high, buffered_at, payload),
+            Priority::Bulk => insert_by_age(&mut buffer.bulk, buffered_at, payload),
+        }
+        dropped
+    }
+
+    /// Removes and returns the payloads buffered for `peer_id` which have not expired at `now`,
+    /// high-priority ones first, along with the time they were first buffered.
+    ///
+    /// The expired payloads of all other peers are removed as well.
+    pub(super) fn take(&self, peer_id: &NodeId, now: Instant) -> Vec<(Instant, P)> {
+        let mut peers = self.peers.lock().expect("lock poisoned");
+        self.purge(&mut peers, now);
+        match peers.remove(peer_id) {
+            Some(buffer) => buffer.high.into_iter().chain(buffer.bulk).collect(),
+            None => Vec::new(),
+        }
+    }
+
+    /// Returns the number of peers with buffered payloads.
+    #[cfg(test)]
+    fn peer_count(&self) -> usize {
+        self.peers.lock().expect("lock poisoned").len()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use rand::Rng;
+
+    use super::*;
+    use crate::testing::TestRng;
+
+    #[derive(Debug, PartialEq)]
+    struct TestPayload {
+        id: u8,
+        priority: Priority,
+    }
+
+    impl Payload for TestPayload {
+        fn priority(&self) -> Priority {
+            self.priority
+        }
+    }
+
+    fn payload(id: u8, priority: Priority) -> TestPayload {
+        TestPayload { id, priority }
+    }
+
+    fn ids(payloads: Vec<(Instant, TestPayload)>) -> Vec<u8> {
+        payloads
+            .into_iter()
+            .map(|(_, payload)| payload.id)
+            .collect()
+    }
+
+    #[test]
+    fn should_retry_high_priority_payloads_first_and_drop_oldest_when_full() {
+        let mut rng = TestRng::new();
+        let peer_id: NodeId = rng.gen();
+        let now = Instant::now();
+        let buffer = RetryBuffer::new(2, Duration::from_secs(10));
+
+        assert!(buffer
+            .push(peer_id, payload(0, Priority::Bulk), now, now)
+            .is_none());
+        assert!(buffer
+            .push(peer_id, payload(1, Priority::High), now, now)
+            .is_none());
+        // A high-priority payload displaces the oldest bulk one.
+        let dropped = buffer.push(peer_id, payload(2, Priority::High), now, now);
+        assert_eq!(dropped, Some(payload(0, Priority::Bulk)));
+        // A bulk payload never displaces a high-priority one.
+        let dropped = buffer.push(peer_id, payload(3, Priority::Bulk), now, now);
+        assert_eq!(dropped, Some(payload(3, Priority::Bulk)));
+        // Otherwise, the oldest high-priority payload is dropped.
+        let dropped = buffer.push(peer_id, payload(4, Priority::High), now, now);
+        assert_eq!(dropped, Some(payload(1, Priority::High)));
+
+        assert_eq!(ids(buffer.take(&peer_id, now)), vec![2, 4]);
+        assert!(buffer.take(&peer_id, now).is_empty());
+    }
+
+    #[test]
+    fn should_not_retry_expired_payloads() {
+        let mut rng = TestRng::new();
+        let peer_id: NodeId = rng.gen();
+        let now = Instant::now();
+        let buffer = RetryBuffer::new(2, Duration::from_secs(10));
+
+        assert!(buffer
+            .push(peer_id, payload(0, Priority::High), now, now)
+            .is_none());
+        assert!(buffer
+            .push(
+                peer_id,
+                payload(1, Priority::Bulk),
+                now + Duration::from_secs(5),
+                now + Duration::from_secs(5)
+            )
+            .is_none());
+        assert_eq!(
+            ids(buffer.take(&peer_id, now + Duration::from_secs(12))),
+            vec![1]
+        );
+    }
+
+    #[test]
+    fn should_keep_original_buffering_time_when_retry_fails() {
+        let mut rng = TestRng::new();
+        let peer_id: NodeId = rng.gen();
+        let now = Instant::now();
+        let later = now + Duration::from_secs(8);
+        let buffer = RetryBuffer::new(2, Duration::from_secs(10));
+
+        assert!(buffer
+            .push(peer_id, payload(0, Priority::Bulk), now, now)
+            .is_none());
+        assert!(buffer
+            .push(peer_id, payload(1, Priority::Bulk), later, later)
+            .is_none());
+
+        // Retrying the first payload fails again, so it is buffered with its original time.
+        let mut retried = buffer.take(&peer_id, later);
+        let (buffered_at, first) = retried.remove(0);
+        assert_eq!(buffered_at, now);
+        for (buffered_at, payload) in retried {
+            assert!(buffer.push(peer_id, payload, buffered_at, later).is_none());
+        }
+        assert!(buffer.push(peer_id, first, buffered_at, later).is_none());
+
+        // It is still the oldest one, and expires as if it had never been retried.
+        assert_eq!(
+            ids(buffer.take(&peer_id, now + Duration::from_secs(12))),
+            vec![1]
+        );
+        assert!(buffer
+            .push(
+                peer_id,
+                payload(2, Priority::Bulk),
+                now,
+                now + Duration::from_secs(12)
+            )
+            .is_some());
+    }
+
+    #[test]
+    fn should_purge_expired_payloads_of_all_peers_when_taking() {
+        let mut rng = TestRng::new();
+        let stale_peer: NodeId = rng.gen();
+        let peer_id: NodeId = rng.gen();
+        let now = Instant::now();
+        let buffer = RetryBuffer::new(2, Duration::from_secs(10));
+
+        assert!(buffer
+            .push(stale_peer, payload(0, Priority::High), now, now)
+            .is_none());
+        assert_eq!(buffer.peer_count(), 1);
+        assert!(buffer
+            .take(&peer_id, now + Duration::from_secs(12))
+            .is_empty());
+        assert_eq!(buffer.peer_count(), 0);
+    }
+}
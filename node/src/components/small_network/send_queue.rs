@@ -0,0 +1,329 @@
+//! Per-connection queues of outgoing messages.
+//!
+//! Messages are queued by priority: All queued high-priority messages are sent before any bulk
+//! messages, even if those were queued earlier.  Within the same priority, messages are sent in the
+//! order they were queued.
+//!
+//! A queue holds a limited number of messages.  Once it is full, the `OverflowPolicy` decides
+//! whether a message is dropped to make room, or whether the queue is closed, which disconnects
+//! the peer.
+
+use std::{
+    collections::VecDeque,
+    sync::{Arc, Mutex},
+};
+
+use tokio::sync::Notify;
+
+use super::{
+    message::{Message, Payload, Priority},
+    OverflowPolicy,
+};
+
+/// Creates a new queue holding up to `capacity` messages, returning the handles to queue and to
+/// receive messages.
+///
+/// If `capacity` is zero, the queue is unbounded.
+pub(super) fn channel<P>(capacity: usize, policy: OverflowPolicy) -> (Sender<P>, Receiver<P>) {
+    let shared = Arc::new(Shared {
+        queues: Mutex::new(Queues {
+            high: VecDeque::new(),
+            bulk: VecDeque::new(),
+            overflow_count: 0,
+            overflowed: false,
+            sender_dropped: false,
+            receiver_dropped: false,
+        }),
+        notify: Notify::new(),
+        capacity,
+        policy,
+    });
+    let sender = Sender {
+        shared: Arc::clone(&shared),
+    };
+    (sender, Receiver { shared })
+}
+
+#[derive(Debug)]
+struct Queues<P> {
+    high: VecDeque<Message<P>>,
+    bulk: VecDeque<Message<P>>,
+    /// The number of times a message did not fit into the full queue.
+    overflow_count: u64,
+    /// Whether the queue has been closed due to an overflow.
+    overflowed: bool,
+    sender_dropped: bool,
+    receiver_dropped: bool,
+}
+
+impl<P> Queues<P> {
+    fn len(&self) -> usize {
+        self.high.len() + self.bulk.len()
+    }
+
+    /// Removes the longest-queued message which a new message of the given priority may displace:
+    /// Any bulk message, or a high-priority one only if the new message is high-priority too.
+    fn pop_oldest(&mut self, priority: Priority) -> Option<Message<P>> {
+        match priority {
+            Priority::High => self.bulk.pop_front().or_else(|| self.high.pop_front()),
+            Priority::Bulk => self.bulk.pop_front(),
+        }
+    }
+}
+
+#[derive(Debug)]
+struct Shared<P> {
+    queues: Mutex<Queues<P>>,
+    /// Notifies the receiver of newly queued messages, the sender being dropped or the queue being
+    /// closed due to an overflow.
+    notify: Notify,
+    /// The maximum number of queued messages, or zero if unbounded.
+    capacity: usize,
+    /// What to do when a message is sent while the queue is full.
+    policy: OverflowPolicy,
+}
+
+/// The reason a message could not be queued as requested.
+#[derive(Debug)]
+pub(super) enum SendError<P> {
+    /// The receiver has been dropped, the message is returned.
+    Closed(Message<P>),
+    /// The queue was full, and the returned message was dropped to apply the policy: Either the
+    /// new message, or the longest-queued one to make room for it.
+    Dropped(Message<P>),
+    /// The queue was full, and has been closed to disconnect the peer.  The new message and all
+    /// queued ones were dropped.
+    Disconnected,
+}
+
+/// The handle to queue messages.
+#[derive(Debug)]
+pub(super) struct Sender<P> {
+    shared: Arc<Shared<P>>,
+}
+
+impl<P: Payload> Sender<P> {
+    /// Queues a message according to its priority.
+    ///
+    /// If the queue is full, the overflow policy is applied, which is reported as an error.
+    pub(super) fn send(&self, msg: Message<P>) -> Result<(), SendError<P>> {
+        let mut queues = self.shared.queues.lock().expect("lock poisoned");
+        if queues.receiver_dropped || queues.overflowed {
+            return Err(SendError::Closed(msg));
+        }
+        let mut result = Ok(());
+        if self.shared.capacity != 0 && queues.len() >= self.shared.capacity {
+            queues.overflow_count += 1;
+            match self.shared.policy {
+                OverflowPolicy::DropNewest => return Err(SendError::Dropped(msg)),
+                OverflowPolicy::DropOldest => match queues.pop_oldest(msg.priority()) {
+                    Some(oldest) => result = Err(SendError::Dropped(oldest)),
+                    None => return Err(SendError::Dropped(msg)),
+                },
+                OverflowPolicy::Disconnect => {
+                    queues.overflowed = true;
+                    queues.high.clear();
+                    queues.bulk.clear();
+                    drop(queues);
+                    self.shared.notify.notify();
+                    return Err(SendError::Disconnected);
+                }
+            }
+        }
+        match msg.priority() {
+            Priority::High => queues.high.push_back(msg),
+            Priority::Bulk => queues.bulk.push_back(msg),
+        }
+        drop(queues);
+        self.shared.notify.notify();
+        result
+    }
+
+    /// Returns the number of times a message did not fit into the full queue.
+    pub(super) fn overflow_count(&self) -> u64 {
+        self.shared
+            .queues
+            .lock()
+            .expect("lock poisoned")
+            .overflow_count
+    }
+}
+
+impl<P> Drop for Sender<P> {
+    fn drop(&mut self) {
+        if let Ok(mut queues) = self.shared.queues.lock() {
+            queues.sender_dropped = true;
+        }
+        self.shared.notify.notify();
+    }
+}
+
+/// The handle to receive queued messages.
+#[derive(Debug)]
+pub(super) struct Receiver<P> {
+    shared: Arc<Shared<P>>,
+}
+
+impl<P> Receiver<P> {
+    /// Receives the next message, waiting for one to be queued if necessary.
+    ///
+    /// Returns `None` once the sender has been dropped and all queued messages have been received,
+    /// or once the queue has been closed due to an overflow.
+    pub(super) async fn recv(&mut self) -> Option<Message<P>> {
+        loop {
+            {
+                let mut queues = self.shared.queues.lock().expect("lock poisoned");
+                if queues.overflowed {
+                    return None;
+                }
+                if let Some(msg) = queues.high.pop_front() {
+                    return Some(msg);
+                }
+                if let Some(msg) = queues.bulk.pop_front() {
+                    return Some(msg);
+                }
+                if queues.sender_dropped {
+                    return None;
+                }
+            }
+            self.shared.notify.notified().await;
+        }
+    }
+
+    /// Returns whether the queue has been closed due to an overflow.
+    pub(super) fn has_overflowed(&self) -> bool {
+        self.shared.queues.lock().expect("lock poisoned").overflowed
+    }
+}
+
+impl<P> Drop for Receiver<P> {
+    fn drop(&mut self) {
+        if let Ok(mut queues) = self.shared.queues.lock() {
+            queues.receiver_dropped = true;
+            queues.high.clear();
+            queues.bulk.clear();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[derive(Debug, PartialEq)]
+    struct TestPayload {
+        id: u8,
+        priority: Priority,
+    }
+
+    impl Payload for TestPayload {
+        fn priority(&self) -> Priority {
+            self.priority
+        }
+    }
+
+    fn payload(id: u8, priority: Priority) -> Message<TestPayload> {
+        Message::Payload(TestPayload { id, priority })
+    }
+
+    fn id(msg: Message<TestPayload>) -> u8 {
+        match msg {
+            Message::Payload(payload) => payload.id,
+            other => panic!("expected payload, got {:?}", other),
+        }
+    }
+
+    #[tokio::test]
+    async fn should_send_high_priority_messages_first() {
+        let (sender, mut receiver) = channel(0, OverflowPolicy::DropNewest);
+        sender.send(payload(0, Priority::Bulk)).unwrap();
+        sender.send(payload(1, Priority::Bulk)).unwrap();
+        sender.send(payload(2, Priority::High)).unwrap();
+        sender.send(payload(3, Priority::High)).unwrap();
+        drop(sender);
+
+        let mut ids = Vec::new();
+        while let Some(msg) = receiver.recv().await {
+            ids.push(id(msg));
+        }
+        assert_eq!(ids, vec![2, 3, 0, 1]);
+    }
+
+    #[tokio::test]
+    async fn should_reject_messages_once_receiver_is_dropped() {
+        let (sender, receiver) = channel(0, OverflowPolicy::DropNewest);
+        drop(receiver);
+        match sender.send(payload(0, Priority::High)) {
+            Err(SendError::Closed(msg)) => assert_eq!(id(msg), 0),
+            other => panic!("should reject message, got {:?}", other),
+        }
+    }
+
+    /// Fills a queue of capacity 2 with a bulk and a high-priority message, then sends another
+    /// message of the given priority.
+    fn overflow(
+        policy: OverflowPolicy,
+        priority: Priority,
+    ) -> (Result<(), SendError<TestPayload>>, Receiver<TestPayload>) {
+        let (sender, receiver) = channel(2, policy);
+        sender.send(payload(0, Priority::Bulk)).unwrap();
+        sender.send(payload(1, Priority::High)).unwrap();
+        let result = sender.send(payload(2, priority));
+        assert_eq!(sender.overflow_count(), 1);
+        (result, receiver)
+    }
+
+    async fn received_ids(mut receiver: Receiver<TestPayload>) -> Vec<u8> {
+        let mut ids = Vec::new();
+        while let Some(msg) = receiver.recv().await {
+            ids.push(id(msg));
+        }
+        ids
+    }
+
+    #[tokio::test]
+    async fn should_drop_newest_message_when_full() {
+        let (result, receiver) = overflow(OverflowPolicy::DropNewest, Priority::High);
+        match result {
+            Err(SendError::Dropped(msg)) => assert_eq!(id(msg), 2),
+            other => panic!("should drop new message, got {:?}", other),
+        }
+        assert_eq!(received_ids(receiver).await, vec![1, 0]);
+    }
+
+    #[tokio::test]
+    async fn should_drop_oldest_message_when_full() {
+        // A high-priority message displaces the oldest bulk one.
+        let (result, receiver) = overflow(OverflowPolicy::DropOldest, Priority::High);
+        match result {
+            Err(SendError::Dropped(msg)) => assert_eq!(id(msg), 0),
+            other => panic!("should drop oldest message, got {:?}", other),
+        }
+        assert_eq!(received_ids(receiver).await, vec![1, 2]);
+
+        // A bulk message displaces another bulk one, but never a high-priority one.
+        let (result, receiver) = overflow(OverflowPolicy::DropOldest, Priority::Bulk);
+        match result {
+            Err(SendError::Dropped(msg)) => assert_eq!(id(msg), 0),
+            other => panic!("should drop oldest message, got {:?}", other),
+        }
+        assert_eq!(received_ids(receiver).await, vec![1, 2]);
+
+        let (sender, receiver) = channel(1, OverflowPolicy::DropOldest);
+        sender.send(payload(0, Priority::High)).unwrap();
+        match sender.send(payload(1, Priority::Bulk)) {
+            Err(SendError::Dropped(msg)) => assert_eq!(id(msg), 1),
+            other => panic!("should drop new message, got {:?}", other),
+        }
+        drop(sender);
+        assert_eq!(received_ids(receiver).await, vec![0]);
+    }
+
+    #[tokio::test]
+    async fn should_close_queue_when_full_if_disconnecting() {
+        let (result, receiver) = overflow(OverflowPolicy::Disconnect, Priority::High);
+        assert!(matches!(result, Err(SendError::Disconnected)));
+        assert!(receiver.has_overflowed());
+        assert!(received_ids(receiver).await.is_empty());
+    }
+}
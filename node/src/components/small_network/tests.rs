@@ -6,13 +6,21 @@
 use std::{
     collections::{HashMap, HashSet},
     fmt::{self, Debug, Display, Formatter},
+    io::{self, Read},
+    net::{Ipv4Addr, Ipv6Addr, SocketAddr, TcpStream},
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc,
+    },
     time::{Duration, Instant},
 };
 
+use bytes::Bytes;
 use derive_more::From;
+use futures::{channel::mpsc, SinkExt, StreamExt};
 use pnet::datalink;
-use prometheus::Registry;
-use serde::{Deserialize, Serialize};
+use prometheus::{Histogram, HistogramOpts, Registry};
+use serde::{Deserialize, Serialize, Serializer};
 use tracing::{debug, info};
 
 use crate::{
@@ -21,22 +29,30 @@ use crate::{
         storage::Storage,
         Component,
     },
+    crypto::asymmetric_key::{PublicKey, SecretKey},
     effect::{
-        announcements::{GossiperAnnouncement, NetworkAnnouncement},
+        announcements::{ControlAnnouncement, GossiperAnnouncement, NetworkAnnouncement},
         requests::{NetworkRequest, StorageRequest},
-        EffectBuilder, Effects,
+        EffectBuilder, EffectExt, Effects,
     },
     protocol,
     reactor::{self, EventQueueHandle, Finalize, Reactor, Runner},
-    small_network::{self, Config, GossipedAddress, NodeId, SmallNetwork},
+    small_network::{
+        self, Capabilities, Capability, Config, GossipedAddress, HandshakeAttestation, NodeId,
+        OverflowPolicy, Payload, Priority, SmallNetwork,
+    },
     testing::{
         self, init_logging,
         network::{Network, NetworkedReactor},
         ConditionCheckReactor, TestRng,
     },
-    utils::Source,
+    tls,
+    types::{ShutdownReason, Timestamp},
+    utils::{self, Source},
 };
 
+use super::{address_book::AddressBook, checksum, message_sender, send_queue};
+
 /// Test-reactor event.
 #[derive(Debug, From)]
 enum Event {
@@ -50,6 +66,8 @@ enum Event {
     NetworkAnnouncement(NetworkAnnouncement<NodeId, Message>),
     #[from]
     AddressGossiperAnnouncement(GossiperAnnouncement<GossipedAddress>),
+    #[from]
+    ControlAnnouncement(ControlAnnouncement),
 }
 
 impl From<NetworkRequest<NodeId, gossiper::Message<GossipedAddress>>> for Event {
@@ -80,6 +98,8 @@ impl Display for Event {
 enum Message {
     #[from]
     AddressGossiper(gossiper::Message<GossipedAddress>),
+    /// Arbitrary data, sent to fill up a connection.
+    Filler(Vec<u8>),
 }
 
 impl Display for Message {
@@ -88,6 +108,12 @@ impl Display for Message {
     }
 }
 
+impl Payload for Message {
+    fn priority(&self) -> Priority {
+        Priority::Bulk
+    }
+}
+
 /// Test reactor.
 ///
 /// Runs a single small network.
@@ -95,6 +121,12 @@ impl Display for Message {
 struct TestReactor {
     net: SmallNetwork<Event, Message>,
     address_gossiper: Gossiper<GossipedAddress, Event>,
+    /// The announced connections, with their handshake duration and whether they were inbound.
+    connected: Vec<(NodeId, Duration, bool)>,
+    /// The announced disconnections, with their reason.
+    disconnected: Vec<(NodeId, String)>,
+    /// The received filler messages, with their sender.
+    received_fillers: Vec<(NodeId, Vec<u8>)>,
 }
 
 impl Reactor<TestRng> for TestReactor {
@@ -104,18 +136,22 @@ impl Reactor<TestRng> for TestReactor {
 
     fn new(
         cfg: Self::Config,
-        _registry: &Registry,
+        registry: &Registry,
         event_queue: EventQueueHandle<Self::Event>,
         _rng: &mut TestRng,
     ) -> anyhow::Result<(Self, Effects<Self::Event>)> {
-        let (net, effects) = SmallNetwork::new(event_queue, cfg)?;
+        let (net, effects) = SmallNetwork::new(event_queue, cfg, registry)?;
         let gossiper_config = gossiper::Config::default();
-        let address_gossiper = Gossiper::new_for_complete_items(gossiper_config);
+        let address_gossiper =
+            Gossiper::new_for_complete_items(gossiper_config, net.latency_store());
 
         Ok((
             TestReactor {
                 net,
                 address_gossiper,
+                connected: Vec::new(),
+                disconnected: Vec::new(),
+                received_fillers: Vec::new(),
             },
             reactor::wrap_effects(Event::SmallNet, effects),
         ))
@@ -150,6 +186,10 @@ impl Reactor<TestRng> for TestReactor {
                     Message::AddressGossiper(message) => {
                         Event::AddressGossiper(gossiper::Event::MessageReceived { sender, message })
                     }
+                    Message::Filler(data) => {
+                        self.received_fillers.push((sender, data));
+                        return Effects::new();
+                    }
                 };
                 self.dispatch_event(effect_builder, rng, reactor_event)
             }
@@ -160,18 +200,43 @@ impl Reactor<TestRng> for TestReactor {
                 };
                 self.dispatch_event(effect_builder, rng, Event::AddressGossiper(event))
             }
+            Event::NetworkAnnouncement(NetworkAnnouncement::PeerConnected {
+                peer,
+                handshake_duration,
+                inbound,
+            }) => {
+                self.connected.push((peer, handshake_duration, inbound));
+                Effects::new()
+            }
+            Event::NetworkAnnouncement(NetworkAnnouncement::PeerDisconnected { peer, reason }) => {
+                self.disconnected.push((peer, reason));
+                Effects::new()
+            }
             Event::NetworkAnnouncement(NetworkAnnouncement::NewPeer(_)) => {
                 // We do not care about the announcement of new peers in this test.
                 Effects::new()
             }
-            Event::AddressGossiperAnnouncement(ann) => {
-                let GossiperAnnouncement::NewCompleteItem(gossiped_address) = ann;
+            Event::AddressGossiperAnnouncement(GossiperAnnouncement::NewCompleteItem(
+                gossiped_address,
+            )) => {
                 let reactor_event =
                     Event::SmallNet(small_network::Event::PeerAddressReceived(gossiped_address));
                 self.dispatch_event(effect_builder, rng, reactor_event)
             }
+            Event::AddressGossiperAnnouncement(GossiperAnnouncement::FinishedGossiping(_)) => {
+                Effects::new()
+            }
+            Event::ControlAnnouncement(ControlAnnouncement::FatalError(reason)) => {
+                // Nodes isolated on purpose by a test request a shutdown, which the test ignores.
+                debug!(%reason, "ignoring shutdown request");
+                Effects::new()
+            }
         }
     }
+
+    fn prepare_shutdown(&mut self, reason: &ShutdownReason) {
+        self.net.say_goodbye(reason);
+    }
 }
 
 impl NetworkedReactor for TestReactor {
@@ -317,6 +382,78 @@ async fn bind_to_real_network_interface() {
     net.finalize().await;
 }
 
+/// Check that IPv6 and dual-stack addresses are parsed, and normalized so that different forms of
+/// the same address are recognized as such.
+#[test]
+fn should_parse_and_normalize_ipv6_addresses() {
+    let ipv6: SocketAddr = (Ipv6Addr::LOCALHOST, 34553).into();
+    let ipv4: SocketAddr = (Ipv4Addr::LOCALHOST, 34553).into();
+    assert_eq!(utils::resolve_address("[::1]:34553").unwrap(), ipv6);
+    assert_eq!(
+        utils::resolve_address("[0:0:0:0:0:0:0:1]:34553").unwrap(),
+        ipv6
+    );
+    assert_eq!(
+        utils::resolve_address("[::ffff:127.0.0.1]:34553").unwrap(),
+        ipv4
+    );
+    assert_eq!(utils::resolve_ip("[::1]").unwrap(), Ipv6Addr::LOCALHOST);
+
+    // An unbracketed IPv6 address is refused rather than having its last group taken as the port.
+    let error = utils::resolve_address("::1:3455").expect_err("should require brackets");
+    assert!(error.to_string().contains("bracketed"));
+
+    // The address book treats an IPv4-mapped address as the plain IPv4 one.
+    let mut address_book = AddressBook::default();
+    assert!(address_book.record(
+        "[::ffff:127.0.0.1]:34553".parse().unwrap(),
+        Timestamp::zero()
+    ));
+    assert!(!address_book.record(ipv4, Timestamp::zero()));
+    assert!(address_book.record(ipv6, Timestamp::zero()));
+    assert_eq!(
+        address_book.addresses().collect::<Vec<_>>(),
+        vec![ipv4, ipv6]
+    );
+}
+
+/// Check that nodes listening on IPv6 addresses connect, and learn of each other through gossip.
+#[tokio::test]
+async fn should_connect_and_gossip_over_ipv6() {
+    init_logging();
+
+    let mut rng = TestRng::new();
+    let mut net = Network::new();
+    let first_node_port = testing::unused_port_on_localhost();
+    net.add_node_with_config(
+        Config::new((Ipv6Addr::LOCALHOST, first_node_port).into()),
+        &mut rng,
+    )
+    .await
+    .unwrap();
+
+    // The later nodes only know the first one, so they can only connect to each other using the
+    // gossiped IPv6 addresses.
+    for _ in 0..2 {
+        let mut config = Config::new((Ipv6Addr::LOCALHOST, 0).into());
+        config.known_addresses = vec![format!("[::1]:{}", first_node_port)];
+        net.add_node_with_config(config, &mut rng).await.unwrap();
+    }
+
+    let timeout = Duration::from_secs(3);
+    net.settle_on(&mut rng, network_is_complete, timeout).await;
+
+    for runner in net.nodes().values() {
+        let peers = runner.reactor().inner().net.peers();
+        assert_eq!(peers.len(), 2);
+        assert!(peers
+            .values()
+            .all(|address| address.ip() == Ipv6Addr::LOCALHOST));
+    }
+
+    net.finalize().await;
+}
+
 /// Check that a network of varying sizes will connect all nodes properly.
 #[tokio::test]
 async fn check_varying_size_network_connects() {
@@ -359,3 +496,953 @@ async fn check_varying_size_network_connects() {
         net.finalize().await;
     }
 }
+
+/// Check that inbound connections beyond the configured maximum are refused.
+#[tokio::test]
+async fn should_refuse_inbound_connections_over_limit() {
+    init_logging();
+
+    let mut rng = TestRng::new();
+
+    const MAX_INBOUND_CONNECTIONS: usize = 2;
+
+    let mut net = Network::new();
+    let first_node_port = testing::unused_port_on_localhost();
+
+    let mut first_node_config = Config::default_local_net_first_node(first_node_port);
+    first_node_config.max_inbound_connections = MAX_INBOUND_CONNECTIONS;
+    net.add_node_with_config(first_node_config, &mut rng)
+        .await
+        .unwrap();
+
+    // Exactly `MAX_INBOUND_CONNECTIONS` peers connect to the first node.
+    for _ in 0..MAX_INBOUND_CONNECTIONS {
+        net.add_node_with_config(Config::default_local_net(first_node_port), &mut rng)
+            .await
+            .unwrap();
+    }
+
+    let timeout = Duration::from_secs(3);
+    net.settle_on(&mut rng, network_is_complete, timeout).await;
+
+    // One more inbound connection should be closed by the first node without a handshake.
+    let mut stream = TcpStream::connect((Ipv4Addr::LOCALHOST, first_node_port))
+        .expect("should connect to first node");
+    stream
+        .set_read_timeout(Some(Duration::from_secs(1)))
+        .expect("should set read timeout");
+
+    net.settle(&mut rng, Duration::from_millis(25), timeout)
+        .await;
+
+    let mut buf = [0u8; 1];
+    match stream.read(&mut buf) {
+        // The connection was closed, or reset, by the first node.
+        Ok(0) => (),
+        Err(err) if err.kind() == io::ErrorKind::ConnectionReset => (),
+        other => panic!("expected refused connection, got {:?}", other),
+    }
+
+    // The existing connections must not have been affected.
+    assert!(
+        network_is_complete(net.nodes()),
+        "network did not stay connected"
+    );
+
+    net.finalize().await;
+}
+
+/// Check that inbound connections from an IP address beyond the configured maximum are refused.
+#[tokio::test]
+async fn should_refuse_inbound_connections_over_per_ip_limit() {
+    init_logging();
+
+    let mut rng = TestRng::new();
+
+    let mut net = Network::new();
+    let first_node_port = testing::unused_port_on_localhost();
+
+    // Plenty of inbound connection slots, but only one per IP address.
+    let mut first_node_config = Config::default_local_net_first_node(first_node_port);
+    first_node_config.max_inbound_connections_per_ip = 1;
+    net.add_node_with_config(first_node_config, &mut rng)
+        .await
+        .unwrap();
+    net.add_node_with_config(Config::default_local_net(first_node_port), &mut rng)
+        .await
+        .unwrap();
+
+    let timeout = Duration::from_secs(3);
+    net.settle_on(&mut rng, network_is_complete, timeout).await;
+
+    // A second connection from localhost should be closed by the first node without a handshake.
+    let mut stream = TcpStream::connect((Ipv4Addr::LOCALHOST, first_node_port))
+        .expect("should connect to first node");
+    stream
+        .set_read_timeout(Some(Duration::from_secs(1)))
+        .expect("should set read timeout");
+
+    net.settle(&mut rng, Duration::from_millis(25), timeout)
+        .await;
+
+    let mut buf = [0u8; 1];
+    match stream.read(&mut buf) {
+        // The connection was closed, or reset, by the first node.
+        Ok(0) => (),
+        Err(err) if err.kind() == io::ErrorKind::ConnectionReset => (),
+        other => panic!("expected refused connection, got {:?}", other),
+    }
+
+    // The existing connection must not have been affected.
+    assert!(
+        network_is_complete(net.nodes()),
+        "network did not stay connected"
+    );
+
+    net.finalize().await;
+}
+
+/// Check that a restarted node dials the peers from its persisted address book, even without any
+/// known addresses.
+#[tokio::test]
+async fn should_dial_peers_from_persisted_address_book() {
+    init_logging();
+
+    let mut rng = TestRng::new();
+    let temp_dir = tempfile::tempdir().expect("should get tempdir");
+    let address_book_path = temp_dir.path().join("address_book.json");
+
+    let mut net = Network::new();
+    let first_node_port = testing::unused_port_on_localhost();
+    net.add_node_with_config(
+        Config::default_local_net_first_node(first_node_port),
+        &mut rng,
+    )
+    .await
+    .unwrap();
+
+    // A node bootstrapping from the first node records it in its address book.
+    let mut config = Config::default_local_net(first_node_port);
+    config.address_book_path = Some(address_book_path.clone());
+    let (restarting_node, _) = net.add_node_with_config(config, &mut rng).await.unwrap();
+
+    let timeout = Duration::from_secs(2);
+    net.settle_on(&mut rng, network_is_complete, timeout).await;
+
+    let address_book = AddressBook::load(&address_book_path).expect("should load address book");
+    assert_eq!(
+        address_book.addresses().collect::<Vec<_>>(),
+        vec![(Ipv4Addr::LOCALHOST, first_node_port).into()]
+    );
+
+    // Shut the node down and restart it without any known addresses.
+    net.remove_node(&restarting_node)
+        .expect("should remove node")
+        .into_inner()
+        .finalize()
+        .await;
+    let mut config = Config::default_local_net_first_node(testing::unused_port_on_localhost());
+    config.address_book_path = Some(address_book_path);
+    net.add_node_with_config(config, &mut rng).await.unwrap();
+
+    // The restarted node can only have connected by dialing the first node from its address book.
+    net.settle_on(&mut rng, network_is_complete, timeout).await;
+
+    net.finalize().await;
+}
+
+/// Check that connections from validators are accepted even if the inbound limit is reached, and
+/// don't take up any of the connections available to ordinary peers.
+#[tokio::test]
+async fn should_prioritize_validator_connections() {
+    init_logging();
+
+    let mut rng = TestRng::new();
+
+    let mut net = Network::<TestReactor>::new();
+    let first_node_port = testing::unused_port_on_localhost();
+
+    // The first node accepts only a single inbound connection from an ordinary peer.
+    let secret_key = SecretKey::random(&mut rng);
+    let public_key = PublicKey::from(&secret_key);
+    let mut first_node_config = Config::default_local_net_first_node(first_node_port);
+    first_node_config.max_inbound_connections = 1;
+    let (first_node_id, first_node) = net
+        .add_node_with_config(first_node_config, &mut rng)
+        .await
+        .unwrap();
+    first_node
+        .reactor_mut()
+        .inner_mut()
+        .net
+        .set_validators(vec![public_key].into_iter().collect());
+
+    let (validator_id, validator) = net
+        .add_node_with_config(Config::default_local_net(first_node_port), &mut rng)
+        .await
+        .unwrap();
+    let attestation = HandshakeAttestation::new(validator_id, &secret_key, &public_key, &mut rng);
+    validator
+        .reactor_mut()
+        .inner_mut()
+        .net
+        .set_attestation(attestation);
+
+    let timeout = Duration::from_secs(3);
+    let is_connected_to = |peer_id: NodeId| {
+        move |nodes: &HashMap<NodeId, Runner<ConditionCheckReactor<TestReactor>, TestRng>>| {
+            let first_node = &nodes[&first_node_id].reactor().inner().net;
+            first_node.incoming.contains_key(&peer_id) && first_node.outgoing.contains_key(&peer_id)
+        }
+    };
+    net.settle_on(&mut rng, is_connected_to(validator_id), timeout)
+        .await;
+    net.settle_on(
+        &mut rng,
+        |nodes| {
+            nodes[&first_node_id]
+                .reactor()
+                .inner()
+                .net
+                .is_validator(&validator_id)
+        },
+        timeout,
+    )
+    .await;
+
+    // The validator's connection doesn't count towards the limit, so an ordinary peer can connect.
+    let (peer_id, _) = net
+        .add_node_with_config(Config::default_local_net(first_node_port), &mut rng)
+        .await
+        .unwrap();
+    net.settle_on(&mut rng, is_connected_to(peer_id), timeout)
+        .await;
+
+    // Now that the limit is reached, a further ordinary peer is refused.
+    let (refused_peer_id, _) = net
+        .add_node_with_config(Config::default_local_net(first_node_port), &mut rng)
+        .await
+        .unwrap();
+    // The refused peer keeps retrying as it learns of the first node's address, so the network
+    // never becomes idle, but each attempt ends in a disconnection.
+    net.settle_on(
+        &mut rng,
+        |nodes| {
+            nodes[&refused_peer_id]
+                .reactor()
+                .inner()
+                .disconnected
+                .iter()
+                .any(|(peer, _)| *peer == first_node_id)
+        },
+        timeout,
+    )
+    .await;
+
+    let first_node = &net.nodes()[&first_node_id].reactor().inner().net;
+    assert!(first_node.incoming.contains_key(&validator_id));
+    assert!(first_node.incoming.contains_key(&peer_id));
+    assert!(!first_node.incoming.contains_key(&refused_peer_id));
+    assert!(!first_node.is_validator(&peer_id));
+
+    net.finalize().await;
+}
+
+/// Check that peers are forgotten once they disconnect, except for validators, which are remembered
+/// for as long as they remain validators.
+#[tokio::test]
+async fn should_only_remember_departed_validators() {
+    init_logging();
+
+    let mut rng = TestRng::new();
+
+    let mut net = Network::<TestReactor>::new();
+    let first_node_port = testing::unused_port_on_localhost();
+
+    let secret_key = SecretKey::random(&mut rng);
+    let public_key = PublicKey::from(&secret_key);
+    let (first_node_id, first_node) = net
+        .add_node_with_config(
+            Config::default_local_net_first_node(first_node_port),
+            &mut rng,
+        )
+        .await
+        .unwrap();
+    first_node
+        .reactor_mut()
+        .inner_mut()
+        .net
+        .set_validators(vec![public_key].into_iter().collect());
+
+    let (validator_id, validator) = net
+        .add_node_with_config(Config::default_local_net(first_node_port), &mut rng)
+        .await
+        .unwrap();
+    let attestation = HandshakeAttestation::new(validator_id, &secret_key, &public_key, &mut rng);
+    validator
+        .reactor_mut()
+        .inner_mut()
+        .net
+        .set_attestation(attestation);
+    let (peer_id, _) = net
+        .add_node_with_config(Config::default_local_net(first_node_port), &mut rng)
+        .await
+        .unwrap();
+
+    let timeout = Duration::from_secs(3);
+    net.settle_on(
+        &mut rng,
+        |nodes| {
+            let first_node = &nodes[&first_node_id].reactor().inner().net;
+            first_node.listening_addresses.len() == 2 && first_node.is_validator(&validator_id)
+        },
+        timeout,
+    )
+    .await;
+
+    for node_id in &[validator_id, peer_id] {
+        net.remove_node(node_id)
+            .expect("should remove node")
+            .into_inner()
+            .finalize()
+            .await;
+    }
+    net.settle_on(
+        &mut rng,
+        |nodes| {
+            let first_node = &nodes[&first_node_id].reactor().inner().net;
+            first_node.incoming.is_empty() && first_node.outgoing.is_empty()
+        },
+        timeout,
+    )
+    .await;
+
+    let first_node = &mut net
+        .nodes_mut()
+        .get_mut(&first_node_id)
+        .unwrap()
+        .reactor_mut()
+        .inner_mut()
+        .net;
+    assert!(first_node.attested_keys.is_empty());
+    assert!(first_node.listening_addresses.is_empty());
+    assert!(first_node.is_validator(&validator_id));
+    assert!(!first_node.is_validator(&peer_id));
+    let known = &first_node.known_validators[&public_key];
+    assert_eq!(known.peer_id, validator_id);
+    assert!(known.listening_address.is_some());
+
+    // Once no longer a validator, the departed validator is forgotten too.
+    first_node.set_validators(HashSet::new());
+    assert!(first_node.known_validators.is_empty());
+    assert!(!first_node.is_validator(&validator_id));
+
+    net.finalize().await;
+}
+
+/// Check that a message which fails to be sent during a brief disconnect is delivered once the
+/// connection is re-established.
+#[tokio::test]
+async fn should_retry_message_after_brief_disconnect() {
+    init_logging();
+
+    let mut rng = TestRng::new();
+
+    let mut net = Network::<TestReactor>::new();
+    let first_node_port = testing::unused_port_on_localhost();
+
+    // The peer is a validator, so the first node reconnects to it right away.
+    let secret_key = SecretKey::random(&mut rng);
+    let public_key = PublicKey::from(&secret_key);
+    let (first_node_id, first_node) = net
+        .add_node_with_config(
+            Config::default_local_net_first_node(first_node_port),
+            &mut rng,
+        )
+        .await
+        .unwrap();
+    first_node
+        .reactor_mut()
+        .inner_mut()
+        .net
+        .set_validators(vec![public_key].into_iter().collect());
+
+    let (peer_id, peer) = net
+        .add_node_with_config(Config::default_local_net(first_node_port), &mut rng)
+        .await
+        .unwrap();
+    let attestation = HandshakeAttestation::new(peer_id, &secret_key, &public_key, &mut rng);
+    peer.reactor_mut()
+        .inner_mut()
+        .net
+        .set_attestation(attestation);
+
+    let timeout = Duration::from_secs(3);
+    net.settle_on(
+        &mut rng,
+        |nodes| {
+            let first_node = &nodes[&first_node_id].reactor().inner().net;
+            first_node.outgoing.contains_key(&peer_id) && first_node.is_validator(&peer_id)
+        },
+        timeout,
+    )
+    .await;
+
+    // Drop the outgoing connection, and send a message before it is re-established.
+    let _ = net
+        .nodes_mut()
+        .get_mut(&first_node_id)
+        .unwrap()
+        .reactor_mut()
+        .inner_mut()
+        .net
+        .outgoing
+        .remove(&peer_id);
+    let filler = vec![1, 2, 3];
+    let msg = Message::Filler(filler.clone());
+    net.process_injected_effect_on(&first_node_id, |effect_builder| {
+        effect_builder.send_message(peer_id, msg).ignore()
+    })
+    .await;
+
+    net.settle_on(
+        &mut rng,
+        |nodes| {
+            !nodes[&peer_id]
+                .reactor()
+                .inner()
+                .received_fillers
+                .is_empty()
+        },
+        Duration::from_secs(5),
+    )
+    .await;
+    net.settle(&mut rng, Duration::from_millis(25), timeout)
+        .await;
+    assert_eq!(
+        net.nodes()[&peer_id].reactor().inner().received_fillers,
+        vec![(first_node_id, filler)]
+    );
+
+    net.finalize().await;
+}
+
+/// Check that a broadcast to validators reaches the validator peers only.
+#[tokio::test]
+async fn should_broadcast_to_validators_only() {
+    init_logging();
+
+    let mut rng = TestRng::new();
+
+    let mut net = Network::<TestReactor>::new();
+    let first_node_port = testing::unused_port_on_localhost();
+
+    let secret_key = SecretKey::random(&mut rng);
+    let public_key = PublicKey::from(&secret_key);
+    let (first_node_id, first_node) = net
+        .add_node_with_config(
+            Config::default_local_net_first_node(first_node_port),
+            &mut rng,
+        )
+        .await
+        .unwrap();
+    first_node
+        .reactor_mut()
+        .inner_mut()
+        .net
+        .set_validators(vec![public_key].into_iter().collect());
+
+    let (validator_id, validator) = net
+        .add_node_with_config(Config::default_local_net(first_node_port), &mut rng)
+        .await
+        .unwrap();
+    let attestation = HandshakeAttestation::new(validator_id, &secret_key, &public_key, &mut rng);
+    validator
+        .reactor_mut()
+        .inner_mut()
+        .net
+        .set_attestation(attestation);
+    let (peer_id, _) = net
+        .add_node_with_config(Config::default_local_net(first_node_port), &mut rng)
+        .await
+        .unwrap();
+
+    let timeout = Duration::from_secs(3);
+    net.settle_on(
+        &mut rng,
+        |nodes| {
+            let first_node = &nodes[&first_node_id].reactor().inner().net;
+            first_node.outgoing.contains_key(&validator_id)
+                && first_node.outgoing.contains_key(&peer_id)
+                && first_node.is_validator(&validator_id)
+        },
+        timeout,
+    )
+    .await;
+
+    let filler = vec![1, 2, 3];
+    let msg = Message::Filler(filler.clone());
+    net.process_injected_effect_on(&first_node_id, |effect_builder| {
+        effect_builder.broadcast_to_validators(msg).ignore()
+    })
+    .await;
+
+    net.settle_on(
+        &mut rng,
+        |nodes| {
+            !nodes[&validator_id]
+                .reactor()
+                .inner()
+                .received_fillers
+                .is_empty()
+        },
+        timeout,
+    )
+    .await;
+    net.settle(&mut rng, Duration::from_millis(25), timeout)
+        .await;
+    assert_eq!(
+        net.nodes()[&validator_id]
+            .reactor()
+            .inner()
+            .received_fillers,
+        vec![(first_node_id, filler)]
+    );
+    assert!(net.nodes()[&peer_id]
+        .reactor()
+        .inner()
+        .received_fillers
+        .is_empty());
+
+    net.finalize().await;
+}
+
+/// Check that connections and disconnections are announced, with the handshake duration and the
+/// reason respectively.
+#[tokio::test]
+async fn should_announce_connections_and_disconnections() {
+    init_logging();
+
+    let mut rng = TestRng::new();
+
+    let mut net = Network::new();
+    let first_node_port = testing::unused_port_on_localhost();
+    let (first_node_id, _) = net
+        .add_node_with_config(
+            Config::default_local_net_first_node(first_node_port),
+            &mut rng,
+        )
+        .await
+        .unwrap();
+    let (peer_id, _) = net
+        .add_node_with_config(Config::default_local_net(first_node_port), &mut rng)
+        .await
+        .unwrap();
+
+    let timeout = Duration::from_secs(2);
+    net.settle_on(&mut rng, network_is_complete, timeout).await;
+    // The announcements are only handled after the connections have been established.
+    net.settle_on(
+        &mut rng,
+        |nodes| {
+            nodes[&first_node_id]
+                .reactor()
+                .inner()
+                .connected
+                .iter()
+                .filter(|(peer, _, _)| *peer == peer_id)
+                .count()
+                >= 2
+        },
+        timeout,
+    )
+    .await;
+
+    // Both the inbound and the outbound connection to the peer are announced.
+    let connected = &net.nodes()[&first_node_id].reactor().inner().connected;
+    for &expected_inbound in &[true, false] {
+        let (_, handshake_duration, _) = connected
+            .iter()
+            .find(|(peer, _, inbound)| *peer == peer_id && *inbound == expected_inbound)
+            .expect("connection should have been announced");
+        assert!(*handshake_duration > Duration::from_secs(0));
+        assert!(*handshake_duration < timeout);
+    }
+    assert!(net.nodes()[&first_node_id]
+        .reactor()
+        .inner()
+        .disconnected
+        .is_empty());
+    let metrics = &net.nodes()[&first_node_id].reactor().inner().net.metrics;
+    assert_eq!(
+        metrics.connections_established.get(),
+        connected.len() as i64
+    );
+    assert_eq!(
+        metrics.handshake_duration.get_sample_count(),
+        connected.len() as u64
+    );
+
+    // Shutting the peer down closes its connection to the first node.
+    net.remove_node(&peer_id)
+        .expect("should remove node")
+        .into_inner()
+        .finalize()
+        .await;
+    net.settle_on(
+        &mut rng,
+        |nodes| {
+            !nodes[&first_node_id]
+                .reactor()
+                .inner()
+                .disconnected
+                .is_empty()
+        },
+        timeout,
+    )
+    .await;
+
+    let disconnected = &net.nodes()[&first_node_id].reactor().inner().disconnected;
+    assert!(disconnected
+        .iter()
+        .all(|(peer, reason)| *peer == peer_id && reason.contains("connection")));
+    let metrics = &net.nodes()[&first_node_id].reactor().inner().net.metrics;
+    assert_eq!(metrics.peer_disconnections.get(), disconnected.len() as i64);
+
+    net.finalize().await;
+}
+
+/// Check that an outgoing connection to a peer which stops reading is closed once sending a message
+/// takes longer than the write timeout.
+#[tokio::test]
+async fn should_disconnect_peer_stalling_writes() {
+    init_logging();
+
+    const SOCKET_BUFFER_SIZE: usize = 4 * 1024;
+    const FILLER_MESSAGES: usize = 8;
+    const FILLER_SIZE: usize = 64 * 1024;
+
+    let mut rng = TestRng::new();
+
+    // The stalling peer completes the TLS handshake, but never reads from the connection.
+    let (cert, secret_key) = tls::generate_node_cert().expect("should generate certificate");
+    let stalling_peer_id = tls::validate_cert(cert.clone())
+        .expect("should validate certificate")
+        .public_key_fingerprint();
+    let mut listener = tokio::net::TcpListener::bind((Ipv4Addr::LOCALHOST, 0))
+        .await
+        .expect("should bind listener");
+    let stalling_peer_port = listener.local_addr().expect("should have address").port();
+    let stalling_peer = tokio::spawn(async move {
+        let (stream, _) = listener.accept().await.expect("should accept connection");
+        stream
+            .set_recv_buffer_size(SOCKET_BUFFER_SIZE)
+            .expect("should set receive buffer size");
+        let acceptor =
+            tls::create_tls_acceptor(&cert, &secret_key).expect("should create acceptor");
+        tokio_openssl::accept(&acceptor, stream)
+            .await
+            .expect("should complete handshake")
+    });
+
+    let mut net = Network::<TestReactor>::new();
+    let mut config = Config::default_local_net(stalling_peer_port);
+    config.write_timeout = Duration::from_millis(500);
+    config.send_buffer_size = SOCKET_BUFFER_SIZE;
+    let (node_id, _) = net.add_node_with_config(config, &mut rng).await.unwrap();
+
+    let timeout = Duration::from_secs(5);
+    net.settle_on(
+        &mut rng,
+        |nodes| {
+            nodes[&node_id]
+                .reactor()
+                .inner()
+                .connected
+                .iter()
+                .any(|(peer, _, _)| *peer == stalling_peer_id)
+        },
+        timeout,
+    )
+    .await;
+    // Hold on to the connection, so that it stays open without being read from.
+    let _stalled_stream = stalling_peer.await.expect("stalling peer should not panic");
+
+    // Queue far more data than the small socket buffers can take.
+    for _ in 0..FILLER_MESSAGES {
+        net.process_injected_effect_on(&node_id, |effect_builder| {
+            effect_builder
+                .send_message(stalling_peer_id, Message::Filler(vec![0; FILLER_SIZE]))
+                .ignore()
+        })
+        .await;
+    }
+
+    net.settle_on(
+        &mut rng,
+        |nodes| !nodes[&node_id].reactor().inner().disconnected.is_empty(),
+        timeout,
+    )
+    .await;
+
+    let disconnected = &net.nodes()[&node_id].reactor().inner().disconnected;
+    assert_eq!(
+        disconnected,
+        &vec![(
+            stalling_peer_id,
+            "outgoing connection timed out".to_string()
+        )]
+    );
+
+    net.finalize().await;
+}
+
+/// Check that a peer shutting down tells the first node why, for every kind of reason.
+#[tokio::test]
+async fn should_report_shutdown_reason_to_peers() {
+    init_logging();
+
+    let mut rng = TestRng::new();
+    let reasons = vec![
+        ShutdownReason::OperatorSignal,
+        ShutdownReason::ConsensusFailure("no progress".to_string()),
+        ShutdownReason::StorageFailure("disk full".to_string()),
+        ShutdownReason::FatalError("invariant violated".to_string()),
+    ];
+
+    for reason in reasons {
+        let mut net = Network::new();
+        let first_node_port = testing::unused_port_on_localhost();
+        let (first_node_id, _) = net
+            .add_node_with_config(
+                Config::default_local_net_first_node(first_node_port),
+                &mut rng,
+            )
+            .await
+            .unwrap();
+        let (peer_id, _) = net
+            .add_node_with_config(Config::default_local_net(first_node_port), &mut rng)
+            .await
+            .unwrap();
+
+        let timeout = Duration::from_secs(2);
+        net.settle_on(&mut rng, network_is_complete, timeout).await;
+        // Goodbyes are only sent to peers whose hello has been received.
+        net.settle_on(
+            &mut rng,
+            |nodes| {
+                nodes[&peer_id]
+                    .reactor()
+                    .inner()
+                    .net
+                    .peer_capabilities
+                    .contains_key(&first_node_id)
+            },
+            timeout,
+        )
+        .await;
+
+        // The peer's connections stay open while it drains, so draining is expected to time out.
+        let mut peer = net.remove_node(&peer_id).expect("should remove node");
+        let _ = peer
+            .shutdown(&mut rng, reason.clone(), Duration::from_millis(100))
+            .await;
+        net.settle_on(
+            &mut rng,
+            |nodes| {
+                nodes[&first_node_id]
+                    .reactor()
+                    .inner()
+                    .net
+                    .peer_shutdown_reason(&peer_id)
+                    == Some(&reason)
+            },
+            timeout,
+        )
+        .await;
+
+        // The reason is given when announcing the disconnection.
+        peer.into_inner().finalize().await;
+        net.settle_on(
+            &mut rng,
+            |nodes| {
+                !nodes[&first_node_id]
+                    .reactor()
+                    .inner()
+                    .disconnected
+                    .is_empty()
+            },
+            timeout,
+        )
+        .await;
+        let expected = format!("peer shutting down: {}", reason);
+        let disconnected = &net.nodes()[&first_node_id].reactor().inner().disconnected;
+        assert!(disconnected
+            .iter()
+            .all(|(peer, reason)| *peer == peer_id && reason.ends_with(&expected)));
+
+        net.finalize().await;
+    }
+}
+
+/// Check that two peers converge on the intersection of their capabilities, so that a feature
+/// supported by only one of them is not used.
+#[tokio::test]
+async fn should_converge_on_shared_capabilities() {
+    init_logging();
+
+    let mut rng = TestRng::new();
+
+    let mut net = Network::<TestReactor>::new();
+    let first_node_port = testing::unused_port_on_localhost();
+    let (first_node_id, _) = net
+        .add_node_with_config(
+            Config::default_local_net_first_node(first_node_port),
+            &mut rng,
+        )
+        .await
+        .unwrap();
+    let (peer_id, _) = net
+        .add_node_with_config(Config::default_local_net(first_node_port), &mut rng)
+        .await
+        .unwrap();
+
+    // The first node pretends to be an older node, unable to receive goodbyes.
+    let expected: Capabilities = vec![Capability::LatencyProbes].into_iter().collect();
+    net.nodes_mut()
+        .get_mut(&first_node_id)
+        .unwrap()
+        .reactor_mut()
+        .inner_mut()
+        .net
+        .capabilities = expected;
+
+    // Before any hello has been received, no optional feature may be used.
+    let first_node = &net.nodes()[&first_node_id].reactor().inner().net;
+    assert_eq!(
+        first_node.peer_capabilities(&peer_id),
+        Capabilities::default()
+    );
+
+    let timeout = Duration::from_secs(2);
+    net.settle_on(
+        &mut rng,
+        |nodes| {
+            nodes[&first_node_id]
+                .reactor()
+                .inner()
+                .net
+                .peer_capabilities
+                .contains_key(&peer_id)
+                && nodes[&peer_id]
+                    .reactor()
+                    .inner()
+                    .net
+                    .peer_capabilities
+                    .contains_key(&first_node_id)
+        },
+        timeout,
+    )
+    .await;
+
+    let first_node = &net.nodes()[&first_node_id].reactor().inner().net;
+    let peer = &net.nodes()[&peer_id].reactor().inner().net;
+    assert_eq!(first_node.peer_capabilities(&peer_id), expected);
+    assert_eq!(peer.peer_capabilities(&first_node_id), expected);
+
+    // The peer shutting down doesn't say goodbye to the first node.
+    let mut peer = net.remove_node(&peer_id).expect("should remove node");
+    let _ = peer
+        .shutdown(
+            &mut rng,
+            ShutdownReason::OperatorSignal,
+            Duration::from_millis(100),
+        )
+        .await;
+    peer.into_inner().finalize().await;
+    net.settle_on(
+        &mut rng,
+        |nodes| {
+            !nodes[&first_node_id]
+                .reactor()
+                .inner()
+                .disconnected
+                .is_empty()
+        },
+        timeout,
+    )
+    .await;
+    let first_node = net.nodes()[&first_node_id].reactor().inner();
+    assert!(first_node.net.peer_shutdown_reason(&peer_id).is_none());
+    assert!(first_node
+        .disconnected
+        .iter()
+        .all(|(_, reason)| !reason.contains("peer shutting down")));
+    // Nor is the peer's capability remembered once it is gone.
+    assert_eq!(
+        first_node.net.peer_capabilities(&peer_id),
+        Capabilities::default()
+    );
+
+    net.finalize().await;
+}
+
+/// A payload counting how often it is encoded.
+#[derive(Debug)]
+struct CountingPayload {
+    value: u64,
+    encode_count: Arc<AtomicUsize>,
+}
+
+impl Serialize for CountingPayload {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let _ = self.encode_count.fetch_add(1, Ordering::SeqCst);
+        self.value.serialize(serializer)
+    }
+}
+
+impl Payload for CountingPayload {
+    fn priority(&self) -> Priority {
+        Priority::Bulk
+    }
+}
+
+#[tokio::test]
+async fn should_encode_sent_message_once() {
+    init_logging();
+
+    let encode_count = Arc::new(AtomicUsize::new(0));
+    let (sender, receiver) = send_queue::channel(0, OverflowPolicy::Disconnect);
+    sender
+        .send(small_network::Message::Payload(CountingPayload {
+            value: 42,
+            encode_count: Arc::clone(&encode_count),
+        }))
+        .expect("should queue message");
+    drop(sender);
+
+    let (sink, frames) = mpsc::unbounded::<Bytes>();
+    let sink = sink.sink_map_err(|_| io::Error::from(io::ErrorKind::BrokenPipe));
+    let message_size = Histogram::with_opts(HistogramOpts::new("message_size", "message size"))
+        .expect("should create histogram");
+    message_sender(
+        receiver,
+        sink,
+        1024,
+        Duration::from_secs(5),
+        message_size.clone(),
+    )
+    .await
+    .expect("should send message");
+
+    // The message was encoded once, for the size check, the metrics and the write alike.
+    assert_eq!(encode_count.load(Ordering::SeqCst), 1);
+    let frames: Vec<Bytes> = frames.collect().await;
+    assert_eq!(frames.len(), 1);
+    let payload = checksum::open(&frames[0]).expect("should have valid checksum");
+    let decoded: small_network::Message<u64> =
+        rmp_serde::from_read_ref(payload).expect("should decode message");
+    assert!(matches!(decoded, small_network::Message::Payload(42)));
+    assert_eq!(message_size.get_sample_count(), 1);
+    assert_eq!(message_size.get_sample_sum() as usize, frames[0].len());
+}
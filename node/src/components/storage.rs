@@ -1,19 +1,28 @@
 mod chainspec_store;
+mod coalescer;
+mod compression;
 mod config;
+mod durability;
 mod error;
 mod event;
 mod in_mem_chainspec_store;
 mod in_mem_store;
+mod integrity;
 mod lmdb_chainspec_store;
 mod lmdb_store;
+mod schema;
 mod store;
 
 use std::{
-    collections::HashMap,
+    collections::{BTreeMap, HashMap},
     fmt::{Debug, Display},
     fs,
     hash::Hash,
-    sync::Arc,
+    result::Result as StdResult,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc,
+    },
 };
 
 use futures::TryFutureExt;
@@ -21,29 +30,38 @@ use rand::{CryptoRng, Rng};
 use semver::Version;
 use serde::{de::DeserializeOwned, Deserialize, Serialize};
 use smallvec::smallvec;
-use tokio::task;
-use tracing::{debug, error, warn};
+use tracing::{debug, error, info, warn};
+
+use casper_execution_engine::shared::motes::Motes;
 
 use crate::{
-    components::{chainspec_loader::Chainspec, small_network::NodeId, Component},
-    crypto::asymmetric_key::Signature,
+    components::{
+        chainspec_loader::Chainspec, consensus::EraId, small_network::NodeId, Component,
+        ComponentLifecycle,
+    },
+    crypto::asymmetric_key::{PublicKey, Signature},
     effect::{
+        announcements::{ControlAnnouncement, StorageAnnouncement},
         requests::{NetworkRequest, StorageRequest},
         EffectBuilder, EffectExt, Effects, Responder,
     },
     protocol::Message,
-    types::{json_compatibility::ExecutionResult, Block, Deploy, Item},
+    types::{json_compatibility::ExecutionResult, Block, Deploy, Item, ShutdownReason},
+    utils,
 };
 use chainspec_store::ChainspecStore;
+use coalescer::Coalescer;
+use compression::Compression;
 pub use config::Config;
-pub use error::Error;
 pub(crate) use error::Result;
+pub use error::{Error, StorageFailure};
 pub use event::Event;
 use in_mem_chainspec_store::InMemChainspecStore;
 use in_mem_store::InMemStore;
+pub use integrity::{IntegrityCheck, IntegrityReport};
 use lmdb_chainspec_store::LmdbChainspecStore;
 use lmdb_store::LmdbStore;
-use store::{DeployStore, Multiple, Store};
+use store::{BlockStore, DeployStore, Multiple, Store};
 
 pub(crate) type Storage = LmdbStorage<Block, Deploy>;
 
@@ -52,6 +70,7 @@ pub(crate) type DeployHashes<S> = Multiple<<<S as StorageType>::Deploy as Value>
 pub(crate) type DeployHeaderResults<S> =
     Multiple<Option<<<S as StorageType>::Deploy as Value>::Header>>;
 type DeployAndMetadata<D, B> = (D, DeployMetadata<B>);
+type DeployReads<D> = Coalescer<Multiple<<D as Value>::Id>, Multiple<Option<D>>>;
 
 const BLOCK_STORE_FILENAME: &str = "block_store.db";
 const DEPLOY_STORE_FILENAME: &str = "deploy_store.db";
@@ -89,6 +108,8 @@ pub trait Value: Clone + Serialize + DeserializeOwned + Send + Sync + Debug + Di
     fn id(&self) -> &Self::Id;
     fn header(&self) -> &Self::Header;
     fn take_header(self) -> Self::Header;
+    /// Returns whether the value's contents match its ID, i.e. whether it is not corrupt.
+    fn is_intact(&self) -> bool;
 }
 
 /// Metadata associated with a block.
@@ -122,6 +143,66 @@ impl<B: Value> Default for DeployMetadata<B> {
     }
 }
 
+/// A summary of the state at the end of an era, stored atomically with the era's last block.
+#[derive(Clone, Serialize, Deserialize, Debug)]
+pub struct EraSummary<B: Value> {
+    /// The era.
+    pub era_id: EraId,
+    /// The weights of the era's validators.
+    pub validator_weights: BTreeMap<PublicKey, Motes>,
+    /// The total weight of the era's validators.
+    pub total_weight: Motes,
+    /// The hash of the era's last block.
+    pub last_block_hash: B::Id,
+}
+
+/// Records the outcome of a write, announcing when storage runs out of space and when space is
+/// available again.
+///
+/// `out_of_space` is shared by all writes, so each change is only announced once.
+async fn track_space<REv, T>(
+    effect_builder: EffectBuilder<REv>,
+    out_of_space: &AtomicBool,
+    result: &Result<T>,
+) where
+    REv: From<StorageAnnouncement>,
+{
+    match result {
+        Err(Error::OutOfSpace) => {
+            if !out_of_space.swap(true, Ordering::SeqCst) {
+                warn!("storage is out of space");
+                effect_builder
+                    .announce_storage_space(StorageAnnouncement::OutOfSpace)
+                    .await
+            }
+        }
+        Ok(_) => {
+            if out_of_space.swap(false, Ordering::SeqCst) {
+                info!("storage has space available again");
+                effect_builder
+                    .announce_storage_space(StorageAnnouncement::SpaceAvailable)
+                    .await
+            }
+        }
+        Err(_) => (),
+    }
+}
+
+/// Requests a shutdown, since storage failed for the given `reason`, and returns the failure to
+/// answer the request with.
+async fn shut_down_on_failure<REv>(
+    effect_builder: EffectBuilder<REv>,
+    reason: String,
+) -> StorageFailure
+where
+    REv: From<ControlAnnouncement>,
+{
+    effect_builder
+        .request_shutdown(ShutdownReason::StorageFailure(reason.clone()))
+        .await;
+    StorageFailure(reason)
+}
+
 /// Trait which will handle management of the various storage sub-components.
 ///
 /// If this trait is ultimately only used for testing scenarios, we shouldn't need to expose it to
@@ -130,15 +211,61 @@ pub trait StorageType {
     type Block: Value;
     type Deploy: Value + Item;
 
-    fn block_store(&self) -> Arc<dyn Store<Value = Self::Block>>;
+    fn block_store(&self) -> Arc<dyn BlockStore<Value = Self::Block>>;
     fn deploy_store(
         &self,
     ) -> Arc<dyn DeployStore<Block = Self::Block, Deploy = Self::Deploy, Value = Self::Deploy>>;
     fn chainspec_store(&self) -> Arc<dyn ChainspecStore>;
+    /// Coalesces identical concurrent reads of deploys.
+    fn deploy_reads(&self) -> Arc<Coalescer<DeployHashes<Self>, DeployResults<Self>>>;
+    /// Whether the last write failed since storage ran out of space.
+    fn out_of_space(&self) -> Arc<AtomicBool>;
+    /// Flushes all written data to disk.
+    fn flush(&self) -> Result<()>;
     fn new(config: &Config) -> Result<Self>
     where
         Self: Sized;
 
+    /// Checks that all stored blocks and deploys are intact, and that all deploys referenced by
+    /// stored blocks are stored as well.
+    ///
+    /// If `repair` is true, corrupt blocks and deploys are quarantined.  This reads the whole
+    /// store, so it should only be run on startup or in a blocking task.
+    fn verify_integrity(&self, repair: bool) -> Result<IntegrityReport<Block, Deploy>>
+    where
+        Self: StorageType<Block = Block, Deploy = Deploy> + Sized,
+    {
+        let corrupt_blocks = self.block_store().verify_integrity(repair)?;
+        let corrupt_deploys = self.deploy_store().verify_integrity(repair)?;
+
+        let block_store = self.block_store();
+        let deploy_store = self.deploy_store();
+        let mut missing_deploys = Vec::new();
+        for block_hash in block_store.ids()? {
+            let block = match block_store.get(smallvec![block_hash]).pop() {
+                Some(Ok(Some(block))) => block,
+                // Unless quarantined, corrupt blocks have been reported already.
+                _ => continue,
+            };
+            let deploy_hashes: DeployHashes<Self> = block.deploy_hashes().iter().copied().collect();
+            for (deploy_hash, result) in deploy_hashes
+                .iter()
+                .zip(deploy_store.get_headers(deploy_hashes.clone()))
+            {
+                if let Ok(None) = result {
+                    missing_deploys.push((block_hash, *deploy_hash));
+                }
+            }
+        }
+
+        Ok(IntegrityReport {
+            corrupt_blocks,
+            corrupt_deploys,
+            missing_deploys,
+            quarantined: repair,
+        })
+    }
+
     fn get_deploy_for_peer<REv>(
         &self,
         effect_builder: EffectBuilder<REv>,
@@ -152,7 +279,7 @@ pub trait StorageType {
         let deploy_store = self.deploy_store();
         let deploy_hashes = smallvec![deploy_hash];
         async move {
-            task::spawn_blocking(move || deploy_store.get(deploy_hashes))
+            utils::spawn_blocking(move || deploy_store.get(deploy_hashes))
                 .await
                 .expect("should run")
                 .pop()
@@ -172,17 +299,64 @@ pub trait StorageType {
         .ignore()
     }
 
-    fn put_block(&self, block: Box<Self::Block>, responder: Responder<bool>) -> Effects<Event<Self>>
+    fn put_block<REv>(
+        &self,
+        effect_builder: EffectBuilder<REv>,
+        block: Box<Self::Block>,
+        responder: Responder<StdResult<bool, StorageFailure>>,
+    ) -> Effects<Event<Self>>
     where
+        REv: From<StorageAnnouncement> + From<ControlAnnouncement> + Send,
         Self: Sized,
     {
         let block_store = self.block_store();
+        let out_of_space = self.out_of_space();
         let block_hash = *block.id();
         async move {
-            let result = task::spawn_blocking(move || block_store.put(*block))
+            let result = utils::spawn_blocking(move || block_store.put(*block))
                 .await
-                .expect("should run")
-                .unwrap_or_else(|error| panic!("failed to put {}: {}", block_hash, error));
+                .expect("should run");
+            track_space(effect_builder, &out_of_space, &result).await;
+            // Blocks can't be dropped, even if storage is out of space.
+            let result = match result {
+                Ok(is_new) => Ok(is_new),
+                Err(error) => {
+                    let reason = format!("failed to put {}: {}", block_hash, error);
+                    Err(shut_down_on_failure(effect_builder, reason).await)
+                }
+            };
+            responder.respond(result).await
+        }
+        .ignore()
+    }
+
+    fn put_switch_block<REv>(
+        &self,
+        effect_builder: EffectBuilder<REv>,
+        block: Box<Self::Block>,
+        era_summary: Box<EraSummary<Self::Block>>,
+        responder: Responder<StdResult<bool, StorageFailure>>,
+    ) -> Effects<Event<Self>>
+    where
+        REv: From<StorageAnnouncement> + From<ControlAnnouncement> + Send,
+        Self: Sized,
+    {
+        let block_store = self.block_store();
+        let out_of_space = self.out_of_space();
+        let block_hash = *block.id();
+        async move {
+            let result =
+                utils::spawn_blocking(move || block_store.put_switch_block(*block, *era_summary))
+                    .await
+                    .expect("should run");
+            track_space(effect_builder, &out_of_space, &result).await;
+            let result = match result {
+                Ok(is_new) => Ok(is_new),
+                Err(error) => {
+                    let reason = format!("failed to put {}: {}", block_hash, error);
+                    Err(shut_down_on_failure(effect_builder, reason).await)
+                }
+            };
             responder.respond(result).await
         }
         .ignore()
@@ -198,7 +372,7 @@ pub trait StorageType {
     {
         let block_store = self.block_store();
         async move {
-            let mut results = task::spawn_blocking(move || block_store.get(smallvec![block_hash]))
+            let mut results = utils::spawn_blocking(move || block_store.get(smallvec![block_hash]))
                 .await
                 .expect("should run");
             let result = results
@@ -210,6 +384,57 @@ pub trait StorageType {
         .ignore()
     }
 
+    fn get_canonical_block(
+        &self,
+        block_hash: <Self::Block as Value>::Id,
+        responder: Responder<Option<Self::Block>>,
+    ) -> Effects<Event<Self>>
+    where
+        Self: Sized,
+    {
+        let block_store = self.block_store();
+        async move {
+            let result = utils::spawn_blocking(move || {
+                let is_canonical = block_store
+                    .is_canonical(block_hash)
+                    .unwrap_or_else(|error| panic!("failed to check {}: {}", block_hash, error));
+                if !is_canonical {
+                    return None;
+                }
+                block_store
+                    .get(smallvec![block_hash])
+                    .pop()
+                    .expect("can only contain one result")
+                    .unwrap_or_else(|error| panic!("failed to get {}: {}", block_hash, error))
+            })
+            .await
+            .expect("should run");
+            responder.respond(result).await
+        }
+        .ignore()
+    }
+
+    fn mark_block_non_canonical(
+        &self,
+        block_hash: <Self::Block as Value>::Id,
+    ) -> Effects<Event<Self>>
+    where
+        Self: Sized,
+    {
+        let block_store = self.block_store();
+        async move {
+            let result = utils::spawn_blocking(move || block_store.mark_non_canonical(block_hash))
+                .await
+                .expect("should run");
+            match result {
+                Ok(true) => info!(%block_hash, "marked orphaned block non-canonical"),
+                Ok(false) => debug!(%block_hash, "orphaned block already marked non-canonical"),
+                Err(error) => error!(%block_hash, %error, "failed to mark block non-canonical"),
+            }
+        }
+        .ignore()
+    }
+
     fn get_block_header(
         &self,
         block_hash: <Self::Block as Value>::Id,
@@ -221,7 +446,7 @@ pub trait StorageType {
         let block_store = self.block_store();
         async move {
             let mut results =
-                task::spawn_blocking(move || block_store.get_headers(smallvec![block_hash]))
+                utils::spawn_blocking(move || block_store.get_headers(smallvec![block_hash]))
                     .await
                     .expect("should run");
             let result = results
@@ -233,45 +458,100 @@ pub trait StorageType {
         .ignore()
     }
 
-    fn put_deploy(
+    fn get_era_summary(
         &self,
+        era_id: EraId,
+        responder: Responder<Option<EraSummary<Self::Block>>>,
+    ) -> Effects<Event<Self>>
+    where
+        Self: Sized,
+    {
+        let block_store = self.block_store();
+        async move {
+            let result = utils::spawn_blocking(move || block_store.get_era_summary(era_id))
+                .await
+                .expect("should run")
+                .unwrap_or_else(|error| {
+                    panic!("failed to get summary of era {}: {}", era_id, error)
+                });
+            responder.respond(result).await
+        }
+        .ignore()
+    }
+
+    /// Stores a deploy.
+    ///
+    /// If storage is out of space, the deploy is dropped and reported as not newly stored.
+    fn put_deploy<REv>(
+        &self,
+        effect_builder: EffectBuilder<REv>,
         deploy: Box<Self::Deploy>,
-        responder: Responder<bool>,
+        responder: Responder<StdResult<bool, StorageFailure>>,
     ) -> Effects<Event<Self>>
     where
+        REv: From<StorageAnnouncement> + From<ControlAnnouncement> + Send,
         Self: Sized,
     {
         let deploy_store = self.deploy_store();
+        let out_of_space = self.out_of_space();
+        let deploy_reads = self.deploy_reads();
         let deploy_hash = *Value::id(&*deploy);
         async move {
-            let result = task::spawn_blocking(move || deploy_store.put(*deploy))
+            let result = utils::spawn_blocking(move || deploy_store.put(*deploy))
                 .await
-                .expect("should run")
-                .unwrap_or_else(|error| panic!("failed to put {}: {}", deploy_hash, error));
-            responder.respond(result).await;
+                .expect("should run");
+            track_space(effect_builder, &out_of_space, &result).await;
+            let result = match result {
+                Ok(is_new) => {
+                    // Reads started before the put may miss the deploy, so must not be joined.
+                    deploy_reads.invalidate(|deploy_hashes| deploy_hashes.contains(&deploy_hash));
+                    Ok(is_new)
+                }
+                Err(Error::OutOfSpace) => {
+                    warn!(%deploy_hash, "dropped deploy since storage is out of space");
+                    Ok(false)
+                }
+                Err(error) => {
+                    let reason = format!("failed to put {}: {}", deploy_hash, error);
+                    Err(shut_down_on_failure(effect_builder, reason).await)
+                }
+            };
+            responder.respond(result).await
         }
         .ignore()
     }
 
-    fn get_deploys(
+    fn get_deploys<REv>(
         &self,
+        effect_builder: EffectBuilder<REv>,
         deploy_hashes: DeployHashes<Self>,
-        responder: Responder<DeployResults<Self>>,
+        responder: Responder<StdResult<DeployResults<Self>, StorageFailure>>,
     ) -> Effects<Event<Self>>
     where
+        REv: From<ControlAnnouncement> + Send,
         Self: Sized,
     {
         let deploy_store = self.deploy_store();
+        let deploy_reads = self.deploy_reads();
         async move {
-            let results = task::spawn_blocking(move || deploy_store.get(deploy_hashes))
-                .await
-                .expect("should run")
-                .into_iter()
-                .map(|result| {
-                    result.unwrap_or_else(|error| panic!("failed to get deploy: {}", error))
+            // The store is only hit once for identical requests arriving at the same time.
+            let result = deploy_reads
+                .read(deploy_hashes.clone(), move || {
+                    deploy_store
+                        .get(deploy_hashes)
+                        .into_iter()
+                        .collect::<Result<_>>()
+                        .map_err(|error| format!("failed to get deploy: {}", error))
                 })
-                .collect();
-            responder.respond(results).await
+                .await;
+            let result = match result {
+                Ok(results) => Ok(results),
+                Err(error) => {
+                    let reason = format!("failed to get deploys: {}", error);
+                    Err(shut_down_on_failure(effect_builder, reason).await)
+                }
+            };
+            responder.respond(result).await
         }
         .ignore()
     }
@@ -286,7 +566,7 @@ pub trait StorageType {
     {
         let deploy_store = self.deploy_store();
         async move {
-            let results = task::spawn_blocking(move || deploy_store.get_headers(deploy_hashes))
+            let results = utils::spawn_blocking(move || deploy_store.get_headers(deploy_hashes))
                 .await
                 .expect("should run")
                 .into_iter()
@@ -310,7 +590,7 @@ pub trait StorageType {
     {
         let deploy_store = self.deploy_store();
         async move {
-            task::spawn_blocking(move || {
+            utils::spawn_blocking(move || {
                 for (deploy_hash, execution_result) in execution_results.into_iter() {
                     match deploy_store.put_execution_result(
                         deploy_hash,
@@ -346,7 +626,7 @@ pub trait StorageType {
         let deploy_store = self.deploy_store();
         async move {
             let result =
-                task::spawn_blocking(move || deploy_store.get_deploy_and_metadata(deploy_hash))
+                utils::spawn_blocking(move || deploy_store.get_deploy_and_metadata(deploy_hash))
                     .await
                     .expect("should run")
                     .unwrap_or_else(|error| panic!("failed to get deploy and metadata: {}", error));
@@ -365,7 +645,7 @@ pub trait StorageType {
     {
         let chainspec_store = self.chainspec_store();
         async move {
-            task::spawn_blocking(move || chainspec_store.put(*chainspec))
+            utils::spawn_blocking(move || chainspec_store.put(*chainspec))
                 .await
                 .expect("should run")
                 .unwrap_or_else(|error| panic!("failed to put chainspec: {}", error));
@@ -384,7 +664,7 @@ pub trait StorageType {
     {
         let chainspec_store = self.chainspec_store();
         async move {
-            let result = task::spawn_blocking(move || chainspec_store.get(version))
+            let result = utils::spawn_blocking(move || chainspec_store.get(version))
                 .await
                 .expect("should run")
                 .unwrap_or_else(|error| panic!("failed to get chainspec: {}", error));
@@ -396,7 +676,10 @@ pub trait StorageType {
 
 impl<REv, R, S> Component<REv, R> for S
 where
-    REv: From<NetworkRequest<NodeId, Message>> + Send,
+    REv: From<NetworkRequest<NodeId, Message>>
+        + From<StorageAnnouncement>
+        + From<ControlAnnouncement>
+        + Send,
     R: Rng + CryptoRng + ?Sized,
     S: StorageType,
     Self: Sized + 'static,
@@ -413,24 +696,37 @@ where
             Event::GetDeployForPeer { deploy_hash, peer } => {
                 self.get_deploy_for_peer(effect_builder, deploy_hash, peer)
             }
+            Event::BlockOrphaned { block_hash } => self.mark_block_non_canonical(block_hash),
             Event::Request(StorageRequest::PutBlock { block, responder }) => {
-                self.put_block(block, responder)
+                self.put_block(effect_builder, block, responder)
             }
+            Event::Request(StorageRequest::PutSwitchBlock {
+                block,
+                era_summary,
+                responder,
+            }) => self.put_switch_block(effect_builder, block, era_summary, responder),
             Event::Request(StorageRequest::GetBlock {
                 block_hash,
                 responder,
             }) => self.get_block(block_hash, responder),
+            Event::Request(StorageRequest::GetCanonicalBlock {
+                block_hash,
+                responder,
+            }) => self.get_canonical_block(block_hash, responder),
             Event::Request(StorageRequest::GetBlockHeader {
                 block_hash,
                 responder,
             }) => self.get_block_header(block_hash, responder),
+            Event::Request(StorageRequest::GetEraSummary { era_id, responder }) => {
+                self.get_era_summary(era_id, responder)
+            }
             Event::Request(StorageRequest::PutDeploy { deploy, responder }) => {
-                self.put_deploy(deploy, responder)
+                self.put_deploy(effect_builder, deploy, responder)
             }
             Event::Request(StorageRequest::GetDeploys {
                 deploy_hashes,
                 responder,
-            }) => self.get_deploys(deploy_hashes, responder),
+            }) => self.get_deploys(effect_builder, deploy_hashes, responder),
             Event::Request(StorageRequest::GetDeployHeaders {
                 deploy_hashes,
                 responder,
@@ -455,12 +751,23 @@ where
     }
 }
 
+impl<S: StorageType> ComponentLifecycle for S {
+    fn on_stop(&mut self) {
+        match self.flush() {
+            Ok(()) => info!("flushed storage"),
+            Err(error) => error!(%error, "failed to flush storage"),
+        }
+    }
+}
+
 // Concrete type of `Storage` backed by in-memory stores.
 #[derive(Debug)]
 pub(crate) struct InMemStorage<B: Value, D: Value> {
     block_store: Arc<InMemStore<B, BlockMetadata>>,
     deploy_store: Arc<InMemStore<D, DeployMetadata<B>>>,
     chainspec_store: Arc<InMemChainspecStore>,
+    deploy_reads: Arc<DeployReads<D>>,
+    out_of_space: Arc<AtomicBool>,
 }
 
 #[allow(trivial_casts)]
@@ -468,8 +775,8 @@ impl<B: Value + 'static, D: Value + Item + 'static> StorageType for InMemStorage
     type Block = B;
     type Deploy = D;
 
-    fn block_store(&self) -> Arc<dyn Store<Value = B>> {
-        Arc::clone(&self.block_store) as Arc<dyn Store<Value = B>>
+    fn block_store(&self) -> Arc<dyn BlockStore<Value = B>> {
+        Arc::clone(&self.block_store) as Arc<dyn BlockStore<Value = B>>
     }
 
     fn deploy_store(&self) -> Arc<dyn DeployStore<Block = B, Deploy = D, Value = D>> {
@@ -480,11 +787,25 @@ impl<B: Value + 'static, D: Value + Item + 'static> StorageType for InMemStorage
         Arc::clone(&self.chainspec_store) as Arc<dyn ChainspecStore>
     }
 
+    fn deploy_reads(&self) -> Arc<DeployReads<D>> {
+        Arc::clone(&self.deploy_reads)
+    }
+
+    fn out_of_space(&self) -> Arc<AtomicBool> {
+        Arc::clone(&self.out_of_space)
+    }
+
+    fn flush(&self) -> Result<()> {
+        Ok(())
+    }
+
     fn new(_config: &Config) -> Result<Self> {
         Ok(InMemStorage {
             block_store: Arc::new(InMemStore::new()),
             deploy_store: Arc::new(InMemStore::new()),
             chainspec_store: Arc::new(InMemChainspecStore::new()),
+            deploy_reads: Arc::new(Coalescer::new()),
+            out_of_space: Arc::new(AtomicBool::new(false)),
         })
     }
 }
@@ -495,6 +816,8 @@ pub struct LmdbStorage<B: Value, D: Value> {
     block_store: Arc<LmdbStore<B, BlockMetadata>>,
     deploy_store: Arc<LmdbStore<D, DeployMetadata<B>>>,
     chainspec_store: Arc<LmdbChainspecStore>,
+    deploy_reads: Arc<DeployReads<D>>,
+    out_of_space: Arc<AtomicBool>,
 }
 
 #[allow(trivial_casts)]
@@ -508,25 +831,45 @@ impl<B: Value + 'static, D: Value + Item + 'static> StorageType for LmdbStorage<
             dir: path.display().to_string(),
             source: error,
         })?;
+        // Refuse stores written by a newer binary before opening, and possibly modifying, any.
+        schema::check_schema_version(&path)?;
 
         let block_store_path = path.join(BLOCK_STORE_FILENAME);
         let deploy_store_path = path.join(DEPLOY_STORE_FILENAME);
         let chainspec_store_path = path.join(CHAINSPEC_STORE_FILENAME);
 
-        let block_store = LmdbStore::new(block_store_path, config.max_block_store_size())?;
-        let deploy_store = LmdbStore::new(deploy_store_path, config.max_deploy_store_size())?;
-        let chainspec_store =
-            LmdbChainspecStore::new(chainspec_store_path, config.max_chainspec_store_size())?;
+        let block_store = LmdbStore::new(
+            block_store_path,
+            config.max_block_store_size(),
+            Compression::none(),
+            config.write_sync(),
+        )?;
+        let deploy_store = LmdbStore::new(
+            deploy_store_path,
+            config.max_deploy_store_size(),
+            Compression::new(
+                config.deploy_compression(),
+                config.deploy_compression_threshold(),
+            ),
+            config.write_sync(),
+        )?;
+        let chainspec_store = LmdbChainspecStore::new(
+            chainspec_store_path,
+            config.max_chainspec_store_size(),
+            config.write_sync(),
+        )?;
 
         Ok(LmdbStorage {
             block_store: Arc::new(block_store),
             deploy_store: Arc::new(deploy_store),
             chainspec_store: Arc::new(chainspec_store),
+            deploy_reads: Arc::new(Coalescer::new()),
+            out_of_space: Arc::new(AtomicBool::new(false)),
         })
     }
 
-    fn block_store(&self) -> Arc<dyn Store<Value = B>> {
-        Arc::clone(&self.block_store) as Arc<dyn Store<Value = B>>
+    fn block_store(&self) -> Arc<dyn BlockStore<Value = B>> {
+        Arc::clone(&self.block_store) as Arc<dyn BlockStore<Value = B>>
     }
 
     fn deploy_store(&self) -> Arc<dyn DeployStore<Block = B, Deploy = D, Value = D>> {
@@ -536,4 +879,155 @@ impl<B: Value + 'static, D: Value + Item + 'static> StorageType for LmdbStorage<
     fn chainspec_store(&self) -> Arc<dyn ChainspecStore> {
         Arc::clone(&self.chainspec_store) as Arc<dyn ChainspecStore>
     }
+
+    fn deploy_reads(&self) -> Arc<DeployReads<D>> {
+        Arc::clone(&self.deploy_reads)
+    }
+
+    fn out_of_space(&self) -> Arc<AtomicBool> {
+        Arc::clone(&self.out_of_space)
+    }
+
+    fn flush(&self) -> Result<()> {
+        self.block_store.sync()?;
+        self.deploy_store.sync()?;
+        self.chainspec_store.sync()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use derive_more::From;
+
+    use super::*;
+    use crate::{
+        reactor::{EventQueueHandle, QueueKind, Scheduler},
+        testing::TestRng,
+        utils,
+    };
+
+    /// A store small enough to fill up within a test: four 4 KiB pages.
+    const TINY_STORE_SIZE: usize = 16_384;
+
+    #[derive(Debug, From)]
+    enum ReactorEvent {
+        #[from]
+        Storage(StorageRequest<Storage>),
+        #[from]
+        Network(NetworkRequest<NodeId, Message>),
+        #[from]
+        StorageAnnouncement(StorageAnnouncement),
+        #[from]
+        ControlAnnouncement(ControlAnnouncement),
+    }
+
+    #[tokio::test]
+    async fn should_report_out_of_space_and_announce_it() {
+        let mut rng = TestRng::new();
+        let scheduler = utils::leak(Scheduler::<ReactorEvent>::new(QueueKind::weights()));
+        let effect_builder = EffectBuilder::new(EventQueueHandle::new(scheduler));
+        let (config, _temp_dir) = Config::default_for_tests();
+        let config = config.with_max_deploy_store_size(TINY_STORE_SIZE);
+        let mut storage = Storage::new(&config).expect("should create storage");
+
+        // Fill the deploy store until a write fails, which must be reported as running out of
+        // space rather than as a generic error.
+        let deploy_store = storage.deploy_store();
+        let error = (0..1_000)
+            .find_map(|_| deploy_store.put(Deploy::random(&mut rng)).err())
+            .expect("deploy store should fill up");
+        assert!(matches!(error, Error::OutOfSpace), "{:?}", error);
+
+        // Storing another deploy through the component drops it and announces the lack of space.
+        let deploy = Box::new(Deploy::random(&mut rng));
+        let put = tokio::spawn(effect_builder.put_deploy_to_storage::<Storage>(deploy));
+        let request = match scheduler.pop().await.0 {
+            ReactorEvent::Storage(request) => request,
+            other => panic!("unexpected event {:?}", other),
+        };
+        for effect in storage.handle_event(effect_builder, &mut rng, Event::Request(request)) {
+            let _ = effect.await;
+        }
+        assert_eq!(put.await.expect("should join"), Ok(false));
+        match scheduler.pop().await.0 {
+            ReactorEvent::StorageAnnouncement(StorageAnnouncement::OutOfSpace) => (),
+            other => panic!("unexpected event {:?}", other),
+        }
+        assert!(storage.out_of_space().load(Ordering::SeqCst));
+    }
+
+    #[tokio::test]
+    async fn should_request_shutdown_if_block_cannot_be_stored() {
+        let mut rng = TestRng::new();
+        let scheduler = utils::leak(Scheduler::<ReactorEvent>::new(QueueKind::weights()));
+        let effect_builder = EffectBuilder::new(EventQueueHandle::new(scheduler));
+        let (config, _temp_dir) = Config::default_for_tests();
+        let config = config.with_max_block_store_size(TINY_STORE_SIZE);
+        let mut storage = Storage::new(&config).expect("should create storage");
+
+        let block_store = storage.block_store();
+        let _ = (0..1_000)
+            .find_map(|_| block_store.put(Block::random(&mut rng)).err())
+            .expect("block store should fill up");
+
+        // Blocks can't be dropped, so the node has to shut down instead.
+        let block = Box::new(Block::random(&mut rng));
+        let put = tokio::spawn(effect_builder.put_block_to_storage::<Storage>(block));
+        let request = match scheduler.pop().await.0 {
+            ReactorEvent::Storage(request) => request,
+            other => panic!("unexpected event {:?}", other),
+        };
+        for effect in storage.handle_event(effect_builder, &mut rng, Event::Request(request)) {
+            let _ = effect.await;
+        }
+        match scheduler.pop().await.0 {
+            ReactorEvent::StorageAnnouncement(StorageAnnouncement::OutOfSpace) => (),
+            other => panic!("unexpected event {:?}", other),
+        }
+        let reason = match scheduler.pop().await.0 {
+            ReactorEvent::ControlAnnouncement(ControlAnnouncement::FatalError(
+                ShutdownReason::StorageFailure(reason),
+            )) => reason,
+            other => panic!("unexpected event {:?}", other),
+        };
+        // The put is answered with the reason for shutting down.
+        let failure = put
+            .await
+            .expect("should join")
+            .expect_err("put should fail");
+        assert_eq!(failure.to_string(), reason);
+    }
+
+    #[test]
+    fn should_refuse_store_with_newer_schema_version() {
+        let (config, _temp_dir) = Config::default_for_tests();
+        let path = config.path();
+        fs::create_dir_all(&path).unwrap();
+        let version_path = path.join(schema::SCHEMA_VERSION_FILENAME);
+        let future_version = schema::SCHEMA_VERSION + 1;
+        fs::write(&version_path, future_version.to_string()).unwrap();
+
+        match Storage::new(&config) {
+            Err(Error::StoreTooNew { found, supported }) => {
+                assert_eq!(found, future_version);
+                assert_eq!(supported, schema::SCHEMA_VERSION);
+            }
+            other => panic!("unexpected result {:?}", other),
+        }
+
+        // The store is left untouched: neither is any database created, nor the version changed.
+        let entries: Vec<_> = fs::read_dir(&path)
+            .unwrap()
+            .map(|entry| entry.unwrap().file_name().into_string().unwrap())
+            .collect();
+        assert_eq!(entries, vec![schema::SCHEMA_VERSION_FILENAME.to_string()]);
+        assert_eq!(
+            fs::read_to_string(&version_path).unwrap(),
+            future_version.to_string()
+        );
+
+        // A store with the current version is opened as usual.
+        fs::write(&version_path, schema::SCHEMA_VERSION.to_string()).unwrap();
+        assert!(Storage::new(&config).is_ok());
+    }
 }
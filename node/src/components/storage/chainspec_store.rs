@@ -34,8 +34,12 @@ mod tests {
     #[test]
     fn lmdb_chainspec_store_should_put_then_get() {
         let (config, _tempdir) = Config::default_for_tests();
-        let mut lmdb_chainspec_store =
-            LmdbChainspecStore::new(config.path(), config.max_chainspec_store_size()).unwrap();
+        let mut lmdb_chainspec_store = LmdbChainspecStore::new(
+            config.path(),
+            config.max_chainspec_store_size(),
+            config.write_sync(),
+        )
+        .unwrap();
         should_put_then_get(&mut lmdb_chainspec_store);
     }
 
@@ -59,8 +63,12 @@ mod tests {
     #[test]
     fn lmdb_chainspec_store_should_fail_to_get_unknown_version() {
         let (config, _tempdir) = Config::default_for_tests();
-        let mut lmdb_chainspec_store =
-            LmdbChainspecStore::new(config.path(), config.max_chainspec_store_size()).unwrap();
+        let mut lmdb_chainspec_store = LmdbChainspecStore::new(
+            config.path(),
+            config.max_chainspec_store_size(),
+            config.write_sync(),
+        )
+        .unwrap();
         should_fail_get(&mut lmdb_chainspec_store);
     }
 
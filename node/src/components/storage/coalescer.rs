@@ -0,0 +1,387 @@
+//! Coalescing of identical concurrent reads.
+//!
+//! When several components request the same values at the same time, e.g. right after a deploy was
+//! gossiped, only the first request hits the store.  Identical requests arriving while that read is
+//! in flight wait for it and share its result.  Results are not cached: once the read completes,
+//! the next identical request hits the store again, so that values stored in the meantime are seen.
+//!
+//! Writing a value invalidates the reads of it in flight, since they may have started before the
+//! write: Requests arriving after the write start a read of their own instead of joining them.
+//!
+//! If a read fails, all requests waiting for it fail with the same error.  If it is abandoned
+//! instead, e.g. because the reading request was cancelled, the waiting requests read themselves.
+
+use std::{
+    collections::HashMap,
+    hash::Hash,
+    sync::{Arc, Mutex},
+};
+
+use thiserror::Error;
+use tokio::sync::oneshot;
+
+use crate::utils;
+
+/// The error shared by all requests waiting for a failed read.
+#[derive(Clone, Debug, Error)]
+#[error("coalesced read failed: {0}")]
+pub struct ReadError(String);
+
+/// The sender to a request waiting for a read.
+type Waiter<V> = oneshot::Sender<Result<V, ReadError>>;
+
+/// The reads in flight.
+#[derive(Debug)]
+struct State<K, V> {
+    /// The ID of the next read.
+    next_id: u64,
+    /// The IDs of the reads identical requests may still join, by key.
+    joinable: HashMap<K, u64>,
+    /// The requests waiting for each read in flight, by the read's ID.
+    waiters: HashMap<u64, Vec<Waiter<V>>>,
+}
+
+/// Coalesces identical reads which are in flight at the same time.
+#[derive(Debug)]
+pub struct Coalescer<K, V> {
+    state: Mutex<State<K, V>>,
+}
+
+impl<K, V> Coalescer<K, V>
+where
+    K: Clone + Eq + Hash + Send + 'static,
+    V: Clone + Send + 'static,
+{
+    /// Creates a coalescer without any reads in flight.
+    pub(super) fn new() -> Self {
+        Coalescer {
+            state: Mutex::new(State {
+                next_id: 0,
+                joinable: HashMap::new(),
+                waiters: HashMap::new(),
+            }),
+        }
+    }
+
+    /// Returns the result of reading `key`.
+    ///
+    /// If a joinable read of `key` is already in flight, its result is awaited.  Otherwise `read`
+    /// is run in a blocking task, and its result shared with all identical requests arriving in the
+    /// meantime.
+    pub(super) async fn read<F>(self: Arc<Self>, key: K, read: F) -> Result<V, ReadError>
+    where
+        F: FnOnce() -> Result<V, String> + Send + 'static,
+    {
+        let id = loop {
+            let waiting = {
+                let mut state = self.state.lock().expect("should lock");
+                let state = &mut *state;
+                match state.joinable.get(&key) {
+                    Some(id) => {
+                        let (sender, receiver) = oneshot::channel();
+                        state.waiters.entry(*id).or_default().push(sender);
+                        Err(receiver)
+                    }
+                    None => {
+                        let id = state.next_id;
+                        state.next_id += 1;
+                        let _ = state.joinable.insert(key.clone(), id);
+                        let _ = state.waiters.insert(id, Vec::new());
+                        Ok(id)
+                    }
+                }
+            };
+            match waiting {
+                Ok(id) => break id,
+                Err(receiver) => {
+                    if let Ok(result) = receiver.await {
+                        return result;
+                    }
+                    // The read was abandoned, so read on our own.
+                }
+            }
+        };
+
+        let guard = InFlight {
+            state: &self.state,
+            key: Some(key),
+            id,
+        };
+        let result = match utils::spawn_blocking(read).await {
+            Ok(result) => result.map_err(ReadError),
+            Err(error) => Err(ReadError(error.to_string())),
+        };
+        for waiter in guard.complete() {
+            let _ = waiter.send(result.clone());
+        }
+        result
+    }
+
+    /// Stops identical requests from joining the reads in flight whose keys match `is_affected`,
+    /// e.g. because the values they read have just been written.
+    ///
+    /// The requests already waiting for these reads still get their results.
+    pub(super) fn invalidate<P: Fn(&K) -> bool>(&self, is_affected: P) {
+        self.state
+            .lock()
+            .expect("should lock")
+            .joinable
+            .retain(|key, _| !is_affected(key));
+    }
+
+    /// Returns the number of requests waiting for the joinable read of `key`, or `None` if there is
+    /// none in flight.
+    #[cfg(test)]
+    fn waiting(&self, key: &K) -> Option<usize> {
+        let state = self.state.lock().expect("should lock");
+        let id = state.joinable.get(key)?;
+        state.waiters.get(id).map(Vec::len)
+    }
+}
+
+/// A read in flight.
+///
+/// If the read is abandoned, e.g. because the reading request was cancelled, its entry is removed
+/// on drop, so that the waiting requests read themselves instead of waiting forever.
+struct InFlight<'a, K: Eq + Hash, V> {
+    state: &'a Mutex<State<K, V>>,
+    key: Option<K>,
+    id: u64,
+}
+
+impl<'a, K: Eq + Hash, V> InFlight<'a, K, V> {
+    /// Marks the read as completed, returning the senders to the waiting requests.
+    fn complete(mut self) -> Vec<Waiter<V>> {
+        self.remove().unwrap_or_default()
+    }
+
+    fn remove(&mut self) -> Option<Vec<Waiter<V>>> {
+        let key = self.key.take()?;
+        let mut state = self.state.lock().expect("should lock");
+        if state.joinable.get(&key) == Some(&self.id) {
+            let _ = state.joinable.remove(&key);
+        }
+        state.waiters.remove(&self.id)
+    }
+}
+
+impl<'a, K: Eq + Hash, V> Drop for InFlight<'a, K, V> {
+    fn drop(&mut self) {
+        let _ = self.remove();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        sync::{
+            atomic::{AtomicUsize, Ordering},
+            mpsc,
+        },
+        time::Duration,
+    };
+
+    use smallvec::smallvec;
+    use tokio::time;
+
+    use super::{
+        super::{DeployMetadata, InMemStore, Multiple, Store},
+        *,
+    };
+    use crate::{
+        testing::TestRng,
+        types::{Block, Deploy, DeployHash},
+    };
+
+    type DeployStore = InMemStore<Deploy, DeployMetadata<Block>>;
+    type DeployReads = Coalescer<Multiple<DeployHash>, Multiple<Option<Deploy>>>;
+
+    const READERS: usize = 10;
+
+    /// Reads `deploy_hash` from the store `READERS` times concurrently, holding up the first read
+    /// until all others are waiting for it.
+    async fn read_concurrently(
+        deploy_reads: &Arc<DeployReads>,
+        store: &Arc<DeployStore>,
+        backend_reads: &Arc<AtomicUsize>,
+        deploy_hash: DeployHash,
+    ) -> Vec<Option<Deploy>> {
+        let key: Multiple<DeployHash> = smallvec![deploy_hash];
+        let (release_sender, release_receiver) = mpsc::channel::<()>();
+        let release_receiver = Arc::new(Mutex::new(release_receiver));
+
+        let handles: Vec<_> = (0..READERS)
+            .map(|_| {
+                let store = Arc::clone(store);
+                let backend_reads = Arc::clone(backend_reads);
+                let release_receiver = Arc::clone(&release_receiver);
+                let key_to_read = key.clone();
+                let read = move || {
+                    release_receiver.lock().unwrap().recv().unwrap();
+                    backend_reads.fetch_add(1, Ordering::SeqCst);
+                    Ok(store
+                        .get(key_to_read)
+                        .into_iter()
+                        .map(|result| result.unwrap())
+                        .collect())
+                };
+                tokio::spawn(Arc::clone(deploy_reads).read(key.clone(), read))
+            })
+            .collect();
+
+        while deploy_reads.waiting(&key) != Some(READERS - 1) {
+            time::delay_for(Duration::from_millis(10)).await;
+        }
+        release_sender.send(()).unwrap();
+
+        let mut results = Vec::new();
+        for handle in handles {
+            let mut result = handle.await.expect("should join").expect("should read");
+            assert_eq!(result.len(), 1);
+            results.push(result.pop().unwrap());
+        }
+        assert_eq!(deploy_reads.waiting(&key), None);
+        results
+    }
+
+    #[tokio::test]
+    async fn should_hit_store_once_for_concurrent_identical_reads() {
+        let mut rng = TestRng::new();
+        let store = Arc::new(DeployStore::new());
+        let deploy_reads = Arc::new(DeployReads::new());
+        let backend_reads = Arc::new(AtomicUsize::new(0));
+        let deploy = Deploy::random(&mut rng);
+        let deploy_hash = *deploy.id();
+
+        // While the deploy is missing, all readers share the single read's empty result.
+        let results = read_concurrently(&deploy_reads, &store, &backend_reads, deploy_hash).await;
+        assert!(results.iter().all(Option::is_none));
+        assert_eq!(backend_reads.load(Ordering::SeqCst), 1);
+
+        // The missing result isn't cached, so the next readers see the deploy once it is stored.
+        assert!(store.put(deploy.clone()).unwrap());
+        let results = read_concurrently(&deploy_reads, &store, &backend_reads, deploy_hash).await;
+        assert!(results
+            .iter()
+            .all(|result| result.as_ref() == Some(&deploy)));
+        assert_eq!(backend_reads.load(Ordering::SeqCst), 2);
+    }
+
+    /// Returns a read of `key` from `store` which blocks until released via the returned sender.
+    fn held_read(
+        store: &Arc<DeployStore>,
+        backend_reads: &Arc<AtomicUsize>,
+        key: &Multiple<DeployHash>,
+    ) -> (
+        impl FnOnce() -> Result<Multiple<Option<Deploy>>, String>,
+        mpsc::Sender<()>,
+    ) {
+        let (release_sender, release_receiver) = mpsc::channel::<()>();
+        let store = Arc::clone(store);
+        let backend_reads = Arc::clone(backend_reads);
+        let key = key.clone();
+        let read = move || {
+            release_receiver.recv().unwrap();
+            backend_reads.fetch_add(1, Ordering::SeqCst);
+            Ok(store
+                .get(key)
+                .into_iter()
+                .map(|result| result.unwrap())
+                .collect())
+        };
+        (read, release_sender)
+    }
+
+    #[tokio::test]
+    async fn should_not_join_reads_invalidated_by_put() {
+        let mut rng = TestRng::new();
+        let store = Arc::new(DeployStore::new());
+        let deploy_reads = Arc::new(DeployReads::new());
+        let backend_reads = Arc::new(AtomicUsize::new(0));
+        let deploy = Deploy::random(&mut rng);
+        let key: Multiple<DeployHash> = smallvec![*deploy.id()];
+
+        // A read starting before the deploy is stored, and only hitting the store after the put.
+        let (read, release_sender) = held_read(&store, &backend_reads, &key);
+        let stale = tokio::spawn(Arc::clone(&deploy_reads).read(key.clone(), read));
+        while deploy_reads.waiting(&key) != Some(0) {
+            time::delay_for(Duration::from_millis(10)).await;
+        }
+
+        assert!(store.put(deploy.clone()).unwrap());
+        deploy_reads.invalidate(|deploy_hashes| deploy_hashes.contains(deploy.id()));
+        assert_eq!(deploy_reads.waiting(&key), None);
+
+        // A read arriving after the put doesn't join the one in flight, so sees the deploy even
+        // though the earlier read is still held up.
+        let (read, fresh_release_sender) = held_read(&store, &backend_reads, &key);
+        fresh_release_sender.send(()).unwrap();
+        let fresh = Arc::clone(&deploy_reads)
+            .read(key.clone(), read)
+            .await
+            .expect("should read");
+        assert_eq!(fresh[0].as_ref(), Some(&deploy));
+        assert_eq!(backend_reads.load(Ordering::SeqCst), 1);
+
+        release_sender.send(()).unwrap();
+        stale.await.expect("should join").expect("should read");
+        assert_eq!(backend_reads.load(Ordering::SeqCst), 2);
+        assert_eq!(deploy_reads.waiting(&key), None);
+    }
+
+    #[tokio::test]
+    async fn should_share_failure_of_read() {
+        let deploy_reads = Arc::new(DeployReads::new());
+        let key: Multiple<DeployHash> = smallvec![DeployHash::new(Default::default())];
+        let (release_sender, release_receiver) = mpsc::channel::<()>();
+
+        let failing = tokio::spawn(Arc::clone(&deploy_reads).read(key.clone(), move || {
+            release_receiver.recv().unwrap();
+            Err("store broken".to_string())
+        }));
+        while deploy_reads.waiting(&key) != Some(0) {
+            time::delay_for(Duration::from_millis(10)).await;
+        }
+        let waiting =
+            tokio::spawn(Arc::clone(&deploy_reads).read(key.clone(), || panic!("should not read")));
+        while deploy_reads.waiting(&key) != Some(1) {
+            time::delay_for(Duration::from_millis(10)).await;
+        }
+        release_sender.send(()).unwrap();
+
+        for handle in vec![failing, waiting] {
+            let error = handle.await.expect("should join").unwrap_err();
+            assert_eq!(error.to_string(), "coalesced read failed: store broken");
+        }
+    }
+
+    #[tokio::test]
+    async fn should_read_again_if_read_is_abandoned() {
+        let mut rng = TestRng::new();
+        let store = Arc::new(DeployStore::new());
+        let deploy_reads = Arc::new(DeployReads::new());
+        let backend_reads = Arc::new(AtomicUsize::new(0));
+        let deploy = Deploy::random(&mut rng);
+        let key: Multiple<DeployHash> = smallvec![*deploy.id()];
+        assert!(store.put(deploy.clone()).unwrap());
+
+        let (read, release_sender) = held_read(&store, &backend_reads, &key);
+        let mut abandoned = Box::pin(Arc::clone(&deploy_reads).read(key.clone(), read));
+        assert!(futures::poll!(&mut abandoned).is_pending());
+
+        let (read, waiter_release_sender) = held_read(&store, &backend_reads, &key);
+        waiter_release_sender.send(()).unwrap();
+        let waiting = tokio::spawn(Arc::clone(&deploy_reads).read(key.clone(), read));
+        while deploy_reads.waiting(&key) != Some(1) {
+            time::delay_for(Duration::from_millis(10)).await;
+        }
+
+        // Dropping the first request makes the waiting one read on its own.
+        drop(abandoned);
+        let results = waiting.await.expect("should join").expect("should read");
+        assert_eq!(results[0].as_ref(), Some(&deploy));
+        assert_eq!(backend_reads.load(Ordering::SeqCst), 1);
+        assert_eq!(deploy_reads.waiting(&key), None);
+        release_sender.send(()).unwrap();
+    }
+}
@@ -0,0 +1,118 @@
+//! Transparent compression of stored values.
+//!
+//! Compressed values are prefixed with a marker byte and a codec tag.  The marker is `0xc1`, which
+//! is never used by MessagePack, so an uncompressed serialized value can never start with it.  This
+//! allows compressed and uncompressed values to coexist in the same store.
+
+use std::borrow::Cow;
+
+use miniz_oxide::{deflate, inflate};
+use serde::{Deserialize, Serialize};
+
+use super::{Error, Result};
+
+/// Marker prepended to compressed values.
+const COMPRESSED_MARKER: u8 = 0xc1;
+/// Tag identifying the deflate codec.
+const DEFLATE_TAG: u8 = 1;
+/// Length of the header prepended to compressed values.
+const HEADER_LENGTH: usize = 2;
+/// The deflate compression level, ranging from 0 (none) to 10 (best).
+const DEFLATE_LEVEL: u8 = 6;
+
+/// The codec used to compress stored values.
+#[derive(Copy, Clone, Debug, Eq, PartialEq, Deserialize, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Codec {
+    /// Values are stored uncompressed.
+    None,
+    /// Values are compressed using deflate.
+    Deflate,
+}
+
+/// Compression settings of a store.
+#[derive(Copy, Clone, Debug)]
+pub(super) struct Compression {
+    codec: Codec,
+    /// Serialized values smaller than this many bytes are stored uncompressed.
+    threshold: usize,
+}
+
+impl Compression {
+    pub(super) fn new(codec: Codec, threshold: usize) -> Self {
+        Compression { codec, threshold }
+    }
+
+    /// Returns settings under which values are never compressed.
+    pub(super) fn none() -> Self {
+        Compression::new(Codec::None, 0)
+    }
+
+    /// Compresses `serialized` if it is at least as large as the threshold.
+    ///
+    /// The value is stored uncompressed if compression wouldn't make it smaller.
+    pub(super) fn compress(&self, serialized: Vec<u8>) -> Vec<u8> {
+        let tag = match self.codec {
+            Codec::None => return serialized,
+            Codec::Deflate => DEFLATE_TAG,
+        };
+        if serialized.len() < self.threshold {
+            return serialized;
+        }
+
+        let compressed = deflate::compress_to_vec(&serialized, DEFLATE_LEVEL);
+        if compressed.len() + HEADER_LENGTH >= serialized.len() {
+            return serialized;
+        }
+        let mut stored = Vec::with_capacity(compressed.len() + HEADER_LENGTH);
+        stored.push(COMPRESSED_MARKER);
+        stored.push(tag);
+        stored.extend(compressed);
+        stored
+    }
+}
+
+/// Returns the serialized value held in `stored`, decompressing it if required.
+pub(super) fn decompress(stored: &[u8]) -> Result<Cow<[u8]>> {
+    match stored {
+        [COMPRESSED_MARKER, DEFLATE_TAG, compressed @ ..] => inflate::decompress_to_vec(compressed)
+            .map(Cow::Owned)
+            .map_err(|status| Error::Decompression(format!("{:?}", status))),
+        [COMPRESSED_MARKER, tag, ..] => {
+            Err(Error::Decompression(format!("unknown codec tag {}", tag)))
+        }
+        _ => Ok(Cow::Borrowed(stored)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn should_round_trip_compressed_value() {
+        let serialized = vec![7; 10_000];
+        let stored = Compression::new(Codec::Deflate, 1_000).compress(serialized.clone());
+        assert!(stored.len() < serialized.len());
+        assert_eq!(decompress(&stored).unwrap(), serialized.as_slice());
+    }
+
+    #[test]
+    fn should_not_compress_below_threshold_or_without_codec() {
+        let serialized = vec![7; 500];
+        let stored = Compression::new(Codec::Deflate, 1_000).compress(serialized.clone());
+        assert_eq!(stored, serialized);
+
+        let stored = Compression::none().compress(serialized.clone());
+        assert_eq!(stored, serialized);
+        assert!(matches!(decompress(&stored).unwrap(), Cow::Borrowed(_)));
+    }
+
+    #[test]
+    fn should_reject_unknown_codec() {
+        assert!(matches!(
+            decompress(&[COMPRESSED_MARKER, 99, 1, 2, 3]),
+            Err(Error::Decompression(_))
+        ));
+    }
+}
@@ -8,6 +8,12 @@ use tracing::warn;
 
 use casper_execution_engine::shared::utils;
 
+use super::{
+    compression::Codec,
+    durability::{Durability, WriteSync},
+    IntegrityCheck,
+};
+
 const QUALIFIER: &str = "io";
 const ORGANIZATION: &str = "CasperLabs";
 const APPLICATION: &str = "casper-node";
@@ -15,6 +21,11 @@ const APPLICATION: &str = "casper-node";
 const DEFAULT_MAX_BLOCK_STORE_SIZE: usize = 483_183_820_800; // 450 GiB
 const DEFAULT_MAX_DEPLOY_STORE_SIZE: usize = 322_122_547_200; // 300 GiB
 const DEFAULT_MAX_CHAINSPEC_STORE_SIZE: usize = 1_073_741_824; // 1 GiB
+const DEFAULT_DEPLOY_COMPRESSION: Codec = Codec::Deflate;
+const DEFAULT_DEPLOY_COMPRESSION_THRESHOLD: usize = 4_096; // 4 KiB
+const DEFAULT_INTEGRITY_CHECK: IntegrityCheck = IntegrityCheck::Off;
+const DEFAULT_DURABILITY: Durability = Durability::Fsync;
+const DEFAULT_FSYNC_BATCH_SIZE: usize = 64;
 
 #[cfg(test)]
 const DEFAULT_TEST_MAX_DB_SIZE: usize = 52_428_800; // 50 MiB
@@ -54,6 +65,29 @@ pub struct Config {
     ///
     /// The size should be a multiple of the OS page size.
     max_chainspec_store_size: Option<usize>,
+    /// The codec used to compress deploys written to the deploy store.
+    ///
+    /// Defaults to `Codec::Deflate`.
+    deploy_compression: Option<Codec>,
+    /// The minimum size in bytes of a serialized deploy for it to be compressed.
+    ///
+    /// Defaults to 4,096 == 4 KiB.
+    deploy_compression_threshold: Option<usize>,
+    /// Whether to check the integrity of the stored blocks and deploys on startup, and whether to
+    /// quarantine corrupt ones.
+    ///
+    /// Defaults to `IntegrityCheck::Off`.
+    integrity_check: Option<IntegrityCheck>,
+    /// When writes are flushed to disk: after every write, after every `fsync_batch_size` writes,
+    /// or only on shutdown.  Only flushing every write keeps the database intact on a crash of the
+    /// operating system.
+    ///
+    /// Defaults to `Durability::Fsync`.
+    durability: Option<Durability>,
+    /// The number of writes to each store flushed to disk together at `Durability::FsyncBatched`.
+    ///
+    /// Defaults to 64.
+    fsync_batch_size: Option<usize>,
 }
 
 impl Config {
@@ -69,10 +103,29 @@ impl Config {
             max_block_store_size: Some(DEFAULT_TEST_MAX_DB_SIZE),
             max_deploy_store_size: Some(DEFAULT_TEST_MAX_DB_SIZE),
             max_chainspec_store_size: Some(DEFAULT_TEST_MAX_DB_SIZE),
+            deploy_compression: Some(DEFAULT_DEPLOY_COMPRESSION),
+            deploy_compression_threshold: Some(DEFAULT_DEPLOY_COMPRESSION_THRESHOLD),
+            integrity_check: Some(DEFAULT_INTEGRITY_CHECK),
+            durability: Some(DEFAULT_DURABILITY),
+            fsync_batch_size: Some(DEFAULT_FSYNC_BATCH_SIZE),
         };
         (config, tempdir)
     }
 
+    /// Sets the maximum size of the block store.
+    #[cfg(test)]
+    pub(crate) fn with_max_block_store_size(mut self, max_block_store_size: usize) -> Self {
+        self.max_block_store_size = Some(max_block_store_size);
+        self
+    }
+
+    /// Sets the maximum size of the deploy store.
+    #[cfg(test)]
+    pub(crate) fn with_max_deploy_store_size(mut self, max_deploy_store_size: usize) -> Self {
+        self.max_deploy_store_size = Some(max_deploy_store_size);
+        self
+    }
+
     pub(crate) fn path(&self) -> PathBuf {
         match self.path {
             Some(ref path) => path.clone(),
@@ -104,6 +157,28 @@ impl Config {
         value
     }
 
+    pub(crate) fn deploy_compression(&self) -> Codec {
+        self.deploy_compression
+            .unwrap_or(DEFAULT_DEPLOY_COMPRESSION)
+    }
+
+    pub(crate) fn deploy_compression_threshold(&self) -> usize {
+        self.deploy_compression_threshold
+            .unwrap_or(DEFAULT_DEPLOY_COMPRESSION_THRESHOLD)
+    }
+
+    pub(crate) fn integrity_check(&self) -> IntegrityCheck {
+        self.integrity_check.unwrap_or(DEFAULT_INTEGRITY_CHECK)
+    }
+
+    /// Returns a new `WriteSync` for a store, flushing its writes at the configured durability.
+    pub(super) fn write_sync(&self) -> WriteSync {
+        WriteSync::new(
+            self.durability.unwrap_or(DEFAULT_DURABILITY),
+            self.fsync_batch_size.unwrap_or(DEFAULT_FSYNC_BATCH_SIZE),
+        )
+    }
+
     fn default_path() -> PathBuf {
         ProjectDirs::from(QUALIFIER, ORGANIZATION, APPLICATION)
             .map(|project_dirs| project_dirs.data_dir().to_path_buf())
@@ -122,6 +197,11 @@ impl Default for Config {
             max_block_store_size: Some(DEFAULT_MAX_BLOCK_STORE_SIZE),
             max_deploy_store_size: Some(DEFAULT_MAX_DEPLOY_STORE_SIZE),
             max_chainspec_store_size: Some(DEFAULT_MAX_CHAINSPEC_STORE_SIZE),
+            deploy_compression: Some(DEFAULT_DEPLOY_COMPRESSION),
+            deploy_compression_threshold: Some(DEFAULT_DEPLOY_COMPRESSION_THRESHOLD),
+            integrity_check: Some(DEFAULT_INTEGRITY_CHECK),
+            durability: Some(DEFAULT_DURABILITY),
+            fsync_batch_size: Some(DEFAULT_FSYNC_BATCH_SIZE),
         }
     }
 }
@@ -0,0 +1,138 @@
+//! Durability of stored writes.
+//!
+//! The configured durability level decides when committed writes are flushed to disk: after every
+//! write, after every batch of writes, or never, leaving it to the operating system.  Storage is
+//! flushed on shutdown regardless of the level.
+//!
+//! A crash of the node itself can't lose any committed writes, as they are held by the operating
+//! system.  A crash of the operating system or a power loss is a different matter:
+//!
+//! * At `Durability::Fsync`, LMDB syncs the data pages on commit and we flush the rest right after,
+//!   so at most the write being committed is lost.
+//! * At the other levels, LMDB is opened with `NO_SYNC` and doesn't sync on commit at all.  Unless
+//!   the file system preserves the order of writes, such a crash can then not only lose the writes
+//!   since the last flush, but also leave the database corrupt.
+
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+use lmdb::{Environment, EnvironmentFlags};
+use serde::{Deserialize, Serialize};
+
+use super::Result;
+
+/// When committed writes are flushed to disk.
+#[derive(Copy, Clone, Debug, Eq, PartialEq, Deserialize, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Durability {
+    /// Every write is flushed to disk before it is reported as stored.
+    Fsync,
+    /// Writes are flushed to disk in batches.  A crash of the operating system can lose the writes
+    /// of an unfinished batch, and can corrupt the database.
+    FsyncBatched,
+    /// Writes are never explicitly flushed to disk, only on shutdown.  A crash of the operating
+    /// system can lose any writes since startup, and can corrupt the database.
+    NoFsync,
+}
+
+impl Durability {
+    /// Returns the flags to open an LMDB environment with at this level.
+    fn env_flags(self) -> EnvironmentFlags {
+        match self {
+            // LMDB still syncs the data pages on commit, so the database can't be corrupted even
+            // if the remaining sync is interrupted.
+            Durability::Fsync => EnvironmentFlags::NO_META_SYNC,
+            // LMDB doesn't sync at all, so the pages of a commit may reach the disk in any order.
+            Durability::FsyncBatched | Durability::NoFsync => EnvironmentFlags::NO_SYNC,
+        }
+    }
+}
+
+/// A store backend whose committed writes can be flushed to disk.
+pub(super) trait Flush {
+    /// Flushes all committed writes to disk.
+    fn flush(&self) -> Result<()>;
+}
+
+impl Flush for Environment {
+    fn flush(&self) -> Result<()> {
+        Ok(self.sync(true)?)
+    }
+}
+
+/// Flushes the committed writes to a store as its durability level requires.
+#[derive(Debug)]
+pub(super) struct WriteSync {
+    durability: Durability,
+    /// The number of writes flushed together at `Durability::FsyncBatched`.
+    batch_size: usize,
+    /// The number of writes committed so far.
+    write_count: AtomicUsize,
+}
+
+impl WriteSync {
+    pub(super) fn new(durability: Durability, batch_size: usize) -> Self {
+        WriteSync {
+            durability,
+            batch_size: batch_size.max(1),
+            write_count: AtomicUsize::new(0),
+        }
+    }
+
+    /// Returns the flags to open the store's LMDB environment with.
+    pub(super) fn env_flags(&self) -> EnvironmentFlags {
+        self.durability.env_flags()
+    }
+
+    /// Records a write committed to `backend`, and flushes it if required.
+    pub(super) fn committed<F: Flush + ?Sized>(&self, backend: &F) -> Result<()> {
+        match self.durability {
+            Durability::Fsync => backend.flush(),
+            Durability::FsyncBatched => {
+                let write_count = self.write_count.fetch_add(1, Ordering::SeqCst) + 1;
+                if write_count % self.batch_size == 0 {
+                    backend.flush()
+                } else {
+                    Ok(())
+                }
+            }
+            Durability::NoFsync => Ok(()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A backend counting how often it is flushed.
+    #[derive(Default)]
+    struct MockBackend {
+        flush_count: AtomicUsize,
+    }
+
+    impl Flush for MockBackend {
+        fn flush(&self) -> Result<()> {
+            let _ = self.flush_count.fetch_add(1, Ordering::SeqCst);
+            Ok(())
+        }
+    }
+
+    /// Commits `write_count` writes at the given level, returning the number of flushes.
+    fn flushes(durability: Durability, write_count: usize) -> usize {
+        let backend = MockBackend::default();
+        let write_sync = WriteSync::new(durability, 4);
+        for _ in 0..write_count {
+            write_sync.committed(&backend).expect("should flush");
+        }
+        backend.flush_count.load(Ordering::SeqCst)
+    }
+
+    #[test]
+    fn should_flush_as_required_by_durability_level() {
+        assert_eq!(flushes(Durability::Fsync, 1), 1);
+        assert_eq!(flushes(Durability::Fsync, 10), 10);
+        assert_eq!(flushes(Durability::FsyncBatched, 3), 0);
+        assert_eq!(flushes(Durability::FsyncBatched, 10), 2);
+        assert_eq!(flushes(Durability::NoFsync, 10), 0);
+    }
+}
@@ -16,6 +16,34 @@ pub enum Error {
         source: io::Error,
     },
 
+    /// Failed to read or write the file recording the schema version.
+    #[error("failed to access {file}: {source}")]
+    SchemaVersionIo {
+        /// The path of the schema version file.
+        file: String,
+        /// Underlying IO error.
+        source: io::Error,
+    },
+
+    /// The file recording the schema version doesn't contain a version.
+    #[error("invalid schema version in {file}: {contents:?}")]
+    InvalidSchemaVersion {
+        /// The path of the schema version file.
+        file: String,
+        /// The contents of the file.
+        contents: String,
+    },
+
+    /// The stores were written by a newer binary, with a schema version this binary doesn't
+    /// support.
+    #[error("store has schema version {found}, but only up to {supported} is supported")]
+    StoreTooNew {
+        /// The schema version recorded for the stores.
+        found: u32,
+        /// The newest schema version supported by this binary.
+        supported: u32,
+    },
+
     /// Failed to serialize data.
     #[error("serialization: {0}")]
     Serialization(#[from] rmp_serde::encode::Error),
@@ -24,13 +52,31 @@ pub enum Error {
     #[error("deserialization: {0}")]
     Deserialization(#[from] rmp_serde::decode::Error),
 
+    /// Failed to decompress a stored value.
+    #[error("decompression: {0}")]
+    Decompression(String),
+
+    /// There is no space left to write to, either on the disk or within the maximum size of the
+    /// database.
+    #[error("out of space")]
+    OutOfSpace,
+
     /// Internal storage component error.
     #[error("internal: {0}")]
     Internal(Box<dyn StdError + Send + Sync>),
 }
 
+/// A request storage failed to fulfil, because of which the node is shutting down.
+#[derive(Clone, Debug, Error, PartialEq, Eq)]
+#[error("{0}")]
+pub struct StorageFailure(pub(super) String);
+
 impl From<lmdb::Error> for Error {
     fn from(error: lmdb::Error) -> Self {
-        Error::Internal(Box::new(error))
+        match error {
+            lmdb::Error::MapFull => Error::OutOfSpace,
+            lmdb::Error::Other(code) if code == libc::ENOSPC => Error::OutOfSpace,
+            error => Error::Internal(Box::new(error)),
+        }
     }
 }
@@ -13,6 +13,8 @@ pub enum Event<S: StorageType + 'static> {
         deploy_hash: <S::Deploy as Value>::Id,
         peer: NodeId,
     },
+    /// A block has been orphaned and should be marked non-canonical.
+    BlockOrphaned { block_hash: <S::Block as Value>::Id },
     #[from]
     Request(StorageRequest<S>),
 }
@@ -23,6 +25,11 @@ impl<S: StorageType + 'static> Display for Event<S> {
             Event::GetDeployForPeer { deploy_hash, peer } => {
                 write!(formatter, "get deploy {} for {}", deploy_hash, peer)
             }
+            Event::BlockOrphaned { block_hash } => write!(
+                formatter,
+                "mark orphaned block {} non-canonical",
+                block_hash
+            ),
             Event::Request(request) => write!(formatter, "{}", request),
         }
     }
@@ -1,11 +1,14 @@
 use std::{
-    collections::{hash_map::Entry, HashMap},
+    collections::{hash_map::Entry, HashMap, HashSet},
     fmt::Debug,
     sync::RwLock,
 };
 
-use super::{DeployMetadata, DeployStore, Multiple, Result, Store, Value};
-use crate::types::json_compatibility::ExecutionResult;
+use super::{
+    BlockMetadata, BlockStore, DeployMetadata, DeployStore, EraSummary, Multiple, Result, Store,
+    Value,
+};
+use crate::{components::consensus::EraId, types::json_compatibility::ExecutionResult};
 
 #[derive(Debug)]
 struct ValueAndMetadata<V, M> {
@@ -26,12 +29,21 @@ impl<V, M: Default + Send + Sync> ValueAndMetadata<V, M> {
 #[derive(Debug)]
 pub(super) struct InMemStore<V: Value, M> {
     inner: RwLock<HashMap<V::Id, ValueAndMetadata<V, M>>>,
+    /// The era summaries, only used if this is a block store.
+    era_summaries: RwLock<HashMap<EraId, EraSummary<V>>>,
+    /// The blocks marked non-canonical, only used if this is a block store.
+    non_canonical: RwLock<HashSet<V::Id>>,
+    /// Values found to be corrupt, which are no longer returned by the store.
+    quarantine: RwLock<Vec<V>>,
 }
 
 impl<V: Value, M> InMemStore<V, M> {
     pub(crate) fn new() -> Self {
         InMemStore {
             inner: RwLock::new(HashMap::new()),
+            era_summaries: RwLock::new(HashMap::new()),
+            non_canonical: RwLock::new(HashSet::new()),
+            quarantine: RwLock::new(Vec::new()),
         }
     }
 }
@@ -85,6 +97,60 @@ impl<V: Value, M: Default + Send + Sync> Store for InMemStore<V, M> {
             .cloned()
             .collect())
     }
+
+    fn verify_integrity(&self, quarantine: bool) -> Result<Vec<V::Id>> {
+        let mut inner = self.inner.write().expect("should lock");
+        let mut corrupt = vec![];
+        for (id, entry) in inner.iter_mut() {
+            let is_intact = entry
+                .value
+                .as_ref()
+                .map_or(true, |value| value.id() == id && value.is_intact());
+            if is_intact {
+                continue;
+            }
+            corrupt.push(*id);
+            if quarantine {
+                if let Some(value) = entry.value.take() {
+                    self.quarantine.write().expect("should lock").push(value);
+                }
+            }
+        }
+        Ok(corrupt)
+    }
+}
+
+impl<B: Value> BlockStore for InMemStore<B, BlockMetadata> {
+    fn put_switch_block(&self, block: B, era_summary: EraSummary<B>) -> Result<bool> {
+        // Insert the summary first, so the block is never visible without it.
+        let _ = self
+            .era_summaries
+            .write()
+            .expect("should lock")
+            .insert(era_summary.era_id, era_summary);
+        self.put(block)
+    }
+
+    fn get_era_summary(&self, era_id: EraId) -> Result<Option<EraSummary<B>>> {
+        Ok(self
+            .era_summaries
+            .read()
+            .expect("should lock")
+            .get(&era_id)
+            .cloned())
+    }
+
+    fn mark_non_canonical(&self, id: B::Id) -> Result<bool> {
+        Ok(self.non_canonical.write().expect("should lock").insert(id))
+    }
+
+    fn is_canonical(&self, id: B::Id) -> Result<bool> {
+        Ok(!self
+            .non_canonical
+            .read()
+            .expect("should lock")
+            .contains(&id))
+    }
 }
 
 impl<D: Value, B: Value> DeployStore for InMemStore<D, DeployMetadata<B>> {
@@ -0,0 +1,64 @@
+//! Integrity checks of the stored data.
+//!
+//! Every stored block and deploy is checked to be readable and to match the key it is stored under,
+//! and every stored block is checked to only reference deploys which are stored as well.  Corrupt
+//! values can optionally be quarantined: they are moved aside, so that they are no longer served,
+//! but kept for later inspection.
+
+use std::fmt::{self, Display, Formatter};
+
+use serde::{Deserialize, Serialize};
+
+use super::Value;
+
+/// Whether and how to check the integrity of the stored data on startup.
+#[derive(Copy, Clone, Debug, Eq, PartialEq, Deserialize, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum IntegrityCheck {
+    /// The integrity is not checked.
+    Off,
+    /// Inconsistencies are reported, but left in place.
+    Report,
+    /// Inconsistencies are reported, and corrupt values are quarantined.
+    Repair,
+}
+
+/// The inconsistencies found by an integrity check.
+#[derive(Debug)]
+pub struct IntegrityReport<B: Value, D: Value> {
+    /// The IDs of blocks which can't be read or don't match their ID.
+    pub corrupt_blocks: Vec<B::Id>,
+    /// The IDs of deploys which can't be read or don't match their ID.
+    pub corrupt_deploys: Vec<D::Id>,
+    /// Deploys referenced by a stored block which are not stored themselves, together with the
+    /// referencing block.
+    pub missing_deploys: Vec<(B::Id, D::Id)>,
+    /// Whether the corrupt blocks and deploys have been quarantined.
+    pub quarantined: bool,
+}
+
+impl<B: Value, D: Value> IntegrityReport<B, D> {
+    /// Returns whether no inconsistencies were found.
+    pub fn is_clean(&self) -> bool {
+        self.corrupt_blocks.is_empty()
+            && self.corrupt_deploys.is_empty()
+            && self.missing_deploys.is_empty()
+    }
+}
+
+impl<B: Value, D: Value> Display for IntegrityReport<B, D> {
+    fn fmt(&self, formatter: &mut Formatter<'_>) -> fmt::Result {
+        write!(
+            formatter,
+            "{} corrupt blocks, {} corrupt deploys{}, {} missing deploys",
+            self.corrupt_blocks.len(),
+            self.corrupt_deploys.len(),
+            if self.quarantined {
+                " (quarantined)"
+            } else {
+                ""
+            },
+            self.missing_deploys.len()
+        )
+    }
+}
@@ -4,7 +4,7 @@ use lmdb::{self, Database, DatabaseFlags, Environment, EnvironmentFlags, Transac
 use semver::Version;
 use tracing::info;
 
-use super::{ChainspecStore, Result};
+use super::{durability::WriteSync, ChainspecStore, Result};
 use crate::Chainspec;
 
 /// LMDB version of a store.
@@ -12,18 +12,33 @@ use crate::Chainspec;
 pub(super) struct LmdbChainspecStore {
     env: Environment,
     db: Database,
+    /// Flushes committed writes to disk as the durability level requires.
+    write_sync: WriteSync,
 }
 
 impl LmdbChainspecStore {
-    pub(crate) fn new<P: AsRef<Path>>(db_path: P, max_size: usize) -> Result<Self> {
+    pub(crate) fn new<P: AsRef<Path>>(
+        db_path: P,
+        max_size: usize,
+        write_sync: WriteSync,
+    ) -> Result<Self> {
         let env = Environment::new()
-            .set_flags(EnvironmentFlags::NO_SUB_DIR)
+            .set_flags(EnvironmentFlags::NO_SUB_DIR | write_sync.env_flags())
             .set_map_size(max_size)
             .open(db_path.as_ref())?;
         let db = env.create_db(None, DatabaseFlags::empty())?;
         info!("opened DB at {}", db_path.as_ref().display());
 
-        Ok(LmdbChainspecStore { env, db })
+        Ok(LmdbChainspecStore {
+            env,
+            db,
+            write_sync,
+        })
+    }
+
+    /// Flushes all committed transactions to disk.
+    pub(super) fn sync(&self) -> Result<()> {
+        Ok(self.env.sync(true)?)
     }
 }
 
@@ -35,7 +50,7 @@ impl ChainspecStore for LmdbChainspecStore {
         txn.put(self.db, &id, &serialized_value, WriteFlags::empty())
             .expect("should put");
         txn.commit().expect("should commit txn");
-        Ok(())
+        self.write_sync.committed(&self.env)
     }
 
     fn get(&self, version: Version) -> Result<Option<Chainspec>> {
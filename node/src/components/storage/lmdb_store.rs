@@ -1,13 +1,20 @@
 use std::{fmt::Debug, marker::PhantomData, path::Path};
 
 use lmdb::{
-    self, Cursor, Database, DatabaseFlags, Environment, EnvironmentFlags, Transaction, WriteFlags,
+    self, Cursor, Database, DatabaseFlags, Environment, EnvironmentFlags, RwTransaction,
+    Transaction, WriteFlags,
 };
+use serde::de::DeserializeOwned;
 use smallvec::smallvec;
-use tracing::info;
+use tracing::{info, warn};
 
-use super::{DeployMetadata, DeployStore, Error, Multiple, Result, Store, Value};
-use crate::types::json_compatibility::ExecutionResult;
+use super::{
+    compression::{self, Compression},
+    durability::WriteSync,
+    BlockMetadata, BlockStore, DeployMetadata, DeployStore, EraSummary, Error, Multiple, Result,
+    Store, Value,
+};
+use crate::{components::consensus::EraId, types::json_compatibility::ExecutionResult};
 
 /// Used to namespace metadata associated with stored values.
 #[repr(u8)]
@@ -16,6 +23,14 @@ enum Tag {
     #[allow(unused)]
     BlockMetadata,
     DeployMetadata,
+    EraSummary,
+    Quarantine,
+    NonCanonical,
+}
+
+/// Returns the key under which the summary of the given era is stored.
+fn era_summary_key(era_id: EraId) -> Result<Vec<u8>> {
+    rmp_serde::to_vec(&(Tag::EraSummary as u8, era_id)).map_err(Error::from)
 }
 
 /// LMDB version of a store.
@@ -23,13 +38,22 @@ enum Tag {
 pub(super) struct LmdbStore<V: Value, M> {
     env: Environment,
     db: Database,
+    /// Compression applied to values when they are written.
+    compression: Compression,
+    /// Flushes committed writes to disk as the durability level requires.
+    write_sync: WriteSync,
     _phantom: PhantomData<(V, M)>,
 }
 
 impl<V: Value, M: Default + Send + Sync> LmdbStore<V, M> {
-    pub(crate) fn new<P: AsRef<Path>>(db_path: P, max_size: usize) -> Result<Self> {
+    pub(crate) fn new<P: AsRef<Path>>(
+        db_path: P,
+        max_size: usize,
+        compression: Compression,
+        write_sync: WriteSync,
+    ) -> Result<Self> {
         let env = Environment::new()
-            .set_flags(EnvironmentFlags::NO_SUB_DIR)
+            .set_flags(EnvironmentFlags::NO_SUB_DIR | write_sync.env_flags())
             .set_map_size(max_size)
             .open(db_path.as_ref())?;
         let db = env.create_db(None, DatabaseFlags::empty())?;
@@ -38,12 +62,25 @@ impl<V: Value, M: Default + Send + Sync> LmdbStore<V, M> {
         Ok(LmdbStore {
             env,
             db,
+            compression,
+            write_sync,
             _phantom: PhantomData,
         })
     }
 }
 
 impl<V: Value, M> LmdbStore<V, M> {
+    /// Flushes all committed transactions to disk.
+    pub(super) fn sync(&self) -> Result<()> {
+        Ok(self.env.sync(true)?)
+    }
+
+    /// Commits a write transaction, flushing it to disk if the durability level requires.
+    fn commit(&self, txn: RwTransaction) -> Result<()> {
+        txn.commit()?;
+        self.write_sync.committed(&self.env)
+    }
+
     fn get_values(&self, ids: Multiple<V::Id>) -> Multiple<Result<Option<V>>> {
         let mut serialized_ids = Multiple::new();
         for id in &ids {
@@ -56,11 +93,8 @@ impl<V: Value, M> LmdbStore<V, M> {
             match maybe_serialized_id {
                 Ok(serialized_id) => {
                     match txn.get(self.db, &serialized_id) {
-                        Ok(serialized_value) => {
-                            let value_result = rmp_serde::from_read_ref(serialized_value)
-                                .map(Some)
-                                .map_err(Error::from);
-                            values.push(value_result)
+                        Ok(stored_value) => {
+                            values.push(Self::deserialize_value(stored_value).map(Some))
                         }
                         Err(lmdb::Error::NotFound) => {
                             values.push(Ok(None));
@@ -75,6 +109,57 @@ impl<V: Value, M> LmdbStore<V, M> {
         values
     }
 
+    /// Deserializes a stored value, decompressing it first if required.
+    fn deserialize_value<T: DeserializeOwned>(stored_value: &[u8]) -> Result<T> {
+        let serialized_value = compression::decompress(stored_value)?;
+        Ok(rmp_serde::from_read_ref(&serialized_value)?)
+    }
+
+    /// Returns the number of bytes occupied by the stored value with the given ID.
+    #[cfg(test)]
+    pub(super) fn stored_size(&self, id: &V::Id) -> usize {
+        let serialized_id = Self::serialized_id(id, None).expect("should serialize id");
+        let txn = self.env.begin_ro_txn().expect("should create ro txn");
+        let size = txn.get(self.db, &serialized_id).expect("should get").len();
+        txn.commit().expect("should commit txn");
+        size
+    }
+
+    /// Stores `stored_value` under the key of `id` as is, bypassing serialization.
+    #[cfg(test)]
+    pub(super) fn put_raw(&self, id: &V::Id, stored_value: &[u8]) {
+        let serialized_id = Self::serialized_id(id, None).expect("should serialize id");
+        let mut txn = self.env.begin_rw_txn().expect("should create rw txn");
+        txn.put(
+            self.db,
+            &serialized_id,
+            &stored_value,
+            WriteFlags::default(),
+        )
+        .expect("should put");
+        txn.commit().expect("should commit txn");
+    }
+
+    /// Returns the number of quarantined values.
+    #[cfg(test)]
+    pub(super) fn quarantined_count(&self) -> usize {
+        let txn = self.env.begin_ro_txn().expect("should create ro txn");
+        let count = {
+            let mut cursor = txn
+                .open_ro_cursor(self.db)
+                .expect("should create ro cursor");
+            cursor
+                .iter()
+                .filter(|(key, _)| {
+                    rmp_serde::from_read_ref::<_, (u8, Vec<u8>)>(key)
+                        .map_or(false, |(tag, _)| tag == Tag::Quarantine as u8)
+                })
+                .count()
+        };
+        txn.commit().expect("should commit txn");
+        count
+    }
+
     fn serialized_id(id: &V::Id, maybe_tag: Option<Tag>) -> Result<Vec<u8>> {
         match maybe_tag {
             Some(tag) => rmp_serde::to_vec(&(tag as u8, id)),
@@ -89,19 +174,20 @@ impl<V: Value, M: Send + Sync> Store for LmdbStore<V, M> {
 
     fn put(&self, value: V) -> Result<bool> {
         let serialized_id = Self::serialized_id(value.id(), None)?;
-        let serialized_value = rmp_serde::to_vec(&value)?;
+        let stored_value = self.compression.compress(rmp_serde::to_vec(&value)?);
         let mut txn = self.env.begin_rw_txn().expect("should create rw txn");
+        // Running out of space is reported as an error, aborting the transaction.
         let result = match txn.put(
             self.db,
             &serialized_id,
-            &serialized_value,
+            &stored_value,
             WriteFlags::NO_OVERWRITE,
         ) {
             Ok(()) => true,
             Err(lmdb::Error::KeyExist) => false,
-            Err(error) => panic!("should put: {:?}", error),
+            Err(error) => return Err(error.into()),
         };
-        txn.commit().expect("should commit txn");
+        self.commit(txn)?;
         Ok(result)
     }
 
@@ -132,6 +218,115 @@ impl<V: Value, M: Send + Sync> Store for LmdbStore<V, M> {
         txn.commit().expect("should commit txn");
         Ok(ids)
     }
+
+    fn verify_integrity(&self, quarantine: bool) -> Result<Vec<V::Id>> {
+        let mut corrupt = vec![];
+        let txn = self.env.begin_ro_txn().expect("should create ro txn");
+        {
+            let mut cursor = txn
+                .open_ro_cursor(self.db)
+                .expect("should create ro cursor");
+            for (serialized_id, stored_value) in cursor.iter() {
+                // The keys of metadata, era summaries, non-canonical markers and quarantined
+                // values are tagged, so they don't deserialize as IDs.
+                let id = match rmp_serde::from_read_ref::<_, V::Id>(serialized_id) {
+                    Ok(id) => id,
+                    Err(_) => continue,
+                };
+                let is_intact = Self::deserialize_value::<V>(stored_value)
+                    .map_or(false, |value| *value.id() == id && value.is_intact());
+                if !is_intact {
+                    warn!(%id, "found corrupt value");
+                    corrupt.push((id, serialized_id.to_vec(), stored_value.to_vec()));
+                }
+            }
+        }
+        txn.commit().expect("should commit txn");
+
+        if quarantine && !corrupt.is_empty() {
+            // Move the values to a tagged key, keeping their stored representation.
+            let mut txn = self.env.begin_rw_txn().expect("should create rw txn");
+            for (_, serialized_id, stored_value) in &corrupt {
+                let quarantine_key = rmp_serde::to_vec(&(Tag::Quarantine as u8, serialized_id))?;
+                txn.put(
+                    self.db,
+                    &quarantine_key,
+                    stored_value,
+                    WriteFlags::default(),
+                )?;
+                txn.del(self.db, serialized_id, None)?;
+            }
+            self.commit(txn)?;
+        }
+        Ok(corrupt.into_iter().map(|(id, _, _)| id).collect())
+    }
+}
+
+impl<B: Value> BlockStore for LmdbStore<B, BlockMetadata> {
+    fn put_switch_block(&self, block: B, era_summary: EraSummary<B>) -> Result<bool> {
+        let serialized_id = Self::serialized_id(block.id(), None)?;
+        let stored_value = self.compression.compress(rmp_serde::to_vec(&block)?);
+        let serialized_era_id = era_summary_key(era_summary.era_id)?;
+        let serialized_era_summary = rmp_serde::to_vec(&era_summary)?;
+
+        // Write both in a single transaction, so the block is never stored without the summary.
+        let mut txn = self.env.begin_rw_txn().expect("should create rw txn");
+        // Running out of space is reported as an error, aborting the transaction.
+        let result = match txn.put(
+            self.db,
+            &serialized_id,
+            &stored_value,
+            WriteFlags::NO_OVERWRITE,
+        ) {
+            Ok(()) => true,
+            Err(lmdb::Error::KeyExist) => false,
+            Err(error) => return Err(error.into()),
+        };
+        txn.put(
+            self.db,
+            &serialized_era_id,
+            &serialized_era_summary,
+            WriteFlags::default(),
+        )?;
+        self.commit(txn)?;
+        Ok(result)
+    }
+
+    fn get_era_summary(&self, era_id: EraId) -> Result<Option<EraSummary<B>>> {
+        let serialized_era_id = era_summary_key(era_id)?;
+        let txn = self.env.begin_ro_txn().expect("should create ro txn");
+        let result = match txn.get(self.db, &serialized_era_id) {
+            Ok(serialized_value) => Some(rmp_serde::from_read_ref(serialized_value)?),
+            Err(lmdb::Error::NotFound) => None,
+            Err(error) => panic!("should get: {:?}", error),
+        };
+        txn.commit().expect("should commit txn");
+        Ok(result)
+    }
+
+    fn mark_non_canonical(&self, id: B::Id) -> Result<bool> {
+        let serialized_id = Self::serialized_id(&id, Some(Tag::NonCanonical))?;
+        let mut txn = self.env.begin_rw_txn().expect("should create rw txn");
+        let result = match txn.put(self.db, &serialized_id, &[], WriteFlags::NO_OVERWRITE) {
+            Ok(()) => true,
+            Err(lmdb::Error::KeyExist) => false,
+            Err(error) => return Err(error.into()),
+        };
+        self.commit(txn)?;
+        Ok(result)
+    }
+
+    fn is_canonical(&self, id: B::Id) -> Result<bool> {
+        let serialized_id = Self::serialized_id(&id, Some(Tag::NonCanonical))?;
+        let txn = self.env.begin_ro_txn().expect("should create ro txn");
+        let result = match txn.get(self.db, &serialized_id) {
+            Ok(_) => false,
+            Err(lmdb::Error::NotFound) => true,
+            Err(error) => panic!("should get: {:?}", error),
+        };
+        txn.commit().expect("should commit txn");
+        Ok(result)
+    }
 }
 
 impl<D: Value, B: Value> DeployStore for LmdbStore<D, DeployMetadata<B>> {
@@ -174,7 +369,7 @@ impl<D: Value, B: Value> DeployStore for LmdbStore<D, DeployMetadata<B>> {
             &serialized_value,
             WriteFlags::default(),
         )?;
-        txn.commit().expect("should commit txn");
+        self.commit(txn)?;
         Ok(true)
     }
 
@@ -185,9 +380,7 @@ impl<D: Value, B: Value> DeployStore for LmdbStore<D, DeployMetadata<B>> {
         // Get the deploy.
         let txn = self.env.begin_ro_txn().expect("should create ro txn");
         let deploy: D = match txn.get(self.db, &serialized_deploy_id) {
-            Ok(serialized_value) => {
-                rmp_serde::from_read_ref(serialized_value).map_err(Error::from)?
-            }
+            Ok(stored_value) => Self::deserialize_value(stored_value)?,
             Err(lmdb::Error::NotFound) => {
                 // Return `None` if the deploy doesn't exist.
                 txn.commit().expect("should commit txn");
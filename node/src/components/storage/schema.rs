@@ -0,0 +1,42 @@
+//! Versioning of the on-disk layout of the stores.
+//!
+//! The schema version of the stores is recorded in a file alongside them when they are first
+//! created.  An older binary doesn't know how a newer binary lays out its data, and writing to such
+//! a store could corrupt it, so stores recorded with a newer version than this binary supports are
+//! refused before any of them is opened.
+
+use std::{fs, io, path::Path};
+
+use super::{Error, Result};
+
+/// The schema version of the stores written by this binary.
+pub(super) const SCHEMA_VERSION: u32 = 1;
+
+/// The name of the file recording the schema version within the storage directory.
+pub(super) const SCHEMA_VERSION_FILENAME: &str = "schema_version";
+
+/// Checks that the stores in `dir` can be opened by this binary, recording the current schema
+/// version if none is recorded yet.
+pub(super) fn check_schema_version(dir: &Path) -> Result<()> {
+    let path = dir.join(SCHEMA_VERSION_FILENAME);
+    let file = path.display().to_string();
+    let contents = match fs::read_to_string(&path) {
+        Ok(contents) => contents,
+        Err(error) if error.kind() == io::ErrorKind::NotFound => {
+            return fs::write(&path, SCHEMA_VERSION.to_string())
+                .map_err(|source| Error::SchemaVersionIo { file, source });
+        }
+        Err(source) => return Err(Error::SchemaVersionIo { file, source }),
+    };
+    let found = contents
+        .trim()
+        .parse()
+        .map_err(|_| Error::InvalidSchemaVersion { file, contents })?;
+    if found > SCHEMA_VERSION {
+        return Err(Error::StoreTooNew {
+            found,
+            supported: SCHEMA_VERSION,
+        });
+    }
+    Ok(())
+}
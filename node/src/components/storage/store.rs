@@ -1,7 +1,7 @@
 use smallvec::SmallVec;
 
-use super::{DeployAndMetadata, Result, Value};
-use crate::types::json_compatibility::ExecutionResult;
+use super::{DeployAndMetadata, EraSummary, Result, Value};
+use crate::{components::consensus::EraId, types::json_compatibility::ExecutionResult};
 
 pub(super) type Multiple<T> = SmallVec<[T; 3]>;
 
@@ -22,6 +22,34 @@ pub trait Store: Send + Sync {
     ) -> Multiple<Result<Option<<Self::Value as Value>::Header>>>;
     /// Returns a copy of all IDs held by the store.
     fn ids(&self) -> Result<Vec<<Self::Value as Value>::Id>>;
+    /// Returns the IDs of all values which can't be read or are not intact.
+    ///
+    /// If `quarantine` is true, these values are moved to quarantine, so that they are no longer
+    /// returned by the store.
+    fn verify_integrity(&self, quarantine: bool) -> Result<Vec<<Self::Value as Value>::Id>>;
+}
+
+pub trait BlockStore: Store {
+    /// Stores the last block of an era together with the era's summary, both or neither.
+    ///
+    /// Returns whether the block was not present before.
+    fn put_switch_block(
+        &self,
+        block: Self::Value,
+        era_summary: EraSummary<Self::Value>,
+    ) -> Result<bool>;
+
+    /// Returns the summary of the given era if its last block has been stored.
+    fn get_era_summary(&self, era_id: EraId) -> Result<Option<EraSummary<Self::Value>>>;
+
+    /// Marks the given block as no longer part of the canonical chain, e.g. as it was orphaned by
+    /// a fork.  The block itself is kept.
+    ///
+    /// Returns whether the block was canonical before.
+    fn mark_non_canonical(&self, id: <Self::Value as Value>::Id) -> Result<bool>;
+
+    /// Returns whether the given block is canonical, i.e. hasn't been marked non-canonical.
+    fn is_canonical(&self, id: <Self::Value as Value>::Id) -> Result<bool>;
 }
 
 pub trait DeployStore: Store {
@@ -44,15 +72,20 @@ pub trait DeployStore: Store {
 
 #[cfg(test)]
 mod tests {
+    use casper_execution_engine::core::engine_state::executable_deploy_item::ExecutableDeployItem;
     use smallvec::smallvec;
 
     use super::{
-        super::{Config, DeployMetadata, InMemStore, LmdbStore},
+        super::{
+            compression::Codec, BlockMetadata, Compression, Config, DeployMetadata, InMemStore,
+            LmdbStorage, LmdbStore, StorageType,
+        },
         *,
     };
     use crate::{
+        crypto::asymmetric_key::SecretKey,
         testing::TestRng,
-        types::{Block, Deploy},
+        types::{Block, Deploy, TimeDiff, Timestamp},
     };
 
     fn should_put_then_get<T: Store<Value = Deploy>>(store: &mut T) {
@@ -78,14 +111,167 @@ mod tests {
         let mut lmdb_deploy_store = LmdbStore::<Deploy, DeployMetadata<Block>>::new(
             config.path(),
             config.max_deploy_store_size(),
+            Compression::none(),
+            config.write_sync(),
         )
         .unwrap();
         should_put_then_get(&mut lmdb_deploy_store);
     }
 
+    #[test]
+    fn lmdb_deploy_store_should_compress_large_deploys() {
+        let mut rng = TestRng::new();
+        let (config, _tempdir) = Config::default_for_tests();
+        let lmdb_deploy_store = LmdbStore::<Deploy, DeployMetadata<Block>>::new(
+            config.path(),
+            config.max_deploy_store_size(),
+            Compression::new(Codec::Deflate, 4_096),
+            config.write_sync(),
+        )
+        .unwrap();
+
+        // Contract bytecode is typically highly compressible.
+        let session = ExecutableDeployItem::ModuleBytes {
+            module_bytes: b"\0asm".iter().cycle().take(64 * 1024).cloned().collect(),
+            args: vec![],
+        };
+        let payment = ExecutableDeployItem::ModuleBytes {
+            module_bytes: vec![],
+            args: vec![],
+        };
+        let deploy = Deploy::new(
+            Timestamp::now(),
+            TimeDiff::from(60_000),
+            1,
+            vec![],
+            String::from("casper-example"),
+            payment,
+            session,
+            &SecretKey::random(&mut rng),
+            &mut rng,
+        );
+        let deploy_hash = *deploy.id();
+
+        assert!(lmdb_deploy_store.put(deploy.clone()).unwrap());
+        let stored_size = lmdb_deploy_store.stored_size(&deploy_hash);
+        let uncompressed_size = rmp_serde::to_vec(&deploy).unwrap().len();
+        assert!(stored_size < uncompressed_size);
+
+        let recovered_deploy = lmdb_deploy_store
+            .get(smallvec![deploy_hash])
+            .pop()
+            .expect("should be only one")
+            .expect("get should return Ok")
+            .expect("should have deploy");
+        assert_eq!(recovered_deploy, deploy);
+
+        let (recovered_deploy, _metadata) = lmdb_deploy_store
+            .get_deploy_and_metadata(deploy_hash)
+            .unwrap()
+            .expect("should have deploy");
+        assert_eq!(recovered_deploy, deploy);
+    }
+
     #[test]
     fn in_mem_deploy_store_should_put_then_get() {
         let mut in_mem_deploy_store = InMemStore::<Deploy, DeployMetadata<Block>>::new();
         should_put_then_get(&mut in_mem_deploy_store);
     }
+
+    fn should_mark_block_non_canonical<T: BlockStore<Value = Block>>(store: &T) {
+        let mut rng = TestRng::new();
+
+        let block = Block::random(&mut rng);
+        let block_hash = *block.id();
+        store.put(block.clone()).unwrap();
+        assert!(store.is_canonical(block_hash).unwrap());
+
+        assert!(store.mark_non_canonical(block_hash).unwrap());
+        assert!(!store.mark_non_canonical(block_hash).unwrap());
+        assert!(!store.is_canonical(block_hash).unwrap());
+
+        // The block is still stored, and is the only ID held by the store.
+        let maybe_block = store
+            .get(smallvec![block_hash])
+            .pop()
+            .expect("should be only one")
+            .expect("get should return Ok");
+        assert_eq!(maybe_block, Some(block));
+        assert_eq!(store.ids().unwrap(), vec![block_hash]);
+    }
+
+    #[test]
+    fn lmdb_block_store_should_mark_block_non_canonical() {
+        let (config, _tempdir) = Config::default_for_tests();
+        let lmdb_block_store = LmdbStore::<Block, BlockMetadata>::new(
+            config.path(),
+            config.max_block_store_size(),
+            Compression::none(),
+            config.write_sync(),
+        )
+        .unwrap();
+        should_mark_block_non_canonical(&lmdb_block_store);
+    }
+
+    #[test]
+    fn in_mem_block_store_should_mark_block_non_canonical() {
+        let in_mem_block_store = InMemStore::<Block, BlockMetadata>::new();
+        should_mark_block_non_canonical(&in_mem_block_store);
+    }
+
+    #[test]
+    fn should_detect_and_quarantine_corrupt_entries() {
+        let mut rng = TestRng::new();
+        let (config, _tempdir) = Config::default_for_tests();
+        let storage = LmdbStorage::<Block, Deploy>::new(&config).unwrap();
+
+        // One intact deploy, one stored under the wrong hash and one which isn't even decodable.
+        let intact = Deploy::random(&mut rng);
+        assert!(storage.deploy_store.put(intact.clone()).unwrap());
+        let misplaced_hash = *Deploy::random(&mut rng).id();
+        storage
+            .deploy_store
+            .put_raw(&misplaced_hash, &rmp_serde::to_vec(&intact).unwrap());
+        let garbled_hash = *Deploy::random(&mut rng).id();
+        storage.deploy_store.put_raw(&garbled_hash, b"garbage");
+        // A block whose deploys were never stored.
+        let block = Block::random(&mut rng);
+        assert!(storage.block_store.put(block.clone()).unwrap());
+
+        let mut expected_corrupt = vec![misplaced_hash, garbled_hash];
+        expected_corrupt.sort();
+        let expected_missing: Vec<_> = block
+            .deploy_hashes()
+            .iter()
+            .map(|deploy_hash| (*block.hash(), *deploy_hash))
+            .collect();
+
+        // Without repair, the inconsistencies are only reported.
+        let mut report = storage.verify_integrity(false).unwrap();
+        report.corrupt_deploys.sort();
+        assert!(!report.is_clean());
+        assert!(report.corrupt_blocks.is_empty());
+        assert_eq!(report.corrupt_deploys, expected_corrupt);
+        assert_eq!(report.missing_deploys, expected_missing);
+        assert_eq!(storage.deploy_store.quarantined_count(), 0);
+
+        // With repair, the corrupt deploys are quarantined and no longer returned.
+        let mut report = storage.verify_integrity(true).unwrap();
+        report.corrupt_deploys.sort();
+        assert_eq!(report.corrupt_deploys, expected_corrupt);
+        assert!(report.quarantined);
+        assert_eq!(storage.deploy_store.quarantined_count(), 2);
+        let results =
+            storage
+                .deploy_store
+                .get(smallvec![*intact.id(), misplaced_hash, garbled_hash]);
+        assert_eq!(results[0].as_ref().unwrap().as_ref(), Some(&intact));
+        assert!(matches!(results[1], Ok(None)));
+        assert!(matches!(results[2], Ok(None)));
+
+        // Only the missing deploys remain to be reported.
+        let report = storage.verify_integrity(true).unwrap();
+        assert!(report.corrupt_deploys.is_empty());
+        assert_eq!(report.missing_deploys, expected_missing);
+    }
 }
@@ -1,7 +1,12 @@
 //! Cryptographic types and functions.
 
 pub mod asymmetric_key;
+pub mod audit;
 mod error;
 pub mod hash;
+mod id_generator;
+pub mod merkle;
+mod verification_cache;
 
 pub use error::{Error, Result};
+pub use id_generator::IdGenerator;
@@ -15,6 +15,7 @@ use hex_fmt::HexFmt;
 use k256::ecdsa::{
     Signature as Secp256k1Signature, Signer as Secp256k1Signer, Verifier as Secp256k1Verifier,
 };
+use lazy_static::lazy_static;
 use pem::Pem;
 #[cfg(test)]
 use rand::RngCore;
@@ -23,7 +24,11 @@ use serde::{Deserialize, Serialize};
 use signature::{RandomizedSigner, Signature as Sig, Verifier};
 use untrusted::Input;
 
-use super::{Error, Result};
+use super::{
+    audit::{self, AuditOperation},
+    verification_cache::VerificationCache,
+    Error, Result,
+};
 #[cfg(test)]
 use crate::testing::TestRng;
 use crate::{
@@ -52,6 +57,111 @@ const SECP256K1_OBJECT_IDENTIFIER: [u8; 5] = [43, 129, 4, 0, 10];
 const SECP256K1_PEM_SECRET_KEY_TAG: &str = "EC PRIVATE KEY";
 const SECP256K1_PEM_PUBLIC_KEY_TAG: &str = "PUBLIC KEY";
 
+const PEM_BEGIN_MARKER: &str = "-----BEGIN ";
+const PEM_END_MARKER: &str = "-----END ";
+
+lazy_static! {
+    /// The successful signature verifications remembered by `verify`.  Disabled until a capacity is
+    /// set.
+    static ref VERIFICATION_CACHE: VerificationCache = VerificationCache::new(0);
+}
+
+#[cfg(test)]
+thread_local! {
+    /// The number of signatures verified on this thread without hitting the verification cache.
+    static UNCACHED_VERIFICATIONS: std::cell::Cell<usize> = std::cell::Cell::new(0);
+}
+
+/// A signature scheme, identified by the tag prefixing the hex representation of keys and
+/// signatures.
+#[derive(Copy, Clone, Debug, Eq, PartialEq, Serialize, Deserialize)]
+pub enum SignatureScheme {
+    /// Ed25519.
+    Ed25519,
+    /// secp256k1.
+    Secp256k1,
+}
+
+impl SignatureScheme {
+    /// Returns the scheme identified by `tag`, or an error if the scheme is not supported.
+    pub fn from_tag(tag: u8) -> Result<Self> {
+        match tag {
+            ED25519_TAG => Ok(SignatureScheme::Ed25519),
+            SECP256K1_TAG => Ok(SignatureScheme::Secp256k1),
+            _ => Err(Error::UnsupportedSignatureScheme(tag)),
+        }
+    }
+}
+
+impl Display for SignatureScheme {
+    fn fmt(&self, formatter: &mut Formatter) -> fmt::Result {
+        match self {
+            SignatureScheme::Ed25519 => write!(formatter, "{}", ED25519),
+            SignatureScheme::Secp256k1 => write!(formatter, "{}", SECP256K1),
+        }
+    }
+}
+
+/// What a signature is made for.
+///
+/// Every signed message is prefixed with the domain-separation tag of its purpose, and only
+/// verifies for the same purpose.  A signature made for one purpose, e.g. a finality signature,
+/// can thus never be replayed for another, e.g. as a consensus vote, even if the signed bytes are
+/// the same.
+///
+/// Signatures of untagged messages, as made before tags were introduced, are rejected for every
+/// purpose, as they could otherwise be replayed for all of them.
+#[derive(Copy, Clone, Debug, Eq, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum SigningPurpose {
+    /// The approval of a deploy by its account or another approver.
+    Deploy,
+    /// A validator's signature of an executed block.
+    FinalitySignature,
+    /// A consensus message, e.g. a vote in the Highway protocol.
+    ConsensusVote,
+    /// A node's attestation of its consensus key, sent when connecting to peers.
+    HandshakeAttestation,
+    /// An operator's order to restart the network following a given block.
+    EmergencyRestart,
+}
+
+impl SigningPurpose {
+    /// Returns the domain-separation tag prefixed to messages signed for this purpose.
+    ///
+    /// No tag is a prefix of another, so no tagged message is ambiguous.
+    fn domain_tag(self) -> &'static [u8] {
+        match self {
+            SigningPurpose::Deploy => b"casper:deploy:",
+            SigningPurpose::FinalitySignature => b"casper:finality_signature:",
+            SigningPurpose::ConsensusVote => b"casper:consensus_vote:",
+            SigningPurpose::HandshakeAttestation => b"casper:handshake_attestation:",
+            SigningPurpose::EmergencyRestart => b"casper:emergency_restart:",
+        }
+    }
+
+    /// Returns `message` prefixed with the domain-separation tag, as actually signed.
+    pub(super) fn tag_message(self, message: &[u8]) -> Vec<u8> {
+        let tag = self.domain_tag();
+        let mut tagged = Vec::with_capacity(tag.len() + message.len());
+        tagged.extend_from_slice(tag);
+        tagged.extend_from_slice(message);
+        tagged
+    }
+}
+
+impl Display for SigningPurpose {
+    fn fmt(&self, formatter: &mut Formatter) -> fmt::Result {
+        match self {
+            SigningPurpose::Deploy => write!(formatter, "deploy"),
+            SigningPurpose::FinalitySignature => write!(formatter, "finality signature"),
+            SigningPurpose::ConsensusVote => write!(formatter, "consensus vote"),
+            SigningPurpose::HandshakeAttestation => write!(formatter, "handshake attestation"),
+            SigningPurpose::EmergencyRestart => write!(formatter, "emergency restart"),
+        }
+    }
+}
+
 /// A secret or private asymmetric key.
 #[derive(Serialize, Deserialize)]
 pub enum SecretKey {
@@ -148,9 +258,19 @@ impl SecretKey {
     }
 
     /// Attempts to read the secret key bytes from configured file path.
+    ///
+    /// The load is recorded by the audit sink, if one is set.
     pub fn from_file<P: AsRef<Path>>(file: P) -> Result<Self> {
-        let data = read_file(file).map_err(Error::SecretKeyLoad)?;
-        Self::from_pem(data)
+        let data = read_file(file.as_ref()).map_err(Error::SecretKeyLoad)?;
+        check_key_file_complete(file.as_ref(), &data)?;
+        let secret_key = Self::from_pem(data)?;
+        audit::record(
+            || PublicKey::from(&secret_key),
+            || AuditOperation::LoadKey {
+                path: file.as_ref().to_path_buf(),
+            },
+        );
+        Ok(secret_key)
     }
 
     /// Duplicates a secret key.
@@ -520,7 +640,8 @@ impl PublicKey {
 
     /// Attempts to read the public key bytes from configured PEM-encoded file.
     pub fn from_file<P: AsRef<Path>>(file: P) -> Result<Self> {
-        let data = read_file(file).map_err(Error::PublicKeyLoad)?;
+        let data = read_file(file.as_ref()).map_err(Error::PublicKeyLoad)?;
+        check_key_file_complete(file.as_ref(), &data)?;
         Self::from_pem(data)
     }
 
@@ -539,21 +660,17 @@ impl PublicKey {
         let mut tag = [0u8; 1];
         hex::decode_to_slice(&input.as_ref()[..2], tag.as_mut())?;
 
-        match tag[0] {
-            ED25519_TAG => {
+        match SignatureScheme::from_tag(tag[0])? {
+            SignatureScheme::Ed25519 => {
                 let mut bytes = [0u8; Self::ED25519_LENGTH];
                 hex::decode_to_slice(&input.as_ref()[2..], bytes.as_mut())?;
                 Self::new_ed25519(bytes)
             }
-            SECP256K1_TAG => {
+            SignatureScheme::Secp256k1 => {
                 let mut bytes = [0u8; Self::SECP256K1_LENGTH];
                 hex::decode_to_slice(&input.as_ref()[2..], bytes.as_mut())?;
                 Self::new_secp256k1(bytes)
             }
-            _ => Err(Error::AsymmetricKey(format!(
-                "invalid tag.  Expected {} or {}, got {}",
-                ED25519_TAG, SECP256K1_TAG, tag[0]
-            ))),
         }
     }
 
@@ -578,6 +695,14 @@ impl PublicKey {
         PublicKey::from(&secret_key)
     }
 
+    /// Returns the signature scheme of the key.
+    pub fn scheme(&self) -> SignatureScheme {
+        match self {
+            PublicKey::Ed25519(_) => SignatureScheme::Ed25519,
+            PublicKey::Secp256k1(_) => SignatureScheme::Secp256k1,
+        }
+    }
+
     fn tag(&self) -> u8 {
         match self {
             PublicKey::Ed25519(_) => ED25519_TAG,
@@ -943,21 +1068,25 @@ impl Signature {
         let mut tag = [0u8; 1];
         hex::decode_to_slice(&input.as_ref()[..2], tag.as_mut())?;
 
-        match tag[0] {
-            ED25519_TAG => {
+        match SignatureScheme::from_tag(tag[0])? {
+            SignatureScheme::Ed25519 => {
                 let mut bytes = [0u8; Self::ED25519_LENGTH];
                 hex::decode_to_slice(&input.as_ref()[2..], bytes.as_mut())?;
                 Self::new_ed25519(bytes)
             }
-            SECP256K1_TAG => {
+            SignatureScheme::Secp256k1 => {
                 let mut bytes = [0u8; Self::SECP256K1_LENGTH];
                 hex::decode_to_slice(&input.as_ref()[2..], bytes.as_mut())?;
                 Self::new_secp256k1(bytes)
             }
-            _ => Err(Error::AsymmetricKey(format!(
-                "invalid tag.  Expected {} or {}, got {}",
-                ED25519_TAG, SECP256K1_TAG, tag[0]
-            ))),
+        }
+    }
+
+    /// Returns the scheme the signature was made with.
+    pub fn scheme(&self) -> SignatureScheme {
+        match self {
+            Signature::Ed25519(_) => SignatureScheme::Ed25519,
+            Signature::Secp256k1(_) => SignatureScheme::Secp256k1,
         }
     }
 
@@ -1083,37 +1212,109 @@ mod secp256k1_signature_serde {
     }
 }
 
-/// Signs the given message using the given key pair.
+/// Signs the given message using the given key pair, for the given purpose.
+///
+/// The message is prefixed with the purpose's domain-separation tag before signing.  The signature
+/// is recorded by the audit sink, if one is set.
 pub fn sign<T: AsRef<[u8]>, R: Rng + CryptoRng + ?Sized>(
     message: T,
     secret_key: &SecretKey,
     public_key: &PublicKey,
+    purpose: SigningPurpose,
+    rng: &mut R,
+) -> Signature {
+    audit::record(|| *public_key, || AuditOperation::Sign { purpose });
+    sign_raw(
+        &purpose.tag_message(message.as_ref()),
+        secret_key,
+        public_key,
+        rng,
+    )
+}
+
+/// Signs the given message as is, without a domain-separation tag.
+fn sign_raw<R: Rng + CryptoRng + ?Sized>(
+    message: &[u8],
+    secret_key: &SecretKey,
+    public_key: &PublicKey,
     rng: &mut R,
 ) -> Signature {
     match (secret_key, public_key) {
         (SecretKey::Ed25519(secret_key), PublicKey::Ed25519(public_key)) => {
             let expanded_secret_key = ExpandedSecretKey::from(secret_key);
-            let signature = expanded_secret_key.sign(message.as_ref(), public_key);
+            let signature = expanded_secret_key.sign(message, public_key);
             Signature::Ed25519(signature.to_bytes())
         }
         (SecretKey::Secp256k1(secret_key), PublicKey::Secp256k1(_public_key)) => {
             let signer = Secp256k1Signer::new(secret_key).expect("should create secp256k1 signer");
-            Signature::Secp256k1(signer.sign_with_rng(rng, message.as_ref()))
+            Signature::Secp256k1(signer.sign_with_rng(rng, message))
         }
         _ => panic!("secret and public key types must match"),
     }
 }
 
-/// Verifies the signature of the given message against the given public key.
+/// Returns an error if the data read from the key file at `path` is empty, or contains the start of
+/// a PEM block but not its end.
+///
+/// This distinguishes a file which was only partially written, e.g. because provisioning the key
+/// was interrupted, from one holding a malformed key.
+fn check_key_file_complete(path: &Path, data: &[u8]) -> Result<()> {
+    let text = String::from_utf8_lossy(data);
+    let is_empty = text.trim().is_empty();
+    let is_truncated = text
+        .find(PEM_BEGIN_MARKER)
+        .map_or(false, |begin| !text[begin..].contains(PEM_END_MARKER));
+    if is_empty || is_truncated {
+        return Err(Error::IncompleteKeyFile {
+            path: path.to_path_buf(),
+            bytes_read: data.len(),
+        });
+    }
+    Ok(())
+}
+
+/// Verifies the signature of the given message against the given public key, requiring it to have
+/// been made for the given purpose.
+///
+/// Successful verifications are remembered, up to the capacity set via
+/// [`set_verification_cache_capacity`], and not repeated.
 pub fn verify<T: AsRef<[u8]>>(
     message: T,
     signature: &Signature,
     public_key: &PublicKey,
+    purpose: SigningPurpose,
+) -> Result<()> {
+    let message = purpose.tag_message(message.as_ref());
+    VERIFICATION_CACHE.verify(message, signature, public_key, verify_uncached)
+}
+
+/// Sets the maximum number of successful signature verifications remembered by [`verify`].
+///
+/// Remembered verifications are not repeated.  If `capacity` is zero, nothing is remembered.
+pub fn set_verification_cache_capacity(capacity: usize) {
+    VERIFICATION_CACHE.set_capacity(capacity)
+}
+
+/// Returns the number of signatures verified on this thread without hitting the verification
+/// cache.
+#[cfg(test)]
+pub(crate) fn uncached_verifications() -> usize {
+    UNCACHED_VERIFICATIONS.with(|count| count.get())
+}
+
+/// Verifies the signature of the given message, already prefixed with its domain-separation tag,
+/// against the given public key, bypassing the verification cache.
+pub(super) fn verify_uncached(
+    message: &[u8],
+    signature: &Signature,
+    public_key: &PublicKey,
 ) -> Result<()> {
+    #[cfg(test)]
+    UNCACHED_VERIFICATIONS.with(|count| count.set(count.get() + 1));
     match (signature, public_key) {
         (Signature::Ed25519(signature), PublicKey::Ed25519(public_key)) => public_key
             .verify_strict(
-                message.as_ref(),
+                message,
                 &ed25519::Signature::from_bytes(signature).map_err(|_| {
                     Error::AsymmetricKey(format!(
                         "failed to construct Ed25519 signature from {:?}",
@@ -1130,11 +1331,9 @@ pub fn verify<T: AsRef<[u8]>>(
                 ))
             })?;
 
-            verifier
-                .verify(message.as_ref(), signature)
-                .map_err(|error| {
-                    Error::AsymmetricKey(format!("failed to verify secp256k1 signature: {}", error))
-                })
+            verifier.verify(message, signature).map_err(|error| {
+                Error::AsymmetricKey(format!("failed to verify secp256k1 signature: {}", error))
+            })
         }
         _ => Err(Error::AsymmetricKey(format!(
             "type mismatch between {} and {}",
@@ -1450,7 +1649,13 @@ MCowBQYDK2VwAyEAGb9ECWmEzf6FQbrBZ9w7lshQhqowtrbLDFw4rXAxZuE=
             let secret_key = SecretKey::random_ed25519(&mut rng);
             let public_key = PublicKey::from(&secret_key);
             let data = b"data";
-            let signature = sign(data, &secret_key, &public_key, &mut rng);
+            let signature = sign(
+                data,
+                &secret_key,
+                &public_key,
+                SigningPurpose::Deploy,
+                &mut rng,
+            );
             signature_hex_roundtrip(signature);
         }
 
@@ -1487,12 +1692,36 @@ MCowBQYDK2VwAyEAGb9ECWmEzf6FQbrBZ9w7lshQhqowtrbLDFw4rXAxZuE=
             let wrong_type_public_key = PublicKey::random_secp256k1(&mut rng);
 
             let message = b"message";
-            let signature = sign(message, &secret_key, &public_key, &mut rng);
+            let signature = sign(
+                message,
+                &secret_key,
+                &public_key,
+                SigningPurpose::Deploy,
+                &mut rng,
+            );
 
-            assert!(verify(message, &signature, &public_key).is_ok());
-            assert!(verify(message, &signature, &other_public_key).is_err());
-            assert!(verify(message, &signature, &wrong_type_public_key).is_err());
-            assert!(verify(&message[1..], &signature, &public_key).is_err());
+            assert!(verify(message, &signature, &public_key, SigningPurpose::Deploy).is_ok());
+            assert!(verify(
+                message,
+                &signature,
+                &other_public_key,
+                SigningPurpose::Deploy
+            )
+            .is_err());
+            assert!(verify(
+                message,
+                &signature,
+                &wrong_type_public_key,
+                SigningPurpose::Deploy
+            )
+            .is_err());
+            assert!(verify(
+                &message[1..],
+                &signature,
+                &public_key,
+                SigningPurpose::Deploy
+            )
+            .is_err());
         }
     }
 
@@ -1625,7 +1854,13 @@ kv+kBR5u4ISEAkuc2TFWQHX0Yj9oTB9fx9+vvQdxJOhMtu46kGo0Uw==
             let secret_key = SecretKey::random_secp256k1(&mut rng);
             let public_key = PublicKey::from(&secret_key);
             let data = b"data";
-            let signature = sign(data, &secret_key, &public_key, &mut rng);
+            let signature = sign(
+                data,
+                &secret_key,
+                &public_key,
+                SigningPurpose::Deploy,
+                &mut rng,
+            );
             super::signature_serialization_roundtrip(signature);
         }
 
@@ -1695,24 +1930,180 @@ kv+kBR5u4ISEAkuc2TFWQHX0Yj9oTB9fx9+vvQdxJOhMtu46kGo0Uw==
         let other_secp256k1_public_key = PublicKey::random_secp256k1(&mut rng);
 
         let message = b"message";
-        let ed25519_signature = sign(message, &ed25519_secret_key, &ed25519_public_key, &mut rng);
+        let ed25519_signature = sign(
+            message,
+            &ed25519_secret_key,
+            &ed25519_public_key,
+            SigningPurpose::Deploy,
+            &mut rng,
+        );
         let secp256k1_signature = sign(
             message,
             &secp256k1_secret_key,
             &secp256k1_public_key,
+            SigningPurpose::Deploy,
             &mut rng,
         );
 
-        assert!(verify(message, &ed25519_signature, &ed25519_public_key).is_ok());
-        assert!(verify(message, &secp256k1_signature, &secp256k1_public_key).is_ok());
+        assert!(verify(
+            message,
+            &ed25519_signature,
+            &ed25519_public_key,
+            SigningPurpose::Deploy
+        )
+        .is_ok());
+        assert!(verify(
+            message,
+            &secp256k1_signature,
+            &secp256k1_public_key,
+            SigningPurpose::Deploy
+        )
+        .is_ok());
 
-        assert!(verify(message, &ed25519_signature, &other_ed25519_public_key).is_err());
-        assert!(verify(message, &secp256k1_signature, &other_secp256k1_public_key).is_err());
+        assert!(verify(
+            message,
+            &ed25519_signature,
+            &other_ed25519_public_key,
+            SigningPurpose::Deploy
+        )
+        .is_err());
+        assert!(verify(
+            message,
+            &secp256k1_signature,
+            &other_secp256k1_public_key,
+            SigningPurpose::Deploy
+        )
+        .is_err());
 
-        assert!(verify(message, &ed25519_signature, &secp256k1_public_key).is_err());
-        assert!(verify(message, &secp256k1_signature, &ed25519_public_key).is_err());
+        assert!(verify(
+            message,
+            &ed25519_signature,
+            &secp256k1_public_key,
+            SigningPurpose::Deploy
+        )
+        .is_err());
+        assert!(verify(
+            message,
+            &secp256k1_signature,
+            &ed25519_public_key,
+            SigningPurpose::Deploy
+        )
+        .is_err());
+
+        assert!(verify(
+            &message[1..],
+            &ed25519_signature,
+            &ed25519_public_key,
+            SigningPurpose::Deploy
+        )
+        .is_err());
+        assert!(verify(
+            &message[1..],
+            &secp256k1_signature,
+            &secp256k1_public_key,
+            SigningPurpose::Deploy
+        )
+        .is_err());
+    }
+
+    #[test]
+    fn finality_signature_should_not_be_replayable_as_consensus_vote() {
+        let mut rng = TestRng::new();
+        let secret_key = SecretKey::random(&mut rng);
+        let public_key = PublicKey::from(&secret_key);
+
+        // Both finality signatures and consensus votes sign a 32-byte hash.
+        let block_hash = crate::crypto::hash::hash(b"block");
+        let finality_signature = sign(
+            block_hash,
+            &secret_key,
+            &public_key,
+            SigningPurpose::FinalitySignature,
+            &mut rng,
+        );
+        assert!(verify(
+            block_hash,
+            &finality_signature,
+            &public_key,
+            SigningPurpose::FinalitySignature
+        )
+        .is_ok());
+        assert!(verify(
+            block_hash,
+            &finality_signature,
+            &public_key,
+            SigningPurpose::ConsensusVote
+        )
+        .is_err());
+
+        // Nor is the untagged message signed.
+        let untagged = block_hash.as_ref();
+        assert!(verify_uncached(untagged, &finality_signature, &public_key).is_err());
+    }
+
+    #[test]
+    fn should_reject_untagged_signatures_for_every_purpose() {
+        let mut rng = TestRng::new();
+        let secret_key = SecretKey::random(&mut rng);
+        let public_key = PublicKey::from(&secret_key);
+
+        let message = b"signed before domain tags".as_ref();
+        let untagged_signature = sign_raw(message, &secret_key, &public_key, &mut rng);
+        for purpose in &[
+            SigningPurpose::Deploy,
+            SigningPurpose::FinalitySignature,
+            SigningPurpose::ConsensusVote,
+            SigningPurpose::HandshakeAttestation,
+            SigningPurpose::EmergencyRestart,
+        ] {
+            assert!(verify(message, &untagged_signature, &public_key, *purpose).is_err());
+        }
+    }
+
+    #[test]
+    fn should_report_incomplete_key_files() {
+        let mut rng = TestRng::new();
+        let tempdir = tempfile::tempdir().unwrap();
+        let assert_incomplete =
+            |error: Error, expected_path: &Path, expected_bytes_read: usize| match error {
+                Error::IncompleteKeyFile { path, bytes_read } => {
+                    assert_eq!(path, expected_path);
+                    assert_eq!(bytes_read, expected_bytes_read);
+                }
+                other => panic!("expected incomplete key file error, got {}", other),
+            };
+
+        let empty_path = tempdir.path().join("empty.pem");
+        std::fs::write(&empty_path, b"\n").unwrap();
+        assert_incomplete(
+            SecretKey::from_file(&empty_path).unwrap_err(),
+            &empty_path,
+            1,
+        );
+        assert_incomplete(
+            PublicKey::from_file(&empty_path).unwrap_err(),
+            &empty_path,
+            1,
+        );
+
+        // A file cut off in the middle of the PEM block.
+        let secret_key = SecretKey::random_ed25519(&mut rng);
+        let pem = secret_key.to_pem().unwrap();
+        let truncated = &pem.as_bytes()[..pem.len() / 2];
+        let truncated_path = tempdir.path().join("truncated.pem");
+        std::fs::write(&truncated_path, truncated).unwrap();
+        assert_incomplete(
+            SecretKey::from_file(&truncated_path).unwrap_err(),
+            &truncated_path,
+            truncated.len(),
+        );
 
-        assert!(verify(&message[1..], &ed25519_signature, &ed25519_public_key).is_err());
-        assert!(verify(&message[1..], &secp256k1_signature, &secp256k1_public_key).is_err());
+        // A complete but malformed file is still reported as a PEM error.
+        let malformed_path = tempdir.path().join("malformed.pem");
+        std::fs::write(&malformed_path, &pem.as_bytes()[1..]).unwrap();
+        assert!(matches!(
+            SecretKey::from_file(&malformed_path).unwrap_err(),
+            Error::FromPem(_)
+        ));
     }
 }
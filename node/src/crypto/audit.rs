@@ -0,0 +1,308 @@
+//! Audit logging of operations using secret keys.
+//!
+//! For compliance, every signature made and every secret key loaded from a file can be recorded by
+//! an [`AuditSink`], e.g. to a log separate from the node's regular one.  Entries identify the key
+//! by the fingerprint of its public key, and never contain any secret material.
+//!
+//! Auditing is disabled until a sink is set.  While disabled, an operation only checks an atomic
+//! flag: no entry is built, and no lock is taken.
+
+use std::{
+    fmt::{self, Display, Formatter},
+    fs::{File, OpenOptions},
+    io::{self, Write},
+    path::{Path, PathBuf},
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        mpsc::{self, Receiver, Sender},
+        Arc, Mutex, RwLock,
+    },
+    thread::{self, JoinHandle},
+};
+
+use lazy_static::lazy_static;
+use serde::Serialize;
+use tracing::{dispatcher, warn, Dispatch};
+
+use super::{
+    asymmetric_key::{PublicKey, SigningPurpose},
+    hash::{self, Digest},
+};
+use crate::types::Timestamp;
+
+/// Whether an audit sink is set, checked before doing any work for it.
+static AUDIT_ENABLED: AtomicBool = AtomicBool::new(false);
+
+lazy_static! {
+    /// The sink audit entries are recorded to, if any.
+    static ref AUDIT_SINK: RwLock<Option<Arc<dyn AuditSink>>> = RwLock::new(None);
+}
+
+/// An operation using a secret key.
+#[derive(Clone, Debug, PartialEq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum AuditOperation {
+    /// A message was signed.
+    Sign {
+        /// What the signature is for.
+        purpose: SigningPurpose,
+    },
+    /// A secret key was loaded from a file.
+    LoadKey {
+        /// The key file.
+        path: PathBuf,
+    },
+}
+
+/// A record of an operation using a secret key.
+#[derive(Clone, Debug, PartialEq, Serialize)]
+pub struct AuditEntry {
+    /// When the operation took place.
+    pub timestamp: Timestamp,
+    /// The hash of the public key of the secret key used.
+    pub key_fingerprint: Digest,
+    /// The operation.
+    pub operation: AuditOperation,
+}
+
+impl Display for AuditEntry {
+    fn fmt(&self, formatter: &mut Formatter) -> fmt::Result {
+        write!(
+            formatter,
+            "{} key {}: ",
+            self.timestamp, self.key_fingerprint
+        )?;
+        match &self.operation {
+            AuditOperation::Sign { purpose } => write!(formatter, "signed {}", purpose),
+            AuditOperation::LoadKey { path } => write!(formatter, "loaded from {}", path.display()),
+        }
+    }
+}
+
+/// A recipient of audit entries.
+pub trait AuditSink: Send + Sync {
+    /// Records `entry`.
+    ///
+    /// Called synchronously by the operation being audited, so this should not block for long.
+    fn record(&self, entry: AuditEntry);
+}
+
+/// An audit sink appending each entry as a line of JSON to a file.
+///
+/// The entries are written by a thread of its own, so that the operations being audited don't wait
+/// for the file.  Dropping the sink waits until all entries recorded so far have been written.
+#[derive(Debug)]
+pub struct FileAuditSink {
+    /// The channel to the writer thread, closed when the sink is dropped.
+    entries: Mutex<Option<Sender<AuditEntry>>>,
+    /// The writer thread.
+    writer: Option<JoinHandle<()>>,
+}
+
+impl FileAuditSink {
+    /// Opens the audit log at `path` for appending, creating it if necessary, and starts the thread
+    /// writing to it.
+    ///
+    /// The writer thread logs to the subscriber which is the default on the calling thread.
+    pub fn open<P: AsRef<Path>>(path: P) -> io::Result<Self> {
+        let mut file = OpenOptions::new().create(true).append(true).open(path)?;
+        let (sender, receiver) = mpsc::channel();
+        let dispatch = dispatcher::get_default(Dispatch::clone);
+        let writer = thread::Builder::new()
+            .name("audit-log".to_string())
+            .spawn(move || {
+                dispatcher::with_default(&dispatch, || write_entries(&mut file, receiver))
+            })?;
+        Ok(FileAuditSink {
+            entries: Mutex::new(Some(sender)),
+            writer: Some(writer),
+        })
+    }
+}
+
+impl AuditSink for FileAuditSink {
+    fn record(&self, entry: AuditEntry) {
+        let entries = self.entries.lock().expect("lock poisoned");
+        if let Some(Err(error)) = entries.as_ref().map(|sender| sender.send(entry)) {
+            warn!(entry = %error.0, "audit log writer has stopped, dropping entry");
+        }
+    }
+}
+
+impl Drop for FileAuditSink {
+    fn drop(&mut self) {
+        // Closing the channel lets the writer finish once it has written the remaining entries.
+        if let Ok(mut entries) = self.entries.lock() {
+            *entries = None;
+        }
+        if let Some(writer) = self.writer.take() {
+            let _ = writer.join();
+        }
+    }
+}
+
+/// Runs the writer thread of a `FileAuditSink`, appending `entries` to `file` until the channel is
+/// closed.
+fn write_entries(file: &mut File, entries: Receiver<AuditEntry>) {
+    for entry in entries {
+        let mut line = match serde_json::to_vec(&entry) {
+            Ok(line) => line,
+            Err(error) => {
+                warn!(%error, %entry, "failed to serialize audit log entry");
+                continue;
+            }
+        };
+        line.push(b'\n');
+        if let Err(error) = file.write_all(&line) {
+            warn!(%error, %entry, "failed to write audit log entry");
+        }
+    }
+}
+
+/// Sets the sink all signatures and secret key loads are recorded to, or disables auditing if
+/// `sink` is `None`.
+pub fn set_audit_sink(sink: Option<Arc<dyn AuditSink>>) {
+    let mut audit_sink = AUDIT_SINK.write().expect("lock poisoned");
+    AUDIT_ENABLED.store(sink.is_some(), Ordering::SeqCst);
+    *audit_sink = sink;
+}
+
+/// Records `operation` using the secret key of `public_key`, if auditing is enabled.
+///
+/// The arguments are only evaluated if auditing is enabled.
+#[inline]
+pub(super) fn record<K, O>(public_key: K, operation: O)
+where
+    K: FnOnce() -> PublicKey,
+    O: FnOnce() -> AuditOperation,
+{
+    if !AUDIT_ENABLED.load(Ordering::Relaxed) {
+        return;
+    }
+    let sink = match AUDIT_SINK.read().expect("lock poisoned").as_ref() {
+        Some(sink) => Arc::clone(sink),
+        None => return,
+    };
+    sink.record(AuditEntry {
+        timestamp: Timestamp::now(),
+        key_fingerprint: fingerprint(&public_key()),
+        operation: operation(),
+    });
+}
+
+/// Returns the fingerprint identifying `public_key` in audit entries.
+pub fn fingerprint(public_key: &PublicKey) -> Digest {
+    hash::hash(public_key)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+
+    use super::*;
+    use crate::{
+        crypto::asymmetric_key::{self, SecretKey},
+        testing::TestRng,
+    };
+
+    /// A sink keeping all recorded entries in memory.
+    #[derive(Default)]
+    struct RecordingSink(Mutex<Vec<AuditEntry>>);
+
+    impl AuditSink for RecordingSink {
+        fn record(&self, entry: AuditEntry) {
+            self.0.lock().unwrap().push(entry);
+        }
+    }
+
+    #[test]
+    fn should_record_fingerprinted_entry_without_secret_material() {
+        let mut rng = TestRng::new();
+        let secret_key = SecretKey::random(&mut rng);
+        let public_key = PublicKey::from(&secret_key);
+        let key_fingerprint = fingerprint(&public_key);
+
+        let sink = Arc::new(RecordingSink::default());
+        set_audit_sink(Some(sink.clone()));
+        let _ = asymmetric_key::sign(
+            b"message",
+            &secret_key,
+            &public_key,
+            SigningPurpose::Deploy,
+            &mut rng,
+        );
+        set_audit_sink(None);
+
+        // Other tests may sign concurrently, so only consider the entries for our key.
+        let entries: Vec<_> = sink
+            .0
+            .lock()
+            .unwrap()
+            .iter()
+            .filter(|entry| entry.key_fingerprint == key_fingerprint)
+            .cloned()
+            .collect();
+        assert_eq!(entries.len(), 1);
+        assert_eq!(
+            entries[0].operation,
+            AuditOperation::Sign {
+                purpose: SigningPurpose::Deploy
+            }
+        );
+
+        // Neither the secret bytes nor their hex encoding appear in the entry.
+        let secret = secret_key.as_secret_slice();
+        let secret_json = serde_json::to_string(secret).unwrap();
+        let secret_encodings = [
+            hex::encode(secret),
+            hex::encode_upper(secret),
+            secret_json
+                .trim_matches(|c| c == '[' || c == ']')
+                .to_string(),
+        ];
+        let serialized = serde_json::to_string(&entries[0]).unwrap();
+        let displayed = entries[0].to_string();
+        for encoded in &secret_encodings {
+            assert!(!serialized.contains(encoded.as_str()));
+            assert!(!displayed.contains(encoded.as_str()));
+        }
+    }
+
+    #[test]
+    fn should_append_entries_to_file_in_background() {
+        let mut rng = TestRng::new();
+        let secret_key = SecretKey::random(&mut rng);
+        let key_fingerprint = fingerprint(&PublicKey::from(&secret_key));
+        let temp_dir = tempfile::tempdir().expect("should get tempdir");
+        let path = temp_dir.path().join("audit.log");
+        fs::write(&path, b"earlier entry\n").expect("should write file");
+
+        let purposes = [SigningPurpose::Deploy, SigningPurpose::FinalitySignature];
+        let sink = FileAuditSink::open(&path).expect("should open audit log");
+        for &purpose in &purposes {
+            sink.record(AuditEntry {
+                timestamp: Timestamp::now(),
+                key_fingerprint,
+                operation: AuditOperation::Sign { purpose },
+            });
+        }
+        // Dropping the sink waits for the entries to be written.
+        drop(sink);
+
+        let contents = fs::read_to_string(&path).expect("should read audit log");
+        let lines: Vec<_> = contents.lines().collect();
+        assert_eq!(lines.len(), 3);
+        assert_eq!(lines[0], "earlier entry");
+        for (line, purpose) in lines[1..].iter().zip(&purposes) {
+            let entry: serde_json::Value = serde_json::from_str(line).expect("should parse entry");
+            assert_eq!(
+                entry["key_fingerprint"],
+                serde_json::to_value(key_fingerprint).unwrap()
+            );
+            assert_eq!(
+                entry["operation"]["sign"]["purpose"],
+                serde_json::to_value(purpose).unwrap()
+            );
+        }
+    }
+}
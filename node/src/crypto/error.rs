@@ -1,4 +1,4 @@
-use std::result;
+use std::{path::PathBuf, result};
 
 use base64::DecodeError;
 use hex::FromHexError;
@@ -17,6 +17,12 @@ pub enum Error {
     #[error("asymmetric key error: {0}")]
     AsymmetricKey(String),
 
+    /// The tag of a key or signature does not identify a supported signature scheme.
+    #[error(
+        "unsupported signature scheme with tag {0}, expected 1 for Ed25519 or 2 for secp256k1"
+    )]
+    UnsupportedSignatureScheme(u8),
+
     /// Error resulting when decoding a type from a hex-encoded representation.
     #[error("parsing from hex: {0}")]
     FromHex(#[from] FromHexError),
@@ -29,6 +35,19 @@ pub enum Error {
     #[error("public key load failed: {0}")]
     PublicKeyLoad(ReadFileError),
 
+    /// A key file is empty or ends before the end of its PEM block, e.g. because writing it was
+    /// interrupted.
+    #[error(
+        "key file {} is incomplete: only {bytes_read} bytes could be read",
+        .path.display()
+    )]
+    IncompleteKeyFile {
+        /// The path of the key file.
+        path: PathBuf,
+        /// The number of bytes read from the file.
+        bytes_read: usize,
+    },
+
     /// Error resulting when decoding a type from a base64 representation.
     #[error("decoding error: {0}")]
     FromBase64(#[from] DecodeError),
@@ -0,0 +1,73 @@
+//! Deterministic generation of unique IDs, e.g. for deploy nonces.
+//!
+//! IDs are drawn from a `ChaCha20Rng`, the same cryptographically secure generator the node uses,
+//! so they are as hard to predict or to collide as any other 256-bit value drawn from it.  Seeding
+//! the generator with a fixed seed yields the same sequence of IDs every time, which keeps test
+//! scenarios reproducible.
+
+use rand::{CryptoRng, Error as RandError, Rng, RngCore, SeedableRng};
+use rand_chacha::ChaCha20Rng;
+
+use super::hash::Digest;
+
+/// A seedable generator of unique IDs.
+#[derive(Clone, Debug)]
+pub struct IdGenerator {
+    rng: ChaCha20Rng,
+}
+
+impl IdGenerator {
+    /// Constructs a generator which always yields the same sequence of IDs for the same `seed`.
+    pub fn from_seed(seed: [u8; 32]) -> Self {
+        IdGenerator {
+            rng: ChaCha20Rng::from_seed(seed),
+        }
+    }
+
+    /// Constructs a generator seeded from `rng`, e.g. from the reactor's random number generator.
+    pub fn from_rng<R: RngCore + CryptoRng + ?Sized>(rng: &mut R) -> Result<Self, RandError> {
+        Ok(IdGenerator {
+            rng: ChaCha20Rng::from_rng(rng)?,
+        })
+    }
+
+    /// Returns the next ID.
+    pub fn next_id(&mut self) -> Digest {
+        Digest::from(self.rng.gen::<[u8; Digest::LENGTH]>())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashSet;
+
+    use super::*;
+
+    #[test]
+    fn should_yield_same_ids_for_same_seed_only() {
+        let ids = |generator: &mut IdGenerator| -> Vec<Digest> {
+            (0..100).map(|_| generator.next_id()).collect()
+        };
+
+        let seed = [7; 32];
+        let first = ids(&mut IdGenerator::from_seed(seed));
+        assert_eq!(first, ids(&mut IdGenerator::from_seed(seed)));
+        assert_eq!(first.iter().collect::<HashSet<_>>().len(), first.len());
+
+        let mut other_seed = seed;
+        other_seed[0] = 8;
+        let other = ids(&mut IdGenerator::from_seed(other_seed));
+        assert!(first
+            .iter()
+            .zip(&other)
+            .all(|(id, other_id)| id != other_id));
+
+        // Generators seeded from the node's RNG are reproducible from that RNG's seed.
+        let from_rng = ids(&mut IdGenerator::from_rng(&mut ChaCha20Rng::seed_from_u64(1)).unwrap());
+        assert_eq!(
+            from_rng,
+            ids(&mut IdGenerator::from_rng(&mut ChaCha20Rng::seed_from_u64(1)).unwrap())
+        );
+        assert_ne!(from_rng, first);
+    }
+}
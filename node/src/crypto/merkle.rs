@@ -0,0 +1,183 @@
+//! Binary Merkle trees over hash digests.
+//!
+//! Leaves and inner nodes are hashed with distinct prefixes, so that an inner node can never be
+//! passed off as a leaf.  If a level of the tree has an odd number of nodes, the last one is
+//! carried up to the next level unchanged rather than being paired with itself.
+
+use serde::{Deserialize, Serialize};
+
+use super::hash::{self, Digest};
+
+/// Prefix of the data hashed to form a leaf node.
+const LEAF_PREFIX: u8 = 0;
+/// Prefix of the data hashed to form an inner node.
+const NODE_PREFIX: u8 = 1;
+
+fn hash_leaf(leaf: &Digest) -> Digest {
+    let mut data = Vec::with_capacity(1 + Digest::LENGTH);
+    data.push(LEAF_PREFIX);
+    data.extend_from_slice(leaf.as_ref());
+    hash::hash(data)
+}
+
+fn hash_node(left: &Digest, right: &Digest) -> Digest {
+    let mut data = Vec::with_capacity(1 + 2 * Digest::LENGTH);
+    data.push(NODE_PREFIX);
+    data.extend_from_slice(left.as_ref());
+    data.extend_from_slice(right.as_ref());
+    hash::hash(data)
+}
+
+/// Returns the level of the tree above `level`.
+fn next_level(level: &[Digest]) -> Vec<Digest> {
+    level
+        .chunks(2)
+        .map(|pair| match pair {
+            [left, right] => hash_node(left, right),
+            [single] => *single,
+            _ => unreachable!("chunks should have one or two elements"),
+        })
+        .collect()
+}
+
+/// Returns the Merkle root of `leaves`.
+///
+/// The root of an empty list is the hash of no data.
+pub fn root(leaves: &[Digest]) -> Digest {
+    if leaves.is_empty() {
+        return hash::hash(b"");
+    }
+    let mut level: Vec<Digest> = leaves.iter().map(hash_leaf).collect();
+    while level.len() > 1 {
+        level = next_level(&level);
+    }
+    level[0]
+}
+
+/// A proof that a leaf is part of the tree with a given Merkle root.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct MerkleProof {
+    /// The position of the leaf among all leaves.
+    index: u64,
+    /// The total number of leaves.
+    leaf_count: u64,
+    /// The sibling nodes on the path from the leaf to the root, starting at the bottom.
+    siblings: Vec<Digest>,
+}
+
+impl MerkleProof {
+    /// Constructs the proof that the leaf at `index` is part of the tree over `leaves`.
+    ///
+    /// Returns `None` if `index` is out of bounds.
+    pub fn new(leaves: &[Digest], index: usize) -> Option<Self> {
+        if index >= leaves.len() {
+            return None;
+        }
+        let mut level: Vec<Digest> = leaves.iter().map(hash_leaf).collect();
+        let mut position = index;
+        let mut siblings = Vec::new();
+        while level.len() > 1 {
+            if let Some(sibling) = level.get(position ^ 1) {
+                siblings.push(*sibling);
+            }
+            level = next_level(&level);
+            position /= 2;
+        }
+        Some(MerkleProof {
+            index: index as u64,
+            leaf_count: leaves.len() as u64,
+            siblings,
+        })
+    }
+
+    /// Returns the position of the leaf among all leaves.
+    pub fn index(&self) -> u64 {
+        self.index
+    }
+
+    /// Returns the total number of leaves.
+    pub fn leaf_count(&self) -> u64 {
+        self.leaf_count
+    }
+
+    /// Returns the sibling nodes on the path from the leaf to the root, starting at the bottom.
+    pub fn siblings(&self) -> &[Digest] {
+        &self.siblings
+    }
+
+    /// Returns whether this proves that `leaf` is part of the tree with the given `root`.
+    pub fn verify(&self, leaf: &Digest, root: &Digest) -> bool {
+        if self.index >= self.leaf_count {
+            return false;
+        }
+        let mut node = hash_leaf(leaf);
+        let mut position = self.index;
+        let mut level_len = self.leaf_count;
+        let mut siblings = self.siblings.iter();
+        while level_len > 1 {
+            if position ^ 1 < level_len {
+                let sibling = match siblings.next() {
+                    Some(sibling) => sibling,
+                    None => return false,
+                };
+                node = if position % 2 == 0 {
+                    hash_node(&node, sibling)
+                } else {
+                    hash_node(sibling, &node)
+                };
+            }
+            position /= 2;
+            level_len = (level_len + 1) / 2;
+        }
+        siblings.next().is_none() && node == *root
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::TestRng;
+
+    #[test]
+    fn should_verify_proofs_for_all_leaves() {
+        let mut rng = TestRng::new();
+        for leaf_count in 1..=9 {
+            let leaves: Vec<Digest> = (0..leaf_count).map(|_| Digest::random(&mut rng)).collect();
+            let root = root(&leaves);
+            for (index, leaf) in leaves.iter().enumerate() {
+                let proof = MerkleProof::new(&leaves, index).expect("index should be in bounds");
+                assert!(proof.verify(leaf, &root));
+                assert!(!proof.verify(&Digest::random(&mut rng), &root));
+            }
+            assert!(MerkleProof::new(&leaves, leaf_count).is_none());
+        }
+    }
+
+    #[test]
+    fn should_reject_tampered_proof() {
+        let mut rng = TestRng::new();
+        let leaves: Vec<Digest> = (0..5).map(|_| Digest::random(&mut rng)).collect();
+        let root = root(&leaves);
+        let proof = MerkleProof::new(&leaves, 2).unwrap();
+
+        let mut wrong_index = proof.clone();
+        wrong_index.index = 3;
+        assert!(!wrong_index.verify(&leaves[2], &root));
+
+        let mut extra_sibling = proof.clone();
+        extra_sibling.siblings.push(Digest::random(&mut rng));
+        assert!(!extra_sibling.verify(&leaves[2], &root));
+
+        let mut missing_sibling = proof;
+        missing_sibling.siblings.pop();
+        assert!(!missing_sibling.verify(&leaves[2], &root));
+    }
+
+    #[test]
+    fn should_not_confuse_inner_nodes_with_leaves() {
+        let mut rng = TestRng::new();
+        let leaves: Vec<Digest> = (0..2).map(|_| Digest::random(&mut rng)).collect();
+        let inner = hash_node(&hash_leaf(&leaves[0]), &hash_leaf(&leaves[1]));
+        assert_ne!(root(&leaves), root(&[inner]));
+    }
+}
@@ -0,0 +1,207 @@
+//! Cache of successful signature verifications.
+//!
+//! The same consensus message or finality signature is often verified by several components.
+//! Successful verifications are memoized in a bounded least-recently-used cache, keyed by the hash
+//! of the message, the signature and the public key, so that repeating them only costs a hash and
+//! a lookup.  Failed verifications are never cached, so an invalid signature can't poison the cache
+//! for a later valid one.
+
+use std::sync::Mutex;
+
+use linked_hash_map::LinkedHashMap;
+
+use super::{
+    asymmetric_key::{PublicKey, Signature},
+    hash::{self, Digest},
+    Result,
+};
+
+/// A message hash, signature and public key which have been verified successfully.
+type Verified = (Digest, Signature, PublicKey);
+
+/// A bounded least-recently-used cache of successful signature verifications.
+#[derive(Debug)]
+pub(super) struct VerificationCache {
+    inner: Mutex<Inner>,
+}
+
+#[derive(Debug)]
+struct Inner {
+    /// The maximum number of cached verifications.  If zero, nothing is cached.
+    capacity: usize,
+    /// The cached verifications, least recently used first.
+    entries: LinkedHashMap<Verified, ()>,
+}
+
+impl VerificationCache {
+    /// Creates an empty cache holding at most `capacity` verifications.
+    pub(super) fn new(capacity: usize) -> Self {
+        VerificationCache {
+            inner: Mutex::new(Inner {
+                capacity,
+                entries: LinkedHashMap::new(),
+            }),
+        }
+    }
+
+    /// Sets the maximum number of cached verifications, evicting the least recently used ones if
+    /// there are more.
+    pub(super) fn set_capacity(&self, capacity: usize) {
+        let mut inner = self.inner.lock().expect("should lock");
+        inner.capacity = capacity;
+        inner.evict();
+    }
+
+    /// Verifies `signature` of `message` against `public_key` using `verify`, unless the same
+    /// verification has succeeded before.
+    pub(super) fn verify<T, F>(
+        &self,
+        message: T,
+        signature: &Signature,
+        public_key: &PublicKey,
+        verify: F,
+    ) -> Result<()>
+    where
+        T: AsRef<[u8]>,
+        F: FnOnce(&[u8], &Signature, &PublicKey) -> Result<()>,
+    {
+        if self.inner.lock().expect("should lock").capacity == 0 {
+            return verify(message.as_ref(), signature, public_key);
+        }
+
+        let key = (hash::hash(message.as_ref()), *signature, *public_key);
+        if self
+            .inner
+            .lock()
+            .expect("should lock")
+            .entries
+            .get_refresh(&key)
+            .is_some()
+        {
+            return Ok(());
+        }
+
+        // The lock is not held while verifying, so that verifications can run in parallel.
+        verify(message.as_ref(), signature, public_key)?;
+        let mut inner = self.inner.lock().expect("should lock");
+        let _ = inner.entries.insert(key, ());
+        inner.evict();
+        Ok(())
+    }
+}
+
+impl Inner {
+    /// Evicts the least recently used verifications until at most `capacity` are left.
+    fn evict(&mut self) {
+        while self.entries.len() > self.capacity {
+            let _ = self.entries.pop_front();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::cell::Cell;
+
+    use super::{
+        super::{
+            asymmetric_key::{self, SecretKey, SigningPurpose},
+            Error,
+        },
+        *,
+    };
+    use crate::testing::TestRng;
+
+    /// Returns a `verify` function for deploy signatures which counts its calls in `calls`.
+    fn counting(calls: &Cell<usize>) -> impl Fn(&[u8], &Signature, &PublicKey) -> Result<()> + '_ {
+        move |message, signature, public_key| {
+            calls.set(calls.get() + 1);
+            let message = SigningPurpose::Deploy.tag_message(message);
+            asymmetric_key::verify_uncached(&message, signature, public_key)
+        }
+    }
+
+    #[test]
+    fn should_skip_repeated_successful_verifications_only() {
+        let mut rng = TestRng::new();
+        let secret_key = SecretKey::random(&mut rng);
+        let public_key = PublicKey::from(&secret_key);
+        let message = b"finalized block";
+        let signature = asymmetric_key::sign(
+            message,
+            &secret_key,
+            &public_key,
+            SigningPurpose::Deploy,
+            &mut rng,
+        );
+        let cache = VerificationCache::new(10);
+        let calls = Cell::new(0);
+
+        // The first verification takes the expensive path, repeating it doesn't.
+        for _ in 0..3 {
+            cache
+                .verify(message, &signature, &public_key, counting(&calls))
+                .expect("signature should be valid");
+        }
+        assert_eq!(calls.get(), 1);
+
+        // Failures are not cached, so they are verified again every time.
+        for _ in 0..3 {
+            let result = cache.verify(b"other message", &signature, &public_key, counting(&calls));
+            assert!(matches!(result, Err(Error::AsymmetricKey(_))));
+        }
+        assert_eq!(calls.get(), 4);
+        cache
+            .verify(message, &signature, &public_key, counting(&calls))
+            .expect("signature should be valid");
+        assert_eq!(calls.get(), 4);
+    }
+
+    #[test]
+    fn should_evict_least_recently_used_verifications() {
+        let mut rng = TestRng::new();
+        let secret_key = SecretKey::random(&mut rng);
+        let public_key = PublicKey::from(&secret_key);
+        let messages: Vec<[u8; 1]> = (0..3).map(|index| [index]).collect();
+        let signatures: Vec<Signature> = messages
+            .iter()
+            .map(|message| {
+                asymmetric_key::sign(
+                    message,
+                    &secret_key,
+                    &public_key,
+                    SigningPurpose::Deploy,
+                    &mut rng,
+                )
+            })
+            .collect();
+        let cache = VerificationCache::new(2);
+        let calls = Cell::new(0);
+        let verify = |index: usize| {
+            cache
+                .verify(
+                    messages[index],
+                    &signatures[index],
+                    &public_key,
+                    counting(&calls),
+                )
+                .expect("signature should be valid");
+        };
+
+        verify(0);
+        verify(1);
+        // Using the first verification again makes the second one the least recently used.
+        verify(0);
+        verify(2);
+        assert_eq!(calls.get(), 3);
+        verify(0);
+        assert_eq!(calls.get(), 3);
+        verify(1);
+        assert_eq!(calls.get(), 4);
+
+        // Without capacity, nothing is cached.
+        cache.set_capacity(0);
+        verify(1);
+        assert_eq!(calls.get(), 5);
+    }
+}
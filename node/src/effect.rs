@@ -63,10 +63,14 @@ pub mod requests;
 
 use std::{
     any::type_name,
-    collections::{HashMap, HashSet},
+    collections::{BTreeMap, HashMap, HashSet},
     fmt::{self, Debug, Display, Formatter},
     future::Future,
     net::SocketAddr,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc,
+    },
     time::{Duration, Instant},
 };
 
@@ -83,18 +87,23 @@ use casper_execution_engine::{
         },
         execution,
     },
-    shared::{additive_map::AdditiveMap, transform::Transform},
+    shared::{additive_map::AdditiveMap, motes::Motes, transform::Transform},
     storage::global_state::CommitResult,
 };
 use casper_types::Key;
 
 use crate::{
     components::{
-        consensus::{BlockContext, EraId},
+        consensus::{BlockContext, EraId, ParticipationReport},
+        deploy_acceptor,
+        deploy_buffer::{DeployCandidate, PendingDeploy},
         fetcher::FetchResult,
-        small_network::GossipedAddress,
+        load_shedder::ShedLevel,
+        proposal_builder::Proposal,
+        small_network::{GossipedAddress, PeerLatency},
         storage::{
-            DeployHashes, DeployHeaderResults, DeployMetadata, DeployResults, StorageType, Value,
+            DeployHashes, DeployHeaderResults, DeployMetadata, DeployResults, EraSummary,
+            StorageFailure, StorageType, Value,
         },
     },
     crypto::{
@@ -104,19 +113,22 @@ use crate::{
     reactor::{EventQueueHandle, QueueKind},
     types::{
         json_compatibility::ExecutionResult, Block, BlockHash, BlockHeader, BlockLike, Deploy,
-        DeployHash, FinalizedBlock, Item, ProtoBlock,
+        DeployHash, FinalitySignature, FinalizedBlock, Item, ProtoBlock, ProtoBlockHash,
+        ShutdownReason, Timestamp,
     },
     utils::Source,
     Chainspec,
 };
 use announcements::{
-    ApiServerAnnouncement, BlockExecutorAnnouncement, ConsensusAnnouncement,
-    DeployAcceptorAnnouncement, GossiperAnnouncement, NetworkAnnouncement,
+    ApiServerAnnouncement, BlockExecutorAnnouncement, ConsensusAnnouncement, ControlAnnouncement,
+    DeployAcceptorAnnouncement, DeployBufferAnnouncement, FinalitySignatureAnnouncement,
+    GossiperAnnouncement, LinearChainAnnouncement, LoadShedderAnnouncement, NetworkAnnouncement,
+    StorageAnnouncement,
 };
 use requests::{
     BlockExecutorRequest, BlockValidationRequest, ConsensusRequest, ContractRuntimeRequest,
     DeployBufferRequest, FetcherRequest, LinearChainRequest, MetricsRequest, NetworkInfoRequest,
-    NetworkRequest, StorageRequest,
+    NetworkRequest, ProposalBuilderRequest, StorageRequest,
 };
 
 /// A pinned, boxed future that produces one or more events.
@@ -290,6 +302,29 @@ where
     }
 }
 
+/// A handle to a repeating schedule created by `EffectBuilder::schedule_repeating`.
+///
+/// Clones of the handle refer to the same schedule, so it can be cancelled from anywhere.
+#[derive(Clone, Debug, Default)]
+pub(crate) struct RepeatingSchedule(Arc<AtomicBool>);
+
+impl RepeatingSchedule {
+    /// Creates a new, not yet cancelled schedule handle.
+    pub(crate) fn new() -> Self {
+        Self::default()
+    }
+
+    /// Cancels the schedule. No more events will be produced.
+    pub(crate) fn cancel(&self) {
+        self.0.store(true, Ordering::SeqCst)
+    }
+
+    /// Returns whether the schedule has been cancelled.
+    pub(crate) fn is_cancelled(&self) -> bool {
+        self.0.load(Ordering::SeqCst)
+    }
+}
+
 /// A builder for [`Effect`](type.Effect.html)s.
 ///
 /// Provides methods allowing the creation of effects which need to be scheduled
@@ -357,9 +392,39 @@ impl<REv> EffectBuilder<REv> {
 
     /// Reports a fatal error.
     ///
-    /// Usually causes the node to cease operations quickly and exit/crash.
-    pub async fn fatal<M: Display + ?Sized>(self, file: &str, line: u32, msg: &M) {
-        panic!("fatal error [{}:{}]: {}", file, line, msg);
+    /// Causes the node to shut down with a [`ShutdownReason::FatalError`].
+    pub async fn fatal<M: Display + ?Sized>(self, file: &str, line: u32, msg: &M)
+    where
+        REv: From<ControlAnnouncement>,
+    {
+        self.request_shutdown(ShutdownReason::FatalError(format!(
+            "{} [{}:{}]",
+            msg, file, line
+        )))
+        .await
+    }
+
+    /// Requests the node to shut down for `reason`, after an error it can't recover from.
+    ///
+    /// The reactor hands the request to its runner, which shuts the node down.
+    pub(crate) async fn request_shutdown(self, reason: ShutdownReason)
+    where
+        REv: From<ControlAnnouncement>,
+    {
+        error!(%reason, "requesting shutdown");
+        self.0
+            .schedule(ControlAnnouncement::FatalError(reason), QueueKind::Regular)
+            .await;
+    }
+
+    /// Returns the number of events currently queued.
+    pub(crate) fn event_queue_depth(self) -> usize {
+        self.0.event_queue_depth()
+    }
+
+    /// Returns whether the reactor is draining its event queue on shutdown.
+    pub(crate) fn is_shutting_down(self) -> bool {
+        self.0.is_sealed()
     }
 
     /// Sets a timeout.
@@ -369,6 +434,64 @@ impl<REv> EffectBuilder<REv> {
         Instant::now() - then
     }
 
+    /// Delivers `event` after `delay`.
+    ///
+    /// Unlike `set_timeout`, the event is put directly onto the regular queue rather than being
+    /// routed back through the requesting component's effect, so it is delivered even if the
+    /// component's other effects are dropped.  Delays are driven by the tokio clock, which tests
+    /// pause and advance manually, so events due earlier are always delivered first.
+    pub(crate) async fn delay<Ev>(self, delay: Duration, event: Ev)
+    where
+        REv: From<Ev>,
+    {
+        tokio::time::delay_for(delay).await;
+        self.0.schedule(event, QueueKind::Regular).await
+    }
+
+    /// Produces an event created by `make_event` every `interval`, until `schedule` is cancelled.
+    ///
+    /// The events are put directly onto the regular queue. The returned future completes at the
+    /// first tick after the schedule has been cancelled.
+    pub(crate) async fn schedule_repeating<Ev, F>(
+        self,
+        interval: Duration,
+        schedule: RepeatingSchedule,
+        make_event: F,
+    ) where
+        REv: From<Ev>,
+        F: Fn() -> Ev + Send,
+    {
+        loop {
+            tokio::time::delay_for(interval).await;
+            if schedule.is_cancelled() {
+                break;
+            }
+            self.0.schedule(make_event(), QueueKind::Regular).await;
+        }
+    }
+
+    /// Runs `first` and `second` concurrently, yielding both results together once both have
+    /// completed.
+    ///
+    /// A result is `None` if it did not become available within `timeout`, counted from the start
+    /// of the join.  Responses to requests arriving after the timeout are discarded.
+    pub(crate) async fn join<A, B>(
+        self,
+        timeout: Duration,
+        first: A,
+        second: B,
+    ) -> (Option<A::Output>, Option<B::Output>)
+    where
+        A: Future,
+        B: Future,
+    {
+        let (first, second) = futures::join!(
+            tokio::time::timeout(timeout, first),
+            tokio::time::timeout(timeout, second)
+        );
+        (first.ok(), second.ok())
+    }
+
     /// Retrieve a snapshot of the nodes current metrics formatted as string.
     ///
     /// If an error occurred producing the metrics, `None` is returned.
@@ -427,10 +550,27 @@ impl<REv> EffectBuilder<REv> {
         .await
     }
 
+    /// Broadcasts a network message to validators only.
+    ///
+    /// Sends the message to all connected peers which are validators in the current era, skipping
+    /// all other peers.
+    pub async fn broadcast_to_validators<I, P>(self, payload: P)
+    where
+        REv: From<NetworkRequest<I, P>>,
+    {
+        self.make_request(
+            |responder| NetworkRequest::BroadcastToValidators { payload, responder },
+            QueueKind::Network,
+        )
+        .await
+    }
+
     /// Gossips a network message.
     ///
     /// A low-level "gossip" function, selects `count` randomly chosen nodes on the network,
-    /// excluding the indicated ones, and sends each a copy of the message.
+    /// excluding the indicated ones, and sends each a copy of the message.  Nodes are chosen with
+    /// likelihoods proportional to their `weights`, or uniformly if `weights` is empty.  If
+    /// `spread_localities` is set, nodes in distinct localities are preferred.
     ///
     /// Returns the IDs of the chosen nodes.
     pub async fn gossip_message<I, P>(
@@ -438,6 +578,8 @@ impl<REv> EffectBuilder<REv> {
         payload: P,
         count: usize,
         exclude: HashSet<I>,
+        weights: HashMap<I, f64>,
+        spread_localities: bool,
     ) -> HashSet<I>
     where
         REv: From<NetworkRequest<I, P>>,
@@ -449,6 +591,8 @@ impl<REv> EffectBuilder<REv> {
                 payload,
                 count,
                 exclude,
+                weights,
+                spread_localities,
                 responder,
             },
             QueueKind::Network,
@@ -469,6 +613,19 @@ impl<REv> EffectBuilder<REv> {
         .await
     }
 
+    /// Gets the latest round-trip times measured to network peers.
+    pub(crate) async fn network_peer_latencies<I>(self) -> HashMap<I, PeerLatency>
+    where
+        REv: From<NetworkInfoRequest<I>>,
+        I: Send + 'static,
+    {
+        self.make_request(
+            |responder| NetworkInfoRequest::GetPeerLatencies { responder },
+            QueueKind::Api,
+        )
+        .await
+    }
+
     /// Announces that a network message has been received.
     pub(crate) async fn announce_message_received<I, P>(self, sender: I, payload: P)
     where
@@ -508,6 +665,40 @@ impl<REv> EffectBuilder<REv> {
             .await;
     }
 
+    /// Announces that a connection to or from a peer has been established.
+    pub(crate) async fn announce_peer_connected<I, P>(
+        self,
+        peer: I,
+        handshake_duration: Duration,
+        inbound: bool,
+    ) where
+        REv: From<NetworkAnnouncement<I, P>>,
+    {
+        self.0
+            .schedule(
+                NetworkAnnouncement::PeerConnected {
+                    peer,
+                    handshake_duration,
+                    inbound,
+                },
+                QueueKind::NetworkIncoming,
+            )
+            .await;
+    }
+
+    /// Announces that a connection to or from a peer has been torn down.
+    pub(crate) async fn announce_peer_disconnected<I, P>(self, peer: I, reason: String)
+    where
+        REv: From<NetworkAnnouncement<I, P>>,
+    {
+        self.0
+            .schedule(
+                NetworkAnnouncement::PeerDisconnected { peer, reason },
+                QueueKind::NetworkIncoming,
+            )
+            .await;
+    }
+
     /// Announces that a gossiper has received a new item, where the item's ID is the complete item.
     pub(crate) async fn announce_complete_item_received_via_gossip<T: Item>(self, item: T::Id)
     where
@@ -526,21 +717,87 @@ impl<REv> EffectBuilder<REv> {
             .await;
     }
 
-    /// Announces that the HTTP API server has received a deploy.
-    pub(crate) async fn announce_deploy_received(self, deploy: Box<Deploy>)
+    /// Announces that a gossiper has finished gossiping an item.
+    pub(crate) async fn announce_finished_gossiping<T: Item>(self, item_id: T::Id)
     where
+        REv: From<GossiperAnnouncement<T>>,
+    {
+        self.0
+            .schedule(
+                GossiperAnnouncement::FinishedGossiping(item_id),
+                QueueKind::Regular,
+            )
+            .await;
+    }
+
+    /// Announces that a finalized block has been orphaned, i.e. is no longer part of the linear
+    /// chain.
+    pub(crate) async fn announce_block_orphaned(self, block: Box<Block>)
+    where
+        REv: From<LinearChainAnnouncement>,
+    {
+        self.0
+            .schedule(
+                LinearChainAnnouncement::BlockOrphaned { block },
+                QueueKind::Regular,
+            )
+            .await;
+    }
+
+    /// Announces that storage ran out of space, or has space available again.
+    pub(crate) async fn announce_storage_space(self, announcement: StorageAnnouncement)
+    where
+        REv: From<StorageAnnouncement>,
+    {
+        self.0.schedule(announcement, QueueKind::Regular).await;
+    }
+
+    /// Announces that the load shedder has moved to a new stage of load shedding.
+    pub(crate) async fn announce_load_shedding_level(self, level: ShedLevel)
+    where
+        REv: From<LoadShedderAnnouncement>,
+    {
+        self.0
+            .schedule(
+                LoadShedderAnnouncement::LevelChanged(level),
+                QueueKind::Regular,
+            )
+            .await;
+    }
+
+    /// Announces that the HTTP API server has received a deploy.
+    pub(crate) async fn announce_deploy_received(
+        self,
+        deploy: Box<Deploy>,
+        responder: Option<Responder<Result<(), deploy_acceptor::Error>>>,
+    ) where
         REv: From<ApiServerAnnouncement>,
     {
         self.0
             .schedule(
-                ApiServerAnnouncement::DeployReceived { deploy },
+                ApiServerAnnouncement::DeployReceived { deploy, responder },
                 QueueKind::Api,
             )
             .await;
     }
 
+    /// Announces that a deploy has been accepted as valid, before it has been stored.
+    pub(crate) fn announce_deploy_accepted<I>(
+        self,
+        deploy: Box<Deploy>,
+        source: Source<I>,
+    ) -> impl Future<Output = ()>
+    where
+        REv: From<DeployAcceptorAnnouncement<I>>,
+    {
+        self.0.schedule(
+            DeployAcceptorAnnouncement::DeployAccepted { deploy, source },
+            QueueKind::Regular,
+        )
+    }
+
     /// Announces that a deploy not previously stored has now been accepted and stored.
-    pub(crate) fn announce_new_deploy_accepted<I>(
+    pub(crate) fn announce_deploy_stored<I>(
         self,
         deploy: Box<Deploy>,
         source: Source<I>,
@@ -549,7 +806,7 @@ impl<REv> EffectBuilder<REv> {
         REv: From<DeployAcceptorAnnouncement<I>>,
     {
         self.0.schedule(
-            DeployAcceptorAnnouncement::AcceptedNewDeploy { deploy, source },
+            DeployAcceptorAnnouncement::DeployStored { deploy, source },
             QueueKind::Regular,
         )
     }
@@ -589,7 +846,13 @@ impl<REv> EffectBuilder<REv> {
     }
 
     /// Puts the given block into the linear block store.
-    pub(crate) async fn put_block_to_storage<S>(self, block: Box<S::Block>) -> bool
+    ///
+    /// Returns whether the block is new, or an error if storage failed and the node is shutting
+    /// down.
+    pub(crate) async fn put_block_to_storage<S>(
+        self,
+        block: Box<S::Block>,
+    ) -> Result<bool, StorageFailure>
     where
         S: StorageType + 'static,
         REv: From<StorageRequest<S>>,
@@ -601,6 +864,31 @@ impl<REv> EffectBuilder<REv> {
         .await
     }
 
+    /// Puts the given last block of an era into the linear block store, together with the era's
+    /// summary.
+    ///
+    /// Returns whether the block is new, or an error if storage failed and the node is shutting
+    /// down.
+    pub(crate) async fn put_switch_block_to_storage<S>(
+        self,
+        block: Box<S::Block>,
+        era_summary: Box<EraSummary<S::Block>>,
+    ) -> Result<bool, StorageFailure>
+    where
+        S: StorageType + 'static,
+        REv: From<StorageRequest<S>>,
+    {
+        self.make_request(
+            |responder| StorageRequest::PutSwitchBlock {
+                block,
+                era_summary,
+                responder,
+            },
+            QueueKind::Regular,
+        )
+        .await
+    }
+
     /// Gets the requested block from the linear block store.
     pub(crate) async fn get_block_from_storage<S>(
         self,
@@ -620,6 +908,25 @@ impl<REv> EffectBuilder<REv> {
         .await
     }
 
+    /// Gets the requested block from the linear block store, unless it has been orphaned.
+    pub(crate) async fn get_canonical_block_from_storage<S>(
+        self,
+        block_hash: <S::Block as Value>::Id,
+    ) -> Option<S::Block>
+    where
+        S: StorageType + 'static,
+        REv: From<StorageRequest<S>>,
+    {
+        self.make_request(
+            |responder| StorageRequest::GetCanonicalBlock {
+                block_hash,
+                responder,
+            },
+            QueueKind::Regular,
+        )
+        .await
+    }
+
     /// Gets the requested block header from the linear block store.
     #[allow(unused)]
     pub(crate) async fn get_block_header_from_storage<S>(
@@ -640,8 +947,31 @@ impl<REv> EffectBuilder<REv> {
         .await
     }
 
+    /// Gets the summary of the requested era from the linear block store.
+    #[allow(unused)]
+    pub(crate) async fn get_era_summary_from_storage<S>(
+        self,
+        era_id: EraId,
+    ) -> Option<EraSummary<S::Block>>
+    where
+        S: StorageType + 'static,
+        REv: From<StorageRequest<S>>,
+    {
+        self.make_request(
+            |responder| StorageRequest::GetEraSummary { era_id, responder },
+            QueueKind::Regular,
+        )
+        .await
+    }
+
     /// Puts the given deploy into the deploy store.
-    pub(crate) async fn put_deploy_to_storage<S>(self, deploy: Box<S::Deploy>) -> bool
+    ///
+    /// Returns whether the deploy is new, or an error if storage failed and the node is shutting
+    /// down.
+    pub(crate) async fn put_deploy_to_storage<S>(
+        self,
+        deploy: Box<S::Deploy>,
+    ) -> Result<bool, StorageFailure>
     where
         S: StorageType + 'static,
         REv: From<StorageRequest<S>>,
@@ -654,10 +984,12 @@ impl<REv> EffectBuilder<REv> {
     }
 
     /// Gets the requested deploys from the deploy store.
+    ///
+    /// Returns an error if storage failed and the node is shutting down.
     pub(crate) async fn get_deploys_from_storage<S>(
         self,
         deploy_hashes: DeployHashes<S>,
-    ) -> DeployResults<S>
+    ) -> Result<DeployResults<S>, StorageFailure>
     where
         S: StorageType + 'static,
         REv: From<StorageRequest<S>>,
@@ -775,31 +1107,75 @@ impl<REv> EffectBuilder<REv> {
         .await
     }
 
-    /// Passes the timestamp of a future block for which deploys are to be proposed.
-    // TODO: The input `BlockContext` will probably be a different type than the context in the
-    //       return value in the future.
-    pub(crate) async fn request_proto_block(
+    /// Adds a deploy to the deploy buffer.
+    ///
+    /// Returns `false` if the deploy buffer is full and the deploy can't displace any other.
+    pub(crate) async fn buffer_deploy(self, deploy: Box<Deploy>) -> bool
+    where
+        REv: From<DeployBufferRequest>,
+    {
+        self.make_request(
+            |responder| DeployBufferRequest::Buffer { deploy, responder },
+            QueueKind::Regular,
+        )
+        .await
+    }
+
+    /// Gets the deploys pending in the deploy buffer.
+    pub(crate) async fn get_pending_deploys(self) -> Vec<PendingDeploy>
+    where
+        REv: From<DeployBufferRequest>,
+    {
+        self.make_request(
+            |responder| DeployBufferRequest::ListPending { responder },
+            QueueKind::Regular,
+        )
+        .await
+    }
+
+    /// Gets the pending deploys which can be included in a block at `current_instant`, to
+    /// assemble a proposal from.  Deploys contained in `past_blocks`, the blocks on which the new
+    /// block builds, are left out.
+    pub(crate) async fn list_proposal_candidates(
+        self,
+        current_instant: Timestamp,
+        past_blocks: HashSet<ProtoBlockHash>,
+    ) -> Vec<DeployCandidate>
+    where
+        REv: From<DeployBufferRequest>,
+    {
+        self.make_request(
+            |responder| DeployBufferRequest::ListCandidates {
+                current_instant,
+                past_blocks,
+                responder,
+            },
+            QueueKind::Regular,
+        )
+        .await
+    }
+
+    /// Requests a proposal for a future block, for which this node is the proposer, building on
+    /// the `past_blocks`.
+    pub(crate) async fn request_proposal(
         self,
         block_context: BlockContext,
+        past_blocks: HashSet<ProtoBlockHash>,
         random_bit: bool,
-    ) -> (ProtoBlock, BlockContext)
+    ) -> Proposal
     where
-        REv: From<DeployBufferRequest>,
+        REv: From<ProposalBuilderRequest>,
     {
-        let deploys = self
-            .make_request(
-                |responder| DeployBufferRequest::ListForInclusion {
-                    current_instant: block_context.timestamp(),
-                    past_blocks: Default::default(), // TODO
-                    responder,
-                },
-                QueueKind::Regular,
-            )
-            .await
-            .into_iter()
-            .collect();
-        let proto_block = ProtoBlock::new(deploys, random_bit);
-        (proto_block, block_context)
+        self.make_request(
+            |responder| ProposalBuilderRequest::BuildProposal {
+                block_context,
+                past_blocks,
+                random_bit,
+                responder,
+            },
+            QueueKind::Regular,
+        )
+        .await
     }
 
     /// Passes a finalized proto-block to the block executor component to execute it.
@@ -896,6 +1272,123 @@ impl<REv> EffectBuilder<REv> {
             .await
     }
 
+    /// Announces that we have signed a linear chain block as final.
+    pub(crate) async fn announce_block_signed(self, finality_signature: FinalitySignature)
+    where
+        REv: From<ConsensusAnnouncement>,
+    {
+        self.0
+            .schedule(
+                ConsensusAnnouncement::BlockSigned(Box::new(finality_signature)),
+                QueueKind::Regular,
+            )
+            .await
+    }
+
+    /// Announces that a new era with the given validators has started.
+    pub(crate) async fn announce_era_started(
+        self,
+        era_id: EraId,
+        validator_weights: BTreeMap<PublicKey, Motes>,
+    ) where
+        REv: From<ConsensusAnnouncement>,
+    {
+        self.0
+            .schedule(
+                ConsensusAnnouncement::EraStarted {
+                    era_id,
+                    validator_weights,
+                },
+                QueueKind::Regular,
+            )
+            .await
+    }
+
+    /// Announces that no block has been finalized for longer than the stall timeout.
+    pub(crate) async fn announce_consensus_stalled(self, era_id: EraId, last_progress: Timestamp)
+    where
+        REv: From<ConsensusAnnouncement>,
+    {
+        self.0
+            .schedule(
+                ConsensusAnnouncement::ConsensusStalled {
+                    era_id,
+                    last_progress,
+                },
+                QueueKind::Regular,
+            )
+            .await
+    }
+
+    /// Announces that a block has been finalized after consensus had stalled.
+    pub(crate) async fn announce_consensus_resumed(self, era_id: EraId)
+    where
+        REv: From<ConsensusAnnouncement>,
+    {
+        self.0
+            .schedule(
+                ConsensusAnnouncement::ConsensusResumed { era_id },
+                QueueKind::Regular,
+            )
+            .await
+    }
+
+    /// Announces the weight of the validators which have signed a block so far, and the weight
+    /// needed for it to be final.
+    pub(crate) async fn announce_finality_progress(
+        self,
+        block_hash: BlockHash,
+        weight_so_far: Motes,
+        weight_needed: Motes,
+    ) where
+        REv: From<FinalitySignatureAnnouncement>,
+    {
+        self.0
+            .schedule(
+                FinalitySignatureAnnouncement::FinalityProgress {
+                    block_hash,
+                    weight_so_far,
+                    weight_needed,
+                },
+                QueueKind::Regular,
+            )
+            .await
+    }
+
+    /// Announces that validators with enough weight have signed a block as final.
+    pub(crate) async fn announce_finality_signatures_complete(
+        self,
+        block_hash: BlockHash,
+        era_id: EraId,
+        signatures: BTreeMap<PublicKey, Signature>,
+    ) where
+        REv: From<FinalitySignatureAnnouncement>,
+    {
+        self.0
+            .schedule(
+                FinalitySignatureAnnouncement::FinalitySignaturesComplete {
+                    block_hash,
+                    era_id,
+                    signatures,
+                },
+                QueueKind::Regular,
+            )
+            .await
+    }
+
+    /// Announces that a pending deploy should be gossiped again.
+    pub(crate) async fn announce_deploy_rebroadcast(self, deploy_hash: DeployHash)
+    where
+        REv: From<DeployBufferAnnouncement>,
+    {
+        self.0
+            .schedule(
+                DeployBufferAnnouncement::RebroadcastDeploy(deploy_hash),
+                QueueKind::Regular,
+            )
+            .await
+    }
+
     /// Runs the genesis process on the contract runtime.
     pub(crate) async fn commit_genesis(
         self,
@@ -1048,6 +1541,15 @@ impl<REv> EffectBuilder<REv> {
         )
         .await
     }
+
+    /// Gets the participation of the validators in the current era from consensus.
+    pub(crate) async fn get_validator_participation(self) -> Option<ParticipationReport<PublicKey>>
+    where
+        REv: From<ConsensusRequest>,
+    {
+        self.make_request(ConsensusRequest::GetValidatorParticipation, QueueKind::Api)
+            .await
+    }
 }
 
 /// Construct a fatal error effect.
@@ -1060,3 +1562,152 @@ macro_rules! fatal {
         $effect_builder.fatal(file!(), line!(), &$msg).ignore()
     };
 }
+
+#[cfg(test)]
+mod tests {
+    use std::sync::atomic::AtomicU32;
+
+    use derive_more::From;
+    use rand::Rng;
+    use tokio::time;
+
+    use super::*;
+    use crate::{
+        components::storage::Storage, reactor::Scheduler, small_network::NodeId, testing::TestRng,
+        utils,
+    };
+
+    #[derive(Debug, From)]
+    enum ReactorEvent {
+        #[from]
+        Storage(StorageRequest<Storage>),
+        #[from]
+        NetworkInfo(NetworkInfoRequest<NodeId>),
+    }
+
+    #[tokio::test]
+    async fn should_repeat_until_cancelled() {
+        time::pause();
+        let scheduler = utils::leak(Scheduler::<u32>::new(QueueKind::weights()));
+        let effect_builder = EffectBuilder::new(EventQueueHandle::new(scheduler));
+        let start = time::Instant::now();
+
+        let schedule = RepeatingSchedule::new();
+        let counter = Arc::new(AtomicU32::new(0));
+        let event_counter = Arc::clone(&counter);
+        let join_handle = tokio::spawn(effect_builder.schedule_repeating(
+            Duration::from_secs(1),
+            schedule.clone(),
+            move || event_counter.fetch_add(1, Ordering::SeqCst),
+        ));
+
+        // The paused clock is advanced to each timer in turn while we wait for events, so no event
+        // should be delivered before its interval has elapsed.
+        for expected in 0..5 {
+            let (event, queue_kind) = scheduler.pop().await;
+            assert_eq!(event, expected);
+            assert_eq!(queue_kind, QueueKind::Regular);
+            assert!(start.elapsed() >= Duration::from_secs(u64::from(expected) + 1));
+        }
+
+        // After cancelling, no more events are produced and the effect completes.
+        schedule.cancel();
+        join_handle.await.expect("schedule should complete");
+        assert_eq!(scheduler.item_count(), 0);
+        assert_eq!(counter.load(Ordering::SeqCst), 5);
+    }
+
+    #[tokio::test]
+    async fn should_deliver_delayed_events_in_timestamp_order() {
+        time::pause();
+        let scheduler = utils::leak(Scheduler::<u32>::new(QueueKind::weights()));
+        let effect_builder = EffectBuilder::new(EventQueueHandle::new(scheduler));
+        let start = time::Instant::now();
+
+        for (delay_millis, event) in &[(300, 3_u32), (100, 1), (400, 4), (200, 2)] {
+            let _ =
+                tokio::spawn(effect_builder.delay(Duration::from_millis(*delay_millis), *event));
+        }
+
+        // Each event is delivered once its own delay has elapsed, in timestamp order rather than
+        // the order in which the delays were requested.
+        for expected in 1..=4 {
+            let (event, queue_kind) = scheduler.pop().await;
+            assert_eq!(event, expected);
+            assert_eq!(queue_kind, QueueKind::Regular);
+            assert!(start.elapsed() >= Duration::from_millis(100 * u64::from(expected)));
+        }
+    }
+
+    #[tokio::test]
+    async fn should_join_results_or_time_out() {
+        time::pause();
+        let mut rng = TestRng::new();
+        let scheduler = utils::leak(Scheduler::<ReactorEvent>::new(QueueKind::weights()));
+        let effect_builder = EffectBuilder::new(EventQueueHandle::new(scheduler));
+        let timeout = Duration::from_secs(5);
+        let block = Block::random(&mut rng);
+        let peer: NodeId = rng.gen();
+        let peer_address: SocketAddr = "127.0.0.1:34553".parse().unwrap();
+
+        let join = |block_hash| {
+            tokio::spawn(effect_builder.join(
+                timeout,
+                effect_builder.get_block_from_storage::<Storage>(block_hash),
+                effect_builder.network_peers::<NodeId>(),
+            ))
+        };
+
+        // Answers the storage query, then the network query if `answer_network` is set, returning
+        // the responder of an unanswered network query.
+        let answer = |answer_network: bool| {
+            let block = block.clone();
+            async move {
+                let mut network_responder = None;
+                for _ in 0..2 {
+                    match scheduler.pop().await.0 {
+                        ReactorEvent::Storage(StorageRequest::GetBlock {
+                            block_hash,
+                            responder,
+                        }) => {
+                            assert_eq!(block_hash, *block.hash());
+                            responder.respond(Some(block.clone())).await
+                        }
+                        ReactorEvent::NetworkInfo(NetworkInfoRequest::GetPeers { responder }) => {
+                            if answer_network {
+                                let peers = [(peer, peer_address)].iter().cloned().collect();
+                                responder.respond(peers).await
+                            } else {
+                                network_responder = Some(responder);
+                            }
+                        }
+                        other => panic!("unexpected event {:?}", other),
+                    }
+                }
+                network_responder
+            }
+        };
+
+        // Both results are yielded together once both queries have been answered.
+        let join_handle = join(*block.hash());
+        assert!(answer(true).await.is_none());
+        let (stored_block, peers) = join_handle.await.expect("join should complete");
+        assert_eq!(stored_block, Some(Some(block.clone())));
+        assert_eq!(
+            peers.expect("should have peers").get(&peer),
+            Some(&peer_address)
+        );
+
+        // If the network doesn't answer in time, only the storage result is available.
+        let join_handle = join(*block.hash());
+        let network_responder = answer(false).await.expect("should have network query");
+        time::advance(timeout).await;
+        let (stored_block, peers) = join_handle.await.expect("join should complete");
+        assert_eq!(stored_block, Some(Some(block)));
+        assert!(peers.is_none());
+
+        // A late response is discarded.
+        network_responder.respond(HashMap::new()).await;
+        assert_eq!(scheduler.item_count(), 0);
+    }
+}
@@ -4,16 +4,41 @@
 //! module documentation for details.
 
 use std::{
-    collections::HashMap,
+    collections::{BTreeMap, HashMap},
     fmt::{self, Display, Formatter},
+    time::Duration,
 };
 
+use casper_execution_engine::shared::motes::Motes;
+
+use super::Responder;
 use crate::{
-    components::{consensus::EraId, small_network::GossipedAddress},
-    types::{json_compatibility::ExecutionResult, Block, Deploy, DeployHash, Item, ProtoBlock},
+    components::{
+        consensus::EraId, deploy_acceptor, load_shedder::ShedLevel, small_network::GossipedAddress,
+    },
+    crypto::asymmetric_key::{PublicKey, Signature},
+    types::{
+        json_compatibility::ExecutionResult, Block, BlockHash, Deploy, DeployHash,
+        FinalitySignature, Item, ProtoBlock, ShutdownReason, Timestamp,
+    },
     utils::Source,
 };
 
+/// A control announcement, concerning the node as a whole.
+#[derive(Debug)]
+pub enum ControlAnnouncement {
+    /// A component hit an error the node can't recover from, so the node should shut down.
+    FatalError(ShutdownReason),
+}
+
+impl Display for ControlAnnouncement {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            ControlAnnouncement::FatalError(reason) => write!(f, "fatal error: {}", reason),
+        }
+    }
+}
+
 /// A networking layer announcement.
 #[derive(Debug)]
 #[must_use]
@@ -33,6 +58,22 @@ pub enum NetworkAnnouncement<I, P> {
     ///                 not rely on or use this for anything without asking anyone that has written
     ///                 this section of the code first!
     NewPeer(I),
+    /// A connection to or from a peer was established.
+    PeerConnected {
+        /// The peer.
+        peer: I,
+        /// The time taken to establish the connection, including the TLS handshake.
+        handshake_duration: Duration,
+        /// Whether the connection was initiated by the peer.
+        inbound: bool,
+    },
+    /// A connection to or from a peer was torn down.
+    PeerDisconnected {
+        /// The peer.
+        peer: I,
+        /// Why the connection was torn down.
+        reason: String,
+    },
 }
 
 impl<I, P> Display for NetworkAnnouncement<I, P>
@@ -49,6 +90,20 @@ where
             NetworkAnnouncement::NewPeer(id) => {
                 write!(formatter, "new peer connection established to {}", id)
             }
+            NetworkAnnouncement::PeerConnected {
+                peer,
+                handshake_duration,
+                inbound,
+            } => write!(
+                formatter,
+                "{} connection with {} established in {:?}",
+                if *inbound { "inbound" } else { "outbound" },
+                peer,
+                handshake_duration
+            ),
+            NetworkAnnouncement::PeerDisconnected { peer, reason } => {
+                write!(formatter, "connection with {} torn down: {}", peer, reason)
+            }
         }
     }
 }
@@ -61,13 +116,15 @@ pub enum ApiServerAnnouncement {
     DeployReceived {
         /// The received deploy.
         deploy: Box<Deploy>,
+        /// Responder to call with whether the deploy was accepted, if the client awaits it.
+        responder: Option<Responder<Result<(), deploy_acceptor::Error>>>,
     },
 }
 
 impl Display for ApiServerAnnouncement {
     fn fmt(&self, formatter: &mut Formatter<'_>) -> fmt::Result {
         match self {
-            ApiServerAnnouncement::DeployReceived { deploy } => {
+            ApiServerAnnouncement::DeployReceived { deploy, .. } => {
                 write!(formatter, "api server received {}", deploy.id())
             }
         }
@@ -77,8 +134,18 @@ impl Display for ApiServerAnnouncement {
 /// A `DeployAcceptor` announcement.
 #[derive(Debug)]
 pub enum DeployAcceptorAnnouncement<I> {
+    /// A deploy has been accepted as valid and is being stored.
+    ///
+    /// The deploy may already have been stored on this node before.
+    DeployAccepted {
+        /// The accepted deploy.
+        deploy: Box<Deploy>,
+        /// The source (peer or client) of the deploy.
+        source: Source<I>,
+    },
+
     /// A deploy which wasn't previously stored on this node has been accepted and stored.
-    AcceptedNewDeploy {
+    DeployStored {
         /// The new deploy.
         deploy: Box<Deploy>,
         /// The source (peer or client) of the deploy.
@@ -97,9 +164,12 @@ pub enum DeployAcceptorAnnouncement<I> {
 impl<I: Display> Display for DeployAcceptorAnnouncement<I> {
     fn fmt(&self, formatter: &mut Formatter<'_>) -> fmt::Result {
         match self {
-            DeployAcceptorAnnouncement::AcceptedNewDeploy { deploy, source } => write!(
+            DeployAcceptorAnnouncement::DeployAccepted { deploy, source } => {
+                write!(formatter, "accepted deploy {} from {}", deploy.id(), source)
+            }
+            DeployAcceptorAnnouncement::DeployStored { deploy, source } => write!(
                 formatter,
-                "accepted new deploy {} from {}",
+                "stored new deploy {} from {}",
                 deploy.id(),
                 source
             ),
@@ -122,9 +192,30 @@ pub enum ConsensusAnnouncement {
     Orphaned(ProtoBlock),
     /// A linear chain block has been handled.
     Handled(u64),
+    /// We have signed a linear chain block as final.
+    BlockSigned(Box<FinalitySignature>),
+    /// A new era has started.
+    EraStarted {
+        /// The ID of the new era.
+        era_id: EraId,
+        /// The weights of the new era's validators, by consensus public key.
+        validator_weights: BTreeMap<PublicKey, Motes>,
+    },
     /// TODO: this is only for purposes of detecting incomplete linear chain synchronization,
     /// remove when proper syncing is implemented
     GotMessageInEra(EraId),
+    /// No block has been finalized for longer than the configured stall timeout.
+    ConsensusStalled {
+        /// The ID of the current era.
+        era_id: EraId,
+        /// When the last block was finalized.
+        last_progress: Timestamp,
+    },
+    /// A block has been finalized after consensus had stalled.
+    ConsensusResumed {
+        /// The ID of the era of the finalized block.
+        era_id: EraId,
+    },
 }
 
 impl Display for ConsensusAnnouncement {
@@ -144,9 +235,99 @@ impl Display for ConsensusAnnouncement {
                 "Linear chain block has been handled by consensus, height={}",
                 height
             ),
+            ConsensusAnnouncement::BlockSigned(finality_signature) => {
+                write!(formatter, "signed block: {}", finality_signature)
+            }
+            ConsensusAnnouncement::EraStarted {
+                era_id,
+                validator_weights,
+            } => write!(
+                formatter,
+                "era {} started with {} validators",
+                era_id,
+                validator_weights.len()
+            ),
             ConsensusAnnouncement::GotMessageInEra(era_id) => {
                 write!(formatter, "message in era {:?} received", era_id)
             }
+            ConsensusAnnouncement::ConsensusStalled {
+                era_id,
+                last_progress,
+            } => write!(
+                formatter,
+                "consensus stalled in era {} since {}",
+                era_id, last_progress
+            ),
+            ConsensusAnnouncement::ConsensusResumed { era_id } => {
+                write!(formatter, "consensus resumed in era {}", era_id)
+            }
+        }
+    }
+}
+
+/// A deploy buffer announcement.
+#[derive(Debug)]
+pub enum DeployBufferAnnouncement {
+    /// A pending deploy has not been included in a block for a while and should be gossiped
+    /// again, in case it was lost.
+    RebroadcastDeploy(DeployHash),
+}
+
+impl Display for DeployBufferAnnouncement {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            DeployBufferAnnouncement::RebroadcastDeploy(deploy_hash) => {
+                write!(f, "rebroadcast pending deploy {}", deploy_hash)
+            }
+        }
+    }
+}
+
+/// A finality signature collector announcement.
+#[derive(Debug)]
+pub enum FinalitySignatureAnnouncement {
+    /// A new valid signature of a block brought its signers closer to finality.
+    FinalityProgress {
+        /// The hash of the signed block.
+        block_hash: BlockHash,
+        /// The total weight of the block's signers so far.
+        weight_so_far: Motes,
+        /// The weight the signers need for the block to be final.
+        weight_needed: Motes,
+    },
+    /// Validators with enough weight have signed a block as final.
+    FinalitySignaturesComplete {
+        /// The hash of the signed block.
+        block_hash: BlockHash,
+        /// The era of the signed block.
+        era_id: EraId,
+        /// The collected signatures, by signer.
+        signatures: BTreeMap<PublicKey, Signature>,
+    },
+}
+
+impl Display for FinalitySignatureAnnouncement {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            FinalitySignatureAnnouncement::FinalityProgress {
+                block_hash,
+                weight_so_far,
+                weight_needed,
+            } => write!(
+                f,
+                "signers of {} have {} of {} weight needed for finality",
+                block_hash, weight_so_far, weight_needed
+            ),
+            FinalitySignatureAnnouncement::FinalitySignaturesComplete {
+                block_hash,
+                signatures,
+                ..
+            } => write!(
+                f,
+                "collected {} finality signatures for {}",
+                signatures.len(),
+                block_hash
+            ),
         }
     }
 }
@@ -173,17 +354,77 @@ impl Display for BlockExecutorAnnouncement {
     }
 }
 
+/// A linear chain announcement.
+#[derive(Debug)]
+pub enum LinearChainAnnouncement {
+    /// A finalized block is no longer part of the linear chain, as it was reorged out by a fork.
+    BlockOrphaned {
+        /// The orphaned block.
+        block: Box<Block>,
+    },
+}
+
+impl Display for LinearChainAnnouncement {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            LinearChainAnnouncement::BlockOrphaned { block } => {
+                write!(f, "orphaned linear chain block {}", block.hash())
+            }
+        }
+    }
+}
+
+/// A storage announcement.
+#[derive(Debug)]
+pub enum StorageAnnouncement {
+    /// A write failed since there is no space left, so new deploys should not be accepted.
+    OutOfSpace,
+    /// A write succeeded after running out of space, so new deploys can be accepted again.
+    SpaceAvailable,
+}
+
+impl Display for StorageAnnouncement {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            StorageAnnouncement::OutOfSpace => write!(f, "storage out of space"),
+            StorageAnnouncement::SpaceAvailable => write!(f, "storage space available"),
+        }
+    }
+}
+
+/// A load shedder announcement.
+#[derive(Debug)]
+pub enum LoadShedderAnnouncement {
+    /// The load shedder moved to a new stage of load shedding.
+    LevelChanged(ShedLevel),
+}
+
+impl Display for LoadShedderAnnouncement {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            LoadShedderAnnouncement::LevelChanged(level) => {
+                write!(f, "load shedding level changed to {}", level)
+            }
+        }
+    }
+}
+
 /// A Gossiper announcement.
 #[derive(Debug)]
 pub enum GossiperAnnouncement<T: Item> {
     /// A new item has been received, where the item's ID is the complete item.
     NewCompleteItem(T::Id),
+    /// Gossiping an item has finished, as enough peers are known to hold it.
+    FinishedGossiping(T::Id),
 }
 
 impl<T: Item> Display for GossiperAnnouncement<T> {
     fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
         match self {
             GossiperAnnouncement::NewCompleteItem(item) => write!(f, "new complete item {}", item),
+            GossiperAnnouncement::FinishedGossiping(item_id) => {
+                write!(f, "finished gossiping {}", item_id)
+            }
         }
     }
 }
@@ -9,6 +9,7 @@ use std::{
     net::SocketAddr,
 };
 
+use hex_fmt::HexFmt;
 use semver::Version;
 
 use casper_execution_engine::{
@@ -29,12 +30,23 @@ use casper_types::{Key, URef};
 use super::Responder;
 use crate::{
     components::{
+        api_server::DeployStatus,
+        consensus::{BlockContext, EraId, ParticipationReport},
+        deploy_acceptor,
+        deploy_buffer::{DeployCandidate, PendingDeploy},
         fetcher::FetchResult,
+        proposal_builder::Proposal,
+        small_network::PeerLatency,
         storage::{
-            DeployHashes, DeployHeaderResults, DeployMetadata, DeployResults, StorageType, Value,
+            DeployHashes, DeployHeaderResults, DeployMetadata, DeployResults, EraSummary,
+            StorageFailure, StorageType, Value,
         },
     },
-    crypto::{asymmetric_key::Signature, hash::Digest},
+    crypto::{
+        asymmetric_key::{PublicKey, Signature},
+        hash::Digest,
+        merkle::MerkleProof,
+    },
     types::{
         json_compatibility::ExecutionResult, Block as LinearBlock, BlockHash, BlockHeader, Deploy,
         DeployHash, FinalizedBlock, Item, ProtoBlockHash, StatusFeed, Timestamp,
@@ -88,6 +100,13 @@ pub enum NetworkRequest<I, P> {
         /// Responder to be called when all messages are queued.
         responder: Responder<()>,
     },
+    /// Send a message on the network to all peers which are validators in the current era.
+    BroadcastToValidators {
+        /// Message payload.
+        payload: P,
+        /// Responder to be called when all messages are queued.
+        responder: Responder<()>,
+    },
     /// Gossip a message to a random subset of peers.
     Gossip {
         /// Payload to gossip.
@@ -96,6 +115,13 @@ pub enum NetworkRequest<I, P> {
         count: usize,
         /// Node IDs of nodes to exclude from gossiping to.
         exclude: HashSet<I>,
+        /// Relative likelihoods of nodes being chosen, defaulting to 1 for unlisted nodes.
+        ///
+        /// If empty, nodes are chosen uniformly at random.
+        weights: HashMap<I, f64>,
+        /// Whether to prefer nodes in localities none of the other chosen nodes is in, where the
+        /// localities of nodes are known.
+        spread_localities: bool,
         /// Responder to be called when all messages are queued.
         responder: Responder<HashSet<I>>,
     },
@@ -123,15 +149,25 @@ impl<I, P> NetworkRequest<I, P> {
                 payload: wrap_payload(payload),
                 responder,
             },
+            NetworkRequest::BroadcastToValidators { payload, responder } => {
+                NetworkRequest::BroadcastToValidators {
+                    payload: wrap_payload(payload),
+                    responder,
+                }
+            }
             NetworkRequest::Gossip {
                 payload,
                 count,
                 exclude,
+                weights,
+                spread_localities,
                 responder,
             } => NetworkRequest::Gossip {
                 payload: wrap_payload(payload),
                 count,
                 exclude,
+                weights,
+                spread_localities,
                 responder,
             },
         }
@@ -151,6 +187,9 @@ where
             NetworkRequest::Broadcast { payload, .. } => {
                 write!(formatter, "broadcast: {}", payload)
             }
+            NetworkRequest::BroadcastToValidators { payload, .. } => {
+                write!(formatter, "broadcast to validators: {}", payload)
+            }
             NetworkRequest::Gossip { payload, .. } => write!(formatter, "gossip: {}", payload),
         }
     }
@@ -165,6 +204,18 @@ pub enum NetworkInfoRequest<I> {
         /// Responder to be called with all connected peers.
         responder: Responder<HashMap<I, SocketAddr>>,
     },
+    /// Get the latest round-trip times measured to peers.
+    GetPeerLatencies {
+        /// Responder to be called with the latest measurement for each peer.
+        responder: Responder<HashMap<I, PeerLatency>>,
+    },
+    /// Get the node responsible for a key among us and our peers, by consistent hashing.
+    GetResponsibleNode {
+        /// The key, e.g. a deploy hash.
+        key: Vec<u8>,
+        /// Responder to be called with the responsible node.
+        responder: Responder<I>,
+    },
 }
 
 impl<I> Display for NetworkInfoRequest<I>
@@ -174,6 +225,12 @@ where
     fn fmt(&self, formatter: &mut Formatter<'_>) -> fmt::Result {
         match self {
             NetworkInfoRequest::GetPeers { responder: _ } => write!(formatter, "get peers"),
+            NetworkInfoRequest::GetPeerLatencies { responder: _ } => {
+                write!(formatter, "get peer latencies")
+            }
+            NetworkInfoRequest::GetResponsibleNode { key, .. } => {
+                write!(formatter, "get node responsible for {}", HexFmt(key))
+            }
         }
     }
 }
@@ -187,8 +244,20 @@ pub enum StorageRequest<S: StorageType + 'static> {
         /// Block to be stored.
         block: Box<S::Block>,
         /// Responder to call with the result.  Returns true if the block was stored on this
-        /// attempt or false if it was previously stored.
-        responder: Responder<bool>,
+        /// attempt or false if it was previously stored, or an error if storage failed and the
+        /// node is shutting down.
+        responder: Responder<Result<bool, StorageFailure>>,
+    },
+    /// Store given block, which is the last block of an era, together with the era's summary.
+    PutSwitchBlock {
+        /// Block to be stored.
+        block: Box<S::Block>,
+        /// Summary of the era the block concludes.
+        era_summary: Box<EraSummary<S::Block>>,
+        /// Responder to call with the result.  Returns true if the block was stored on this
+        /// attempt or false if it was previously stored, or an error if storage failed and the
+        /// node is shutting down.
+        responder: Responder<Result<bool, StorageFailure>>,
     },
     /// Retrieve block with given hash.
     GetBlock {
@@ -198,6 +267,14 @@ pub enum StorageRequest<S: StorageType + 'static> {
         /// storage.
         responder: Responder<Option<S::Block>>,
     },
+    /// Retrieve block with given hash, unless it has been orphaned.
+    GetCanonicalBlock {
+        /// Hash of block to be retrieved.
+        block_hash: <S::Block as Value>::Id,
+        /// Responder to call with the result.  Returns `None` if the block doesn't exist in local
+        /// storage or isn't part of the linear chain anymore.
+        responder: Responder<Option<S::Block>>,
+    },
     /// Retrieve block header with given hash.
     GetBlockHeader {
         /// Hash of block to get header of.
@@ -206,20 +283,30 @@ pub enum StorageRequest<S: StorageType + 'static> {
         /// local storage.
         responder: Responder<Option<<S::Block as Value>::Header>>,
     },
+    /// Retrieve the summary of the given era.
+    GetEraSummary {
+        /// The era.
+        era_id: EraId,
+        /// Responder to call with the result.  Returns `None` if the era's last block hasn't been
+        /// stored.
+        responder: Responder<Option<EraSummary<S::Block>>>,
+    },
     /// Store given deploy.
     PutDeploy {
         /// Deploy to store.
         deploy: Box<S::Deploy>,
         /// Responder to call with the result.  Returns true if the deploy was stored on this
-        /// attempt or false if it was previously stored.
-        responder: Responder<bool>,
+        /// attempt or false if it was previously stored or dropped, or an error if storage failed
+        /// and the node is shutting down.
+        responder: Responder<Result<bool, StorageFailure>>,
     },
     /// Retrieve deploys with given hashes.
     GetDeploys {
         /// Hashes of deploys to be retrieved.
         deploy_hashes: DeployHashes<S>,
-        /// Responder to call with the results.
-        responder: Responder<DeployResults<S>>,
+        /// Responder to call with the results, or an error if storage failed and the node is
+        /// shutting down.
+        responder: Responder<Result<DeployResults<S>, StorageFailure>>,
     },
     /// Retrieve deploy headers with given hashes.
     GetDeployHeaders {
@@ -265,10 +352,23 @@ impl<S: StorageType> Display for StorageRequest<S> {
     fn fmt(&self, formatter: &mut Formatter<'_>) -> fmt::Result {
         match self {
             StorageRequest::PutBlock { block, .. } => write!(formatter, "put {}", block),
+            StorageRequest::PutSwitchBlock {
+                block, era_summary, ..
+            } => write!(
+                formatter,
+                "put {} with summary of era {}",
+                block, era_summary.era_id
+            ),
             StorageRequest::GetBlock { block_hash, .. } => write!(formatter, "get {}", block_hash),
+            StorageRequest::GetCanonicalBlock { block_hash, .. } => {
+                write!(formatter, "get canonical {}", block_hash)
+            }
             StorageRequest::GetBlockHeader { block_hash, .. } => {
                 write!(formatter, "get {}", block_hash)
             }
+            StorageRequest::GetEraSummary { era_id, .. } => {
+                write!(formatter, "get summary of era {}", era_id)
+            }
             StorageRequest::PutDeploy { deploy, .. } => write!(formatter, "put {}", deploy),
             StorageRequest::GetDeploys { deploy_hashes, .. } => {
                 write!(formatter, "get {}", DisplayIter::new(deploy_hashes.iter()))
@@ -309,6 +409,28 @@ pub enum DeployBufferRequest {
         /// Responder to call with the result.
         responder: Responder<HashSet<DeployHash>>,
     },
+    /// Request the deploys which can be included in a new block, to assemble a proposal from.
+    ListCandidates {
+        /// The instant for which the deploys are requested.
+        current_instant: Timestamp,
+        /// Set of block hashes pointing to blocks whose deploys should be excluded.
+        past_blocks: HashSet<ProtoBlockHash>,
+        /// Responder to call with the result.
+        responder: Responder<Vec<DeployCandidate>>,
+    },
+    /// Request to buffer a new deploy, possibly by evicting lower-fee deploys.
+    Buffer {
+        /// The deploy.
+        deploy: Box<Deploy>,
+        /// Responder to call with whether the deploy buffer holds the deploy, i.e. it has either
+        /// been buffered or was already known.
+        responder: Responder<bool>,
+    },
+    /// Request the list of pending deploys.
+    ListPending {
+        /// Responder to call with the result.
+        responder: Responder<Vec<PendingDeploy>>,
+    },
 }
 
 impl Display for DeployBufferRequest {
@@ -324,6 +446,49 @@ impl Display for DeployBufferRequest {
                 current_instant,
                 past_blocks.len()
             ),
+            DeployBufferRequest::ListCandidates {
+                current_instant,
+                past_blocks,
+                responder: _,
+            } => write!(
+                formatter,
+                "list candidates: instant {} past {}",
+                current_instant,
+                past_blocks.len()
+            ),
+            DeployBufferRequest::Buffer { deploy, .. } => {
+                write!(formatter, "buffer {}", deploy.id())
+            }
+            DeployBufferRequest::ListPending { .. } => write!(formatter, "list pending deploys"),
+        }
+    }
+}
+
+/// A proposal builder request.
+#[derive(Debug)]
+#[must_use]
+pub enum ProposalBuilderRequest {
+    /// Request a proposal for a new block, as this node is its proposer.
+    BuildProposal {
+        /// The context of the new block.
+        block_context: BlockContext,
+        /// The blocks on which the new block builds, whose deploys must not be proposed again.
+        past_blocks: HashSet<ProtoBlockHash>,
+        /// The random bit to include in the proposal.
+        random_bit: bool,
+        /// Responder to call with the proposal.
+        responder: Responder<Proposal>,
+    },
+}
+
+impl Display for ProposalBuilderRequest {
+    fn fmt(&self, formatter: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            ProposalBuilderRequest::BuildProposal { block_context, .. } => write!(
+                formatter,
+                "build proposal for block at {}",
+                block_context.timestamp()
+            ),
         }
     }
 }
@@ -339,8 +504,8 @@ pub enum ApiRequest<I> {
     SubmitDeploy {
         /// The deploy to be announced.
         deploy: Box<Deploy>,
-        /// Responder to call.
-        responder: Responder<()>,
+        /// Responder to call with whether the deploy was accepted, or why not.
+        responder: Responder<Result<(), deploy_acceptor::Error>>,
     },
     /// If `maybe_hash` is `Some`, return the specified block if it exists, else `None`.  If
     /// `maybe_hash` is `None`, return the latest block.
@@ -377,6 +542,14 @@ pub enum ApiRequest<I> {
         /// Responder to call with the result.
         responder: Responder<Option<(Deploy, DeployMetadata<LinearBlock>)>>,
     },
+    /// Return the header of a stored block containing the specified deploy, along with a proof
+    /// that the deploy is included in it, or `None` if no stored block contains the deploy.
+    GetDeployInclusionProof {
+        /// The hash of the deploy to prove the inclusion of.
+        hash: DeployHash,
+        /// Responder to call with the result.
+        responder: Responder<Option<(BlockHeader, MerkleProof)>>,
+    },
     /// Return the connected peers.
     GetPeers {
         /// Responder to call with the result.
@@ -392,6 +565,29 @@ pub enum ApiRequest<I> {
         /// Responder to call with the result.
         responder: Responder<Option<String>>,
     },
+    /// Return the deploys pending in the deploy buffer.
+    GetMempool {
+        /// Responder to call with the result.
+        responder: Responder<Vec<PendingDeploy>>,
+    },
+    /// Return the latest round-trip times measured to peers.
+    GetPeerLatencies {
+        /// Responder to call with the result.
+        responder: Responder<HashMap<I, PeerLatency>>,
+    },
+    /// Return the lifecycle state of the specified deploy, or `None` if it isn't tracked.
+    GetDeployStatus {
+        /// The hash of the deploy.
+        hash: DeployHash,
+        /// Responder to call with the result.
+        responder: Responder<Option<DeployStatus>>,
+    },
+    /// Return the participation of the validators in the current era, or `None` if consensus
+    /// hasn't started yet.
+    GetValidatorParticipation {
+        /// Responder to call with the result.
+        responder: Responder<Option<ParticipationReport<PublicKey>>>,
+    },
 }
 
 impl<I> Display for ApiRequest<I> {
@@ -425,9 +621,18 @@ impl<I> Display for ApiRequest<I> {
                 global_state_hash, purse_uref
             ),
             ApiRequest::GetDeploy { hash, .. } => write!(formatter, "get {}", hash),
+            ApiRequest::GetDeployInclusionProof { hash, .. } => {
+                write!(formatter, "get inclusion proof for {}", hash)
+            }
             ApiRequest::GetPeers { .. } => write!(formatter, "get peers"),
             ApiRequest::GetStatus { .. } => write!(formatter, "get status"),
             ApiRequest::GetMetrics { .. } => write!(formatter, "get metrics"),
+            ApiRequest::GetMempool { .. } => write!(formatter, "get mempool"),
+            ApiRequest::GetPeerLatencies { .. } => write!(formatter, "get peer latencies"),
+            ApiRequest::GetDeployStatus { hash, .. } => write!(formatter, "get status of {}", hash),
+            ApiRequest::GetValidatorParticipation { .. } => {
+                write!(formatter, "get validator participation")
+            }
         }
     }
 }
@@ -611,4 +816,6 @@ impl<I: Display> Display for LinearChainRequest<I> {
 pub enum ConsensusRequest {
     /// Request for consensus to sign a new linear chain block and possibly start a new era.
     HandleLinearBlock(Box<BlockHeader>, Responder<Signature>),
+    /// Request for the participation of the validators in the current era.
+    GetValidatorParticipation(Responder<Option<ParticipationReport<PublicKey>>>),
 }
@@ -37,9 +37,11 @@ pub(crate) use components::small_network;
 pub use components::{
     api_server::{rpcs, Config as ApiServerConfig},
     chainspec_loader::{Chainspec, Error as ChainspecError},
-    consensus::Config as ConsensusConfig,
+    consensus::{toggle_message_tracing, Config as ConsensusConfig},
     contract_runtime::Config as ContractRuntimeConfig,
+    deploy_acceptor::Config as DeployAcceptorConfig,
     gossiper::{Config as GossipConfig, Error as GossipError},
+    load_shedder::Config as LoadShedderConfig,
     small_network::{Config as SmallNetworkConfig, Error as SmallNetworkError},
     storage::{Config as StorageConfig, Error as StorageError},
 };
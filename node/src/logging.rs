@@ -1,18 +1,24 @@
 //! Logging via the tracing crate.
 
-use std::{fmt, io};
+use std::{
+    collections::BTreeMap,
+    fmt, io,
+    sync::atomic::{AtomicU64, Ordering},
+};
 
 use ansi_term::{Color, Style};
 use serde::{Deserialize, Serialize};
 use smallvec::SmallVec;
-use tracing::{Event, Level, Subscriber};
+use tracing::{subscriber::Interest, Event, Level, Metadata, Subscriber};
 use tracing_subscriber::{
     fmt::{
         format,
         time::{FormatTime, SystemTime},
         FmtContext, FormatEvent, FormatFields, FormattedFields,
     },
+    layer::{Context, Layer},
     prelude::*,
+    registry,
     registry::LookupSpan,
     EnvFilter,
 };
@@ -28,6 +34,14 @@ pub struct LoggingConfig {
     /// If set, human-readable formats will abbreviate module names, `foo::bar::baz::bizz` will
     /// turn into `f:b:b:bizz`.
     abbreviate_modules: bool,
+    /// Sample rates of log events, keyed by target prefix.
+    ///
+    /// With a sample rate of `N`, only one in `N` events whose target starts with the given prefix
+    /// is logged.  If several prefixes match, the longest one applies, so a rate of 1 can be used
+    /// to exempt a more specific target.  Warnings and errors are never sampled out, and only
+    /// events enabled by the `RUST_LOG` filter are counted.
+    #[serde(default)]
+    pub(crate) sample_rates: BTreeMap<String, u64>,
 }
 
 impl LoggingConfig {
@@ -36,6 +50,7 @@ impl LoggingConfig {
         LoggingConfig {
             format,
             abbreviate_modules,
+            sample_rates: BTreeMap::new(),
         }
     }
 }
@@ -178,6 +193,71 @@ where
     }
 }
 
+/// A sampling rule for log events with a given target prefix.
+#[derive(Debug)]
+struct SampleRule {
+    target_prefix: String,
+    rate: u64,
+    /// Number of events seen so far which this rule applies to.
+    seen: AtomicU64,
+}
+
+/// A layer which drops all but one in every `N` log events of configured targets.
+///
+/// Events at `WARN` level or above are never dropped.  The layer has to be wrapped by the
+/// `EnvFilter`, so that it only counts events the filter has enabled, and so that callsites the
+/// filter has disabled for good stay disabled.
+#[derive(Debug)]
+struct SamplingLayer {
+    /// The rules, ordered by descending length of target prefix.
+    rules: Vec<SampleRule>,
+}
+
+impl SamplingLayer {
+    fn new(sample_rates: &BTreeMap<String, u64>) -> Self {
+        let mut rules: Vec<_> = sample_rates
+            .iter()
+            .map(|(target_prefix, rate)| SampleRule {
+                target_prefix: target_prefix.clone(),
+                rate: *rate,
+                seen: AtomicU64::new(0),
+            })
+            .collect();
+        rules.sort_by(|lhs, rhs| rhs.target_prefix.len().cmp(&lhs.target_prefix.len()));
+        SamplingLayer { rules }
+    }
+
+    /// Returns the rule applying to events with the given metadata, unless they are not sampled.
+    fn rule(&self, metadata: &Metadata<'_>) -> Option<&SampleRule> {
+        if !metadata.is_event() || *metadata.level() <= Level::WARN {
+            return None;
+        }
+        self.rules
+            .iter()
+            .find(|rule| metadata.target().starts_with(&rule.target_prefix))
+            .filter(|rule| rule.rate > 1)
+    }
+}
+
+impl<S: Subscriber> Layer<S> for SamplingLayer {
+    fn register_callsite(&self, metadata: &'static Metadata<'static>) -> Interest {
+        // Sampled callsites have to be checked every time an event occurs.  For all others, the
+        // interest of the wrapped subscriber applies.
+        if self.rule(metadata).is_some() {
+            Interest::sometimes()
+        } else {
+            Interest::always()
+        }
+    }
+
+    fn enabled(&self, metadata: &Metadata<'_>, _ctx: Context<'_, S>) -> bool {
+        match self.rule(metadata) {
+            Some(rule) => rule.seen.fetch_add(1, Ordering::Relaxed) % rule.rate == 0,
+            None => true,
+        }
+    }
+}
+
 /// Initializes the logging system with the default parameters.
 ///
 /// See `init_params` for details.
@@ -200,26 +280,133 @@ pub fn init_with_config(config: &LoggingConfig) -> anyhow::Result<()> {
         }
     })
     .delimited("; ");
+    // The env filter decides first, so that the sampling layer only sees enabled events.
+    let filtered = registry()
+        .with(SamplingLayer::new(&config.sample_rates))
+        .with(EnvFilter::from_default_env());
 
     match config.format {
         // Setup a new tracing-subscriber writing to `stdout` for logging.
         LoggingFormat::Text => tracing::subscriber::set_global_default(
-            tracing_subscriber::fmt()
-                .with_writer(io::stdout)
-                .with_env_filter(EnvFilter::from_default_env())
-                .fmt_fields(formatter)
-                .event_format(FmtEvent::new(config.abbreviate_modules))
-                .finish(),
+            filtered.with(
+                tracing_subscriber::fmt::layer()
+                    .with_writer(io::stdout)
+                    .fmt_fields(formatter)
+                    .event_format(FmtEvent::new(config.abbreviate_modules)),
+            ),
         )?,
         // JSON logging writes to `stdout` as well but uses the JSON format.
         LoggingFormat::Json => tracing::subscriber::set_global_default(
-            tracing_subscriber::fmt()
-                .with_writer(io::stdout)
-                .with_env_filter(EnvFilter::from_default_env())
-                .json()
-                .finish(),
+            filtered.with(
+                tracing_subscriber::fmt::layer()
+                    .with_writer(io::stdout)
+                    .json(),
+            ),
         )?,
     }
 
     Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+    use std::sync::{Arc, Mutex};
+
+    use tracing::{debug, error, info};
+
+    use super::*;
+
+    const SAMPLED_TARGET: &str = "casper_node::logging::tests::sampled";
+    const UNSAMPLED_TARGET: &str = "casper_node::logging::tests::unsampled";
+
+    /// A layer recording the target and level of every event it sees.
+    #[derive(Clone, Default)]
+    struct CapturingLayer(Arc<Mutex<Vec<(String, Level)>>>);
+
+    impl<S: Subscriber> Layer<S> for CapturingLayer {
+        fn on_event(&self, event: &Event<'_>, _ctx: Context<'_, S>) {
+            let metadata = event.metadata();
+            self.0
+                .lock()
+                .unwrap()
+                .push((metadata.target().to_string(), metadata.level().clone()));
+        }
+    }
+
+    impl CapturingLayer {
+        fn count(&self, target: &str, level: Level) -> usize {
+            self.0
+                .lock()
+                .unwrap()
+                .iter()
+                .filter(|(event_target, event_level)| {
+                    event_target == target && *event_level == level
+                })
+                .count()
+        }
+    }
+
+    #[test]
+    fn should_sample_high_frequency_events_but_not_errors() {
+        const RATE: u64 = 10;
+        const EVENTS: usize = 1_000;
+
+        let mut sample_rates = BTreeMap::new();
+        let _ = sample_rates.insert("casper_node::logging".to_string(), 1_000);
+        let _ = sample_rates.insert(SAMPLED_TARGET.to_string(), RATE);
+        let _ = sample_rates.insert(UNSAMPLED_TARGET.to_string(), 1);
+        let capturing_layer = CapturingLayer::default();
+        let subscriber = registry()
+            .with(capturing_layer.clone())
+            .with(SamplingLayer::new(&sample_rates));
+
+        tracing::subscriber::with_default(subscriber, || {
+            for i in 0..EVENTS {
+                debug!(target: SAMPLED_TARGET, i, "ping");
+                debug!(target: UNSAMPLED_TARGET, i, "ping");
+                error!(target: SAMPLED_TARGET, i, "failure");
+            }
+        });
+
+        let sampled = capturing_layer.count(SAMPLED_TARGET, Level::DEBUG);
+        let expected = EVENTS / RATE as usize;
+        assert!(
+            sampled >= expected * 9 / 10 && sampled <= expected * 11 / 10,
+            "logged {} of {} sampled events",
+            sampled,
+            EVENTS
+        );
+        assert_eq!(
+            capturing_layer.count(UNSAMPLED_TARGET, Level::DEBUG),
+            EVENTS
+        );
+        assert_eq!(capturing_layer.count(SAMPLED_TARGET, Level::ERROR), EVENTS);
+    }
+
+    #[test]
+    fn should_only_count_events_enabled_by_env_filter() {
+        const EVENTS: usize = 100;
+
+        let mut sample_rates = BTreeMap::new();
+        let _ = sample_rates.insert(SAMPLED_TARGET.to_string(), 2);
+        let capturing_layer = CapturingLayer::default();
+        let subscriber = registry()
+            .with(SamplingLayer::new(&sample_rates))
+            .with(EnvFilter::new(format!("{}=info", SAMPLED_TARGET)))
+            .with(capturing_layer.clone());
+
+        // If the filtered out debug events were counted, every info event would be dropped.
+        tracing::subscriber::with_default(subscriber, || {
+            for i in 0..EVENTS {
+                debug!(target: SAMPLED_TARGET, i, "ping");
+                info!(target: SAMPLED_TARGET, i, "pong");
+            }
+        });
+
+        assert_eq!(capturing_layer.count(SAMPLED_TARGET, Level::DEBUG), 0);
+        assert_eq!(
+            capturing_layer.count(SAMPLED_TARGET, Level::INFO),
+            EVENTS / 2
+        );
+    }
+}
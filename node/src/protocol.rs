@@ -8,8 +8,11 @@ use hex_fmt::HexFmt;
 use serde::{Deserialize, Serialize};
 
 use crate::{
-    components::{consensus, gossiper, small_network::GossipedAddress},
-    types::{Deploy, Item, Tag},
+    components::{
+        consensus, gossiper,
+        small_network::{GossipedAddress, Payload, Priority},
+    },
+    types::{Deploy, FinalitySignature, Item, Tag},
 };
 
 /// Reactor message.
@@ -24,6 +27,12 @@ pub enum Message {
     /// Address gossiper component message.
     #[from]
     AddressGossiper(gossiper::Message<GossipedAddress>),
+    /// A validator's signature of a block as final.
+    #[from]
+    FinalitySignature(Box<FinalitySignature>),
+    /// An operator's order to restart consensus from a known-good block.
+    #[from]
+    EmergencyRestart(Box<consensus::EmergencyRestart>),
     /// Request to get an item from a peer.
     GetRequest {
         /// The type tag of the requested item.
@@ -56,12 +65,30 @@ impl Message {
     }
 }
 
+impl Payload for Message {
+    fn priority(&self) -> Priority {
+        match self {
+            Message::Consensus(_)
+            | Message::FinalitySignature(_)
+            | Message::EmergencyRestart(_) => Priority::High,
+            Message::DeployGossiper(_)
+            | Message::AddressGossiper(_)
+            | Message::GetRequest { .. }
+            | Message::GetResponse { .. } => Priority::Bulk,
+        }
+    }
+}
+
 impl Debug for Message {
     fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
         match self {
             Message::Consensus(c) => f.debug_tuple("Consensus").field(&c).finish(),
             Message::DeployGossiper(dg) => f.debug_tuple("DeployGossiper").field(&dg).finish(),
             Message::AddressGossiper(ga) => f.debug_tuple("AddressGossiper").field(&ga).finish(),
+            Message::FinalitySignature(fs) => {
+                f.debug_tuple("FinalitySignature").field(&fs).finish()
+            }
+            Message::EmergencyRestart(er) => f.debug_tuple("EmergencyRestart").field(&er).finish(),
             Message::GetRequest { tag, serialized_id } => f
                 .debug_struct("GetRequest")
                 .field("tag", tag)
@@ -87,6 +114,10 @@ impl Display for Message {
             Message::AddressGossiper(gossiped_address) => {
                 write!(f, "AddressGossiper::({})", gossiped_address)
             }
+            Message::FinalitySignature(finality_signature) => {
+                write!(f, "FinalitySignature::({})", finality_signature)
+            }
+            Message::EmergencyRestart(restart) => write!(f, "EmergencyRestart::({})", restart),
             Message::GetRequest { tag, serialized_id } => {
                 write!(f, "GetRequest({}-{:10})", tag, HexFmt(serialized_id))
             }
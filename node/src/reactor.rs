@@ -23,28 +23,63 @@
 //! With all these set up, a reactor can be executed using a [`Runner`](struct.Runner.html), either
 //! in a step-wise manner using [`crank`](struct.Runner.html#method.crank) or indefinitely using
 //! [`run`](struct.Runner.html#method.crank).
+//!
+//! # Stalls
+//!
+//! Since events are dispatched one at a time, a single slow dispatch, e.g. due to accidental
+//! blocking I/O in a component, stalls the whole node.  If a
+//! [stall threshold](struct.Runner.html#method.set_stall_threshold) is set, a watchdog thread
+//! logs a warning naming the event as soon as a dispatch has taken longer, while it is still in
+//! progress, and counts it in the `runner_dispatch_stalls` metric.
+//!
+//! # Shutdown
+//!
+//! On shutdown, the runner can [`drain`](struct.Runner.html#method.drain) the event queue: external
+//! events are no longer accepted, but everything already queued is processed, along with any events
+//! resulting from it, until the queue is empty or a deadline passes.
+//!
+//! [`shutdown`](struct.Runner.html#method.shutdown) additionally logs the
+//! [reason](../types/enum.ShutdownReason.html) for shutting down and lets the reactor prepare for
+//! it, e.g. by reporting the reason to peers, before draining.  Besides the operator, components
+//! hitting an error the node can't recover from request a shutdown, for a reason describing the
+//! error, via [`EffectBuilder::request_shutdown`](../effect/struct.EffectBuilder.html), which
+//! makes [`run`](struct.Runner.html#method.run) return.
 
+mod event_metrics;
 pub mod initializer;
 pub mod joiner;
 mod queue_kind;
+mod stall_watchdog;
 pub mod validator;
 
 use std::{
-    fmt::{Debug, Display},
+    fmt::{self, Debug, Display},
     mem,
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc,
+    },
+    time::{Duration, Instant},
 };
 
-use futures::{future::BoxFuture, FutureExt};
+use futures::{
+    future::{self, BoxFuture, Either},
+    pin_mut, Future, FutureExt,
+};
 use prometheus::{self, IntCounter, Registry};
 use rand::{CryptoRng, Rng};
-use tracing::{debug, debug_span, info, trace, warn};
+use tokio::time;
+use tracing::{debug, debug_span, error, info, trace, warn};
 use tracing_futures::Instrument;
 
 use crate::{
     effect::{Effect, EffectBuilder, Effects},
+    types::ShutdownReason,
     utils::{self, WeightedRoundRobin},
 };
+pub(crate) use event_metrics::EventMetrics;
 pub use queue_kind::QueueKind;
+use stall_watchdog::StallWatchdog;
 
 /// Event scheduler
 ///
@@ -75,12 +110,28 @@ impl<REv> EventQueueHandle<REv> {
         EventQueueHandle(scheduler)
     }
 
+    /// Returns the number of events currently queued.
+    pub(crate) fn event_queue_depth(self) -> usize {
+        self.0.item_count()
+    }
+
+    /// Returns whether the queue has been sealed, since the reactor is draining it on shutdown.
+    pub(crate) fn is_sealed(self) -> bool {
+        self.0.is_sealed()
+    }
+
     /// Schedule an event on a specific queue.
+    ///
+    /// External events are dropped if the reactor is draining its queue on shutdown.
     #[inline]
     pub(crate) async fn schedule<Ev>(self, event: Ev, queue_kind: QueueKind)
     where
         REv: From<Ev>,
     {
+        if queue_kind.is_external() && self.0.is_sealed() {
+            debug!(?queue_kind, "rejecting external event while draining");
+            return;
+        }
         self.0.push(event.into(), queue_kind).await
     }
 }
@@ -133,6 +184,41 @@ pub trait Reactor<R: Rng + CryptoRng + ?Sized>: Sized {
     fn is_stopped(&mut self) -> bool {
         false
     }
+
+    /// Returns the reason for which a component requested the node to shut down, if any, clearing
+    /// the request.
+    ///
+    /// Reactors receiving a `ControlAnnouncement::FatalError` should return its reason here.  The
+    /// runner checks for a request after every dispatch and stops running the reactor once there
+    /// is one.
+    #[inline]
+    fn take_shutdown_request(&mut self) -> Option<ShutdownReason> {
+        None
+    }
+
+    /// Prepares the reactor for the node shutting down for the given reason.
+    ///
+    /// Called before the event queue is drained, e.g. to report the reason to peers.
+    #[inline]
+    fn prepare_shutdown(&mut self, _reason: &ShutdownReason) {}
+
+    /// Starts the reactor, calling `ComponentLifecycle::on_start` on its components.
+    ///
+    /// Called once the effects of the instantiation have been processed, before any event is
+    /// dispatched.
+    #[inline]
+    fn start(&mut self) {}
+
+    /// Stops the reactor, calling `ComponentLifecycle::on_stop` on its components.
+    ///
+    /// Called once the reactor has finished running, or when shutting down once the event queue has
+    /// been drained.
+    #[inline]
+    fn stop(&mut self) {}
+
+    /// Restarts the reactor in place, calling `ComponentLifecycle::on_restart` on its components.
+    #[inline]
+    fn restart(&mut self) {}
 }
 
 /// A drop-like trait for `async` compatible drop-and-wait.
@@ -148,6 +234,19 @@ pub trait Finalize: Sized {
     }
 }
 
+/// Interval at which draining checks whether effects have completed while the queue is empty.
+const DRAIN_POLL_INTERVAL: Duration = Duration::from_millis(10);
+
+/// Time after which draining considers the reactor idle if the queue has stayed empty, even though
+/// effects are still running.
+///
+/// Some effects never complete by themselves, e.g. timers for periodic tasks or handlers of
+/// connections, so draining can't wait for all of them.
+const DRAIN_IDLE_TIMEOUT: Duration = Duration::from_millis(500);
+
+/// The maximum length of the event kind logged when a dispatch stalls.
+const MAX_EVENT_KIND_LENGTH: usize = 64;
+
 /// A runner for a reactor.
 ///
 /// The runner manages a reactors event queue and reactor itself and can run it either continuously
@@ -169,6 +268,12 @@ where
 
     /// Metrics for the runner.
     metrics: RunnerMetrics,
+
+    /// The watchdog reporting stalling dispatches, if a stall threshold is set.
+    stall_watchdog: Option<StallWatchdog>,
+
+    /// The number of effects created while draining which are still running, if draining.
+    draining: Option<Arc<AtomicUsize>>,
 }
 
 /// Metric data for the Runner
@@ -177,6 +282,9 @@ struct RunnerMetrics {
     /// Total number of events processed.
     events: IntCounter,
 
+    /// Number of dispatches exceeding the stall threshold.
+    dispatch_stalls: IntCounter,
+
     /// Handle to the metrics registry, in case we need to unregister.
     registry: Registry,
 }
@@ -186,9 +294,15 @@ impl RunnerMetrics {
     fn new(registry: &Registry) -> Result<Self, prometheus::Error> {
         let events = IntCounter::new("runner_events", "total event count")?;
         registry.register(Box::new(events.clone()))?;
+        let dispatch_stalls = IntCounter::new(
+            "runner_dispatch_stalls",
+            "number of event dispatches exceeding the stall threshold",
+        )?;
+        registry.register(Box::new(dispatch_stalls.clone()))?;
 
         Ok(RunnerMetrics {
             events,
+            dispatch_stalls,
             registry: registry.clone(),
         })
     }
@@ -198,7 +312,10 @@ impl Drop for RunnerMetrics {
     fn drop(&mut self) {
         self.registry
             .unregister(Box::new(self.events.clone()))
-            .expect("did not expect deregistering metrics to fail")
+            .expect("did not expect deregistering metrics to fail");
+        self.registry
+            .unregister(Box::new(self.dispatch_stalls.clone()))
+            .expect("did not expect deregistering metrics to fail");
     }
 }
 
@@ -237,13 +354,14 @@ where
         let scheduler = utils::leak(Scheduler::new(QueueKind::weights()));
 
         let event_queue = EventQueueHandle::new(scheduler);
-        let (reactor, initial_effects) = R::new(cfg, registry, event_queue, rng)?;
+        let (mut reactor, initial_effects) = R::new(cfg, registry, event_queue, rng)?;
 
         // Run all effects from component instantiation.
         let span = debug_span!("process initial effects");
-        process_effects(scheduler, initial_effects)
+        process_effects(scheduler, initial_effects, None)
             .instrument(span)
             .await;
+        reactor.start();
 
         info!("reactor main loop is ready");
 
@@ -252,9 +370,25 @@ where
             reactor,
             event_count: 0,
             metrics: RunnerMetrics::new(registry)?,
+            stall_watchdog: None,
+            draining: None,
         })
     }
 
+    /// Sets the duration after which a single dispatch is considered to stall the reactor.
+    ///
+    /// Stalling dispatches are logged and counted.  A zero `threshold` disables stall detection.
+    pub fn set_stall_threshold(&mut self, threshold: Duration) {
+        // Dropping the previous watchdog stops its thread.
+        self.stall_watchdog = None;
+        if threshold > Duration::from_secs(0) {
+            self.stall_watchdog = Some(StallWatchdog::new(
+                threshold,
+                self.metrics.dispatch_stalls.clone(),
+            ));
+        }
+    }
+
     /// Inject (schedule then process) effects created via a call to `create_effects` which is
     /// itself passed an instance of an `EffectBuilder`.
     #[cfg(test)]
@@ -268,7 +402,7 @@ where
         let effects = create_effects(effect_builder);
 
         let effect_span = debug_span!("process injected effects", ev = self.event_count);
-        process_effects(self.scheduler, effects)
+        process_effects(self.scheduler, effects, None)
             .instrument(effect_span)
             .await;
     }
@@ -296,15 +430,24 @@ where
         debug!(%event, ?q);
         trace!(?event, ?q);
 
+        // The event is consumed by the dispatch, so its kind is recorded upfront in case it stalls.
+        if let Some(stall_watchdog) = &self.stall_watchdog {
+            stall_watchdog.dispatch_started(event_kind(&event));
+        }
+
         // Dispatch the event, then execute the resulting effect.
         let effects = self.reactor.dispatch_event(effect_builder, rng, event);
 
+        if let Some(stall_watchdog) = &self.stall_watchdog {
+            stall_watchdog.dispatch_finished();
+        }
+
         drop(inner_enter);
 
         // We create another span for the effects, but will keep the same ID.
         let effect_span = debug_span!("process effects", ev = self.event_count);
 
-        process_effects(self.scheduler, effects)
+        process_effects(self.scheduler, effects, self.draining.clone())
             .instrument(effect_span)
             .await;
         self.event_count += 1;
@@ -321,14 +464,122 @@ where
         }
     }
 
-    /// Runs the reactor until `is_stopped()` returns true.
+    /// Runs the reactor until `is_stopped()` returns true or a component requests a shutdown.
+    ///
+    /// Returns the reason for the requested shutdown, if any.
     #[inline]
-    pub async fn run(&mut self, rng: &mut RNG) {
+    pub async fn run(&mut self, rng: &mut RNG) -> Option<ShutdownReason> {
+        while !self.reactor.is_stopped() {
+            self.crank(rng).await;
+            if let Some(reason) = self.reactor.take_shutdown_request() {
+                return Some(reason);
+            }
+        }
+        None
+    }
+
+    /// Runs the reactor until `is_stopped()` returns true, a component requests a shutdown or
+    /// `shutdown` completes.
+    ///
+    /// Returns the reason to shut down for, which is `ShutdownReason::OperatorSignal` if
+    /// `shutdown` completed, or `None` if the reactor stopped.
+    pub async fn run_until<F: Future>(
+        &mut self,
+        rng: &mut RNG,
+        shutdown: F,
+    ) -> Option<ShutdownReason> {
+        pin_mut!(shutdown);
         while !self.reactor.is_stopped() {
+            let item_available = self.scheduler.wait_for_item();
+            pin_mut!(item_available);
+            if let Either::Right(_) = future::select(item_available, shutdown.as_mut()).await {
+                return Some(ShutdownReason::OperatorSignal);
+            }
             self.crank(rng).await;
+            if let Some(reason) = self.reactor.take_shutdown_request() {
+                return Some(reason);
+            }
+        }
+        None
+    }
+
+    /// Drains the event queue.
+    ///
+    /// External events are rejected from now on. All events already queued are processed, as are
+    /// all events resulting from effects created while draining, until the reactor is idle or
+    /// `timeout` has elapsed.  The reactor is idle once the queue is empty and either no effects
+    /// created while draining are running anymore, or the queue has stayed empty for
+    /// `DRAIN_IDLE_TIMEOUT`.
+    ///
+    /// Returns `true` if the queue was drained completely.
+    pub async fn drain(&mut self, rng: &mut RNG, timeout: Duration) -> bool {
+        self.scheduler.seal();
+        let pending_effects = Arc::clone(
+            self.draining
+                .get_or_insert_with(|| Arc::new(AtomicUsize::new(0))),
+        );
+        let deadline = Instant::now() + timeout;
+        let mut last_event = Instant::now();
+
+        loop {
+            if self.scheduler.item_count() > 0 {
+                self.crank(rng).await;
+                last_event = Instant::now();
+            } else if pending_effects.load(Ordering::SeqCst) > 0
+                && last_event.elapsed() < DRAIN_IDLE_TIMEOUT
+            {
+                time::delay_for(DRAIN_POLL_INTERVAL).await;
+            } else {
+                info!(
+                    pending_effects = pending_effects.load(Ordering::SeqCst),
+                    "drained event queue"
+                );
+                return true;
+            }
+
+            if Instant::now() >= deadline {
+                warn!(
+                    remaining_events = self.scheduler.item_count(),
+                    pending_effects = pending_effects.load(Ordering::SeqCst),
+                    "timed out draining event queue"
+                );
+                return false;
+            }
         }
     }
 
+    /// Shuts down for the given reason.
+    ///
+    /// The reason is logged and passed to the reactor to prepare for the shutdown, then the event
+    /// queue is [drained](#method.drain) and the reactor stopped.  Returns `true` if the queue was
+    /// drained completely.
+    pub async fn shutdown(
+        &mut self,
+        rng: &mut RNG,
+        reason: ShutdownReason,
+        timeout: Duration,
+    ) -> bool {
+        match reason {
+            ShutdownReason::OperatorSignal => info!(%reason, "shutting down"),
+            _ => error!(%reason, "shutting down"),
+        }
+        self.reactor.prepare_shutdown(&reason);
+        let drained = self.drain(rng, timeout).await;
+        self.stop();
+        drained
+    }
+
+    /// Stops the reactor once it has finished running, before it is deconstructed or dropped.
+    pub fn stop(&mut self) {
+        self.reactor.stop();
+    }
+
+    /// Restarts the reactor in place, discarding the transient state of its components.
+    pub fn restart(&mut self) {
+        info!("restarting reactor");
+        self.reactor.restart();
+    }
+
     /// Returns a reference to the reactor.
     #[inline]
     pub fn reactor(&self) -> &R {
@@ -348,20 +599,81 @@ where
     }
 }
 
+/// Returns the kind of `event`, i.e. the leading variant names of its `Debug` representation, like
+/// `Storage::Request::GetDeploys`.
+///
+/// Formatting is aborted as soon as the names end, so the cost does not depend on the event's
+/// payload.
+fn event_kind<E: Debug>(event: &E) -> String {
+    use fmt::Write;
+
+    /// A writer collecting variant names, failing once they end to abort formatting.
+    struct VariantNames {
+        names: String,
+        /// Whether the next character starts a name.
+        at_start: bool,
+    }
+
+    impl Write for VariantNames {
+        fn write_str(&mut self, s: &str) -> fmt::Result {
+            for c in s.chars() {
+                if self.names.len() >= MAX_EVENT_KIND_LENGTH {
+                    return Err(fmt::Error);
+                }
+                if self.at_start && !c.is_uppercase() {
+                    // Not a variant or type name, but the start of the payload.
+                    return Err(fmt::Error);
+                }
+                match c {
+                    '(' => {
+                        self.names.push_str("::");
+                        self.at_start = true;
+                    }
+                    c if c.is_alphanumeric() || c == '_' => {
+                        self.names.push(c);
+                        self.at_start = false;
+                    }
+                    _ => return Err(fmt::Error),
+                }
+            }
+            Ok(())
+        }
+    }
+
+    let mut writer = VariantNames {
+        names: String::new(),
+        at_start: true,
+    };
+    let _ = write!(writer, "{:?}", event);
+    writer.names.trim_end_matches(':').to_string()
+}
+
 /// Spawns tasks that will process the given effects.
+///
+/// If given, `pending` is incremented for each effect and decremented once its events are queued.
 #[inline]
-async fn process_effects<Ev>(scheduler: &'static Scheduler<Ev>, effects: Effects<Ev>)
-where
+async fn process_effects<Ev>(
+    scheduler: &'static Scheduler<Ev>,
+    effects: Effects<Ev>,
+    pending: Option<Arc<AtomicUsize>>,
+) where
     Ev: Send + 'static,
 {
     // TODO: Properly carry around priorities.
     let queue_kind = QueueKind::default();
 
     for effect in effects {
+        let pending = pending.clone();
+        if let Some(ref pending) = pending {
+            pending.fetch_add(1, Ordering::SeqCst);
+        }
         tokio::spawn(async move {
             for event in effect.await {
                 scheduler.push(event, queue_kind).await
             }
+            if let Some(pending) = pending {
+                pending.fetch_sub(1, Ordering::SeqCst);
+            }
         });
     }
 }
@@ -395,3 +707,505 @@ where
         .map(move |effect| wrap_effect(wrap.clone(), effect))
         .collect()
 }
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        fmt::{self, Formatter},
+        sync::Mutex,
+        thread,
+    };
+
+    use tracing::{
+        field::{Field, Visit},
+        Event, Level, Subscriber,
+    };
+    use tracing_subscriber::{
+        layer::{Context, Layer, SubscriberExt},
+        registry,
+    };
+
+    use super::*;
+    use crate::{
+        components::ComponentLifecycle,
+        effect::{announcements::ControlAnnouncement, EffectExt},
+        fatal,
+        testing::TestRng,
+    };
+
+    /// An event counting down to zero, each step being handled in a separate effect.
+    #[derive(Debug)]
+    struct Countdown(u32);
+
+    /// A countdown whose next step never comes, like the timer of a periodic task.
+    const STUCK: u32 = u32::MAX;
+
+    impl Display for Countdown {
+        fn fmt(&self, formatter: &mut Formatter<'_>) -> fmt::Result {
+            write!(formatter, "countdown {}", self.0)
+        }
+    }
+
+    /// A reactor which counts the events it has processed and records why it was shut down.
+    #[derive(Debug)]
+    struct CountingReactor {
+        processed: usize,
+        shutdown_reason: Option<ShutdownReason>,
+    }
+
+    impl Reactor<TestRng> for CountingReactor {
+        type Event = Countdown;
+        type Config = ();
+        type Error = prometheus::Error;
+
+        fn dispatch_event(
+            &mut self,
+            _effect_builder: EffectBuilder<Self::Event>,
+            _rng: &mut TestRng,
+            event: Self::Event,
+        ) -> Effects<Self::Event> {
+            self.processed += 1;
+            match event.0 {
+                0 => Effects::new(),
+                STUCK => future::pending::<()>().ignore(),
+                remaining => async move {
+                    time::delay_for(Duration::from_millis(5)).await;
+                    Countdown(remaining - 1)
+                }
+                .event(|event| event),
+            }
+        }
+
+        fn new(
+            _cfg: Self::Config,
+            _registry: &Registry,
+            _event_queue: EventQueueHandle<Self::Event>,
+            _rng: &mut TestRng,
+        ) -> Result<(Self, Effects<Self::Event>), Self::Error> {
+            let reactor = CountingReactor {
+                processed: 0,
+                shutdown_reason: None,
+            };
+            Ok((reactor, Effects::new()))
+        }
+
+        fn prepare_shutdown(&mut self, reason: &ShutdownReason) {
+            self.shutdown_reason = Some(reason.clone());
+        }
+    }
+
+    #[tokio::test]
+    async fn should_process_queued_events_when_draining_and_reject_external_ones() {
+        let mut rng = TestRng::new();
+        let mut runner = Runner::<CountingReactor, _>::new((), &mut rng)
+            .await
+            .unwrap();
+        let event_queue = EventQueueHandle::new(runner.scheduler);
+
+        // Events queued before shutdown, each resulting in two more via effects.
+        for _ in 0..3 {
+            event_queue.schedule(Countdown(2), QueueKind::Regular).await;
+            event_queue
+                .schedule(Countdown(2), QueueKind::NetworkIncoming)
+                .await;
+        }
+
+        assert!(runner.drain(&mut rng, Duration::from_secs(10)).await);
+        assert_eq!(runner.reactor().processed, 18);
+
+        // New external events are rejected, while internal ones are still accepted.
+        event_queue
+            .schedule(Countdown(0), QueueKind::NetworkIncoming)
+            .await;
+        assert_eq!(runner.scheduler.item_count(), 0);
+        event_queue.schedule(Countdown(0), QueueKind::Regular).await;
+        assert_eq!(runner.scheduler.item_count(), 1);
+    }
+
+    #[tokio::test]
+    async fn should_stop_draining_once_idle_despite_running_effects() {
+        let mut rng = TestRng::new();
+        let mut runner = Runner::<CountingReactor, _>::new((), &mut rng)
+            .await
+            .unwrap();
+        let event_queue = EventQueueHandle::new(runner.scheduler);
+        event_queue
+            .schedule(Countdown(STUCK), QueueKind::Regular)
+            .await;
+        event_queue.schedule(Countdown(2), QueueKind::Regular).await;
+
+        // The stuck effect never completes, but the queue stays empty once the countdown is done.
+        let start = Instant::now();
+        assert!(runner.drain(&mut rng, Duration::from_secs(10)).await);
+        assert!(start.elapsed() < Duration::from_secs(5));
+        assert_eq!(runner.reactor().processed, 4);
+    }
+
+    #[tokio::test]
+    async fn should_log_and_pass_on_shutdown_reason() {
+        let mut rng = TestRng::new();
+        let events = RecordingLayer::default();
+        let _guard = tracing::subscriber::set_default(registry().with(events.clone()));
+
+        let reasons = [
+            (ShutdownReason::OperatorSignal, Level::INFO),
+            (
+                ShutdownReason::ConsensusFailure("equivocation".to_string()),
+                Level::ERROR,
+            ),
+            (
+                ShutdownReason::StorageFailure("disk full".to_string()),
+                Level::ERROR,
+            ),
+            (
+                ShutdownReason::FatalError("invariant violated".to_string()),
+                Level::ERROR,
+            ),
+        ];
+        for (reason, level) in &reasons {
+            let mut runner = Runner::<CountingReactor, _>::new((), &mut rng)
+                .await
+                .unwrap();
+            assert!(
+                runner
+                    .shutdown(&mut rng, reason.clone(), Duration::from_secs(10))
+                    .await
+            );
+            assert_eq!(runner.reactor().shutdown_reason.as_ref(), Some(reason));
+            let logged = ("reason".to_string(), reason.to_string());
+            assert!(events
+                .recorded(level.clone())
+                .iter()
+                .any(|fields| fields.contains(&logged)));
+        }
+    }
+
+    /// A reactor recording the shutdowns requested by its components.
+    #[derive(Debug)]
+    struct FailingReactor {
+        shutdown_request: Option<ShutdownReason>,
+    }
+
+    impl Reactor<TestRng> for FailingReactor {
+        type Event = ControlAnnouncement;
+        type Config = ();
+        type Error = prometheus::Error;
+
+        fn dispatch_event(
+            &mut self,
+            _effect_builder: EffectBuilder<Self::Event>,
+            _rng: &mut TestRng,
+            event: Self::Event,
+        ) -> Effects<Self::Event> {
+            let ControlAnnouncement::FatalError(reason) = event;
+            self.shutdown_request = Some(reason);
+            Effects::new()
+        }
+
+        fn new(
+            _cfg: Self::Config,
+            _registry: &Registry,
+            _event_queue: EventQueueHandle<Self::Event>,
+            _rng: &mut TestRng,
+        ) -> Result<(Self, Effects<Self::Event>), Self::Error> {
+            let reactor = FailingReactor {
+                shutdown_request: None,
+            };
+            Ok((reactor, Effects::new()))
+        }
+
+        fn take_shutdown_request(&mut self) -> Option<ShutdownReason> {
+            self.shutdown_request.take()
+        }
+    }
+
+    #[tokio::test]
+    async fn should_stop_running_when_shutdown_is_requested() {
+        let mut rng = TestRng::new();
+        let reasons = vec![
+            ShutdownReason::ConsensusFailure("equivocation".to_string()),
+            ShutdownReason::StorageFailure("disk full".to_string()),
+            ShutdownReason::FatalError("invariant violated".to_string()),
+        ];
+        for reason in reasons {
+            let mut runner = Runner::<FailingReactor, _>::new((), &mut rng)
+                .await
+                .unwrap();
+            let requested = reason.clone();
+            runner
+                .process_injected_effects(|effect_builder| {
+                    effect_builder.request_shutdown(requested).ignore()
+                })
+                .await;
+            assert_eq!(runner.run(&mut rng).await, Some(reason));
+        }
+
+        // Fatal errors are reported along with their location.
+        let mut runner = Runner::<FailingReactor, _>::new((), &mut rng)
+            .await
+            .unwrap();
+        runner
+            .process_injected_effects(|effect_builder| fatal!(effect_builder, "invariant violated"))
+            .await;
+        match runner.run(&mut rng).await {
+            Some(ShutdownReason::FatalError(error)) => {
+                assert!(error.starts_with("invariant violated ["), "{}", error)
+            }
+            other => panic!("unexpected shutdown reason {:?}", other),
+        }
+    }
+
+    /// A component recording the lifecycle hooks called on it.
+    #[derive(Debug, Default)]
+    struct LifecycleRecorder(Vec<&'static str>);
+
+    impl ComponentLifecycle for LifecycleRecorder {
+        fn on_start(&mut self) {
+            self.0.push("start");
+        }
+
+        fn on_stop(&mut self) {
+            self.0.push("stop");
+        }
+
+        fn on_restart(&mut self) {
+            self.0.push("restart");
+        }
+    }
+
+    /// A reactor with two lifecycle-aware components.
+    #[derive(Debug, Default)]
+    struct LifecycleReactor {
+        first: LifecycleRecorder,
+        second: LifecycleRecorder,
+    }
+
+    impl LifecycleReactor {
+        fn recorded(&self) -> Vec<Vec<&'static str>> {
+            vec![self.first.0.clone(), self.second.0.clone()]
+        }
+    }
+
+    impl Reactor<TestRng> for LifecycleReactor {
+        type Event = Countdown;
+        type Config = ();
+        type Error = prometheus::Error;
+
+        fn dispatch_event(
+            &mut self,
+            _effect_builder: EffectBuilder<Self::Event>,
+            _rng: &mut TestRng,
+            _event: Self::Event,
+        ) -> Effects<Self::Event> {
+            Effects::new()
+        }
+
+        fn new(
+            _cfg: Self::Config,
+            _registry: &Registry,
+            _event_queue: EventQueueHandle<Self::Event>,
+            _rng: &mut TestRng,
+        ) -> Result<(Self, Effects<Self::Event>), Self::Error> {
+            Ok((LifecycleReactor::default(), Effects::new()))
+        }
+
+        fn start(&mut self) {
+            self.first.on_start();
+            self.second.on_start();
+        }
+
+        fn stop(&mut self) {
+            self.first.on_stop();
+            self.second.on_stop();
+        }
+
+        fn restart(&mut self) {
+            self.first.on_restart();
+            self.second.on_restart();
+        }
+    }
+
+    #[tokio::test]
+    async fn should_call_lifecycle_hooks_of_components() {
+        let mut rng = TestRng::new();
+        let mut runner = Runner::<LifecycleReactor, _>::new((), &mut rng)
+            .await
+            .unwrap();
+        assert_eq!(runner.reactor().recorded(), vec![vec!["start"]; 2]);
+
+        runner.restart();
+        assert_eq!(
+            runner.reactor().recorded(),
+            vec![vec!["start", "restart"]; 2]
+        );
+
+        // Stopping happens only once the queue has been drained.
+        let event_queue = EventQueueHandle::new(runner.scheduler);
+        event_queue.schedule(Countdown(0), QueueKind::Regular).await;
+        assert!(
+            runner
+                .shutdown(
+                    &mut rng,
+                    ShutdownReason::OperatorSignal,
+                    Duration::from_secs(10)
+                )
+                .await
+        );
+        assert_eq!(runner.scheduler.item_count(), 0);
+        assert_eq!(
+            runner.reactor().recorded(),
+            vec![vec!["start", "restart", "stop"]; 2]
+        );
+    }
+
+    /// An event taking the reactor a given time to dispatch.
+    #[derive(Debug)]
+    enum Workload {
+        Quick,
+        Slow(Duration),
+        /// Blocks until the given stall counter has been incremented.
+        UntilStalled(IntCounter),
+    }
+
+    impl Display for Workload {
+        fn fmt(&self, formatter: &mut Formatter<'_>) -> fmt::Result {
+            write!(formatter, "{:?}", self)
+        }
+    }
+
+    /// A reactor blocking for the duration of slow events.
+    #[derive(Debug)]
+    struct BlockingReactor;
+
+    impl Reactor<TestRng> for BlockingReactor {
+        type Event = Workload;
+        type Config = ();
+        type Error = prometheus::Error;
+
+        fn dispatch_event(
+            &mut self,
+            _effect_builder: EffectBuilder<Self::Event>,
+            _rng: &mut TestRng,
+            event: Self::Event,
+        ) -> Effects<Self::Event> {
+            match event {
+                Workload::Quick => (),
+                Workload::Slow(duration) => thread::sleep(duration),
+                Workload::UntilStalled(stalls) => {
+                    let started = Instant::now();
+                    while stalls.get() == 0 {
+                        assert!(
+                            started.elapsed() < Duration::from_secs(10),
+                            "dispatch was not reported as stalling while in progress"
+                        );
+                        thread::sleep(Duration::from_millis(1));
+                    }
+                }
+            }
+            Effects::new()
+        }
+
+        fn new(
+            _cfg: Self::Config,
+            _registry: &Registry,
+            _event_queue: EventQueueHandle<Self::Event>,
+            _rng: &mut TestRng,
+        ) -> Result<(Self, Effects<Self::Event>), Self::Error> {
+            Ok((BlockingReactor, Effects::new()))
+        }
+    }
+
+    /// The fields of a log event.
+    type Fields = Vec<(String, String)>;
+
+    /// A layer recording the level and fields of every event it sees.
+    #[derive(Clone, Default)]
+    struct RecordingLayer(Arc<Mutex<Vec<(Level, Fields)>>>);
+
+    impl RecordingLayer {
+        /// Returns the fields of the recorded events at `level`.
+        fn recorded(&self, level: Level) -> Vec<Fields> {
+            self.0
+                .lock()
+                .unwrap()
+                .iter()
+                .filter(|(event_level, _)| *event_level == level)
+                .map(|(_, fields)| fields.clone())
+                .collect()
+        }
+    }
+
+    impl<S: Subscriber> Layer<S> for RecordingLayer {
+        fn on_event(&self, event: &Event<'_>, _ctx: Context<'_, S>) {
+            struct Visitor(Fields);
+
+            impl Visit for Visitor {
+                fn record_debug(&mut self, field: &Field, value: &dyn Debug) {
+                    self.0
+                        .push((field.name().to_string(), format!("{:?}", value)));
+                }
+            }
+
+            let mut visitor = Visitor(Vec::new());
+            event.record(&mut visitor);
+            self.0
+                .lock()
+                .unwrap()
+                .push((event.metadata().level().clone(), visitor.0));
+        }
+    }
+
+    #[tokio::test]
+    async fn should_warn_about_stalling_dispatch() {
+        let mut rng = TestRng::new();
+        let events = RecordingLayer::default();
+        let _guard = tracing::subscriber::set_default(registry().with(events.clone()));
+
+        let mut runner = Runner::<BlockingReactor, _>::new((), &mut rng)
+            .await
+            .unwrap();
+        runner.set_stall_threshold(Duration::from_millis(50));
+        let event_queue = EventQueueHandle::new(runner.scheduler);
+
+        event_queue
+            .schedule(Workload::Quick, QueueKind::Regular)
+            .await;
+        runner.crank(&mut rng).await;
+        assert_eq!(runner.metrics.dispatch_stalls.get(), 0);
+        assert!(events.recorded(Level::WARN).is_empty());
+
+        // The dispatch only returns once the watchdog has reported it as stalling.
+        event_queue
+            .schedule(
+                Workload::UntilStalled(runner.metrics.dispatch_stalls.clone()),
+                QueueKind::Regular,
+            )
+            .await;
+        runner.crank(&mut rng).await;
+        assert_eq!(runner.metrics.dispatch_stalls.get(), 1);
+        let recorded = events.recorded(Level::WARN);
+        assert_eq!(recorded.len(), 1);
+        assert!(recorded[0]
+            .iter()
+            .any(|(name, value)| name == "event" && value.starts_with("UntilStalled")));
+
+        // Without a threshold, slow dispatches go unnoticed.
+        runner.set_stall_threshold(Duration::from_secs(0));
+        event_queue
+            .schedule(
+                Workload::Slow(Duration::from_millis(100)),
+                QueueKind::Regular,
+            )
+            .await;
+        runner.crank(&mut rng).await;
+        assert_eq!(runner.metrics.dispatch_stalls.get(), 1);
+    }
+
+    #[test]
+    fn should_name_event_kind_by_leading_variants() {
+        assert_eq!(event_kind(&Workload::Quick), "Quick");
+        assert_eq!(event_kind(&Workload::Slow(Duration::from_secs(1))), "Slow");
+        assert_eq!(event_kind(&Some(Workload::Quick)), "Some::Quick");
+        assert_eq!(event_kind(&Countdown(3)), "Countdown");
+    }
+}
@@ -1,26 +1,40 @@
 //! Reactor used to initialize a node.
 
-use std::fmt::{self, Display, Formatter};
+mod self_check;
+
+use std::{
+    fmt::{self, Display, Formatter},
+    io,
+    path::PathBuf,
+    sync::Arc,
+};
 
 use derive_more::From;
 use prometheus::Registry;
 use rand::{CryptoRng, Rng};
 use thiserror::Error;
+use tracing::{info, warn};
 
 use crate::{
     components::{
         chainspec_loader::{self, ChainspecLoader},
         contract_runtime::{self, ContractRuntime},
         small_network::NodeId,
-        storage::{self, Storage, StorageType},
-        Component,
+        storage::{self, IntegrityCheck, Storage, StorageType},
+        Component, ComponentLifecycle,
+    },
+    crypto::{
+        asymmetric_key,
+        audit::{self, FileAuditSink},
     },
     effect::{
+        announcements::{ControlAnnouncement, StorageAnnouncement},
         requests::{ContractRuntimeRequest, NetworkRequest, StorageRequest},
         EffectBuilder, Effects,
     },
     protocol::Message,
     reactor::{self, validator, EventQueueHandle},
+    types::ShutdownReason,
     utils::WithDir,
 };
 
@@ -39,6 +53,14 @@ pub enum Event {
     /// Contract runtime event.
     #[from]
     ContractRuntime(contract_runtime::Event),
+
+    /// Control announcement.
+    #[from]
+    ControlAnnouncement(ControlAnnouncement),
+
+    /// Storage announcement.
+    #[from]
+    StorageAnnouncement(StorageAnnouncement),
 }
 
 impl From<StorageRequest<Storage>> for Event {
@@ -65,6 +87,8 @@ impl Display for Event {
             Event::Chainspec(event) => write!(formatter, "chainspec: {}", event),
             Event::Storage(event) => write!(formatter, "storage: {}", event),
             Event::ContractRuntime(event) => write!(formatter, "contract runtime: {}", event),
+            Event::ControlAnnouncement(ann) => write!(formatter, "control announcement: {}", ann),
+            Event::StorageAnnouncement(ann) => write!(formatter, "storage announcement: {}", ann),
         }
     }
 }
@@ -76,6 +100,19 @@ pub enum Error {
     #[error("config error: {0}")]
     ConfigError(String),
 
+    /// Failed to open the audit log.
+    #[error("failed to open audit log {}: {source}", path.display())]
+    AuditLog {
+        /// The path of the audit log.
+        path: PathBuf,
+        /// The underlying error.
+        source: io::Error,
+    },
+
+    /// Startup self-check error.
+    #[error(transparent)]
+    SelfCheck(#[from] self_check::Error),
+
     /// Metrics-related error
     #[error("prometheus (metrics) error: {0}")]
     Metrics(#[from] prometheus::Error),
@@ -100,6 +137,8 @@ pub struct Reactor {
     pub(super) chainspec_loader: ChainspecLoader,
     pub(super) storage: Storage,
     pub(super) contract_runtime: ContractRuntime,
+    /// The reason for which a component requested the node to shut down, if any.
+    shutdown_request: Option<ShutdownReason>,
 }
 
 impl Reactor {
@@ -122,6 +161,21 @@ impl<RNG: Rng + CryptoRng + ?Sized> reactor::Reactor<RNG> for Reactor {
     ) -> Result<(Self, Effects<Self::Event>), Error> {
         let (root, config) = config.into_parts();
 
+        // Start auditing before the self-check loads the secret key.
+        if let Some(ref audit_log_path) = config.node.audit_log_path {
+            let path = root.join(audit_log_path);
+            let sink = FileAuditSink::open(&path).map_err(|source| Error::AuditLog {
+                path: path.clone(),
+                source,
+            })?;
+            audit::set_audit_sink(Some(Arc::new(sink)));
+        }
+
+        // Verify the environment is usable before creating any components.
+        self_check::run(&root, &config)?;
+
+        asymmetric_key::set_verification_cache_capacity(config.node.signature_cache_capacity);
+
         let chainspec = config
             .node
             .chainspec_config_path
@@ -132,6 +186,15 @@ impl<RNG: Rng + CryptoRng + ?Sized> reactor::Reactor<RNG> for Reactor {
         let effect_builder = EffectBuilder::new(event_queue);
 
         let storage = Storage::new(&config.storage)?;
+        let integrity_check = config.storage.integrity_check();
+        if integrity_check != IntegrityCheck::Off {
+            let report = storage.verify_integrity(integrity_check == IntegrityCheck::Repair)?;
+            if report.is_clean() {
+                info!("storage integrity check passed");
+            } else {
+                warn!("storage integrity check found {}", report);
+            }
+        }
         let contract_runtime =
             ContractRuntime::new(&config.storage, config.contract_runtime, registry)?;
         let (chainspec_loader, chainspec_effects) =
@@ -145,6 +208,7 @@ impl<RNG: Rng + CryptoRng + ?Sized> reactor::Reactor<RNG> for Reactor {
                 chainspec_loader,
                 storage,
                 contract_runtime,
+                shutdown_request: None,
             },
             effects,
         ))
@@ -171,10 +235,28 @@ impl<RNG: Rng + CryptoRng + ?Sized> reactor::Reactor<RNG> for Reactor {
                 self.contract_runtime
                     .handle_event(effect_builder, rng, event),
             ),
+            Event::ControlAnnouncement(ControlAnnouncement::FatalError(reason)) => {
+                let _ = self.shutdown_request.get_or_insert(reason);
+                Effects::new()
+            }
+            // The initializer accepts no deploys, so there is no load to shed.
+            Event::StorageAnnouncement(StorageAnnouncement::OutOfSpace) => {
+                warn!("storage ran out of space during initialization");
+                Effects::new()
+            }
+            Event::StorageAnnouncement(StorageAnnouncement::SpaceAvailable) => Effects::new(),
         }
     }
 
+    fn take_shutdown_request(&mut self) -> Option<ShutdownReason> {
+        self.shutdown_request.take()
+    }
+
     fn is_stopped(&mut self) -> bool {
         self.chainspec_loader.is_stopped()
     }
+
+    fn stop(&mut self) {
+        self.storage.on_stop();
+    }
 }
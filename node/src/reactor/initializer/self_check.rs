@@ -0,0 +1,204 @@
+//! Startup self-check.
+//!
+//! Before any component is created, the node verifies that the environment described by its
+//! configuration is usable: storage can be written to and read from, the network and API ports can
+//! be bound and the consensus signing key can be loaded. All failures are collected and reported as
+//! a single error, so that misconfiguration is obvious rather than surfacing deep inside a
+//! component later on.
+
+use std::{
+    fmt::{self, Display, Formatter},
+    fs, io,
+    net::{SocketAddr, TcpListener},
+    path::{Path, PathBuf},
+};
+
+use thiserror::Error;
+
+use crate::{
+    components::storage,
+    crypto::asymmetric_key::SecretKey,
+    reactor::validator,
+    utils::{self, External},
+};
+
+/// Name of the file written to and read back from the storage folder.
+const PROBE_FILE_NAME: &str = ".self_check";
+/// Contents of the probe file.
+const PROBE_CONTENTS: &[u8] = b"casper-node self-check";
+
+/// A single failed check.
+#[derive(Debug, Error)]
+pub enum Failure {
+    /// The storage folder cannot be written to or read from.
+    #[error("storage at {} is not usable: {error}", .path.display())]
+    Storage {
+        /// The storage folder.
+        path: PathBuf,
+        /// The underlying error.
+        error: io::Error,
+    },
+    /// The network listening address cannot be bound.
+    #[error("cannot bind network address {address}: {error}")]
+    NetworkBind {
+        /// The configured bind address.
+        address: String,
+        /// The underlying error.
+        error: io::Error,
+    },
+    /// The API server address cannot be bound.
+    #[error("cannot bind API server address {address}: {error}")]
+    ApiBind {
+        /// The configured bind address.
+        address: SocketAddr,
+        /// The underlying error.
+        error: io::Error,
+    },
+    /// The consensus signing key cannot be loaded.
+    #[error("cannot load signing key: {0}")]
+    SigningKey(String),
+}
+
+/// The aggregated failures of the startup self-check.
+#[derive(Debug)]
+pub struct Error {
+    failures: Vec<Failure>,
+}
+
+impl Display for Error {
+    fn fmt(&self, formatter: &mut Formatter<'_>) -> fmt::Result {
+        write!(formatter, "startup self-check failed")?;
+        for failure in &self.failures {
+            write!(formatter, "; {}", failure)?;
+        }
+        Ok(())
+    }
+}
+
+impl std::error::Error for Error {}
+
+/// Runs all checks against `config`, resolving relative paths from `root`.
+pub(super) fn run<P: AsRef<Path>>(root: P, config: &validator::Config) -> Result<(), Error> {
+    let failures: Vec<_> = vec![
+        check_storage(&config.storage),
+        check_network_bind(&config.network.bind_address),
+        check_api_bind(SocketAddr::from((
+            config.http_server.bind_interface,
+            config.http_server.bind_port,
+        ))),
+        check_signing_key(root, &config.consensus.secret_key_path),
+    ]
+    .into_iter()
+    .filter_map(Result::err)
+    .collect();
+
+    if failures.is_empty() {
+        Ok(())
+    } else {
+        Err(Error { failures })
+    }
+}
+
+/// Checks that a file can be written to, read back from and removed from the storage folder.
+fn check_storage(config: &storage::Config) -> Result<(), Failure> {
+    let path = config.path();
+    probe_storage(&path).map_err(|error| Failure::Storage { path, error })
+}
+
+fn probe_storage(path: &Path) -> io::Result<()> {
+    fs::create_dir_all(path)?;
+    let probe_path = path.join(PROBE_FILE_NAME);
+    fs::write(&probe_path, PROBE_CONTENTS)?;
+    let contents = fs::read(&probe_path)?;
+    fs::remove_file(&probe_path)?;
+    if contents != PROBE_CONTENTS {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            "read back different contents than written",
+        ));
+    }
+    Ok(())
+}
+
+/// Checks that the network listening address resolves and can be bound.
+fn check_network_bind(address: &str) -> Result<(), Failure> {
+    utils::resolve_address(address)
+        .and_then(TcpListener::bind)
+        .map(drop)
+        .map_err(|error| Failure::NetworkBind {
+            address: address.to_string(),
+            error,
+        })
+}
+
+/// Checks that the API server address can be bound.
+fn check_api_bind(address: SocketAddr) -> Result<(), Failure> {
+    TcpListener::bind(address)
+        .map(drop)
+        .map_err(|error| Failure::ApiBind { address, error })
+}
+
+/// Checks that the consensus signing key can be loaded.
+fn check_signing_key<P: AsRef<Path>>(
+    root: P,
+    secret_key_path: &External<SecretKey>,
+) -> Result<(), Failure> {
+    let result = match secret_key_path {
+        External::Loaded(_) => return Ok(()),
+        External::Path(path) => External::<SecretKey>::path(path).load(root),
+        External::Missing => External::<SecretKey>::Missing.load(root),
+    };
+    result
+        .map(drop)
+        .map_err(|error| Failure::SigningKey(error.to_string()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn should_fail_for_unwritable_storage() {
+        // A path beneath a regular file can never be created, regardless of permissions.
+        let temp_dir = tempfile::tempdir().expect("should get tempdir");
+        let file_path = temp_dir.path().join("not-a-dir");
+        fs::write(&file_path, b"").expect("should write file");
+        let storage_path = file_path.join("storage");
+
+        let mut config = validator::Config::default();
+        config.storage = toml::from_str(&format!("path = '{}'", storage_path.display()))
+            .expect("should parse storage config");
+        config.network.bind_address = "127.0.0.1:0".to_string();
+        config.http_server.bind_port = 0;
+
+        let error = run(temp_dir.path(), &config).expect_err("self-check should fail");
+        assert!(matches!(
+            error.failures[0],
+            Failure::Storage { ref path, .. } if *path == storage_path
+        ));
+
+        let message = error.to_string();
+        assert!(message.starts_with("startup self-check failed"));
+        assert!(message.contains(&format!(
+            "storage at {} is not usable",
+            storage_path.display()
+        )));
+    }
+
+    #[test]
+    fn should_pass_for_valid_environment() {
+        let temp_dir = tempfile::tempdir().expect("should get tempdir");
+
+        let mut config = validator::Config::default();
+        config.storage = toml::from_str(&format!(
+            "path = '{}'",
+            temp_dir.path().join("storage").display()
+        ))
+        .expect("should parse storage config");
+        config.network.bind_address = "127.0.0.1:0".to_string();
+        config.http_server.bind_port = 0;
+        config.consensus.secret_key_path = External::value(SecretKey::generate_ed25519());
+
+        run(temp_dir.path(), &config).expect("self-check should pass");
+    }
+}
@@ -8,7 +8,7 @@ use derive_more::From;
 use prometheus::Registry;
 use rand::{CryptoRng, Rng};
 use small_network::GossipedAddress;
-use tracing::{error, info, trace, warn};
+use tracing::{debug, error, info, trace, warn};
 
 use crate::{
     components::{
@@ -21,19 +21,20 @@ use crate::{
         gossiper::{self, Gossiper},
         linear_chain,
         linear_chain_sync::{self, LinearChainSync},
-        small_network::{self, NodeId, SmallNetwork},
+        small_network::{self, HandshakeAttestation, NodeId, SmallNetwork},
         storage::{self, Storage},
-        Component,
+        Component, ComponentLifecycle,
     },
     crypto::hash::Digest,
     effect::{
         announcements::{
-            BlockExecutorAnnouncement, ConsensusAnnouncement, GossiperAnnouncement,
-            NetworkAnnouncement,
+            BlockExecutorAnnouncement, ConsensusAnnouncement, ControlAnnouncement,
+            GossiperAnnouncement, LinearChainAnnouncement, NetworkAnnouncement,
+            StorageAnnouncement,
         },
         requests::{
             BlockExecutorRequest, BlockValidationRequest, ConsensusRequest, ContractRuntimeRequest,
-            DeployBufferRequest, FetcherRequest, NetworkRequest, StorageRequest,
+            FetcherRequest, NetworkRequest, ProposalBuilderRequest, StorageRequest,
         },
         EffectBuilder, Effects,
     },
@@ -43,7 +44,7 @@ use crate::{
         validator::{self, Error, ValidatorInitConfig},
         EventQueueHandle, Finalize,
     },
-    types::{Block, BlockHash, Deploy, ProtoBlock, Tag, Timestamp},
+    types::{Block, BlockHash, Deploy, ProtoBlock, ShutdownReason, Tag, Timestamp},
     utils::{Source, WithDir},
 };
 
@@ -69,7 +70,7 @@ pub enum Event {
 
     /// Block validator event.
     #[from]
-    BlockValidator(block_validator::Event<Block, NodeId>),
+    BlockValidator(block_validator::Event<Box<Block>, NodeId>),
 
     /// Linear chain event.
     #[from]
@@ -106,15 +107,15 @@ pub enum Event {
 
     /// Block validation request.
     #[from]
-    BlockValidatorRequest(BlockValidationRequest<Block, NodeId>),
+    BlockValidatorRequest(BlockValidationRequest<Box<Block>, NodeId>),
 
     /// Block executor request.
     #[from]
     BlockExecutorRequest(BlockExecutorRequest),
 
-    /// Deploy buffer request.
+    /// Proposal builder request.
     #[from]
-    DeployBufferRequest(DeployBufferRequest),
+    ProposalBuilderRequest(ProposalBuilderRequest),
 
     /// Proto block validator request.
     #[from]
@@ -136,6 +137,18 @@ pub enum Event {
     /// Address Gossiper announcement.
     #[from]
     AddressGossiperAnnouncement(GossiperAnnouncement<GossipedAddress>),
+
+    /// Linear chain announcement.
+    #[from]
+    LinearChainAnnouncement(LinearChainAnnouncement),
+
+    /// Storage announcement.
+    #[from]
+    StorageAnnouncement(StorageAnnouncement),
+
+    /// Control announcement.
+    #[from]
+    ControlAnnouncement(ControlAnnouncement),
 }
 
 impl From<StorageRequest<Storage>> for Event {
@@ -191,7 +204,7 @@ impl Display for Event {
             Event::BlockExecutorRequest(request) => {
                 write!(f, "block executor request: {}", request)
             }
-            Event::DeployBufferRequest(req) => write!(f, "deploy buffer request: {}", req),
+            Event::ProposalBuilderRequest(req) => write!(f, "proposal builder request: {}", req),
             Event::ContractRuntime(event) => write!(f, "contract runtime event: {}", event),
             Event::LinearChain(event) => write!(f, "linear chain event: {}", event),
             Event::BlockExecutorAnnouncement(announcement) => {
@@ -204,6 +217,9 @@ impl Display for Event {
             Event::AddressGossiperAnnouncement(ann) => {
                 write!(f, "address gossiper announcement: {}", ann)
             }
+            Event::LinearChainAnnouncement(ann) => write!(f, "linear chain announcement: {}", ann),
+            Event::StorageAnnouncement(ann) => write!(f, "storage announcement: {}", ann),
+            Event::ControlAnnouncement(ann) => write!(f, "control announcement: {}", ann),
         }
     }
 }
@@ -218,11 +234,12 @@ pub struct Reactor<R: Rng + CryptoRng + ?Sized> {
     pub(super) contract_runtime: ContractRuntime,
     pub(super) linear_chain_fetcher: Fetcher<Block>,
     pub(super) linear_chain_sync: LinearChainSync<NodeId>,
-    pub(super) block_validator: BlockValidator<Block, NodeId>,
+    pub(super) block_validator: BlockValidator<Box<Block>, NodeId>,
     pub(super) deploy_fetcher: Fetcher<Deploy>,
     pub(super) block_executor: BlockExecutor,
     pub(super) linear_chain: linear_chain::LinearChain<NodeId>,
-    pub(super) consensus: EraSupervisor<NodeId, R>,
+    /// The consensus component, or `None` in observer mode.
+    pub(super) consensus: Option<EraSupervisor<NodeId, R>>,
     // Effects consensus component returned during creation.
     // In the `joining` phase we don't want to handle it,
     // so we carry them forward to the `validator` reactor.
@@ -230,6 +247,8 @@ pub struct Reactor<R: Rng + CryptoRng + ?Sized> {
     // TODO: remove after proper syncing is implemented
     // `None` means we haven't seen any consensus messages yet
     latest_received_era_id: Option<EraId>,
+    /// The reason for which a component requested the node to shut down, if any.
+    shutdown_request: Option<ShutdownReason>,
 }
 
 impl<R: Rng + CryptoRng + ?Sized> reactor::Reactor<R> for Reactor<R> {
@@ -242,7 +261,7 @@ impl<R: Rng + CryptoRng + ?Sized> reactor::Reactor<R> for Reactor<R> {
 
     fn new(
         initializer: Self::Config,
-        _registry: &Registry,
+        registry: &Registry,
         event_queue: EventQueueHandle<Self::Event>,
         rng: &mut R,
     ) -> Result<(Self, Effects<Self::Event>), Self::Error> {
@@ -253,14 +272,16 @@ impl<R: Rng + CryptoRng + ?Sized> reactor::Reactor<R> for Reactor<R> {
             chainspec_loader,
             storage,
             contract_runtime,
+            ..
         } = initializer;
 
-        let (net, net_effects) = SmallNetwork::new(event_queue, config.network.clone())?;
+        let (mut net, net_effects) =
+            SmallNetwork::new(event_queue, config.network.clone(), registry)?;
 
         let linear_chain_fetcher = Fetcher::new(config.gossip);
         let effects = reactor::wrap_effects(Event::Network, net_effects);
 
-        let address_gossiper = Gossiper::new_for_complete_items(config.gossip);
+        let address_gossiper = Gossiper::new_for_complete_items(config.gossip, net.latency_store());
 
         let effect_builder = EffectBuilder::new(event_queue);
 
@@ -287,27 +308,42 @@ impl<R: Rng + CryptoRng + ?Sized> reactor::Reactor<R> for Reactor<R> {
 
         let block_executor = BlockExecutor::new(genesis_post_state_hash);
 
-        let linear_chain = linear_chain::LinearChain::new();
-
         let validator_stakes = chainspec_loader
             .chainspec()
             .genesis
             .genesis_validator_stakes();
 
+        let linear_chain = linear_chain::LinearChain::new(validator_stakes.clone());
+
         // Used to decide whether era should be activated.
         let timestamp = Timestamp::now();
 
-        let (consensus, init_consensus_effects) = EraSupervisor::new(
-            timestamp,
-            WithDir::new(root, config.consensus.clone()),
-            effect_builder,
-            validator_stakes,
-            chainspec_loader.chainspec(),
-            chainspec_loader
-                .genesis_post_state_hash()
-                .expect("should have genesis post state hash"),
-            rng,
-        )?;
+        let (consensus, init_consensus_effects) = if config.node.observer_mode {
+            info!("running in observer mode, not participating in consensus");
+            (None, Effects::new())
+        } else {
+            let (consensus, init_consensus_effects) = EraSupervisor::new(
+                timestamp,
+                WithDir::new(root, config.consensus.clone()),
+                effect_builder,
+                validator_stakes,
+                chainspec_loader.chainspec(),
+                chainspec_loader
+                    .genesis_post_state_hash()
+                    .expect("should have genesis post state hash"),
+                registry,
+                rng,
+            )?;
+
+            let (secret_key, public_key) = consensus.signing_key_pair();
+            net.set_attestation(HandshakeAttestation::new(
+                net.node_id(),
+                secret_key,
+                public_key,
+                rng,
+            ));
+            (Some(consensus), init_consensus_effects)
+        };
 
         Ok((
             Self {
@@ -326,6 +362,7 @@ impl<R: Rng + CryptoRng + ?Sized> reactor::Reactor<R> for Reactor<R> {
                 consensus,
                 init_consensus_effects,
                 latest_received_era_id: None,
+                shutdown_request: None,
             },
             effects,
         ))
@@ -342,14 +379,36 @@ impl<R: Rng + CryptoRng + ?Sized> reactor::Reactor<R> for Reactor<R> {
                 Event::Network,
                 self.net.handle_event(effect_builder, rng, event),
             ),
-            Event::NetworkAnnouncement(NetworkAnnouncement::NewPeer(id)) => reactor::wrap_effects(
-                Event::LinearChainSync,
-                self.linear_chain_sync.handle_event(
+            Event::NetworkAnnouncement(NetworkAnnouncement::PeerConnected {
+                peer,
+                handshake_duration,
+                inbound,
+            }) => {
+                info!(%peer, ?handshake_duration, inbound, "peer connected");
+                Effects::new()
+            }
+            Event::NetworkAnnouncement(NetworkAnnouncement::PeerDisconnected { peer, reason }) => {
+                info!(%peer, %reason, "peer disconnected");
+                let event = fetcher::Event::PeerDisconnected(peer);
+                self.dispatch_event(effect_builder, rng, Event::DeployFetcher(event))
+            }
+            Event::NetworkAnnouncement(NetworkAnnouncement::NewPeer(id)) => {
+                let mut effects = reactor::wrap_effects(
+                    Event::LinearChainSync,
+                    self.linear_chain_sync.handle_event(
+                        effect_builder,
+                        rng,
+                        linear_chain_sync::Event::NewPeerConnected(id),
+                    ),
+                );
+                let event = fetcher::Event::PeerConnected(id);
+                effects.extend(self.dispatch_event(
                     effect_builder,
                     rng,
-                    linear_chain_sync::Event::NewPeerConnected(id),
-                ),
-            ),
+                    Event::DeployFetcher(event),
+                ));
+                effects
+            }
             Event::NetworkAnnouncement(NetworkAnnouncement::GossipOurAddress(gossiped_address)) => {
                 let event = gossiper::Event::ItemReceived {
                     item_id: gossiped_address,
@@ -380,6 +439,11 @@ impl<R: Rng + CryptoRng + ?Sized> reactor::Reactor<R> for Reactor<R> {
                 }
                 // needed so that consensus can notify us of the eras it knows of
                 // TODO: remove when proper syncing is implemented
+                Message::Consensus(msg) if self.consensus.is_none() => {
+                    // Without consensus, observers only note the era.
+                    self.note_received_era(msg.era_id());
+                    Effects::new()
+                }
                 Message::Consensus(msg) => self.dispatch_event(
                     effect_builder,
                     rng,
@@ -447,7 +511,7 @@ impl<R: Rng + CryptoRng + ?Sized> reactor::Reactor<R> for Reactor<R> {
                 execution_results,
             }) => {
                 let reactor_event = Event::LinearChain(linear_chain::Event::LinearChainBlock {
-                    block,
+                    block: Box::new(block),
                     execution_results,
                 });
                 self.dispatch_event(effect_builder, rng, reactor_event)
@@ -456,10 +520,14 @@ impl<R: Rng + CryptoRng + ?Sized> reactor::Reactor<R> for Reactor<R> {
                 Event::LinearChain,
                 self.linear_chain.handle_event(effect_builder, rng, event),
             ),
-            Event::Consensus(event) => reactor::wrap_effects(
-                Event::Consensus,
-                self.consensus.handle_event(effect_builder, rng, event),
-            ),
+            Event::Consensus(event) => match self.consensus.as_mut() {
+                Some(consensus) => reactor::wrap_effects(
+                    Event::Consensus,
+                    consensus.handle_event(effect_builder, rng, event),
+                ),
+                // Observers don't participate in consensus.
+                None => Effects::new(),
+            },
             Event::ConsensusAnnouncement(announcement) => match announcement {
                 ConsensusAnnouncement::Handled(height) => reactor::wrap_effects(
                     Event::LinearChainSync,
@@ -470,14 +538,26 @@ impl<R: Rng + CryptoRng + ?Sized> reactor::Reactor<R> for Reactor<R> {
                     ),
                 ),
                 ConsensusAnnouncement::GotMessageInEra(era_id) => {
-                    // note if the era id is later than the latest we've received so far
-                    if self
-                        .latest_received_era_id
-                        .map(|lreid| era_id > lreid)
-                        .unwrap_or(true)
-                    {
-                        self.latest_received_era_id = Some(era_id);
-                    }
+                    self.note_received_era(era_id);
+                    Effects::new()
+                }
+                ConsensusAnnouncement::BlockSigned(finality_signature) => {
+                    // Finality signatures are only collected once we are a validator.
+                    debug!("Ignoring {} while joining", finality_signature);
+                    Effects::new()
+                }
+                ConsensusAnnouncement::EraStarted { era_id, .. } => {
+                    // The validator reactor's network component learns the validators on startup.
+                    debug!("Ignoring start of era {} while joining", era_id);
+                    Effects::new()
+                }
+                ConsensusAnnouncement::ConsensusStalled { era_id, .. }
+                | ConsensusAnnouncement::ConsensusResumed { era_id } => {
+                    // Consensus isn't expected to make progress before we have caught up.
+                    debug!(
+                        "Ignoring consensus progress in era {} while joining",
+                        era_id
+                    );
                     Effects::new()
                 }
                 other => {
@@ -485,10 +565,10 @@ impl<R: Rng + CryptoRng + ?Sized> reactor::Reactor<R> for Reactor<R> {
                     Effects::new()
                 }
             },
-            Event::DeployBufferRequest(request) => {
+            Event::ProposalBuilderRequest(request) => {
                 // Consensus component should not be trying to create new blocks during joining
                 // phase.
-                warn!("Ignoring deploy buffer request {}", request);
+                warn!("Ignoring proposal builder request {}", request);
                 Effects::new()
             }
             Event::ProtoBlockValidatorRequest(request) => {
@@ -502,15 +582,35 @@ impl<R: Rng + CryptoRng + ?Sized> reactor::Reactor<R> for Reactor<R> {
                 self.address_gossiper
                     .handle_event(effect_builder, rng, event),
             ),
-            Event::AddressGossiperAnnouncement(ann) => {
-                let GossiperAnnouncement::NewCompleteItem(gossiped_address) = ann;
+            Event::AddressGossiperAnnouncement(GossiperAnnouncement::NewCompleteItem(
+                gossiped_address,
+            )) => {
                 let reactor_event =
                     Event::Network(small_network::Event::PeerAddressReceived(gossiped_address));
                 self.dispatch_event(effect_builder, rng, reactor_event)
             }
+            Event::AddressGossiperAnnouncement(GossiperAnnouncement::FinishedGossiping(_)) => {
+                Effects::new()
+            }
+            Event::LinearChainAnnouncement(LinearChainAnnouncement::BlockOrphaned { block }) => {
+                let event = storage::Event::BlockOrphaned {
+                    block_hash: *block.hash(),
+                };
+                self.dispatch_event(effect_builder, rng, Event::Storage(event))
+            }
+            // A joining node accepts no deploys from clients, so there is no load to shed.
+            Event::StorageAnnouncement(_) => Effects::new(),
+            Event::ControlAnnouncement(ControlAnnouncement::FatalError(reason)) => {
+                let _ = self.shutdown_request.get_or_insert(reason);
+                Effects::new()
+            }
         }
     }
 
+    fn take_shutdown_request(&mut self) -> Option<ShutdownReason> {
+        self.shutdown_request.take()
+    }
+
     fn is_stopped(&mut self) -> bool {
         if self.linear_chain_sync.is_synced() {
             trace!("Linear chain is synced.");
@@ -543,9 +643,25 @@ impl<R: Rng + CryptoRng + ?Sized> reactor::Reactor<R> for Reactor<R> {
             && (self.latest_received_era_id.is_some()
                 || self.linear_chain_sync.init_block_era().is_none())
     }
+
+    fn stop(&mut self) {
+        self.storage.on_stop();
+    }
 }
 
 impl<R: Rng + CryptoRng + ?Sized> Reactor<R> {
+    /// Notes that a consensus message in `era_id` has been received, if the era is later than the
+    /// latest one received so far.
+    fn note_received_era(&mut self, era_id: EraId) {
+        if self
+            .latest_received_era_id
+            .map(|lreid| era_id > lreid)
+            .unwrap_or(true)
+        {
+            self.latest_received_era_id = Some(era_id);
+        }
+    }
+
     /// Deconstructs the reactor into config useful for creating a Validator reactor. Shuts down
     /// the network, closing all incoming and outgoing connections, and frees up the listening
     /// socket.
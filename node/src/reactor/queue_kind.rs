@@ -51,8 +51,17 @@ impl QueueKind {
         .expect("weight must be positive")
     }
 
+    /// Returns whether events on this queue originate outside of the node.
+    ///
+    /// These events are rejected once the reactor is draining its queue on shutdown.  API requests
+    /// are not included, since components make some of these internally and would be left waiting
+    /// for a response forever.  Instead, the API server stops accepting new requests.
+    pub(crate) fn is_external(self) -> bool {
+        self == QueueKind::NetworkIncoming
+    }
+
     /// Return weights of all possible `Queue`s.
-    pub(super) fn weights() -> Vec<(Self, NonZeroUsize)> {
+    pub(crate) fn weights() -> Vec<(Self, NonZeroUsize)> {
         QueueKind::into_enum_iter()
             .map(|q| (q, q.weight()))
             .collect()
@@ -0,0 +1,154 @@
+//! Stall watchdog.
+//!
+//! Dispatching an event runs synchronously on the reactor's thread, so a stalling dispatch can't be
+//! noticed from within the reactor until it has returned, if it ever does.  The watchdog instead
+//! watches dispatches from a thread of its own: The runner reports the start and end of every
+//! dispatch, and once a dispatch has been running for longer than the threshold, the watchdog
+//! warns about it and counts it as a stall while it is still in progress.
+
+use std::{
+    sync::{Arc, Condvar, Mutex},
+    thread::{self, JoinHandle},
+    time::{Duration, Instant},
+};
+
+use prometheus::IntCounter;
+use tracing::{dispatcher, info, warn, Dispatch};
+
+/// The dispatch currently being watched.
+#[derive(Debug)]
+struct Dispatching {
+    /// The kind of event being dispatched.
+    kind: String,
+    /// When the dispatch started.
+    started: Instant,
+    /// Whether the dispatch has already been reported as stalling.
+    reported: bool,
+}
+
+/// The state shared between the runner and the watchdog thread.
+#[derive(Debug, Default)]
+struct State {
+    /// The dispatch in progress, if any.
+    dispatching: Option<Dispatching>,
+    /// Whether the watchdog thread should exit.
+    shutdown: bool,
+}
+
+/// A watchdog reporting dispatches which take longer than a threshold while they are running.
+#[derive(Debug)]
+pub(super) struct StallWatchdog {
+    /// The state shared with the watchdog thread, and the condition variable waking it up.
+    shared: Arc<(Mutex<State>, Condvar)>,
+    /// The duration after which a dispatch is considered to stall the reactor.
+    threshold: Duration,
+    /// The watchdog thread.
+    thread: Option<JoinHandle<()>>,
+}
+
+impl StallWatchdog {
+    /// Starts a watchdog reporting dispatches running longer than `threshold`, counting them in
+    /// `stalls`.
+    ///
+    /// The watchdog logs to the subscriber which is the default on the calling thread.
+    pub(super) fn new(threshold: Duration, stalls: IntCounter) -> Self {
+        let shared = Arc::new((Mutex::new(State::default()), Condvar::new()));
+        let thread_shared = Arc::clone(&shared);
+        let dispatch = dispatcher::get_default(Dispatch::clone);
+        let thread = thread::Builder::new()
+            .name("stall-watchdog".to_string())
+            .spawn(move || {
+                dispatcher::with_default(&dispatch, || watch(&thread_shared, threshold, &stalls))
+            })
+            .expect("should spawn stall watchdog thread");
+        StallWatchdog {
+            shared,
+            threshold,
+            thread: Some(thread),
+        }
+    }
+
+    /// Marks the start of dispatching an event of the given kind.
+    pub(super) fn dispatch_started(&self, kind: String) {
+        let (state, wakeup) = &*self.shared;
+        state
+            .lock()
+            .expect("stall watchdog lock poisoned")
+            .dispatching = Some(Dispatching {
+            kind,
+            started: Instant::now(),
+            reported: false,
+        });
+        wakeup.notify_one();
+    }
+
+    /// Marks the end of the current dispatch.
+    pub(super) fn dispatch_finished(&self) {
+        let (state, _) = &*self.shared;
+        let dispatching = state
+            .lock()
+            .expect("stall watchdog lock poisoned")
+            .dispatching
+            .take();
+        if let Some(dispatching) = dispatching {
+            if dispatching.reported {
+                info!(
+                    event = %dispatching.kind,
+                    elapsed_ms = dispatching.started.elapsed().as_millis() as u64,
+                    threshold_ms = self.threshold.as_millis() as u64,
+                    "stalled event dispatch finished"
+                );
+            }
+        }
+    }
+}
+
+impl Drop for StallWatchdog {
+    fn drop(&mut self) {
+        let (state, wakeup) = &*self.shared;
+        if let Ok(mut state) = state.lock() {
+            state.shutdown = true;
+        }
+        wakeup.notify_one();
+        if let Some(thread) = self.thread.take() {
+            let _ = thread.join();
+        }
+    }
+}
+
+/// Runs the watchdog thread until shut down.
+fn watch(shared: &(Mutex<State>, Condvar), threshold: Duration, stalls: &IntCounter) {
+    let (state, wakeup) = shared;
+    let mut state = state.lock().expect("stall watchdog lock poisoned");
+    while !state.shutdown {
+        let timeout = match state.dispatching.as_mut() {
+            Some(dispatching) if !dispatching.reported => {
+                let elapsed = dispatching.started.elapsed();
+                if elapsed > threshold {
+                    dispatching.reported = true;
+                    stalls.inc();
+                    warn!(
+                        event = %dispatching.kind,
+                        elapsed_ms = elapsed.as_millis() as u64,
+                        threshold_ms = threshold.as_millis() as u64,
+                        "event dispatch is stalling the reactor"
+                    );
+                    None
+                } else {
+                    // Wake up just after the threshold has been exceeded.
+                    Some(threshold - elapsed + Duration::from_millis(1))
+                }
+            }
+            _ => None,
+        };
+        state = match timeout {
+            Some(timeout) => {
+                wakeup
+                    .wait_timeout(state, timeout)
+                    .expect("stall watchdog lock poisoned")
+                    .0
+            }
+            None => wakeup.wait(state).expect("stall watchdog lock poisoned"),
+        };
+    }
+}
@@ -1,19 +1,30 @@
 //! Reactor for validator nodes.
 //!
 //! Validator nodes join the validator-only network upon startup.
+//!
+//! In observer mode, the node doesn't participate in consensus at all: The consensus component is
+//! not constructed, and consensus events are ignored.  The node still syncs the linear chain while
+//! joining, then follows it by fetching and executing the blocks signed by the validators, and
+//! serves the HTTP API.  It can also relay the consensus messages it receives to its peers, except
+//! back to their sender.
 
 mod config;
+mod consensus_relay;
 mod error;
 #[cfg(test)]
 mod tests;
 
-use std::fmt::{self, Display, Formatter};
+use std::{
+    collections::{HashMap, HashSet},
+    fmt::{self, Display, Formatter},
+    iter,
+};
 
 use derive_more::From;
 use fmt::Debug;
 use prometheus::Registry;
 use rand::{CryptoRng, Rng};
-use tracing::{debug, error, warn};
+use tracing::{debug, error, info, warn};
 
 #[cfg(test)]
 use crate::testing::network::NetworkedReactor;
@@ -22,37 +33,45 @@ use crate::{
         api_server::{self, ApiServer},
         block_executor::{self, BlockExecutor},
         block_validator::{self, BlockValidator},
+        chain_follower::{self, ChainFollower},
         chainspec_loader::ChainspecLoader,
-        consensus::{self, EraSupervisor},
+        consensus::{self, ConsensusMessage, EraId, EraSupervisor},
         contract_runtime::{self, ContractRuntime},
-        deploy_acceptor::{self, DeployAcceptor},
-        deploy_buffer::{self, DeployBuffer},
+        deploy_acceptor::{self, ConfiguredFilter, DeployAcceptor},
+        deploy_buffer::{self, DefaultGasEstimator, DeployBuffer},
         fetcher::{self, Fetcher},
+        finality_signature_collector::{self, FinalitySignatureCollector},
         gossiper::{self, Gossiper},
         linear_chain,
+        load_shedder::{self, LoadShedder},
         metrics::Metrics,
-        small_network::{self, GossipedAddress, NodeId, SmallNetwork},
+        proposal_builder::{self, ProposalBuilder},
+        small_network::{self, GossipedAddress, HandshakeAttestation, NodeId, SmallNetwork},
         storage::{self, Storage},
-        Component,
+        Component, ComponentLifecycle,
     },
     effect::{
         announcements::{
             ApiServerAnnouncement, BlockExecutorAnnouncement, ConsensusAnnouncement,
-            DeployAcceptorAnnouncement, GossiperAnnouncement, NetworkAnnouncement,
+            ControlAnnouncement, DeployAcceptorAnnouncement, DeployBufferAnnouncement,
+            FinalitySignatureAnnouncement, GossiperAnnouncement, LinearChainAnnouncement,
+            LoadShedderAnnouncement, NetworkAnnouncement, StorageAnnouncement,
         },
         requests::{
             ApiRequest, BlockExecutorRequest, BlockValidationRequest, ConsensusRequest,
             ContractRuntimeRequest, DeployBufferRequest, FetcherRequest, LinearChainRequest,
-            MetricsRequest, NetworkInfoRequest, NetworkRequest, StorageRequest,
+            MetricsRequest, NetworkInfoRequest, NetworkRequest, ProposalBuilderRequest,
+            StorageRequest,
         },
-        EffectBuilder, Effects,
+        EffectBuilder, EffectExt, Effects,
     },
     protocol::Message,
-    reactor::{self, EventQueueHandle},
-    types::{Block, Deploy, ProtoBlock, Tag},
+    reactor::{self, EventMetrics, EventQueueHandle},
+    types::{Block, Deploy, ProtoBlock, ShutdownReason, Tag, TimeDiff},
     utils::Source,
 };
 pub use config::Config;
+use consensus_relay::ConsensusRelay;
 pub use error::Error;
 use linear_chain::LinearChain;
 
@@ -96,9 +115,27 @@ pub enum Event {
     /// Block validator event.
     #[from]
     ProtoBlockValidator(block_validator::Event<ProtoBlock, NodeId>),
+    /// Linear chain block fetcher event.
+    #[from]
+    BlockFetcher(fetcher::Event<Block>),
+    /// Linear chain block validator event.
+    #[from]
+    BlockValidator(block_validator::Event<Box<Block>, NodeId>),
+    /// Chain follower event.
+    #[from]
+    ChainFollower(chain_follower::Event),
     /// Linear chain event.
     #[from]
     LinearChain(linear_chain::Event<NodeId>),
+    /// Finality signature collector event.
+    #[from]
+    FinalitySignatureCollector(finality_signature_collector::Event),
+    /// Load shedder event.
+    #[from]
+    LoadShedder(load_shedder::Event),
+    /// Proposal builder event.
+    #[from]
+    ProposalBuilder(proposal_builder::Event),
 
     // Requests
     /// Network request.
@@ -119,9 +156,18 @@ pub enum Event {
     /// Block validator request.
     #[from]
     ProtoBlockValidatorRequest(BlockValidationRequest<ProtoBlock, NodeId>),
+    /// Linear chain block fetcher request.
+    #[from]
+    BlockFetcherRequest(FetcherRequest<NodeId, Block>),
+    /// Linear chain block validator request.
+    #[from]
+    BlockValidatorRequest(BlockValidationRequest<Box<Block>, NodeId>),
     /// Metrics request.
     #[from]
     MetricsRequest(MetricsRequest),
+    /// Proposal builder request.
+    #[from]
+    ProposalBuilderRequest(ProposalBuilderRequest),
 
     // Announcements
     /// Network announcement.
@@ -139,12 +185,30 @@ pub enum Event {
     /// BlockExecutor announcement.
     #[from]
     BlockExecutorAnnouncement(BlockExecutorAnnouncement),
+    /// Deploy buffer announcement.
+    #[from]
+    DeployBufferAnnouncement(DeployBufferAnnouncement),
     /// Deploy Gossiper announcement.
     #[from]
     DeployGossiperAnnouncement(GossiperAnnouncement<Deploy>),
     /// Address Gossiper announcement.
     #[from]
     AddressGossiperAnnouncement(GossiperAnnouncement<GossipedAddress>),
+    /// Finality signature collector announcement.
+    #[from]
+    FinalitySignatureAnnouncement(FinalitySignatureAnnouncement),
+    /// Linear chain announcement.
+    #[from]
+    LinearChainAnnouncement(LinearChainAnnouncement),
+    /// Storage announcement.
+    #[from]
+    StorageAnnouncement(StorageAnnouncement),
+    /// Load shedder announcement.
+    #[from]
+    LoadShedderAnnouncement(LoadShedderAnnouncement),
+    /// Control announcement.
+    #[from]
+    ControlAnnouncement(ControlAnnouncement),
 }
 
 impl From<StorageRequest<Storage>> for Event {
@@ -159,8 +223,8 @@ impl From<ApiRequest<NodeId>> for Event {
     }
 }
 
-impl From<NetworkRequest<NodeId, consensus::ConsensusMessage>> for Event {
-    fn from(request: NetworkRequest<NodeId, consensus::ConsensusMessage>) -> Self {
+impl From<NetworkRequest<NodeId, ConsensusMessage>> for Event {
+    fn from(request: NetworkRequest<NodeId, ConsensusMessage>) -> Self {
         Event::NetworkRequest(request.map_payload(Message::from))
     }
 }
@@ -210,14 +274,27 @@ impl Display for Event {
             Event::ContractRuntime(event) => write!(f, "contract runtime: {}", event),
             Event::BlockExecutor(event) => write!(f, "block executor: {}", event),
             Event::LinearChain(event) => write!(f, "linear-chain event {}", event),
+            Event::FinalitySignatureCollector(event) => {
+                write!(f, "finality signature collector: {}", event)
+            }
             Event::ProtoBlockValidator(event) => write!(f, "block validator: {}", event),
+            Event::BlockFetcher(event) => write!(f, "block fetcher: {}", event),
+            Event::BlockValidator(event) => write!(f, "linear chain block validator: {}", event),
+            Event::ChainFollower(event) => write!(f, "chain follower: {}", event),
+            Event::LoadShedder(event) => write!(f, "load shedder: {}", event),
+            Event::ProposalBuilder(event) => write!(f, "proposal builder: {}", event),
             Event::NetworkRequest(req) => write!(f, "network request: {}", req),
             Event::NetworkInfoRequest(req) => write!(f, "network info request: {}", req),
             Event::DeployFetcherRequest(req) => write!(f, "deploy fetcher request: {}", req),
             Event::DeployBufferRequest(req) => write!(f, "deploy buffer request: {}", req),
             Event::BlockExecutorRequest(req) => write!(f, "block executor request: {}", req),
             Event::ProtoBlockValidatorRequest(req) => write!(f, "block validator request: {}", req),
+            Event::BlockFetcherRequest(req) => write!(f, "block fetcher request: {}", req),
+            Event::BlockValidatorRequest(req) => {
+                write!(f, "linear chain block validator request: {}", req)
+            }
             Event::MetricsRequest(req) => write!(f, "metrics request: {}", req),
+            Event::ProposalBuilderRequest(req) => write!(f, "proposal builder request: {}", req),
             Event::NetworkAnnouncement(ann) => write!(f, "network announcement: {}", ann),
             Event::ApiServerAnnouncement(ann) => write!(f, "api server announcement: {}", ann),
             Event::DeployAcceptorAnnouncement(ann) => {
@@ -227,12 +304,22 @@ impl Display for Event {
             Event::BlockExecutorAnnouncement(ann) => {
                 write!(f, "block-executor announcement: {}", ann)
             }
+            Event::DeployBufferAnnouncement(ann) => {
+                write!(f, "deploy buffer announcement: {}", ann)
+            }
             Event::DeployGossiperAnnouncement(ann) => {
                 write!(f, "deploy gossiper announcement: {}", ann)
             }
             Event::AddressGossiperAnnouncement(ann) => {
                 write!(f, "address gossiper announcement: {}", ann)
             }
+            Event::FinalitySignatureAnnouncement(ann) => {
+                write!(f, "finality signature announcement: {}", ann)
+            }
+            Event::LinearChainAnnouncement(ann) => write!(f, "linear chain announcement: {}", ann),
+            Event::StorageAnnouncement(ann) => write!(f, "storage announcement: {}", ann),
+            Event::LoadShedderAnnouncement(ann) => write!(f, "load shedder announcement: {}", ann),
+            Event::ControlAnnouncement(ann) => write!(f, "control announcement: {}", ann),
         }
     }
 }
@@ -243,7 +330,7 @@ pub struct ValidatorInitConfig<R: Rng + CryptoRng + ?Sized> {
     pub(super) chainspec_loader: ChainspecLoader,
     pub(super) storage: Storage,
     pub(super) contract_runtime: ContractRuntime,
-    pub(super) consensus: EraSupervisor<NodeId, R>,
+    pub(super) consensus: Option<EraSupervisor<NodeId, R>>,
     pub(super) init_consensus_effects: Effects<consensus::Event<NodeId>>,
     pub(super) linear_chain: Vec<Block>,
 }
@@ -252,26 +339,88 @@ pub struct ValidatorInitConfig<R: Rng + CryptoRng + ?Sized> {
 #[derive(Debug)]
 pub struct Reactor<R: Rng + CryptoRng + ?Sized> {
     metrics: Metrics,
+    event_metrics: EventMetrics,
     net: SmallNetwork<Event, Message>,
     address_gossiper: Gossiper<GossipedAddress, Event>,
     storage: Storage,
     contract_runtime: ContractRuntime,
     api_server: ApiServer,
-    consensus: EraSupervisor<NodeId, R>,
+    /// The consensus component, or `None` in observer mode.
+    consensus: Option<EraSupervisor<NodeId, R>>,
+    /// The consensus messages relayed, if this is an observer relaying them.
+    consensus_relay: Option<ConsensusRelay>,
     deploy_acceptor: DeployAcceptor,
     deploy_fetcher: Fetcher<Deploy>,
     deploy_gossiper: Gossiper<Deploy, Event>,
     deploy_buffer: DeployBuffer,
     block_executor: BlockExecutor,
     proto_block_validator: BlockValidator<ProtoBlock, NodeId>,
+    block_fetcher: Fetcher<Block>,
+    block_validator: BlockValidator<Box<Block>, NodeId>,
+    /// The chain follower, if this is an observer.
+    chain_follower: Option<ChainFollower>,
     linear_chain: LinearChain<NodeId>,
+    finality_signature_collector: FinalitySignatureCollector,
+    load_shedder: LoadShedder,
+    proposal_builder: ProposalBuilder,
+    /// The reason for which a component requested the node to shut down, if any.
+    shutdown_request: Option<ShutdownReason>,
+}
+
+impl<R: Rng + CryptoRng + ?Sized> Reactor<R> {
+    /// Relays a consensus message received by an observer from `sender` to all other peers, unless
+    /// relaying is disabled or the message has been relayed already.
+    fn relay_consensus_message(
+        &mut self,
+        effect_builder: EffectBuilder<Event>,
+        sender: NodeId,
+        msg: ConsensusMessage,
+    ) -> Effects<Event> {
+        let should_relay = self
+            .consensus_relay
+            .as_mut()
+            .map_or(false, |relay| relay.should_relay(&msg));
+        if !should_relay {
+            return Effects::new();
+        }
+        // The message isn't part of our own sequence, so it's relayed without its number.
+        let msg = Message::Consensus(msg.without_sequence_number());
+        async move {
+            let peer_count = effect_builder.network_peers::<NodeId>().await.len();
+            let exclude = iter::once(sender).collect();
+            effect_builder
+                .gossip_message(msg, peer_count, exclude, HashMap::new(), false)
+                .await
+        }
+        .ignore()
+    }
+
+    /// Returns the components with startup or teardown hooks.
+    fn lifecycle_components(&mut self) -> Vec<&mut dyn ComponentLifecycle> {
+        vec![
+            &mut self.storage,
+            &mut self.address_gossiper,
+            &mut self.deploy_gossiper,
+            &mut self.deploy_buffer,
+        ]
+    }
 }
 
 #[cfg(test)]
 impl<R: Rng + CryptoRng + ?Sized> Reactor<R> {
-    /// Inspect consensus.
-    pub(crate) fn consensus(&self) -> &EraSupervisor<NodeId, R> {
-        &self.consensus
+    /// Inspect consensus, unless in observer mode.
+    pub(crate) fn consensus(&self) -> Option<&EraSupervisor<NodeId, R>> {
+        self.consensus.as_ref()
+    }
+
+    /// Inspect the event handling metrics.
+    pub(crate) fn event_metrics(&self) -> &EventMetrics {
+        &self.event_metrics
+    }
+
+    /// Inspect the blocks added to the linear chain since joining.
+    pub(crate) fn linear_chain(&self) -> &[Block] {
+        self.linear_chain.linear_chain()
     }
 }
 
@@ -287,9 +436,7 @@ impl<R: Rng + CryptoRng + ?Sized> reactor::Reactor<R> for Reactor<R> {
         config: Self::Config,
         registry: &Registry,
         event_queue: EventQueueHandle<Self::Event>,
-        // We don't need `rng` b/c consensus component was the only one using it,
-        // and now it's being passed on from the `joiner` reactor via `config`.
-        _rng: &mut R,
+        rng: &mut R,
     ) -> Result<(Self, Effects<Event>), Error> {
         let ValidatorInitConfig {
             config,
@@ -302,20 +449,76 @@ impl<R: Rng + CryptoRng + ?Sized> reactor::Reactor<R> for Reactor<R> {
         } = config;
 
         let metrics = Metrics::new(registry.clone());
+        let event_metrics = EventMetrics::new(if config.node.event_metrics {
+            Some(registry)
+        } else {
+            None
+        })?;
 
         let effect_builder = EffectBuilder::new(event_queue);
-        let (net, net_effects) = SmallNetwork::new(event_queue, config.network)?;
+        let (mut net, net_effects) = SmallNetwork::new(event_queue, config.network, registry)?;
+        let validator_stakes = chainspec_loader
+            .chainspec()
+            .genesis
+            .genesis_validator_stakes();
+        match consensus.as_ref() {
+            Some(consensus) => {
+                let (secret_key, public_key) = consensus.signing_key_pair();
+                net.set_attestation(HandshakeAttestation::new(
+                    net.node_id(),
+                    secret_key,
+                    public_key,
+                    rng,
+                ));
+                net.set_validators(
+                    consensus
+                        .current_validator_weights()
+                        .keys()
+                        .copied()
+                        .collect(),
+                );
+            }
+            None => {
+                // Without consensus, observers only know the genesis validators.
+                let validators: HashSet<_> = validator_stakes
+                    .iter()
+                    .map(|(public_key, _)| *public_key)
+                    .collect();
+                net.set_validators(validators);
+            }
+        }
+        let consensus_relay = if consensus.is_none() && config.node.relay_consensus_messages {
+            Some(ConsensusRelay::default())
+        } else {
+            None
+        };
+        let chain_follower = if consensus.is_none() {
+            let validators = validator_stakes.iter().map(|(public_key, _)| *public_key);
+            Some(ChainFollower::new(validators, &linear_chain))
+        } else {
+            None
+        };
 
-        let address_gossiper = Gossiper::new_for_complete_items(config.gossip);
+        let address_gossiper = Gossiper::new_for_complete_items(config.gossip, net.latency_store());
 
-        let api_server = ApiServer::new(config.http_server, effect_builder);
-        let deploy_acceptor = DeployAcceptor::new();
+        let api_server =
+            ApiServer::new(config.http_server, effect_builder, !linear_chain.is_empty());
+        let deploy_acceptor = DeployAcceptor::new(
+            Box::new(ConfiguredFilter::new(&config.deploy_acceptor)?),
+            config.node.max_concurrent_deploy_validations,
+            config.node.max_queued_deploy_validations,
+            TimeDiff::from(config.node.deploy_max_future_skew_secs * 1000),
+            config.node.prefetch_deploy_dependencies,
+            registry,
+        )?;
         let deploy_fetcher = Fetcher::new(config.gossip);
         let deploy_gossiper = Gossiper::new_for_partial_items(
             config.gossip,
             gossiper::get_deploy_from_storage::<Deploy, Event>,
+            net.latency_store(),
         );
-        let deploy_buffer = DeployBuffer::new(config.node.block_max_deploy_count as usize);
+        let deploy_buffer =
+            DeployBuffer::new(&config.node, Box::new(DefaultGasEstimator), registry)?;
         // Post state hash is expected to be present.
         let genesis_post_state_hash = chainspec_loader
             .genesis_post_state_hash()
@@ -323,30 +526,72 @@ impl<R: Rng + CryptoRng + ?Sized> reactor::Reactor<R> for Reactor<R> {
         let block_executor =
             BlockExecutor::new(genesis_post_state_hash).with_parent_map(linear_chain);
         let proto_block_validator = BlockValidator::new();
-        let linear_chain = LinearChain::new();
+        let block_fetcher = Fetcher::new(config.gossip);
+        let block_validator = BlockValidator::new();
+        let linear_chain = LinearChain::new(validator_stakes.clone());
+        // Without consensus, observers only know the genesis validators.
+        let (era_id, validator_weights) = match consensus.as_ref() {
+            Some(consensus) => (
+                consensus.current_era(),
+                consensus.current_validator_weights(),
+            ),
+            None => (EraId(0), validator_stakes.iter().copied().collect()),
+        };
+        let finality_signature_collector = FinalitySignatureCollector::new(
+            era_id,
+            validator_weights,
+            config.consensus.finality_quorum,
+            registry,
+        )?;
+        let load_shedder = LoadShedder::new(config.load_shedder);
+        let proposal_builder = ProposalBuilder::new(
+            chainspec_loader.chainspec().genesis.deploy_config,
+            config.node.block_max_deploy_count as usize,
+        );
 
         let mut effects = reactor::wrap_effects(Event::Network, net_effects);
         effects.extend(reactor::wrap_effects(
             Event::Consensus,
             init_consensus_effects,
         ));
+        effects.extend(reactor::wrap_effects(
+            Event::DeployGossiper,
+            deploy_gossiper.start_anti_entropy(effect_builder),
+        ));
+        effects.extend(reactor::wrap_effects(
+            Event::LoadShedder,
+            load_shedder.start(effect_builder),
+        ));
+        effects.extend(reactor::wrap_effects(
+            Event::DeployBuffer,
+            deploy_buffer.restore_persisted(effect_builder),
+        ));
 
         Ok((
             Reactor {
                 metrics,
+                event_metrics,
                 net,
                 address_gossiper,
                 storage,
                 contract_runtime,
                 api_server,
                 consensus,
+                consensus_relay,
                 deploy_acceptor,
                 deploy_fetcher,
                 deploy_gossiper,
                 deploy_buffer,
                 block_executor,
                 proto_block_validator,
+                block_fetcher,
+                block_validator,
+                chain_follower,
                 linear_chain,
+                finality_signature_collector,
+                load_shedder,
+                proposal_builder,
+                shutdown_request: None,
             },
             effects,
         ))
@@ -359,63 +604,157 @@ impl<R: Rng + CryptoRng + ?Sized> reactor::Reactor<R> for Reactor<R> {
         event: Event,
     ) -> Effects<Self::Event> {
         match event {
-            Event::Network(event) => reactor::wrap_effects(
-                Event::Network,
-                self.net.handle_event(effect_builder, rng, event),
-            ),
-            Event::DeployBuffer(event) => reactor::wrap_effects(
-                Event::DeployBuffer,
-                self.deploy_buffer.handle_event(effect_builder, rng, event),
-            ),
-            Event::Storage(event) => reactor::wrap_effects(
-                Event::Storage,
-                self.storage.handle_event(effect_builder, rng, event),
-            ),
-            Event::ApiServer(event) => reactor::wrap_effects(
-                Event::ApiServer,
-                self.api_server.handle_event(effect_builder, rng, event),
-            ),
-            Event::Consensus(event) => reactor::wrap_effects(
-                Event::Consensus,
-                self.consensus.handle_event(effect_builder, rng, event),
-            ),
-            Event::DeployAcceptor(event) => reactor::wrap_effects(
-                Event::DeployAcceptor,
-                self.deploy_acceptor
-                    .handle_event(effect_builder, rng, event),
-            ),
-            Event::DeployFetcher(event) => reactor::wrap_effects(
-                Event::DeployFetcher,
-                self.deploy_fetcher.handle_event(effect_builder, rng, event),
-            ),
-            Event::DeployGossiper(event) => reactor::wrap_effects(
-                Event::DeployGossiper,
-                self.deploy_gossiper
-                    .handle_event(effect_builder, rng, event),
-            ),
-            Event::AddressGossiper(event) => reactor::wrap_effects(
-                Event::AddressGossiper,
-                self.address_gossiper
-                    .handle_event(effect_builder, rng, event),
-            ),
-            Event::ContractRuntime(event) => reactor::wrap_effects(
-                Event::ContractRuntime,
-                self.contract_runtime
-                    .handle_event(effect_builder, rng, event),
-            ),
-            Event::BlockExecutor(event) => reactor::wrap_effects(
-                Event::BlockExecutor,
-                self.block_executor.handle_event(effect_builder, rng, event),
-            ),
-            Event::ProtoBlockValidator(event) => reactor::wrap_effects(
-                Event::ProtoBlockValidator,
-                self.proto_block_validator
-                    .handle_event(effect_builder, rng, event),
-            ),
-            Event::LinearChain(event) => reactor::wrap_effects(
-                Event::LinearChain,
-                self.linear_chain.handle_event(effect_builder, rng, event),
-            ),
+            Event::Network(event) => {
+                let _timer = self.event_metrics.start_timer("network");
+                reactor::wrap_effects(
+                    Event::Network,
+                    self.net.handle_event(effect_builder, rng, event),
+                )
+            }
+            Event::DeployBuffer(event) => {
+                let _timer = self.event_metrics.start_timer("deploy_buffer");
+                reactor::wrap_effects(
+                    Event::DeployBuffer,
+                    self.deploy_buffer.handle_event(effect_builder, rng, event),
+                )
+            }
+            Event::Storage(event) => {
+                let _timer = self.event_metrics.start_timer("storage");
+                reactor::wrap_effects(
+                    Event::Storage,
+                    self.storage.handle_event(effect_builder, rng, event),
+                )
+            }
+            Event::ApiServer(event) => {
+                let _timer = self.event_metrics.start_timer("api_server");
+                reactor::wrap_effects(
+                    Event::ApiServer,
+                    self.api_server.handle_event(effect_builder, rng, event),
+                )
+            }
+            Event::Consensus(event) => match self.consensus.as_mut() {
+                Some(consensus) => {
+                    let _timer = self.event_metrics.start_timer("consensus");
+                    reactor::wrap_effects(
+                        Event::Consensus,
+                        consensus.handle_event(effect_builder, rng, event),
+                    )
+                }
+                // Observers don't participate in consensus.
+                None => Effects::new(),
+            },
+            Event::DeployAcceptor(event) => {
+                let _timer = self.event_metrics.start_timer("deploy_acceptor");
+                reactor::wrap_effects(
+                    Event::DeployAcceptor,
+                    self.deploy_acceptor
+                        .handle_event(effect_builder, rng, event),
+                )
+            }
+            Event::DeployFetcher(event) => {
+                let _timer = self.event_metrics.start_timer("deploy_fetcher");
+                reactor::wrap_effects(
+                    Event::DeployFetcher,
+                    self.deploy_fetcher.handle_event(effect_builder, rng, event),
+                )
+            }
+            Event::DeployGossiper(event) => {
+                let _timer = self.event_metrics.start_timer("deploy_gossiper");
+                reactor::wrap_effects(
+                    Event::DeployGossiper,
+                    self.deploy_gossiper
+                        .handle_event(effect_builder, rng, event),
+                )
+            }
+            Event::AddressGossiper(event) => {
+                let _timer = self.event_metrics.start_timer("address_gossiper");
+                reactor::wrap_effects(
+                    Event::AddressGossiper,
+                    self.address_gossiper
+                        .handle_event(effect_builder, rng, event),
+                )
+            }
+            Event::ContractRuntime(event) => {
+                let _timer = self.event_metrics.start_timer("contract_runtime");
+                reactor::wrap_effects(
+                    Event::ContractRuntime,
+                    self.contract_runtime
+                        .handle_event(effect_builder, rng, event),
+                )
+            }
+            Event::BlockExecutor(event) => {
+                let _timer = self.event_metrics.start_timer("block_executor");
+                reactor::wrap_effects(
+                    Event::BlockExecutor,
+                    self.block_executor.handle_event(effect_builder, rng, event),
+                )
+            }
+            Event::ProtoBlockValidator(event) => {
+                let _timer = self.event_metrics.start_timer("proto_block_validator");
+                reactor::wrap_effects(
+                    Event::ProtoBlockValidator,
+                    self.proto_block_validator
+                        .handle_event(effect_builder, rng, event),
+                )
+            }
+            Event::BlockFetcher(event) => {
+                let _timer = self.event_metrics.start_timer("block_fetcher");
+                reactor::wrap_effects(
+                    Event::BlockFetcher,
+                    self.block_fetcher.handle_event(effect_builder, rng, event),
+                )
+            }
+            Event::BlockValidator(event) => {
+                let _timer = self.event_metrics.start_timer("block_validator");
+                reactor::wrap_effects(
+                    Event::BlockValidator,
+                    self.block_validator
+                        .handle_event(effect_builder, rng, event),
+                )
+            }
+            Event::ChainFollower(event) => match self.chain_follower.as_mut() {
+                Some(chain_follower) => {
+                    let _timer = self.event_metrics.start_timer("chain_follower");
+                    reactor::wrap_effects(
+                        Event::ChainFollower,
+                        chain_follower.handle_event(effect_builder, rng, event),
+                    )
+                }
+                // Validators learn of new blocks by finalizing them.
+                None => Effects::new(),
+            },
+            Event::LinearChain(event) => {
+                let _timer = self.event_metrics.start_timer("linear_chain");
+                reactor::wrap_effects(
+                    Event::LinearChain,
+                    self.linear_chain.handle_event(effect_builder, rng, event),
+                )
+            }
+            Event::FinalitySignatureCollector(event) => {
+                let _timer = self
+                    .event_metrics
+                    .start_timer("finality_signature_collector");
+                reactor::wrap_effects(
+                    Event::FinalitySignatureCollector,
+                    self.finality_signature_collector
+                        .handle_event(effect_builder, rng, event),
+                )
+            }
+            Event::LoadShedder(event) => {
+                let _timer = self.event_metrics.start_timer("load_shedder");
+                reactor::wrap_effects(
+                    Event::LoadShedder,
+                    self.load_shedder.handle_event(effect_builder, rng, event),
+                )
+            }
+            Event::ProposalBuilder(event) => {
+                let _timer = self.event_metrics.start_timer("proposal_builder");
+                reactor::wrap_effects(
+                    Event::ProposalBuilder,
+                    self.proposal_builder
+                        .handle_event(effect_builder, rng, event),
+                )
+            }
 
             // Requests:
             Event::NetworkRequest(req) => self.dispatch_event(
@@ -434,6 +773,9 @@ impl<R: Rng + CryptoRng + ?Sized> reactor::Reactor<R> for Reactor<R> {
             Event::DeployBufferRequest(req) => {
                 self.dispatch_event(effect_builder, rng, Event::DeployBuffer(req.into()))
             }
+            Event::ProposalBuilderRequest(req) => {
+                self.dispatch_event(effect_builder, rng, Event::ProposalBuilder(req.into()))
+            }
             Event::BlockExecutorRequest(req) => self.dispatch_event(
                 effect_builder,
                 rng,
@@ -444,6 +786,14 @@ impl<R: Rng + CryptoRng + ?Sized> reactor::Reactor<R> for Reactor<R> {
                 rng,
                 Event::ProtoBlockValidator(block_validator::Event::from(req)),
             ),
+            Event::BlockFetcherRequest(req) => {
+                self.dispatch_event(effect_builder, rng, Event::BlockFetcher(req.into()))
+            }
+            Event::BlockValidatorRequest(req) => self.dispatch_event(
+                effect_builder,
+                rng,
+                Event::BlockValidator(block_validator::Event::from(req)),
+            ),
             Event::MetricsRequest(req) => reactor::wrap_effects(
                 Event::MetricsRequest,
                 self.metrics.handle_event(effect_builder, rng, req),
@@ -455,6 +805,9 @@ impl<R: Rng + CryptoRng + ?Sized> reactor::Reactor<R> for Reactor<R> {
                 payload,
             }) => {
                 let reactor_event = match payload {
+                    Message::Consensus(msg) if self.consensus.is_none() => {
+                        return self.relay_consensus_message(effect_builder, sender, msg);
+                    }
                     Message::Consensus(msg) => {
                         Event::Consensus(consensus::Event::MessageReceived { sender, msg })
                     }
@@ -464,6 +817,28 @@ impl<R: Rng + CryptoRng + ?Sized> reactor::Reactor<R> for Reactor<R> {
                     Message::AddressGossiper(message) => {
                         Event::AddressGossiper(gossiper::Event::MessageReceived { sender, message })
                     }
+                    Message::FinalitySignature(finality_signature)
+                        if self.chain_follower.is_some() =>
+                    {
+                        let event =
+                            Event::FinalitySignatureCollector((*finality_signature).clone().into());
+                        let mut effects = self.dispatch_event(effect_builder, rng, event);
+                        let event = Event::ChainFollower(chain_follower::Event::BlockSigned {
+                            finality_signature,
+                            sender,
+                        });
+                        effects.extend(self.dispatch_event(effect_builder, rng, event));
+                        return effects;
+                    }
+                    Message::FinalitySignature(finality_signature) => {
+                        Event::FinalitySignatureCollector((*finality_signature).into())
+                    }
+                    Message::EmergencyRestart(restart) => {
+                        Event::Consensus(consensus::Event::EmergencyRestart {
+                            sender,
+                            restart: *restart,
+                        })
+                    }
                     Message::GetRequest { tag, serialized_id } => match tag {
                         Tag::Deploy => {
                             let deploy_hash = match rmp_serde::from_read_ref(&serialized_id) {
@@ -516,9 +891,22 @@ impl<R: Rng + CryptoRng + ?Sized> reactor::Reactor<R> for Reactor<R> {
                             Event::DeployAcceptor(deploy_acceptor::Event::Accept {
                                 deploy,
                                 source: Source::Peer(sender),
+                                responder: None,
+                            })
+                        }
+                        Tag::Block => {
+                            let block = match rmp_serde::from_read_ref(&serialized_item) {
+                                Ok(block) => Box::new(block),
+                                Err(error) => {
+                                    error!("failed to decode block from {}: {}", sender, error);
+                                    return Effects::new();
+                                }
+                            };
+                            Event::BlockFetcher(fetcher::Event::GotRemotely {
+                                item: block,
+                                source: Source::Peer(sender),
                             })
                         }
-                        Tag::Block => todo!("Handle GET block response"),
                         Tag::GossipedAddress => {
                             warn!("received get request for gossiped-address from {}", sender);
                             return Effects::new();
@@ -534,38 +922,90 @@ impl<R: Rng + CryptoRng + ?Sized> reactor::Reactor<R> for Reactor<R> {
                 };
                 self.dispatch_event(effect_builder, rng, Event::AddressGossiper(event))
             }
+            Event::NetworkAnnouncement(NetworkAnnouncement::PeerConnected {
+                peer,
+                handshake_duration,
+                inbound,
+            }) => {
+                info!(%peer, ?handshake_duration, inbound, "peer connected");
+                let event = api_server::Event::PeerConnected(peer);
+                self.dispatch_event(effect_builder, rng, Event::ApiServer(event))
+            }
+            Event::NetworkAnnouncement(NetworkAnnouncement::PeerDisconnected { peer, reason }) => {
+                info!(%peer, %reason, "peer disconnected");
+                let event = api_server::Event::PeerDisconnected(peer);
+                let mut effects = self.dispatch_event(effect_builder, rng, Event::ApiServer(event));
+                let event = gossiper::Event::PeerDisconnected(peer);
+                effects.extend(self.dispatch_event(
+                    effect_builder,
+                    rng,
+                    Event::DeployGossiper(event),
+                ));
+                let event = fetcher::Event::PeerDisconnected(peer);
+                effects.extend(self.dispatch_event(
+                    effect_builder,
+                    rng,
+                    Event::DeployFetcher(event),
+                ));
+                let event = fetcher::Event::PeerDisconnected(peer);
+                effects.extend(self.dispatch_event(
+                    effect_builder,
+                    rng,
+                    Event::BlockFetcher(event),
+                ));
+                effects
+            }
             Event::NetworkAnnouncement(NetworkAnnouncement::NewPeer(peer_id)) => {
-                debug!(%peer_id, "new peer announcement event ignored (validator reactor does not care)");
-                Effects::new()
+                // Exchange digests of the deploys held, so only the missing ones are gossiped.
+                let event = gossiper::Event::PeerConnected(peer_id);
+                let mut effects =
+                    self.dispatch_event(effect_builder, rng, Event::DeployGossiper(event));
+                let event = fetcher::Event::PeerConnected(peer_id);
+                effects.extend(self.dispatch_event(
+                    effect_builder,
+                    rng,
+                    Event::DeployFetcher(event),
+                ));
+                let event = fetcher::Event::PeerConnected(peer_id);
+                effects.extend(self.dispatch_event(
+                    effect_builder,
+                    rng,
+                    Event::BlockFetcher(event),
+                ));
+                effects
             }
-            Event::ApiServerAnnouncement(ApiServerAnnouncement::DeployReceived { deploy }) => {
+            Event::ApiServerAnnouncement(ApiServerAnnouncement::DeployReceived {
+                deploy,
+                responder,
+            }) => {
                 let event = deploy_acceptor::Event::Accept {
                     deploy,
                     source: Source::<NodeId>::Client,
+                    responder,
                 };
                 self.dispatch_event(effect_builder, rng, Event::DeployAcceptor(event))
             }
-            Event::DeployAcceptorAnnouncement(DeployAcceptorAnnouncement::AcceptedNewDeploy {
+            Event::DeployAcceptorAnnouncement(DeployAcceptorAnnouncement::DeployAccepted {
+                deploy,
+                source: _,
+            }) => self.dispatch_event(
+                effect_builder,
+                rng,
+                Event::ApiServer(api_server::Event::DeployAccepted(*deploy.id())),
+            ),
+            Event::DeployAcceptorAnnouncement(DeployAcceptorAnnouncement::DeployStored {
                 deploy,
                 source,
             }) => {
-                let event = deploy_buffer::Event::Buffer {
-                    hash: *deploy.id(),
-                    header: Box::new(deploy.header().clone()),
-                };
-                let mut effects =
-                    self.dispatch_event(effect_builder, rng, Event::DeployBuffer(event));
-
+                // Only new deploys are gossiped, and only once peers can get them from storage.
                 let event = gossiper::Event::ItemReceived {
                     item_id: *deploy.id(),
                     source,
                 };
-                effects.extend(self.dispatch_event(
-                    effect_builder,
-                    rng,
-                    Event::DeployGossiper(event),
-                ));
+                let mut effects =
+                    self.dispatch_event(effect_builder, rng, Event::DeployGossiper(event));
 
+                // The deploy acceptor has already added the deploy to the deploy buffer.
                 let event = fetcher::Event::GotRemotely {
                     item: deploy,
                     source,
@@ -575,7 +1015,6 @@ impl<R: Rng + CryptoRng + ?Sized> reactor::Reactor<R> for Reactor<R> {
                     rng,
                     Event::DeployFetcher(event),
                 ));
-
                 effects
             }
             Event::DeployAcceptorAnnouncement(DeployAcceptorAnnouncement::InvalidDeploy {
@@ -603,27 +1042,199 @@ impl<R: Rng + CryptoRng + ?Sized> reactor::Reactor<R> for Reactor<R> {
                         debug!("Ignoring `Handled` announcement in `validator` reactor.");
                         Effects::new()
                     }
+                    ConsensusAnnouncement::BlockSigned(finality_signature) => {
+                        let mut effects = effect_builder
+                            .broadcast_message::<NodeId, _>(Message::FinalitySignature(
+                                finality_signature.clone(),
+                            ))
+                            .ignore();
+                        effects.extend(self.dispatch_event(
+                            effect_builder,
+                            rng,
+                            Event::FinalitySignatureCollector((*finality_signature).into()),
+                        ));
+                        effects
+                    }
+                    ConsensusAnnouncement::EraStarted {
+                        era_id,
+                        validator_weights,
+                    } => {
+                        let validators = validator_weights.keys().copied().collect();
+                        let mut effects = self.dispatch_event(
+                            effect_builder,
+                            rng,
+                            Event::Network(small_network::Event::ValidatorsChanged(validators)),
+                        );
+                        let event = finality_signature_collector::Event::EraStarted {
+                            era_id,
+                            validator_weights,
+                        };
+                        effects.extend(self.dispatch_event(
+                            effect_builder,
+                            rng,
+                            Event::FinalitySignatureCollector(event),
+                        ));
+                        effects
+                    }
+                    // Stalls are logged by consensus itself, and reported in the status.
+                    ConsensusAnnouncement::ConsensusStalled { last_progress, .. } => {
+                        let event = api_server::Event::ConsensusStalled { last_progress };
+                        self.dispatch_event(effect_builder, rng, Event::ApiServer(event))
+                    }
+                    ConsensusAnnouncement::ConsensusResumed { .. } => {
+                        let event = api_server::Event::ConsensusResumed;
+                        self.dispatch_event(effect_builder, rng, Event::ApiServer(event))
+                    }
                 }
             }
             Event::BlockExecutorAnnouncement(BlockExecutorAnnouncement::LinearChainBlock {
                 block,
                 execution_results,
             }) => {
+                let event = api_server::Event::BlockAdded {
+                    block_hash: *block.hash(),
+                    deploy_hashes: block.deploy_hashes().clone(),
+                };
+                let mut effects = self.dispatch_event(effect_builder, rng, Event::ApiServer(event));
+
+                let event = chain_follower::Event::BlockAdded {
+                    height: block.height(),
+                };
+                effects.extend(self.dispatch_event(
+                    effect_builder,
+                    rng,
+                    Event::ChainFollower(event),
+                ));
+
+                let event = finality_signature_collector::Event::BlockAdded {
+                    block_hash: *block.hash(),
+                    era_id: block.era_id(),
+                };
+                effects.extend(self.dispatch_event(
+                    effect_builder,
+                    rng,
+                    Event::FinalitySignatureCollector(event),
+                ));
+
                 let reactor_event = Event::LinearChain(linear_chain::Event::LinearChainBlock {
-                    block,
+                    block: Box::new(block),
                     execution_results,
                 });
-                self.dispatch_event(effect_builder, rng, reactor_event)
+                effects.extend(self.dispatch_event(effect_builder, rng, reactor_event));
+                effects
             }
-            Event::DeployGossiperAnnouncement(_ann) => {
-                unreachable!("the deploy gossiper should never make an announcement")
+            Event::DeployBufferAnnouncement(DeployBufferAnnouncement::RebroadcastDeploy(
+                deploy_hash,
+            )) => {
+                let event = gossiper::Event::Regossip {
+                    item_id: deploy_hash,
+                };
+                self.dispatch_event(effect_builder, rng, Event::DeployGossiper(event))
             }
-            Event::AddressGossiperAnnouncement(ann) => {
-                let GossiperAnnouncement::NewCompleteItem(gossiped_address) = ann;
+            Event::DeployGossiperAnnouncement(GossiperAnnouncement::NewCompleteItem(_)) => {
+                unreachable!("the deploy gossiper should never announce a complete item")
+            }
+            Event::DeployGossiperAnnouncement(GossiperAnnouncement::FinishedGossiping(
+                deploy_hash,
+            )) => {
+                let event = api_server::Event::DeployGossiped(deploy_hash);
+                self.dispatch_event(effect_builder, rng, Event::ApiServer(event))
+            }
+            Event::AddressGossiperAnnouncement(GossiperAnnouncement::NewCompleteItem(
+                gossiped_address,
+            )) => {
                 let reactor_event =
                     Event::Network(small_network::Event::PeerAddressReceived(gossiped_address));
                 self.dispatch_event(effect_builder, rng, reactor_event)
             }
+            Event::AddressGossiperAnnouncement(GossiperAnnouncement::FinishedGossiping(_)) => {
+                Effects::new()
+            }
+            Event::FinalitySignatureAnnouncement(
+                FinalitySignatureAnnouncement::FinalityProgress { .. },
+            ) => {
+                // The finality signature collector records the progress in its metrics.
+                Effects::new()
+            }
+            Event::FinalitySignatureAnnouncement(
+                FinalitySignatureAnnouncement::FinalitySignaturesComplete {
+                    block_hash,
+                    era_id,
+                    signatures,
+                },
+            ) => {
+                info!(%block_hash, signatures = signatures.len(), "block is final");
+                let event = consensus::Event::FinalitySignatures {
+                    era_id,
+                    signers: signatures.keys().copied().collect(),
+                };
+                let mut effects = self.dispatch_event(effect_builder, rng, Event::Consensus(event));
+                let event = api_server::Event::BlockFinalized(block_hash);
+                effects.extend(self.dispatch_event(effect_builder, rng, Event::ApiServer(event)));
+                effects
+            }
+            Event::LinearChainAnnouncement(LinearChainAnnouncement::BlockOrphaned { block }) => {
+                let event = storage::Event::BlockOrphaned {
+                    block_hash: *block.hash(),
+                };
+                let mut effects = self.dispatch_event(effect_builder, rng, Event::Storage(event));
+                let event = deploy_buffer::Event::OrphanedBlock(block);
+                effects.extend(self.dispatch_event(
+                    effect_builder,
+                    rng,
+                    Event::DeployBuffer(event),
+                ));
+                effects
+            }
+            Event::StorageAnnouncement(StorageAnnouncement::OutOfSpace) => {
+                // Shed load by refusing deploys from clients until space frees up.
+                self.deploy_acceptor.set_storage_full(true);
+                Effects::new()
+            }
+            Event::StorageAnnouncement(StorageAnnouncement::SpaceAvailable) => {
+                self.deploy_acceptor.set_storage_full(false);
+                Effects::new()
+            }
+            Event::LoadShedderAnnouncement(LoadShedderAnnouncement::LevelChanged(level)) => {
+                self.deploy_gossiper
+                    .set_anti_entropy_paused(level.pauses_anti_entropy());
+                self.deploy_gossiper
+                    .set_fan_out_reduced(level.reduces_fan_out());
+                self.api_server
+                    .set_rejecting_submissions(level.rejects_submissions());
+                Effects::new()
+            }
+            Event::ControlAnnouncement(ControlAnnouncement::FatalError(reason)) => {
+                // The first request wins, later ones are usually consequences of it.
+                let _ = self.shutdown_request.get_or_insert(reason);
+                Effects::new()
+            }
+        }
+    }
+
+    fn take_shutdown_request(&mut self) -> Option<ShutdownReason> {
+        self.shutdown_request.take()
+    }
+
+    fn prepare_shutdown(&mut self, reason: &ShutdownReason) {
+        self.net.say_goodbye(reason);
+    }
+
+    fn start(&mut self) {
+        for component in self.lifecycle_components() {
+            component.on_start();
+        }
+    }
+
+    fn stop(&mut self) {
+        for component in self.lifecycle_components() {
+            component.on_stop();
+        }
+    }
+
+    fn restart(&mut self) {
+        for component in self.lifecycle_components() {
+            component.on_restart();
         }
     }
 }
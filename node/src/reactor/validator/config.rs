@@ -1,8 +1,16 @@
+use std::{
+    fmt::{self, Display, Formatter},
+    fs,
+    path::{Path, PathBuf},
+};
+
 use serde::{Deserialize, Serialize};
+use thiserror::Error;
 
 use crate::{
-    logging::LoggingConfig, types::NodeConfig, ApiServerConfig, ConsensusConfig,
-    ContractRuntimeConfig, GossipConfig, SmallNetworkConfig, StorageConfig,
+    crypto::asymmetric_key::PublicKey, logging::LoggingConfig, types::NodeConfig, ApiServerConfig,
+    ConsensusConfig, ContractRuntimeConfig, DeployAcceptorConfig, GossipConfig, LoadShedderConfig,
+    SmallNetworkConfig, StorageConfig,
 };
 
 /// Root configuration.
@@ -26,4 +34,321 @@ pub struct Config {
     pub gossip: GossipConfig,
     /// Contract runtime configuration.
     pub contract_runtime: ContractRuntimeConfig,
+    /// Load shedder configuration.
+    pub load_shedder: LoadShedderConfig,
+    /// Deploy acceptor configuration.
+    pub deploy_acceptor: DeployAcceptorConfig,
+}
+
+impl Config {
+    /// Checks the configuration for values which are invalid, either by themselves or in
+    /// combination with other values, resolving relative paths from `root`.
+    ///
+    /// All problems found are reported at once.
+    pub fn validate<P: AsRef<Path>>(&self, root: P) -> Result<(), ValidationError> {
+        let mut problems = Vec::new();
+
+        if self.network.bind_address.is_empty() {
+            problems.push(Problem::MissingBindAddress);
+        }
+        if self.network.public_address.is_empty() {
+            problems.push(Problem::MissingPublicAddress);
+        }
+
+        let storage_path = self.storage.path();
+        if !is_writable_dir(&storage_path) {
+            problems.push(Problem::StorageNotWritable(storage_path));
+        }
+
+        match self.node.chainspec_config_path.clone().load(root) {
+            Ok(chainspec) => {
+                if chainspec.genesis.highway_config.era_duration.millis() == 0 {
+                    problems.push(Problem::ZeroEraDuration);
+                }
+            }
+            Err(error) => problems.push(Problem::Chainspec(error.to_string())),
+        }
+
+        for (target_prefix, rate) in &self.logging.sample_rates {
+            if *rate == 0 {
+                problems.push(Problem::ZeroLogSampleRate(target_prefix.clone()));
+            }
+        }
+
+        if self.consensus.verification_pool_size == 0 {
+            problems.push(Problem::ZeroVerificationPoolSize);
+        }
+        if self.consensus.max_pending_verifications == 0 {
+            problems.push(Problem::ZeroMaxPendingVerifications);
+        }
+        if self.node.max_concurrent_deploy_validations == 0 {
+            problems.push(Problem::ZeroDeployValidationConcurrency);
+        }
+        if self.node.deploy_rebroadcast_threshold_secs == 0 {
+            problems.push(Problem::ZeroDeployRebroadcastThreshold);
+        }
+        if self.network.cert_expiry_check_interval.as_millis() == 0 {
+            problems.push(Problem::ZeroCertExpiryCheckInterval);
+        }
+        let jitter = self.network.validator_reconnect_jitter;
+        if !(0.0..=1.0).contains(&jitter) {
+            problems.push(Problem::InvalidReconnectJitter(jitter));
+        }
+        let threshold = self.consensus.inactive_validator_threshold;
+        if !(0.0..=1.0).contains(&threshold) {
+            problems.push(Problem::InvalidInactiveValidatorThreshold(threshold));
+        }
+        let operators = self.consensus.emergency_restart_operators.len();
+        let threshold = self.consensus.emergency_restart_threshold;
+        if threshold > operators {
+            problems.push(Problem::EmergencyRestartThresholdExceedsOperators {
+                threshold,
+                operators,
+            });
+        }
+
+        let load_shedder = &self.load_shedder;
+        if !(load_shedder.pause_anti_entropy_at < load_shedder.reduce_fan_out_at
+            && load_shedder.reduce_fan_out_at < load_shedder.reject_submissions_at)
+        {
+            problems.push(Problem::UnorderedLoadSheddingThresholds {
+                pause_anti_entropy_at: load_shedder.pause_anti_entropy_at,
+                reduce_fan_out_at: load_shedder.reduce_fan_out_at,
+                reject_submissions_at: load_shedder.reject_submissions_at,
+            });
+        }
+
+        for account in &self.deploy_acceptor.accepted_accounts {
+            if let Err(error) = PublicKey::from_hex(account) {
+                problems.push(Problem::InvalidAcceptedAccount {
+                    account: account.clone(),
+                    error: error.to_string(),
+                });
+            }
+        }
+
+        let max_peers = self.network.max_inbound_connections;
+        for &(name, target) in &[
+            ("infection_target", self.gossip.infection_target()),
+            (
+                "local_infection_target",
+                self.gossip.local_infection_target(),
+            ),
+        ] {
+            if usize::from(target) > max_peers {
+                problems.push(Problem::InfectionTargetExceedsMaxPeers {
+                    name,
+                    target,
+                    max_peers,
+                });
+            }
+        }
+
+        if problems.is_empty() {
+            Ok(())
+        } else {
+            Err(ValidationError { problems })
+        }
+    }
+}
+
+/// Returns whether `path` is, or can be created as, a writable directory.
+///
+/// This only inspects the permissions of `path` or its closest existing ancestor; whether files can
+/// actually be written is probed by the initializer's self-check.
+fn is_writable_dir(path: &Path) -> bool {
+    path.ancestors()
+        .find(|ancestor| ancestor.exists())
+        .and_then(|existing| fs::metadata(existing).ok())
+        .map(|metadata| metadata.is_dir() && !metadata.permissions().readonly())
+        .unwrap_or(false)
+}
+
+/// A single problem with the configuration.
+#[derive(Debug, Error)]
+pub enum Problem {
+    /// The network listening address is not set.
+    #[error("network bind address is not set")]
+    MissingBindAddress,
+    /// The publicly advertised network address is not set.
+    #[error("network public address is not set")]
+    MissingPublicAddress,
+    /// The storage folder is not writable.
+    #[error("storage at {} is not writable", .0.display())]
+    StorageNotWritable(PathBuf),
+    /// The chainspec cannot be loaded.
+    #[error("cannot load chainspec: {0}")]
+    Chainspec(String),
+    /// The chainspec's era duration is zero.
+    #[error("era duration must be greater than zero")]
+    ZeroEraDuration,
+    /// A log sample rate is zero, i.e. not one in a number of events.
+    #[error("log sample rate of target prefix '{0}' must be greater than zero")]
+    ZeroLogSampleRate(String),
+    /// No incoming consensus messages could ever be verified.
+    #[error("consensus verification pool size must be greater than zero")]
+    ZeroVerificationPoolSize,
+    /// All incoming consensus messages would be dropped.
+    #[error("maximum number of pending consensus message verifications must be greater than zero")]
+    ZeroMaxPendingVerifications,
+    /// No deploys could ever be validated.
+    #[error("maximum number of concurrent deploy validations must be greater than zero")]
+    ZeroDeployValidationConcurrency,
+    /// Pending deploys would be rebroadcast continuously.
+    #[error("deploy rebroadcast threshold must be greater than zero")]
+    ZeroDeployRebroadcastThreshold,
+    /// The expiry of our certificate would be checked continuously.
+    #[error("certificate expiry check interval must be greater than zero")]
+    ZeroCertExpiryCheckInterval,
+    /// The jitter of the delay before reconnecting to a validator is not a fraction.
+    #[error("validator reconnect jitter must be between 0 and 1, not {0}")]
+    InvalidReconnectJitter(f64),
+    /// The participation rate below which a validator is reported as inactive is not a fraction.
+    #[error("inactive validator threshold must be between 0 and 1, not {0}")]
+    InvalidInactiveValidatorThreshold(f64),
+    /// An emergency restart can never be ordered.
+    #[error(
+        "emergency restart threshold of {threshold} exceeds the {operators} configured operators"
+    )]
+    EmergencyRestartThresholdExceedsOperators {
+        /// The configured threshold.
+        threshold: usize,
+        /// The number of configured operators.
+        operators: usize,
+    },
+    /// The load shedding stages wouldn't be entered in order as the pressure rises.
+    #[error(
+        "load shedding thresholds must increase, but pause_anti_entropy_at is \
+         {pause_anti_entropy_at}, reduce_fan_out_at {reduce_fan_out_at} and \
+         reject_submissions_at {reject_submissions_at}"
+    )]
+    UnorderedLoadSheddingThresholds {
+        /// The pressure at which anti-entropy rounds are paused.
+        pause_anti_entropy_at: f64,
+        /// The pressure at which the gossip fan-out is reduced.
+        reduce_fan_out_at: f64,
+        /// The pressure at which client submissions are rejected.
+        reject_submissions_at: f64,
+    },
+    /// An account whose deploys are accepted is not a valid public key.
+    #[error("accepted account {account} is invalid: {error}")]
+    InvalidAcceptedAccount {
+        /// The configured account.
+        account: String,
+        /// The reason the account is invalid.
+        error: String,
+    },
+    /// A gossip infection target can never be reached.
+    #[error("gossip {name} of {target} exceeds the maximum of {max_peers} peers")]
+    InfectionTargetExceedsMaxPeers {
+        /// The name of the infection target setting.
+        name: &'static str,
+        /// The configured infection target.
+        target: u8,
+        /// The maximum number of peers.
+        max_peers: usize,
+    },
+}
+
+/// The aggregated problems found when validating the configuration.
+#[derive(Debug)]
+pub struct ValidationError {
+    problems: Vec<Problem>,
+}
+
+impl ValidationError {
+    /// Returns all problems found.
+    pub fn problems(&self) -> &[Problem] {
+        &self.problems
+    }
+}
+
+impl Display for ValidationError {
+    fn fmt(&self, formatter: &mut Formatter<'_>) -> fmt::Result {
+        write!(formatter, "invalid configuration")?;
+        for problem in &self.problems {
+            write!(formatter, "; {}", problem)?;
+        }
+        Ok(())
+    }
+}
+
+impl std::error::Error for ValidationError {}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use super::*;
+    use crate::{testing::TestRng, types::TimeDiff, utils::External, Chainspec};
+
+    #[test]
+    fn should_report_all_problems() {
+        // A path beneath a regular file can never be created, regardless of permissions.
+        let temp_dir = tempfile::tempdir().expect("should get tempdir");
+        let file_path = temp_dir.path().join("not-a-dir");
+        fs::write(&file_path, b"").expect("should write file");
+        let storage_path = file_path.join("storage");
+
+        let mut rng = TestRng::new();
+        let mut chainspec = Chainspec::random(&mut rng);
+        chainspec.genesis.highway_config.era_duration = TimeDiff::from(0);
+
+        let mut config = Config::default();
+        config.network.bind_address = String::new();
+        config.network.max_inbound_connections = 4;
+        config.storage = toml::from_str(&format!("path = '{}'", storage_path.display()))
+            .expect("should parse storage config");
+        config.node.chainspec_config_path = External::value(chainspec);
+        config.deploy_acceptor.accepted_accounts = vec![String::from("not-a-key")];
+        config.load_shedder.reduce_fan_out_at = config.load_shedder.reject_submissions_at;
+        config.node.deploy_rebroadcast_threshold_secs = 0;
+        config.network.cert_expiry_check_interval = Duration::from_millis(0);
+        let _ = config
+            .logging
+            .sample_rates
+            .insert(String::from("casper_node::reactor"), 0);
+
+        let error = config
+            .validate(temp_dir.path())
+            .expect_err("validation should fail");
+        let problems = error.problems();
+        assert_eq!(problems.len(), 9, "unexpected problems: {}", error);
+        assert!(matches!(problems[0], Problem::MissingBindAddress));
+        assert!(matches!(
+            problems[1],
+            Problem::StorageNotWritable(ref path) if *path == storage_path
+        ));
+        assert!(matches!(problems[2], Problem::ZeroEraDuration));
+        assert!(matches!(
+            problems[3],
+            Problem::ZeroLogSampleRate(ref target_prefix) if target_prefix == "casper_node::reactor"
+        ));
+        assert!(matches!(
+            problems[4],
+            Problem::ZeroDeployRebroadcastThreshold
+        ));
+        assert!(matches!(problems[5], Problem::ZeroCertExpiryCheckInterval));
+        assert!(matches!(
+            problems[6],
+            Problem::UnorderedLoadSheddingThresholds { .. }
+        ));
+        assert!(matches!(
+            problems[7],
+            Problem::InvalidAcceptedAccount { ref account, .. } if account == "not-a-key"
+        ));
+        assert!(matches!(
+            problems[8],
+            Problem::InfectionTargetExceedsMaxPeers {
+                name: "local_infection_target",
+                target: 6,
+                max_peers: 4,
+            }
+        ));
+
+        let message = error.to_string();
+        assert!(message.starts_with("invalid configuration"));
+        assert!(message.contains("network bind address is not set"));
+        assert!(message.contains("era duration must be greater than zero"));
+    }
 }
@@ -0,0 +1,43 @@
+//! Relaying of consensus messages by observers.
+//!
+//! An observer doesn't handle consensus messages itself, but can pass them on to its peers, e.g. to
+//! reach nodes not directly connected to the validators.  Since observers may be connected to each
+//! other, every message is relayed at most once, so it doesn't circulate between them forever.
+
+use std::collections::{HashSet, VecDeque};
+
+use crate::{
+    components::consensus::ConsensusMessage,
+    crypto::hash::{self, Digest},
+};
+
+/// The number of relayed messages remembered.
+const RELAYED_MESSAGES_CAPACITY: usize = 10_000;
+
+/// Remembers the most recently relayed consensus messages.
+#[derive(Debug, Default)]
+pub(super) struct ConsensusRelay {
+    /// The hashes of the relayed messages.
+    relayed: HashSet<Digest>,
+    /// The hashes of the relayed messages, oldest first.
+    order: VecDeque<Digest>,
+}
+
+impl ConsensusRelay {
+    /// Returns whether `msg` should be relayed, i.e. hasn't been relayed recently, and remembers it
+    /// as relayed if so.
+    pub(super) fn should_relay(&mut self, msg: &ConsensusMessage) -> bool {
+        let serialized = rmp_serde::to_vec(msg).expect("should serialize consensus message");
+        let digest = hash::hash(&serialized);
+        if !self.relayed.insert(digest) {
+            return false;
+        }
+        self.order.push_back(digest);
+        if self.order.len() > RELAYED_MESSAGES_CAPACITY {
+            if let Some(oldest) = self.order.pop_front() {
+                self.relayed.remove(&oldest);
+            }
+        }
+        true
+    }
+}
@@ -1,6 +1,9 @@
 use thiserror::Error;
 
-use crate::components::{contract_runtime, small_network, storage};
+use crate::{
+    components::{contract_runtime, small_network, storage},
+    crypto,
+};
 
 /// Error type returned by the validator reactor.
 #[derive(Debug, Error)]
@@ -25,6 +28,10 @@ pub enum Error {
     #[error("contract runtime config error: {0}")]
     ContractRuntime(#[from] contract_runtime::ConfigError),
 
+    /// `DeployAcceptor` config error.
+    #[error("deploy acceptor config error: {0}")]
+    DeployAcceptor(#[from] crypto::Error),
+
     /// Failed to serialize data.
     #[error("serialization: {0}")]
     Serialization(#[from] rmp_serde::encode::Error),
@@ -1,4 +1,8 @@
-use std::{collections::HashSet, time::Duration};
+use std::{
+    collections::HashSet,
+    sync::{Arc, Mutex},
+    time::Duration,
+};
 
 use anyhow::bail;
 use rand::Rng;
@@ -8,9 +12,14 @@ use casper_execution_engine::{core::engine_state::genesis::GenesisAccount, share
 use casper_types::U512;
 
 use crate::{
-    components::{consensus::EraId, small_network, storage},
+    components::{
+        consensus::EraId,
+        small_network::{self, NodeId},
+        storage,
+    },
     crypto::asymmetric_key::{PublicKey, SecretKey},
-    reactor::{initializer, joiner, validator, Runner},
+    effect::{requests::ApiRequest, EffectExt},
+    reactor::{initializer, joiner, validator, QueueKind, Runner},
     testing::{self, network::Network, ConditionCheckReactor, TestRng},
     types::Timestamp,
     utils::{External, Loadable, WithDir, RESOURCES_PATH},
@@ -19,6 +28,8 @@ use crate::{
 
 struct TestChain {
     keys: Vec<SecretKey>,
+    /// The number of observer nodes, in addition to a node per validator key.
+    observers: usize,
     storages: Vec<TempDir>,
     chainspec: Chainspec,
 }
@@ -55,12 +66,14 @@ impl TestChain {
 
         TestChain {
             keys,
+            observers: 0,
             chainspec,
             storages: Vec::new(),
         }
     }
 
-    /// Creates an initializer/validator configuration for the `idx`th validator.
+    /// Creates an initializer/validator configuration for the `idx`th node, which is an observer if
+    /// there is no `idx`th validator key.
     fn create_node_config(&mut self, idx: usize, first_node_port: u16) -> validator::Config {
         // Start with a default configuration.
         let mut cfg = validator::Config::default();
@@ -76,7 +89,12 @@ impl TestChain {
         cfg.node.chainspec_config_path = External::value(self.chainspec.clone());
 
         // ...and the secret key for our validator.
-        cfg.consensus.secret_key_path = External::value(self.keys[idx].duplicate());
+        match self.keys.get(idx) {
+            Some(secret_key) => {
+                cfg.consensus.secret_key_path = External::value(secret_key.duplicate())
+            }
+            None => cfg.node.observer_mode = true,
+        }
 
         // Additionally set up storage in a temporary directory.
         let (storage_cfg, temp_dir) = storage::Config::default_for_tests();
@@ -95,7 +113,7 @@ impl TestChain {
         let mut network: Network<validator::Reactor<TestRng>> = Network::new();
         let first_node_port = testing::unused_port_on_localhost();
 
-        for idx in 0..self.keys.len() {
+        for idx in 0..(self.keys.len() + self.observers) {
             let cfg = self.create_node_config(idx, first_node_port);
 
             // We create an initializer reactor here and run it to completion.
@@ -137,6 +155,7 @@ fn era_ids(
         .reactor()
         .inner()
         .consensus()
+        .expect("validator should run consensus")
         .active_eras()
         .keys()
         .cloned()
@@ -184,4 +203,114 @@ async fn run_validator_network() {
 
     net.settle_on(&mut rng, is_in_era(2), Duration::from_secs(60))
         .await;
+
+    // Every node should have recorded event handling times for its busiest components.
+    for runner in net.nodes().values() {
+        let event_metrics = runner.reactor().inner().event_metrics();
+        for component in &["network", "consensus", "storage"] {
+            assert!(
+                event_metrics.sample_count(component) > 0,
+                "no samples recorded for {}",
+                component
+            );
+        }
+    }
+}
+
+#[tokio::test]
+async fn observer_should_not_participate_in_consensus_but_serve_chain_queries() {
+    testing::init_logging();
+
+    let mut rng = TestRng::new();
+
+    const VALIDATOR_COUNT: usize = 2;
+    let mut chain = TestChain::new(&mut rng, VALIDATOR_COUNT);
+    chain.observers = 1;
+
+    let mut net = chain
+        .create_initialized_network(&mut rng)
+        .await
+        .expect("network initialization failed");
+
+    let observer_id = *net
+        .nodes()
+        .iter()
+        .find(|(_, runner)| runner.reactor().inner().consensus().is_none())
+        .map(|(node_id, _)| node_id)
+        .expect("should have an observer");
+    let is_validator = move |node_id: &NodeId| *node_id != observer_id;
+
+    // The validators progress without the observer.
+    net.settle_on(
+        &mut rng,
+        |nodes: &Nodes| {
+            let validator_eras: Vec<_> = nodes
+                .iter()
+                .filter(|(node_id, _)| is_validator(node_id))
+                .map(|(_, runner)| era_ids(runner))
+                .collect();
+            validator_eras[0].len() > 1
+                && validator_eras.iter().all(|eras| *eras == validator_eras[0])
+        },
+        Duration::from_secs(60),
+    )
+    .await;
+
+    // The observer has handled no consensus events at all...
+    let observer = net.nodes()[&observer_id].reactor().inner();
+    assert_eq!(observer.event_metrics().sample_count("consensus"), 0);
+    assert!(observer.event_metrics().sample_count("network") > 0);
+
+    // ...but still follows the validators' linear chain and serves its blocks via the API.
+    let validator_id = *net
+        .nodes()
+        .keys()
+        .find(|node_id| is_validator(node_id))
+        .expect("should have a validator");
+    let block = net.nodes()[&validator_id]
+        .reactor()
+        .inner()
+        .linear_chain()
+        .first()
+        .cloned()
+        .expect("validators should have added a block");
+    let block_hash = *block.hash();
+    net.settle_on(
+        &mut rng,
+        |nodes: &Nodes| {
+            nodes[&observer_id]
+                .reactor()
+                .inner()
+                .linear_chain()
+                .iter()
+                .any(|observed| *observed.hash() == block_hash)
+        },
+        Duration::from_secs(60),
+    )
+    .await;
+    let served = Arc::new(Mutex::new(None));
+    let served_clone = Arc::clone(&served);
+    net.process_injected_effect_on(&observer_id, move |effect_builder| {
+        async move {
+            let maybe_block = effect_builder
+                .make_request(
+                    |responder| ApiRequest::GetBlock {
+                        maybe_hash: Some(block_hash),
+                        responder,
+                    },
+                    QueueKind::Api,
+                )
+                .await;
+            *served_clone.lock().unwrap() = Some(maybe_block);
+        }
+        .ignore()
+    })
+    .await;
+    net.settle_on(
+        &mut rng,
+        |_| served.lock().unwrap().is_some(),
+        Duration::from_secs(10),
+    )
+    .await;
+    assert_eq!(served.lock().unwrap().take(), Some(Some(block)));
 }
@@ -4,6 +4,7 @@
 //! `casper-node` library.
 
 mod condition_check_reactor;
+pub mod harness;
 pub mod network;
 mod test_rng;
 
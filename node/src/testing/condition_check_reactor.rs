@@ -8,6 +8,7 @@ use crate::{
     effect::{EffectBuilder, Effects},
     reactor::{EventQueueHandle, Finalize, Reactor},
     testing::TestRng,
+    types::ShutdownReason,
 };
 
 /// A reactor wrapping an inner reactor, and which has an optional hook into
@@ -18,10 +19,13 @@ use crate::{
 /// been met.
 ///
 /// Once the condition is met, the hook is reset to `None`.
+///
+/// Optionally, the `Display` representation of every dispatched event is recorded.
 pub struct ConditionCheckReactor<R: Reactor<TestRng>> {
     reactor: R,
     condition_checker: Option<Box<dyn Fn(&R::Event) -> bool + Send>>,
     condition_result: bool,
+    recorded_events: Option<Vec<String>>,
 }
 
 impl<R: Reactor<TestRng>> ConditionCheckReactor<R> {
@@ -38,6 +42,18 @@ impl<R: Reactor<TestRng>> ConditionCheckReactor<R> {
         self.condition_result
     }
 
+    /// Starts recording all subsequently dispatched events.
+    pub fn record_events(&mut self) {
+        if self.recorded_events.is_none() {
+            self.recorded_events = Some(Vec::new());
+        }
+    }
+
+    /// Returns the events recorded so far, in the order they were dispatched.
+    pub fn recorded_events(&self) -> &[String] {
+        self.recorded_events.as_deref().unwrap_or_default()
+    }
+
     /// Returns a reference to the wrapped reactor.
     pub fn inner(&self) -> &R {
         &self.reactor
@@ -66,6 +82,7 @@ impl<R: Reactor<TestRng>> Reactor<TestRng> for ConditionCheckReactor<R> {
                 reactor,
                 condition_checker: None,
                 condition_result: false,
+                recorded_events: None,
             },
             effects,
         ))
@@ -85,8 +102,27 @@ impl<R: Reactor<TestRng>> Reactor<TestRng> for ConditionCheckReactor<R> {
         if self.condition_result {
             self.condition_checker = None;
         }
+        if let Some(recorded_events) = self.recorded_events.as_mut() {
+            recorded_events.push(event.to_string());
+        }
         self.reactor.dispatch_event(effect_builder, rng, event)
     }
+
+    fn prepare_shutdown(&mut self, reason: &ShutdownReason) {
+        self.reactor.prepare_shutdown(reason)
+    }
+
+    fn start(&mut self) {
+        self.reactor.start()
+    }
+
+    fn stop(&mut self) {
+        self.reactor.stop()
+    }
+
+    fn restart(&mut self) {
+        self.reactor.restart()
+    }
 }
 
 impl<R: Reactor<TestRng> + Finalize> Finalize for ConditionCheckReactor<R> {
@@ -0,0 +1,177 @@
+//! A deterministic harness for end-to-end tests of test reactors.
+//!
+//! The harness runs a [`Network`] of test reactors with a seeded [`TestRng`] while the tokio clock
+//! is paused, so timers only fire once time is advanced explicitly via [`Harness::advance_time`]
+//! and no test ever has to sleep.  Blocking operations, like storage operations, run inline on the
+//! test thread instead of on tokio's blocking pool, so no other thread takes part in a run.  Nodes
+//! are always cranked in the order they were added, and every event dispatched on a node is
+//! recorded, so tests can assert on the sequence of events as well as on the final state of each
+//! reactor.
+//!
+//! Reactors run in a harness should be built from components which don't perform any real network
+//! I/O, like the in-memory network, for runs to be replayable from the seed of the `TestRng`.
+
+use std::{
+    fmt::Debug,
+    time::{Duration, Instant},
+};
+
+use tokio::{task, time};
+
+use super::{
+    network::{Network, NetworkedReactor, Nodes},
+    TestRng,
+};
+use crate::{
+    effect::{EffectBuilder, Effects},
+    reactor::Reactor,
+    utils,
+};
+
+/// The maximum wall-clock time a run may take before the harness gives up.
+///
+/// This only guards against conditions which are never met, since the mock clock doesn't advance
+/// while running.
+const MAX_RUN_TIME: Duration = Duration::from_secs(20);
+/// The number of consecutive rounds without any events after which the nodes are considered idle.
+const IDLE_ROUNDS: usize = 100;
+
+/// A harness driving a network of test reactors deterministically.
+pub struct Harness<R: Reactor<TestRng> + NetworkedReactor> {
+    network: Network<R>,
+    /// The IDs of all nodes, in the order they were added.
+    node_ids: Vec<R::NodeId>,
+    rng: TestRng,
+}
+
+impl<R> Harness<R>
+where
+    R: Reactor<TestRng> + NetworkedReactor,
+    R::Error: From<prometheus::Error> + Debug,
+{
+    /// Creates an empty harness using `rng`, pauses the tokio clock for the rest of the test and
+    /// runs blocking operations inline until the harness is dropped.
+    pub fn new(rng: TestRng) -> Self {
+        time::pause();
+        utils::set_run_blocking_inline(true);
+        Harness {
+            network: Network::new(),
+            node_ids: Vec::new(),
+            rng,
+        }
+    }
+
+    /// Adds a node with the given configuration, and returns its ID.
+    pub async fn add_node(&mut self, cfg: R::Config) -> R::NodeId {
+        let (node_id, runner) = self
+            .network
+            .add_node_with_config(cfg, &mut self.rng)
+            .await
+            .expect("should create node");
+        runner.reactor_mut().record_events();
+        self.node_ids.push(node_id.clone());
+        node_id
+    }
+
+    /// Creates effects via `create_effects` and processes them on the given node.
+    pub async fn inject<F>(&mut self, node_id: &R::NodeId, create_effects: F)
+    where
+        F: FnOnce(EffectBuilder<R::Event>) -> Effects<R::Event>,
+    {
+        self.network
+            .process_injected_effect_on(node_id, create_effects)
+            .await
+    }
+
+    /// Advances the mock clock by `duration`, firing all timers which have become due.
+    ///
+    /// The resulting events are only processed by subsequent runs.
+    pub async fn advance_time(&mut self, duration: Duration) {
+        time::advance(duration).await;
+    }
+
+    /// Cranks every node once, in the order they were added, and returns the number of events
+    /// processed.
+    async fn crank_round(&mut self) -> usize {
+        let mut event_count = 0;
+        for node_id in &self.node_ids {
+            event_count += self.network.crank(node_id, &mut self.rng).await;
+        }
+        // Give the effects spawned by the events a chance to run.
+        task::yield_now().await;
+        event_count
+    }
+
+    /// Runs all nodes until `condition` is met.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the condition isn't met within `MAX_RUN_TIME`.
+    pub async fn run_until<F>(&mut self, condition: F)
+    where
+        F: Fn(&Nodes<R>) -> bool,
+    {
+        let start = Instant::now();
+        while !condition(self.network.nodes()) {
+            assert!(
+                start.elapsed() < MAX_RUN_TIME,
+                "condition not met within {:?}",
+                MAX_RUN_TIME
+            );
+            let _ = self.crank_round().await;
+        }
+    }
+
+    /// Runs all nodes until none of them has processed an event for `IDLE_ROUNDS` rounds.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the nodes don't become idle within `MAX_RUN_TIME`.
+    pub async fn run_until_idle(&mut self) {
+        let start = Instant::now();
+        let mut idle_rounds = 0;
+        while idle_rounds < IDLE_ROUNDS {
+            assert!(
+                start.elapsed() < MAX_RUN_TIME,
+                "nodes did not become idle within {:?}",
+                MAX_RUN_TIME
+            );
+            if self.crank_round().await == 0 {
+                idle_rounds += 1;
+            } else {
+                idle_rounds = 0;
+            }
+        }
+    }
+
+    /// Returns all nodes.
+    pub fn nodes(&self) -> &Nodes<R> {
+        self.network.nodes()
+    }
+
+    /// Returns the reactor of the given node.
+    pub fn reactor(&self, node_id: &R::NodeId) -> &R {
+        self.network.nodes()[node_id].reactor().inner()
+    }
+
+    /// Returns the events dispatched on the given node so far, in order.
+    pub fn events(&self, node_id: &R::NodeId) -> &[String] {
+        self.network.nodes()[node_id].reactor().recorded_events()
+    }
+
+    /// Returns the IDs of all nodes, in the order they were added.
+    pub fn node_ids(&self) -> &[R::NodeId] {
+        &self.node_ids
+    }
+
+    /// Returns the harness' random number generator.
+    pub fn rng(&mut self) -> &mut TestRng {
+        &mut self.rng
+    }
+}
+
+impl<R: Reactor<TestRng> + NetworkedReactor> Drop for Harness<R> {
+    fn drop(&mut self) {
+        utils::set_run_blocking_inline(false);
+    }
+}
@@ -148,6 +148,12 @@ impl Debug for KeyFingerprint {
     }
 }
 
+impl AsRef<[u8]> for KeyFingerprint {
+    fn as_ref(&self) -> &[u8] {
+        self.0.bytes()
+    }
+}
+
 #[cfg(test)]
 impl From<[u8; Sha512::SIZE]> for KeyFingerprint {
     fn from(raw_bytes: [u8; Sha512::SIZE]) -> Self {
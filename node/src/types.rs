@@ -5,13 +5,15 @@ mod deploy;
 mod item;
 pub mod json_compatibility;
 mod node_config;
+mod shutdown_reason;
 mod status_feed;
 mod timestamp;
 
-pub use block::{Block, BlockHash, BlockHeader};
+pub use block::{Block, BlockHash, BlockHeader, FinalitySignature};
 pub(crate) use block::{BlockLike, FinalizedBlock, ProtoBlock, ProtoBlockHash, SystemTransaction};
 pub use deploy::{Approval, Deploy, DeployHash, DeployHeader, Error as DeployError};
 pub use item::{Item, Tag};
 pub use node_config::NodeConfig;
+pub use shutdown_reason::ShutdownReason;
 pub use status_feed::StatusFeed;
 pub use timestamp::{TimeDiff, Timestamp};
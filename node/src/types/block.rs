@@ -21,17 +21,16 @@ use super::{Item, Tag, Timestamp};
 use crate::{
     components::{consensus::EraId, storage::Value},
     crypto::{
-        asymmetric_key::{PublicKey, Signature},
+        self,
+        asymmetric_key::{self, PublicKey, Signature, SigningPurpose},
         hash::{self, Digest},
+        merkle::{self, MerkleProof},
     },
     types::DeployHash,
     utils::DisplayIter,
 };
 #[cfg(test)]
-use crate::{
-    crypto::asymmetric_key::{self, SecretKey},
-    testing::TestRng,
-};
+use crate::{crypto::asymmetric_key::SecretKey, testing::TestRng};
 
 /// Error returned from constructing or validating a `Block`.
 #[derive(Debug, Error)]
@@ -99,11 +98,36 @@ impl Display for ProtoBlockHash {
 ///
 /// The word "proto" does _not_ refer to "protocol" or "protobuf"! It is just a prefix to highlight
 /// that this comes before a block in the linear, executed, finalized blockchain is produced.
+///
+/// The Merkle root over the deploys isn't serialized, but recomputed on deserializing it.
 #[derive(Clone, Debug, PartialOrd, Ord, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(from = "SerializedProtoBlock")]
 pub struct ProtoBlock {
     hash: ProtoBlockHash,
     deploys: Vec<DeployHash>,
     random_bit: bool,
+    #[serde(skip_serializing)]
+    deploy_root: Digest,
+}
+
+/// The serialized fields of a `ProtoBlock`.
+#[derive(Deserialize)]
+struct SerializedProtoBlock {
+    hash: ProtoBlockHash,
+    deploys: Vec<DeployHash>,
+    random_bit: bool,
+}
+
+impl From<SerializedProtoBlock> for ProtoBlock {
+    fn from(serialized: SerializedProtoBlock) -> Self {
+        let deploy_root = deploy_root(&serialized.deploys);
+        ProtoBlock {
+            hash: serialized.hash,
+            deploys: serialized.deploys,
+            random_bit: serialized.random_bit,
+            deploy_root,
+        }
+    }
 }
 
 impl ProtoBlock {
@@ -111,11 +135,13 @@ impl ProtoBlock {
         let hash = ProtoBlockHash::new(hash::hash(
             &rmp_serde::to_vec(&(&deploys, random_bit)).expect("serialize ProtoBlock"),
         ));
+        let deploy_root = deploy_root(&deploys);
 
         ProtoBlock {
             hash,
             deploys,
             random_bit,
+            deploy_root,
         }
     }
 
@@ -133,6 +159,11 @@ impl ProtoBlock {
         self.random_bit
     }
 
+    /// The Merkle root over the deploy hashes included in the block.
+    pub(crate) fn deploy_root(&self) -> &Digest {
+        &self.deploy_root
+    }
+
     pub(crate) fn destructure(self) -> (ProtoBlockHash, Vec<DeployHash>, bool) {
         (self.hash, self.deploys, self.random_bit)
     }
@@ -356,6 +387,39 @@ impl AsRef<[u8]> for BlockHash {
     }
 }
 
+/// A validator's signature of a block's hash, attesting that the block is final.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct FinalitySignature {
+    /// The hash of the signed block.
+    pub block_hash: BlockHash,
+    /// The public key of the signing validator.
+    pub public_key: PublicKey,
+    /// The signature of the block hash.
+    pub signature: Signature,
+}
+
+impl FinalitySignature {
+    /// Verifies that `signature` is a valid signature of `block_hash` by `public_key`.
+    pub fn verify(&self) -> crypto::Result<()> {
+        asymmetric_key::verify(
+            self.block_hash.inner(),
+            &self.signature,
+            &self.public_key,
+            SigningPurpose::FinalitySignature,
+        )
+    }
+}
+
+impl Display for FinalitySignature {
+    fn fmt(&self, formatter: &mut Formatter) -> fmt::Result {
+        write!(
+            formatter,
+            "finality signature for {} by {}",
+            self.block_hash, self.public_key
+        )
+    }
+}
+
 /// The header portion of a [`Block`](struct.Block.html).
 #[derive(Clone, Ord, PartialOrd, Eq, PartialEq, Hash, Serialize, Deserialize, Debug)]
 pub struct BlockHeader {
@@ -363,6 +427,7 @@ pub struct BlockHeader {
     global_state_hash: Digest,
     body_hash: Digest,
     deploy_hashes: Vec<DeployHash>,
+    deploy_root: Digest,
     random_bit: bool,
     switch_block: bool,
     timestamp: Timestamp,
@@ -393,6 +458,11 @@ impl BlockHeader {
         &self.deploy_hashes
     }
 
+    /// The Merkle root over the hashes of the deploys included in the block.
+    pub fn deploy_root(&self) -> &Digest {
+        &self.deploy_root
+    }
+
     /// Returns `true` if this is the last block of an era.
     pub fn switch_block(&self) -> bool {
         self.switch_block
@@ -482,12 +552,15 @@ impl Block {
 
         let era_id = finalized_block.era_id();
         let height = finalized_block.height();
+        let deploy_root = finalized_block.proto_block.deploy_root;
+        let deploy_hashes = finalized_block.proto_block.deploys;
 
         let header = BlockHeader {
             parent_hash,
             global_state_hash,
             body_hash,
-            deploy_hashes: finalized_block.proto_block.deploys,
+            deploy_hashes,
+            deploy_root,
             random_bit: finalized_block.proto_block.random_bit,
             switch_block: finalized_block.switch_block,
             timestamp: finalized_block.timestamp,
@@ -523,10 +596,24 @@ impl Block {
         self.header.deploy_hashes()
     }
 
+    /// Returns the hash of the proto block this block was created from.
+    pub(crate) fn proto_block_hash(&self) -> ProtoBlockHash {
+        *ProtoBlock::new(self.header.deploy_hashes.clone(), self.header.random_bit).hash()
+    }
+
     pub(crate) fn height(&self) -> u64 {
         self.header.height()
     }
 
+    /// Returns a proof that the given deploy is included in this block, verifiable against the
+    /// header's deploy root, or `None` if it isn't included.
+    pub(crate) fn deploy_inclusion_proof(&self, deploy_hash: &DeployHash) -> Option<MerkleProof> {
+        let deploy_hashes = self.deploy_hashes();
+        let index = deploy_hashes.iter().position(|hash| hash == deploy_hash)?;
+        let leaves: Vec<Digest> = deploy_hashes.iter().map(|hash| *hash.inner()).collect();
+        MerkleProof::new(&leaves, index)
+    }
+
     pub(crate) fn era_id(&self) -> EraId {
         self.header.era_id()
     }
@@ -596,7 +683,13 @@ impl Block {
         for _ in 0..signatures_count {
             let secret_key = SecretKey::random(rng);
             let public_key = PublicKey::from(&secret_key);
-            let signature = asymmetric_key::sign(block.hash.inner(), &secret_key, &public_key, rng);
+            let signature = asymmetric_key::sign(
+                block.hash.inner(),
+                &secret_key,
+                &public_key,
+                SigningPurpose::FinalitySignature,
+                rng,
+            );
             block.append_proof(signature);
         }
 
@@ -604,6 +697,12 @@ impl Block {
     }
 }
 
+/// Returns the Merkle root over the given deploy hashes.
+fn deploy_root(deploy_hashes: &[DeployHash]) -> Digest {
+    let leaves: Vec<Digest> = deploy_hashes.iter().map(|hash| *hash.inner()).collect();
+    merkle::root(&leaves)
+}
+
 impl Display for Block {
     fn fmt(&self, formatter: &mut Formatter<'_>) -> fmt::Result {
         write!(
@@ -631,6 +730,12 @@ impl BlockLike for Block {
     }
 }
 
+impl<T: BlockLike> BlockLike for Box<T> {
+    fn deploys(&self) -> &Vec<DeployHash> {
+        (**self).deploys()
+    }
+}
+
 impl Value for Block {
     type Id = BlockHash;
     type Header = BlockHeader;
@@ -646,6 +751,10 @@ impl Value for Block {
     fn take_header(self) -> Self::Header {
         self.header
     }
+
+    fn is_intact(&self) -> bool {
+        self.header.hash() == self.hash
+    }
 }
 
 impl Item for Block {
@@ -744,6 +853,7 @@ mod json {
         global_state_hash: String,
         body_hash: String,
         deploy_hashes: Vec<String>,
+        deploy_root: String,
         random_bit: bool,
         switch_block: bool,
         timestamp: Timestamp,
@@ -764,6 +874,7 @@ mod json {
                     .iter()
                     .map(|deploy_hash| hex::encode(deploy_hash.as_ref()))
                     .collect(),
+                deploy_root: hex::encode(header.deploy_root),
                 random_bit: header.random_bit,
                 switch_block: header.switch_block,
                 timestamp: header.timestamp,
@@ -805,6 +916,8 @@ mod json {
                 body_hash: Digest::from_hex(&header.body_hash)
                     .map_err(|error| Error::DecodeFromJson(Box::new(error)))?,
                 deploy_hashes,
+                deploy_root: Digest::from_hex(&header.deploy_root)
+                    .map_err(|error| Error::DecodeFromJson(Box::new(error)))?,
                 random_bit: header.random_bit,
                 switch_block: header.switch_block,
                 timestamp: header.timestamp,
@@ -869,4 +982,48 @@ mod tests {
         let decoded = Block::from_json(json).unwrap();
         assert_eq!(block, decoded);
     }
+
+    #[test]
+    fn deploy_inclusion_proof_should_verify_against_deploy_root() {
+        let mut rng = TestRng::new();
+        let block = loop {
+            let block = Block::random(&mut rng);
+            if !block.deploy_hashes().is_empty() {
+                break block;
+            }
+        };
+
+        for deploy_hash in block.deploy_hashes() {
+            let proof = block
+                .deploy_inclusion_proof(deploy_hash)
+                .expect("should prove included deploy");
+            assert!(proof.verify(deploy_hash.inner(), block.header.deploy_root()));
+        }
+
+        let other_deploy = DeployHash::new(Digest::random(&mut rng));
+        assert!(block.deploy_inclusion_proof(&other_deploy).is_none());
+    }
+
+    #[test]
+    fn proto_block_should_recompute_deploy_root_when_deserialized() {
+        let mut rng = TestRng::new();
+        let deploys = vec![
+            DeployHash::new(Digest::random(&mut rng)),
+            DeployHash::new(Digest::random(&mut rng)),
+        ];
+        let proto_block = ProtoBlock::new(deploys, true);
+        let serialized = rmp_serde::to_vec(&proto_block).unwrap();
+        assert_eq!(
+            serialized,
+            rmp_serde::to_vec(&(
+                proto_block.hash(),
+                proto_block.deploys(),
+                proto_block.random_bit()
+            ))
+            .unwrap()
+        );
+        let decoded: ProtoBlock = rmp_serde::from_read_ref(&serialized).unwrap();
+        assert_eq!(decoded, proto_block);
+        assert_eq!(decoded.deploy_root(), &deploy_root(proto_block.deploys()));
+    }
 }
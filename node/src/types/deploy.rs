@@ -1,14 +1,19 @@
+mod canonical;
+
 use std::{
     array::TryFromSliceError,
+    cmp::Ordering,
     collections::BTreeSet,
     convert::TryFrom,
     error::Error as StdError,
     fmt::{self, Debug, Display, Formatter},
+    hash::{Hash, Hasher},
     iter::FromIterator,
 };
 
 use hex::FromHexError;
 use itertools::Itertools;
+use once_cell::sync::OnceCell;
 #[cfg(test)]
 use rand::RngCore;
 use rand::{CryptoRng, Rng};
@@ -22,17 +27,18 @@ use casper_execution_engine::core::engine_state::{
 };
 
 use super::{Item, Tag, TimeDiff, Timestamp};
-#[cfg(test)]
-use crate::testing::TestRng;
 use crate::{
     components::storage::Value,
     crypto::{
-        asymmetric_key::{self, PublicKey, SecretKey, Signature},
-        hash::{self, Digest},
+        asymmetric_key::{self, PublicKey, SecretKey, Signature, SignatureScheme, SigningPurpose},
+        hash::Digest,
         Error as CryptoError,
     },
     utils::DisplayIter,
 };
+#[cfg(test)]
+use crate::{crypto::hash, testing::TestRng};
+use canonical::Layout;
 
 const DESER_ERROR_MSG_GENERAL: &str = "failed to deserialize deploy";
 const DEPLOY_HASH_MISMATCH_MSG: &str = "deploy hash mismatch";
@@ -61,6 +67,51 @@ pub enum Error {
         /// The verification error.
         error: CryptoError,
     },
+
+    /// The signature of an approval was made with a different scheme than the signer's key.
+    #[error(
+        "approval {index} has a {signature_scheme} signature, but a {signer_scheme} signer key"
+    )]
+    SignatureSchemeMismatch {
+        /// The index of the approval.
+        index: usize,
+        /// The scheme of the signer's key.
+        signer_scheme: SignatureScheme,
+        /// The scheme of the signature.
+        signature_scheme: SignatureScheme,
+    },
+
+    /// The deploy hash does not match the deploy's header.
+    #[error("{}", DEPLOY_HASH_MISMATCH_MSG)]
+    DeployHashMismatch,
+
+    /// The body hash in the deploy's header does not match its payment and session code.
+    #[error("{}", DEPLOY_BODY_HASH_MISMATCH_MSG)]
+    BodyHashMismatch,
+
+    /// The deploy is hashed in a different layout than the one required at its timestamp.
+    #[error("deploy hash not in the layout required at the deploy's timestamp")]
+    HashLayoutMismatch,
+
+    /// The deploy's timestamp is further ahead of the current time than tolerated.
+    #[error("timestamp {timestamp} is more than {max_future_skew} ms ahead of now ({now})")]
+    TimestampInFuture {
+        /// The deploy's timestamp.
+        timestamp: Timestamp,
+        /// The tolerated difference to the current time.
+        max_future_skew: TimeDiff,
+        /// The current time.
+        now: Timestamp,
+    },
+
+    /// The deploy has expired.
+    #[error("expired at {expires}, before now ({now})")]
+    Expired {
+        /// The time the deploy expired at.
+        expires: Timestamp,
+        /// The current time.
+        now: Timestamp,
+    },
 }
 
 impl From<FromHexError> for Error {
@@ -208,10 +259,76 @@ impl Display for Approval {
     }
 }
 
+/// A value derived from a deploy, computed on first use and cached from then on.
+///
+/// The cache is an implementation detail: it compares equal to, and hashes the same as, any other
+/// cache, so that it doesn't affect the comparison of deploys.
+#[derive(Clone, Debug)]
+struct Cache<T>(OnceCell<T>);
+
+impl<T> Cache<T> {
+    /// Returns a cache already holding `value`.
+    fn with(value: T) -> Self {
+        Cache(OnceCell::from(value))
+    }
+}
+
+impl<T> Default for Cache<T> {
+    fn default() -> Self {
+        Cache(OnceCell::new())
+    }
+}
+
+impl<T> PartialEq for Cache<T> {
+    fn eq(&self, _other: &Self) -> bool {
+        true
+    }
+}
+
+impl<T> Eq for Cache<T> {}
+
+impl<T> PartialOrd for Cache<T> {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl<T> Ord for Cache<T> {
+    fn cmp(&self, _other: &Self) -> Ordering {
+        Ordering::Equal
+    }
+}
+
+impl<T> Hash for Cache<T> {
+    fn hash<H: Hasher>(&self, _state: &mut H) {}
+}
+
+#[cfg(test)]
+thread_local! {
+    /// The number of deploy hashes computed on this thread.
+    static HASH_COMPUTATIONS: std::cell::Cell<usize> = std::cell::Cell::new(0);
+}
+
+/// Computes the hash of a deploy with the given header in the given layout.
+fn compute_hash(header: &DeployHeader, layout: Layout) -> DeployHash {
+    #[cfg(test)]
+    HASH_COMPUTATIONS.with(|count| count.set(count.get() + 1));
+    DeployHash::new(layout.header_hash(header))
+}
+
 /// A deploy; an item containing a smart contract along with the requester's signature(s).
 #[derive(Clone, Ord, PartialOrd, Eq, PartialEq, Hash, Debug)]
 pub struct Deploy {
     hash: DeployHash,
+    /// The hash computed from `header`, which is immutable once the deploy is constructed.
+    /// Approvals aren't covered by the hash, so signing the deploy doesn't invalidate it.
+    computed_hash: Cache<DeployHash>,
+    /// The layout the deploy's hashes are computed from.
+    layout: Layout,
+    /// The estimated gas the deploy will consume.
+    gas_estimate: Cache<u64>,
+    /// The size in bytes of the serialized deploy.
+    serialized_size: Cache<usize>,
     header: DeployHeader,
     payment: ExecutableDeployItem,
     session: ExecutableDeployItem,
@@ -232,9 +349,8 @@ impl Deploy {
         secret_key: &SecretKey,
         rng: &mut R,
     ) -> Deploy {
-        let serialized_body = serialize_body(&payment, &session)
-            .unwrap_or_else(|error| panic!("should serialize deploy body: {}", error));
-        let body_hash = hash::hash(&serialized_body);
+        let layout = Layout::CURRENT;
+        let body_hash = layout.body_hash(&payment, &session);
 
         let account = PublicKey::from(secret_key);
         // Remove duplicates.
@@ -248,12 +364,14 @@ impl Deploy {
             dependencies,
             chain_name,
         };
-        let serialized_header = serialize_header(&header)
-            .unwrap_or_else(|error| panic!("should serialize deploy header: {}", error));
-        let hash = DeployHash::new(hash::hash(&serialized_header));
+        let hash = compute_hash(&header, layout);
 
         let mut deploy = Deploy {
             hash,
+            computed_hash: Cache::with(hash),
+            layout,
+            gas_estimate: Cache::default(),
+            serialized_size: Cache::default(),
             header,
             payment,
             session,
@@ -267,9 +385,12 @@ impl Deploy {
     /// Adds a signature of this deploy's hash to its approvals.
     pub fn sign<R: Rng + CryptoRng + ?Sized>(&mut self, secret_key: &SecretKey, rng: &mut R) {
         let signer = PublicKey::from(secret_key);
-        let signature = asymmetric_key::sign(&self.hash, secret_key, &signer, rng);
+        let signature =
+            asymmetric_key::sign(&self.hash, secret_key, &signer, SigningPurpose::Deploy, rng);
         let approval = Approval { signer, signature };
         self.approvals.push(approval);
+        // The approvals are part of the serialized deploy.
+        self.serialized_size = Cache::default();
     }
 
     /// Returns the `DeployHash` identifying this `Deploy`.
@@ -277,6 +398,104 @@ impl Deploy {
         &self.hash
     }
 
+    /// Returns the hash computed from the header of this `Deploy`.
+    ///
+    /// The hash is computed on the first call only; subsequent calls return the cached value.
+    /// Unlike the `DeployHash` returned by `id()`, which may have been provided by the deploy's
+    /// sender, this is guaranteed to match the deploy's contents.
+    pub fn hash(&self) -> &DeployHash {
+        self.computed_hash
+            .0
+            .get_or_init(|| compute_hash(&self.header, self.layout))
+    }
+
+    /// Checks that the deploy's hashes match its contents and that all of its approvals are valid.
+    ///
+    /// Each approval is verified according to the signature scheme of its signer's key, which is
+    /// detected from the key's tag.
+    pub fn validate(&self) -> Result<(), Error> {
+        if self.hash() != self.id() {
+            return Err(Error::DeployHashMismatch);
+        }
+        let body_hash = self.layout.body_hash(&self.payment, &self.session);
+        if body_hash != self.header.body_hash {
+            return Err(Error::BodyHashMismatch);
+        }
+        self.validate_approvals()
+    }
+
+    /// Checks that the deploy's hashes are computed in the layout required at its timestamp, on a
+    /// chain which introduced the canonical layout at `canonical_hash_timestamp`.
+    ///
+    /// Without this check, the same deploy could be accepted under both its legacy and its
+    /// canonical hash.
+    pub fn validate_hash_layout(&self, canonical_hash_timestamp: Timestamp) -> Result<(), Error> {
+        if self.layout != Layout::required(self.header.timestamp(), canonical_hash_timestamp) {
+            return Err(Error::HashLayoutMismatch);
+        }
+        Ok(())
+    }
+
+    /// Checks that the deploy's timestamp is at most `max_future_skew` ahead of `now`, and that the
+    /// deploy hasn't expired yet.
+    ///
+    /// Unlike `validate`, this depends on the time, so it only applies to deploys yet to be
+    /// included in a block, not to those which are stored or fetched as part of a block.
+    pub fn validate_timestamp(
+        &self,
+        now: Timestamp,
+        max_future_skew: TimeDiff,
+    ) -> Result<(), Error> {
+        let timestamp = self.header.timestamp();
+        if timestamp > now + max_future_skew {
+            return Err(Error::TimestampInFuture {
+                timestamp,
+                max_future_skew,
+                now,
+            });
+        }
+        let expires = self.header.expires();
+        if now > expires {
+            return Err(Error::Expired { expires, now });
+        }
+        Ok(())
+    }
+
+    /// Verifies all approvals of this `Deploy`.
+    ///
+    /// Successful verifications are remembered by the verification cache shared by the whole
+    /// node, keyed by the deploy hash, signature and signer, so approvals which have been verified
+    /// before aren't verified again when the same deploy is gossiped, fetched or stored.
+    fn validate_approvals(&self) -> Result<(), Error> {
+        for (index, approval) in self.approvals.iter().enumerate() {
+            let signer_scheme = approval.signer.scheme();
+            let signature_scheme = approval.signature.scheme();
+            if signer_scheme != signature_scheme {
+                return Err(Error::SignatureSchemeMismatch {
+                    index,
+                    signer_scheme,
+                    signature_scheme,
+                });
+            }
+            asymmetric_key::verify(
+                &self.hash,
+                &approval.signature,
+                &approval.signer,
+                SigningPurpose::Deploy,
+            )
+            .map_err(|error| Error::FailedVerification { index, error })?;
+        }
+        Ok(())
+    }
+
+    /// Returns the estimated gas this `Deploy` will consume.
+    ///
+    /// The estimate is computed via `estimate` on the first call only; subsequent calls return the
+    /// cached value.
+    pub(crate) fn gas_estimate<F: FnOnce(&Deploy) -> u64>(&self, estimate: F) -> u64 {
+        *self.gas_estimate.0.get_or_init(|| estimate(self))
+    }
+
     /// Returns a reference to the `DeployHeader` of this `Deploy`.
     pub fn header(&self) -> &DeployHeader {
         &self.header
@@ -287,6 +506,17 @@ impl Deploy {
         self.header
     }
 
+    /// Returns the size in bytes of the serialized `Deploy`.
+    ///
+    /// The deploy is serialized on the first successful call only; subsequent calls return the
+    /// cached size.
+    pub fn serialized_size(&self) -> Result<usize, rmp_serde::encode::Error> {
+        self.serialized_size
+            .0
+            .get_or_try_init(|| rmp_serde::to_vec(self).map(|serialized| serialized.len()))
+            .map(|size| *size)
+    }
+
     /// Convert the `Deploy` to a JSON value.
     pub fn to_json(&self) -> JsonValue {
         let json_deploy = json::JsonDeploy::from(self);
@@ -297,13 +527,7 @@ impl Deploy {
     pub fn from_json(input: JsonValue) -> Result<Self, Error> {
         let json: json::JsonDeploy = serde_json::from_value(input)?;
         let deploy = Deploy::try_from(json)?;
-
-        // Serialize and deserialize to run validity checks in deserialization.
-        let serialized =
-            rmp_serde::to_vec(&deploy).map_err(|error| Error::DecodeFromJson(Box::new(error)))?;
-        let _: Deploy = rmp_serde::from_read_ref(&serialized)
-            .map_err(|error| Error::DecodeFromJson(Box::new(error)))?;
-
+        deploy.validate()?;
         Ok(deploy)
     }
 
@@ -401,22 +625,27 @@ impl<'de> Deserialize<'de> for Deploy {
         let (bridging_deploy, approvals) =
             <(BridgingDeploy, Vec<Approval>)>::deserialize(deserializer)?;
 
-        let actual_deploy_hash = hash::hash(&bridging_deploy.serialized_header);
-        if actual_deploy_hash.as_ref() != bridging_deploy.deploy_hash {
-            warn!(
-                ?actual_deploy_hash,
-                ?bridging_deploy.deploy_hash,
-                "{}: {}",
-                DESER_ERROR_MSG_GENERAL,
-                DEPLOY_HASH_MISMATCH_MSG
-            );
-            return Err(serde::de::Error::custom(DEPLOY_HASH_MISMATCH_MSG));
-        }
-
         let header = deserialize_header(&bridging_deploy.serialized_header)
             .map_err(serde::de::Error::custom)?;
 
-        let actual_body_hash = hash::hash(&bridging_deploy.serialized_body);
+        let hash = Digest::try_from(bridging_deploy.deploy_hash)
+            .map(DeployHash::new)
+            .map_err(serde::de::Error::custom)?;
+        let layout = match Layout::detect(&header, &hash) {
+            Some(layout) => layout,
+            None => {
+                warn!(
+                    ?hash,
+                    "{}: {}", DESER_ERROR_MSG_GENERAL, DEPLOY_HASH_MISMATCH_MSG
+                );
+                return Err(serde::de::Error::custom(DEPLOY_HASH_MISMATCH_MSG));
+            }
+        };
+
+        let (payment, session) =
+            deserialize_body(&bridging_deploy.serialized_body).map_err(serde::de::Error::custom)?;
+
+        let actual_body_hash = layout.body_hash(&payment, &session);
         if actual_body_hash != header.body_hash {
             warn!(
                 ?actual_body_hash,
@@ -428,27 +657,22 @@ impl<'de> Deserialize<'de> for Deploy {
             return Err(serde::de::Error::custom(DEPLOY_BODY_HASH_MISMATCH_MSG));
         }
 
-        let (payment, session) =
-            deserialize_body(&bridging_deploy.serialized_body).map_err(serde::de::Error::custom)?;
-
         let deploy = Deploy {
-            hash: DeployHash::from(
-                Digest::try_from(bridging_deploy.deploy_hash).map_err(serde::de::Error::custom)?,
-            ),
+            hash,
+            // The hash has just been verified, so there's no need to compute it again later.
+            computed_hash: Cache::with(hash),
+            layout,
+            gas_estimate: Cache::default(),
+            serialized_size: Cache::default(),
             header,
             payment,
             session,
             approvals,
         };
 
-        for (index, approval) in deploy.approvals.iter().enumerate() {
-            if let Err(error) =
-                asymmetric_key::verify(&deploy.hash, &approval.signature, &approval.signer)
-            {
-                let error_msg = format!("failed to verify approval {}", index);
-                warn!("{}: {}: {}", DESER_ERROR_MSG_GENERAL, error_msg, error);
-                return Err(serde::de::Error::custom(error_msg));
-            }
+        if let Err(error) = deploy.validate_approvals() {
+            warn!("{}: {}", DESER_ERROR_MSG_GENERAL, error);
+            return Err(serde::de::Error::custom(error));
         }
 
         Ok(deploy)
@@ -471,6 +695,10 @@ impl Value for Deploy {
     fn take_header(self) -> Self::Header {
         self.take_header()
     }
+
+    fn is_intact(&self) -> bool {
+        self.validate().is_ok()
+    }
 }
 
 impl Item for Deploy {
@@ -819,9 +1047,17 @@ mod json {
                 let approval = Approval::try_from(json_approval)?;
                 approvals.push(approval);
             }
+            let hash = deploy.hash.try_into()?;
+            let header = deploy.header.try_into()?;
+            // A deploy whose hash matches no layout fails validation in either layout.
+            let layout = Layout::detect(&header, &hash).unwrap_or(Layout::CURRENT);
             Ok(Deploy {
-                hash: deploy.hash.try_into()?,
-                header: deploy.header.try_into()?,
+                hash,
+                computed_hash: Cache::default(),
+                layout,
+                gas_estimate: Cache::default(),
+                serialized_size: Cache::default(),
+                header,
                 payment: deploy.payment.try_into()?,
                 session: deploy.session.try_into()?,
                 approvals,
@@ -855,4 +1091,182 @@ mod tests {
         let deserialized = rmp_serde::from_read_ref(&serialized).unwrap();
         assert_eq!(deploy, deserialized);
     }
+
+    #[test]
+    #[allow(clippy::redundant_clone)] // Clones should keep the computed hash.
+    fn should_compute_hash_once() {
+        let mut rng = TestRng::new();
+        let deploy = Deploy::random(&mut rng);
+        let hash_computations = || HASH_COMPUTATIONS.with(|count| count.get());
+
+        // Decoding from JSON computes the hash once to validate the deploy.
+        let before = hash_computations();
+        let decoded = Deploy::from_json(deploy.to_json()).unwrap();
+        assert_eq!(hash_computations(), before + 1);
+        assert_eq!(decoded.clone().hash(), deploy.id());
+        assert_eq!(decoded.hash(), deploy.id());
+        assert_eq!(hash_computations(), before + 1);
+
+        // Deserialized deploys reuse the hash verified during deserialization.
+        let serialized = rmp_serde::to_vec(&deploy).unwrap();
+        let deserialized: Deploy = rmp_serde::from_read_ref(&serialized).unwrap();
+        assert_eq!(deserialized.hash(), deploy.id());
+        assert_eq!(hash_computations(), before + 1);
+    }
+
+    #[test]
+    fn should_verify_approvals_once() {
+        let mut rng = TestRng::new();
+        asymmetric_key::set_verification_cache_capacity(1_000);
+        let deploy = Deploy::random(&mut rng);
+        let approval_verifications = asymmetric_key::uncached_verifications;
+
+        // Deserializing verifies the approvals, validating, storing or receiving the deploy again
+        // later doesn't.
+        let before = approval_verifications();
+        let serialized = rmp_serde::to_vec(&deploy).unwrap();
+        let deserialized: Deploy = rmp_serde::from_read_ref(&serialized).unwrap();
+        assert_eq!(approval_verifications(), before + 1);
+        deserialized.validate().expect("should validate");
+        assert!(deserialized.is_intact());
+        let received_again: Deploy = rmp_serde::from_read_ref(&serialized).unwrap();
+        received_again.validate().expect("should validate");
+        assert_eq!(approval_verifications(), before + 1);
+
+        // A new approval has to be verified, but only once.
+        let mut signed = deserialized.clone();
+        signed.sign(&SecretKey::random(&mut rng), &mut rng);
+        signed.validate().expect("should validate");
+        signed.validate().expect("should validate");
+        assert_eq!(approval_verifications(), before + 2);
+
+        // Tampering with an approval is detected despite the earlier verification, and invalid
+        // approvals are verified again every time.
+        let mut tampered = deserialized;
+        let other_key = match tampered.approvals[0].signer {
+            PublicKey::Ed25519(_) => SecretKey::random_ed25519(&mut rng),
+            PublicKey::Secp256k1(_) => SecretKey::random_secp256k1(&mut rng),
+        };
+        tampered.approvals[0].signature = asymmetric_key::sign(
+            &tampered.hash,
+            &other_key,
+            &PublicKey::from(&other_key),
+            SigningPurpose::Deploy,
+            &mut rng,
+        );
+        assert!(tampered.validate().is_err());
+        assert!(tampered.validate().is_err());
+        assert_eq!(approval_verifications(), before + 4);
+    }
+
+    #[test]
+    fn should_detect_signature_scheme_of_submitted_deploys() {
+        let mut rng = TestRng::new();
+        let deploy_signed_by = |secret_key: SecretKey, rng: &mut TestRng| {
+            let module_bytes = ExecutableDeployItem::ModuleBytes {
+                module_bytes: vec![],
+                args: vec![],
+            };
+            Deploy::new(
+                Timestamp::now(),
+                TimeDiff::from(60_000),
+                1,
+                vec![],
+                String::from("casper-example"),
+                module_bytes.clone(),
+                module_bytes,
+                &secret_key,
+                rng,
+            )
+        };
+
+        // Valid deploys of either scheme are accepted.
+        for secret_key in vec![
+            SecretKey::random_ed25519(&mut rng),
+            SecretKey::random_secp256k1(&mut rng),
+        ] {
+            let scheme = PublicKey::from(&secret_key).scheme();
+            let deploy = deploy_signed_by(secret_key, &mut rng);
+            let decoded = Deploy::from_json(deploy.to_json())
+                .unwrap_or_else(|error| panic!("{} deploy should be valid: {}", scheme, error));
+            assert_eq!(decoded.header().account().scheme(), scheme);
+            decoded.validate().expect("should validate");
+        }
+
+        // An account key tagged with an unknown scheme is refused.
+        let deploy = deploy_signed_by(SecretKey::random_ed25519(&mut rng), &mut rng);
+        let mut json = deploy.to_json();
+        let account = json["header"]["account"].as_str().unwrap().to_string();
+        json["header"]["account"] = JsonValue::String(format!("03{}", &account[2..]));
+        let error = Deploy::from_json(json).expect_err("unknown scheme should be refused");
+        assert!(
+            error
+                .to_string()
+                .contains("unsupported signature scheme with tag 3"),
+            "unexpected error: {}",
+            error
+        );
+
+        // A signature of a different scheme than the signer's key is detected.
+        let mut deploy = deploy_signed_by(SecretKey::random_ed25519(&mut rng), &mut rng);
+        let other = deploy_signed_by(SecretKey::random_secp256k1(&mut rng), &mut rng);
+        deploy.approvals[0].signature = other.approvals[0].signature;
+        assert!(matches!(
+            deploy.validate(),
+            Err(Error::SignatureSchemeMismatch {
+                index: 0,
+                signer_scheme: SignatureScheme::Ed25519,
+                signature_scheme: SignatureScheme::Secp256k1,
+            })
+        ));
+    }
+
+    #[test]
+    fn should_validate_timestamp_against_current_time() {
+        let mut rng = TestRng::new();
+        let now = Timestamp::from(1_600_000_000_000);
+        let max_future_skew = TimeDiff::from(10_000);
+        let deploy_at = |timestamp: Timestamp, rng: &mut TestRng| {
+            let module_bytes = ExecutableDeployItem::ModuleBytes {
+                module_bytes: vec![],
+                args: vec![],
+            };
+            Deploy::new(
+                timestamp,
+                TimeDiff::from(60_000),
+                1,
+                vec![],
+                String::from("casper-example"),
+                module_bytes.clone(),
+                module_bytes,
+                &SecretKey::random(rng),
+                rng,
+            )
+        };
+
+        // A recent deploy which hasn't expired yet is accepted, as is one slightly ahead of the
+        // current time.
+        let recent = deploy_at(now - TimeDiff::from(30_000), &mut rng);
+        recent
+            .validate_timestamp(now, max_future_skew)
+            .expect("recent deploy should be valid");
+        let slightly_ahead = deploy_at(now + TimeDiff::from(5_000), &mut rng);
+        slightly_ahead
+            .validate_timestamp(now, max_future_skew)
+            .expect("deploy within the tolerance should be valid");
+
+        // A deploy too far in the future is rejected.
+        let future = deploy_at(now + TimeDiff::from(20_000), &mut rng);
+        assert!(matches!(
+            future.validate_timestamp(now, max_future_skew),
+            Err(Error::TimestampInFuture { .. })
+        ));
+
+        // So is an expired one.
+        let expired = deploy_at(now - TimeDiff::from(120_000), &mut rng);
+        assert!(matches!(
+            expired.validate_timestamp(now, max_future_skew),
+            Err(Error::Expired { .. })
+        ));
+    }
 }
@@ -0,0 +1,368 @@
+//! The canonical serialization of deploys, from which their hashes are computed.
+//!
+//! A deploy's hash identifies it across the whole network and is embedded in blocks, so it must
+//! never change.  It is therefore computed from an explicit byte layout independent of the
+//! serialization used on the wire or in storage, which may change whenever the types involved are
+//! refactored.  Every layout is prefixed with its version, and changing a layout requires a new
+//! version.
+//!
+//! Following the conventions of `casper_types::bytesrepr`, integers are little-endian, and strings
+//! and byte sequences are prefixed with their length as a `u32`.  An `Option` is a `0` tag if
+//! `None`, or a `1` tag followed by the value.
+//!
+//! Version 1 of the header layout is:
+//!
+//! * the version `1`,
+//! * the account key's scheme tag, `0` for Ed25519 or `1` for secp256k1, followed by the raw key,
+//! * the timestamp and time to live in milliseconds, and the gas price, each as a `u64`,
+//! * the 32-byte body hash,
+//! * the number of dependencies as a `u32`, followed by each 32-byte dependency hash,
+//! * the chain name.
+//!
+//! Version 1 of the body layout is the version `1`, followed by the payment and the session code.
+//! Each is tagged with its kind, from `0` for module bytes to `5` for a transfer in the order of
+//! the variants of `ExecutableDeployItem`, followed by the fields of the variant in order.
+//!
+//! Deploys created before the canonical layout was introduced were hashed from the MessagePack
+//! serialization of their header and body, which is also what is sent on the wire and stored.
+//! Those deploys keep their hashes in the legacy layout: when a deploy is received, its layout is
+//! detected from the hash it comes with, and all of its hashes are computed in that layout.  The
+//! stored bytes of deploys are unchanged, so existing stores remain valid without a migration.
+//!
+//! So that a deploy has exactly one valid hash, the chainspec's `canonical_hash_timestamp`
+//! determines which layout a deploy must use: deploys with an earlier timestamp must be hashed in
+//! the legacy layout, all others in the canonical layout.  Deploys hashed in the wrong layout are
+//! not accepted.
+
+use std::convert::TryFrom;
+
+use casper_execution_engine::core::engine_state::executable_deploy_item::ExecutableDeployItem;
+
+use super::{DeployHash, DeployHeader};
+use crate::{
+    crypto::{
+        asymmetric_key::PublicKey,
+        hash::{self, Digest},
+    },
+    types::Timestamp,
+};
+
+/// The version of the canonical layout of deploy headers.
+const HEADER_VERSION: u8 = 1;
+
+/// The version of the canonical layout of deploy bodies.
+const BODY_VERSION: u8 = 1;
+
+/// The layout a deploy's hashes are computed from.
+#[derive(Clone, Copy, Ord, PartialOrd, Eq, PartialEq, Hash, Debug)]
+pub(super) enum Layout {
+    /// The MessagePack serialization of deploys created before the canonical layout existed.
+    Legacy,
+    /// Version 1 of the canonical layout.
+    V1,
+}
+
+impl Layout {
+    /// The layout of newly created deploys.
+    pub(super) const CURRENT: Layout = Layout::V1;
+
+    /// Returns the layout a deploy with the given `timestamp` must be hashed in, on a chain which
+    /// introduced the canonical layout at `canonical_hash_timestamp`.
+    pub(super) fn required(timestamp: Timestamp, canonical_hash_timestamp: Timestamp) -> Layout {
+        if timestamp < canonical_hash_timestamp {
+            Layout::Legacy
+        } else {
+            Layout::CURRENT
+        }
+    }
+
+    /// Returns the layout in which `header` hashes to `deploy_hash`, if any.
+    pub(super) fn detect(header: &DeployHeader, deploy_hash: &DeployHash) -> Option<Layout> {
+        [Layout::CURRENT, Layout::Legacy]
+            .iter()
+            .copied()
+            .find(|layout| layout.header_hash(header) == *deploy_hash.inner())
+    }
+
+    /// Returns the hash of `header` in this layout, i.e. the deploy hash.
+    pub(super) fn header_hash(self, header: &DeployHeader) -> Digest {
+        match self {
+            Layout::Legacy => {
+                hash::hash(rmp_serde::to_vec(header).expect("should serialize deploy header"))
+            }
+            Layout::V1 => hash::hash(header_bytes(header)),
+        }
+    }
+
+    /// Returns the hash of the deploy body `payment` and `session` in this layout.
+    pub(super) fn body_hash(
+        self,
+        payment: &ExecutableDeployItem,
+        session: &ExecutableDeployItem,
+    ) -> Digest {
+        match self {
+            Layout::Legacy => hash::hash(
+                rmp_serde::to_vec(&(payment, session)).expect("should serialize deploy body"),
+            ),
+            Layout::V1 => hash::hash(body_bytes(payment, session)),
+        }
+    }
+}
+
+/// Returns the canonical serialization of `header`, from which the deploy hash is computed.
+fn header_bytes(header: &DeployHeader) -> Vec<u8> {
+    let mut encoder = Encoder(vec![HEADER_VERSION]);
+    match &header.account {
+        PublicKey::Ed25519(_) => encoder.u8(0),
+        PublicKey::Secp256k1(_) => encoder.u8(1),
+    }
+    encoder.raw(header.account.as_ref());
+    encoder.u64(header.timestamp.millis());
+    encoder.u64(header.ttl.millis());
+    encoder.u64(header.gas_price);
+    encoder.raw(header.body_hash.as_ref());
+    encoder.len(header.dependencies.len());
+    for dependency in &header.dependencies {
+        encoder.raw(dependency.as_ref());
+    }
+    encoder.bytes(header.chain_name.as_bytes());
+    encoder.0
+}
+
+/// Returns the canonical serialization of the deploy body `payment` and `session`, from which the
+/// body hash is computed.
+fn body_bytes(payment: &ExecutableDeployItem, session: &ExecutableDeployItem) -> Vec<u8> {
+    let mut encoder = Encoder(vec![BODY_VERSION]);
+    encoder.executable_deploy_item(payment);
+    encoder.executable_deploy_item(session);
+    encoder.0
+}
+
+/// Appends values to a canonical serialization.
+struct Encoder(Vec<u8>);
+
+impl Encoder {
+    fn u8(&mut self, value: u8) {
+        self.0.push(value);
+    }
+
+    fn u32(&mut self, value: u32) {
+        self.0.extend_from_slice(&value.to_le_bytes());
+    }
+
+    fn u64(&mut self, value: u64) {
+        self.0.extend_from_slice(&value.to_le_bytes());
+    }
+
+    fn len(&mut self, len: usize) {
+        // Deploys are far smaller than 4 GiB, so their lengths always fit.
+        self.u32(u32::try_from(len).expect("length should fit into a u32"));
+    }
+
+    /// Appends `bytes` without a length prefix, for values of a fixed length.
+    fn raw(&mut self, bytes: &[u8]) {
+        self.0.extend_from_slice(bytes);
+    }
+
+    fn bytes(&mut self, bytes: &[u8]) {
+        self.len(bytes.len());
+        self.raw(bytes);
+    }
+
+    fn optional_u32(&mut self, value: Option<u32>) {
+        match value {
+            None => self.u8(0),
+            Some(value) => {
+                self.u8(1);
+                self.u32(value);
+            }
+        }
+    }
+
+    fn executable_deploy_item(&mut self, item: &ExecutableDeployItem) {
+        match item {
+            ExecutableDeployItem::ModuleBytes { module_bytes, args } => {
+                self.u8(0);
+                self.bytes(module_bytes);
+                self.bytes(args);
+            }
+            ExecutableDeployItem::StoredContractByHash {
+                hash,
+                entry_point,
+                args,
+            } => {
+                self.u8(1);
+                self.raw(hash);
+                self.bytes(entry_point.as_bytes());
+                self.bytes(args);
+            }
+            ExecutableDeployItem::StoredContractByName {
+                name,
+                entry_point,
+                args,
+            } => {
+                self.u8(2);
+                self.bytes(name.as_bytes());
+                self.bytes(entry_point.as_bytes());
+                self.bytes(args);
+            }
+            ExecutableDeployItem::StoredVersionedContractByName {
+                name,
+                version,
+                entry_point,
+                args,
+            } => {
+                self.u8(3);
+                self.bytes(name.as_bytes());
+                self.optional_u32(*version);
+                self.bytes(entry_point.as_bytes());
+                self.bytes(args);
+            }
+            ExecutableDeployItem::StoredVersionedContractByHash {
+                hash,
+                version,
+                entry_point,
+                args,
+            } => {
+                self.u8(4);
+                self.raw(hash);
+                self.optional_u32(*version);
+                self.bytes(entry_point.as_bytes());
+                self.bytes(args);
+            }
+            ExecutableDeployItem::Transfer { args } => {
+                self.u8(5);
+                self.bytes(args);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        crypto::{asymmetric_key::SecretKey, hash},
+        testing::TestRng,
+        types::{Deploy, DeployError, DeployHash, TimeDiff},
+    };
+
+    /// The secret key of the first test vector of RFC 8032.
+    const SECRET_KEY: &str = "9d61b19deffd5a60ba844af492ec2cc44449c5697b326919703bac031cae7f60";
+
+    const BODY_BYTES: &str = "0100040000000061736d03000000010203030600000066617563657401020000000\
+                              400000063616c6c00000000";
+    const BODY_HASH: &str = "6f3eae194a2349e9e95b8ce16a76e47af603a7acfefe49580f4b7b4bb364f63a";
+    const HEADER_BYTES: &str = "0100d75a980182b10ab7d54bfed3c964073a0ee172f3daa62325af021a68f7075\
+                                11a00806e877401000080ee3600000000000a000000000000006f3eae194a2349e\
+                                9e95b8ce16a76e47af603a7acfefe49580f4b7b4bb364f63a01000000c6144779c\
+                                e9fd6823fbf405545a19a1b543a6e4dd1b30e06aefa6140120cdb160e000000636\
+                                1737065722d6578616d706c65";
+    const DEPLOY_HASH: &str = "f234847665ed4321bad444dfa43ce2382cc88c428de0cca6be5734c739b0a396";
+
+    /// The contents of the known deploy, without approvals, as serialized by the last version
+    /// hashing deploys in the legacy layout.
+    const LEGACY_DEPLOY: &str =
+        "9293c420a477ba059b4f3385bae65366b63e87ec8ee456b8810db34e82ec00e36f\
+                                 df9bbec4ab978100c420d75a980182b10ab7d54bfed3c964073a0ee172f3daa623\
+                                 25af021a68f707511acf00000174876e8000ce0036ee800adc002041ccf5ccbdcc\
+                                 f019ccf5cc9bccfe68ccab39cc8b684dccbc6ccc8eccb8ccdecc9bcc90cc9d3b43\
+                                 51cc9dcccd4ccce9ccab33cccb91dc0020ccc6144779cccecc9fccd6cc823fccbf\
+                                 405545cca1cc9a1b543a6e4dccd1ccb30e06ccaeccfa6140120cccdb16ae636173\
+                                 7065722d6578616d706c65c42192810092c4040061736dc403010203810394a666\
+                                 617563657402a463616c6cc40090";
+    const LEGACY_DEPLOY_HASH: &str =
+        "a477ba059b4f3385bae65366b63e87ec8ee456b8810db34e82ec00e36fdf9bbe";
+
+    fn known_deploy() -> Deploy {
+        let mut secret_key = [0; SecretKey::ED25519_LENGTH];
+        hex::decode_to_slice(SECRET_KEY, &mut secret_key).unwrap();
+        Deploy::new(
+            Timestamp::from(1_600_000_000_000),
+            TimeDiff::from(3_600_000),
+            10,
+            vec![DeployHash::new(hash::hash(b"dependency"))],
+            String::from("casper-example"),
+            ExecutableDeployItem::ModuleBytes {
+                module_bytes: b"\0asm".to_vec(),
+                args: vec![1, 2, 3],
+            },
+            ExecutableDeployItem::StoredVersionedContractByName {
+                name: String::from("faucet"),
+                version: Some(2),
+                entry_point: String::from("call"),
+                args: vec![],
+            },
+            &SecretKey::new_ed25519(secret_key),
+            &mut TestRng::new(),
+        )
+    }
+
+    #[test]
+    fn should_produce_pinned_body_bytes_and_hash() {
+        let deploy = known_deploy();
+        let body_bytes = body_bytes(deploy.payment(), deploy.session());
+        assert_eq!(hex::encode(&body_bytes), BODY_BYTES);
+        assert_eq!(hex::encode(hash::hash(&body_bytes)), BODY_HASH);
+        assert_eq!(hex::encode(deploy.header().body_hash()), BODY_HASH);
+    }
+
+    #[test]
+    fn should_produce_pinned_header_bytes_and_hash() {
+        let deploy = known_deploy();
+        let header_bytes = header_bytes(deploy.header());
+        assert_eq!(hex::encode(&header_bytes), HEADER_BYTES);
+        assert_eq!(hex::encode(hash::hash(&header_bytes)), DEPLOY_HASH);
+        assert_eq!(hex::encode(deploy.id().inner()), DEPLOY_HASH);
+
+        // The hash is independent of the wire format, and survives a roundtrip through it.
+        let serialized = rmp_serde::to_vec(&deploy).unwrap();
+        let deserialized: Deploy = rmp_serde::from_read_ref(&serialized).unwrap();
+        assert_eq!(hex::encode(deserialized.id().inner()), DEPLOY_HASH);
+        let decoded = Deploy::from_json(deploy.to_json()).unwrap();
+        assert_eq!(hex::encode(decoded.hash().inner()), DEPLOY_HASH);
+    }
+
+    #[test]
+    fn should_keep_pinned_hash_of_legacy_deploy() {
+        let serialized = hex::decode(LEGACY_DEPLOY).unwrap();
+        let deploy: Deploy = rmp_serde::from_read_ref(&serialized).unwrap();
+        assert_eq!(hex::encode(deploy.id().inner()), LEGACY_DEPLOY_HASH);
+        assert_eq!(hex::encode(deploy.hash().inner()), LEGACY_DEPLOY_HASH);
+        deploy.validate().unwrap();
+
+        // It is the known deploy, hashed in the legacy layout.
+        let known_deploy = known_deploy();
+        assert_eq!(deploy.payment(), known_deploy.payment());
+        assert_eq!(deploy.session(), known_deploy.session());
+        assert_ne!(deploy.id(), known_deploy.id());
+
+        // It is stored and sent unchanged, and keeps its hash when converted to JSON and back.
+        assert_eq!(rmp_serde::to_vec(&deploy).unwrap(), serialized);
+        let decoded = Deploy::from_json(deploy.to_json()).unwrap();
+        assert_eq!(hex::encode(decoded.hash().inner()), LEGACY_DEPLOY_HASH);
+    }
+
+    #[test]
+    fn should_require_layout_by_timestamp() {
+        let legacy_deploy: Deploy =
+            rmp_serde::from_read_ref(&hex::decode(LEGACY_DEPLOY).unwrap()).unwrap();
+        let known_deploy = known_deploy();
+        let timestamp = known_deploy.header().timestamp();
+
+        // From the activation on, only the canonical hash is valid.
+        known_deploy.validate_hash_layout(timestamp).unwrap();
+        assert!(matches!(
+            legacy_deploy.validate_hash_layout(timestamp),
+            Err(DeployError::HashLayoutMismatch)
+        ));
+
+        // Before the activation, only the legacy hash is valid.
+        let activation = timestamp + TimeDiff::from(1);
+        legacy_deploy.validate_hash_layout(activation).unwrap();
+        assert!(matches!(
+            known_deploy.validate_hash_layout(activation),
+            Err(DeployError::HashLayoutMismatch)
+        ));
+    }
+}
@@ -1,9 +1,21 @@
+use std::path::PathBuf;
+
 use serde::{Deserialize, Serialize};
 
 use crate::{utils::External, Chainspec};
 
 const DEFAULT_CHAINSPEC_CONFIG_PATH: &str = "chainspec.toml";
 const DEFAULT_BLOCK_MAX_DEPLOY_COUNT: u32 = 3;
+const DEFAULT_DEPLOY_REBROADCAST_THRESHOLD_SECS: u64 = 60;
+const DEFAULT_DEPLOY_MAX_REBROADCASTS: u32 = 3;
+const DEFAULT_DEPLOY_REBROADCAST_EXPIRY_MARGIN_SECS: u64 = 60;
+const DEFAULT_DEPLOY_BUFFER_MAX_COUNT: u32 = 10_000;
+const DEFAULT_DEPLOY_BUFFER_MAX_BYTES: u64 = 100 * 1024 * 1024;
+const DEFAULT_SIGNATURE_CACHE_CAPACITY: usize = 10_000;
+const DEFAULT_MAX_CONCURRENT_DEPLOY_VALIDATIONS: usize = 4;
+const DEFAULT_MAX_QUEUED_DEPLOY_VALIDATIONS: usize = 1_000;
+const DEFAULT_DEPLOY_MAX_FUTURE_SKEW_SECS: u64 = 60;
+const DEFAULT_DISPATCH_STALL_THRESHOLD_MILLIS: u64 = 500;
 
 /// Node configuration.
 #[derive(Debug, Deserialize, Serialize)]
@@ -14,6 +26,53 @@ pub struct NodeConfig {
     pub chainspec_config_path: External<Chainspec>,
     /// The maximum number of deploys permitted in a single block.
     pub block_max_deploy_count: u32,
+    /// Time in seconds after which a deploy not yet included in a block is gossiped again.
+    pub deploy_rebroadcast_threshold_secs: u64,
+    /// The maximum number of times a pending deploy is gossiped again.
+    pub deploy_max_rebroadcasts: u32,
+    /// Time in seconds before its expiry after which a pending deploy is no longer gossiped again,
+    /// as it is unlikely to still be included in a block.
+    pub deploy_rebroadcast_expiry_margin_secs: u64,
+    /// The maximum number of pending deploys held in the deploy buffer.
+    pub deploy_buffer_max_count: u32,
+    /// The maximum total serialized size in bytes of the pending deploys in the deploy buffer.
+    pub deploy_buffer_max_bytes: u64,
+    /// Path of the file in which the hashes of the deploys not yet finalized are persisted, so the
+    /// deploy buffer can be restored from storage on restart.
+    ///
+    /// If unset, the deploy buffer starts out empty after a restart.
+    pub deploy_buffer_persistence_path: Option<PathBuf>,
+    /// The maximum number of successful signature verifications remembered so that they are not
+    /// repeated.  If zero, every signature is verified every time.
+    pub signature_cache_capacity: usize,
+    /// The maximum number of deploys validated concurrently, off the reactor thread.  Further
+    /// deploys wait for a validation to finish.
+    pub max_concurrent_deploy_validations: usize,
+    /// The maximum number of deploys waiting for a validation to finish.  Further deploys are
+    /// dropped, and refused if they were submitted by a client.
+    pub max_queued_deploy_validations: usize,
+    /// Time in seconds a deploy's timestamp may be ahead of the current time, to allow for clock
+    /// differences.  Deploys further in the future are rejected.
+    pub deploy_max_future_skew_secs: u64,
+    /// Whether the dependencies of a deploy received from a peer are fetched from the peer as soon
+    /// as the deploy is stored, rather than when a block including them is validated.
+    pub prefetch_deploy_dependencies: bool,
+    /// Time in milliseconds after which dispatching a single event is logged as stalling the
+    /// reactor.  If zero, stalls are not detected.
+    pub dispatch_stall_threshold_millis: u64,
+    /// Whether the time each component spends handling an event is recorded in the metrics.  If
+    /// disabled, events are not timed at all.
+    pub event_metrics: bool,
+    /// The file every signature made and every secret key loaded is recorded to, if any.
+    ///
+    /// Entries identify the key by the hash of its public key, and never contain secret material.
+    /// A relative path is relative to the directory of the config file.
+    pub audit_log_path: Option<PathBuf>,
+    /// Whether to run as an observer, e.g. an archival node: The linear chain is synced and the
+    /// HTTP API is served, but consensus isn't participated in at all.
+    pub observer_mode: bool,
+    /// Whether an observer relays the consensus messages received from peers to its other peers.
+    pub relay_consensus_messages: bool,
     /// Hash used as a trust anchor when joining, if any.
     pub trusted_hash: Option<String>,
 }
@@ -23,6 +82,22 @@ impl Default for NodeConfig {
         NodeConfig {
             chainspec_config_path: External::path(DEFAULT_CHAINSPEC_CONFIG_PATH),
             block_max_deploy_count: DEFAULT_BLOCK_MAX_DEPLOY_COUNT,
+            deploy_rebroadcast_threshold_secs: DEFAULT_DEPLOY_REBROADCAST_THRESHOLD_SECS,
+            deploy_max_rebroadcasts: DEFAULT_DEPLOY_MAX_REBROADCASTS,
+            deploy_rebroadcast_expiry_margin_secs: DEFAULT_DEPLOY_REBROADCAST_EXPIRY_MARGIN_SECS,
+            deploy_buffer_max_count: DEFAULT_DEPLOY_BUFFER_MAX_COUNT,
+            deploy_buffer_max_bytes: DEFAULT_DEPLOY_BUFFER_MAX_BYTES,
+            deploy_buffer_persistence_path: None,
+            signature_cache_capacity: DEFAULT_SIGNATURE_CACHE_CAPACITY,
+            max_concurrent_deploy_validations: DEFAULT_MAX_CONCURRENT_DEPLOY_VALIDATIONS,
+            max_queued_deploy_validations: DEFAULT_MAX_QUEUED_DEPLOY_VALIDATIONS,
+            deploy_max_future_skew_secs: DEFAULT_DEPLOY_MAX_FUTURE_SKEW_SECS,
+            prefetch_deploy_dependencies: true,
+            dispatch_stall_threshold_millis: DEFAULT_DISPATCH_STALL_THRESHOLD_MILLIS,
+            event_metrics: true,
+            audit_log_path: None,
+            observer_mode: false,
+            relay_consensus_messages: false,
             trusted_hash: None,
         }
     }
@@ -0,0 +1,34 @@
+use std::fmt::{self, Display, Formatter};
+
+use serde::{Deserialize, Serialize};
+
+/// Why the node is shutting down.
+///
+/// The reason is logged on shutdown and, unless disabled, reported to peers in a goodbye message,
+/// so that the cause of a node leaving the network is recorded on both sides.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub enum ShutdownReason {
+    /// The operator requested the shutdown, e.g. by sending a signal.
+    OperatorSignal,
+    /// Consensus failed in a way the node can't recover from.
+    ConsensusFailure(String),
+    /// Storage failed in a way the node can't recover from, e.g. because the disk is full.
+    StorageFailure(String),
+    /// Any other unrecoverable error.
+    FatalError(String),
+}
+
+impl Display for ShutdownReason {
+    fn fmt(&self, formatter: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            ShutdownReason::OperatorSignal => write!(formatter, "operator signal"),
+            ShutdownReason::ConsensusFailure(error) => {
+                write!(formatter, "consensus failure: {}", error)
+            }
+            ShutdownReason::StorageFailure(error) => {
+                write!(formatter, "storage failure: {}", error)
+            }
+            ShutdownReason::FatalError(error) => write!(formatter, "fatal error: {}", error),
+        }
+    }
+}
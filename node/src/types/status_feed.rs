@@ -2,7 +2,7 @@ use std::{collections::HashMap, hash::Hash, net::SocketAddr};
 
 use serde::Serialize;
 
-use crate::types::Block;
+use crate::types::{Block, Timestamp};
 
 /// Data feed for client "info_get_status" endpoint.
 #[derive(Debug, Serialize)]
@@ -12,13 +12,24 @@ pub struct StatusFeed<I> {
     pub last_finalized_block: Option<Block>,
     /// The peer nodes which are connected to this node.
     pub peers: HashMap<I, SocketAddr>,
+    /// Whether the node is ready to serve chain queries.
+    pub is_ready: bool,
+    /// If consensus has stalled, when the last block was finalized before.
+    pub consensus_stalled_since: Option<Timestamp>,
 }
 
 impl<I> StatusFeed<I> {
-    pub(crate) fn new(last_finalized_block: Option<Block>, peers: HashMap<I, SocketAddr>) -> Self {
+    pub(crate) fn new(
+        last_finalized_block: Option<Block>,
+        peers: HashMap<I, SocketAddr>,
+        is_ready: bool,
+        consensus_stalled_since: Option<Timestamp>,
+    ) -> Self {
         StatusFeed {
             last_finalized_block,
             peers,
+            is_ready,
+            consensus_stalled_since,
         }
     }
 }
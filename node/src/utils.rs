@@ -2,28 +2,43 @@
 //! being factored out into standalone crates.
 
 mod external;
+mod hash_ring;
 pub mod milliseconds;
+mod rng_state;
 mod round_robin;
 
+#[cfg(test)]
+use std::cell::Cell;
 use std::{
     cell::RefCell,
+    cmp::Ordering,
     env::current_dir,
     fmt::{self, Display, Formatter},
     fs, io,
-    net::{IpAddr, SocketAddr, ToSocketAddrs},
+    net::{IpAddr, Ipv4Addr, SocketAddr, SocketAddrV6, ToSocketAddrs},
     path::{Path, PathBuf},
 };
 
 use lazy_static::lazy_static;
 use libc::{c_long, sysconf, _SC_PAGESIZE};
+use rand::Rng;
 use thiserror::Error;
+use tokio::task::{self, JoinError};
 use tracing::warn;
 
 #[cfg(test)]
 pub use external::RESOURCES_PATH;
 pub use external::{External, LoadError, Loadable};
+pub(crate) use hash_ring::HashRing;
+pub use rng_state::RngState;
 pub(crate) use round_robin::WeightedRoundRobin;
 
+#[cfg(test)]
+thread_local! {
+    /// Whether blocking operations are run inline on the current thread.
+    static RUN_BLOCKING_INLINE: Cell<bool> = Cell::new(false);
+}
+
 /// Sensible default for many if not all systems.
 const DEFAULT_PAGE_SIZE: usize = 4096;
 
@@ -46,26 +61,116 @@ pub fn format_address<I: Into<IpAddr>>(host: I, port: u16) -> String {
 }
 
 /// Parses a network address from a string, with DNS resolution.
+///
+/// IPv6 addresses must be enclosed in brackets to be followed by a port, e.g. `[::1]:34553`.  The
+/// resolved address is normalized, see `normalize_address`.
 pub fn resolve_address(addr: &str) -> io::Result<SocketAddr> {
-    addr.to_socket_addrs()?.next().ok_or_else(|| {
-        io::Error::new(
-            io::ErrorKind::Other,
-            format!("could not resolve `{}`", addr),
-        )
-    })
+    // Without brackets, the last group of an IPv6 address would be mistaken for the port.
+    if addr.parse::<IpAddr>().is_ok() {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            format!(
+                "missing port in `{}` (IPv6 addresses with a port must be bracketed, e.g. \
+                 `[::1]:34553`)",
+                addr
+            ),
+        ));
+    }
+    addr.to_socket_addrs()?
+        .next()
+        .map(normalize_address)
+        .ok_or_else(|| {
+            io::Error::new(
+                io::ErrorKind::Other,
+                format!("could not resolve `{}`", addr),
+            )
+        })
 }
 
-/// Resolves a hostname.
+/// Resolves a hostname, or parses an IP address, which may be bracketed if it is IPv6.
 pub fn resolve_ip(host: &str) -> io::Result<IpAddr> {
+    let unbracketed = host.trim_start_matches('[').trim_end_matches(']');
+    if let Ok(ip) = unbracketed.parse::<IpAddr>() {
+        return Ok(normalize_address(SocketAddr::new(ip, 0)).ip());
+    }
     Ok(resolve_address(format!("{}:0", host).as_str())?.ip())
 }
 
+/// Normalizes a network address, so that different forms of the same address compare equal.
+///
+/// IPv4-mapped IPv6 addresses, as reported by dual-stack sockets for IPv4 peers, are converted to
+/// plain IPv4 addresses.  The flow label of IPv6 addresses is cleared, while the scope ID is kept,
+/// as it is required to reach link-local addresses.
+pub fn normalize_address(address: SocketAddr) -> SocketAddr {
+    match address {
+        SocketAddr::V4(_) => address,
+        SocketAddr::V6(v6) => match v6.ip().segments() {
+            [0, 0, 0, 0, 0, 0xffff, high, low] => {
+                let ipv4 = Ipv4Addr::from((u32::from(high) << 16) | u32::from(low));
+                SocketAddr::new(IpAddr::V4(ipv4), v6.port())
+            }
+            _ => SocketAddrV6::new(*v6.ip(), v6.port(), 0, v6.scope_id()).into(),
+        },
+    }
+}
+
+/// Runs the blocking operation `f` on tokio's blocking thread pool.
+///
+/// In tests, `f` is run inline on the current thread instead once enabled via
+/// `set_run_blocking_inline`, so that no other threads take part in running a reactor.
+pub(crate) async fn spawn_blocking<F, T>(f: F) -> Result<T, JoinError>
+where
+    F: FnOnce() -> T + Send + 'static,
+    T: Send + 'static,
+{
+    #[cfg(test)]
+    {
+        if RUN_BLOCKING_INLINE.with(Cell::get) {
+            return Ok(f());
+        }
+    }
+    task::spawn_blocking(f).await
+}
+
+/// Sets whether blocking operations started via `spawn_blocking` on the current thread are run
+/// inline.
+#[cfg(test)]
+pub(crate) fn set_run_blocking_inline(inline: bool) {
+    RUN_BLOCKING_INLINE.with(|run_inline| run_inline.set(inline));
+}
+
 /// Moves a value to the heap and then forgets about, leaving only a static reference behind.
 #[inline]
 pub(crate) fn leak<T>(value: T) -> &'static T {
     Box::leak(Box::new(value))
 }
 
+/// Chooses up to `count` of `items` at random, without replacement, with the likelihood of an item
+/// being chosen proportional to its `weight`.
+///
+/// Weights must be positive and finite.  This uses the algorithm by Efraimidis and Spirakis: each
+/// item gets the random key `ln(u) / weight` for a uniformly random `u`, and the items with the
+/// largest keys are chosen.
+pub(crate) fn choose_weighted_multiple<T, I, F, R>(
+    rng: &mut R,
+    items: I,
+    count: usize,
+    weight: F,
+) -> Vec<T>
+where
+    I: IntoIterator<Item = T>,
+    F: Fn(&T) -> f64,
+    R: Rng + ?Sized,
+{
+    let mut keyed_items: Vec<(f64, T)> = items
+        .into_iter()
+        .map(|item| (rng.gen::<f64>().ln() / weight(&item), item))
+        .collect();
+    keyed_items.sort_by(|(key1, _), (key2, _)| key2.partial_cmp(key1).unwrap_or(Ordering::Equal));
+    keyed_items.truncate(count);
+    keyed_items.into_iter().map(|(_, item)| item).collect()
+}
+
 /// A display-helper that shows iterators display joined by ",".
 #[derive(Debug)]
 pub(crate) struct DisplayIter<T>(RefCell<Option<T>>);
@@ -0,0 +1,133 @@
+//! Consistent hashing.
+//!
+//! Nodes are placed on a ring at the hashes of a fixed number of virtual points each, and a key is
+//! the responsibility of the node owning the first point at or after the key's hash.  When a node
+//! joins or leaves, only keys between its points and their predecessors change hands; all other
+//! keys keep their responsible node.
+
+use std::collections::BTreeMap;
+
+use crate::crypto::hash::{self, Digest};
+
+/// A consistent-hashing ring of nodes.
+#[derive(Debug)]
+pub(crate) struct HashRing<I> {
+    /// The number of points each node is placed at.
+    virtual_nodes: u32,
+    /// The nodes, by the hashes of their points.
+    points: BTreeMap<Digest, I>,
+}
+
+impl<I: AsRef<[u8]> + Copy + Eq> HashRing<I> {
+    /// Creates an empty ring, placing each node at `virtual_nodes` points.
+    ///
+    /// More points spread the keys more evenly among the nodes.
+    pub(crate) fn new(virtual_nodes: u32) -> Self {
+        HashRing {
+            virtual_nodes: virtual_nodes.max(1),
+            points: BTreeMap::new(),
+        }
+    }
+
+    /// Adds `node` to the ring.
+    pub(crate) fn insert(&mut self, node: I) {
+        for index in 0..self.virtual_nodes {
+            let _ = self.points.entry(point(&node, index)).or_insert(node);
+        }
+    }
+
+    /// Removes `node` from the ring.
+    pub(crate) fn remove(&mut self, node: &I) {
+        for index in 0..self.virtual_nodes {
+            let point = point(node, index);
+            if self.points.get(&point) == Some(node) {
+                let _ = self.points.remove(&point);
+            }
+        }
+    }
+
+    /// Returns the node responsible for `key`, or `None` if the ring is empty.
+    pub(crate) fn responsible_node<K: AsRef<[u8]>>(&self, key: K) -> Option<I> {
+        let key_hash = hash::hash(key);
+        self.points
+            .range(key_hash..)
+            .next()
+            .or_else(|| self.points.iter().next())
+            .map(|(_, node)| *node)
+    }
+}
+
+/// Returns the hash of the `index`-th point of `node`.
+fn point<I: AsRef<[u8]>>(node: &I, index: u32) -> Digest {
+    let mut bytes = node.as_ref().to_vec();
+    bytes.extend_from_slice(&index.to_le_bytes());
+    hash::hash(bytes)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+
+    use rand::Rng;
+
+    use super::*;
+    use crate::{small_network::NodeId, testing::TestRng, types::DeployHash};
+
+    /// Returns the responsible node of each key.
+    fn owners(ring: &HashRing<NodeId>, keys: &[DeployHash]) -> HashMap<DeployHash, NodeId> {
+        keys.iter()
+            .map(|key| (*key, ring.responsible_node(key).expect("ring is not empty")))
+            .collect()
+    }
+
+    #[test]
+    fn should_only_move_keys_of_joining_or_leaving_node() {
+        let mut rng = TestRng::new();
+        let nodes: Vec<NodeId> = (0..10).map(|_| rng.gen()).collect();
+        let keys: Vec<DeployHash> = (0..1_000)
+            .map(|_| DeployHash::new(hash::hash(rng.gen::<[u8; 32]>())))
+            .collect();
+
+        let mut ring = HashRing::new(64);
+        assert_eq!(ring.responsible_node(&keys[0]), None);
+        for node in &nodes {
+            ring.insert(*node);
+        }
+        let before = owners(&ring, &keys);
+        // Every node is responsible for some keys.
+        for node in &nodes {
+            assert!(before.values().any(|owner| owner == node));
+        }
+
+        // A joining node only takes over keys, it doesn't shuffle them among the others.
+        let newcomer: NodeId = rng.gen();
+        ring.insert(newcomer);
+        let after_join = owners(&ring, &keys);
+        let mut moved = 0;
+        for key in &keys {
+            if after_join[key] != before[key] {
+                assert_eq!(after_join[key], newcomer);
+                moved += 1;
+            }
+        }
+        assert!(moved > 0);
+        assert!(moved < keys.len() / 4);
+
+        // A leaving node only hands over its own keys.
+        let leaving = nodes[3];
+        ring.remove(&leaving);
+        let after_leave = owners(&ring, &keys);
+        for key in &keys {
+            if after_join[key] != leaving {
+                assert_eq!(after_leave[key], after_join[key]);
+            } else {
+                assert_ne!(after_leave[key], leaving);
+            }
+        }
+
+        // Undoing the churn restores the original ownership.
+        ring.remove(&newcomer);
+        ring.insert(leaving);
+        assert_eq!(owners(&ring, &keys), before);
+    }
+}
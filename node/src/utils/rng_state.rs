@@ -0,0 +1,113 @@
+//! Persisted random number generator state.
+//!
+//! The state of the node's `ChaCha20Rng` can be snapshotted and saved to disk, so that on restart
+//! the node continues with exactly the same random sequence it would have produced had it kept
+//! running.  This makes long-running tests reproducible across restarts.
+//!
+//! A snapshot reveals every value the generator will produce, so it must never be used for a
+//! production node: The node only accepts the `--rng-state` flag when built for testing.
+
+use std::{
+    fs::{self, OpenOptions},
+    io::{self, Write},
+    os::unix::fs::OpenOptionsExt,
+    path::Path,
+};
+
+use rand::{RngCore, SeedableRng};
+use rand_chacha::ChaCha20Rng;
+use serde::{Deserialize, Serialize};
+
+/// A snapshot of a `ChaCha20Rng`, from which the generator can be restored.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct RngState {
+    seed: [u8; 32],
+}
+
+impl RngState {
+    /// Takes a snapshot of `rng`.
+    ///
+    /// The generator is reseeded from its own output, and the new seed is recorded: Every value
+    /// `rng` produces after the snapshot is produced identically by the restored generator.
+    pub fn snapshot(rng: &mut ChaCha20Rng) -> Self {
+        let mut seed = [0; 32];
+        rng.fill_bytes(&mut seed);
+        *rng = ChaCha20Rng::from_seed(seed);
+        RngState { seed }
+    }
+
+    /// Restores the generator from the snapshot.
+    pub fn restore(&self) -> ChaCha20Rng {
+        ChaCha20Rng::from_seed(self.seed)
+    }
+
+    /// Loads a snapshot from `path`.
+    ///
+    /// Returns `None` if the file does not exist.
+    pub fn load(path: &Path) -> io::Result<Option<Self>> {
+        match fs::read(path) {
+            Ok(bytes) => Ok(Some(serde_json::from_slice(&bytes)?)),
+            Err(error) if error.kind() == io::ErrorKind::NotFound => Ok(None),
+            Err(error) => Err(error),
+        }
+    }
+
+    /// Saves the snapshot to `path`, replacing any previous version.
+    ///
+    /// The file is only readable and writable by its owner, as the snapshot reveals all future
+    /// output of the generator.
+    pub fn save(&self, path: &Path) -> io::Result<()> {
+        // Write to a temporary file first, so that a crash doesn't leave a truncated snapshot.  A
+        // leftover temporary file is removed, so it is always created with restricted permissions.
+        let temp_path = path.with_extension("tmp");
+        match fs::remove_file(&temp_path) {
+            Err(error) if error.kind() != io::ErrorKind::NotFound => return Err(error),
+            _ => (),
+        }
+        let mut file = OpenOptions::new()
+            .write(true)
+            .create_new(true)
+            .mode(0o600)
+            .open(&temp_path)?;
+        file.write_all(&serde_json::to_vec(self)?)?;
+        file.sync_all()?;
+        fs::rename(temp_path, path)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::os::unix::fs::PermissionsExt;
+
+    use super::*;
+
+    #[test]
+    fn should_continue_identical_sequence_after_restore() {
+        let mut rng = ChaCha20Rng::seed_from_u64(42);
+        let _ = rng.next_u64();
+
+        let state = RngState::snapshot(&mut rng);
+        let expected: Vec<u64> = (0..100).map(|_| rng.next_u64()).collect();
+
+        let temp_dir = tempfile::tempdir().expect("should get tempdir");
+        let path = temp_dir.path().join("rng_state.json");
+        assert_eq!(
+            RngState::load(&path).expect("missing file should load"),
+            None
+        );
+        state.save(&path).expect("should save");
+        let loaded = RngState::load(&path)
+            .expect("should load")
+            .expect("should exist");
+        assert_eq!(loaded, state);
+        let mode = fs::metadata(&path)
+            .expect("should stat")
+            .permissions()
+            .mode();
+        assert_eq!(mode & 0o777, 0o600);
+
+        let mut restored = loaded.restore();
+        let actual: Vec<u64> = (0..100).map(|_| restored.next_u64()).collect();
+        assert_eq!(actual, expected);
+    }
+}
@@ -8,6 +8,7 @@ use std::{
     collections::{HashMap, VecDeque},
     hash::Hash,
     num::NonZeroUsize,
+    sync::atomic::{AtomicBool, Ordering},
 };
 
 use tokio::sync::{Mutex, Semaphore};
@@ -35,6 +36,11 @@ pub struct WeightedRoundRobin<I, K> {
 
     /// Number of items in all queues combined.
     total: Semaphore,
+
+    /// Whether the scheduler has been sealed, i.e. should no longer accept items from outside.
+    ///
+    /// Enforcing this is up to the users of the scheduler.
+    sealed: AtomicBool,
 }
 
 /// The inner state of the queue iteration.
@@ -94,6 +100,7 @@ where
             slots,
             queues,
             total: Semaphore::new(0),
+            sealed: AtomicBool::new(false),
         }
     }
 
@@ -152,10 +159,26 @@ where
         }
     }
 
+    /// Waits until at least one item is queued, without removing it.
+    pub(crate) async fn wait_for_item(&self) {
+        // Dropping the permit returns it, so the item remains available to `pop`.
+        let _permit = self.total.acquire().await;
+    }
+
     /// Returns the number of events currently in the queue.
     pub(crate) fn item_count(&self) -> usize {
         self.total.available_permits()
     }
+
+    /// Seals the scheduler.
+    pub(crate) fn seal(&self) {
+        self.sealed.store(true, Ordering::SeqCst);
+    }
+
+    /// Returns whether the scheduler has been sealed.
+    pub(crate) fn is_sealed(&self) -> bool {
+        self.sealed.load(Ordering::SeqCst)
+    }
 }
 
 #[cfg(test)]